@@ -0,0 +1,169 @@
+//! Standard suite of perf jobs, run in-binary against a malloc bdev and a
+//! nexus on top of it, emitting machine-readable results which can be
+//! compared with the results of a previous build.
+//!
+//! Environment variables:
+//! - `PERF_RUNTIME`: runtime of every job, in seconds (default 5).
+//! - `PERF_RESULTS`: where to write the results (default
+//!   `results/perf/<build>-<timestamp>.json`).
+//! - `PERF_BASELINE`: results to compare with; the run fails on regressions.
+//! - `PERF_COMPARE`: `<baseline>,<current>` results to compare without running
+//!   the suite.
+//! - `PERF_IOPS_THRESHOLD`, `PERF_LATENCY_THRESHOLD`: tolerated degradation of
+//!   the IOPS and p99 latency, in percent (default 10 and 20).
+
+use io_engine::{
+    bdev::nexus::nexus_create,
+    bdev_api::{bdev_create, bdev_destroy},
+    core::{
+        perf::{PerfJob, PerfPattern},
+        MayastorCliArgs,
+    },
+    grpc::v1::nexus::nexus_destroy,
+};
+use std::path::{Path, PathBuf};
+
+#[allow(unused)]
+mod common;
+mod regression;
+use common::{build_type, compose::MayastorTest};
+use regression::{compare, Environment, JobResult, SuiteResults, Thresholds};
+
+const MALLOC: &str = "malloc:///perf-malloc?size_mb=256";
+const NEXUS: &str = "perf-nexus";
+
+/// Jobs of the standard suite, run against every target.
+fn suite(runtime_secs: u64) -> Vec<(&'static str, PerfJob)> {
+    let job = |pattern, io_size, queue_depth| PerfJob {
+        pattern,
+        io_size,
+        queue_depth,
+        runtime_secs,
+        ..Default::default()
+    };
+    vec![
+        ("randread-4k-qd32", job(PerfPattern::RandRead, 4096, 32)),
+        ("randwrite-4k-qd32", job(PerfPattern::RandWrite, 4096, 32)),
+        (
+            "randrw70-4k-qd32",
+            PerfJob {
+                rw_mix_read: 70,
+                ..job(PerfPattern::RandRw, 4096, 32)
+            },
+        ),
+        ("read-128k-qd8", job(PerfPattern::Read, 128 * 1024, 8)),
+        ("write-128k-qd8", job(PerfPattern::Write, 128 * 1024, 8)),
+    ]
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Runs the suite against a malloc bdev and a nexus with a malloc child.
+async fn run_suite(ms: &MayastorTest<'_>, runtime_secs: u64) -> Vec<JobResult> {
+    ms.spawn(async {
+        bdev_create(MALLOC).await.unwrap();
+        nexus_create(
+            NEXUS,
+            128 * 1024 * 1024,
+            None,
+            &["malloc:///perf-child?size_mb=256".to_string()],
+        )
+        .await
+        .unwrap();
+    })
+    .await;
+
+    let mut results = vec![];
+    for target in ["perf-malloc", NEXUS] {
+        for (name, job) in suite(runtime_secs) {
+            let job = PerfJob {
+                bdev: target.to_string(),
+                ..job
+            };
+            let name = format!("{target}/{name}");
+            println!("Running {name}...");
+            let stats = ms
+                .spawn({
+                    let job = job.clone();
+                    async move { job.run().await }
+                })
+                .await
+                .unwrap_or_else(|e| panic!("Perf job {name} failed: {e}"));
+            results.push(JobResult {
+                name,
+                job,
+                stats,
+            });
+        }
+    }
+
+    ms.spawn(async {
+        nexus_destroy(NEXUS).await.unwrap();
+        bdev_destroy(MALLOC).await.unwrap();
+    })
+    .await;
+    results
+}
+
+/// Compares the results with the baseline, exiting on regressions.
+fn check(baseline: &Path, current: &SuiteResults) {
+    let baseline = SuiteResults::load(baseline).unwrap();
+    let thresholds = Thresholds {
+        iops_pct: env_or("PERF_IOPS_THRESHOLD", Thresholds::default().iops_pct),
+        latency_pct: env_or(
+            "PERF_LATENCY_THRESHOLD",
+            Thresholds::default().latency_pct,
+        ),
+    };
+    let regressions = compare(&baseline, current, thresholds);
+    if !regressions.is_empty() {
+        for regression in &regressions {
+            eprintln!("REGRESSION {regression}");
+        }
+        std::process::exit(1);
+    }
+    println!("No regressions beyond the thresholds {thresholds:?}");
+}
+
+fn main() {
+    if let Ok(files) = std::env::var("PERF_COMPARE") {
+        let (baseline, current) = files
+            .split_once(',')
+            .expect("PERF_COMPARE must be '<baseline>,<current>'");
+        let current = SuiteResults::load(Path::new(current)).unwrap();
+        check(Path::new(baseline), &current);
+        return;
+    }
+
+    let runtime_secs = env_or("PERF_RUNTIME", 5);
+    let environment = Environment::capture(build_type());
+    let output = std::env::var("PERF_RESULTS")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| {
+            PathBuf::from(format!(
+                "results/perf/{}-{}.json",
+                environment.build, environment.timestamp
+            ))
+        });
+
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    let results = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(run_suite(&ms, runtime_secs));
+
+    let results = SuiteResults {
+        environment,
+        results,
+    };
+    results.save(&output).unwrap();
+    println!("Results written to {}", output.display());
+
+    if let Ok(baseline) = std::env::var("PERF_BASELINE") {
+        check(Path::new(&baseline), &results);
+    }
+}
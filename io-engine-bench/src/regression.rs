@@ -0,0 +1,196 @@
+//! Machine-readable perf results and their comparison across runs.
+
+use io_engine::core::perf::{PerfJob, PerfJobStats};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Environment a perf suite ran in, recorded so that results of different
+/// machines or builds are not compared by mistake.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Environment {
+    pub timestamp: String,
+    pub build: String,
+    pub version: String,
+    pub commit: Option<String>,
+    pub hostname: String,
+    pub kernel: String,
+    pub cpu_model: String,
+    pub cpus: usize,
+}
+
+impl Environment {
+    /// Captures the environment of the current process.
+    pub fn capture(build: String) -> Self {
+        let read = |path: &str| {
+            std::fs::read_to_string(path)
+                .map(|s| s.trim().to_string())
+                .unwrap_or_default()
+        };
+        let commit = std::process::Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string());
+        let cpu_model = read("/proc/cpuinfo")
+            .lines()
+            .find(|l| l.starts_with("model name"))
+            .and_then(|l| l.split(':').nth(1))
+            .map(|m| m.trim().to_string())
+            .unwrap_or_default();
+
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            build,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            commit,
+            hostname: read("/proc/sys/kernel/hostname"),
+            kernel: read("/proc/sys/kernel/osrelease"),
+            cpu_model,
+            cpus: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Result of a single job of the suite.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobResult {
+    /// Name of the job, unique within the suite.
+    pub name: String,
+    pub job: PerfJob,
+    pub stats: PerfJobStats,
+}
+
+/// Results of a perf suite run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SuiteResults {
+    pub environment: Environment,
+    pub results: Vec<JobResult>,
+}
+
+impl SuiteResults {
+    /// Loads results from a JSON file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        serde_json::from_str(&data)
+            .map_err(|e| format!("Failed to parse {}: {e}", path.display()))
+    }
+
+    /// Saves the results as a JSON file, creating its parent directory.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                format!("Failed to create {}: {e}", parent.display())
+            })?;
+        }
+        let data = serde_json::to_string_pretty(self).unwrap();
+        std::fs::write(path, data)
+            .map_err(|e| format!("Failed to write {}: {e}", path.display()))
+    }
+}
+
+/// Maximum degradation, in percent, tolerated before flagging a regression.
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    /// Drop of IOPS.
+    pub iops_pct: f64,
+    /// Increase of the p99 latency.
+    pub latency_pct: f64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            iops_pct: 10.0,
+            latency_pct: 20.0,
+        }
+    }
+}
+
+/// A job whose performance degraded beyond the thresholds.
+#[derive(Debug, Clone)]
+pub struct Regression {
+    pub name: String,
+    pub metric: &'static str,
+    pub baseline: u64,
+    pub current: u64,
+    /// Degradation, in percent.
+    pub change_pct: f64,
+}
+
+impl std::fmt::Display for Regression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} degraded by {:.1}% ({} -> {})",
+            self.name,
+            self.metric,
+            self.change_pct,
+            self.baseline,
+            self.current
+        )
+    }
+}
+
+/// Relative change from `baseline` to `current`, in percent.
+fn change_pct(baseline: u64, current: u64) -> f64 {
+    if baseline == 0 {
+        return 0.0;
+    }
+    (current as f64 - baseline as f64) * 100.0 / baseline as f64
+}
+
+/// Compares the results of the jobs present in both runs and returns the
+/// regressions. Jobs missing from either run are skipped.
+pub fn compare(
+    baseline: &SuiteResults,
+    current: &SuiteResults,
+    thresholds: Thresholds,
+) -> Vec<Regression> {
+    let mut regressions = vec![];
+    for result in &current.results {
+        let Some(base) =
+            baseline.results.iter().find(|b| b.name == result.name)
+        else {
+            continue;
+        };
+
+        let iops = change_pct(base.stats.iops, result.stats.iops);
+        println!(
+            "{:<24} iops {:>10} -> {:>10} ({:+.1}%)",
+            result.name, base.stats.iops, result.stats.iops, iops
+        );
+        if -iops > thresholds.iops_pct {
+            regressions.push(Regression {
+                name: result.name.clone(),
+                metric: "iops",
+                baseline: base.stats.iops,
+                current: result.stats.iops,
+                change_pct: -iops,
+            });
+        }
+
+        let latency =
+            change_pct(base.stats.lat_p99_us, result.stats.lat_p99_us);
+        println!(
+            "{:<24} p99  {:>8}us -> {:>8}us ({:+.1}%)",
+            result.name,
+            base.stats.lat_p99_us,
+            result.stats.lat_p99_us,
+            latency
+        );
+        if latency > thresholds.latency_pct {
+            regressions.push(Regression {
+                name: result.name.clone(),
+                metric: "p99 latency",
+                baseline: base.stats.lat_p99_us,
+                current: result.stats.lat_p99_us,
+                change_pct: latency,
+            });
+        }
+    }
+    regressions
+}
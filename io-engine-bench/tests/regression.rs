@@ -0,0 +1,93 @@
+//! Tests of the comparison of perf results against a baseline.
+
+// only part of the module is exercised here
+#[allow(dead_code)]
+#[path = "../src/regression.rs"]
+mod regression;
+
+use io_engine::core::perf::{PerfJob, PerfJobStats};
+use regression::{compare, JobResult, SuiteResults, Thresholds};
+
+fn job(name: &str, iops: u64, lat_p99_us: u64) -> JobResult {
+    JobResult {
+        name: name.to_string(),
+        job: PerfJob::default(),
+        stats: PerfJobStats {
+            iops,
+            lat_p99_us,
+            ..Default::default()
+        },
+    }
+}
+
+fn suite(results: Vec<JobResult>) -> SuiteResults {
+    SuiteResults {
+        results,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn compare_within_thresholds() {
+    let baseline = suite(vec![job("randread", 1000, 100)]);
+    // a 10% drop of IOPS and a 20% increase of latency are tolerated
+    let current = suite(vec![job("randread", 900, 120)]);
+    assert!(compare(&baseline, &current, Thresholds::default()).is_empty());
+    // improvements are never regressions
+    let current = suite(vec![job("randread", 2000, 50)]);
+    assert!(compare(&baseline, &current, Thresholds::default()).is_empty());
+}
+
+#[test]
+fn compare_regressions() {
+    let baseline = suite(vec![
+        job("randread", 1000, 100),
+        job("randwrite", 1000, 100),
+    ]);
+    let current =
+        suite(vec![job("randread", 800, 100), job("randwrite", 1000, 150)]);
+
+    let regressions = compare(&baseline, &current, Thresholds::default());
+    assert_eq!(regressions.len(), 2);
+    assert_eq!(regressions[0].name, "randread");
+    assert_eq!(regressions[0].metric, "iops");
+    assert_eq!(regressions[0].baseline, 1000);
+    assert_eq!(regressions[0].current, 800);
+    assert_eq!(regressions[0].change_pct, 20.0);
+    assert_eq!(regressions[1].name, "randwrite");
+    assert_eq!(regressions[1].metric, "p99 latency");
+    assert_eq!(regressions[1].change_pct, 50.0);
+
+    // tighter thresholds flag smaller degradations
+    let current = suite(vec![job("randread", 950, 105)]);
+    let thresholds = Thresholds {
+        iops_pct: 1.0,
+        latency_pct: 1.0,
+    };
+    assert_eq!(compare(&baseline, &current, thresholds).len(), 2);
+}
+
+#[test]
+fn compare_skips_unmatched_jobs() {
+    // jobs missing from either run and an empty baseline are not compared
+    let baseline = suite(vec![job("randread", 1000, 100), job("seq", 0, 0)]);
+    let current = suite(vec![job("randwrite", 1, 1000), job("seq", 10, 10)]);
+    assert!(compare(&baseline, &current, Thresholds::default()).is_empty());
+}
+
+#[test]
+fn results_round_trip() {
+    let path = std::env::temp_dir()
+        .join(format!("perf-regression-{}", std::process::id()))
+        .join("results.json");
+    let results = suite(vec![job("randread", 1000, 100)]);
+    results.save(&path).unwrap();
+
+    let loaded = SuiteResults::load(&path).unwrap();
+    assert_eq!(loaded.results.len(), 1);
+    assert_eq!(loaded.results[0].name, "randread");
+    assert_eq!(loaded.results[0].stats.iops, 1000);
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+    assert!(SuiteResults::load(&path).is_err());
+}
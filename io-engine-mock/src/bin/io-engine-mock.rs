@@ -0,0 +1,82 @@
+use clap::Parser;
+use io_engine_mock::{
+    registration::HB_INTERVAL,
+    FaultConfig,
+    MockIoEngine,
+    MockRegistration,
+    DISK_CAPACITY,
+};
+use std::{net::SocketAddr, time::Duration};
+use tonic::transport::Uri;
+
+#[derive(Debug, Parser)]
+#[clap(
+    name = "io-engine-mock",
+    about = "In-memory io-engine for control-plane testing"
+)]
+struct CliArgs {
+    /// Name of the node the mock pretends to be.
+    #[clap(short = 'N', long, default_value = "mock-node")]
+    node_name: String,
+    /// gRPC endpoint to listen on.
+    #[clap(short = 'g', long, default_value = "0.0.0.0:10124")]
+    grpc_endpoint: SocketAddr,
+    /// Registration gRPC endpoint of the control plane, the mock does not
+    /// register itself when not set.
+    #[clap(short = 'R', long)]
+    registration_endpoint: Option<Uri>,
+    /// NVMe host NQN reported to the control plane.
+    #[clap(long)]
+    node_nqn: Option<String>,
+    /// Capacity in bytes of every disk backing a pool.
+    #[clap(long, default_value_t = DISK_CAPACITY)]
+    disk_capacity: u64,
+    /// Latency in milliseconds added to every request.
+    #[clap(long, env = "MOCK_LATENCY_MS", default_value_t = 0)]
+    latency_ms: u64,
+    /// Probability, between 0 and 1, of a request failing.
+    #[clap(long, env = "MOCK_FAILURE_RATE", default_value_t = 0.0)]
+    failure_rate: f64,
+    /// Seed used to decide which requests fail.
+    #[clap(long, env = "MOCK_SEED", default_value_t = 0)]
+    seed: u64,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tracing_subscriber::fmt::init();
+    let args = CliArgs::parse();
+
+    let faults = FaultConfig {
+        latency: Duration::from_millis(args.latency_ms),
+        failure_rate: args.failure_rate,
+        seed: args.seed,
+    };
+    let (stop, mut stopped) = tokio::sync::watch::channel(());
+    let registration = args.registration_endpoint.map(|endpoint| {
+        let registration = MockRegistration::new(
+            &args.node_name,
+            &args.grpc_endpoint.to_string(),
+            args.node_nqn,
+            endpoint,
+            HB_INTERVAL,
+        );
+        tokio::spawn(registration.run(async move {
+            stopped.changed().await.ok();
+        }))
+    });
+
+    let result = MockIoEngine::new(&args.node_name, args.disk_capacity, faults)
+        .serve_with_shutdown(args.grpc_endpoint, async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+        .await;
+
+    // deregister on the way out, whether the server stopped or failed
+    stop.send(()).ok();
+    if let Some(registration) = registration {
+        registration.await?;
+    }
+    result?;
+    Ok(())
+}
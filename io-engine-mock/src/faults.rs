@@ -0,0 +1,53 @@
+//! Deterministic latency and failure injection for the mock services.
+
+use parking_lot::Mutex;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::time::Duration;
+use tonic::Status;
+
+/// Faults to inject into every request served by the mock.
+#[derive(Debug, Clone, Default)]
+pub struct FaultConfig {
+    /// Delay applied to every request.
+    pub latency: Duration,
+    /// Probability, between 0 and 1, of a request failing.
+    pub failure_rate: f64,
+    /// Seed of the generator deciding which requests fail.
+    pub seed: u64,
+}
+
+/// Injects the configured faults, drawing failures from a seeded generator
+/// so that the same sequence of requests always yields the same outcome.
+#[derive(Debug)]
+pub struct FaultInjector {
+    config: FaultConfig,
+    rng: Mutex<ChaCha8Rng>,
+}
+
+impl FaultInjector {
+    /// Create a new injector from the given configuration.
+    pub fn new(config: FaultConfig) -> Self {
+        let rng = Mutex::new(ChaCha8Rng::seed_from_u64(config.seed));
+        Self {
+            config,
+            rng,
+        }
+    }
+
+    /// Delays the request and decides whether it fails.
+    pub async fn inject(&self, method: &str) -> Result<(), Status> {
+        if !self.config.latency.is_zero() {
+            tokio::time::sleep(self.config.latency).await;
+        }
+        if self.config.failure_rate > 0.0
+            && self.rng.lock().gen_bool(self.config.failure_rate.min(1.0))
+        {
+            tracing::debug!("Injecting failure into {method}");
+            return Err(Status::unavailable(format!(
+                "injected failure for {method}"
+            )));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+//! In-memory io-engine for control-plane testing.
+//!
+//! Serves the v1 pool and replica gRPC services from an in-memory state, with
+//! no SPDK underneath, so that control-plane logic can be exercised at scale
+//! without hugepages or privileged containers. Latency and failures can be
+//! injected deterministically through a [`FaultConfig`].
+
+use io_engine_api::v1::{pool::PoolRpcServer, replica::ReplicaRpcServer};
+use parking_lot::Mutex;
+use std::{future::Future, net::SocketAddr, sync::Arc};
+
+pub use faults::{FaultConfig, FaultInjector};
+pub use registration::MockRegistration;
+pub use state::{MockPool, MockReplica, MockState};
+
+pub mod faults;
+mod pool;
+pub mod registration;
+mod replica;
+pub mod state;
+#[cfg(test)]
+mod test;
+
+/// Default capacity of a disk backing a mock pool.
+pub const DISK_CAPACITY: u64 = 10 * 1024 * 1024 * 1024;
+
+/// A mock io-engine, implementing the gRPC services on top of `MockState`.
+#[derive(Debug, Clone)]
+pub struct MockIoEngine {
+    state: Arc<Mutex<MockState>>,
+    faults: Arc<FaultInjector>,
+}
+
+impl MockIoEngine {
+    /// Create a mock io-engine for the given node.
+    pub fn new(node: &str, disk_capacity: u64, faults: FaultConfig) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(MockState::new(node, disk_capacity))),
+            faults: Arc::new(FaultInjector::new(faults)),
+        }
+    }
+
+    /// Shared state of the mock, for assertions in tests.
+    pub fn state(&self) -> Arc<Mutex<MockState>> {
+        self.state.clone()
+    }
+
+    /// Serve the gRPC services on the given endpoint until the server fails.
+    pub async fn serve(
+        self,
+        endpoint: SocketAddr,
+    ) -> Result<(), tonic::transport::Error> {
+        self.serve_with_shutdown(endpoint, std::future::pending())
+            .await
+    }
+
+    /// Serve the gRPC services on the given endpoint until `shutdown`
+    /// completes or the server fails.
+    pub async fn serve_with_shutdown(
+        self,
+        endpoint: SocketAddr,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<(), tonic::transport::Error> {
+        tracing::info!("Mock io-engine gRPC server listening on {endpoint}");
+        tonic::transport::Server::builder()
+            .add_service(PoolRpcServer::new(self.clone()))
+            .add_service(ReplicaRpcServer::new(self))
+            .serve_with_shutdown(endpoint, shutdown)
+            .await
+    }
+}
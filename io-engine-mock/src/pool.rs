@@ -0,0 +1,75 @@
+//! Mock of the v1 pool gRPC service.
+
+use crate::MockIoEngine;
+use io_engine_api::v1::pool::*;
+use tonic::{Request, Response, Status};
+
+#[tonic::async_trait]
+impl PoolRpc for MockIoEngine {
+    async fn create_pool(
+        &self,
+        request: Request<CreatePoolRequest>,
+    ) -> Result<Response<Pool>, Status> {
+        self.faults.inject("create_pool").await?;
+        let args = request.into_inner();
+        let pool = self.state.lock().create_pool(
+            args.name,
+            args.uuid,
+            args.disks,
+            args.cluster_size,
+        )?;
+        Ok(Response::new(pool))
+    }
+
+    async fn destroy_pool(
+        &self,
+        request: Request<DestroyPoolRequest>,
+    ) -> Result<Response<()>, Status> {
+        self.faults.inject("destroy_pool").await?;
+        let args = request.into_inner();
+        self.state.lock().destroy_pool(&args.name, &args.uuid)?;
+        Ok(Response::new(()))
+    }
+
+    async fn export_pool(
+        &self,
+        request: Request<ExportPoolRequest>,
+    ) -> Result<Response<()>, Status> {
+        self.faults.inject("export_pool").await?;
+        let args = request.into_inner();
+        self.state.lock().export_pool(&args.name, &args.uuid)?;
+        Ok(Response::new(()))
+    }
+
+    async fn import_pool(
+        &self,
+        request: Request<ImportPoolRequest>,
+    ) -> Result<Response<Pool>, Status> {
+        self.faults.inject("import_pool").await?;
+        let args = request.into_inner();
+        let pool = self
+            .state
+            .lock()
+            .import_pool(args.name, args.uuid, args.disks)?;
+        Ok(Response::new(pool))
+    }
+
+    async fn list_pools(
+        &self,
+        request: Request<ListPoolOptions>,
+    ) -> Result<Response<ListPoolsResponse>, Status> {
+        self.faults.inject("list_pools").await?;
+        let args = request.into_inner();
+        if let Some(pooltype) = args.pooltype {
+            if pooltype.value != PoolType::Lvs as i32 {
+                return Ok(Response::new(ListPoolsResponse {
+                    pools: vec![],
+                }));
+            }
+        }
+        let pools = self.state.lock().list_pools(args.name, args.uuid);
+        Ok(Response::new(ListPoolsResponse {
+            pools,
+        }))
+    }
+}
@@ -0,0 +1,105 @@
+//! Registration of the mock with the control plane.
+//!
+//! Like the real io-engine, the mock periodically sends a register request to
+//! the control plane so that it is discovered as a node, and deregisters when
+//! it shuts down.
+
+use io_engine_api::v1::registration::{
+    registration_client::RegistrationClient,
+    ApiVersion,
+    DeregisterRequest,
+    RegisterRequest,
+};
+use std::{future::Future, time::Duration};
+use tonic::transport::{Channel, Endpoint, Uri};
+
+/// Interval between two register requests.
+pub const HB_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Periodic registration of a mock node with the control plane.
+#[derive(Debug, Clone)]
+pub struct MockRegistration {
+    node: String,
+    grpc_endpoint: String,
+    hostnqn: Option<String>,
+    instance_uuid: uuid::Uuid,
+    interval: Duration,
+    client: RegistrationClient<Channel>,
+}
+
+impl MockRegistration {
+    /// Create a registration of the given node, advertising its gRPC
+    /// endpoint to the control plane listening on `registration_addr`.
+    pub fn new(
+        node: &str,
+        grpc_endpoint: &str,
+        hostnqn: Option<String>,
+        registration_addr: Uri,
+        interval: Duration,
+    ) -> Self {
+        let channel = Endpoint::from(registration_addr)
+            .connect_timeout(interval)
+            .timeout(interval)
+            .connect_lazy();
+        Self {
+            node: node.to_string(),
+            grpc_endpoint: grpc_endpoint.to_string(),
+            hostnqn,
+            instance_uuid: uuid::Uuid::new_v4(),
+            interval,
+            client: RegistrationClient::new(channel),
+        }
+    }
+
+    /// Uuid identifying this run of the mock.
+    pub fn instance_uuid(&self) -> &uuid::Uuid {
+        &self.instance_uuid
+    }
+
+    /// Send a single register request.
+    pub async fn register(&mut self) -> Result<(), tonic::Status> {
+        let request = RegisterRequest {
+            id: self.node.clone(),
+            grpc_endpoint: self.grpc_endpoint.clone(),
+            instance_uuid: Some(self.instance_uuid.to_string()),
+            api_version: vec![ApiVersion::V1 as i32],
+            hostnqn: self.hostnqn.clone(),
+            features: None,
+            bugfixes: None,
+            version: Some(env!("CARGO_PKG_VERSION").to_string()),
+        };
+        self.client.register(request).await.map(|_| ())
+    }
+
+    /// Deregister the node.
+    pub async fn deregister(&mut self) -> Result<(), tonic::Status> {
+        let request = DeregisterRequest {
+            id: self.node.clone(),
+        };
+        self.client.deregister(request).await.map(|_| ())
+    }
+
+    /// Register the node every interval until `shutdown` completes, then
+    /// deregister it.
+    pub async fn run(mut self, shutdown: impl Future<Output = ()>) {
+        tokio::pin!(shutdown);
+        let mut show_error = true;
+        loop {
+            match self.register().await {
+                Ok(()) => show_error = true,
+                Err(error) if show_error => {
+                    tracing::error!("Registration failed: {error}");
+                    show_error = false;
+                }
+                Err(_) => {}
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(self.interval) => {}
+                _ = &mut shutdown => break,
+            }
+        }
+        if let Err(error) = self.deregister().await {
+            tracing::error!("Deregistration failed: {error}");
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! Mock of the v1 replica gRPC service.
+
+use crate::{
+    state::{MockReplica, SHARE_NONE, SHARE_NVMF},
+    MockIoEngine,
+};
+use io_engine_api::v1::replica::*;
+use tonic::{Request, Response, Status};
+
+#[tonic::async_trait]
+impl ReplicaRpc for MockIoEngine {
+    async fn create_replica(
+        &self,
+        request: Request<CreateReplicaRequest>,
+    ) -> Result<Response<Replica>, Status> {
+        self.faults.inject("create_replica").await?;
+        let args = request.into_inner();
+        if !matches!(args.share, SHARE_NONE | SHARE_NVMF) {
+            return Err(Status::invalid_argument(format!(
+                "invalid replica share protocol value: {}",
+                args.share
+            )));
+        }
+        let replica = MockReplica {
+            name: args.name,
+            uuid: args.uuid,
+            pool_uuid: String::new(),
+            size: args.size,
+            thin: args.thin,
+            share: args.share,
+            allowed_hosts: args.allowed_hosts,
+            entity_id: args.entity_id,
+        };
+        let replica =
+            self.state.lock().create_replica(&args.pooluuid, replica)?;
+        Ok(Response::new(replica))
+    }
+
+    async fn destroy_replica(
+        &self,
+        request: Request<DestroyReplicaRequest>,
+    ) -> Result<Response<()>, Status> {
+        self.faults.inject("destroy_replica").await?;
+        let args = request.into_inner();
+        let mut state = self.state.lock();
+        let pool = match args.pool {
+            Some(destroy_replica_request::Pool::PoolUuid(uuid)) => Some(uuid),
+            Some(destroy_replica_request::Pool::PoolName(name)) => state
+                .list_pools(Some(name.clone()), None)
+                .first()
+                .map(|p| p.uuid.clone())
+                .or(Some(name)),
+            None => None,
+        };
+        state.destroy_replica(&args.uuid, pool.as_deref())?;
+        Ok(Response::new(()))
+    }
+
+    async fn list_replicas(
+        &self,
+        request: Request<ListReplicaOptions>,
+    ) -> Result<Response<ListReplicasResponse>, Status> {
+        self.faults.inject("list_replicas").await?;
+        let args = request.into_inner();
+        let pool = args.pooluuid.or(args.poolname);
+        let replicas = self
+            .state
+            .lock()
+            .list_replicas(args.name, args.uuid, pool)
+            .into_iter()
+            .filter(|_| args.query.as_ref().map_or(true, |q| q.replica))
+            .collect();
+        Ok(Response::new(ListReplicasResponse {
+            replicas,
+        }))
+    }
+
+    async fn share_replica(
+        &self,
+        request: Request<ShareReplicaRequest>,
+    ) -> Result<Response<Replica>, Status> {
+        self.faults.inject("share_replica").await?;
+        let args = request.into_inner();
+        if args.share != SHARE_NVMF {
+            return Err(Status::invalid_argument(
+                "Invalid share protocol NONE",
+            ));
+        }
+        let replica = self.state.lock().update_replica(&args.uuid, |r| {
+            r.share = SHARE_NVMF;
+            r.allowed_hosts = args.allowed_hosts;
+            Ok(())
+        })?;
+        Ok(Response::new(replica))
+    }
+
+    async fn unshare_replica(
+        &self,
+        request: Request<UnshareReplicaRequest>,
+    ) -> Result<Response<Replica>, Status> {
+        self.faults.inject("unshare_replica").await?;
+        let args = request.into_inner();
+        let replica = self.state.lock().update_replica(&args.uuid, |r| {
+            r.share = SHARE_NONE;
+            r.allowed_hosts.clear();
+            Ok(())
+        })?;
+        Ok(Response::new(replica))
+    }
+
+    async fn resize_replica(
+        &self,
+        request: Request<ResizeReplicaRequest>,
+    ) -> Result<Response<Replica>, Status> {
+        self.faults.inject("resize_replica").await?;
+        let args = request.into_inner();
+        let replica = self.state.lock().update_replica(&args.uuid, |r| {
+            if args.requested_size < r.size {
+                return Err(Status::invalid_argument(
+                    "replica cannot be shrunk",
+                ));
+            }
+            r.size = args.requested_size;
+            Ok(())
+        })?;
+        Ok(Response::new(replica))
+    }
+
+    async fn set_replica_entity_id(
+        &self,
+        request: Request<SetReplicaEntityIdRequest>,
+    ) -> Result<Response<Replica>, Status> {
+        self.faults.inject("set_replica_entity_id").await?;
+        let args = request.into_inner();
+        let replica = self.state.lock().update_replica(&args.uuid, |r| {
+            r.entity_id = Some(args.entity_id);
+            Ok(())
+        })?;
+        Ok(Response::new(replica))
+    }
+}
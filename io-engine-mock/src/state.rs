@@ -0,0 +1,382 @@
+//! In-memory state shared by the mock services.
+
+use io_engine_api::v1::{
+    pool::{Pool, PoolState, PoolType},
+    replica::{Replica, ReplicaSpaceUsage},
+};
+use std::collections::BTreeMap;
+use tonic::Status;
+
+/// Default cluster size of the mock pools.
+pub const CLUSTER_SIZE: u32 = 4 * 1024 * 1024;
+
+/// Share protocol values, as encoded on the wire.
+pub const SHARE_NONE: i32 = 0;
+pub const SHARE_NVMF: i32 = 1;
+
+/// A pool held in memory.
+#[derive(Debug, Clone)]
+pub struct MockPool {
+    pub name: String,
+    pub uuid: String,
+    pub disks: Vec<String>,
+    pub capacity: u64,
+    pub cluster_size: u32,
+    /// Whether the pool is imported, exported pools keep their replicas.
+    pub imported: bool,
+}
+
+/// A replica held in memory.
+#[derive(Debug, Clone)]
+pub struct MockReplica {
+    pub name: String,
+    pub uuid: String,
+    pub pool_uuid: String,
+    pub size: u64,
+    pub thin: bool,
+    pub share: i32,
+    pub allowed_hosts: Vec<String>,
+    pub entity_id: Option<String>,
+}
+
+/// State of the mock io-engine.
+#[derive(Debug)]
+pub struct MockState {
+    node: String,
+    disk_capacity: u64,
+    pools: BTreeMap<String, MockPool>,
+    replicas: BTreeMap<String, MockReplica>,
+}
+
+impl MockState {
+    /// Create an empty state for the given node, every disk having the
+    /// given capacity.
+    pub fn new(node: &str, disk_capacity: u64) -> Self {
+        Self {
+            node: node.to_string(),
+            disk_capacity,
+            pools: BTreeMap::new(),
+            replicas: BTreeMap::new(),
+        }
+    }
+
+    /// Create a pool, or import it when it has been exported.
+    pub fn create_pool(
+        &mut self,
+        name: String,
+        uuid: Option<String>,
+        disks: Vec<String>,
+        cluster_size: Option<u32>,
+    ) -> Result<Pool, Status> {
+        if disks.is_empty() {
+            return Err(Status::invalid_argument(
+                "invalid argument, missing devices",
+            ));
+        }
+        if let Some(pool) = self.pools.values_mut().find(|p| p.name == name) {
+            if pool.disks != disks {
+                return Err(Status::already_exists(format!(
+                    "pool {name} already exists on different disks"
+                )));
+            }
+            if pool.imported {
+                return Err(Status::already_exists(format!(
+                    "pool {name} already exists"
+                )));
+            }
+            pool.imported = true;
+            let pool = pool.clone();
+            return Ok(self.to_pool(&pool));
+        }
+        if self
+            .pools
+            .values()
+            .any(|p| p.disks.iter().any(|d| disks.contains(d)))
+        {
+            return Err(Status::already_exists(
+                "disk already used by another pool",
+            ));
+        }
+
+        let pool = MockPool {
+            name,
+            uuid: uuid.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            disks: disks.clone(),
+            capacity: self.disk_capacity * disks.len() as u64,
+            cluster_size: cluster_size.unwrap_or(CLUSTER_SIZE),
+            imported: true,
+        };
+        let result = self.to_pool(&pool);
+        self.pools.insert(pool.uuid.clone(), pool);
+        Ok(result)
+    }
+
+    /// Import a previously exported pool.
+    pub fn import_pool(
+        &mut self,
+        name: String,
+        uuid: Option<String>,
+        disks: Vec<String>,
+    ) -> Result<Pool, Status> {
+        let Some(pool) = self.pools.values_mut().find(|p| {
+            p.name == name && uuid.as_ref().map_or(true, |u| &p.uuid == u)
+        }) else {
+            return Err(Status::not_found(format!(
+                "no pool {name} found on disks {disks:?}"
+            )));
+        };
+        pool.imported = true;
+        let pool = pool.clone();
+        Ok(self.to_pool(&pool))
+    }
+
+    /// Destroy a pool along with its replicas.
+    pub fn destroy_pool(
+        &mut self,
+        name: &str,
+        uuid: &Option<String>,
+    ) -> Result<(), Status> {
+        let pool = self.find_pool(name, uuid)?.uuid.clone();
+        self.pools.remove(&pool);
+        self.replicas.retain(|_, r| r.pool_uuid != pool);
+        Ok(())
+    }
+
+    /// Export a pool, hiding it and its replicas until it is imported.
+    pub fn export_pool(
+        &mut self,
+        name: &str,
+        uuid: &Option<String>,
+    ) -> Result<(), Status> {
+        let pool = self.find_pool(name, uuid)?.uuid.clone();
+        if let Some(pool) = self.pools.get_mut(&pool) {
+            pool.imported = false;
+        }
+        Ok(())
+    }
+
+    /// List the imported pools matching the optional filters.
+    pub fn list_pools(
+        &self,
+        name: Option<String>,
+        uuid: Option<String>,
+    ) -> Vec<Pool> {
+        self.pools
+            .values()
+            .filter(|p| p.imported)
+            .filter(|p| name.as_ref().map_or(true, |n| &p.name == n))
+            .filter(|p| uuid.as_ref().map_or(true, |u| &p.uuid == u))
+            .map(|p| self.to_pool(p))
+            .collect()
+    }
+
+    /// Create a replica on the pool identified by its uuid or name.
+    pub fn create_replica(
+        &mut self,
+        pool: &str,
+        replica: MockReplica,
+    ) -> Result<Replica, Status> {
+        let pool = self
+            .pools
+            .values()
+            .find(|p| p.imported && (p.uuid == pool || p.name == pool))
+            .cloned()
+            .ok_or_else(|| {
+                Status::not_found(format!("pool {pool} not found"))
+            })?;
+
+        if let Some(existing) = self.replicas.get(&replica.uuid) {
+            if existing.name == replica.name
+                && existing.pool_uuid == pool.uuid
+                && existing.size >= replica.size
+            {
+                return Ok(self.to_replica(existing));
+            }
+            return Err(Status::already_exists(format!(
+                "replica {} already exists",
+                replica.uuid
+            )));
+        }
+
+        let size = Self::round_up(replica.size, pool.cluster_size);
+        if !replica.thin && self.committed(&pool.uuid) + size > pool.capacity {
+            return Err(Status::resource_exhausted(format!(
+                "not enough space on pool {} for replica {}",
+                pool.name, replica.name
+            )));
+        }
+
+        let replica = MockReplica {
+            pool_uuid: pool.uuid,
+            size,
+            ..replica
+        };
+        let result = self.to_replica(&replica);
+        self.replicas.insert(replica.uuid.clone(), replica);
+        Ok(result)
+    }
+
+    /// Destroy a replica, optionally checking the pool it lives on.
+    pub fn destroy_replica(
+        &mut self,
+        uuid: &str,
+        pool: Option<&str>,
+    ) -> Result<(), Status> {
+        let replica = self.replica_mut(uuid)?;
+        if let Some(pool) = pool {
+            if replica.pool_uuid != pool {
+                return Err(Status::aborted(format!(
+                    "replica {uuid} does not belong to pool {pool}"
+                )));
+            }
+        }
+        self.replicas.remove(uuid);
+        Ok(())
+    }
+
+    /// Update a replica through the given function.
+    pub fn update_replica<F>(
+        &mut self,
+        uuid: &str,
+        update: F,
+    ) -> Result<Replica, Status>
+    where
+        F: FnOnce(&mut MockReplica) -> Result<(), Status>,
+    {
+        update(self.replica_mut(uuid)?)?;
+        Ok(self.to_replica(&self.replicas[uuid]))
+    }
+
+    /// List the replicas of the imported pools matching the optional filters.
+    pub fn list_replicas(
+        &self,
+        name: Option<String>,
+        uuid: Option<String>,
+        pool: Option<String>,
+    ) -> Vec<Replica> {
+        self.replicas
+            .values()
+            .filter(|r| {
+                self.pools.get(&r.pool_uuid).map_or(false, |p| p.imported)
+            })
+            .filter(|r| name.as_ref().map_or(true, |n| &r.name == n))
+            .filter(|r| uuid.as_ref().map_or(true, |u| &r.uuid == u))
+            .filter(|r| {
+                pool.as_ref().map_or(true, |p| {
+                    &r.pool_uuid == p || &self.pools[&r.pool_uuid].name == p
+                })
+            })
+            .map(|r| self.to_replica(r))
+            .collect()
+    }
+
+    fn find_pool(
+        &self,
+        name: &str,
+        uuid: &Option<String>,
+    ) -> Result<&MockPool, Status> {
+        self.pools
+            .values()
+            .find(|p| {
+                p.imported
+                    && p.name == name
+                    && uuid.as_ref().map_or(true, |u| &p.uuid == u)
+            })
+            .ok_or_else(|| Status::not_found(format!("pool {name} not found")))
+    }
+
+    fn replica_mut(&mut self, uuid: &str) -> Result<&mut MockReplica, Status> {
+        let pools = &self.pools;
+        self.replicas
+            .get_mut(uuid)
+            .filter(|r| pools.get(&r.pool_uuid).map_or(false, |p| p.imported))
+            .ok_or_else(|| {
+                Status::not_found(format!("replica {uuid} not found"))
+            })
+    }
+
+    /// Space committed on the pool, thin replicas only counting for the
+    /// clusters they have allocated.
+    fn committed(&self, pool: &str) -> u64 {
+        self.replicas
+            .values()
+            .filter(|r| r.pool_uuid == pool)
+            .map(Self::allocated)
+            .sum()
+    }
+
+    fn used(&self, pool: &str) -> u64 {
+        self.replicas
+            .values()
+            .filter(|r| r.pool_uuid == pool && !r.thin)
+            .map(|r| r.size)
+            .sum()
+    }
+
+    /// Allocated size of a replica, thin replicas never being written to.
+    fn allocated(replica: &MockReplica) -> u64 {
+        if replica.thin {
+            0
+        } else {
+            replica.size
+        }
+    }
+
+    /// Rounds the size up to a multiple of the cluster size.
+    pub fn round_up(size: u64, cluster_size: u32) -> u64 {
+        let cluster_size = cluster_size as u64;
+        size.div_ceil(cluster_size) * cluster_size
+    }
+
+    fn to_pool(&self, pool: &MockPool) -> Pool {
+        Pool {
+            uuid: pool.uuid.clone(),
+            name: pool.name.clone(),
+            disks: pool.disks.clone(),
+            state: PoolState::PoolOnline.into(),
+            capacity: pool.capacity,
+            used: self.used(&pool.uuid),
+            committed: self.committed(&pool.uuid),
+            pooltype: PoolType::Lvs as i32,
+            cluster_size: pool.cluster_size,
+        }
+    }
+
+    fn to_replica(&self, replica: &MockReplica) -> Replica {
+        let pool = &self.pools[&replica.pool_uuid];
+        let allocated = Self::allocated(replica);
+        let uri = match replica.share {
+            SHARE_NVMF => format!(
+                "nvmf://{}:8420/nqn.2019-05.io.openebs:{}",
+                self.node, replica.uuid
+            ),
+            _ => format!("bdev:///{}", replica.name),
+        };
+        Replica {
+            name: replica.name.clone(),
+            uuid: replica.uuid.clone(),
+            size: replica.size,
+            thin: replica.thin,
+            share: replica.share,
+            uri,
+            poolname: pool.name.clone(),
+            usage: Some(ReplicaSpaceUsage {
+                capacity_bytes: replica.size,
+                allocated_bytes: allocated,
+                cluster_size: pool.cluster_size as u64,
+                num_clusters: replica.size / pool.cluster_size as u64,
+                num_allocated_clusters: allocated / pool.cluster_size as u64,
+                allocated_bytes_snapshots: 0,
+                num_allocated_clusters_snapshots: 0,
+                allocated_bytes_snapshot_from_clone: None,
+            }),
+            allowed_hosts: replica.allowed_hosts.clone(),
+            is_snapshot: false,
+            is_clone: false,
+            pooltype: PoolType::Lvs as i32,
+            pooluuid: pool.uuid.clone(),
+            snapshot_uuid: None,
+            entity_id: replica.entity_id.clone(),
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! Unit tests for the mock io-engine.
+
+use super::*;
+use io_engine_api::v1::{
+    pool::{pool_rpc_client::PoolRpcClient, CreatePoolRequest},
+    registration::{
+        registration_server::{Registration, RegistrationServer},
+        DeregisterRequest,
+        RegisterRequest,
+    },
+    replica::{replica_rpc_client::ReplicaRpcClient, CreateReplicaRequest},
+};
+use std::{net::TcpListener, time::Duration};
+use tokio::sync::mpsc;
+use tonic::{Code, Request, Response, Status};
+
+const GIB: u64 = 1024 * 1024 * 1024;
+
+fn replica(name: &str, size: u64, thin: bool) -> MockReplica {
+    MockReplica {
+        name: name.to_string(),
+        uuid: uuid::Uuid::new_v4().to_string(),
+        pool_uuid: String::new(),
+        size,
+        thin,
+        share: state::SHARE_NONE,
+        allowed_hosts: vec![],
+        entity_id: None,
+    }
+}
+
+/// Returns a local endpoint nothing listens on.
+fn free_endpoint() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+#[test]
+fn pool_create_import() {
+    let mut state = MockState::new("node", GIB);
+    let pool = state
+        .create_pool("p0".into(), None, vec!["/dev/a".into()], None)
+        .unwrap();
+    assert_eq!(pool.capacity, GIB);
+    assert_eq!(pool.cluster_size, state::CLUSTER_SIZE);
+
+    // the disks of a pool cannot be reused
+    let err = state
+        .create_pool("p1".into(), None, vec!["/dev/a".into()], None)
+        .unwrap_err();
+    assert_eq!(err.code(), Code::AlreadyExists);
+
+    // an exported pool is hidden until imported, along with its replicas
+    state
+        .create_replica("p0", replica("r0", 4 * 1024 * 1024, false))
+        .unwrap();
+    state.export_pool("p0", &None).unwrap();
+    assert!(state.list_pools(None, None).is_empty());
+    assert!(state.list_replicas(None, None, None).is_empty());
+    state
+        .import_pool("p0".into(), None, vec!["/dev/a".into()])
+        .unwrap();
+    assert_eq!(state.list_replicas(None, None, None).len(), 1);
+
+    state.destroy_pool("p0", &None).unwrap();
+    assert!(state.list_replicas(None, None, None).is_empty());
+}
+
+#[test]
+fn replica_committed() {
+    let mut state = MockState::new("node", GIB);
+    state
+        .create_pool("p0".into(), None, vec!["/dev/a".into()], None)
+        .unwrap();
+
+    // replica sizes are rounded up to the cluster size
+    let r = state
+        .create_replica("p0", replica("thick", GIB / 2 + 1, false))
+        .unwrap();
+    assert_eq!(r.size, GIB / 2 + state::CLUSTER_SIZE as u64);
+
+    // thin replicas only commit the space they have allocated
+    let r = state
+        .create_replica("p0", replica("thin", GIB, true))
+        .unwrap();
+    assert_eq!(r.usage.unwrap().allocated_bytes, 0);
+    let pool = &state.list_pools(None, None)[0];
+    assert_eq!(pool.committed, GIB / 2 + state::CLUSTER_SIZE as u64);
+    assert_eq!(pool.used, pool.committed);
+
+    // so they do not prevent thick replicas from using the remaining space
+    state
+        .create_replica("p0", replica("thick2", GIB / 4, false))
+        .unwrap();
+    let err = state
+        .create_replica("p0", replica("thick3", GIB / 2, false))
+        .unwrap_err();
+    assert_eq!(err.code(), Code::ResourceExhausted);
+}
+
+#[tokio::test]
+async fn faults_are_deterministic() {
+    let config = FaultConfig {
+        latency: Duration::ZERO,
+        failure_rate: 0.5,
+        seed: 42,
+    };
+    let outcomes = |injector: FaultInjector| async move {
+        let mut outcomes = vec![];
+        for _ in 0 .. 32 {
+            outcomes.push(injector.inject("test").await.is_ok());
+        }
+        outcomes
+    };
+    let first = outcomes(FaultInjector::new(config.clone())).await;
+    let second = outcomes(FaultInjector::new(config)).await;
+    assert_eq!(first, second);
+    assert!(first.contains(&true) && first.contains(&false));
+}
+
+#[tokio::test]
+async fn grpc_services() {
+    let endpoint = free_endpoint();
+    let mock = MockIoEngine::new("node", GIB, FaultConfig::default());
+    let state = mock.state();
+    tokio::spawn(mock.serve(endpoint));
+
+    let uri = format!("http://{endpoint}");
+    let mut pools = loop {
+        match PoolRpcClient::connect(uri.clone()).await {
+            Ok(client) => break client,
+            Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+        }
+    };
+    let pool = pools
+        .create_pool(CreatePoolRequest {
+            name: "p0".into(),
+            disks: vec!["/dev/a".into()],
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+
+    let mut replicas = ReplicaRpcClient::connect(uri).await.unwrap();
+    let replica = replicas
+        .create_replica(CreateReplicaRequest {
+            name: "r0".into(),
+            uuid: uuid::Uuid::new_v4().to_string(),
+            pooluuid: pool.uuid.clone(),
+            size: GIB / 4,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(replica.pooluuid, pool.uuid);
+    assert_eq!(state.lock().list_replicas(None, None, None), vec![replica]);
+}
+
+/// Control plane registration service recording the requests it receives.
+struct RegistrationRecorder(mpsc::UnboundedSender<Result<String, String>>);
+
+#[tonic::async_trait]
+impl Registration for RegistrationRecorder {
+    async fn register(
+        &self,
+        request: Request<RegisterRequest>,
+    ) -> Result<Response<()>, Status> {
+        self.0.send(Ok(request.into_inner().id)).ok();
+        Ok(Response::new(()))
+    }
+
+    async fn deregister(
+        &self,
+        request: Request<DeregisterRequest>,
+    ) -> Result<Response<()>, Status> {
+        self.0.send(Err(request.into_inner().id)).ok();
+        Ok(Response::new(()))
+    }
+}
+
+#[tokio::test]
+async fn registration() {
+    let endpoint = free_endpoint();
+    let (sender, mut requests) = mpsc::unbounded_channel();
+    tokio::spawn(
+        tonic::transport::Server::builder()
+            .add_service(RegistrationServer::new(RegistrationRecorder(sender)))
+            .serve(endpoint),
+    );
+
+    let registration = MockRegistration::new(
+        "node",
+        "127.0.0.1:10124",
+        None,
+        format!("http://{endpoint}").parse().unwrap(),
+        Duration::from_millis(50),
+    );
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let run = tokio::spawn(registration.run(async move {
+        stopped.await.ok();
+    }));
+
+    // the node keeps registering until it is stopped, then deregisters
+    for _ in 0 .. 2 {
+        assert_eq!(requests.recv().await, Some(Ok("node".to_string())));
+    }
+    stop.send(()).unwrap();
+    run.await.unwrap();
+    while let Some(request) = requests.recv().await {
+        if request.is_err() {
+            assert_eq!(request, Err("node".to_string()));
+            return;
+        }
+    }
+    panic!("the node was not deregistered");
+}
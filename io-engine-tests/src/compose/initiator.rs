@@ -0,0 +1,189 @@
+//! NVMe initiator running in a separate container.
+//!
+//! Data-path tests usually write to the published targets from the test
+//! process itself. An `Initiator` instead drives the kernel NVMe initiator
+//! with nvme-cli and fio from within a compose container, so that ANA,
+//! reconnect and multipath behaviour can be checked the way a real host would
+//! see it. The container must be privileged and have nvme-cli and fio
+//! available.
+
+use super::network::run;
+use crate::fio::Fio;
+use std::time::{Duration, Instant};
+
+/// ANA state of a path, as reported by the kernel initiator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnaPath {
+    /// Controller name, e.g. `nvme1`.
+    pub controller: String,
+    /// Transport address of the controller.
+    pub address: String,
+    /// ANA state, e.g. `optimized` or `inaccessible`.
+    pub state: String,
+}
+
+/// Kernel NVMe initiator running in a compose container.
+#[derive(Debug, Clone)]
+pub struct Initiator {
+    container: String,
+}
+
+impl Initiator {
+    /// Uses the given container as the initiator.
+    pub fn new(container: &str) -> Self {
+        Self {
+            container: container.to_string(),
+        }
+    }
+
+    /// Name of the initiator container.
+    pub fn container(&self) -> &str {
+        &self.container
+    }
+
+    /// Runs a command in the initiator container.
+    pub fn exec(&self, args: &[&str]) -> Result<String, String> {
+        let mut exec = vec!["exec", "--privileged", self.container.as_str()];
+        exec.extend_from_slice(args);
+        run("docker", &exec)
+    }
+
+    /// Runs a shell script in the initiator container.
+    pub fn sh(&self, script: &str) -> Result<String, String> {
+        self.exec(&["sh", "-c", script])
+    }
+
+    /// Connects to an NVMe-oF TCP target.
+    pub fn connect(
+        &self,
+        address: &str,
+        port: u16,
+        nqn: &str,
+    ) -> Result<(), String> {
+        let port = port.to_string();
+        self.exec(&[
+            "nvme", "connect", "-t", "tcp", "-a", address, "-s", &port, "-n",
+            nqn,
+        ])
+        .map(|_| ())
+    }
+
+    /// Disconnects all the controllers of a subsystem.
+    pub fn disconnect(&self, nqn: &str) -> Result<(), String> {
+        self.exec(&["nvme", "disconnect", "-n", nqn]).map(|_| ())
+    }
+
+    /// Whether native NVMe multipath is enabled in the kernel.
+    pub fn multipath_enabled(&self) -> Result<bool, String> {
+        self.exec(&["cat", "/sys/module/nvme_core/parameters/multipath"])
+            .map(|m| m == "Y")
+    }
+
+    /// Returns the block device of a connected subsystem, e.g.
+    /// `/dev/nvme1n1`.
+    pub fn device(&self, nqn: &str) -> Result<Option<String>, String> {
+        let devices = self.sh(&format!(
+            "for s in /sys/class/nvme-subsystem/*; do \
+                [ \"$(cat $s/subsysnqn)\" = \"{nqn}\" ] || continue; \
+                for n in $s/nvme*n*; do basename $n; done; \
+            done"
+        ))?;
+        Ok(devices
+            .split_whitespace()
+            .find(|d| !d.contains('c'))
+            .map(|d| format!("/dev/{d}")))
+    }
+
+    /// Waits for the block device of a subsystem to appear.
+    pub fn wait_device(
+        &self,
+        nqn: &str,
+        timeout: Duration,
+    ) -> Result<String, String> {
+        let start = Instant::now();
+        loop {
+            if let Some(device) = self.device(nqn)? {
+                return Ok(device);
+            }
+            if start.elapsed() > timeout {
+                return Err(format!(
+                    "No device for {nqn} on {} after {timeout:?}",
+                    self.container
+                ));
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    /// Returns the ANA state of every path to a subsystem.
+    pub fn ana_paths(&self, nqn: &str) -> Result<Vec<AnaPath>, String> {
+        let paths = self.sh(&format!(
+            "for s in /sys/class/nvme-subsystem/*; do \
+                [ \"$(cat $s/subsysnqn)\" = \"{nqn}\" ] || continue; \
+                for c in $s/nvme[0-9]*; do \
+                    [ -e $c/address ] || continue; \
+                    for p in $c/nvme*c*n*; do \
+                        echo \"$(basename $c)|$(cat $c/address)|$(cat $p/ana_state)\"; \
+                    done; \
+                done; \
+            done"
+        ))?;
+        Ok(paths
+            .lines()
+            .filter_map(|l| {
+                let mut fields = l.splitn(3, '|');
+                Some(AnaPath {
+                    controller: fields.next()?.to_string(),
+                    address: fields.next()?.to_string(),
+                    state: fields.next()?.to_string(),
+                })
+            })
+            .collect())
+    }
+
+    /// Waits until the path through the given target address reaches the
+    /// expected ANA state.
+    pub fn wait_ana_state(
+        &self,
+        nqn: &str,
+        target: &str,
+        state: &str,
+        timeout: Duration,
+    ) -> Result<(), String> {
+        let start = Instant::now();
+        loop {
+            let paths = self.ana_paths(nqn)?;
+            if paths
+                .iter()
+                .any(|p| p.address.contains(target) && p.state == state)
+            {
+                return Ok(());
+            }
+            if start.elapsed() > timeout {
+                return Err(format!(
+                    "Path to {target} of {nqn} not {state} after \
+                    {timeout:?}: {paths:?}"
+                ));
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    /// Runs the jobs of the given FIO in the initiator container against the
+    /// kernel block devices: the I/O engine of the jobs is replaced by
+    /// `libaio`.
+    pub fn fio(&self, fio: &Fio) -> Result<String, String> {
+        let args = fio
+            .jobs
+            .iter()
+            .flat_map(|j| {
+                let mut args = j.as_fio_args();
+                args.push("--ioengine=libaio".to_string());
+                args
+            })
+            .collect::<Vec<_>>();
+        let mut cmd = vec!["fio", "--output-format=json"];
+        cmd.extend(args.iter().map(String::as_str));
+        self.exec(&cmd)
+    }
+}
@@ -15,6 +15,9 @@ use io_engine::core::{
 };
 use std::time::Duration;
 
+pub mod initiator;
+pub mod network;
+pub mod recovery;
 pub mod rpc;
 
 /// Mayastor test structure that simplifies sending futures. Mayastor has
@@ -0,0 +1,318 @@
+//! Network fault helpers for compose tests.
+//!
+//! The compose builder places all containers on a single network. These
+//! helpers extend it with additional networks, partition containers from
+//! each other and shape the traffic of a container with netem, so that
+//! failover and split-brain scenarios can be reproduced.
+//! Faults are applied from the host inside the network namespace of the
+//! containers, so the container images do not need `tc` or `iptables`.
+
+use composer::{Builder, ComposeTest};
+use std::{ops::Deref, process::Command, time::Duration};
+
+/// Runs a command on the host and returns its standard output.
+pub(super) fn run(program: &str, args: &[&str]) -> Result<String, String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run '{program}': {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "'{program} {}' failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Runs a command in the network namespace of a container.
+fn run_in_netns(container: &str, args: &[&str]) -> Result<String, String> {
+    let pid = run("docker", &["inspect", "-f", "{{.State.Pid}}", container])?;
+    let mut nsenter = vec!["-t", pid.as_str(), "-n"];
+    nsenter.extend_from_slice(args);
+    run("nsenter", &nsenter)
+}
+
+/// Returns the IP addresses of a container on all of its networks.
+pub fn container_ips(container: &str) -> Result<Vec<String>, String> {
+    let ips = run(
+        "docker",
+        &[
+            "inspect",
+            "-f",
+            "{{range .NetworkSettings.Networks}}{{.IPAddress}} {{end}}",
+            container,
+        ],
+    )?;
+    Ok(ips.split_whitespace().map(ToString::to_string).collect())
+}
+
+/// An additional docker network, removed when dropped.
+#[derive(Debug)]
+pub struct ComposeNetwork {
+    name: String,
+}
+
+impl ComposeNetwork {
+    /// Creates a bridge network with the given subnet, e.g. `10.2.0.0/16`.
+    pub fn create(name: &str, subnet: &str) -> Result<Self, String> {
+        run(
+            "docker",
+            &[
+                "network", "create", "--driver", "bridge", "--subnet", subnet,
+                name,
+            ],
+        )?;
+        Ok(Self {
+            name: name.to_string(),
+        })
+    }
+
+    /// Name of the network.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Attaches a container to the network, with an optional fixed IP.
+    pub fn connect(
+        &self,
+        container: &str,
+        ip: Option<&str>,
+    ) -> Result<(), String> {
+        let mut args = vec!["network", "connect"];
+        if let Some(ip) = ip {
+            args.extend_from_slice(&["--ip", ip]);
+        }
+        args.extend_from_slice(&[self.name.as_str(), container]);
+        run("docker", &args).map(|_| ())
+    }
+
+    /// Detaches a container from the network.
+    pub fn disconnect(&self, container: &str) -> Result<(), String> {
+        run(
+            "docker",
+            &["network", "disconnect", "--force", &self.name, container],
+        )
+        .map(|_| ())
+    }
+}
+
+impl Drop for ComposeNetwork {
+    fn drop(&mut self) {
+        if let Err(error) = run("docker", &["network", "rm", &self.name]) {
+            tracing::warn!("Failed to remove network {}: {error}", self.name);
+        }
+    }
+}
+
+/// Specification of an additional network of a compose test.
+#[derive(Debug, Clone)]
+struct NetworkSpec {
+    name: String,
+    subnet: String,
+    containers: Vec<(String, Option<String>)>,
+}
+
+/// Extends the compose `Builder` with additional networks.
+pub trait BuilderNetworkExt {
+    /// Adds a bridge network with the given subnet, e.g. `10.2.0.0/16`, which
+    /// the given containers are attached to once they are built.
+    fn add_network(
+        self,
+        name: &str,
+        subnet: &str,
+        containers: &[&str],
+    ) -> NetworkBuilder;
+}
+
+impl BuilderNetworkExt for Builder {
+    fn add_network(
+        self,
+        name: &str,
+        subnet: &str,
+        containers: &[&str],
+    ) -> NetworkBuilder {
+        NetworkBuilder {
+            builder: self,
+            networks: vec![],
+        }
+        .add_network(name, subnet, containers)
+    }
+}
+
+/// A compose `Builder` along with the additional networks of the test.
+pub struct NetworkBuilder {
+    builder: Builder,
+    networks: Vec<NetworkSpec>,
+}
+
+impl NetworkBuilder {
+    /// Adds another bridge network, see `BuilderNetworkExt::add_network`.
+    pub fn add_network(
+        mut self,
+        name: &str,
+        subnet: &str,
+        containers: &[&str],
+    ) -> Self {
+        self.networks.push(NetworkSpec {
+            name: name.to_string(),
+            subnet: subnet.to_string(),
+            containers: containers
+                .iter()
+                .map(|c| (c.to_string(), None))
+                .collect(),
+        });
+        self
+    }
+
+    /// Attaches a container to the last added network with a fixed IP.
+    pub fn with_ip(mut self, container: &str, ip: &str) -> Self {
+        let network = self
+            .networks
+            .last_mut()
+            .expect("no network to attach the container to");
+        network.containers.retain(|(c, _)| c != container);
+        network
+            .containers
+            .push((container.to_string(), Some(ip.to_string())));
+        self
+    }
+
+    /// Builds the compose test, then creates the additional networks and
+    /// attaches their containers.
+    pub async fn build(self) -> Result<NetworkedTest, String> {
+        let test = self.builder.build().await.map_err(|e| e.to_string())?;
+        let mut networks = Vec::with_capacity(self.networks.len());
+        for spec in self.networks {
+            let network = ComposeNetwork::create(&spec.name, &spec.subnet)?;
+            for (container, ip) in &spec.containers {
+                network.connect(container, ip.as_deref())?;
+            }
+            networks.push(network);
+        }
+        Ok(NetworkedTest {
+            test,
+            networks,
+        })
+    }
+}
+
+/// A compose test with additional networks.
+pub struct NetworkedTest {
+    test: ComposeTest,
+    // declared after the test so that the networks are removed once its
+    // containers are gone
+    networks: Vec<ComposeNetwork>,
+}
+
+impl NetworkedTest {
+    /// Returns the additional network with the given name.
+    pub fn network(&self, name: &str) -> Option<&ComposeNetwork> {
+        self.networks.iter().find(|n| n.name() == name)
+    }
+}
+
+impl Deref for NetworkedTest {
+    type Target = ComposeTest;
+
+    fn deref(&self) -> &Self::Target {
+        &self.test
+    }
+}
+
+/// Traffic shaping applied to an interface of a container.
+#[derive(Debug, Default, Clone)]
+pub struct Netem {
+    /// Delay added to every packet.
+    pub delay: Duration,
+    /// Random variation of the delay.
+    pub jitter: Duration,
+    /// Percentage of packets dropped.
+    pub loss: f64,
+}
+
+impl Netem {
+    /// Netem adding the given delay.
+    pub fn delay(delay: Duration) -> Self {
+        Self {
+            delay,
+            ..Default::default()
+        }
+    }
+
+    /// Netem dropping the given percentage of packets.
+    pub fn loss(loss: f64) -> Self {
+        Self {
+            loss,
+            ..Default::default()
+        }
+    }
+
+    /// Sets the jitter of the delay.
+    pub fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    fn args(&self) -> Vec<String> {
+        let mut args =
+            vec!["delay".to_string(), format!("{}us", self.delay.as_micros())];
+        if !self.jitter.is_zero() {
+            args.push(format!("{}us", self.jitter.as_micros()));
+        }
+        if self.loss > 0.0 {
+            args.push("loss".to_string());
+            args.push(format!("{}%", self.loss));
+        }
+        args
+    }
+}
+
+/// Applies netem on an interface of a container, replacing any previous
+/// shaping of that interface.
+pub fn set_netem(
+    container: &str,
+    iface: &str,
+    netem: &Netem,
+) -> Result<(), String> {
+    let netem = netem.args();
+    let mut args =
+        vec!["tc", "qdisc", "replace", "dev", iface, "root", "netem"];
+    args.extend(netem.iter().map(String::as_str));
+    run_in_netns(container, &args).map(|_| ())
+}
+
+/// Removes the netem shaping from an interface of a container.
+pub fn clear_netem(container: &str, iface: &str) -> Result<(), String> {
+    run_in_netns(container, &["tc", "qdisc", "del", "dev", iface, "root"])
+        .map(|_| ())
+}
+
+/// Adds or removes the rules dropping the traffic between two containers.
+fn partition_rules(a: &str, b: &str, op: &str) -> Result<(), String> {
+    for (this, other) in [(a, b), (b, a)] {
+        for ip in container_ips(other)? {
+            run_in_netns(
+                this,
+                &["iptables", op, "INPUT", "-s", &ip, "-j", "DROP"],
+            )?;
+            run_in_netns(
+                this,
+                &["iptables", op, "OUTPUT", "-d", &ip, "-j", "DROP"],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Partitions two containers from each other, on all of their networks,
+/// while leaving their connectivity with other containers intact.
+pub fn partition(a: &str, b: &str) -> Result<(), String> {
+    partition_rules(a, b, "-I")
+}
+
+/// Heals a partition created with `partition`.
+pub fn heal(a: &str, b: &str) -> Result<(), String> {
+    partition_rules(a, b, "-D")
+}
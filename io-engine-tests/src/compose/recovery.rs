@@ -0,0 +1,238 @@
+//! Crash-recovery helpers for compose tests.
+//!
+//! An io-engine container can be killed or restarted mid-test; the helpers
+//! wait for it to serve gRPC again, re-import its pools and re-share its
+//! replicas, and compare the state found after recovery with the state that
+//! was captured before the crash.
+
+use super::rpc::v1::{
+    pool::{ImportPoolRequest, PoolType},
+    replica::ShareReplicaRequest,
+    GrpcConnect,
+    SharedRpcHandle,
+    Status,
+};
+use crate::{pool::list_pools, replica::list_replicas};
+use composer::ComposeTest;
+use std::time::{Duration, Instant};
+
+/// Persisted properties of a pool, which must survive a crash.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PersistedPool {
+    pub name: String,
+    pub uuid: String,
+    pub disks: Vec<String>,
+}
+
+/// Persisted properties of a replica, which must survive a crash.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PersistedReplica {
+    pub name: String,
+    pub uuid: String,
+    pub pool_uuid: String,
+    pub size: u64,
+    pub thin: bool,
+    pub shared: bool,
+    pub allowed_hosts: Vec<String>,
+    pub entity_id: Option<String>,
+}
+
+/// Persisted state of an io-engine.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PersistedState {
+    pub pools: Vec<PersistedPool>,
+    pub replicas: Vec<PersistedReplica>,
+}
+
+impl PersistedState {
+    /// Captures the pools and replicas of an io-engine.
+    pub async fn capture(rpc: SharedRpcHandle) -> Result<Self, Status> {
+        let mut pools = list_pools(rpc.clone())
+            .await?
+            .into_iter()
+            .map(|p| PersistedPool {
+                name: p.name,
+                uuid: p.uuid,
+                disks: p.disks,
+            })
+            .collect::<Vec<_>>();
+        let mut replicas = list_replicas(rpc)
+            .await?
+            .into_iter()
+            .filter(|r| !r.is_snapshot)
+            .map(|r| {
+                let mut allowed_hosts = r.allowed_hosts;
+                allowed_hosts.sort();
+                PersistedReplica {
+                    name: r.name,
+                    uuid: r.uuid,
+                    pool_uuid: r.pooluuid,
+                    size: r.size,
+                    thin: r.thin,
+                    shared: r.share != 0,
+                    allowed_hosts,
+                    entity_id: r.entity_id,
+                }
+            })
+            .collect::<Vec<_>>();
+        pools.sort();
+        replicas.sort();
+
+        Ok(Self {
+            pools,
+            replicas,
+        })
+    }
+
+    /// Re-imports the pools of this state which are not present.
+    pub async fn import_pools(
+        &self,
+        rpc: SharedRpcHandle,
+    ) -> Result<(), Status> {
+        let present = list_pools(rpc.clone()).await?;
+        for pool in &self.pools {
+            if present.iter().any(|p| p.uuid == pool.uuid) {
+                continue;
+            }
+            rpc.lock()
+                .await
+                .pool
+                .import_pool(ImportPoolRequest {
+                    name: pool.name.clone(),
+                    uuid: Some(pool.uuid.clone()),
+                    disks: pool.disks.clone(),
+                    pooltype: PoolType::Lvs as i32,
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Re-shares the replicas of this state which were shared.
+    pub async fn share_replicas(
+        &self,
+        rpc: SharedRpcHandle,
+    ) -> Result<(), Status> {
+        for replica in self.replicas.iter().filter(|r| r.shared) {
+            rpc.lock()
+                .await
+                .replica
+                .share_replica(ShareReplicaRequest {
+                    uuid: replica.uuid.clone(),
+                    share: 1,
+                    allowed_hosts: replica.allowed_hosts.clone(),
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Re-imports the pools and re-shares the replicas of this state, then
+    /// waits until the io-engine reports exactly this state.
+    pub async fn restore(
+        &self,
+        rpc: SharedRpcHandle,
+        timeout: Duration,
+    ) -> Result<(), String> {
+        self.import_pools(rpc.clone())
+            .await
+            .map_err(|e| format!("Failed to import pools: {e}"))?;
+        self.share_replicas(rpc.clone())
+            .await
+            .map_err(|e| format!("Failed to share replicas: {e}"))?;
+        self.wait_for(rpc, timeout).await
+    }
+
+    /// Waits until the io-engine reports exactly this state.
+    pub async fn wait_for(
+        &self,
+        rpc: SharedRpcHandle,
+        timeout: Duration,
+    ) -> Result<(), String> {
+        let start = Instant::now();
+        loop {
+            let current = Self::capture(rpc.clone()).await;
+            match current {
+                Ok(current) if &current == self => return Ok(()),
+                Ok(current) if start.elapsed() > timeout => {
+                    return Err(format!(
+                        "State not restored after {timeout:?}: expected \
+                        {self:#?}, found {current:#?}"
+                    ));
+                }
+                Err(error) if start.elapsed() > timeout => {
+                    return Err(format!(
+                        "State not restored after {timeout:?}: {error}"
+                    ));
+                }
+                _ => tokio::time::sleep(Duration::from_millis(100)).await,
+            }
+        }
+    }
+
+    /// Asserts that the io-engine reports exactly this state.
+    pub async fn assert_matches(&self, rpc: SharedRpcHandle) {
+        let current = Self::capture(rpc).await.unwrap();
+        assert_eq!(&current, self, "persisted state mismatch");
+    }
+}
+
+/// How an io-engine container is brought down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Crash {
+    /// SIGKILL the container, without any chance of a clean shutdown.
+    Kill,
+    /// Restart the container, letting the io-engine shut down cleanly.
+    Restart,
+}
+
+/// Brings an io-engine container down and up again, then waits for its gRPC
+/// server to answer and returns a new handle to it.
+pub async fn crash_and_restart(
+    test: &ComposeTest,
+    name: &str,
+    crash: Crash,
+    timeout: Duration,
+) -> Result<SharedRpcHandle, String> {
+    match crash {
+        Crash::Kill => {
+            test.kill(name)
+                .await
+                .map_err(|e| format!("Failed to kill {name}: {e}"))?;
+            test.start(name)
+                .await
+                .map_err(|e| format!("Failed to start {name}: {e}"))?;
+        }
+        Crash::Restart => {
+            test.restart(name)
+                .await
+                .map_err(|e| format!("Failed to restart {name}: {e}"))?;
+        }
+    }
+
+    let rpc = GrpcConnect::new(test).grpc_handle_shared(name).await?;
+    let start = Instant::now();
+    while let Err(error) = list_pools(rpc.clone()).await {
+        if start.elapsed() > timeout {
+            return Err(format!(
+                "{name} not serving gRPC after {timeout:?}: {error}"
+            ));
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    Ok(rpc)
+}
+
+/// Crashes an io-engine container, restarts it and restores the given state,
+/// failing if the state found after recovery differs.
+pub async fn crash_and_recover(
+    test: &ComposeTest,
+    name: &str,
+    crash: Crash,
+    state: &PersistedState,
+    timeout: Duration,
+) -> Result<SharedRpcHandle, String> {
+    let rpc = crash_and_restart(test, name, crash, timeout).await?;
+    state.restore(rpc.clone(), timeout).await?;
+    Ok(rpc)
+}
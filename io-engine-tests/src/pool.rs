@@ -263,6 +263,7 @@ impl PoolBuilderLocal {
             uuid: Some(self.uuid()),
             disks: vec![self.bdev.as_ref().unwrap().clone()],
             cluster_size: None,
+            reserved_pct: None,
             backend: Default::default(),
         })
         .await?;
@@ -1,15 +1,16 @@
 //! Implementation of parse() - the main
 //! dispatch function for parsing device URIs.
-//! This should be the only place where we require any
-//! knowledge of the URI schemes and the corresponding
-//! bdev types that we can support.
+//! The URI schemes and the corresponding bdev types
+//! that we can support are kept in a registry, which
+//! holds the built-in ones and any registered since.
 //!
 //! Adding support for a new device type requires the following:
 //!  - Providing an implementation for the bdev::CreateDestroy trait.
 //!  - Providing an implementation for the bdev::GetName trait.
 //!  - Providing an implementation for the TryFrom<&Url> trait.
-//!  - Arranging for the try_from() method to be called by the parse() function
-//!    below for a URI with the appropriate scheme.
+//!  - Adding the type to the built-in schemes below, or, for a type which lives
+//!    out of tree, e.g. behind a feature flag of its own, registering its
+//!    scheme with register_uri_scheme() before any URI is parsed.
 //!
 //! See mod.rs for the appropriate trait definition(s), and refer
 //! to the files in the dev directory for sample implementations.
@@ -32,9 +33,11 @@ use crate::{
 use url::Url;
 
 pub(crate) mod uri {
-    use std::convert::TryFrom;
+    use std::{collections::HashMap, convert::TryFrom, sync::Mutex};
 
+    use once_cell::sync::Lazy;
     use snafu::ResultExt;
+    use url::Url;
 
     use crate::{
         bdev::{
@@ -52,6 +55,110 @@ pub(crate) mod uri {
         bdev_api::{self, BdevError},
     };
 
+    /// A device which a URI describes.
+    type Device = Box<dyn BdevCreateDestroy<Error = BdevError>>;
+
+    /// Parser of the URIs of a scheme into the device they describe.
+    pub type UriParser = fn(&Url) -> Result<Device, BdevError>;
+
+    /// A scheme which the URIs may be given with.
+    #[derive(Clone, Copy)]
+    struct UriScheme {
+        /// parser of the URIs of the scheme
+        parser: UriParser,
+        /// name of the bdev driver which creates the devices of the scheme
+        driver: &'static str,
+    }
+
+    /// Returns a parser of the URIs into devices of the given type.
+    fn parser<T>() -> UriParser
+    where
+        T: BdevCreateDestroy<Error = BdevError>
+            + for<'a> TryFrom<&'a Url, Error = BdevError>
+            + 'static,
+    {
+        |url| Ok(Box::new(T::try_from(url)?))
+    }
+
+    /// Schemes which the URIs may be given with, initially the built-in ones.
+    static URI_SCHEMES: Lazy<Mutex<HashMap<String, UriScheme>>> =
+        Lazy::new(|| {
+            let schemes: [(&str, UriParser, &str); 10] = [
+                ("aio", parser::<aio::Aio>(), "aio"),
+                ("bdev", parser::<loopback::Loopback>(), "bdev"),
+                ("loopback", parser::<loopback::Loopback>(), "loopback"),
+                ("malloc", parser::<malloc::Malloc>(), "malloc"),
+                ("null", parser::<null_bdev::Null>(), "null"),
+                ("nvmf", parser::<nvmx::NvmfDeviceTemplate>(), "nvme"),
+                ("pcie", parser::<nvme::NVMe>(), "nvme"),
+                ("uring", parser::<uring::Uring>(), "uring"),
+                ("nexus", parser::<nx::Nexus>(), "nexus"),
+                ("lvol", parser::<lvs::Lvol>(), "lvol"),
+            ];
+            Mutex::new(
+                schemes
+                    .into_iter()
+                    .map(|(scheme, parser, driver)| {
+                        (
+                            scheme.to_string(),
+                            UriScheme {
+                                parser,
+                                driver,
+                            },
+                        )
+                    })
+                    .collect(),
+            )
+        });
+
+    /// Registers a scheme, so that the devices of a type which is not built
+    /// in may be created from their URIs, e.g. as the disks of the pools or
+    /// the children of the nexuses. The driver is the name of the bdev driver
+    /// which creates these devices.
+    pub fn register_uri_scheme(
+        scheme: &str,
+        driver: &'static str,
+        parser: UriParser,
+    ) -> Result<(), BdevError> {
+        let mut schemes = URI_SCHEMES.lock().unwrap();
+        if schemes.contains_key(scheme) {
+            return Err(BdevError::UriSchemeExists {
+                scheme: scheme.to_string(),
+            });
+        }
+        schemes.insert(
+            scheme.to_string(),
+            UriScheme {
+                parser,
+                driver,
+            },
+        );
+        info!("URI scheme '{scheme}' registered for driver '{driver}'");
+        Ok(())
+    }
+
+    /// Returns the schemes which the URIs may be given with.
+    pub fn uri_schemes() -> Vec<String> {
+        let mut schemes = URI_SCHEMES
+            .lock()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        schemes.sort();
+        schemes
+    }
+
+    /// Returns the name of the bdev driver which creates the devices of a
+    /// scheme, if registered.
+    pub(crate) fn scheme_driver(scheme: &str) -> Option<&'static str> {
+        URI_SCHEMES
+            .lock()
+            .unwrap()
+            .get(scheme)
+            .map(|scheme| scheme.driver)
+    }
+
     pub fn parse(
         uri: &str,
     ) -> Result<Box<dyn BdevCreateDestroy<Error = BdevError>>, BdevError> {
@@ -59,21 +166,12 @@ pub(crate) mod uri {
             uri: uri.to_string(),
         })?;
 
-        match url.scheme() {
-            "aio" => Ok(Box::new(aio::Aio::try_from(&url)?)),
-            "bdev" | "loopback" => {
-                Ok(Box::new(loopback::Loopback::try_from(&url)?))
-            }
-            "malloc" => Ok(Box::new(malloc::Malloc::try_from(&url)?)),
-            "null" => Ok(Box::new(null_bdev::Null::try_from(&url)?)),
-            "nvmf" => Ok(Box::new(nvmx::NvmfDeviceTemplate::try_from(&url)?)),
-            "pcie" => Ok(Box::new(nvme::NVMe::try_from(&url)?)),
-            "uring" => Ok(Box::new(uring::Uring::try_from(&url)?)),
-            "nexus" => Ok(Box::new(nx::Nexus::try_from(&url)?)),
-            "lvol" => Ok(Box::new(lvs::Lvol::try_from(&url)?)),
-
-            scheme => Err(BdevError::UriSchemeUnsupported {
-                scheme: scheme.to_string(),
+        // not called under the lock, as a parser may look the schemes up
+        let scheme = URI_SCHEMES.lock().unwrap().get(url.scheme()).copied();
+        match scheme {
+            Some(scheme) => (scheme.parser)(&url),
+            None => Err(BdevError::UriSchemeUnsupported {
+                scheme: url.scheme().to_string(),
             }),
         }
     }
@@ -17,6 +17,7 @@ use spdk_rs::{
         spdk_bdev_comparev_blocks,
         spdk_bdev_flush,
         spdk_bdev_free_io,
+        spdk_bdev_get_dif_type,
         spdk_bdev_io,
         spdk_bdev_readv_blocks_with_flags,
         spdk_bdev_reset,
@@ -145,6 +146,14 @@ impl BlockDevice for SpdkBlockDevice {
     fn io_type_supported(&self, io_type: IoType) -> bool {
         self.0.io_type_supported(io_type)
     }
+    /// returns the protection information type of the device
+    fn pi_type(&self) -> u32 {
+        unsafe { spdk_bdev_get_dif_type(self.0.unsafe_inner_ptr()) as u32 }
+    }
+    /// returns the name of the bdev module the device is provided by
+    fn transport(&self) -> String {
+        self.0.driver().to_string()
+    }
     /// returns the IO statistics
     async fn io_stats(&self) -> Result<BlockDeviceIoStats, CoreError> {
         self.0.stats_async().await
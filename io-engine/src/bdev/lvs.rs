@@ -215,6 +215,7 @@ impl Lvs {
             disks: vec![self.disk.to_owned()],
             uuid: None,
             cluster_size: None,
+            reserved_pct: None,
             backend: PoolBackend::Lvs,
         };
         match &self.mode {
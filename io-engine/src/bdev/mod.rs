@@ -1,10 +1,18 @@
 use async_trait::async_trait;
 
-pub use dev::{device_create, device_destroy, device_lookup, device_open};
+pub use dev::{
+    device_create,
+    device_destroy,
+    device_lookup,
+    device_open,
+    uri::{register_uri_scheme, uri_schemes, UriParser},
+};
 pub use device::{bdev_event_callback, bdev_io_ctx_pool_init, SpdkBlockDevice};
 pub use nexus::{Nexus, NexusInfo, NexusState};
 pub use nvmx::{
+    nvme_congestion,
     nvme_io_ctx_pool_init,
+    NvmeCongestion,
     NvmeController,
     NvmeControllerState,
     NVME_CONTROLLERS,
@@ -1,6 +1,6 @@
 #![allow(clippy::vec_box)]
 
-use std::{pin::Pin, sync::atomic::AtomicBool};
+use std::{pin::Pin, sync::atomic::AtomicBool, time::Duration};
 
 use crate::core::VerboseError;
 use events_api::event::EventAction;
@@ -10,7 +10,10 @@ mod nexus_bdev;
 mod nexus_bdev_children;
 mod nexus_bdev_error;
 mod nexus_bdev_rebuild;
+mod nexus_bdev_replace;
 mod nexus_bdev_snapshot;
+mod nexus_bdev_takeover;
+mod nexus_capabilities;
 mod nexus_channel;
 mod nexus_child;
 mod nexus_io;
@@ -20,11 +23,19 @@ mod nexus_iter;
 mod nexus_module;
 mod nexus_nbd;
 mod nexus_persistence;
+mod nexus_retention;
 mod nexus_share;
 
 use crate::{
     bdev::nexus::nexus_iter::NexusIterMut,
     eventing::{Event, EventMetaGen, EventWithMeta},
+    subsys::{
+        HostDhChap,
+        IdentifyOverrides,
+        NvmfListener,
+        NvmfShareMode,
+        NvmfTransport,
+    },
 };
 pub(crate) use nexus_bdev::NEXUS_PRODUCT_ID;
 pub use nexus_bdev::{
@@ -39,10 +50,30 @@ pub use nexus_bdev::{
     NexusTarget,
     NvmeAnaState,
     NvmeReservation,
+    NVME_MAX_CNTLID,
+    NVME_MIN_CNTLID,
 };
 pub(crate) use nexus_bdev_error::nexus_err;
 pub use nexus_bdev_error::Error;
-pub(crate) use nexus_channel::{DrEvent, IoMode, NexusChannel};
+pub use nexus_capabilities::{
+    ChildCapabilities,
+    DeviceCapabilities,
+    NexusDetail,
+};
+pub(crate) use nexus_channel::{
+    DrEvent,
+    IoMode,
+    LaggingChildren,
+    NexusChannel,
+};
+pub use nexus_channel::{
+    NexusChannelIops,
+    NexusChannelStats,
+    NexusChildIoStats,
+    NexusFlushStats,
+    NexusReadPolicy,
+    NexusWritePolicy,
+};
 pub use nexus_child::{
     ChildError,
     ChildState,
@@ -51,7 +82,7 @@ pub use nexus_child::{
     FaultReason,
     NexusChild,
 };
-use nexus_io::{NexusBio, NioCtx};
+use nexus_io::{NexusBio, NioCtx, TimedChildIo};
 use nexus_io_log::{IOLog, IOLogChannel};
 use nexus_io_subsystem::NexusIoSubsystem;
 pub use nexus_io_subsystem::NexusPauseState;
@@ -69,13 +100,16 @@ pub(crate) use nexus_module::{NexusModule, NEXUS_MODULE_NAME};
 pub(crate) use nexus_nbd::{NbdDisk, NbdError};
 pub(crate) use nexus_persistence::PersistOp;
 pub use nexus_persistence::{ChildInfo, NexusInfo};
+pub use nexus_retention::{NexusRetention, RetentionMode};
 pub(crate) use nexus_share::NexusPtpl;
 
+pub use nexus_bdev_replace::{ChildReplacement, ReplacementState};
 pub use nexus_bdev_snapshot::{
     NexusReplicaSnapshotDescriptor,
     NexusReplicaSnapshotStatus,
     NexusSnapshotStatus,
 };
+pub use nexus_bdev_takeover::{nexus_repair_takeover, RetargetedChild};
 
 /// TODO
 #[derive(Deserialize)]
@@ -88,6 +122,45 @@ struct NexusShareArgs {
     cntlid_min: u16,
     /// TODO
     cntlid_max: u16,
+    /// Explicit NQN of the subsystem, derived from the name by default.
+    #[serde(default)]
+    nqn: Option<String>,
+    /// Lease of the share in milliseconds, which must then be renewed.
+    #[serde(default)]
+    lease_ms: Option<u64>,
+    /// Transports to listen on at the address of the target, TCP when
+    /// neither transports nor listeners are given.
+    #[serde(default)]
+    transports: Vec<NvmfTransport>,
+    /// Addresses to listen on in addition to those of the transports.
+    #[serde(default)]
+    listeners: Vec<NvmfListener>,
+    /// Hosts allowed to connect, any host when none is given.
+    #[serde(default)]
+    allowed_hosts: Vec<String>,
+    /// Keys of the allowed hosts which must authenticate.
+    #[serde(default)]
+    host_auth: Vec<HostDhChap>,
+    /// Report ANA on the share, as configured for the nexuses by default.
+    #[serde(default)]
+    ana_reporting: Option<bool>,
+    /// Serial number of the subsystem, derived from the UUID by default.
+    #[serde(default)]
+    serial: Option<String>,
+    /// Model number of the subsystem, the Mayastor one by default.
+    #[serde(default)]
+    model: Option<String>,
+    /// Overrides of the identify controller data of the subsystem.
+    #[serde(default)]
+    identify: Option<IdentifyOverrides>,
+    /// Keep alive timeout in milliseconds, the one of the nexuses by
+    /// default.
+    #[serde(default)]
+    kato_ms: Option<u32>,
+    /// Whether the nexus is used by one host at a time or by several,
+    /// exclusive by default.
+    #[serde(default)]
+    share_mode: NvmfShareMode,
 }
 
 /// TODO
@@ -97,6 +170,114 @@ struct NexusShareReply {
     uri: String,
 }
 
+/// Arguments of the nexus detail call.
+#[derive(Deserialize)]
+struct NexusDetailArgs {
+    /// Name of the nexus.
+    name: String,
+}
+
+/// Arguments of the nexus channel IOPS call.
+#[derive(Deserialize)]
+struct NexusChannelIopsArgs {
+    /// Name of the nexus.
+    name: String,
+    /// Interval the IOPS are sampled over, in milliseconds.
+    #[serde(default = "default_iops_interval_ms")]
+    interval_ms: u64,
+}
+
+fn default_iops_interval_ms() -> u64 {
+    1000
+}
+
+/// Arguments of the nexus ANA reporting call.
+#[derive(Deserialize)]
+struct NexusAnaReportingArgs {
+    /// Name of the nexus.
+    name: String,
+    /// Whether ANA is reported on the share of the nexus.
+    enable: bool,
+}
+
+/// Arguments of the nexus flush verification call.
+#[derive(Deserialize)]
+struct NexusFlushVerifyArgs {
+    /// Name of the nexus.
+    name: String,
+    /// Whether the flushes are verified to reach every healthy child.
+    enable: bool,
+}
+
+/// Arguments of the nexus read policy call.
+#[derive(Deserialize)]
+struct NexusReadPolicyArgs {
+    /// Name of the nexus.
+    name: String,
+    /// Policy the children to read from are selected by.
+    policy: NexusReadPolicy,
+}
+
+/// Arguments of the nexus write policy call.
+#[derive(Deserialize)]
+struct NexusWritePolicyArgs {
+    /// Name of the nexus.
+    name: String,
+    /// Policy the writes are acknowledged by.
+    policy: NexusWritePolicy,
+}
+
+/// Arguments of the nexus child I/O timeout call.
+#[derive(Deserialize)]
+struct NexusChildIoTimeoutArgs {
+    /// Name of the nexus.
+    name: String,
+    /// Time in milliseconds a child may take to complete an I/O, or none to
+    /// stop timing the child I/Os.
+    timeout_ms: Option<u64>,
+}
+
+/// Arguments of the nexus takeover repair call.
+#[derive(Deserialize)]
+struct NexusTakeoverArgs {
+    /// URIs the replicas of the pools taken over are shared at.
+    uris: Vec<String>,
+}
+
+/// Arguments of the nexus host fencing calls.
+#[derive(Deserialize)]
+struct NexusFenceHostArgs {
+    /// Name of the nexus.
+    name: String,
+    /// NQN of the host.
+    host: String,
+}
+
+/// Arguments of the nexus retention call.
+#[derive(Deserialize)]
+struct NexusRetentionArgs {
+    /// Name of the nexus.
+    name: String,
+    mode: RetentionMode,
+    /// Time the retention is enforced until, in seconds since the UNIX
+    /// epoch.
+    retain_until: u64,
+    /// Block the data may be appended from, in the append-only mode.
+    #[serde(default)]
+    append_offset: u64,
+}
+
+/// Arguments of the nexus child replacement call.
+#[derive(Deserialize)]
+struct NexusReplaceChildArgs {
+    /// Name of the nexus.
+    name: String,
+    /// URI of the child to replace.
+    src_uri: String,
+    /// URI of the new child.
+    dst_uri: String,
+}
+
 /// public function which simply calls register module
 pub fn register_module(register_json: bool) {
     nexus_module::register_module();
@@ -107,7 +288,9 @@ pub fn register_module(register_json: bool) {
 
     use crate::{
         core::{NvmfShareProps, Share, UntypedBdev},
-        jsonrpc::{jsonrpc_register, Code, JsonRpcError, Result},
+        jsonrpc::{jsonrpc_register, Code, JsonRpcError, Result, RpcErrorCode},
+        sleep::mayastor_sleep,
+        subsys::{Config, NvmfControllerInfo},
     };
 
     jsonrpc_register(
@@ -126,7 +309,7 @@ pub fn register_module(register_json: bool) {
                     let mut bdev = Pin::new(&mut bdev);
                     match proto.as_str() {
                         "nvmf" => {
-                            let share = NvmfShareProps::new().with_range(Some((args.cntlid_min, args.cntlid_max))).with_ana(true);
+                            let share = NvmfShareProps::new().with_range(Some((args.cntlid_min, args.cntlid_max))).with_ana(args.ana_reporting.unwrap_or(Config::get().nexus_opts.nvmf_ana_reporting)).with_nqn(args.nqn).with_lease(args.lease_ms.map(Duration::from_millis)).with_transports(args.transports).with_listeners(args.listeners).with_allowed_hosts(args.allowed_hosts).with_host_auth(args.host_auth).with_serial(args.serial).with_model(args.model).with_identify(args.identify).with_kato(args.kato_ms).with_share_mode(args.share_mode);
                             bdev.as_mut().share_nvmf(Some(share))
                                 .await
                                 .map_err(|e| {
@@ -153,6 +336,375 @@ pub fn register_module(register_json: bool) {
             Box::pin(f.boxed_local())
         },
     );
+
+    jsonrpc_register(
+        "nexus_detail",
+        |args: NexusDetailArgs| -> Pin<Box<dyn Future<Output = Result<NexusDetail>>>> {
+            let f = async move {
+                nexus_lookup(&args.name)
+                    .map(|nexus| nexus.detail())
+                    .ok_or_else(|| JsonRpcError {
+                        code: Code::NotFound,
+                        message: format!("nexus {} not found", args.name),
+                    })
+            };
+            Box::pin(f.boxed_local())
+        },
+    );
+
+    // IOPS of the nexus per core, so that a core handling most of the I/O
+    // of a hot nexus can be spotted
+    jsonrpc_register(
+        "nexus_channel_iops",
+        |args: NexusChannelIopsArgs| -> Pin<Box<dyn Future<Output = Result<Vec<NexusChannelIops>>>>> {
+            let f = async move {
+                let not_found = || JsonRpcError {
+                    code: Code::NotFound,
+                    message: format!("nexus {} not found", args.name),
+                };
+                let before = nexus_lookup(&args.name)
+                    .ok_or_else(not_found)?
+                    .channel_stats()
+                    .await;
+                let start = std::time::Instant::now();
+                mayastor_sleep(Duration::from_millis(args.interval_ms))
+                    .await
+                    .ok();
+                // the nexus may have gone in the meantime
+                let after = nexus_lookup(&args.name)
+                    .ok_or_else(not_found)?
+                    .channel_stats()
+                    .await;
+                Ok(NexusChannelIops::from_samples(
+                    &before,
+                    &after,
+                    start.elapsed(),
+                ))
+            };
+            Box::pin(f.boxed_local())
+        },
+    );
+
+    jsonrpc_register(
+        "nexus_controllers",
+        |args: NexusDetailArgs| -> Pin<Box<dyn Future<Output = Result<Vec<NvmfControllerInfo>>>>> {
+            let f = async move {
+                nexus_lookup(&args.name)
+                    .map(|nexus| nexus.nvmf_controllers())
+                    .ok_or_else(|| JsonRpcError {
+                        code: Code::NotFound,
+                        message: format!("nexus {} not found", args.name),
+                    })
+            };
+            Box::pin(f.boxed_local())
+        },
+    );
+
+    // ANA reporting of the share of the nexus, toggled on a paused
+    // subsystem when it is shared already
+    jsonrpc_register(
+        "nexus_ana_reporting_set",
+        |args: NexusAnaReportingArgs| -> Pin<Box<dyn Future<Output = Result<()>>>> {
+            let f = async move {
+                let nexus = nexus_lookup_mut(&args.name).ok_or_else(|| {
+                    JsonRpcError {
+                        code: Code::NotFound,
+                        message: format!("nexus {} not found", args.name),
+                    }
+                })?;
+                nexus.set_ana_reporting(args.enable).await.map_err(|e| {
+                    JsonRpcError {
+                        code: Code::InternalError,
+                        message: e.to_string(),
+                    }
+                })
+            };
+            Box::pin(f.boxed_local())
+        },
+    );
+
+    // verification that the flushes of the nexus reach every healthy child,
+    // a violation being counted and raising an event
+    jsonrpc_register(
+        "nexus_flush_verify_set",
+        |args: NexusFlushVerifyArgs| -> Pin<Box<dyn Future<Output = Result<()>>>> {
+            let f = async move {
+                let nexus = nexus_lookup(&args.name).ok_or_else(|| {
+                    JsonRpcError {
+                        code: Code::NotFound,
+                        message: format!("nexus {} not found", args.name),
+                    }
+                })?;
+                nexus.set_flush_verify(args.enable);
+                Ok(())
+            };
+            Box::pin(f.boxed_local())
+        },
+    );
+
+    jsonrpc_register(
+        "nexus_flush_stats",
+        |args: NexusDetailArgs| -> Pin<Box<dyn Future<Output = Result<NexusFlushStats>>>> {
+            let f = async move {
+                match nexus_lookup(&args.name) {
+                    Some(nexus) => Ok(nexus.flush_stats().await),
+                    None => Err(JsonRpcError {
+                        code: Code::NotFound,
+                        message: format!("nexus {} not found", args.name),
+                    }),
+                }
+            };
+            Box::pin(f.boxed_local())
+        },
+    );
+
+    // selection of the children to read from: in turn, local ones first, or
+    // by their latency
+    jsonrpc_register(
+        "nexus_read_policy_set",
+        |args: NexusReadPolicyArgs| -> Pin<Box<dyn Future<Output = Result<()>>>> {
+            let f = async move {
+                let nexus = nexus_lookup(&args.name).ok_or_else(|| {
+                    JsonRpcError {
+                        code: Code::NotFound,
+                        message: format!("nexus {} not found", args.name),
+                    }
+                })?;
+                nexus.set_read_policy(args.policy);
+                Ok(())
+            };
+            Box::pin(f.boxed_local())
+        },
+    );
+
+    // acknowledgement of the writes once all the children or a quorum of
+    // them completed them
+    jsonrpc_register(
+        "nexus_write_policy_set",
+        |args: NexusWritePolicyArgs| -> Pin<Box<dyn Future<Output = Result<()>>>> {
+            let f = async move {
+                let nexus = nexus_lookup(&args.name).ok_or_else(|| {
+                    JsonRpcError {
+                        code: Code::NotFound,
+                        message: format!("nexus {} not found", args.name),
+                    }
+                })?;
+                nexus.set_write_policy(args.policy).map_err(|e| {
+                    JsonRpcError {
+                        code: Code::InvalidParams,
+                        message: e.to_string(),
+                    }
+                })
+            };
+            Box::pin(f.boxed_local())
+        },
+    );
+
+    // faulting of the children which do not complete an I/O in time, the
+    // I/O being retried on the other children
+    jsonrpc_register(
+        "nexus_child_io_timeout_set",
+        |args: NexusChildIoTimeoutArgs| -> Pin<Box<dyn Future<Output = Result<()>>>> {
+            let f = async move {
+                let nexus = nexus_lookup(&args.name).ok_or_else(|| {
+                    JsonRpcError {
+                        code: Code::NotFound,
+                        message: format!("nexus {} not found", args.name),
+                    }
+                })?;
+                nexus
+                    .set_child_io_timeout(
+                        args.timeout_ms.map(Duration::from_millis),
+                    )
+                    .map_err(|e| JsonRpcError {
+                        code: Code::InvalidParams,
+                        message: e.to_string(),
+                    })
+            };
+            Box::pin(f.boxed_local())
+        },
+    );
+
+    jsonrpc_register(
+        "nexus_child_io_stats",
+        |args: NexusDetailArgs| -> Pin<Box<dyn Future<Output = Result<Vec<NexusChildIoStats>>>>> {
+            let f = async move {
+                match nexus_lookup(&args.name) {
+                    Some(nexus) => Ok(nexus.child_io_stats().await),
+                    None => Err(JsonRpcError {
+                        code: Code::NotFound,
+                        message: format!("nexus {} not found", args.name),
+                    }),
+                }
+            };
+            Box::pin(f.boxed_local())
+        },
+    );
+
+    // errors of the subsystem keep their own code, a nexus which is not
+    // shared over NVMf being an invalid argument
+    fn fence_rpc_error(error: Error) -> JsonRpcError {
+        let code = match &error {
+            Error::FenceHost {
+                source, ..
+            }
+            | Error::UnfenceHost {
+                source, ..
+            } => source.rpc_error_code(),
+            Error::NotSharedNvmf {
+                ..
+            } => Code::InvalidParams,
+            _ => Code::InternalError,
+        };
+        JsonRpcError {
+            code,
+            message: error.to_string(),
+        }
+    }
+
+    // fencing of a host from the share of the nexus, e.g. during the eviction
+    // of its node, so that it is no stale writer
+    jsonrpc_register(
+        "nexus_fence_host",
+        |args: NexusFenceHostArgs| -> Pin<Box<dyn Future<Output = Result<()>>>> {
+            let f = async move {
+                let nexus = nexus_lookup(&args.name).ok_or_else(|| {
+                    JsonRpcError {
+                        code: Code::NotFound,
+                        message: format!("nexus {} not found", args.name),
+                    }
+                })?;
+                nexus.fence_host(&args.host).await.map_err(fence_rpc_error)
+            };
+            Box::pin(f.boxed_local())
+        },
+    );
+
+    jsonrpc_register(
+        "nexus_unfence_host",
+        |args: NexusFenceHostArgs| -> Pin<Box<dyn Future<Output = Result<bool>>>> {
+            let f = async move {
+                let nexus = nexus_lookup(&args.name).ok_or_else(|| {
+                    JsonRpcError {
+                        code: Code::NotFound,
+                        message: format!("nexus {} not found", args.name),
+                    }
+                })?;
+                nexus.unfence_host(&args.host).map_err(fence_rpc_error)
+            };
+            Box::pin(f.boxed_local())
+        },
+    );
+
+    // move of the children which are not healthy to the URIs their replicas
+    // are shared at once their pool was taken over by another node
+    jsonrpc_register(
+        "nexus_takeover_repair",
+        |args: NexusTakeoverArgs| -> Pin<Box<dyn Future<Output = Result<Vec<RetargetedChild>>>>> {
+            let f = async move { Ok(nexus_repair_takeover(&args.uris).await) };
+            Box::pin(f.boxed_local())
+        },
+    );
+
+    // a retention which is invalid or may not replace the one in force is
+    // an invalid argument
+    fn retention_rpc_error(error: Error) -> JsonRpcError {
+        let code = match &error {
+            Error::InvalidRetention {
+                ..
+            }
+            | Error::RetentionInForce {
+                ..
+            } => Code::InvalidParams,
+            _ => Code::InternalError,
+        };
+        JsonRpcError {
+            code,
+            message: error.to_string(),
+        }
+    }
+
+    // write-once retention of the nexus until a given time, e.g. for backup
+    // targets and audit logs
+    jsonrpc_register(
+        "nexus_retention_set",
+        |args: NexusRetentionArgs| -> Pin<Box<dyn Future<Output = Result<()>>>> {
+            let f = async move {
+                let nexus = nexus_lookup(&args.name).ok_or_else(|| {
+                    JsonRpcError {
+                        code: Code::NotFound,
+                        message: format!("nexus {} not found", args.name),
+                    }
+                })?;
+                nexus
+                    .set_retention(
+                        args.mode,
+                        args.retain_until,
+                        args.append_offset,
+                    )
+                    .await
+                    .map_err(retention_rpc_error)
+            };
+            Box::pin(f.boxed_local())
+        },
+    );
+
+    jsonrpc_register(
+        "nexus_retention",
+        |args: NexusDetailArgs| -> Pin<Box<dyn Future<Output = Result<Option<NexusRetention>>>>> {
+            let f = async move {
+                match nexus_lookup(&args.name) {
+                    Some(nexus) => Ok(nexus.retention()),
+                    None => Err(JsonRpcError {
+                        code: Code::NotFound,
+                        message: format!("nexus {} not found", args.name),
+                    }),
+                }
+            };
+            Box::pin(f.boxed_local())
+        },
+    );
+
+    // replacement of a child, e.g. to move a replica to another pool without
+    // losing redundancy: the new child is rebuilt before the replaced one is
+    // removed
+    jsonrpc_register(
+        "nexus_replace_child",
+        |args: NexusReplaceChildArgs| -> Pin<Box<dyn Future<Output = Result<NexusStatus>>>> {
+            let f = async move {
+                let nexus = nexus_lookup_mut(&args.name).ok_or_else(|| {
+                    JsonRpcError {
+                        code: Code::NotFound,
+                        message: format!("nexus {} not found", args.name),
+                    }
+                })?;
+                nexus
+                    .replace_child(&args.src_uri, &args.dst_uri)
+                    .await
+                    .map_err(|e| JsonRpcError {
+                        code: Code::InternalError,
+                        message: e.to_string(),
+                    })
+            };
+            Box::pin(f.boxed_local())
+        },
+    );
+
+    jsonrpc_register(
+        "nexus_child_replacements",
+        |args: NexusDetailArgs| -> Pin<Box<dyn Future<Output = Result<Vec<ChildReplacement>>>>> {
+            let f = async move {
+                match nexus_lookup(&args.name) {
+                    Some(nexus) => Ok(nexus.child_replacements().await),
+                    None => Err(JsonRpcError {
+                        code: Code::NotFound,
+                        message: format!("nexus {} not found", args.name),
+                    }),
+                }
+            };
+            Box::pin(f.boxed_local())
+        },
+    );
 }
 
 /// called during shutdown so that all nexus children are in Destroying state
@@ -6,14 +6,18 @@
 
 use std::{
     cmp::min,
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     convert::TryFrom,
     fmt::{Debug, Display, Formatter},
     marker::PhantomPinned,
     ops::Deref,
     os::raw::c_void,
     pin::Pin,
-    sync::atomic::Ordering,
+    sync::{
+        atomic::{AtomicPtr, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
 use crossbeam::atomic::AtomicCell;
@@ -28,11 +32,18 @@ use super::{
     nexus_lookup_name_uuid,
     DrEvent,
     Error,
+    LaggingChildren,
     NbdDisk,
     NexusBio,
     NexusChannel,
+    NexusChannelStats,
     NexusChild,
+    NexusChildIoStats,
+    NexusFlushStats,
     NexusModule,
+    NexusReadPolicy,
+    NexusRetention,
+    NexusWritePolicy,
     PersistOp,
 };
 
@@ -40,6 +51,8 @@ use crate::{
     bdev::{
         device_destroy,
         nexus::{
+            nexus_bdev_replace::forget_replacements,
+            nexus_channel::ChildIoAccum,
             nexus_io_subsystem::NexusPauseState,
             nexus_persistence::PersistentNexusInfo,
             NexusIoSubsystem,
@@ -50,6 +63,8 @@ use crate::{
     core::{
         partition,
         Bdev,
+        BdevFence,
+        BdevOperation,
         DeviceEventSink,
         IoType,
         Protocol,
@@ -64,13 +79,13 @@ use crate::{
         EventWithMeta,
     },
     rebuild::HistoryRecord,
-    subsys::NvmfSubsystem,
+    subsys::{Config, HostIoCounters, NvmfSubsystem},
 };
 
 use crate::core::{BdevStater, BdevStats, CoreError, IoCompletionStatus};
 use events_api::event::EventAction;
 use spdk_rs::{
-    libspdk::spdk_bdev_notify_blockcnt_change,
+    libspdk::{spdk_bdev_desc, spdk_bdev_notify_blockcnt_change},
     BdevIo,
     BdevOps,
     ChannelTraverseStatus,
@@ -189,6 +204,11 @@ pub struct NexusNvmeParams {
     pub(crate) resv_type: NvmeReservation,
     /// NVMe Preempting policy.
     pub(crate) preempt_policy: NexusNvmePreemption,
+    /// Report ANA on the NVMf share, None for the configured default.
+    pub(crate) ana_reporting: Option<bool>,
+    /// Policy the children to read from are selected by, None for the
+    /// configured default.
+    pub(crate) read_policy: Option<NexusReadPolicy>,
 }
 
 impl Default for NexusNvmeParams {
@@ -200,6 +220,8 @@ impl Default for NexusNvmeParams {
             preempt_key: None,
             resv_type: NvmeReservation::WriteExclusiveAllRegs,
             preempt_policy: NexusNvmePreemption::ArgKey,
+            ana_reporting: None,
+            read_policy: None,
         }
     }
 }
@@ -232,6 +254,14 @@ impl NexusNvmeParams {
     pub fn set_preempt_policy(&mut self, preempt_policy: NexusNvmePreemption) {
         self.preempt_policy = preempt_policy;
     }
+    /// Set whether ANA is reported on the NVMf share.
+    pub fn set_ana_reporting(&mut self, ana_reporting: Option<bool>) {
+        self.ana_reporting = ana_reporting;
+    }
+    /// Set the policy the children to read from are selected by.
+    pub fn set_read_policy(&mut self, read_policy: Option<NexusReadPolicy>) {
+        self.read_policy = read_policy;
+    }
     /// Check if reservations are enabled.
     pub fn reservations_enabled(&self) -> bool {
         self.resv_key != 0
@@ -282,8 +312,28 @@ pub struct Nexus<'n> {
     pub(super) rebuild_history: parking_lot::Mutex<Vec<HistoryRecord>>,
     /// Flag to control shutdown from I/O path.
     pub(crate) shutdown_requested: AtomicCell<bool>,
+    /// Verify that the flushes reach every healthy child.
+    flush_verify: AtomicCell<bool>,
+    /// Policy the children to read from are selected by.
+    read_policy: AtomicCell<NexusReadPolicy>,
+    /// Policy the writes are acknowledged by.
+    write_policy: AtomicCell<NexusWritePolicy>,
+    /// Writes acknowledged and still in flight to the children.
+    pub(super) lagging_children: Arc<LaggingChildren>,
+    /// Time in milliseconds a child may take to complete an I/O, or 0 if the
+    /// child I/Os are not timed.
+    child_io_timeout_ms: AtomicCell<u64>,
+    /// Write-once retention of the nexus.
+    pub(super) retention: AtomicCell<Option<NexusRetention>>,
+    /// End of the data appended under an append-only retention, in blocks.
+    pub(super) append_offset: AtomicU64,
+    /// Tick the retention is enforced until, on the monotonic tick counter.
+    pub(super) retention_deadline: AtomicU64,
     /// Last child I/O error.
     pub(super) last_error: IoCompletionStatus,
+    /// Descriptor the NVMf target submits the I/O of the hosts connected to
+    /// the share of the nexus with, null while none is connected.
+    nvmf_desc: AtomicPtr<spdk_bdev_desc>,
     /// Prevent auto-Unpin.
     _pin: PhantomPinned,
 }
@@ -386,6 +436,9 @@ impl<'n> Nexus<'n> {
         nvme_params: NexusNvmeParams,
         nexus_info_key: Option<String>,
     ) -> spdk_rs::Bdev<Nexus<'n>> {
+        let read_policy = nvme_params
+            .read_policy
+            .unwrap_or_else(|| Config::get().nexus_opts.nexus_read_policy);
         let n = Nexus {
             name: name.to_string(),
             children: Vec::new(),
@@ -405,7 +458,23 @@ impl<'n> Nexus<'n> {
             event_sink: None,
             rebuild_history: parking_lot::Mutex::new(Vec::new()),
             shutdown_requested: AtomicCell::new(false),
+            flush_verify: AtomicCell::new(false),
+            read_policy: AtomicCell::new(read_policy),
+            write_policy: AtomicCell::new(
+                Config::get().nexus_opts.nexus_write_policy,
+            ),
+            lagging_children: Default::default(),
+            child_io_timeout_ms: AtomicCell::new(
+                Config::get()
+                    .nexus_opts
+                    .nexus_child_io_timeout_ms
+                    .unwrap_or_default(),
+            ),
+            retention: AtomicCell::new(None),
+            append_offset: AtomicU64::new(0),
+            retention_deadline: AtomicU64::new(0),
             last_error: IoCompletionStatus::Success,
+            nvmf_desc: AtomicPtr::new(std::ptr::null_mut()),
             _pin: Default::default(),
         };
 
@@ -690,6 +759,150 @@ impl<'n> Nexus<'n> {
         );
     }
 
+    /// Returns the I/O statistics of the I/O channels of the nexus, i.e. of
+    /// the nexus I/O submitted on every core, sorted by core.
+    pub async fn channel_stats(&self) -> Vec<NexusChannelStats> {
+        if !self.has_io_device {
+            return Vec::new();
+        }
+
+        let (sender, recv) = oneshot::channel::<Vec<NexusChannelStats>>();
+
+        self.traverse_io_channels(
+            (sender, Vec::new()),
+            |chan, (_, stats)| -> ChannelTraverseStatus {
+                if chan.is_io_channel() {
+                    stats.push(chan.stats());
+                }
+                ChannelTraverseStatus::Ok
+            },
+            |_, (sender, stats)| {
+                sender.send(stats).ok();
+            },
+        );
+
+        let mut stats = recv.await.unwrap_or_default();
+        stats.sort_by_key(|s| s.core);
+        stats
+    }
+
+    /// Returns the flush statistics of the nexus, summed over its I/O
+    /// channels.
+    pub async fn flush_stats(&self) -> NexusFlushStats {
+        NexusFlushStats::from_stats(
+            self.flush_verify(),
+            &self.channel_stats().await,
+        )
+    }
+
+    /// Returns the descriptor the NVMf target submits the I/O of the hosts
+    /// with, null while none is connected.
+    pub(super) fn nvmf_desc(&self) -> *mut spdk_bdev_desc {
+        self.nvmf_desc.load(Ordering::Relaxed)
+    }
+
+    /// Sets the descriptor the NVMf target submits the I/O of the hosts with,
+    /// so that their reads and writes are counted by host.
+    pub(crate) fn set_nvmf_desc(&self, desc: *mut spdk_bdev_desc) {
+        self.nvmf_desc.store(desc, Ordering::Relaxed);
+    }
+
+    /// Stops counting the I/O of the hosts by the given descriptor, before
+    /// it is closed.
+    pub(crate) fn clear_nvmf_desc(&self, desc: *mut spdk_bdev_desc) {
+        self.nvmf_desc
+            .compare_exchange(
+                desc,
+                std::ptr::null_mut(),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .ok();
+    }
+
+    /// Returns the reads and writes submitted by the NVMf hosts of the
+    /// nexus, summed over its I/O channels, by ID of their controllers.
+    pub async fn host_io_stats(&self) -> HashMap<u16, HostIoCounters> {
+        if !self.has_io_device {
+            return HashMap::new();
+        }
+
+        let (sender, recv) = oneshot::channel::<HashMap<u16, HostIoCounters>>();
+
+        self.traverse_io_channels(
+            (sender, HashMap::new()),
+            |chan, (_, stats)| -> ChannelTraverseStatus {
+                for (cntlid, counters) in chan.host_stats() {
+                    *stats.entry(*cntlid).or_default() += *counters;
+                }
+                ChannelTraverseStatus::Ok
+            },
+            |_, (sender, stats)| {
+                sender.send(stats).ok();
+            },
+        );
+
+        recv.await.unwrap_or_default()
+    }
+
+    /// Forgets the reads and writes of the controller of a host which
+    /// disconnected from the share of the nexus, on all its I/O channels.
+    pub(crate) fn forget_host_io_stats(&self, cntlid: u16) {
+        if !self.has_io_device {
+            return;
+        }
+
+        self.traverse_io_channels(
+            (),
+            |chan, _| -> ChannelTraverseStatus {
+                chan.forget_host_stats(cntlid);
+                ChannelTraverseStatus::Ok
+            },
+            |_, _| {},
+        );
+    }
+
+    /// Returns the I/O statistics of the children of the nexus, summed over
+    /// its I/O channels.
+    pub async fn child_io_stats(&self) -> Vec<NexusChildIoStats> {
+        let mut accums = HashMap::<String, ChildIoAccum>::new();
+
+        if self.has_io_device {
+            let (sender, recv) =
+                oneshot::channel::<HashMap<String, ChildIoAccum>>();
+
+            self.traverse_io_channels(
+                (sender, HashMap::new()),
+                |chan, (_, accums)| -> ChannelTraverseStatus {
+                    if chan.is_io_channel() {
+                        for (name, accum) in chan.child_stats() {
+                            accums
+                                .entry(name)
+                                .or_insert_with(ChildIoAccum::default)
+                                .merge(&accum);
+                        }
+                    }
+                    ChannelTraverseStatus::Ok
+                },
+                |_, (sender, accums)| {
+                    sender.send(accums).ok();
+                },
+            );
+
+            accums = recv.await.unwrap_or_default();
+        }
+
+        self.children_iter()
+            .map(|child| {
+                let accum = child
+                    .get_device_name()
+                    .and_then(|name| accums.get(&name).cloned())
+                    .unwrap_or_default();
+                NexusChildIoStats::new(child.uri().to_string(), &accum)
+            })
+            .collect()
+    }
+
     /// Configure nexus's block device to match parameters of the child devices.
     async fn setup_nexus_bdev(
         mut self: Pin<&mut Self>,
@@ -828,6 +1041,7 @@ impl<'n> Nexus<'n> {
         assert_eq!(*nex.state.lock(), NexusState::Init);
 
         info!("{:?}: registering nexus bdev...", nex);
+        let start = std::time::Instant::now();
 
         nex.as_mut().setup_nexus_bdev(false).await?;
 
@@ -874,7 +1088,13 @@ impl<'n> Nexus<'n> {
         }
 
         nex.as_mut().set_state(NexusState::Open);
-        info!("{:?}: nexus bdev registered successfully", nex);
+        info!(
+            volume = %nex.uuid(),
+            op = "create",
+            duration_ms = start.elapsed().as_millis() as u64,
+            "{:?}: nexus bdev registered successfully",
+            nex
+        );
 
         Ok(())
     }
@@ -893,8 +1113,15 @@ impl<'n> Nexus<'n> {
         sigterm: bool,
     ) -> Result<(), Error> {
         info!("{:?}: destroying nexus...", self);
+        let start = std::time::Instant::now();
 
-        self.as_mut().unshare_nexus().await?;
+        // the unshare below is part of the destroy
+        let fence = BdevFence::acquire(&self.name, BdevOperation::Destroy)
+            .context(nexus_err::NexusDestroyBusy {
+                name: self.name.clone(),
+            })?;
+
+        self.as_mut().unshare_nexus_within(Some(&fence)).await?;
 
         // wait for all rebuild jobs to be cancelled before proceeding with the
         // destruction of the nexus
@@ -903,6 +1130,9 @@ impl<'n> Nexus<'n> {
             self.as_mut().cancel_rebuild_jobs(&child).await;
         }
 
+        // the children are closed once the writes acknowledged already
+        // completed on them
+        self.lagging_children.drain().await;
         self.close_children().await;
 
         // Persist the fact that the nexus destruction has completed.
@@ -919,12 +1149,19 @@ impl<'n> Nexus<'n> {
 
         unsafe {
             let name = self.name.clone();
+            let volume = self.uuid();
+            forget_replacements(&name);
 
             // After calling unregister_bdev_async(), Nexus is gone.
             let evt = Event::event(self.deref(), EventAction::Delete);
             match self.as_mut().bdev_mut().unregister_bdev_async().await {
                 Ok(_) => {
-                    info!("Nexus '{name}': nexus destroyed ok");
+                    info!(
+                        volume = %volume,
+                        op = "destroy",
+                        duration_ms = start.elapsed().as_millis() as u64,
+                        "Nexus '{name}': nexus destroyed ok"
+                    );
                     evt.generate();
                     Ok(())
                 }
@@ -943,6 +1180,9 @@ impl<'n> Nexus<'n> {
 
     /// Resize the nexus as part of volume resize workflow. The underlying
     /// replicas are already resized before nexus resize is called.
+    /// The nexus is paused meanwhile: resuming its NVMf subsystem makes the
+    /// hosts get a namespace attribute changed notice, upon which they
+    /// read the new size of the namespace.
     pub async fn resize(
         mut self: Pin<&mut Self>,
         resize_to: u64,
@@ -955,18 +1195,30 @@ impl<'n> Nexus<'n> {
         if current_size == resize_to {
             return Ok(());
         }
+        if resize_to < current_size {
+            return Err(Error::InvalidResize {
+                name: self.name.clone(),
+                reason: format!(
+                    "cannot shrink from {current_size} to {resize_to} bytes"
+                ),
+            });
+        }
         info!(
             "Resizing nexus {} from {current_size} to {resize_to}",
             self.uuid()
         );
+
+        self.as_mut().pause().await?;
         unsafe { self.as_mut().set_req_size(resize_to) };
         let ret = self.as_mut().setup_nexus_bdev(true).await;
         if ret.is_err() {
             // Reset the req_size back to original in case of failure.
             unsafe { self.as_mut().set_req_size(current_size) };
         }
+        self.as_mut().resume().await?;
+        ret?;
 
-        ret
+        self.persist(PersistOp::Resize).await
     }
 
     /// Returns a mutable reference to Nexus I/O.
@@ -1123,6 +1375,8 @@ impl<'n> Nexus<'n> {
 
     /// Suspend any incoming IO to the bdev pausing the controller allows us to
     /// handle internal events and which is a protocol feature.
+    /// The writes acknowledged already but still in flight to the children
+    /// under the quorum write policy are waited for as well.
     /// In case concurrent pause requests take place, the other callers
     /// will wait till the nexus is resumed and will continue execution
     /// with the nexus paused once they are awakened via resume().
@@ -1137,6 +1391,9 @@ impl<'n> Nexus<'n> {
         .generate();
         let start_time = std::time::Instant::now();
         let result = self.as_mut().io_subsystem_mut().suspend().await;
+        if result.is_ok() {
+            self.lagging_children.drain().await;
+        }
         match result {
             Ok(_) => {
                 EventWithMeta::event(
@@ -1199,6 +1456,155 @@ impl<'n> Nexus<'n> {
         })
     }
 
+    /// check if ANA is reported on the NVMf share of the nexus
+    pub fn ana_reporting(&self) -> bool {
+        self.nvme_params
+            .ana_reporting
+            .unwrap_or_else(|| Config::get().nexus_opts.nvmf_ana_reporting)
+    }
+
+    /// enable or disable ANA reporting on the NVMf share of the nexus, at
+    /// once if it is shared already; the connected hosts are disconnected
+    /// and see the change once they reconnect
+    pub async fn set_ana_reporting(
+        mut self: Pin<&mut Self>,
+        enable: bool,
+    ) -> Result<(), Error> {
+        unsafe {
+            self.as_mut().get_unchecked_mut().nvme_params.ana_reporting =
+                Some(enable);
+        }
+
+        if let Some(Protocol::Nvmf) = self.shared() {
+            if let Some(subsystem) = NvmfSubsystem::nqn_lookup(&self.name) {
+                subsystem.update_ana_reporting(enable).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// fence a host from the NVMf share of the nexus, e.g. when its node is
+    /// evicted, so that it can no longer write to the nexus
+    pub async fn fence_host(&self, host: &str) -> Result<(), Error> {
+        match self.nvmf_subsystem() {
+            Some(subsystem) => {
+                subsystem
+                    .fence_host(host)
+                    .await
+                    .context(nexus_err::FenceHost {
+                        name: self.name.clone(),
+                    })
+            }
+            None => Err(Error::NotSharedNvmf {
+                name: self.name.clone(),
+            }),
+        }
+    }
+
+    /// unfence a host from the NVMf share of the nexus, returning whether it
+    /// was fenced
+    pub fn unfence_host(&self, host: &str) -> Result<bool, Error> {
+        match self.nvmf_subsystem() {
+            Some(subsystem) => {
+                subsystem
+                    .unfence_host(host)
+                    .context(nexus_err::UnfenceHost {
+                        name: self.name.clone(),
+                    })
+            }
+            None => Err(Error::NotSharedNvmf {
+                name: self.name.clone(),
+            }),
+        }
+    }
+
+    /// the subsystem of the nexus, when shared over NVMf
+    fn nvmf_subsystem(&self) -> Option<NvmfSubsystem> {
+        match self.shared() {
+            Some(Protocol::Nvmf) => NvmfSubsystem::nqn_lookup(&self.name),
+            _ => None,
+        }
+    }
+
+    /// check if the flushes of the nexus are verified to reach every healthy
+    /// child
+    pub fn flush_verify(&self) -> bool {
+        self.flush_verify.load()
+    }
+
+    /// enable or disable the verification of the flushes of the nexus,
+    /// which costs a walk of the children on every flush completion
+    pub fn set_flush_verify(&self, enable: bool) {
+        self.flush_verify.store(enable);
+    }
+
+    /// get the policy the children to read from are selected by
+    pub fn read_policy(&self) -> NexusReadPolicy {
+        self.read_policy.load()
+    }
+
+    /// set the policy the children to read from are selected by, which the
+    /// channels apply from their next read on
+    pub fn set_read_policy(&self, policy: NexusReadPolicy) {
+        info!("{self:?}: read policy set to {policy:?}");
+        self.read_policy.store(policy);
+    }
+
+    /// get the policy the writes are acknowledged by
+    pub fn write_policy(&self) -> NexusWritePolicy {
+        self.write_policy.load()
+    }
+
+    /// set the policy the writes are acknowledged by, which the channels
+    /// apply from their next write on
+    pub fn set_write_policy(
+        &self,
+        policy: NexusWritePolicy,
+    ) -> Result<(), Error> {
+        if policy == NexusWritePolicy::Quorum(0) {
+            return Err(Error::InvalidArguments {
+                name: self.name.clone(),
+                args: "the write quorum must be at least 1".to_string(),
+            });
+        }
+        info!("{self:?}: write policy set to {policy:?}");
+        self.write_policy.store(policy);
+        Ok(())
+    }
+
+    /// get the time a child may take to complete an I/O, if the child I/Os
+    /// are timed
+    pub fn child_io_timeout(&self) -> Option<Duration> {
+        match self.child_io_timeout_ms.load() {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        }
+    }
+
+    /// set the time a child may take to complete an I/O, past which it is
+    /// faulted and the I/O retried on the other children, or stop timing
+    /// the child I/Os
+    pub fn set_child_io_timeout(
+        &self,
+        timeout: Option<Duration>,
+    ) -> Result<(), Error> {
+        let ms = timeout.map_or(0, |t| t.as_millis() as u64);
+        if timeout.is_some() && ms == 0 {
+            return Err(Error::InvalidArguments {
+                name: self.name.clone(),
+                args: "the child I/O timeout must be at least 1 ms".to_string(),
+            });
+        }
+        info!("{self:?}: child I/O timeout set to {timeout:?}");
+        self.child_io_timeout_ms.store(ms);
+        Ok(())
+    }
+
+    /// get the writes acknowledged and still in flight to the children
+    pub(crate) fn lagging_children(&self) -> &LaggingChildren {
+        &self.lagging_children
+    }
+
     /// determine if any of the children do not support the requested
     /// io type. Break the loop on first occurrence.
     /// TODO: optionally add this check during nexus creation
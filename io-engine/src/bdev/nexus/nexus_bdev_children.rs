@@ -1096,6 +1096,14 @@ impl<'n> Nexus<'n> {
         })
         .await;
 
+        // the device is not read from anymore, whether lagging or not
+        if let Some(device) = self
+            .lookup_child_by_device(dev)
+            .and_then(|c| c.get_device().ok())
+        {
+            self.lagging_children().forget(&device.uuid());
+        }
+
         debug!("{self:?}: '{dev}' detached from all I/O channels");
     }
 
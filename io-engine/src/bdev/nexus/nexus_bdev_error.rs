@@ -46,6 +46,18 @@ pub enum Error {
     NotShared { name: String },
     #[snafu(display("The nexus {} has not been shared over NVMf", name))]
     NotSharedNvmf { name: String },
+    #[snafu(display(
+        "Failed to fence a host from nexus {}: {}",
+        name,
+        source
+    ))]
+    FenceHost { source: NvmfError, name: String },
+    #[snafu(display(
+        "Failed to unfence a host from nexus {}: {}",
+        name,
+        source
+    ))]
+    UnfenceHost { source: NvmfError, name: String },
     #[snafu(display("Failed to share nexus over NBD {}", name))]
     ShareNbdNexus { source: NbdError, name: String },
     #[snafu(display("Failed to share nvmf nexus {}", name))]
@@ -186,8 +198,12 @@ pub enum Error {
     NexusCreate { name: String, reason: String },
     #[snafu(display("Failed to destroy nexus {}", name))]
     NexusDestroy { name: String },
+    #[snafu(display("Failed to destroy nexus {}: {}", name, source))]
+    NexusDestroyBusy { source: CoreError, name: String },
     #[snafu(display("Failed to resize nexus {}", name))]
     NexusResize { source: Errno, name: String },
+    #[snafu(display("Invalid resize of nexus {}: {}", name, reason))]
+    InvalidResize { name: String, reason: String },
     #[snafu(display(
         "Child {} of nexus {} is not degraded but {}",
         child,
@@ -222,6 +238,14 @@ pub enum Error {
     UpdateShareProperties { source: CoreError, name: String },
     #[snafu(display("failed to save nexus state {}", name))]
     SaveStateFailed { source: StoreError, name: String },
+    #[snafu(display("Invalid retention for nexus {}: {}", name, reason))]
+    InvalidRetention { name: String, reason: String },
+    #[snafu(display(
+        "Retention of nexus {} is in force until {}",
+        name,
+        retain_until
+    ))]
+    RetentionInForce { name: String, retain_until: u64 },
 }
 
 impl From<NvmfError> for Error {
@@ -256,6 +280,13 @@ impl From<Error> for tonic::Status {
             Error::NotSharedNvmf {
                 ..
             } => Status::invalid_argument(e.to_string()),
+            Error::FenceHost {
+                source:
+                    NvmfError::InvalidFence {
+                        ..
+                    },
+                ..
+            } => Status::failed_precondition(e.to_string()),
             Error::CreateChild {
                 ..
             } => Status::invalid_argument(e.to_string()),
@@ -274,6 +305,9 @@ impl From<Error> for tonic::Status {
             Error::OperationNotAllowed {
                 ..
             } => Status::failed_precondition(e.to_string()),
+            Error::NexusDestroyBusy {
+                ..
+            } => Status::aborted(e.to_string()),
             Error::RemoveLastChild {
                 ..
             } => Status::failed_precondition(e.to_string()),
@@ -292,6 +326,9 @@ impl From<Error> for tonic::Status {
             Error::NexusResize {
                 ..
             } => Status::failed_precondition(e.to_string()),
+            Error::InvalidResize {
+                ..
+            } => Status::invalid_argument(e.to_string()),
             Error::NexusNotFound {
                 ..
             } => Status::not_found(e.to_string()),
@@ -304,6 +341,12 @@ impl From<Error> for tonic::Status {
             Error::InvalidArguments {
                 ..
             } => Status::invalid_argument(e.to_string()),
+            Error::InvalidRetention {
+                ..
+            } => Status::invalid_argument(e.to_string()),
+            Error::RetentionInForce {
+                ..
+            } => Status::failed_precondition(e.to_string()),
             e => Status::new(Code::Internal, e.verbose()),
         }
     }
@@ -86,6 +86,9 @@ impl<'n> Nexus<'n> {
         let name = self.name.clone();
         info!("{self:?}: start rebuild request for {child_uri}");
 
+        // The source must have completed the writes acknowledged already.
+        self.lagging_children().drain().await;
+
         // Find a healthy child to rebuild from.
         let Some(src_child_uri) = self.find_src_replica(child_uri) else {
             return Err(Error::NoRebuildSource {
@@ -517,6 +520,8 @@ impl<'n> Nexus<'n> {
 
         self.reconfigure(DrEvent::ChildRebuild).await;
 
+        self.on_replacement_rebuilt(child_uri, job_state, c.is_healthy());
+
         Ok(())
     }
 
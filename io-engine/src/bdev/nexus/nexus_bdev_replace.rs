@@ -0,0 +1,210 @@
+//! Implements the replacement of a child of a nexus.
+//!
+//! A child is replaced by adding the new child, rebuilding it from the
+//! healthy children, and only then removing the replaced child, so that the
+//! nexus never runs with fewer healthy children than it had, e.g. when a
+//! replica moves to another pool. Should the rebuild fail, the replaced child
+//! is kept.
+use std::{collections::HashMap, pin::Pin, sync::Mutex};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use super::{nexus_lookup_mut, Error, Nexus, NexusStatus};
+use crate::{core::Reactors, rebuild::RebuildState};
+
+/// State of a child replacement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplacementState {
+    /// The new child is being rebuilt.
+    Rebuilding,
+    /// The new child is rebuilt, and the replaced child removed.
+    Completed,
+    /// The new child could not be rebuilt, the replaced child is kept.
+    Failed,
+}
+
+/// Replacement of a child of a nexus.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChildReplacement {
+    /// URI of the replaced child.
+    pub src_uri: String,
+    /// URI of the new child.
+    pub dst_uri: String,
+    pub state: ReplacementState,
+    /// Rebuild progress of the new child in percent, while rebuilding.
+    pub progress: Option<u32>,
+    /// Time the replacement started at, in RFC 3339 format.
+    pub started_at: String,
+    /// Why the replacement failed.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Replacement {
+    src_uri: String,
+    dst_uri: String,
+    state: ReplacementState,
+    started_at: DateTime<Utc>,
+    error: Option<String>,
+}
+
+/// Replacements of the children, by nexus name.
+static REPLACEMENTS: Lazy<Mutex<HashMap<String, Vec<Replacement>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Forgets the replacements of a nexus which is being destroyed.
+pub(crate) fn forget_replacements(name: &str) {
+    REPLACEMENTS.lock().unwrap().remove(name);
+}
+
+impl<'n> Nexus<'n> {
+    /// Replaces a healthy child with a new one, which is added and rebuilt
+    /// before the replaced child is removed.
+    pub async fn replace_child(
+        mut self: Pin<&mut Self>,
+        src_uri: &str,
+        dst_uri: &str,
+    ) -> Result<NexusStatus, Error> {
+        info!("{self:?}: replace child request: '{src_uri}' -> '{dst_uri}'");
+
+        let name = self.name.clone();
+        let src = self.child(src_uri)?;
+        if !src.is_healthy() {
+            return Err(Error::OperationNotAllowed {
+                reason: format!("child '{src_uri}' to replace is not healthy"),
+            });
+        }
+        if self.contains_child_uri(dst_uri) {
+            return Err(Error::ChildAlreadyExists {
+                child: dst_uri.to_owned(),
+                name,
+            });
+        }
+        if self.replacement(src_uri).is_some() {
+            return Err(Error::OperationNotAllowed {
+                reason: format!("child '{src_uri}' is already being replaced"),
+            });
+        }
+
+        let status = self.as_mut().add_child(dst_uri, false).await?;
+
+        let mut replacement = Replacement {
+            src_uri: src_uri.to_owned(),
+            dst_uri: dst_uri.to_owned(),
+            state: ReplacementState::Rebuilding,
+            started_at: Utc::now(),
+            error: None,
+        };
+        if self.rebuild_job(dst_uri).is_err() {
+            replacement.state = ReplacementState::Failed;
+            replacement.error = Some("rebuild failed to start".to_string());
+        }
+        let mut all = REPLACEMENTS.lock().unwrap();
+        let replacements = all.entry(name).or_default();
+        replacements.retain(|r| r.src_uri != src_uri);
+        replacements.push(replacement);
+
+        Ok(status)
+    }
+
+    /// Returns the ongoing replacement of a child, if any.
+    fn replacement(&self, src_uri: &str) -> Option<Replacement> {
+        REPLACEMENTS
+            .lock()
+            .unwrap()
+            .get(&self.name)?
+            .iter()
+            .find(|r| {
+                r.src_uri == src_uri && r.state == ReplacementState::Rebuilding
+            })
+            .cloned()
+    }
+
+    /// Returns the replacements of the children of the nexus.
+    pub async fn child_replacements(&self) -> Vec<ChildReplacement> {
+        let replacements = REPLACEMENTS
+            .lock()
+            .unwrap()
+            .get(&self.name)
+            .cloned()
+            .unwrap_or_default();
+
+        let mut result = Vec::with_capacity(replacements.len());
+        for r in replacements {
+            let progress = match r.state {
+                ReplacementState::Rebuilding => {
+                    self.rebuild_progress(&r.dst_uri).await.ok()
+                }
+                ReplacementState::Completed => Some(100),
+                ReplacementState::Failed => None,
+            };
+            result.push(ChildReplacement {
+                src_uri: r.src_uri,
+                dst_uri: r.dst_uri,
+                state: r.state,
+                progress,
+                started_at: r
+                    .started_at
+                    .to_rfc3339_opts(SecondsFormat::Secs, true),
+                error: r.error,
+            });
+        }
+        result
+    }
+
+    /// Called once the rebuild of a child is done, to complete its
+    /// replacement if it replaces another child.
+    pub(super) fn on_replacement_rebuilt(
+        &self,
+        dst_uri: &str,
+        state: RebuildState,
+        healthy: bool,
+    ) {
+        let src_uri = {
+            let mut all = REPLACEMENTS.lock().unwrap();
+            let Some(r) = all.get_mut(&self.name).and_then(|r| {
+                r.iter_mut().find(|r| {
+                    r.dst_uri == dst_uri
+                        && r.state == ReplacementState::Rebuilding
+                })
+            }) else {
+                return;
+            };
+            if state != RebuildState::Completed || !healthy {
+                warn!(
+                    "{self:?}: replacement of '{src}' failed, keeping it",
+                    src = r.src_uri
+                );
+                r.state = ReplacementState::Failed;
+                r.error = Some(format!("rebuild of '{dst_uri}': {state:?}"));
+                return;
+            }
+            r.state = ReplacementState::Completed;
+            r.src_uri.clone()
+        };
+
+        // the child cannot be removed while its rebuild is being processed
+        let name = self.name.clone();
+        Reactors::master().send_future(async move {
+            let Some(nexus) = nexus_lookup_mut(&name) else {
+                return;
+            };
+            match nexus.remove_child(&src_uri).await {
+                Ok(_) => info!("{name}: replaced child '{src_uri}' removed"),
+                Err(error) => {
+                    error!("{name}: failed to remove replaced child: {error}");
+                    let mut all = REPLACEMENTS.lock().unwrap();
+                    if let Some(r) = all.get_mut(&name).and_then(|r| {
+                        r.iter_mut().find(|r| r.src_uri == src_uri)
+                    }) {
+                        r.state = ReplacementState::Failed;
+                        r.error = Some(error.to_string());
+                    }
+                }
+            }
+        });
+    }
+}
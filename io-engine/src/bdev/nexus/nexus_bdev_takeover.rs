@@ -0,0 +1,90 @@
+//! Implements the repair of the nexuses after a pool takeover.
+//!
+//! Once the pool of a failed node is taken over by another node, its
+//! replicas are shared under the same NQNs at the address of that node,
+//! while the children of the nexuses still point at the failed node. Given
+//! the URIs the replicas are now shared at, every child of a nexus of this
+//! node which is not healthy and has the NQN of one of them is replaced with
+//! a child at the new URI, rebuilt from the healthy children. A healthy child
+//! still reaches its replica and is left alone.
+use std::pin::Pin;
+
+use serde::Serialize;
+
+use super::{nexus_iter, nexus_lookup_mut, Nexus};
+
+/// A child of a nexus moved to the URI its replica is shared at after a
+/// takeover.
+#[derive(Debug, Clone, Serialize)]
+pub struct RetargetedChild {
+    pub nexus: String,
+    /// URI of the replaced child.
+    pub src_uri: String,
+    /// URI of the new child.
+    pub dst_uri: String,
+    /// Why the child could not be moved.
+    pub error: Option<String>,
+}
+
+/// Returns the NQN of an NVMf URI.
+fn uri_nqn(uri: &str) -> Option<String> {
+    let url = url::Url::parse(uri).ok()?;
+    (url.scheme() == "nvmf")
+        .then(|| url.path().trim_start_matches('/').to_string())
+}
+
+/// Moves the children of all the nexuses which are not healthy to the URIs
+/// their replicas are shared at after a pool takeover.
+pub async fn nexus_repair_takeover(uris: &[String]) -> Vec<RetargetedChild> {
+    let names = nexus_iter().map(|n| n.name.clone()).collect::<Vec<_>>();
+    let mut retargeted = Vec::new();
+    for name in names {
+        if let Some(nexus) = nexus_lookup_mut(&name) {
+            retargeted.extend(nexus.repair_takeover(uris).await);
+        }
+    }
+    retargeted
+}
+
+impl<'n> Nexus<'n> {
+    /// Moves the children of the nexus which are not healthy to the URIs
+    /// their replicas are shared at after a pool takeover.
+    pub async fn repair_takeover(
+        mut self: Pin<&mut Self>,
+        uris: &[String],
+    ) -> Vec<RetargetedChild> {
+        let moves = self
+            .children_iter()
+            .filter(|c| !c.is_healthy())
+            .filter_map(|c| {
+                let nqn = uri_nqn(c.uri())?;
+                let dst =
+                    uris.iter().find(|u| uri_nqn(u).as_ref() == Some(&nqn))?;
+                (dst != c.uri() && !self.contains_child_uri(dst))
+                    .then(|| (c.uri().to_string(), dst.clone()))
+            })
+            .collect::<Vec<_>>();
+
+        let mut retargeted = Vec::with_capacity(moves.len());
+        for (src_uri, dst_uri) in moves {
+            info!("{self:?}: moving child '{src_uri}' to '{dst_uri}'");
+            let result = match self.as_mut().add_child(&dst_uri, false).await {
+                Ok(_) => self.as_mut().remove_child(&src_uri).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = &result {
+                error!(
+                    "{self:?}: failed to move child '{src_uri}' to \
+                    '{dst_uri}': {e}"
+                );
+            }
+            retargeted.push(RetargetedChild {
+                nexus: self.name.clone(),
+                src_uri,
+                dst_uri,
+                error: result.err().map(|e| e.to_string()),
+            });
+        }
+        retargeted
+    }
+}
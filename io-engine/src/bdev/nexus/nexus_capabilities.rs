@@ -0,0 +1,126 @@
+//! Capabilities negotiated between a nexus and its children.
+//!
+//! A nexus only advertises the features that all of its children support,
+//! so a single child on a less capable device silently downgrades the whole
+//! nexus. The detail reported here lists the capabilities of every child
+//! along with the effective ones of the nexus, and names the downgrades.
+
+use serde::Serialize;
+use spdk_rs::libspdk::spdk_bdev_get_dif_type;
+
+use super::{Nexus, NexusChild, NexusStatus};
+use crate::core::{BlockDevice, IoType};
+
+/// Capabilities of a block device.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeviceCapabilities {
+    /// Maximum size of a single I/O in bytes, `None` when unlimited.
+    pub max_transfer_size: Option<u64>,
+    pub write_zeroes: bool,
+    pub unmap: bool,
+    /// Protection information type, 0 meaning no protection information.
+    pub pi_type: u32,
+}
+
+impl DeviceCapabilities {
+    /// Capabilities of the given device.
+    pub fn of(device: &dyn BlockDevice) -> Self {
+        Self {
+            max_transfer_size: device.max_transfer_size(),
+            write_zeroes: device.io_type_supported(IoType::WriteZeros),
+            unmap: device.io_type_supported(IoType::Unmap),
+            pi_type: device.pi_type(),
+        }
+    }
+}
+
+/// Capabilities of a nexus child.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChildCapabilities {
+    pub uri: String,
+    pub state: String,
+    /// Transport the child device is reached over.
+    pub transport: Option<String>,
+    /// Capabilities of the child device, `None` when it is not open.
+    pub capabilities: Option<DeviceCapabilities>,
+}
+
+impl From<&NexusChild<'_>> for ChildCapabilities {
+    fn from(child: &NexusChild<'_>) -> Self {
+        let device = child.get_device().ok();
+        Self {
+            uri: child.uri().to_string(),
+            state: child.state().to_string(),
+            transport: device.map(|d| d.transport()),
+            capabilities: device.map(DeviceCapabilities::of),
+        }
+    }
+}
+
+/// Capabilities of a nexus and of its children.
+#[derive(Debug, Clone, Serialize)]
+pub struct NexusDetail {
+    pub name: String,
+    pub uuid: String,
+    pub status: NexusStatus,
+    /// Capabilities the nexus advertises, derived from its open children.
+    pub capabilities: DeviceCapabilities,
+    pub children: Vec<ChildCapabilities>,
+    /// Capabilities of children which the nexus does not advertise.
+    pub downgrades: Vec<String>,
+}
+
+impl<'n> Nexus<'n> {
+    /// Returns the capabilities of the nexus and of its children.
+    pub fn detail(&self) -> NexusDetail {
+        let children = self
+            .children_iter()
+            .map(ChildCapabilities::from)
+            .collect::<Vec<_>>();
+
+        let open = children
+            .iter()
+            .filter_map(|c| c.capabilities.as_ref().map(|caps| (c, caps)))
+            .collect::<Vec<_>>();
+        let capabilities = DeviceCapabilities {
+            max_transfer_size: open
+                .iter()
+                .filter_map(|(_, caps)| caps.max_transfer_size)
+                .min(),
+            write_zeroes: open.iter().all(|(_, caps)| caps.write_zeroes),
+            unmap: open.iter().all(|(_, caps)| caps.unmap),
+            pi_type: unsafe {
+                spdk_bdev_get_dif_type(self.bdev().unsafe_inner_ptr()) as u32
+            },
+        };
+
+        let mut downgrades = vec![];
+        for (child, caps) in &open {
+            if caps.write_zeroes && !capabilities.write_zeroes {
+                downgrades.push(format!(
+                    "write zeroes of {} not advertised",
+                    child.uri
+                ));
+            }
+            if caps.unmap && !capabilities.unmap {
+                downgrades
+                    .push(format!("unmap of {} not advertised", child.uri));
+            }
+            if caps.pi_type != capabilities.pi_type {
+                downgrades.push(format!(
+                    "protection information type {} of {} not advertised",
+                    caps.pi_type, child.uri
+                ));
+            }
+        }
+
+        NexusDetail {
+            name: self.name.clone(),
+            uuid: self.uuid().to_string(),
+            status: self.status(),
+            capabilities,
+            children,
+            downgrades,
+        }
+    }
+}
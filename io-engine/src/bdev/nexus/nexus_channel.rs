@@ -1,31 +1,69 @@
 //!
 //! IO is driven by means of so called channels.
 use std::{
-    cell::UnsafeCell,
+    cell::{RefCell, UnsafeCell},
+    collections::{BTreeMap, HashMap},
     fmt::{Debug, Display, Formatter},
     pin::Pin,
-    sync::atomic::Ordering,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 
-use super::{FaultReason, IOLogChannel, Nexus, NexusBio};
+use futures::channel::oneshot;
 
-use crate::core::{BlockDeviceHandle, CoreError, Cores};
-use spdk_rs::Thread;
+use super::{FaultReason, IOLogChannel, Nexus, NexusBio, TimedChildIo};
+
+use crate::{
+    bdev::device_lookup,
+    core::{BlockDevice, BlockDeviceHandle, CoreError, Cores, IoType},
+    subsys::{Config, HostIoCounters},
+};
+use spdk_rs::{
+    libspdk::{spdk_get_ticks, spdk_get_ticks_hz},
+    Poller,
+    PollerBuilder,
+    Thread,
+};
+use uuid::Uuid;
 
 /// I/O channel, per core.
 #[repr(C)]
 pub struct NexusChannel<'n> {
     writers: Vec<Box<dyn BlockDeviceHandle>>,
     readers: Vec<Box<dyn BlockDeviceHandle>>,
+    /// Slots of the devices of the readers, in the same order.
+    reader_slots: Vec<usize>,
     detached: Vec<Box<dyn BlockDeviceHandle>>,
     io_logs: Vec<IOLogChannel>,
     previous_reader: UnsafeCell<usize>,
+    /// Number of the reads selected a reader for by the adaptive policy.
+    adaptive_reads: UnsafeCell<u64>,
     fail_fast: u32,
     io_mode: IoMode,
     frozen_ios: Vec<NexusBio<'n>>,
+    /// Writes deferred until the laggard writes they overlap complete.
+    deferred_writes: Vec<NexusBio<'n>>,
     nexus: Pin<&'n mut Nexus<'n>>,
     core: u32,
     is_io_chan: bool,
+    stats: NexusChannelStats,
+    /// Slots of the child devices, with their I/O statistics. A slot is
+    /// never removed, so that its index identifies the device on this
+    /// channel.
+    child_slots: Vec<ChildSlot<'n>>,
+    /// Reads and writes submitted by the NVMf hosts of the nexus, by ID of
+    /// their controllers.
+    host_stats: Vec<(u16, HostIoCounters)>,
+    /// Child writes in flight after their nexus writes were acknowledged
+    /// under the quorum write policy.
+    laggards: Rc<RefCell<LaggardWrites>>,
+    /// Poller checking the laggard writes and the child I/O timeouts,
+    /// started with the first quorum write or timed child I/O.
+    poller: Option<Poller<'n, ChannelPollerCtx<'n>>>,
 }
 
 impl<'n> Debug for NexusChannel<'n> {
@@ -45,6 +83,530 @@ impl<'n> Debug for NexusChannel<'n> {
     }
 }
 
+/// I/O statistics of a nexus channel, i.e. of the nexus I/O submitted on a
+/// core.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct NexusChannelStats {
+    /// Core of the channel.
+    pub core: u32,
+    /// Number of read operations.
+    pub num_read_ops: u64,
+    /// Number of write operations.
+    pub num_write_ops: u64,
+    /// Number of the other operations: unmap, write zeroes, flush and reset.
+    pub num_other_ops: u64,
+    /// Number of bytes read.
+    pub bytes_read: u64,
+    /// Number of bytes written.
+    pub bytes_written: u64,
+    /// Number of flush operations, also counted in the other operations.
+    pub num_flush_ops: u64,
+    /// Number of flush operations which completed without reaching every
+    /// healthy child, when verifying flushes.
+    pub num_flush_violations: u64,
+}
+
+/// IOPS of a nexus channel over a sampling interval.
+#[derive(Debug, Clone, Serialize)]
+pub struct NexusChannelIops {
+    /// Core of the channel.
+    pub core: u32,
+    /// Read operations per second.
+    pub read_iops: u64,
+    /// Write operations per second.
+    pub write_iops: u64,
+    /// Other operations per second.
+    pub other_iops: u64,
+    /// Share of the operations of the nexus submitted on the core, in
+    /// percent.
+    pub share_pct: u32,
+}
+
+impl NexusChannelIops {
+    /// Computes the IOPS of the channels from two samples of their
+    /// statistics taken the given time apart. A channel created in between
+    /// counts from zero.
+    pub fn from_samples(
+        before: &[NexusChannelStats],
+        after: &[NexusChannelStats],
+        elapsed: Duration,
+    ) -> Vec<Self> {
+        let ms = elapsed.as_millis().max(1) as u64;
+        let deltas = after
+            .iter()
+            .map(|a| {
+                let b = before
+                    .iter()
+                    .find(|b| b.core == a.core)
+                    .cloned()
+                    .unwrap_or_default();
+                (
+                    a.core,
+                    a.num_read_ops.saturating_sub(b.num_read_ops),
+                    a.num_write_ops.saturating_sub(b.num_write_ops),
+                    a.num_other_ops.saturating_sub(b.num_other_ops),
+                )
+            })
+            .collect::<Vec<_>>();
+        let total = deltas.iter().map(|(_, r, w, o)| r + w + o).sum::<u64>();
+
+        deltas
+            .into_iter()
+            .map(|(core, r, w, o)| Self {
+                core,
+                read_iops: r * 1000 / ms,
+                write_iops: w * 1000 / ms,
+                other_iops: o * 1000 / ms,
+                share_pct: ((r + w + o) * 100)
+                    .checked_div(total)
+                    .unwrap_or_default() as u32,
+            })
+            .collect()
+    }
+}
+
+/// Flush statistics of a nexus, over all its channels.
+#[derive(Debug, Clone, Serialize)]
+pub struct NexusFlushStats {
+    /// Whether the flushes are verified to reach every healthy child.
+    pub verify: bool,
+    /// Number of flush operations.
+    pub num_flush_ops: u64,
+    /// Number of flush operations which did not reach every healthy child.
+    pub num_flush_violations: u64,
+}
+
+impl NexusFlushStats {
+    /// Sums the flush statistics of the channels of a nexus.
+    pub fn from_stats(verify: bool, stats: &[NexusChannelStats]) -> Self {
+        Self {
+            verify,
+            num_flush_ops: stats.iter().map(|s| s.num_flush_ops).sum(),
+            num_flush_violations: stats
+                .iter()
+                .map(|s| s.num_flush_violations)
+                .sum(),
+        }
+    }
+}
+
+/// Number of the buckets of the latency histograms, the bucket `i` counting
+/// the latencies under 2^i microseconds and not under the previous one.
+const LATENCY_BUCKETS: usize = 32;
+
+/// I/O statistics of a child of a nexus on a channel, i.e. of the child I/O
+/// of the nexus I/O submitted on a core.
+#[derive(Debug, Clone)]
+pub(crate) struct ChildIoAccum {
+    in_flight: u64,
+    num_ops: u64,
+    num_errors: u64,
+    /// Sum of the latencies of the completed I/O, in microseconds.
+    total_latency_us: u64,
+    max_latency_us: u64,
+    latency_buckets: [u64; LATENCY_BUCKETS],
+    /// Moving average of the latencies, in microseconds. It only makes sense
+    /// on a channel, and is not merged.
+    moving_latency_us: u64,
+}
+
+impl Default for ChildIoAccum {
+    fn default() -> Self {
+        Self {
+            in_flight: 0,
+            num_ops: 0,
+            num_errors: 0,
+            total_latency_us: 0,
+            max_latency_us: 0,
+            latency_buckets: [0; LATENCY_BUCKETS],
+            moving_latency_us: 0,
+        }
+    }
+}
+
+impl ChildIoAccum {
+    /// Adds the statistics of a child on another channel.
+    pub(crate) fn merge(&mut self, other: &Self) {
+        self.in_flight += other.in_flight;
+        self.num_ops += other.num_ops;
+        self.num_errors += other.num_errors;
+        self.total_latency_us += other.total_latency_us;
+        self.max_latency_us = self.max_latency_us.max(other.max_latency_us);
+        for (b, o) in self.latency_buckets.iter_mut().zip(other.latency_buckets)
+        {
+            *b += o;
+        }
+    }
+
+    /// Returns the latency under which the given share of the completed I/O
+    /// completed, in microseconds, as the upper bound of its bucket.
+    fn latency_percentile_us(&self, pct: u64) -> u64 {
+        let target = (self.num_ops * pct).div_ceil(100);
+        if target == 0 {
+            return 0;
+        }
+        let mut count = 0;
+        for (i, b) in self.latency_buckets.iter().enumerate() {
+            count += b;
+            if count >= target {
+                return (1u64 << i).min(self.max_latency_us);
+            }
+        }
+        self.max_latency_us
+    }
+}
+
+/// I/O statistics of a child of a nexus, over all the I/O channels of the
+/// nexus, so that a slow child stands out before it fails.
+#[derive(Debug, Clone, Serialize)]
+pub struct NexusChildIoStats {
+    /// URI of the child.
+    pub uri: String,
+    /// Number of child I/O submitted and not completed yet.
+    pub in_flight: u64,
+    /// Number of child I/O completed.
+    pub num_ops: u64,
+    /// Number of child I/O which failed.
+    pub num_errors: u64,
+    /// Average latency of the completed child I/O, in microseconds.
+    pub avg_latency_us: u64,
+    /// Latency 50% of the child I/O completed under, in microseconds.
+    pub p50_latency_us: u64,
+    /// Latency 99% of the child I/O completed under, in microseconds.
+    pub p99_latency_us: u64,
+    /// Highest latency of a child I/O, in microseconds.
+    pub max_latency_us: u64,
+}
+
+impl NexusChildIoStats {
+    /// Makes the statistics of a child from its statistics summed over the
+    /// channels.
+    pub(crate) fn new(uri: String, accum: &ChildIoAccum) -> Self {
+        Self {
+            uri,
+            in_flight: accum.in_flight,
+            num_ops: accum.num_ops,
+            num_errors: accum.num_errors,
+            avg_latency_us: accum
+                .total_latency_us
+                .checked_div(accum.num_ops)
+                .unwrap_or_default(),
+            p50_latency_us: accum.latency_percentile_us(50),
+            p99_latency_us: accum.latency_percentile_us(99),
+            max_latency_us: accum.max_latency_us,
+        }
+    }
+}
+
+/// A child device of a channel, identified by its UUID, with its I/O
+/// statistics on the channel.
+#[derive(Debug)]
+struct ChildSlot<'n> {
+    uuid: Uuid,
+    device_name: String,
+    stats: ChildIoAccum,
+    /// Submission times of the child I/Os in flight, in ticks, with their
+    /// numbers. Only tracked while the child I/O timeout of the nexus is
+    /// set.
+    in_flight: BTreeMap<u64, u32>,
+    /// Child I/Os in flight whose nexus I/Os are completed without them if
+    /// they time out.
+    timed_ios: Vec<*mut TimedChildIo<'n>>,
+}
+
+/// Number of the reads after which the adaptive read policy selects the next
+/// reader in turn, so that the latencies of the slower children are still
+/// measured.
+const ADAPTIVE_PROBE_INTERVAL: u64 = 64;
+
+/// Policy the readers of the nexus are selected by.
+#[derive(
+    Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum NexusReadPolicy {
+    /// Each reader in turn.
+    #[default]
+    RoundRobin,
+    /// Each reader local to the nexus in turn, or each reader in turn when
+    /// none is local.
+    LocalityPreferred,
+    /// The reader with the lowest moving average latency, weighted by the
+    /// I/Os in flight to it.
+    Adaptive,
+}
+
+/// Policy the writes of the nexus are acknowledged by.
+#[derive(
+    Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum NexusWritePolicy {
+    /// Once all the writers completed the write.
+    #[default]
+    All,
+    /// Once the given number of writers completed the write, the others
+    /// completing it in the background. A writer which does not complete it
+    /// within the laggard timeout of the nexus options is faulted.
+    Quorum(u8),
+}
+
+/// Interval the laggard writes and the child I/O timeouts of a channel are
+/// checked at.
+const CHANNEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The writes acknowledged to the hosts and still in flight to the children
+/// of a nexus, over all its channels. A child is not read from until it
+/// completed them, nor while a write it failed is not faulted and detached
+/// yet. The writes overlapping them are deferred until they complete, so
+/// that the children write the same blocks in the same order.
+#[derive(Debug, Default)]
+pub(crate) struct LaggingChildren {
+    /// Number of the writes marking a child lagging.
+    total: AtomicUsize,
+    inner: parking_lot::Mutex<LaggingInner>,
+}
+
+#[derive(Debug, Default)]
+struct LaggingInner {
+    next_id: u64,
+    writes: Vec<LaggingWrite>,
+    /// Waiters for the writes in flight to complete.
+    drain_waiters: Vec<oneshot::Sender<()>>,
+}
+
+/// A write marking a child lagging.
+#[derive(Debug)]
+struct LaggingWrite {
+    id: u64,
+    device: Uuid,
+    /// Effective offset of the write, in blocks.
+    offset: u64,
+    num_blocks: u64,
+    /// Whether the write failed or timed out, the child being lagging until
+    /// it is detached from the channels.
+    failed: bool,
+}
+
+impl LaggingInner {
+    /// Signals the waiters once no write is in flight anymore.
+    fn notify_drained(&mut self) {
+        if self.writes.iter().all(|w| w.failed) {
+            self.drain_waiters.drain(..).for_each(|s| {
+                s.send(()).ok();
+            });
+        }
+    }
+}
+
+impl LaggingChildren {
+    /// Marks the device lagging for the given write, and returns the id of
+    /// the mark.
+    fn add(&self, device: Uuid, offset: u64, num_blocks: u64) -> u64 {
+        let mut inner = self.inner.lock();
+        inner.next_id += 1;
+        let id = inner.next_id;
+        inner.writes.push(LaggingWrite {
+            id,
+            device,
+            offset,
+            num_blocks,
+            failed: false,
+        });
+        self.total.fetch_add(1, Ordering::Relaxed);
+        id
+    }
+
+    /// Removes the mark of a write which completed successfully.
+    fn remove(&self, id: u64) {
+        let mut inner = self.inner.lock();
+        if let Some(idx) = inner.writes.iter().position(|w| w.id == id) {
+            inner.writes.swap_remove(idx);
+            self.total.fetch_sub(1, Ordering::Relaxed);
+            inner.notify_drained();
+        }
+    }
+
+    /// Keeps the mark of a write which failed or timed out, until its device
+    /// is detached from the channels.
+    fn fail(&self, id: u64) {
+        let mut inner = self.inner.lock();
+        if let Some(w) = inner.writes.iter_mut().find(|w| w.id == id) {
+            w.failed = true;
+            inner.notify_drained();
+        }
+    }
+
+    /// Removes the marks of the device, once it is detached from the
+    /// channels.
+    pub(crate) fn forget(&self, device: &Uuid) {
+        let mut inner = self.inner.lock();
+        let before = inner.writes.len();
+        inner.writes.retain(|w| w.device != *device);
+        self.total
+            .fetch_sub(before - inner.writes.len(), Ordering::Relaxed);
+        inner.notify_drained();
+    }
+
+    /// Checks if writes acknowledged to the hosts are in flight to any
+    /// child.
+    #[inline(always)]
+    fn any(&self) -> bool {
+        self.total.load(Ordering::Relaxed) > 0
+    }
+
+    /// Checks if the device is lagging.
+    fn is_lagging(&self, device: &Uuid) -> bool {
+        self.any()
+            && self.inner.lock().writes.iter().any(|w| w.device == *device)
+    }
+
+    /// Checks if a write in flight overlaps the given blocks.
+    pub(super) fn overlaps(&self, offset: u64, num_blocks: u64) -> bool {
+        self.any()
+            && self.inner.lock().writes.iter().any(|w| {
+                !w.failed
+                    && w.offset < offset + num_blocks
+                    && offset < w.offset + w.num_blocks
+            })
+    }
+
+    /// Waits for the writes in flight to complete, or to fail or time out.
+    pub(crate) async fn drain(&self) {
+        let recv = {
+            let mut inner = self.inner.lock();
+            if inner.writes.iter().all(|w| w.failed) {
+                return;
+            }
+            let (sender, recv) = oneshot::channel();
+            inner.drain_waiters.push(sender);
+            recv
+        };
+        recv.await.ok();
+    }
+}
+
+/// A child write in flight after its nexus write was acknowledged.
+#[derive(Debug)]
+struct LaggardWrite {
+    /// Identifies the nexus write.
+    id: u64,
+    /// Identifies the write in the lagging children.
+    mark: u64,
+    device: Uuid,
+    /// Effective offset of the write, in blocks.
+    offset: u64,
+    num_blocks: u64,
+    /// Time the write was submitted at, in ticks.
+    submitted_at: u64,
+    /// Whether the device was faulted for not completing it in time.
+    timed_out: bool,
+}
+
+/// The laggard writes of a channel. They are shared with the quorum writes
+/// they belong to, which may complete after the channel is destroyed.
+pub(super) struct LaggardWrites {
+    lagging: Arc<LaggingChildren>,
+    next_id: u64,
+    in_flight: Vec<LaggardWrite>,
+    /// Completed laggard writes, with their success, to be accounted for by
+    /// the channel.
+    completed: Vec<(LaggardWrite, bool)>,
+    /// Whether the channel is destroyed, the completed laggard writes not
+    /// being accounted for anymore.
+    closed: bool,
+    /// Handles of the writers of the destroyed channel, kept open until the
+    /// laggard writes submitted through them complete.
+    handles: Vec<Box<dyn BlockDeviceHandle>>,
+}
+
+impl Debug for LaggardWrites {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LaggardWrites")
+            .field("in_flight", &self.in_flight)
+            .field("completed", &self.completed)
+            .finish()
+    }
+}
+
+impl LaggardWrites {
+    fn new(lagging: Arc<LaggingChildren>) -> Self {
+        Self {
+            lagging,
+            next_id: 0,
+            in_flight: Vec::new(),
+            completed: Vec::new(),
+            closed: false,
+            handles: Vec::new(),
+        }
+    }
+
+    /// Registers the child writes still in flight to the given devices when
+    /// their nexus write is acknowledged, and returns the id of the nexus
+    /// write.
+    pub(super) fn add(
+        &mut self,
+        devices: &[Uuid],
+        offset: u64,
+        num_blocks: u64,
+        submitted_at: u64,
+    ) -> u64 {
+        self.next_id += 1;
+        for device in devices {
+            self.in_flight.push(LaggardWrite {
+                id: self.next_id,
+                mark: self.lagging.add(*device, offset, num_blocks),
+                device: *device,
+                offset,
+                num_blocks,
+                submitted_at,
+                timed_out: false,
+            });
+        }
+        self.next_id
+    }
+
+    /// Completes the child write of the given nexus write to the device. A
+    /// device which failed it stays lagging until it is faulted and
+    /// detached.
+    pub(super) fn complete(&mut self, id: u64, device: &Uuid, success: bool) {
+        let Some(idx) = self
+            .in_flight
+            .iter()
+            .position(|w| w.id == id && w.device == *device)
+        else {
+            return;
+        };
+        let write = self.in_flight.swap_remove(idx);
+        if !write.timed_out {
+            if success {
+                self.lagging.remove(write.mark);
+            } else {
+                self.lagging.fail(write.mark);
+            }
+        }
+        if !self.closed {
+            self.completed.push((write, success));
+        }
+    }
+
+    /// Stops accounting for the completed laggard writes, and keeps the
+    /// given handles of the writers open until the laggard writes in flight
+    /// complete. Their marks stay until then, and the marks of the failed
+    /// ones until their devices are detached.
+    fn close(&mut self, handles: Vec<Box<dyn BlockDeviceHandle>>) {
+        self.closed = true;
+        self.completed.clear();
+        self.handles = handles;
+    }
+}
+
+/// Channel checked by the channel poller.
+struct ChannelPollerCtx<'n>(*mut NexusChannel<'n>);
+
+// The poller runs on the thread of the channel.
+unsafe impl Send for ChannelPollerCtx<'_> {}
+
 /// Channel I/O disposition.
 #[derive(Debug, Copy, Clone)]
 pub enum IoMode {
@@ -112,18 +674,30 @@ impl<'n> NexusChannel<'n> {
             );
         }
 
+        let lagging = nexus.lagging_children.clone();
         let mut res = Self {
             writers: Vec::new(),
             readers: Vec::new(),
+            reader_slots: Vec::new(),
             detached: Vec::new(),
             io_logs: nexus.io_log_channels(),
             previous_reader: UnsafeCell::new(0),
+            adaptive_reads: UnsafeCell::new(0),
             nexus: unsafe { nexus.pinned_mut() },
             fail_fast: 0,
             io_mode: IoMode::Normal,
             frozen_ios: Vec::new(),
+            deferred_writes: Vec::new(),
             core: Cores::current(),
             is_io_chan,
+            stats: NexusChannelStats {
+                core: Cores::current(),
+                ..Default::default()
+            },
+            child_slots: Vec::new(),
+            host_stats: Vec::new(),
+            laggards: Rc::new(RefCell::new(LaggardWrites::new(lagging))),
+            poller: None,
         };
 
         res.connect_children();
@@ -143,8 +717,18 @@ impl<'n> NexusChannel<'n> {
             nex = self.nexus,
             core = self.core
         );
-        self.writers.clear();
+        if let Some(poller) = self.poller.take() {
+            poller.stop();
+        }
+        self.deferred_writes.drain(..).for_each(|io| io.fail());
+        // the channel is destroyed along with the nexus, once its laggard
+        // writes are drained, but the writes submitted since then must
+        // still complete through the handles of the writers
+        self.laggards
+            .borrow_mut()
+            .close(std::mem::take(&mut self.writers));
         self.readers.clear();
+        self.reader_slots.clear();
         self.detached.clear();
         self.io_logs.clear();
     }
@@ -167,6 +751,11 @@ impl<'n> NexusChannel<'n> {
         self.readers.len()
     }
 
+    /// Returns the number of the writers of this channel.
+    pub(crate) fn num_writers(&self) -> usize {
+        self.writers.len()
+    }
+
     // Returns a bool indicating whether this channel is setup for normal IOs.
     pub(crate) fn is_io_channel(&self) -> bool {
         self.is_io_chan
@@ -190,26 +779,78 @@ impl<'n> NexusChannel<'n> {
         self.io_logs.iter().for_each(f)
     }
 
-    /// Very simplistic routine to rotate between children for read operations
-    /// note that the channels can be None during a reconfigure; this is usually
-    /// not the case but a side effect of using the async. As we poll
+    /// Selects the child to read from, by the read policy of the nexus.
+    /// Note that the channels can be None during a reconfigure; this is
+    /// usually not the case but a side effect of using the async. As we poll
     /// threads more often depending on what core we are on etc, we might be
     /// "awaiting' while the thread is already trying to submit IO.
     pub(crate) fn select_reader(&self) -> Option<&dyn BlockDeviceHandle> {
         if self.readers.is_empty() {
-            None
-        } else {
-            let idx = unsafe {
-                let idx = &mut *self.previous_reader.get();
-                if *idx < self.readers.len() - 1 {
-                    *idx += 1;
-                } else {
-                    *idx = 0;
-                }
-                *idx
+            return None;
+        }
+
+        let idx = match self.nexus.read_policy() {
+            NexusReadPolicy::RoundRobin => self.next_reader(|_| true),
+            NexusReadPolicy::LocalityPreferred => self
+                .next_reader(|h| h.get_device().driver_name() != "nvme")
+                .or_else(|| self.next_reader(|_| true)),
+            NexusReadPolicy::Adaptive => self.fastest_reader(),
+        }?;
+
+        // a child which has not completed the writes acknowledged already
+        // is not read from, unless all of them are lagging
+        let lagging = self.nexus.lagging_children();
+        if lagging.any() {
+            let is_lagging = |h: &dyn BlockDeviceHandle| {
+                lagging.is_lagging(&h.get_device().uuid())
             };
-            Some(self.readers[idx].as_ref())
+            if is_lagging(self.readers[idx].as_ref()) {
+                if let Some(idx) = self.next_reader(|h| !is_lagging(h)) {
+                    return Some(self.readers[idx].as_ref());
+                }
+            }
         }
+        Some(self.readers[idx].as_ref())
+    }
+
+    /// Rotates to the next reader matching the given predicate, and returns
+    /// its index.
+    fn next_reader<F>(&self, pred: F) -> Option<usize>
+    where
+        F: Fn(&dyn BlockDeviceHandle) -> bool,
+    {
+        let n = self.readers.len();
+        let previous = unsafe { &mut *self.previous_reader.get() };
+        let idx = (1 ..= n)
+            .map(|i| (*previous + i) % n)
+            .find(|&i| pred(self.readers[i].as_ref()))?;
+        *previous = idx;
+        Some(idx)
+    }
+
+    /// Selects the reader with the lowest moving average latency weighted by
+    /// the I/Os in flight to it, starting from the next reader in turn so
+    /// that the equally fast readers are rotated between, and returns its
+    /// index. A reader not read from yet counts as the fastest.
+    fn fastest_reader(&self) -> Option<usize> {
+        let reads = unsafe { &mut *self.adaptive_reads.get() };
+        *reads += 1;
+        if *reads % ADAPTIVE_PROBE_INTERVAL == 0 {
+            return self.next_reader(|_| true);
+        }
+
+        let n = self.readers.len();
+        let previous = unsafe { &mut *self.previous_reader.get() };
+        let idx = (1 ..= n).map(|i| (*previous + i) % n).min_by_key(|&i| {
+            let accum = &self.child_slots[self.reader_slots[i]].stats;
+            if accum.num_ops == 0 {
+                0
+            } else {
+                accum.moving_latency_us.max(1) * (accum.in_flight + 1)
+            }
+        })?;
+        *previous = idx;
+        Some(idx)
     }
 
     /// Detaches a child device from this I/O channel, moving the device's
@@ -226,6 +867,7 @@ impl<'n> NexusChannel<'n> {
             .position(|c| c.get_device().device_name() == device_name)
         {
             let t = self.readers.remove(d);
+            self.reader_slots.remove(d);
             self.detached.push(t);
         }
 
@@ -357,6 +999,13 @@ impl<'n> NexusChannel<'n> {
                 });
         }
 
+        for w in &writers {
+            self.child_slot(w.get_device());
+        }
+        self.reader_slots = readers
+            .iter()
+            .map(|r| self.child_slot(r.get_device()))
+            .collect();
         self.writers = writers;
         self.readers = readers;
     }
@@ -383,11 +1032,420 @@ impl<'n> NexusChannel<'n> {
         Some(io_log)
     }
 
+    /// Starts the poller checking the laggard writes and the child I/O
+    /// timeouts, if not started yet.
+    fn start_poller(&mut self) {
+        if self.poller.is_some() {
+            return;
+        }
+        let ctx = ChannelPollerCtx(self as *mut _);
+        self.poller = Some(
+            PollerBuilder::new()
+                .with_name("nexus_channel")
+                .with_interval(CHANNEL_POLL_INTERVAL)
+                .with_data(ctx)
+                .with_poll_fn(|ctx| {
+                    let chan = unsafe { &mut *ctx.0 };
+                    chan.check_laggards()
+                        + chan.submit_deferred_writes()
+                        + chan.check_io_timeouts()
+                })
+                .build(),
+        );
+    }
+
+    /// Returns the laggard writes of the channel, starting the poller which
+    /// checks them if not started yet.
+    pub(super) fn laggard_writes(&mut self) -> Rc<RefCell<LaggardWrites>> {
+        self.start_poller();
+        self.laggards.clone()
+    }
+
+    /// Defers a write overlapping a laggard write of the nexus, until the
+    /// laggard write completes.
+    pub(super) fn defer_write(&mut self, io: NexusBio<'n>) {
+        trace!("{io:?}: deferring write overlapping a laggard write");
+        self.start_poller();
+        self.deferred_writes.push(io);
+    }
+
+    /// Submits the deferred writes which do not overlap a laggard write
+    /// anymore.
+    fn submit_deferred_writes(&mut self) -> i32 {
+        if self.deferred_writes.is_empty() {
+            return 0;
+        }
+        let lagging = self.nexus.lagging_children();
+        let (ready, deferred): (Vec<_>, Vec<_>) =
+            std::mem::take(&mut self.deferred_writes)
+                .into_iter()
+                .partition(|io| {
+                    !lagging.overlaps(io.effective_offset(), io.num_blocks())
+                });
+        self.deferred_writes = deferred;
+        let n = ready.len();
+        ready.into_iter().for_each(|io| io.submit_request());
+        n as i32
+    }
+
+    /// Faults the devices which have not completed a child I/O within the
+    /// child I/O timeout of the nexus, detaching them from this channel. The
+    /// child I/Os in flight to them cannot be aborted, so their nexus I/Os
+    /// are completed without them, as if they were aborted: the reads are
+    /// retried on the other readers and the writes resubmitted to the other
+    /// writers. Their late completions are dropped.
+    fn check_io_timeouts(&mut self) -> i32 {
+        let Some(timeout) = self.nexus.child_io_timeout() else {
+            self.child_slots
+                .iter_mut()
+                .for_each(|s| s.in_flight.clear());
+            return 0;
+        };
+        let timeout_ms = timeout.as_millis() as u64;
+        let ticks = timeout_ms * unsafe { spdk_get_ticks_hz() } / 1000;
+        let now = unsafe { spdk_get_ticks() };
+
+        let timed_out = self
+            .child_slots
+            .iter_mut()
+            .filter(|slot| {
+                slot.in_flight
+                    .first_key_value()
+                    .is_some_and(|(t, _)| now.saturating_sub(*t) > ticks)
+            })
+            .map(|slot| {
+                slot.in_flight.clear();
+                (
+                    slot.device_name.clone(),
+                    std::mem::take(&mut slot.timed_ios),
+                )
+            })
+            .collect::<Vec<_>>();
+        let n = timed_out.len();
+
+        for (device_name, ios) in timed_out {
+            error!(
+                "{self:?}: child I/O to '{device_name}' not completed \
+                within {timeout_ms} ms"
+            );
+            self.detach_device(&device_name);
+            self.disconnect_detached_devices(|_| true);
+            self.fault_device(&device_name, FaultReason::IoTimeout);
+
+            let Some(device) = device_lookup(&device_name) else {
+                error!(
+                    "{self:?}: '{device_name}' not found, {n} nexus I/Os \
+                    wait for its child I/Os",
+                    n = ios.len()
+                );
+                continue;
+            };
+            ios.into_iter()
+                .for_each(|io| TimedChildIo::time_out(io, device.as_ref()));
+        }
+
+        n as i32
+    }
+
+    /// Accounts for the completed laggard writes, and faults the devices
+    /// which failed one, or did not complete one within the laggard
+    /// timeout. The blocks of these writes are logged for the rebuild of the
+    /// devices, which are not read from until they are detached.
+    fn check_laggards(&mut self) -> i32 {
+        let timeout_ms =
+            Config::get().nexus_opts.nexus_write_laggard_timeout_ms;
+        let timeout = timeout_ms * unsafe { spdk_get_ticks_hz() } / 1000;
+        let now = unsafe { spdk_get_ticks() };
+
+        let (completed, timed_out) = {
+            let mut laggards = self.laggards.borrow_mut();
+            let completed = std::mem::take(&mut laggards.completed);
+            let timed_out = laggards
+                .in_flight
+                .iter_mut()
+                .filter(|w| {
+                    !w.timed_out && now.saturating_sub(w.submitted_at) > timeout
+                })
+                .map(|w| {
+                    w.timed_out = true;
+                    (w.mark, w.device, w.offset, w.num_blocks)
+                })
+                .collect::<Vec<_>>();
+            (completed, timed_out)
+        };
+        let busy = completed.len() + timed_out.len();
+
+        for (write, success) in completed {
+            let Some(slot) =
+                self.child_slots.iter().position(|s| s.uuid == write.device)
+            else {
+                continue;
+            };
+            self.account_slot_completion(slot, write.submitted_at, success);
+            if !success && !write.timed_out {
+                let device_name = self.child_slots[slot].device_name.clone();
+                error!("{self:?}: laggard write failed on '{device_name}'");
+                self.fault_laggard(
+                    &device_name,
+                    FaultReason::IoError,
+                    write.offset,
+                    write.num_blocks,
+                );
+            }
+        }
+
+        for (mark, device, offset, num_blocks) in timed_out {
+            if let Some(slot) =
+                self.child_slots.iter().find(|s| s.uuid == device)
+            {
+                let device_name = slot.device_name.clone();
+                error!(
+                    "{self:?}: laggard write to '{device_name}' not \
+                    completed within {timeout_ms} ms"
+                );
+                self.fault_laggard(
+                    &device_name,
+                    FaultReason::TimedOut,
+                    offset,
+                    num_blocks,
+                );
+            }
+            // the device stays lagging until detached, but the write is
+            // not waited for anymore
+            self.nexus.lagging_children().fail(mark);
+        }
+
+        busy as i32
+    }
+
+    /// Faults the device of a laggard write, and logs the blocks of the
+    /// write for its rebuild.
+    fn fault_laggard(
+        &mut self,
+        device_name: &str,
+        reason: FaultReason,
+        offset: u64,
+        num_blocks: u64,
+    ) {
+        self.fault_device(device_name, reason);
+        self.for_each_io_log(|log| {
+            log.log_io(IoType::Write, offset, num_blocks)
+        });
+    }
+
     /// Returns core on which channel was created.
     pub fn core(&self) -> u32 {
         self.core
     }
 
+    /// Accounts for a new nexus I/O submitted on this channel.
+    pub(super) fn account_io(&mut self, io_type: IoType, num_bytes: u64) {
+        match io_type {
+            IoType::Read => {
+                self.stats.num_read_ops += 1;
+                self.stats.bytes_read += num_bytes;
+            }
+            IoType::Write => {
+                self.stats.num_write_ops += 1;
+                self.stats.bytes_written += num_bytes;
+            }
+            IoType::Flush => {
+                self.stats.num_other_ops += 1;
+                self.stats.num_flush_ops += 1;
+            }
+            _ => self.stats.num_other_ops += 1,
+        }
+    }
+
+    /// Accounts for a read or write submitted by the NVMf host of the given
+    /// controller.
+    pub(super) fn account_host_io(
+        &mut self,
+        cntlid: u16,
+        io_type: IoType,
+        num_bytes: u64,
+    ) {
+        let idx = match self.host_stats.iter().position(|(c, _)| *c == cntlid) {
+            Some(idx) => idx,
+            None => {
+                self.host_stats.push((cntlid, HostIoCounters::default()));
+                self.host_stats.len() - 1
+            }
+        };
+        self.host_stats[idx].1.account(io_type, num_bytes);
+    }
+
+    /// Returns the reads and writes submitted by the NVMf hosts on this
+    /// channel, by controller ID.
+    pub(crate) fn host_stats(&self) -> &[(u16, HostIoCounters)] {
+        &self.host_stats
+    }
+
+    /// Forgets the reads and writes of the controller of a host which
+    /// disconnected, so that a controller given the same ID counts from
+    /// zero.
+    pub(crate) fn forget_host_stats(&mut self, cntlid: u16) {
+        self.host_stats.retain(|(c, _)| *c != cntlid);
+    }
+
+    /// Accounts for a flush which did not reach every healthy child, and
+    /// returns the number of such flushes on this channel.
+    pub(super) fn account_flush_violation(&mut self) -> u64 {
+        self.stats.num_flush_violations += 1;
+        self.stats.num_flush_violations
+    }
+
+    /// Returns the I/O statistics of this channel.
+    pub(crate) fn stats(&self) -> NexusChannelStats {
+        self.stats.clone()
+    }
+
+    /// Returns the slot of the device on this channel, if it has one.
+    #[inline(always)]
+    pub(super) fn find_child_slot(
+        &self,
+        device: &dyn BlockDevice,
+    ) -> Option<usize> {
+        let uuid = device.uuid();
+        self.child_slots.iter().position(|s| s.uuid == uuid)
+    }
+
+    /// Returns the slot of the device on this channel, adding one for it if
+    /// it has none yet.
+    fn child_slot(&mut self, device: &dyn BlockDevice) -> usize {
+        if let Some(slot) = self.find_child_slot(device) {
+            return slot;
+        }
+        self.child_slots.push(ChildSlot {
+            uuid: device.uuid(),
+            device_name: device.device_name(),
+            stats: ChildIoAccum::default(),
+            in_flight: BTreeMap::new(),
+            timed_ios: Vec::new(),
+        });
+        self.child_slots.len() - 1
+    }
+
+    /// Accounts for child I/O submitted to the device of the given slot at
+    /// the given time, in ticks.
+    pub(super) fn account_child_submit(
+        &mut self,
+        slot: usize,
+        submitted_at: u64,
+    ) {
+        if self.nexus.child_io_timeout().is_some() {
+            self.start_poller();
+            *self.child_slots[slot]
+                .in_flight
+                .entry(submitted_at)
+                .or_default() += 1;
+        }
+        self.child_slots[slot].stats.in_flight += 1;
+    }
+
+    /// Tracks a child I/O submitted to the device, to complete its nexus I/O
+    /// without it if it times out.
+    pub(super) fn track_child_io(
+        &mut self,
+        device: &dyn BlockDevice,
+        io: *mut TimedChildIo<'n>,
+    ) {
+        if let Some(slot) = self.find_child_slot(device) {
+            self.child_slots[slot].timed_ios.push(io);
+        }
+    }
+
+    /// Stops tracking a child I/O which completed or failed to be
+    /// submitted.
+    pub(super) fn untrack_child_io(
+        &mut self,
+        device: &dyn BlockDevice,
+        io: *mut TimedChildIo<'n>,
+    ) {
+        if let Some(slot) = self.find_child_slot(device) {
+            let ios = &mut self.child_slots[slot].timed_ios;
+            if let Some(idx) = ios.iter().position(|i| *i == io) {
+                ios.swap_remove(idx);
+            }
+        }
+    }
+
+    /// Accounts for child I/O submitted at the given time, in ticks, to the
+    /// given number of the first writers.
+    pub(super) fn account_writers_submit(
+        &mut self,
+        num_writers: usize,
+        submitted_at: u64,
+    ) {
+        for i in 0 .. num_writers.min(self.writers.len()) {
+            // the slots of the writers are added as they are connected
+            if let Some(slot) =
+                self.find_child_slot(self.writers[i].get_device())
+            {
+                self.account_child_submit(slot, submitted_at);
+            }
+        }
+    }
+
+    /// Accounts for the completion of child I/O submitted to the given
+    /// device at the given time, in ticks.
+    pub(super) fn account_child_completion(
+        &mut self,
+        device: &dyn BlockDevice,
+        submitted_at: u64,
+        success: bool,
+    ) {
+        if let Some(slot) = self.find_child_slot(device) {
+            self.account_slot_completion(slot, submitted_at, success);
+        }
+    }
+
+    /// Accounts for the completion of child I/O submitted to the device of
+    /// the given slot at the given time, in ticks.
+    fn account_slot_completion(
+        &mut self,
+        slot: usize,
+        submitted_at: u64,
+        success: bool,
+    ) {
+        let slot = &mut self.child_slots[slot];
+        if let Some(n) = slot.in_flight.get_mut(&submitted_at) {
+            *n -= 1;
+            if *n == 0 {
+                slot.in_flight.remove(&submitted_at);
+            }
+        }
+
+        let accum = &mut slot.stats;
+        let ticks = unsafe { spdk_get_ticks() }.saturating_sub(submitted_at);
+        let hz = unsafe { spdk_get_ticks_hz() }.max(1);
+        let latency_us = (ticks as u128 * 1_000_000 / hz as u128) as u64;
+        let bucket = (u64::BITS - latency_us.leading_zeros()) as usize;
+
+        accum.in_flight = accum.in_flight.saturating_sub(1);
+        accum.num_ops += 1;
+        if !success {
+            accum.num_errors += 1;
+        }
+        accum.total_latency_us += latency_us;
+        accum.max_latency_us = accum.max_latency_us.max(latency_us);
+        accum.latency_buckets[bucket.min(LATENCY_BUCKETS - 1)] += 1;
+        accum.moving_latency_us = if accum.num_ops == 1 {
+            latency_us
+        } else {
+            (accum.moving_latency_us * 7 + latency_us) / 8
+        };
+    }
+
+    /// Returns the I/O statistics of the children on this channel, by
+    /// device name.
+    pub(crate) fn child_stats(&self) -> HashMap<String, ChildIoAccum> {
+        self.child_slots
+            .iter()
+            .map(|s| (s.device_name.clone(), s.stats.clone()))
+            .collect()
+    }
+
     /// Sets the current I/O mode for this channel.
     pub(super) fn set_io_mode(&mut self, io_mode: IoMode) {
         self.io_mode = io_mode;
@@ -132,6 +132,9 @@ pub enum FaultReason {
     /// This a recoverable state in case the device can be expected
     /// to come back online.
     TimedOut,
+    /// The child did not complete an I/O within the child I/O timeout of
+    /// the nexus.
+    IoTimeout,
     /// The child has been faulted due to I/O error(s).
     IoError,
     /// The child failed to rebuild successfully.
@@ -151,6 +154,7 @@ impl Display for FaultReason {
             Self::CantOpen => write!(f, "cannot open"),
             Self::NoSpace => write!(f, "no space"),
             Self::TimedOut => write!(f, "timed out"),
+            Self::IoTimeout => write!(f, "I/O timeout"),
             Self::IoError => write!(f, "I/O error"),
             Self::RebuildFailed => write!(f, "rebuild failed"),
             Self::AdminCommandFailed => write!(f, "admin command failed"),
@@ -167,6 +171,7 @@ impl FaultReason {
             self,
             Self::NoSpace
                 | Self::TimedOut
+                | Self::IoTimeout
                 | Self::IoError
                 | Self::Offline
                 | Self::AdminCommandFailed
@@ -1,40 +1,63 @@
 use std::{
+    cell::RefCell,
     fmt::{Debug, Formatter},
     ops::{Deref, DerefMut},
     pin::Pin,
+    rc::Rc,
 };
 
+use events_api::event::EventAction;
 use libc::c_void;
 use nix::errno::Errno;
+use uuid::Uuid;
 
 use spdk_rs::{
     libspdk::{
         spdk_bdev_io,
         spdk_bdev_io_complete_nvme_status,
+        spdk_get_ticks,
         spdk_io_channel,
+        spdk_nvmf_request,
+        SPDK_NVME_SC_ABORTED_BY_REQUEST,
         SPDK_NVME_SC_ABORTED_SQ_DELETION,
         SPDK_NVME_SC_CAPACITY_EXCEEDED,
         SPDK_NVME_SC_INVALID_OPCODE,
+        SPDK_NVME_SC_NAMESPACE_IS_WRITE_PROTECTED,
         SPDK_NVME_SC_RESERVATION_CONFLICT,
     },
     BdevIo,
+    DmaBuf,
+    IoVec,
 };
 
-use super::{FaultReason, IOLogChannel, Nexus, NexusChannel, NEXUS_PRODUCT_ID};
-
-use crate::core::{
-    BlockDevice,
-    BlockDeviceHandle,
-    CoreError,
-    Cores,
-    IoCompletionStatus,
-    IoStatus,
-    IoSubmissionFailure,
-    IoType,
-    LvolFailure,
-    Mthread,
-    NvmeStatus,
-    ReadOptions,
+use super::{
+    nexus_channel::LaggardWrites,
+    ChildState,
+    FaultReason,
+    IOLogChannel,
+    Nexus,
+    NexusChannel,
+    NexusWritePolicy,
+    NEXUS_PRODUCT_ID,
+};
+
+use crate::{
+    core::{
+        BlockDevice,
+        BlockDeviceHandle,
+        CoreError,
+        Cores,
+        IoCompletionCallback,
+        IoCompletionStatus,
+        IoStatus,
+        IoSubmissionFailure,
+        IoType,
+        LvolFailure,
+        Mthread,
+        NvmeStatus,
+        ReadOptions,
+    },
+    eventing::{nexus_events::flush_violation_event_meta, EventWithMeta},
 };
 
 #[cfg(feature = "nexus-io-tracing")]
@@ -74,6 +97,11 @@ pub(super) struct NioCtx<'n> {
     failed: u8,
     /// Number of resubmissions. Incremented with each resubmission.
     resubmits: u8,
+    /// Time the child I/Os were submitted at, in ticks.
+    submitted_at: u64,
+    /// Children which completed a flush being verified, as a mask of their
+    /// indexes in the nexus.
+    flushed: u64,
     /// Debug serial number.
     #[cfg(feature = "nexus-io-tracing")]
     serial: u64,
@@ -107,6 +135,202 @@ impl<'n> Debug for NioCtx<'n> {
     }
 }
 
+/// A nexus write submitted to all the writers, and acknowledged once a
+/// quorum of them completed it. It lives until all of them completed it.
+struct QuorumWrite<'n> {
+    /// The nexus write, until acknowledged.
+    bio: Option<NexusBio<'n>>,
+    /// Copy of the data, which the laggards write from once the buffers of
+    /// the nexus write are released.
+    _buf: DmaBuf,
+    laggards: Rc<RefCell<LaggardWrites>>,
+    /// Identifies the nexus write in the laggard writes, once acknowledged.
+    id: u64,
+    /// Offset of the nexus write, in blocks.
+    offset: u64,
+    num_blocks: u64,
+    quorum: u8,
+    in_flight: u8,
+    successful: u8,
+    failed: u8,
+    /// Devices the child writes are in flight to.
+    pending: Vec<Uuid>,
+}
+
+impl<'n> QuorumWrite<'n> {
+    /// Completion handler of the child writes.
+    fn child_completion(
+        device: &dyn BlockDevice,
+        status: IoCompletionStatus,
+        ctx: *mut c_void,
+    ) {
+        let qw = ctx as *mut QuorumWrite<'n>;
+        let done = unsafe {
+            (*qw).complete(device, status);
+            (*qw).in_flight == 0
+        };
+        if done {
+            drop(unsafe { Box::from_raw(qw) });
+        }
+    }
+
+    /// Acknowledges the nexus write once the quorum of the child writes
+    /// succeeded, registering the ones still in flight as laggard writes.
+    /// When the quorum cannot be reached, the nexus write is resubmitted or
+    /// failed once all the child writes completed, as under the policy
+    /// waiting for all of them.
+    fn complete(
+        &mut self,
+        child: &dyn BlockDevice,
+        status: IoCompletionStatus,
+    ) {
+        #[cfg(feature = "fault-injection")]
+        let status = match &self.bio {
+            Some(bio) => bio.inject_completion_error(child, status),
+            None => self.inject_laggard_error(child, status),
+        };
+
+        debug_assert!(self.in_flight > 0);
+        self.in_flight -= 1;
+
+        let device = child.uuid();
+        self.pending.retain(|d| *d != device);
+        let success = status == IoCompletionStatus::Success;
+
+        let Some(bio) = self.bio.as_mut() else {
+            self.laggards
+                .borrow_mut()
+                .complete(self.id, &device, success);
+            return;
+        };
+
+        let submitted_at = bio.ctx().submitted_at;
+        bio.channel_mut().account_child_completion(
+            child,
+            submitted_at,
+            success,
+        );
+
+        if success {
+            self.successful += 1;
+        } else {
+            bio.ctx_mut().status = IoStatus::Failed;
+            self.failed += 1;
+            bio.completion_error(child, status);
+        }
+
+        if self.successful >= self.quorum {
+            trace_nexus_io!("Quorum: {bio:?}");
+            if self.in_flight > 0 {
+                self.id = self.laggards.borrow_mut().add(
+                    &self.pending,
+                    bio.effective_offset(),
+                    bio.num_blocks(),
+                    submitted_at,
+                );
+            }
+            bio.retention_appended();
+            bio.ok();
+            self.bio = None;
+        } else if self.in_flight == 0 {
+            if self.successful > 0 {
+                let ctx = bio.ctx_mut();
+                ctx.successful = self.successful;
+                ctx.failed = self.failed;
+                bio.resubmit();
+            } else {
+                error!("{bio:?}: failing nexus I/O: all child I/Os failed");
+
+                unsafe {
+                    bio.nexus_mut().get_unchecked_mut().last_error = status;
+                }
+
+                bio.fail();
+            }
+            self.bio = None;
+        }
+    }
+
+    /// Injects the completion errors of the child writes into the laggard
+    /// writes as well.
+    #[cfg(feature = "fault-injection")]
+    fn inject_laggard_error(
+        &self,
+        child: &dyn BlockDevice,
+        status: IoCompletionStatus,
+    ) -> IoCompletionStatus {
+        use crate::core::fault_injection::{
+            inject_completion_error,
+            FaultDomain::NexusChild,
+            InjectIoCtx,
+        };
+
+        inject_completion_error(
+            &InjectIoCtx::with_iovs(
+                NexusChild,
+                child,
+                IoType::Write,
+                self.offset,
+                self.num_blocks,
+                &[self._buf.to_io_vec()],
+            ),
+            status,
+        )
+    }
+}
+
+/// A child I/O submitted while the child I/O timeout of the nexus is set.
+/// When the child does not complete it in time, its nexus I/O is completed
+/// without it, as the child I/O cannot be aborted: its late completion is
+/// then dropped.
+pub(super) struct TimedChildIo<'n> {
+    /// The nexus I/O, until the child I/O completes or times out.
+    bio: Option<NexusBio<'n>>,
+    /// Buffer a child read reads into, copied to the buffers of the nexus
+    /// read once it succeeded, so that a late read does not write into
+    /// buffers the nexus read no longer owns.
+    buf: Option<DmaBuf>,
+}
+
+impl<'n> TimedChildIo<'n> {
+    /// Completion handler of the timed child I/Os.
+    fn child_completion(
+        device: &dyn BlockDevice,
+        status: IoCompletionStatus,
+        ctx: *mut c_void,
+    ) {
+        let io = unsafe { Box::from_raw(ctx as *mut TimedChildIo<'n>) };
+        let TimedChildIo {
+            bio,
+            buf,
+        } = *io;
+        if let Some(mut bio) = bio {
+            bio.channel_mut().untrack_child_io(device, ctx.cast());
+            if let (Some(buf), IoCompletionStatus::Success) = (&buf, &status) {
+                bio.copy_from_buf(buf);
+            }
+            bio.complete(device, status);
+        }
+    }
+
+    /// Completes the nexus I/O of a child I/O which timed out on the given
+    /// device, as if the child I/O was aborted. A child read keeps its
+    /// buffer until its late completion.
+    pub(super) fn time_out(
+        io: *mut TimedChildIo<'n>,
+        device: &dyn BlockDevice,
+    ) {
+        if let Some(mut bio) = unsafe { (*io).bio.take() } {
+            bio.complete(
+                device,
+                IoCompletionStatus::NvmeError(NvmeStatus::Generic(
+                    SPDK_NVME_SC_ABORTED_BY_REQUEST,
+                )),
+            );
+        }
+    }
+}
+
 /// TODO
 #[repr(transparent)]
 #[derive(Clone)]
@@ -165,17 +389,55 @@ impl<'n> NexusBio<'n> {
         ctx.resubmits = 0;
         ctx.successful = 0;
         ctx.failed = 0;
+        ctx.submitted_at = 0;
+        ctx.flushed = 0;
 
         #[cfg(feature = "nexus-io-tracing")]
         {
             ctx.serial = debug_nexus_io::new_serial();
         }
 
+        let io_type = bio.io_type();
+        let num_bytes = bio.num_blocks() * bio.nexus().block_len();
+        bio.channel_mut().account_io(io_type, num_bytes);
+        if matches!(io_type, IoType::Read | IoType::Write) {
+            if let Some(cntlid) = bio.nvmf_cntlid() {
+                bio.channel_mut()
+                    .account_host_io(cntlid, io_type, num_bytes);
+            }
+        }
+
         trace_nexus_io!("New: {bio:?}");
 
         bio
     }
 
+    /// Returns the ID of the controller of the NVMf host which submitted the
+    /// read or write through the share of the nexus, if it was.
+    fn nvmf_cntlid(&self) -> Option<u16> {
+        let desc = self.nexus().nvmf_desc();
+        if desc.is_null() {
+            return None;
+        }
+        unsafe {
+            let io = &*self.as_ptr();
+            if io.internal.desc != desc {
+                return None;
+            }
+            // the target completes the request of a read or write with the
+            // bdev I/O it submitted for it
+            let req = io.internal.caller_ctx as *const spdk_nvmf_request;
+            if req.is_null() {
+                return None;
+            }
+            let qpair = (*req).qpair;
+            if qpair.is_null() || (*qpair).ctrlr.is_null() {
+                return None;
+            }
+            Some((*(*qpair).ctrlr).cntlid)
+        }
+    }
+
     /// TODO
     pub(super) fn submit_request(mut self) {
         if self.channel().is_frozen() {
@@ -184,8 +446,42 @@ impl<'n> NexusBio<'n> {
             return;
         }
 
+        // a write overlapping a write acknowledged already but still in
+        // flight to a child waits for it, so that the child does not write
+        // them out of order
+        if matches!(
+            self.io_type(),
+            IoType::Write | IoType::WriteZeros | IoType::Unmap
+        ) && self
+            .nexus()
+            .lagging_children()
+            .overlaps(self.effective_offset(), self.num_blocks())
+        {
+            let s = self.clone();
+            self.channel_mut().defer_write(s);
+            return;
+        }
+
+        // the resubmissions of a write have been let through already
+        if self.ctx().resubmits == 0
+            && matches!(
+                self.io_type(),
+                IoType::Write | IoType::WriteZeros | IoType::Unmap
+            )
+            && !self.nexus().retention_allows_write(self.offset())
+        {
+            trace_nexus_io!("Write refused by the retention: {self:?}");
+            self.fail_nvme_status(NvmeStatus::Generic(
+                SPDK_NVME_SC_NAMESPACE_IS_WRITE_PROTECTED,
+            ));
+            return;
+        }
+
         if let Err(_e) = match self.io_type() {
             IoType::Read => self.readv(),
+            IoType::Write if self.write_quorum().is_some() => {
+                self.submit_quorum_write()
+            }
             // these IOs are submitted to all the underlying children
             IoType::Write
             | IoType::WriteZeros
@@ -257,8 +553,18 @@ impl<'n> NexusBio<'n> {
         debug_assert!(self.ctx().in_flight > 0);
         self.ctx_mut().in_flight -= 1;
 
+        let submitted_at = self.ctx().submitted_at;
+        self.channel_mut().account_child_completion(
+            child,
+            submitted_at,
+            status == IoCompletionStatus::Success,
+        );
+
         if status == IoCompletionStatus::Success {
             self.ctx_mut().successful += 1;
+            if self.io_type() == IoType::Flush && self.nexus().flush_verify() {
+                self.account_flushed(child);
+            }
         } else {
             self.ctx_mut().status = IoStatus::Failed;
             self.ctx_mut().failed += 1;
@@ -275,10 +581,16 @@ impl<'n> NexusBio<'n> {
         if self.ctx().failed == 0 {
             // No child failures, complete nexus I/O with success.
             trace_nexus_io!("Success: {self:?}");
+            if self.io_type() == IoType::Flush && self.nexus().flush_verify() {
+                self.verify_flush();
+            }
+            self.retention_appended();
             self.ok();
         } else if self.ctx().successful > 0 {
             // Having some child failures, resubmit the I/O.
             self.resubmit();
+        } else if self.is_read_retriable(child) {
+            self.retry_read(&child.device_name());
         } else {
             error!("{self:?}: failing nexus I/O: all child I/Os failed");
 
@@ -290,6 +602,51 @@ impl<'n> NexusBio<'n> {
         }
     }
 
+    /// Records a child which completed a flush being verified.
+    fn account_flushed(&mut self, child: &dyn BlockDevice) {
+        let name = child.device_name();
+        let idx = self
+            .nexus()
+            .children_iter()
+            .position(|c| c.get_device_name().as_deref() == Some(&name));
+        if let Some(idx) = idx.filter(|idx| *idx < u64::BITS as usize) {
+            self.ctx_mut().flushed |= 1 << idx;
+        }
+    }
+
+    /// Checks that a flush completed by all the child I/Os reached every
+    /// healthy child of the nexus, i.e. that every healthy child completed
+    /// the flush it was fanned out to. The first violation on a channel
+    /// raises an event, the following ones are only counted and logged.
+    fn verify_flush(&mut self) {
+        let flushed = self.ctx().flushed;
+        let missed = self
+            .nexus()
+            .children_iter()
+            .enumerate()
+            .filter(|(idx, c)| {
+                c.is_healthy()
+                    && *idx < u64::BITS as usize
+                    && flushed & (1 << idx) == 0
+            })
+            .filter_map(|(_, c)| c.get_device_name())
+            .collect::<Vec<_>>();
+        if missed.is_empty() {
+            return;
+        }
+
+        error!("{self:?}: flush did not reach healthy children: {missed:?}");
+
+        if self.channel_mut().account_flush_violation() == 1 {
+            EventWithMeta::event(
+                self.nexus(),
+                EventAction::StateChange,
+                flush_violation_event_meta(&missed),
+            )
+            .generate();
+        }
+    }
+
     /// Fails the current I/O with a generic internal error. If the nexus
     /// already had a last child error, it fails with it.
     fn fail(&self) {
@@ -332,6 +689,44 @@ impl<'n> NexusBio<'n> {
         bio.submit_request();
     }
 
+    /// Checks if a failed read is to be retried on another reader, which is
+    /// the case when the child it was submitted to was faulted for not
+    /// completing an I/O in time. A read is retried at most once per child.
+    fn is_read_retriable(&self, child: &dyn BlockDevice) -> bool {
+        self.io_type() == IoType::Read
+            && (self.ctx().resubmits as usize) < self.nexus().child_count()
+            && self
+                .nexus()
+                .lookup_child_by_device(&child.device_name())
+                .is_some_and(|c| {
+                    c.state() == ChildState::Faulted(FaultReason::IoTimeout)
+                })
+    }
+
+    /// Retries a read on another reader, after the child it was submitted
+    /// to timed out.
+    fn retry_read(&mut self, device_name: &str) {
+        warn!(
+            "{self:?}: retrying nexus read timed out on '{device_name}' on \
+            another child"
+        );
+
+        // the child may still be a reader of this channel, if it timed out
+        // on another one
+        self.channel_mut().detach_device(device_name);
+        self.channel_mut().disconnect_detached_devices(|_| true);
+
+        let ctx = self.ctx_mut();
+        ctx.status = IoStatus::Pending;
+        ctx.resubmits += 1;
+        ctx.successful = 0;
+        ctx.failed = 0;
+
+        let bio = self.clone();
+        trace_nexus_io!("New read retry: {bio:?}");
+        bio.submit_request();
+    }
+
     /// reference to the channel. The channel contains the specific
     /// per-core data structures.
     #[inline(always)]
@@ -346,6 +741,15 @@ impl<'n> NexusBio<'n> {
         self.ctx_mut().channel.channel_data_mut()
     }
 
+    /// Moves the end of the data appended to the nexus past a write which
+    /// completed successfully.
+    fn retention_appended(&self) {
+        if matches!(self.io_type(), IoType::Write | IoType::WriteZeros) {
+            self.nexus()
+                .retention_appended(self.offset(), self.num_blocks());
+        }
+    }
+
     /// Returns the offset in num blocks where the data partition starts.
     #[inline]
     fn data_ent_offset(&self) -> u64 {
@@ -355,7 +759,7 @@ impl<'n> NexusBio<'n> {
     /// Returns the effictive offset in num blocks where the I/O operation
     /// starts.
     #[inline]
-    fn effective_offset(&self) -> u64 {
+    pub(super) fn effective_offset(&self) -> u64 {
         self.offset() + self.data_ent_offset()
     }
 
@@ -368,18 +772,100 @@ impl<'n> NexusBio<'n> {
         #[cfg(feature = "fault-injection")]
         self.inject_submission_error(hdl)?;
 
-        hdl.readv_blocks(
-            self.iovs_mut(),
-            self.effective_offset(),
-            self.num_blocks(),
-            ReadOptions::None,
-            Self::child_completion,
-            self.as_ptr().cast(),
-        )
+        let read =
+            |iovs: &mut [IoVec], cb: IoCompletionCallback, ctx: *mut c_void| {
+                hdl.readv_blocks(
+                    iovs,
+                    self.effective_offset(),
+                    self.num_blocks(),
+                    ReadOptions::None,
+                    cb,
+                    ctx,
+                )
+            };
+
+        if self.nexus().child_io_timeout().is_none() {
+            return read(
+                self.iovs_mut(),
+                Self::child_completion,
+                self.as_ptr().cast(),
+            );
+        }
+
+        // a timed out read may still complete later on: it reads into a
+        // buffer of its own rather than into the buffers of the nexus read
+        let num_bytes = self.num_blocks() * self.nexus().block_len();
+        let Ok(buf) = DmaBuf::new(num_bytes, self.nexus().alignment()) else {
+            warn!("{self:?}: no buffer for a timed read, reading untimed");
+            return read(
+                self.iovs_mut(),
+                Self::child_completion,
+                self.as_ptr().cast(),
+            );
+        };
+        let mut iovs = [buf.to_io_vec()];
+        self.submit_timed(hdl, Some(buf), |cb, ctx| read(&mut iovs, cb, ctx))
+    }
+
+    /// Copies the data a child read into the given buffer to the
+    /// buffers of the nexus read.
+    fn copy_from_buf(&self, buf: &DmaBuf) {
+        let mut pos = 0;
+        for iov in self.iovs_mut() {
+            let len = iov.len() as usize;
+            iov[..].copy_from_slice(&buf.as_slice()[pos .. pos + len]);
+            pos += len;
+        }
+    }
+
+    /// Submits a child I/O to the given device with the given function,
+    /// passing it the completion callback and context. While the child I/O
+    /// timeout of the nexus is set, the child I/O gets a context of its own,
+    /// tracked by the channel, so that the nexus I/O can be completed
+    /// without it if it times out.
+    #[inline]
+    fn submit_child<F>(
+        &self,
+        hdl: &dyn BlockDeviceHandle,
+        submit: F,
+    ) -> Result<(), CoreError>
+    where
+        F: FnOnce(IoCompletionCallback, *mut c_void) -> Result<(), CoreError>,
+    {
+        if self.nexus().child_io_timeout().is_none() {
+            return submit(Self::child_completion, self.as_ptr().cast());
+        }
+        self.submit_timed(hdl, None, submit)
+    }
+
+    /// Submits a timed child I/O to the given device with the given
+    /// function, the child I/O owning the given buffer until it completes.
+    fn submit_timed<F>(
+        &self,
+        hdl: &dyn BlockDeviceHandle,
+        buf: Option<DmaBuf>,
+        submit: F,
+    ) -> Result<(), CoreError>
+    where
+        F: FnOnce(IoCompletionCallback, *mut c_void) -> Result<(), CoreError>,
+    {
+        let io = Box::into_raw(Box::new(TimedChildIo {
+            bio: Some(self.clone()),
+            buf,
+        }));
+        let mut bio = self.clone();
+        bio.channel_mut().track_child_io(hdl.get_device(), io);
+        submit(TimedChildIo::child_completion, io.cast()).map_err(|err| {
+            bio.channel_mut()
+                .untrack_child_io(hdl.get_device(), io.cast());
+            drop(unsafe { Box::from_raw(io) });
+            err
+        })
     }
 
     /// Submit a Read operation to the next available replica.
     fn __do_readv_one(&mut self) -> Result<(), CoreError> {
+        self.ctx_mut().submitted_at = unsafe { spdk_get_ticks() };
         if let Some(hdl) = self.channel().select_reader() {
             let r = self.submit_read(hdl);
 
@@ -406,7 +892,12 @@ impl<'n> NexusBio<'n> {
                 );
                 r
             } else {
+                let slot = self.channel().find_child_slot(hdl.get_device());
                 self.ctx_mut().in_flight = 1;
+                let submitted_at = self.ctx().submitted_at;
+                if let Some(slot) = slot {
+                    self.channel_mut().account_child_submit(slot, submitted_at);
+                }
                 r
             }
         } else {
@@ -515,13 +1006,15 @@ impl<'n> NexusBio<'n> {
         #[cfg(feature = "fault-injection")]
         self.inject_submission_error(hdl)?;
 
-        hdl.writev_blocks(
-            self.iovs(),
-            self.effective_offset(),
-            self.num_blocks(),
-            Self::child_completion,
-            self.as_ptr().cast(),
-        )
+        self.submit_child(hdl, |cb, ctx| {
+            hdl.writev_blocks(
+                self.iovs(),
+                self.effective_offset(),
+                self.num_blocks(),
+                cb,
+                ctx,
+            )
+        })
     }
 
     #[inline]
@@ -534,12 +1027,14 @@ impl<'n> NexusBio<'n> {
             name = hdl.get_device().device_name()
         );
 
-        hdl.unmap_blocks(
-            self.effective_offset(),
-            self.num_blocks(),
-            Self::child_completion,
-            self.as_ptr().cast(),
-        )
+        self.submit_child(hdl, |cb, ctx| {
+            hdl.unmap_blocks(
+                self.effective_offset(),
+                self.num_blocks(),
+                cb,
+                ctx,
+            )
+        })
     }
 
     #[inline]
@@ -555,12 +1050,14 @@ impl<'n> NexusBio<'n> {
         #[cfg(feature = "fault-injection")]
         self.inject_submission_error(hdl)?;
 
-        hdl.write_zeroes(
-            self.effective_offset(),
-            self.num_blocks(),
-            Self::child_completion,
-            self.as_ptr().cast(),
-        )
+        self.submit_child(hdl, |cb, ctx| {
+            hdl.write_zeroes(
+                self.effective_offset(),
+                self.num_blocks(),
+                cb,
+                ctx,
+            )
+        })
     }
 
     #[inline]
@@ -573,7 +1070,7 @@ impl<'n> NexusBio<'n> {
             name = hdl.get_device().device_name()
         );
 
-        hdl.reset(Self::child_completion, self.as_ptr().cast())
+        self.submit_child(hdl, |cb, ctx| hdl.reset(cb, ctx))
     }
 
     #[inline]
@@ -586,7 +1083,7 @@ impl<'n> NexusBio<'n> {
             name = hdl.get_device().device_name()
         );
 
-        hdl.flush_io(Self::child_completion, self.as_ptr().cast())
+        self.submit_child(hdl, |cb, ctx| hdl.flush_io(cb, ctx))
     }
 
     /// Submit the IO to all underlying children, failing on the first error we
@@ -599,6 +1096,7 @@ impl<'n> NexusBio<'n> {
         // Name of the device which experiences I/O submission failures.
         let mut failed_device = None;
 
+        self.ctx_mut().submitted_at = unsafe { spdk_get_ticks() };
         let result = self.channel().for_each_writer(|h| {
             match self.io_type() {
                 IoType::Write => self.submit_write(h),
@@ -609,9 +1107,7 @@ impl<'n> NexusBio<'n> {
                 // we should never reach here, if we do it is a bug.
                 _ => unreachable!(),
             }
-            .map(|_| {
-                inflight += 1;
-            })
+            .map(|_| inflight += 1)
             .map_err(|err| {
                 error!(
                     "(core: {core} thread: {thread}): IO submission \
@@ -626,6 +1122,12 @@ impl<'n> NexusBio<'n> {
             })
         });
 
+        // the child I/Os were submitted to the first writers, up to the one
+        // which failed
+        let submitted_at = self.ctx().submitted_at;
+        self.channel_mut()
+            .account_writers_submit(inflight as usize, submitted_at);
+
         // Submission errors can also trigger device retire.
         // Such a situation can happen when there is no active I/O in the
         // queues, but error on qpair is observed due to network
@@ -675,6 +1177,127 @@ impl<'n> NexusBio<'n> {
         result
     }
 
+    /// Returns the number of the writers which must complete a write for it
+    /// to be acknowledged, when less than all of them under the quorum write
+    /// policy.
+    fn write_quorum(&self) -> Option<u8> {
+        match self.nexus().write_policy() {
+            NexusWritePolicy::Quorum(q)
+                if (q as usize) < self.channel().num_writers() =>
+            {
+                Some(q)
+            }
+            _ => None,
+        }
+    }
+
+    /// Submits a write to all the writers, to be acknowledged once the
+    /// quorum of them completed it. The data is copied first, so that the
+    /// laggards do not write from the buffers of a write acknowledged
+    /// already.
+    fn submit_quorum_write(&mut self) -> Result<(), CoreError> {
+        let quorum = self.write_quorum().unwrap_or_default();
+        let num_bytes = self.num_blocks() * self.nexus().block_len();
+        let Ok(mut buf) = DmaBuf::new(num_bytes, self.nexus().alignment())
+        else {
+            // wait for all the writers rather than fail the write
+            warn!("{self:?}: no buffer for a quorum write, writing to all");
+            return self.submit_all();
+        };
+        let mut pos = 0;
+        for iov in self.iovs() {
+            let len = iov.len() as usize;
+            buf.as_mut_slice()[pos .. pos + len].copy_from_slice(&iov[..]);
+            pos += len;
+        }
+        let iovs = [buf.to_io_vec()];
+
+        let qw = Box::into_raw(Box::new(QuorumWrite {
+            bio: Some(self.clone()),
+            _buf: buf,
+            laggards: self.channel_mut().laggard_writes(),
+            id: 0,
+            offset: self.offset(),
+            num_blocks: self.num_blocks(),
+            quorum,
+            in_flight: 0,
+            successful: 0,
+            failed: 0,
+            pending: Vec::new(),
+        }));
+        // Name of the device which experiences I/O submission failures.
+        let mut failed_device = None;
+        let mut submitted = 0;
+
+        self.ctx_mut().submitted_at = unsafe { spdk_get_ticks() };
+        let result = self.channel().for_each_writer(|h| {
+            trace_nexus_io!(
+                "Submitting quorum write: {self:?} -> {name}",
+                name = h.get_device().device_name()
+            );
+
+            #[cfg(feature = "fault-injection")]
+            self.inject_submission_error(h)?;
+
+            h.writev_blocks(
+                &iovs,
+                self.effective_offset(),
+                self.num_blocks(),
+                QuorumWrite::child_completion,
+                qw.cast(),
+            )
+            .map(|_| {
+                let qw = unsafe { &mut *qw };
+                qw.in_flight += 1;
+                qw.pending.push(h.get_device().uuid());
+                submitted += 1;
+            })
+            .map_err(|err| {
+                error!(
+                    "{self:?}: quorum write submission to '{dev}' failed \
+                    with error {err:?}",
+                    dev = h.get_device().device_name()
+                );
+                failed_device = Some(h.get_device().device_name());
+                err
+            })
+        });
+
+        let submitted_at = self.ctx().submitted_at;
+        self.channel_mut()
+            .account_writers_submit(submitted, submitted_at);
+
+        if let Some(device) = failed_device {
+            unsafe { (*qw).failed += 1 };
+
+            self.channel_mut().detach_device(&device);
+            self.channel_mut().disconnect_detached_devices(|_| true);
+
+            if let Some(log) = self.fault_device(
+                &device,
+                IoCompletionStatus::IoSubmissionError(
+                    IoSubmissionFailure::Write,
+                ),
+            ) {
+                self.log_io(&log);
+            }
+        }
+
+        self.channel().for_each_io_log(|log| self.log_io(log));
+
+        if submitted == 0 {
+            drop(unsafe { Box::from_raw(qw) });
+            error!(
+                "{self:?}: failing nexus I/O: all child I/O submissions failed"
+            );
+            self.fail();
+        } else {
+            self.ctx_mut().status = IoStatus::Success;
+        }
+
+        result
+    }
+
     /// Logs all write-like operation in the rebuild logs, if any exist.
     #[inline]
     fn log_io(&self, log: &IOLogChannel) {
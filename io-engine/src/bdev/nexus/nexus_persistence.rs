@@ -1,4 +1,4 @@
-use super::{IoMode, Nexus, NexusChild};
+use super::{IoMode, Nexus, NexusChild, NexusRetention};
 use crate::{persistent_store::PersistentStore, sleep::mayastor_sleep};
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
@@ -37,6 +37,12 @@ pub struct NexusInfo {
     pub clean_shutdown: bool,
     /// Information about children.
     pub children: Vec<ChildInfo>,
+    /// Write-once retention of the nexus.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention: Option<NexusRetention>,
+    /// Size of the nexus in bytes, as requested on creation or resize.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
 }
 
 /// Definition of the child information that gets saved in the persistent
@@ -68,6 +74,10 @@ pub(crate) enum PersistOp<'a> {
         healthy: bool,
         predicate: &'a dyn Fn(&NexusInfo) -> bool,
     },
+    /// Save the retention of the nexus.
+    Retention,
+    /// Save the size of the nexus after it is resized.
+    Resize,
     /// Save the clean shutdown variable.
     Shutdown,
 }
@@ -96,6 +106,20 @@ impl<'n> Nexus<'n> {
                 // expect the NexusInfo structure to contain default values.
                 assert!(nexus_info.children.is_empty());
                 assert!(!nexus_info.clean_shutdown);
+                // The retention of a previous instance of the nexus is kept,
+                // as it may not be lifted before it expires.
+                if let Some(previous) = self.load(&persistent_nexus_info).await
+                {
+                    if let Some(retention) = previous.retention {
+                        self.restore_retention(
+                            retention,
+                            previous.clean_shutdown,
+                        );
+                    }
+                }
+                let nexus_info = persistent_nexus_info.inner_mut();
+                nexus_info.retention = self.retention();
+                nexus_info.size = Some(self.req_size());
                 self.children_iter().for_each(|c| {
                     let child_info = ChildInfo {
                         uuid: NexusChild::uuid(c.uri())
@@ -178,6 +202,13 @@ impl<'n> Nexus<'n> {
                 // child state information.
                 // This should only be called when destroying a nexus.
                 nexus_info.clean_shutdown = true;
+                nexus_info.retention = self.retention();
+            }
+            PersistOp::Retention => {
+                nexus_info.retention = self.retention();
+            }
+            PersistOp::Resize => {
+                nexus_info.size = Some(self.req_size());
             }
         }
 
@@ -203,16 +234,34 @@ impl<'n> Nexus<'n> {
         }
     }
 
-    // Saves the nexus info to the store. This is integral to ensuring data
-    // consistency across restarts of Mayastor. Therefore, keep retrying
-    // until successful.
-    async fn save(&self, info: &PersistentNexusInfo) -> Result<(), Error> {
+    /// Key the nexus info is stored with.
+    fn key(&self, info: &PersistentNexusInfo) -> String {
         // If a key has been provided, use it to store the NexusInfo; use the
         // nexus uuid as the key otherwise.
-        let key = match &info.key {
+        match &info.key {
             Some(k) => k.clone(),
             None => self.uuid().to_string(),
-        };
+        }
+    }
+
+    /// Loads the nexus info stored by a previous instance of the nexus, if
+    /// any.
+    async fn load(&self, info: &PersistentNexusInfo) -> Option<NexusInfo> {
+        let value = PersistentStore::get(&self.key(info)).await.ok()?;
+        match serde_json::from_value(value) {
+            Ok(previous) => Some(previous),
+            Err(e) => {
+                warn!("{self:?}: ignoring invalid persisted information: {e}");
+                None
+            }
+        }
+    }
+
+    // Saves the nexus info to the store. This is integral to ensuring data
+    // consistency across restarts of Mayastor. Therefore, keep retrying
+    // until successful.
+    async fn save(&self, info: &PersistentNexusInfo) -> Result<(), Error> {
+        let key = self.key(info);
 
         let mut retry = PersistentStore::retries();
         loop {
@@ -0,0 +1,221 @@
+//! Write-once retention of the nexuses.
+//!
+//! A nexus may be switched to a retention mode until a given time, e.g. for
+//! the volumes of backup targets and audit logs. In the append-only mode,
+//! the blocks below the end of the writes completed may no longer be
+//! written, so the data may only be appended to; in the read-only mode, no
+//! block may be written. The writes, write zeroes and unmaps refused are
+//! failed with the "namespace is write protected" status, which the hosts
+//! do not retry. Until it expires, a retention may only be extended or made
+//! stricter.
+//!
+//! The end of the appended data only moves as the writes complete
+//! successfully, so that a failed write consumes no block, and the writes in
+//! flight together may complete in any order. The time the retention is
+//! given until is turned into a deadline on the monotonic tick counter as
+//! the retention is set or restored, so that a change of the wall clock
+//! neither lifts nor extends it.
+//!
+//! The retention is recorded in the persistent nexus information, and
+//! restored when the nexus is created again with the same key. The end of
+//! the appended data is only saved when the retention is set and when the
+//! nexus is destroyed: after an unclean shutdown, the whole of an
+//! append-only nexus is protected, as the data appended since is unknown.
+
+use std::{
+    sync::atomic::Ordering,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use spdk_rs::libspdk::{spdk_get_ticks, spdk_get_ticks_hz};
+
+use super::{Error, Nexus, PersistOp};
+
+/// Retention mode of a nexus.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RetentionMode {
+    /// Data may only be appended.
+    AppendOnly,
+    /// No data may be written.
+    ReadOnly,
+}
+
+/// Retention of a nexus.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NexusRetention {
+    pub mode: RetentionMode,
+    /// Time the retention is enforced until, in seconds since the UNIX
+    /// epoch.
+    pub retain_until: u64,
+    /// Block the data may be appended from, in the append-only mode.
+    #[serde(default)]
+    pub append_offset: u64,
+}
+
+/// Current time, in seconds since the UNIX epoch.
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+impl NexusRetention {
+    /// Whether the retention is still enforced by the wall clock, only
+    /// relevant until it has a deadline.
+    fn in_force(&self) -> bool {
+        now_secs() < self.retain_until
+    }
+
+    /// Deadline of the retention on the monotonic tick counter.
+    fn deadline(&self) -> u64 {
+        let secs = self.retain_until.saturating_sub(now_secs());
+        let (now, hz) = unsafe { (spdk_get_ticks(), spdk_get_ticks_hz()) };
+        now.saturating_add(secs.saturating_mul(hz))
+    }
+
+    /// Whether the retention in force may be replaced by the given one,
+    /// which must be at least as long and as strict.
+    fn may_become(&self, other: &NexusRetention) -> bool {
+        other.retain_until >= self.retain_until
+            && (other.mode == self.mode
+                || other.mode == RetentionMode::ReadOnly)
+    }
+}
+
+impl<'n> Nexus<'n> {
+    /// Returns the retention of the nexus, if any.
+    pub fn retention(&self) -> Option<NexusRetention> {
+        self.retention.load().map(|r| NexusRetention {
+            append_offset: self.append_offset.load(Ordering::Relaxed),
+            ..r
+        })
+    }
+
+    /// Switches the nexus to a retention mode until the given time, in
+    /// seconds since the UNIX epoch. In the append-only mode, the data may
+    /// be appended from the given block, or from the end of the appended
+    /// data if the nexus is append-only already.
+    pub async fn set_retention(
+        &self,
+        mode: RetentionMode,
+        retain_until: u64,
+        append_offset: u64,
+    ) -> Result<(), Error> {
+        if retain_until <= now_secs() {
+            return Err(Error::InvalidRetention {
+                name: self.name.clone(),
+                reason: "the retention time has passed".to_string(),
+            });
+        }
+
+        let current = self.retention().filter(|_| self.retention_in_force());
+        let appending = matches!(
+            current,
+            Some(NexusRetention {
+                mode: RetentionMode::AppendOnly,
+                ..
+            })
+        );
+        let append_offset = match current {
+            Some(current) if appending => {
+                current.append_offset.max(append_offset)
+            }
+            _ => append_offset,
+        };
+        let retention = NexusRetention {
+            mode,
+            retain_until,
+            append_offset,
+        };
+        if let Some(current) = current {
+            if !current.may_become(&retention) {
+                return Err(Error::RetentionInForce {
+                    name: self.name.clone(),
+                    retain_until: current.retain_until,
+                });
+            }
+        }
+
+        // the writes completing meanwhile may only move the end further
+        if appending {
+            self.append_offset
+                .fetch_max(append_offset, Ordering::Relaxed);
+        } else {
+            self.append_offset.store(append_offset, Ordering::Relaxed);
+        }
+        self.retention_deadline
+            .store(retention.deadline(), Ordering::Relaxed);
+        self.retention.store(Some(retention));
+        info!("{self:?}: retention set to {retention:?}");
+        self.persist(PersistOp::Retention).await
+    }
+
+    /// Restores the retention recorded for a previous instance of the
+    /// nexus, if still in force. The appended data is unknown when the
+    /// previous instance was not shut down cleanly.
+    pub(super) fn restore_retention(
+        &self,
+        retention: NexusRetention,
+        clean_shutdown: bool,
+    ) {
+        if !retention.in_force() {
+            return;
+        }
+        let retention = match (retention.mode, clean_shutdown) {
+            (RetentionMode::AppendOnly, false) => NexusRetention {
+                append_offset: self.num_blocks(),
+                ..retention
+            },
+            _ => retention,
+        };
+        self.append_offset
+            .store(retention.append_offset, Ordering::Relaxed);
+        self.retention_deadline
+            .store(retention.deadline(), Ordering::Relaxed);
+        self.retention.store(Some(retention));
+        warn!("{self:?}: retention restored as {retention:?}");
+    }
+
+    /// Whether the retention of the nexus is still enforced.
+    fn retention_in_force(&self) -> bool {
+        let now = unsafe { spdk_get_ticks() };
+        now < self.retention_deadline.load(Ordering::Relaxed)
+    }
+
+    /// Checks whether the given blocks may be written under the retention
+    /// of the nexus: in the append-only mode, they must start at or past the
+    /// end of the writes completed. Writes in flight together may thus
+    /// overlap, none of them having been acknowledged yet.
+    pub(super) fn retention_allows_write(&self, offset: u64) -> bool {
+        let Some(retention) = self.retention.load() else {
+            return true;
+        };
+        if !self.retention_in_force() {
+            return true;
+        }
+        match retention.mode {
+            RetentionMode::ReadOnly => false,
+            RetentionMode::AppendOnly => {
+                offset >= self.append_offset.load(Ordering::Relaxed)
+            }
+        }
+    }
+
+    /// Moves the end of the appended data past the given blocks, once they
+    /// were written successfully under an append-only retention.
+    pub(super) fn retention_appended(&self, offset: u64, num_blocks: u64) {
+        if matches!(
+            self.retention.load(),
+            Some(NexusRetention {
+                mode: RetentionMode::AppendOnly,
+                ..
+            })
+        ) && self.retention_in_force()
+        {
+            self.append_offset
+                .fetch_max(offset + num_blocks, Ordering::Relaxed);
+        }
+    }
+}
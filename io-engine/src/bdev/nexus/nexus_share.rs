@@ -1,11 +1,21 @@
 use crate::bdev::PtplFileOps;
 use async_trait::async_trait;
 use snafu::ResultExt;
-use std::pin::Pin;
+use std::{pin::Pin, time::Instant};
 
 use super::{nexus_err, Error, NbdDisk, Nexus, NexusTarget};
 
-use crate::core::{NvmfShareProps, Protocol, PtplProps, Share, UpdateProps};
+use crate::{
+    core::{
+        BdevFence,
+        NvmfShareProps,
+        Protocol,
+        PtplProps,
+        Share,
+        UpdateProps,
+    },
+    subsys::{NvmfControllerInfo, NvmfSubsystem},
+};
 
 ///
 /// The sharing of the nexus is different compared to regular bdevs
@@ -39,6 +49,7 @@ impl<'n> Share for Nexus<'n> {
             Some(Protocol::Off) | None => {
                 info!("{:?}: sharing NVMF target...", self);
 
+                let start = Instant::now();
                 let name = self.name.clone();
                 self.as_mut()
                     .pin_bdev_mut()
@@ -49,7 +60,15 @@ impl<'n> Share for Nexus<'n> {
                     })?;
 
                 let uri = self.share_uri().unwrap();
-                info!("{:?}: shared NVMF target as '{}'", self, uri);
+                info!(
+                    volume = %self.uuid(),
+                    nqn = self.nqn().as_deref().unwrap_or_default(),
+                    op = "share",
+                    duration_ms = start.elapsed().as_millis() as u64,
+                    "{:?}: shared NVMF target as '{}'",
+                    self,
+                    uri
+                );
                 uri
             }
             Some(Protocol::Nvmf) => {
@@ -75,19 +94,8 @@ impl<'n> Share for Nexus<'n> {
     }
 
     /// TODO
-    async fn unshare(mut self: Pin<&mut Self>) -> Result<(), Self::Error> {
-        info!("{:?}: unsharing nexus bdev...", self);
-
-        let name = self.name.clone();
-        self.as_mut().pin_bdev_mut().unshare().await.context(
-            nexus_err::UnshareNexus {
-                name,
-            },
-        )?;
-
-        info!("{:?}: unshared nexus bdev", self);
-
-        Ok(())
+    async fn unshare(self: Pin<&mut Self>) -> Result<(), Self::Error> {
+        self.unshare_within(None).await
     }
 
     /// TODO
@@ -104,6 +112,10 @@ impl<'n> Share for Nexus<'n> {
         unsafe { self.bdev().allowed_hosts() }
     }
 
+    fn nvmf_controllers(&self) -> Vec<NvmfControllerInfo> {
+        unsafe { self.bdev().nvmf_controllers() }
+    }
+
     /// TODO
     fn bdev_uri(&self) -> Option<url::Url> {
         unsafe { self.bdev().bdev_uri() }
@@ -125,6 +137,11 @@ impl From<&NexusTarget> for Protocol {
 }
 
 impl<'n> Nexus<'n> {
+    /// NQN of the subsystem the nexus is shared with, if any.
+    fn nqn(&self) -> Option<String> {
+        NvmfSubsystem::nqn_lookup(&self.name).map(|ss| ss.get_nqn())
+    }
+
     /// TODO
     pub async fn share(
         self: Pin<&mut Self>,
@@ -186,7 +203,7 @@ impl<'n> Nexus<'n> {
                         self.nvme_params.min_cntlid,
                         self.nvme_params.max_cntlid,
                     )))
-                    .with_ana(true)
+                    .with_ana(self.ana_reporting())
                     .with_allowed_hosts(allowed_hosts)
                     .with_ptpl(self.create_ptpl()?);
                 let uri = self.as_mut().share_nvmf(Some(props)).await?;
@@ -200,8 +217,49 @@ impl<'n> Nexus<'n> {
         }
     }
 
+    /// Unshares the nexus bdev, under the given fence of the operation in
+    /// progress on the nexus which the unshare is part of, if any.
+    async fn unshare_within(
+        mut self: Pin<&mut Self>,
+        owner: Option<&BdevFence>,
+    ) -> Result<(), Error> {
+        info!("{:?}: unsharing nexus bdev...", self);
+
+        let start = Instant::now();
+        let nqn = self.nqn();
+        let name = self.name.clone();
+        self.as_mut()
+            .pin_bdev_mut()
+            .unshare_within(owner)
+            .await
+            .context(nexus_err::UnshareNexus {
+                name,
+            })?;
+
+        info!(
+            volume = %self.uuid(),
+            nqn = nqn.as_deref().unwrap_or_default(),
+            op = "unshare",
+            duration_ms = start.elapsed().as_millis() as u64,
+            "{:?}: unshared nexus bdev",
+            self
+        );
+
+        Ok(())
+    }
+
     /// TODO
-    pub async fn unshare_nexus(mut self: Pin<&mut Self>) -> Result<(), Error> {
+    pub async fn unshare_nexus(self: Pin<&mut Self>) -> Result<(), Error> {
+        self.unshare_nexus_within(None).await
+    }
+
+    /// Unshares the nexus and destroys its target, under the given fence of
+    /// the operation in progress on the nexus which the unshare is part of,
+    /// if any.
+    pub(crate) async fn unshare_nexus_within(
+        mut self: Pin<&mut Self>,
+        owner: Option<&BdevFence>,
+    ) -> Result<(), Error> {
         match unsafe { self.as_mut().get_unchecked_mut().nexus_target.take() } {
             Some(NexusTarget::NbdDisk(disk)) => {
                 info!("{:?}: destroying NBD device target...", self);
@@ -216,7 +274,12 @@ impl<'n> Nexus<'n> {
             }
         }
 
-        self.as_mut().unshare().await
+        self.as_mut().unshare_within(owner).await
+    }
+
+    /// Whether the nexus was published over NVMe-oF.
+    pub(crate) fn is_nvmf_target(&self) -> bool {
+        matches!(self.nexus_target, Some(NexusTarget::NexusNvmfTarget))
     }
 
     /// TODO
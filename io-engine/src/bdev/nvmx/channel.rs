@@ -22,6 +22,7 @@ use crate::{
 };
 
 use super::{
+    congestion::CongestionControl,
     nvme_bdev_running_config,
     NvmeControllerState,
     PollGroup,
@@ -82,6 +83,8 @@ pub struct NvmeIoChannelInner<'a> {
         std::sync::Arc<parking_lot::Mutex<crate::bdev::NvmeController<'a>>>,
     >,
     num_pending_ios: u64,
+    /// Limit of the commands in flight, when congestion control is enabled.
+    congestion: Option<CongestionControl>,
 
     // Flag to indicate the shutdown state of the channel.
     // We need such a flag to differentiate between channel reset and shutdown.
@@ -121,10 +124,56 @@ impl NvmeIoChannelInner<'_> {
         self.qpair.take()
     }
 
+    #[inline(always)]
+    pub(crate) fn congestion_mut(&mut self) -> Option<&mut CongestionControl> {
+        self.congestion.as_mut()
+    }
+
+    /// Submits the deferred commands the congestion limit admits.
+    pub(crate) fn dispatch_deferred(&mut self) {
+        let Some(qpair) = self.qpair.as_ref().map(QPair::as_ptr) else {
+            return;
+        };
+        match self.congestion.as_mut() {
+            Some(cc) if cc.start_dispatch() => {}
+            _ => return,
+        }
+
+        // a deferred command whose submission fails completes right away and
+        // gets back here, where the next ones must not be submitted again
+        while let Some(io) = self
+            .congestion
+            .as_mut()
+            .and_then(CongestionControl::next_deferred)
+        {
+            io(Some(qpair));
+        }
+
+        if let Some(cc) = self.congestion.as_mut() {
+            cc.stop_dispatch();
+        }
+    }
+
+    /// Fails the commands deferred by the congestion control.
+    fn fail_deferred(&mut self) {
+        let deferred = match self.congestion.as_mut() {
+            Some(cc) => cc.take_deferred(),
+            None => return,
+        };
+        if !deferred.is_empty() {
+            trace!("failing {} deferred I/O requests", deferred.len());
+        }
+        for io in deferred {
+            io(None);
+        }
+    }
+
     /// Reset channel, making it unusable till reinitialize() is called.
     pub fn reset(&mut self) -> i32 {
         // Remove qpair and trigger its deallocation via drop().
-        match self.remove_qpair() {
+        let qpair = self.remove_qpair();
+        self.fail_deferred();
+        match qpair {
             Some(qpair) => {
                 trace!(
                     "reset: dropping qpair {:p} ({}) I/O requests pending)",
@@ -434,6 +483,7 @@ impl NvmeControllerIoChannel {
             device,
             ctrl: Some(carc),
             num_pending_ios: 0,
+            congestion: CongestionControl::new(&cname),
         });
 
         nvme_channel.inner = Box::into_raw(inner);
@@ -454,6 +504,7 @@ impl NvmeControllerIoChannel {
             let mut inner = unsafe { Box::from_raw(ch.inner) };
 
             let qpair = inner.remove_qpair();
+            inner.fail_deferred();
 
             // Stop the poller and do extra handling for I/O qpair, as it needs
             // to be detached from the poller prior poller
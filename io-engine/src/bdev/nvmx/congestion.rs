@@ -0,0 +1,366 @@
+//! Congestion control of the I/O towards the remote replicas.
+//!
+//! The number of read and write commands in flight on the qpair of every I/O
+//! channel is limited, much like the congestion window of TCP: the limit
+//! shrinks when commands fail or when their latency rises well above its
+//! baseline and past the target, and grows back by one command for every
+//! window of completions otherwise. The commands in excess wait in the
+//! channel, in order, rather than in the queues of a slow replica path, so
+//! that they do not delay the failover once the controller is reset.
+//!
+//! The congestion control is enabled when a latency target is given.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use futures::FutureExt;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+use spdk_rs::libspdk::{spdk_get_ticks_hz, spdk_nvme_qpair};
+
+use crate::{
+    core::{Cores, MayastorEnvironment},
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+};
+
+/// Command deferred by the congestion control, submitted to the given qpair
+/// or failed when the qpair is gone.
+pub(crate) type DeferredIo = Box<dyn FnOnce(Option<*mut spdk_nvme_qpair>)>;
+
+/// Lowest limit of the commands in flight.
+const MIN_LIMIT: u32 = 4;
+/// Highest limit of the commands in flight, where the limit starts.
+const MAX_LIMIT: u32 = 256;
+/// Share of the limit kept when congestion is observed, in percent.
+const DECREASE_PCT: u32 = 75;
+/// Latency relative to the baseline past which a path is congested.
+const BASELINE_FACTOR: u64 = 4;
+
+/// Congestion control state of the I/O channel of a controller.
+#[derive(Debug, Clone, Serialize)]
+pub struct NvmeCongestion {
+    /// Name of the controller.
+    pub controller: String,
+    /// Core of the I/O channel.
+    pub core: u32,
+    /// Identifier of the I/O channel, unique among the channels.
+    pub channel: u64,
+    /// Limit of the read and write commands in flight.
+    pub limit: u32,
+    /// Average latency of the commands, in microseconds.
+    pub latency_us: u64,
+    /// Baseline latency of the commands, in microseconds.
+    pub base_latency_us: u64,
+    /// Number of times the limit was decreased.
+    pub decreases: u64,
+    /// Number of commands which waited for the limit.
+    pub deferred: u64,
+}
+
+/// State of the I/O channels as of the end of their last window of
+/// completions, by channel identifier.
+static CONGESTION: Lazy<Mutex<HashMap<u64, NvmeCongestion>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Identifier of the next I/O channel.
+static NEXT_CHANNEL: AtomicU64 = AtomicU64::new(1);
+
+/// Returns the congestion control state of the I/O channels as of the end of
+/// their last window of completions.
+pub fn nvme_congestion() -> Vec<NvmeCongestion> {
+    let mut states = CONGESTION.lock().values().cloned().collect::<Vec<_>>();
+    states.sort_by(|a, b| {
+        (&a.controller, a.core, a.channel).cmp(&(
+            &b.controller,
+            b.core,
+            b.channel,
+        ))
+    });
+    states
+}
+
+/// Converts ticks into microseconds.
+fn ticks_to_us(ticks: u64) -> u64 {
+    let hz = unsafe { spdk_get_ticks_hz() }.max(1);
+    (ticks as u128 * 1_000_000 / hz as u128) as u64
+}
+
+/// Congestion control of the read and write commands of an I/O channel.
+pub(crate) struct CongestionControl {
+    state: NvmeCongestion,
+    /// Latency target, in ticks.
+    target: u64,
+    /// Average latency, in ticks.
+    latency: u64,
+    /// Baseline latency, in ticks.
+    base_latency: u64,
+    in_flight: u32,
+    /// Commands completed during the current window.
+    completions: u32,
+    /// A command failed during the current window.
+    failed: bool,
+    /// A command waited for the limit during the current window.
+    saturated: bool,
+    dispatching: bool,
+    deferred: VecDeque<DeferredIo>,
+}
+
+impl CongestionControl {
+    /// Returns the congestion control of a new I/O channel of the given
+    /// controller, if enabled.
+    pub(crate) fn new(controller: &str) -> Option<Self> {
+        let target =
+            MayastorEnvironment::global_or_default().nvme_latency_target_us?;
+        let hz = unsafe { spdk_get_ticks_hz() };
+        let target = (target as u128 * hz as u128 / 1_000_000) as u64;
+        Some(Self::with_target(controller, Cores::current(), target))
+    }
+
+    /// Returns the congestion control of a new I/O channel of the given
+    /// controller on the given core, with a latency target in ticks.
+    fn with_target(controller: &str, core: u32, target: u64) -> Self {
+        let cc = Self {
+            state: NvmeCongestion {
+                controller: controller.to_string(),
+                core,
+                channel: NEXT_CHANNEL.fetch_add(1, Ordering::Relaxed),
+                limit: MAX_LIMIT,
+                latency_us: 0,
+                base_latency_us: 0,
+                decreases: 0,
+                deferred: 0,
+            },
+            target,
+            latency: 0,
+            base_latency: 0,
+            in_flight: 0,
+            completions: 0,
+            failed: false,
+            saturated: false,
+            dispatching: false,
+            deferred: VecDeque::new(),
+        };
+        cc.publish();
+        cc
+    }
+
+    /// Publishes the state of the congestion control.
+    fn publish(&self) {
+        CONGESTION
+            .lock()
+            .insert(self.state.channel, self.state.clone());
+    }
+
+    /// Admits a command unless the limit is reached or commands are already
+    /// waiting.
+    pub(crate) fn admit(&mut self) -> bool {
+        if self.deferred.is_empty() && self.in_flight < self.state.limit {
+            self.in_flight += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Defers a command until the limit admits it.
+    pub(crate) fn defer(&mut self, io: DeferredIo) {
+        self.saturated = true;
+        self.state.deferred += 1;
+        self.deferred.push_back(io);
+    }
+
+    /// Releases the admission of a command whose submission failed.
+    pub(crate) fn cancel(&mut self) {
+        self.in_flight = self.in_flight.saturating_sub(1);
+    }
+
+    /// Accounts for the completion of an admitted command, adjusting the
+    /// limit at the end of every window.
+    pub(crate) fn completed(&mut self, latency: u64, failed: bool) {
+        self.in_flight = self.in_flight.saturating_sub(1);
+        self.failed |= failed;
+
+        self.latency = if self.latency == 0 {
+            latency
+        } else {
+            (self.latency * 7 + latency) / 8
+        };
+        // the baseline follows the lowest latency, slowly rising back when
+        // the path gets slower for good
+        if self.base_latency == 0 || latency < self.base_latency {
+            self.base_latency = latency;
+        } else {
+            self.base_latency += (latency - self.base_latency) >> 10;
+        }
+
+        self.completions += 1;
+        if self.completions < self.state.limit {
+            return;
+        }
+
+        let congested = self.failed
+            || (self.latency > self.target
+                && self.latency > self.base_latency * BASELINE_FACTOR);
+        let limit = if congested {
+            (self.state.limit * DECREASE_PCT / 100).max(MIN_LIMIT)
+        } else if self.saturated {
+            (self.state.limit + 1).min(MAX_LIMIT)
+        } else {
+            self.state.limit
+        };
+        self.completions = 0;
+        self.failed = false;
+        self.saturated = false;
+
+        if limit < self.state.limit {
+            self.state.decreases += 1;
+        }
+        self.state.limit = limit;
+        self.state.latency_us = ticks_to_us(self.latency);
+        self.state.base_latency_us = ticks_to_us(self.base_latency);
+        self.publish();
+    }
+
+    /// Starts submitting the deferred commands, unless they are already
+    /// being submitted further up the stack.
+    pub(crate) fn start_dispatch(&mut self) -> bool {
+        !std::mem::replace(&mut self.dispatching, true)
+    }
+
+    /// Stops submitting the deferred commands.
+    pub(crate) fn stop_dispatch(&mut self) {
+        self.dispatching = false;
+    }
+
+    /// Returns the next deferred command the limit admits.
+    pub(crate) fn next_deferred(&mut self) -> Option<DeferredIo> {
+        if self.in_flight >= self.state.limit {
+            return None;
+        }
+        let io = self.deferred.pop_front()?;
+        self.in_flight += 1;
+        Some(io)
+    }
+
+    /// Takes all the deferred commands, to fail them.
+    pub(crate) fn take_deferred(&mut self) -> VecDeque<DeferredIo> {
+        std::mem::take(&mut self.deferred)
+    }
+}
+
+impl Drop for CongestionControl {
+    fn drop(&mut self) {
+        CONGESTION.lock().remove(&self.state.channel);
+    }
+}
+
+/// Registers the JSON-RPC methods of the congestion control.
+pub(super) fn register_rpc_methods() {
+    // limits of the commands in flight towards the remote replicas,
+    // per controller and core
+    jsonrpc_register::<(), _, _, JsonRpcError>(
+        "mayastor_nvme_congestion",
+        |_| async move { Ok(nvme_congestion()) }.boxed_local(),
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Returns the published states of the channels of a controller.
+    fn states(controller: &str) -> Vec<NvmeCongestion> {
+        nvme_congestion()
+            .into_iter()
+            .filter(|s| s.controller == controller)
+            .collect()
+    }
+
+    /// Admits and completes a window of commands with the given latency.
+    fn window(cc: &mut CongestionControl, latency: u64, failed: bool) {
+        let limit = cc.state.limit;
+        for _ in 0 .. limit {
+            assert!(cc.admit());
+        }
+        for _ in 0 .. limit {
+            cc.completed(latency, failed);
+        }
+    }
+
+    #[test]
+    fn congestion_channels() {
+        let cc1 = CongestionControl::with_target("cc_channels", 0, 100);
+        let cc2 = CongestionControl::with_target("cc_channels", 0, 100);
+        assert_ne!(cc1.state.channel, cc2.state.channel);
+        assert_eq!(states("cc_channels").len(), 2);
+
+        // the channels left on the core are still reported
+        drop(cc1);
+        let states = states("cc_channels");
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].channel, cc2.state.channel);
+
+        drop(cc2);
+        assert!(nvme_congestion()
+            .iter()
+            .all(|s| s.controller != "cc_channels"));
+    }
+
+    #[test]
+    fn congestion_limit() {
+        let mut cc = CongestionControl::with_target("cc_limit", 0, 100);
+        assert_eq!(cc.state.limit, MAX_LIMIT);
+
+        // failures shrink the limit, down to the lowest one
+        window(&mut cc, 10, true);
+        assert_eq!(cc.state.limit, MAX_LIMIT * DECREASE_PCT / 100);
+        assert_eq!(cc.state.decreases, 1);
+        for _ in 0 .. 32 {
+            window(&mut cc, 10, true);
+        }
+        assert_eq!(cc.state.limit, MIN_LIMIT);
+
+        // the limit only grows back while commands wait for it
+        window(&mut cc, 10, false);
+        assert_eq!(cc.state.limit, MIN_LIMIT);
+        for _ in 0 .. MIN_LIMIT {
+            assert!(cc.admit());
+        }
+        assert!(!cc.admit());
+        cc.defer(Box::new(|_| {}));
+        assert_eq!(cc.state.deferred, 1);
+        assert!(cc.next_deferred().is_none());
+        for _ in 0 .. MIN_LIMIT {
+            cc.completed(10, false);
+        }
+        assert_eq!(cc.state.limit, MIN_LIMIT + 1);
+        assert!(cc.next_deferred().is_some());
+        assert_eq!(states("cc_limit")[0].limit, MIN_LIMIT + 1);
+    }
+
+    #[test]
+    fn congestion_latency() {
+        let mut cc = CongestionControl::with_target("cc_latency", 0, 1000);
+
+        // a latency past the target but close to the baseline is not
+        // congestion
+        window(&mut cc, 2000, false);
+        assert_eq!(cc.state.limit, MAX_LIMIT);
+
+        // nor is one well above the baseline but within the target
+        let mut cc = CongestionControl::with_target("cc_latency", 0, 1000);
+        window(&mut cc, 10, false);
+        window(&mut cc, 500, false);
+        assert_eq!(cc.state.limit, MAX_LIMIT);
+
+        // a latency past both is
+        for _ in 0 .. 4 {
+            window(&mut cc, 5000, false);
+        }
+        assert!(cc.state.limit < MAX_LIMIT);
+        assert!(cc.state.decreases > 0);
+    }
+}
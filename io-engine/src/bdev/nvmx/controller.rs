@@ -1060,6 +1060,7 @@ pub(crate) mod options {
         host_nqn: Option<String>,
         keep_alive_timeout_ms: Option<u32>,
         transport_retry_count: Option<u8>,
+        src_addr: Option<String>,
     }
 
     #[allow(dead_code)]
@@ -1105,6 +1106,12 @@ pub(crate) mod options {
             self
         }
 
+        /// Sets the address to connect to the target from.
+        pub fn with_src_addr<T: Into<String>>(mut self, src_addr: T) -> Self {
+            self.src_addr = Some(src_addr.into());
+            self
+        }
+
         /// Builder to override default values
         pub fn build(self) -> NvmeControllerOpts {
             let mut opts = NvmeControllerOpts::default();
@@ -1133,6 +1140,10 @@ pub(crate) mod options {
                 copy_str_with_null(&host_nqn, &mut opts.0.hostnqn);
             }
 
+            if let Some(src_addr) = self.src_addr {
+                copy_str_with_null(&src_addr, &mut opts.0.src_addr);
+            }
+
             opts
         }
     }
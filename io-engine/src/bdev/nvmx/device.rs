@@ -2,9 +2,11 @@ use async_trait::async_trait;
 use futures::channel::oneshot;
 use nix::errno::Errno;
 use parking_lot::Mutex;
-use std::{convert::From, sync::Arc};
+use std::{convert::From, ffi::CStr, sync::Arc};
 use uuid::Uuid;
 
+use spdk_rs::libspdk::spdk_nvme_ctrlr_get_transport_id;
+
 use crate::{
     bdev::nvmx::{
         controller_inner::SpdkNvmeController,
@@ -208,6 +210,30 @@ impl BlockDevice for NvmeBlockDevice {
         }
     }
 
+    fn max_transfer_size(&self) -> Option<u64> {
+        Some(self.ns.max_io_xfer_size())
+    }
+
+    fn pi_type(&self) -> u32 {
+        self.ns.pi_type()
+    }
+
+    fn transport(&self) -> String {
+        let Some(carc) = NVME_CONTROLLERS.lookup_by_name(&self.name) else {
+            return String::from("unknown");
+        };
+        let ctrlr = carc.lock().ctrlr_as_ptr();
+        if ctrlr.is_null() {
+            return String::from("unknown");
+        }
+        unsafe {
+            let trid = spdk_nvme_ctrlr_get_transport_id(ctrlr);
+            CStr::from_ptr(&(*trid).trstring[0])
+                .to_string_lossy()
+                .to_lowercase()
+        }
+    }
+
     async fn io_stats(&self) -> Result<BlockDeviceIoStats, CoreError> {
         let carc = NVME_CONTROLLERS.lookup_by_name(&self.name).ok_or(
             CoreError::BdevNotFound {
@@ -15,12 +15,14 @@ use spdk_rs::{
         iovec,
         nvme_cmd_cdw10_get,
         spdk_get_io_channel,
+        spdk_get_ticks,
         spdk_io_channel,
         spdk_nvme_cmd,
         spdk_nvme_cpl,
         spdk_nvme_ctrlr_cmd_admin_raw,
         spdk_nvme_ctrlr_cmd_io_raw,
         spdk_nvme_dsm_range,
+        spdk_nvme_ns,
         spdk_nvme_ns_cmd_compare,
         spdk_nvme_ns_cmd_comparev,
         spdk_nvme_ns_cmd_dataset_management,
@@ -30,6 +32,7 @@ use spdk_rs::{
         spdk_nvme_ns_cmd_write,
         spdk_nvme_ns_cmd_write_zeroes,
         spdk_nvme_ns_cmd_writev,
+        spdk_nvme_qpair,
         SPDK_NVME_SC_INTERNAL_DEVICE_ERROR,
     },
     nvme_admin_opc,
@@ -63,6 +66,7 @@ use crate::{
         IoCompletionCallback,
         IoCompletionCallbackArg,
         IoCompletionStatus,
+        IoSubmissionFailure,
         IoType,
         Reactors,
         ReadOptions,
@@ -96,6 +100,9 @@ struct NvmeIoCtx {
     iov_offset: u64,
     op: IoType,
     num_blocks: u64,
+    /// Ticks at which the command was submitted, when admitted by the
+    /// congestion control.
+    submitted_at: u64,
     channel: *mut spdk_io_channel,
     #[cfg(feature = "fault-injection")]
     inj_op: InjectIoCtx,
@@ -167,6 +174,38 @@ impl NvmeDeviceHandle {
         })
     }
 
+    /// Submits a read or write command, deferring it while the congestion
+    /// limit of the channel is reached.
+    fn submit_or_defer(
+        &self,
+        inner: &mut NvmeIoChannelInner,
+        bio: *mut NvmeIoCtx,
+        offset_blocks: u64,
+        flags: u32,
+    ) -> i32 {
+        let ns = self.ns.as_ptr();
+        let qpair = unsafe { inner.qpair_ptr() };
+        let Some(cc) = inner.congestion_mut() else {
+            return unsafe { submit_rw(ns, qpair, bio, offset_blocks, flags) };
+        };
+
+        if !cc.admit() {
+            let ns = Arc::clone(&self.ns);
+            cc.defer(Box::new(move |qpair| {
+                submit_deferred(&ns, qpair, bio, offset_blocks, flags)
+            }));
+            return 0;
+        }
+
+        unsafe { (*bio).submitted_at = spdk_get_ticks() };
+        let rc = unsafe { submit_rw(ns, qpair, bio, offset_blocks, flags) };
+        if rc < 0 {
+            unsafe { (*bio).submitted_at = 0 };
+            cc.cancel();
+        }
+        rc
+    }
+
     // Create and perform a synchronous connect.
     pub fn create(
         name: &str,
@@ -336,8 +375,21 @@ extern "C" fn nvme_queued_next_sge(
 /// Notify the caller and deallocate Nvme IO context.
 #[inline]
 fn complete_nvme_command(ctx: *mut NvmeIoCtx, cpl: *const spdk_nvme_cpl) {
+    let status = if nvme_cpl_succeeded(cpl) {
+        IoCompletionStatus::Success
+    } else {
+        IoCompletionStatus::from(NvmeStatus::from(cpl))
+    };
+
+    finish_nvme_command(ctx, status);
+}
+
+/// Notify the caller of the status of a command, deallocate Nvme IO context
+/// and submit the commands deferred by the congestion control.
+#[inline]
+fn finish_nvme_command(ctx: *mut NvmeIoCtx, status: IoCompletionStatus) {
     let io_ctx = unsafe { &mut *ctx };
-    let op_succeeded = nvme_cpl_succeeded(cpl);
+    let op_succeeded = status == IoCompletionStatus::Success;
     let inner = NvmeIoChannel::inner_from_channel(io_ctx.channel);
 
     // Update I/O statistics in case the operation succeeded.
@@ -346,6 +398,14 @@ fn complete_nvme_command(ctx: *mut NvmeIoCtx, cpl: *const spdk_nvme_cpl) {
         stats_controller.account_block_io(io_ctx.op, 1, io_ctx.num_blocks);
     }
 
+    // Feed the latency of admitted commands to the congestion control.
+    if io_ctx.submitted_at != 0 {
+        if let Some(cc) = inner.congestion_mut() {
+            let latency = unsafe { spdk_get_ticks() } - io_ctx.submitted_at;
+            cc.completed(latency, !op_succeeded);
+        }
+    }
+
     // Adjust the number of active I/O operations in case operation is
     // accountable.
     match io_ctx.op {
@@ -353,19 +413,111 @@ fn complete_nvme_command(ctx: *mut NvmeIoCtx, cpl: *const spdk_nvme_cpl) {
         _ => inner.discard_io(),
     }
 
-    // Invoke caller's callback and free I/O context.
-    let status = if op_succeeded {
-        IoCompletionStatus::Success
-    } else {
-        IoCompletionStatus::from(NvmeStatus::from(cpl))
-    };
-
     #[cfg(feature = "fault-injection")]
     let status = inject_completion_error(&io_ctx.inj_op, status);
 
+    // Invoke caller's callback and free I/O context.
     (io_ctx.cb)(&*inner.device, status, io_ctx.cb_arg);
 
     free_nvme_io_ctx(ctx);
+
+    inner.dispatch_deferred();
+}
+
+/// Submits the read or write command of an I/O context.
+#[inline]
+unsafe fn submit_rw(
+    ns: *mut spdk_nvme_ns,
+    qpair: *mut spdk_nvme_qpair,
+    bio: *mut NvmeIoCtx,
+    offset_blocks: u64,
+    flags: u32,
+) -> i32 {
+    let io_ctx = &*bio;
+    let num_blocks = io_ctx.num_blocks as u32;
+
+    match (io_ctx.op, io_ctx.iovcnt) {
+        (IoType::Read, 1) => spdk_nvme_ns_cmd_read(
+            ns,
+            qpair,
+            (*io_ctx.iov).iov_base,
+            offset_blocks,
+            num_blocks,
+            Some(nvme_io_done),
+            bio as *mut c_void,
+            flags,
+        ),
+        (IoType::Read, _) => spdk_nvme_ns_cmd_readv(
+            ns,
+            qpair,
+            offset_blocks,
+            num_blocks,
+            Some(nvme_io_done),
+            bio as *mut c_void,
+            flags,
+            Some(nvme_queued_reset_sgl),
+            Some(nvme_queued_next_sge),
+        ),
+        (_, 1) => spdk_nvme_ns_cmd_write(
+            ns,
+            qpair,
+            (*io_ctx.iov).iov_base,
+            offset_blocks,
+            num_blocks,
+            Some(nvme_io_done),
+            bio as *mut c_void,
+            flags,
+        ),
+        _ => spdk_nvme_ns_cmd_writev(
+            ns,
+            qpair,
+            offset_blocks,
+            num_blocks,
+            Some(nvme_writev_done),
+            bio as *mut c_void,
+            flags,
+            Some(nvme_queued_reset_sgl),
+            Some(nvme_queued_next_sge),
+        ),
+    }
+}
+
+/// Submits a read or write command deferred by the congestion control,
+/// failing it when the qpair is gone or the submission fails.
+fn submit_deferred(
+    ns: &NvmeNamespace,
+    qpair: Option<*mut spdk_nvme_qpair>,
+    bio: *mut NvmeIoCtx,
+    offset_blocks: u64,
+    flags: u32,
+) {
+    let io_ctx = unsafe { &mut *bio };
+
+    if let Some(qpair) = qpair {
+        io_ctx.submitted_at = unsafe { spdk_get_ticks() };
+        let rc =
+            unsafe { submit_rw(ns.as_ptr(), qpair, bio, offset_blocks, flags) };
+        if rc >= 0 {
+            return;
+        }
+
+        error!(
+            "deferred {:?} I/O submission failed: {}",
+            io_ctx.op,
+            Errno::from_i32(-rc)
+        );
+        io_ctx.submitted_at = 0;
+        let inner = NvmeIoChannel::inner_from_channel(io_ctx.channel);
+        if let Some(cc) = inner.congestion_mut() {
+            cc.cancel();
+        }
+    }
+
+    let failure = match io_ctx.op {
+        IoType::Read => IoSubmissionFailure::Read,
+        _ => IoSubmissionFailure::Write,
+    };
+    finish_nvme_command(bio, IoCompletionStatus::IoSubmissionError(failure));
 }
 
 /// Completion handler for vectored write requests.
@@ -764,6 +916,7 @@ impl BlockDeviceHandle for NvmeDeviceHandle {
                 channel,
                 op: IoType::Read,
                 num_blocks,
+                submitted_at: 0,
                 #[cfg(feature = "fault-injection")]
                 inj_op: InjectIoCtx::with_iovs(
                     FaultDomain::BlockDevice,
@@ -781,34 +934,7 @@ impl BlockDeviceHandle for NvmeDeviceHandle {
         #[cfg(feature = "fault-injection")]
         inject_submission_error(unsafe { &(*bio).inj_op })?;
 
-        let rc = if iovs.len() == 1 {
-            unsafe {
-                spdk_nvme_ns_cmd_read(
-                    self.ns.as_ptr(),
-                    inner.qpair_ptr(),
-                    iovs[0].as_mut_ptr(),
-                    offset_blocks,
-                    num_blocks as u32,
-                    Some(nvme_io_done),
-                    bio as *mut c_void,
-                    flags,
-                )
-            }
-        } else {
-            unsafe {
-                spdk_nvme_ns_cmd_readv(
-                    self.ns.as_ptr(),
-                    inner.qpair_ptr(),
-                    offset_blocks,
-                    num_blocks as u32,
-                    Some(nvme_io_done),
-                    bio as *mut c_void,
-                    flags,
-                    Some(nvme_queued_reset_sgl),
-                    Some(nvme_queued_next_sge),
-                )
-            }
-        };
+        let rc = self.submit_or_defer(inner, bio, offset_blocks, flags);
 
         if rc < 0 {
             Err(CoreError::ReadDispatch {
@@ -850,6 +976,7 @@ impl BlockDeviceHandle for NvmeDeviceHandle {
                 channel,
                 op: IoType::Write,
                 num_blocks,
+                submitted_at: 0,
                 #[cfg(feature = "fault-injection")]
                 inj_op: InjectIoCtx::with_iovs(
                     FaultDomain::BlockDevice,
@@ -867,34 +994,8 @@ impl BlockDeviceHandle for NvmeDeviceHandle {
         #[cfg(feature = "fault-injection")]
         inject_submission_error(unsafe { &(*bio).inj_op })?;
 
-        let rc = if iovs.len() == 1 {
-            unsafe {
-                spdk_nvme_ns_cmd_write(
-                    self.ns.as_ptr(),
-                    inner.qpair_ptr(),
-                    iovs[0].as_ptr() as *mut _,
-                    offset_blocks,
-                    num_blocks as u32,
-                    Some(nvme_io_done),
-                    bio as *mut c_void,
-                    self.prchk_flags,
-                )
-            }
-        } else {
-            unsafe {
-                spdk_nvme_ns_cmd_writev(
-                    self.ns.as_ptr(),
-                    inner.qpair_ptr(),
-                    offset_blocks,
-                    num_blocks as u32,
-                    Some(nvme_writev_done),
-                    bio as *mut c_void,
-                    self.prchk_flags,
-                    Some(nvme_queued_reset_sgl),
-                    Some(nvme_queued_next_sge),
-                )
-            }
-        };
+        let rc =
+            self.submit_or_defer(inner, bio, offset_blocks, self.prchk_flags);
 
         if rc < 0 {
             Err(CoreError::WriteDispatch {
@@ -941,6 +1042,7 @@ impl BlockDeviceHandle for NvmeDeviceHandle {
                 channel,
                 op: IoType::Compare,
                 num_blocks,
+                submitted_at: 0,
                 #[cfg(feature = "fault-injection")]
                 inj_op: InjectIoCtx::new(FaultDomain::BlockDevice),
             },
@@ -1039,6 +1141,7 @@ impl BlockDeviceHandle for NvmeDeviceHandle {
                 channel,
                 op: IoType::Flush,
                 num_blocks,
+                submitted_at: 0,
                 #[cfg(feature = "fault-injection")]
                 inj_op: InjectIoCtx::new(FaultDomain::BlockDevice),
             },
@@ -1102,6 +1205,7 @@ impl BlockDeviceHandle for NvmeDeviceHandle {
                 channel,
                 op: IoType::Unmap,
                 num_blocks,
+                submitted_at: 0,
                 #[cfg(feature = "fault-injection")]
                 inj_op: InjectIoCtx::new(FaultDomain::BlockDevice),
             },
@@ -1201,6 +1305,7 @@ impl BlockDeviceHandle for NvmeDeviceHandle {
                 channel,
                 op: IoType::WriteZeros,
                 num_blocks,
+                submitted_at: 0,
                 #[cfg(feature = "fault-injection")]
                 inj_op: InjectIoCtx::new(FaultDomain::BlockDevice),
             },
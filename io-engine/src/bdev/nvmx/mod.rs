@@ -4,6 +4,7 @@ use once_cell::sync::Lazy;
 use parking_lot::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 pub use channel::{NvmeControllerIoChannel, NvmeIoChannel, NvmeIoChannelInner};
+pub use congestion::{nvme_congestion, NvmeCongestion};
 pub use controller::NvmeController;
 use controller_inner::SpdkNvmeController;
 pub use controller_state::NvmeControllerState;
@@ -21,6 +22,7 @@ use crate::{
 };
 
 mod channel;
+mod congestion;
 mod controller;
 mod controller_inner;
 mod controller_state;
@@ -33,6 +35,11 @@ mod snapshot;
 mod uri;
 pub mod utils;
 
+/// Registers the JSON-RPC methods of the NVMe bdevs.
+pub(crate) fn register_rpc_methods() {
+    congestion::register_rpc_methods();
+}
+
 #[derive(Debug)]
 #[allow(clippy::upper_case_acronyms)]
 pub struct NVMeCtlrList<'a> {
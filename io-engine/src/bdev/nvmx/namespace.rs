@@ -4,9 +4,11 @@ use spdk_rs::libspdk::{
     spdk_nvme_ns,
     spdk_nvme_ns_get_extended_sector_size,
     spdk_nvme_ns_get_flags,
+    spdk_nvme_ns_get_max_io_xfer_size,
     spdk_nvme_ns_get_md_size,
     spdk_nvme_ns_get_num_sectors,
     spdk_nvme_ns_get_optimal_io_boundary,
+    spdk_nvme_ns_get_pi_type,
     spdk_nvme_ns_get_size,
     spdk_nvme_ns_get_uuid,
     spdk_nvme_ns_supports_compare,
@@ -69,6 +71,17 @@ impl NvmeNamespace {
         unsafe { spdk_nvme_ns_get_md_size(self.0.as_ptr()) as u64 }
     }
 
+    /// Maximum size of a single I/O, in bytes.
+    pub fn max_io_xfer_size(&self) -> u64 {
+        unsafe { spdk_nvme_ns_get_max_io_xfer_size(self.0.as_ptr()) as u64 }
+    }
+
+    /// Protection information type of the namespace, 0 when it is not
+    /// formatted with protection information.
+    pub fn pi_type(&self) -> u32 {
+        unsafe { spdk_nvme_ns_get_pi_type(self.0.as_ptr()) as u32 }
+    }
+
     pub fn from_ptr(ns: *mut spdk_nvme_ns) -> NvmeNamespace {
         NonNull::new(ns)
             .map(NvmeNamespace)
@@ -47,10 +47,9 @@ use crate::{
         GetName,
     },
     bdev_api::{self, BdevError},
-    constants::NVME_NQN_PREFIX,
     core::MayastorEnvironment,
     ffihelper::ErrnoResult,
-    subsys::Config,
+    subsys::{nqn_prefix, Config},
 };
 
 use super::controller::transport::NvmeTransportId;
@@ -113,6 +112,11 @@ pub struct NvmfDeviceTemplate {
     uuid: Option<uuid::Uuid>,
     /// The HostNqn to connect to the nvmf target with.
     hostnqn: Option<String>,
+    /// The host ID to connect to the nvmf target with, overriding that of
+    /// the node.
+    hostid: Option<uuid::Uuid>,
+    /// The address to connect to the nvmf target from.
+    hostaddr: Option<String>,
 }
 
 impl TryFrom<&Url> for NvmfDeviceTemplate {
@@ -178,6 +182,14 @@ impl TryFrom<&Url> for NvmfDeviceTemplate {
 
         let hostnqn = parameters.remove("hostnqn");
 
+        let hostid = uri::uuid(parameters.remove("hostid")).context(
+            bdev_api::UuidParamParseFailed {
+                uri: url.to_string(),
+            },
+        )?;
+
+        let hostaddr = parameters.remove("hostaddr");
+
         Ok(NvmfDeviceTemplate {
             name: url[url::Position::BeforeHost .. url::Position::AfterPath]
                 .to_string(),
@@ -188,6 +200,8 @@ impl TryFrom<&Url> for NvmfDeviceTemplate {
             prchk_flags,
             uuid,
             hostnqn,
+            hostid,
+            hostaddr,
         })
     }
 }
@@ -244,7 +258,7 @@ impl<'probe> NvmeControllerContext<'probe> {
                 opts = opts.with_ext_host_id(*uuid.as_bytes());
                 if hostnqn.is_none() {
                     opts = opts
-                        .with_hostnqn(format!("{NVME_NQN_PREFIX}:uuid:{uuid}"));
+                        .with_hostnqn(format!("{}:uuid:{uuid}", nqn_prefix()));
                 }
             }
         }
@@ -253,6 +267,14 @@ impl<'probe> NvmeControllerContext<'probe> {
             opts = opts.with_hostnqn(host_nqn);
         }
 
+        if let Some(hostid) = template.hostid {
+            opts = opts.with_ext_host_id(*hostid.as_bytes());
+        }
+
+        if let Some(hostaddr) = &template.hostaddr {
+            opts = opts.with_src_addr(hostaddr);
+        }
+
         let (sender, receiver) = oneshot::channel::<ErrnoResult<()>>();
         let opts = opts.build();
 
@@ -26,6 +26,9 @@ pub enum BdevError {
     // Unsupported URI scheme.
     #[snafu(display("Unsupported URI scheme: '{}'", scheme))]
     UriSchemeUnsupported { scheme: String },
+    // URI scheme registered already.
+    #[snafu(display("URI scheme '{}' is already registered", scheme))]
+    UriSchemeExists { scheme: String },
     // Scheme-specific URI format errors.
     #[snafu(display("Invalid URI '{}': {}", uri, message))]
     InvalidUri { uri: String, message: String },
@@ -124,11 +127,7 @@ where
 {
     match uri::parse(uri.as_ref()) {
         Ok(device) if device.get_name() == bdev.name() => {
-            bdev.driver()
-                == match uri.scheme() {
-                    "nvmf" | "pcie" => "nvme",
-                    scheme => scheme,
-                }
+            Some(bdev.driver()) == uri::scheme_driver(uri.scheme())
         }
         _ => false,
     }
@@ -141,11 +140,7 @@ where
 {
     match uri::parse(uri.as_ref()) {
         Ok(device) if device.get_name() == bdev.name() => {
-            bdev.driver()
-                == match uri.scheme() {
-                    "nvmf" | "pcie" => "nvme",
-                    scheme => scheme,
-                }
+            Some(bdev.driver()) == uri::scheme_driver(uri.scheme())
         }
         _ => false,
     }
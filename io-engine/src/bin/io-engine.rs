@@ -1,7 +1,7 @@
 #[macro_use]
 extern crate tracing;
 
-use std::{env, path::Path, sync::atomic::Ordering};
+use std::{env, path::Path, sync::atomic::Ordering, time::Duration};
 
 use events_api::event::EventAction;
 
@@ -17,8 +17,10 @@ use io_engine::{
         util::uring,
     },
     core::{
+        clock::clock_skew_loop,
         device_monitor_loop,
         diagnostics::process_diagnostics_cli,
+        iobuf::iobuf_monitor_loop,
         lock::{
             ProtectedSubsystems,
             ResourceLockManager,
@@ -26,6 +28,7 @@ use io_engine::{
         },
         reactor_monitor_loop,
         runtime,
+        telemetry::{telemetry_loop, TelemetryConfig},
         MayastorCliArgs,
         MayastorEnvironment,
         Mthread,
@@ -34,8 +37,15 @@ use io_engine::{
     eventing::Event,
     grpc,
     logger,
+    lvs::pool_backpressure_loop,
     persistent_store::PersistentStoreBuilder,
-    subsys::Registration,
+    subsys::{
+        nvmf_io_stats_loop,
+        share_audit_loop,
+        share_lease_loop,
+        stale_subsystem_loop,
+        Registration,
+    },
 };
 use version_info::fmt_package_info;
 
@@ -72,6 +82,37 @@ fn start_tokio_runtime(args: &MayastorCliArgs) {
     let reactor_freeze_detection = args.reactor_freeze_detection;
     let reactor_freeze_timeout = args.reactor_freeze_timeout;
 
+    let share_audit_interval = args.share_audit_interval;
+    let share_audit_fix = args.share_audit_fix;
+    let stale_subsystem_interval = args.stale_subsystem_interval;
+    let nvmf_io_stats_interval = args.nvmf_io_stats_interval;
+    let share_lease_interval =
+        Duration::from_millis(args.share_lease_interval_ms.max(1));
+
+    let iobuf_monitor_interval =
+        Duration::from_millis(args.iobuf_monitor_interval_ms.max(1));
+
+    let clock_skew_interval =
+        Duration::from_millis(args.clock_skew_interval_ms.max(1));
+    let clock_skew_threshold =
+        Duration::from_millis(args.clock_skew_threshold_ms);
+
+    let pool_latency_threshold =
+        args.pool_latency_threshold_us.map(Duration::from_micros);
+    let pool_latency_interval =
+        Duration::from_millis(args.pool_latency_interval_ms.max(1));
+
+    let telemetry = match (&args.telemetry_endpoint, &args.telemetry_key_file) {
+        (Some(endpoint), Some(key_file)) => TelemetryConfig::new(
+            endpoint,
+            key_file,
+            Duration::from_secs(args.telemetry_interval),
+        )
+        .map_err(|error| error!("Telemetry is disabled: {error}"))
+        .ok(),
+        _ => None,
+    };
+
     // Enable partial rebuild.
     if let Ok(v) = std::env::var("NEXUS_PARTIAL_REBUILD") {
         ENABLE_PARTIAL_REBUILD.store(v == "1", Ordering::SeqCst);
@@ -151,6 +192,43 @@ fn start_tokio_runtime(args: &MayastorCliArgs) {
                 runtime::spawn(reactor_monitor_loop(reactor_freeze_timeout));
             }
 
+            if let Some(interval) = share_audit_interval {
+                runtime::spawn(share_audit_loop(
+                    Duration::from_secs(interval),
+                    share_audit_fix,
+                ));
+            }
+
+            if let Some(interval) = stale_subsystem_interval {
+                runtime::spawn(stale_subsystem_loop(Duration::from_secs(
+                    interval,
+                )));
+            }
+
+            if let Some(interval) = nvmf_io_stats_interval {
+                runtime::spawn(nvmf_io_stats_loop(Duration::from_secs(
+                    interval,
+                )));
+            }
+
+            runtime::spawn(share_lease_loop(share_lease_interval));
+            runtime::spawn(iobuf_monitor_loop(iobuf_monitor_interval));
+            runtime::spawn(clock_skew_loop(
+                clock_skew_interval,
+                clock_skew_threshold,
+            ));
+
+            if let Some(threshold) = pool_latency_threshold {
+                runtime::spawn(pool_backpressure_loop(
+                    threshold,
+                    pool_latency_interval,
+                ));
+            }
+
+            if let Some(telemetry) = telemetry {
+                runtime::spawn(telemetry_loop(telemetry));
+            }
+
             futures.push(
                 grpc::MayastorGrpcServer::run(
                     &node_name,
@@ -255,6 +333,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: MayastorCliArgs = clap::Parser::parse();
 
     let log_format = args.log_format.unwrap_or_default();
+    logger::set_json_context(args.node_name.as_deref(), &args.log_redact);
 
     // setup our logger first if -L is passed, raise the log level
     // automatically. trace maps to debug at FFI level. If RUST_LOG is
@@ -0,0 +1,320 @@
+//! Offload statistics of the accel framework.
+//!
+//! The checksums of the NVMe-oF TCP digests, the copies and the compression
+//! go through the accel framework, which executes every operation on the
+//! module it is assigned to: the software module, or a hardware one such as
+//! DSA or IAA when enabled by the `accel_opts` of the configuration. The
+//! operations executed are counted per thread and per operation by SPDK.
+//!
+//! Each time they are sampled, the operations executed since the previous
+//! sample are added to the execution counters of the module which executes
+//! them, so that the counters of the modules only ever grow with the
+//! operations they ran. The operations meant to be offloaded, by the
+//! assignments of the configuration or by the enabled DSA and IAA devices,
+//! which ran on the software module instead are counted as falling back.
+
+use std::{
+    collections::BTreeMap,
+    ffi::CStr,
+    mem::size_of,
+    os::raw::c_void,
+    sync::Mutex,
+};
+
+use futures::{channel::oneshot, FutureExt};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use spdk_rs::libspdk::{
+    spdk_accel_get_io_channel,
+    spdk_accel_get_opc_module_name,
+    spdk_accel_get_opcode_name,
+    spdk_accel_get_opcode_stats,
+    spdk_accel_opcode,
+    spdk_accel_opcode_stats,
+    spdk_for_each_thread,
+    spdk_put_io_channel,
+    SPDK_ACCEL_OPC_LAST,
+};
+
+use crate::{
+    ffihelper::cb_arg,
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+    subsys::Config,
+};
+
+/// Name of the software module of the accel framework.
+const SOFTWARE_MODULE: &str = "software";
+/// Name of the module of the DSA devices.
+const DSA_MODULE: &str = "dsa";
+/// Name of the module of the IAA devices.
+const IAA_MODULE: &str = "iaa";
+/// Operations the DSA devices execute.
+const DSA_OPCODES: &[&str] = &[
+    "copy",
+    "fill",
+    "dualcast",
+    "compare",
+    "crc32c",
+    "copy_crc32c",
+    "dif_verify",
+    "dif_generate_copy",
+];
+/// Operations the IAA devices execute.
+const IAA_OPCODES: &[&str] = &["compress", "decompress"];
+
+/// Execution counters of the modules, with the executions by operation at
+/// the last sample.
+struct ModuleCounters {
+    sampled: Vec<spdk_accel_opcode_stats>,
+    modules: BTreeMap<String, AccelModuleStats>,
+}
+
+static MODULE_COUNTERS: Lazy<Mutex<ModuleCounters>> = Lazy::new(|| {
+    Mutex::new(ModuleCounters {
+        sampled: vec![
+            spdk_accel_opcode_stats::default();
+            SPDK_ACCEL_OPC_LAST as usize
+        ],
+        modules: BTreeMap::new(),
+    })
+});
+
+/// Operations of a kind executed by the accel framework.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct AccelOpcodeStats {
+    pub opcode: String,
+    /// Module executing the operations.
+    pub module: String,
+    /// Hardware module the operations are meant to be offloaded to, if
+    /// any.
+    pub offload_module: Option<String>,
+    /// Operations executed.
+    pub executed: u64,
+    /// Operations which failed.
+    pub failed: u64,
+    /// Bytes processed.
+    pub num_bytes: u64,
+}
+
+/// Operations executed by a module of the accel framework.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct AccelModuleStats {
+    pub module: String,
+    /// Operations executed.
+    pub executed: u64,
+    /// Bytes processed.
+    pub num_bytes: u64,
+    /// Operations executed while meant to be offloaded to another module.
+    pub fallback_ops: u64,
+}
+
+/// Offload statistics of the accel framework.
+#[derive(Debug, Clone, Serialize)]
+pub struct AccelStats {
+    /// Whether the DSA devices are enabled.
+    pub dsa_enable: bool,
+    /// Whether the IAA devices are enabled.
+    pub iaa_enable: bool,
+    /// Operations executed by a hardware module.
+    pub offloaded_ops: u64,
+    /// Operations executed by the software module.
+    pub software_ops: u64,
+    /// Operations executed by the software module while meant to be
+    /// offloaded to a hardware module.
+    pub fallback_ops: u64,
+    /// Share of the operations meant to be offloaded which were executed
+    /// by a hardware module.
+    pub hit_rate: f64,
+    pub modules: Vec<AccelModuleStats>,
+    pub opcodes: Vec<AccelOpcodeStats>,
+}
+
+/// Returns the accel operation of the given name.
+pub(crate) fn accel_opcode(name: &str) -> Option<spdk_accel_opcode> {
+    (0 .. SPDK_ACCEL_OPC_LAST).find(|&opcode| opcode_name(opcode) == name)
+}
+
+/// Returns the name of an accel operation.
+fn opcode_name(opcode: spdk_accel_opcode) -> String {
+    let name = unsafe { spdk_accel_get_opcode_name(opcode) };
+    if name.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(name) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Returns the module an accel operation is assigned to.
+fn opcode_module(opcode: spdk_accel_opcode) -> String {
+    let mut name = std::ptr::null();
+    let rc = unsafe { spdk_accel_get_opc_module_name(opcode, &mut name) };
+    if rc != 0 || name.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(name) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Sums the operations executed on every thread, by operation.
+async fn opcode_stats() -> Vec<spdk_accel_opcode_stats> {
+    struct Ctx {
+        sender: oneshot::Sender<Vec<spdk_accel_opcode_stats>>,
+        stats: Vec<spdk_accel_opcode_stats>,
+    }
+
+    extern "C" fn thread_fn(arg: *mut c_void) {
+        let ctx = unsafe { &mut *(arg as *mut Ctx) };
+        let ch = unsafe { spdk_accel_get_io_channel() };
+        if ch.is_null() {
+            return;
+        }
+        for (opcode, total) in ctx.stats.iter_mut().enumerate() {
+            let mut stats = spdk_accel_opcode_stats::default();
+            unsafe {
+                spdk_accel_get_opcode_stats(
+                    ch,
+                    opcode as spdk_accel_opcode,
+                    &mut stats,
+                    size_of::<spdk_accel_opcode_stats>() as _,
+                )
+            };
+            total.executed += stats.executed;
+            total.failed += stats.failed;
+            total.num_bytes += stats.num_bytes;
+        }
+        unsafe { spdk_put_io_channel(ch) };
+    }
+
+    extern "C" fn done_fn(arg: *mut c_void) {
+        let ctx = unsafe { Box::from_raw(arg as *mut Ctx) };
+        ctx.sender.send(ctx.stats).ok();
+    }
+
+    let (s, r) = oneshot::channel();
+    let ctx = Ctx {
+        sender: s,
+        stats: vec![
+            spdk_accel_opcode_stats::default();
+            SPDK_ACCEL_OPC_LAST as usize
+        ],
+    };
+    unsafe {
+        spdk_for_each_thread(Some(thread_fn), cb_arg(ctx), Some(done_fn))
+    };
+    r.await.unwrap_or_default()
+}
+
+/// Returns the hardware module an accel operation is meant to be offloaded
+/// to, by the accel options of the configuration, if any.
+fn offload_module(opcode: &str) -> Option<String> {
+    let opts = &Config::get().accel_opts;
+    if let Some(module) = opts.assignments.get(opcode) {
+        return (module != SOFTWARE_MODULE).then(|| module.clone());
+    }
+    if opts.dsa_enable && DSA_OPCODES.contains(&opcode) {
+        Some(DSA_MODULE.to_string())
+    } else if opts.iaa_enable && IAA_OPCODES.contains(&opcode) {
+        Some(IAA_MODULE.to_string())
+    } else {
+        None
+    }
+}
+
+impl AccelStats {
+    /// Returns the operations executed by the accel framework, by operation
+    /// and by module, adding the operations executed since the previous
+    /// sample to the counters of the modules which executed them.
+    pub async fn get() -> Self {
+        let opts = &Config::get().accel_opts;
+        let stats = opcode_stats().await;
+        let opcodes = stats
+            .iter()
+            .enumerate()
+            .map(|(opcode, stats)| {
+                let name = opcode_name(opcode as spdk_accel_opcode);
+                AccelOpcodeStats {
+                    module: opcode_module(opcode as spdk_accel_opcode),
+                    offload_module: offload_module(&name),
+                    opcode: name,
+                    executed: stats.executed,
+                    failed: stats.failed,
+                    num_bytes: stats.num_bytes,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let modules = {
+            let mut counters = MODULE_COUNTERS.lock().unwrap();
+            let ModuleCounters {
+                sampled,
+                modules,
+            } = &mut *counters;
+            for ((op, stats), last) in
+                opcodes.iter().zip(&stats).zip(sampled.iter_mut())
+            {
+                // the counters of an exited thread are gone, the operations
+                // executed since are counted from the new sums
+                if stats.executed < last.executed {
+                    *last = spdk_accel_opcode_stats::default();
+                }
+                let executed = stats.executed - last.executed;
+                let num_bytes = stats.num_bytes.saturating_sub(last.num_bytes);
+                *last = *stats;
+                if executed == 0 || op.module.is_empty() {
+                    continue;
+                }
+
+                let module =
+                    modules.entry(op.module.clone()).or_insert_with(|| {
+                        AccelModuleStats {
+                            module: op.module.clone(),
+                            ..Default::default()
+                        }
+                    });
+                module.executed += executed;
+                module.num_bytes += num_bytes;
+                if op.offload_module.as_ref().is_some_and(|m| *m != op.module) {
+                    module.fallback_ops += executed;
+                }
+            }
+            modules.values().cloned().collect::<Vec<_>>()
+        };
+
+        let (software_ops, offloaded_ops) =
+            modules.iter().fold((0, 0), |(sw, hw), m| {
+                if m.module == SOFTWARE_MODULE {
+                    (sw + m.executed, hw)
+                } else {
+                    (sw, hw + m.executed)
+                }
+            });
+        let fallback_ops = modules.iter().map(|m| m.fallback_ops).sum::<u64>();
+        let meant = offloaded_ops + fallback_ops;
+
+        Self {
+            dsa_enable: opts.dsa_enable,
+            iaa_enable: opts.iaa_enable,
+            offloaded_ops,
+            software_ops,
+            fallback_ops,
+            hit_rate: if meant == 0 {
+                0.0
+            } else {
+                offloaded_ops as f64 / meant as f64
+            },
+            modules,
+            opcodes,
+        }
+    }
+}
+
+/// Registers the JSON-RPC methods of the accel framework.
+pub(crate) fn register_rpc_methods() {
+    // operations executed by the accel framework per operation and
+    // module, and the share of them offloaded to the hardware
+    jsonrpc_register::<(), _, _, JsonRpcError>("mayastor_accel_stats", |_| {
+        async move { Ok(AccelStats::get().await) }.boxed_local()
+    });
+}
@@ -0,0 +1,275 @@
+//! Bounded executor of the control-plane operations.
+//!
+//! The gRPC calls run their operations on the primary reactor, which polls
+//! the I/O of its core as well. Rather than spawning every operation there at
+//! once, so that a burst of long-running ones, such as rebuild starts or
+//! state changes, silently piles up and starves the polling, the operations
+//! go through this executor: at most `max_running` of them run at a time,
+//! the others wait in a queue without being polled, and an operation is
+//! rejected when `max_queued` operations wait already.
+//!
+//! An operation which waits longer than its timeout, if one is given or
+//! configured, is dropped without running. A running operation is not
+//! cancelled, as it could leave the state it changes half done, but it is
+//! flagged as overdue. The pending operations can be listed to tell what the
+//! reactor is busy with.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    future::Future,
+    panic::Location,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::{
+    channel::oneshot::{self, Canceled},
+    future::{self, Either},
+    FutureExt,
+};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tokio::sync::Semaphore;
+
+use crate::{
+    core::{runtime, CoreError, Reactor},
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+    subsys::Config,
+};
+
+/// State of a pending operation.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminOpState {
+    /// Waiting for one of the running operations to complete.
+    Queued,
+    Running,
+}
+
+/// An operation queued or running.
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingOp {
+    pub id: u64,
+    /// Source location the operation was submitted from.
+    pub name: String,
+    pub state: AdminOpState,
+    /// Time since the operation was submitted, in milliseconds.
+    pub age_ms: u64,
+    /// Whether the operation is still running past its timeout.
+    pub overdue: bool,
+}
+
+/// The operations queued and running, and the limits they are subject to.
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingOps {
+    pub max_running: usize,
+    pub max_queued: usize,
+    pub running: usize,
+    pub queued: usize,
+    /// Operations rejected since startup, as the queue was full.
+    pub rejected: u64,
+    /// Operations dropped since startup, as they timed out in the queue.
+    pub expired: u64,
+    pub ops: Vec<PendingOp>,
+}
+
+#[derive(Debug)]
+struct OpEntry {
+    name: String,
+    state: AdminOpState,
+    submitted: Instant,
+    overdue: bool,
+}
+
+#[derive(Debug, Default)]
+struct Executor {
+    next_id: u64,
+    ops: BTreeMap<u64, OpEntry>,
+    rejected: u64,
+    expired: u64,
+}
+
+impl Executor {
+    fn queued(&self) -> usize {
+        self.ops
+            .values()
+            .filter(|op| op.state == AdminOpState::Queued)
+            .count()
+    }
+}
+
+static EXECUTOR: Lazy<Mutex<Executor>> =
+    Lazy::new(|| Mutex::new(Executor::default()));
+
+/// Permits of the running operations.
+static RUNNING: Lazy<Semaphore> =
+    Lazy::new(|| Semaphore::new(Config::get().admin_opts.max_running.max(1)));
+
+/// Removes an operation from the pending ones once it completes, expires or
+/// is dropped.
+struct OpGuard(u64);
+
+impl Drop for OpGuard {
+    fn drop(&mut self) {
+        EXECUTOR.lock().unwrap().ops.remove(&self.0);
+    }
+}
+
+/// Returns a receiver signalled on the primary reactor once the timeout
+/// elapses, unless dropped before.
+fn expiry(timeout: Duration) -> oneshot::Receiver<()> {
+    let (mut tx, rx) = oneshot::channel::<()>();
+    runtime::spawn(async move {
+        tokio::select! {
+            _ = tokio::time::sleep(timeout) => {}
+            _ = tx.cancellation() => return,
+        }
+        if let Ok(r) = Reactor::spawn_at_primary(async move {
+            tx.send(()).ok();
+        }) {
+            r.await.ok();
+        }
+    });
+    rx
+}
+
+/// Receiver of the output of an operation, cancelled when the operation
+/// expired in the queue.
+#[derive(Debug)]
+pub struct AdminOpReceiver<T>(oneshot::Receiver<Option<T>>);
+
+impl<T> Future for AdminOpReceiver<T> {
+    type Output = Result<T, Canceled>;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Self::Output> {
+        Pin::new(&mut self.0)
+            .poll(cx)
+            .map(|r| r.and_then(|out| out.ok_or(Canceled)))
+    }
+}
+
+/// Submits an operation to the primary reactor, where it runs once fewer
+/// than `max_running` operations are running. It is named after the source
+/// location it is submitted from, and times out after the given timeout, or
+/// the configured one if not given.
+#[track_caller]
+pub fn submit<F>(
+    timeout: Option<Duration>,
+    future: F,
+) -> Result<AdminOpReceiver<F::Output>, CoreError>
+where
+    F: Future + 'static,
+    F::Output: Send + Debug,
+{
+    let opts = &Config::get().admin_opts;
+    let timeout = timeout.or(opts.op_timeout_ms.map(Duration::from_millis));
+    let name = Location::caller().to_string();
+
+    let guard = {
+        let mut executor = EXECUTOR.lock().unwrap();
+        let queued = executor.queued();
+        if queued >= opts.max_queued {
+            executor.rejected += 1;
+            warn!("Rejecting operation {name}: {queued} operations queued");
+            return Err(CoreError::AdminQueueFull {
+                queued,
+            });
+        }
+        executor.next_id += 1;
+        let id = executor.next_id;
+        executor.ops.insert(
+            id,
+            OpEntry {
+                name: name.clone(),
+                state: AdminOpState::Queued,
+                submitted: Instant::now(),
+                overdue: false,
+            },
+        );
+        OpGuard(id)
+    };
+
+    let op = async move {
+        let mut deadline = match timeout {
+            Some(t) => expiry(t).map(|_| ()).left_future(),
+            None => future::pending().right_future(),
+        };
+
+        let permit =
+            match future::select(RUNNING.acquire().boxed(), deadline).await {
+                Either::Left((permit, e)) => {
+                    deadline = e;
+                    permit.ok()
+                }
+                Either::Right(_) => {
+                    EXECUTOR.lock().unwrap().expired += 1;
+                    warn!("Operation {name} timed out in the queue, dropped");
+                    return None;
+                }
+            };
+        if let Some(op) = EXECUTOR.lock().unwrap().ops.get_mut(&guard.0) {
+            op.state = AdminOpState::Running;
+        }
+
+        let output = match future::select(future.boxed_local(), deadline).await
+        {
+            Either::Left((output, _)) => output,
+            Either::Right((_, running)) => {
+                warn!("Operation {name} still running past its timeout");
+                if let Some(op) = EXECUTOR.lock().unwrap().ops.get_mut(&guard.0)
+                {
+                    op.overdue = true;
+                }
+                running.await
+            }
+        };
+        drop(permit);
+        drop(guard);
+        Some(output)
+    };
+
+    Reactor::spawn_at_primary(op).map(AdminOpReceiver)
+}
+
+/// Returns the operations queued and running.
+pub fn pending() -> PendingOps {
+    let opts = &Config::get().admin_opts;
+    let executor = EXECUTOR.lock().unwrap();
+    let ops = executor
+        .ops
+        .iter()
+        .map(|(id, op)| PendingOp {
+            id: *id,
+            name: op.name.clone(),
+            state: op.state,
+            age_ms: op.submitted.elapsed().as_millis() as u64,
+            overdue: op.overdue,
+        })
+        .collect::<Vec<_>>();
+    let queued = executor.queued();
+
+    PendingOps {
+        max_running: opts.max_running,
+        max_queued: opts.max_queued,
+        running: ops.len() - queued,
+        queued,
+        rejected: executor.rejected,
+        expired: executor.expired,
+        ops,
+    }
+}
+
+/// Registers the JSON-RPC methods of the admin operations.
+pub(crate) fn register_rpc_methods() {
+    // control-plane operations queued and running on the primary
+    // reactor, with the limits of their executor
+    jsonrpc_register::<(), _, _, JsonRpcError>("mayastor_pending_ops", |_| {
+        async move { Ok(pending()) }.boxed_local()
+    });
+}
@@ -15,6 +15,8 @@ use crate::{
     bdev_api::bdev_uri_eq,
     core::{
         share::{NvmfShareProps, Protocol, Share, UpdateProps},
+        BdevFence,
+        BdevOperation,
         BlockDeviceIoStats,
         CoreError,
         DescriptorGuard,
@@ -22,7 +24,19 @@ use crate::{
         ShareNvmf,
         UnshareNvmf,
     },
-    subsys::NvmfSubsystem,
+    subsys::{
+        expand_hosts,
+        record_identity,
+        replay_share,
+        resolve_cntlid_range,
+        validate_kato,
+        HostDhChap,
+        NvmfControllerInfo,
+        NvmfError,
+        NvmfListener,
+        NvmfSubsystem,
+        NvmfTargetKind,
+    },
     target::nvmf,
 };
 
@@ -195,7 +209,70 @@ where
     }
 }
 
+/// Configures a subsystem created for a share, before it is started.
+async fn configure_subsystem(
+    subsystem: &NvmfSubsystem,
+    props: &NvmfShareProps,
+    disallowed_hosts: &[String],
+) -> Result<(), NvmfError> {
+    if let Some(serial) = props.serial() {
+        subsystem.set_serial(serial)?;
+    }
+    if let Some(model) = props.model() {
+        subsystem.set_model(model)?;
+    }
+    if let Some(identify) = props.identify() {
+        subsystem.set_identify_overrides(identify)?;
+    }
+    subsystem.set_kato(props.kato())?;
+    subsystem.set_share_mode(props.share_mode());
+    if let Some((cntlid_min, cntlid_max)) =
+        resolve_cntlid_range(props.cntlid_range())
+    {
+        subsystem.set_cntlid_range(cntlid_min, cntlid_max)?;
+    }
+    subsystem.set_ana_reporting(props.ana())?;
+    subsystem.apply_allowed_hosts(props.allowed_hosts()).await?;
+    // the replayed hosts disallowed since they were recorded stay so
+    let allowed = subsystem.allowed_hosts();
+    for host in disallowed_hosts.iter().filter(|h| allowed.contains(h)) {
+        subsystem.disallow_host(host)?;
+    }
+    subsystem.apply_host_auth(props.host_auth()).await?;
+    record_identity(&subsystem.get_nqn(), props.serial(), props.model());
+    Ok(())
+}
+
 #[async_trait(? Send)]
+impl<T> Bdev<T>
+where
+    T: spdk_rs::BdevOps,
+{
+    /// Unshares the bdev regardless of current active share, under the given
+    /// fence of the operation in progress on the bdev which the unshare is
+    /// part of, or under its own fence otherwise.
+    pub(crate) async fn unshare_within(
+        self: Pin<&mut Self>,
+        owner: Option<&BdevFence>,
+    ) -> Result<(), CoreError> {
+        let _fence = BdevFence::acquire_within(
+            self.name(),
+            BdevOperation::Unshare,
+            owner,
+        )?;
+        match self.shared() {
+            Some(Protocol::Nvmf) => {
+                if let Some(ss) = NvmfSubsystem::nqn_lookup(self.name()) {
+                    ss.shutdown().await.context(UnshareNvmf {})?;
+                }
+            }
+            Some(Protocol::Off) | None => {}
+        }
+
+        Ok(())
+    }
+}
+
 impl<T> Share for Bdev<T>
 where
     T: spdk_rs::BdevOps,
@@ -209,30 +286,68 @@ where
         props: Option<NvmfShareProps>,
     ) -> Result<Self::Output, Self::Error> {
         let me = unsafe { self.get_unchecked_mut() };
-        let props = NvmfShareProps::from(props);
+        let _fence = BdevFence::acquire(me.name(), BdevOperation::Share)?;
+        // the hosts and listeners set since the share was created are
+        // replayed when it is created again after a restart without them
+        let replayed = replay_share(me.name(), NvmfShareProps::from(props));
+        let props = replayed.props;
 
         let ptpl = props.ptpl().as_ref().map(|ptpl| ptpl.path());
 
-        // todo: add option to use uuid here, will allow for the replica uuid to
-        // be used!
-        let subsystem =
-            NvmfSubsystem::try_from_with(me, ptpl).context(ShareNvmf {})?;
-
-        if let Some((cntlid_min, cntlid_max)) = props.cntlid_range() {
-            subsystem
-                .set_cntlid_range(cntlid_min, cntlid_max)
+        // fail before creating the subsystem if a host group is unknown
+        let hosts =
+            expand_hosts(props.allowed_hosts()).context(ShareNvmf {})?;
+        // or if a host to authenticate is not allowed or has no key
+        HostDhChap::validate(props.host_auth(), &hosts)
+            .context(ShareNvmf {})?;
+        // or if a listener is not valid
+        let listeners = props.listeners();
+        NvmfListener::validate(NvmfTargetKind::of_bdev(me.name()), &listeners)
+            .context(ShareNvmf {})?;
+        // or if the serial or model number is not valid
+        NvmfSubsystem::validate_identity(props.serial(), props.model())
+            .context(ShareNvmf {})?;
+        // or if an override of the identify controller data is not valid
+        if let Some(identify) = props.identify() {
+            identify.validate().context(ShareNvmf {})?;
+        }
+        // or if the keep alive timeout is not valid
+        if let Some(kato_ms) = props.kato() {
+            validate_kato(kato_ms)
+                .map_err(|reason| NvmfError::InvalidKato {
+                    kato_ms,
+                    reason,
+                })
                 .context(ShareNvmf {})?;
         }
-        subsystem
-            .set_ana_reporting(props.ana())
+        // or if the bdev may not be shared in the requested mode
+        props
+            .share_mode()
+            .validate(me, props.allowed_hosts())
             .context(ShareNvmf {})?;
-        subsystem.allow_any(props.host_any());
-        subsystem
-            .set_allowed_hosts(props.allowed_hosts())
-            .await
+        // or if ANA may not be reported for it
+        NvmfSubsystem::validate_ana(me, props.ana()).context(ShareNvmf {})?;
+
+        // todo: add option to use uuid here, will allow for the replica uuid to
+        // be used!
+        let subsystem = NvmfSubsystem::try_from_with(me, ptpl, props.nqn())
             .context(ShareNvmf {})?;
+        // the subsystem is not left behind, holding the bdev, when it fails
+        // to be configured
+        if let Err(error) =
+            configure_subsystem(&subsystem, &props, &replayed.disallowed_hosts)
+                .await
+        {
+            subsystem.discard();
+            return Err(error).context(ShareNvmf {});
+        }
 
-        subsystem.start().await.context(ShareNvmf {})
+        let uri = subsystem
+            .start_with(&listeners)
+            .await
+            .context(ShareNvmf {})?;
+        subsystem.set_lease(props.lease());
+        Ok(uri)
     }
 
     fn create_ptpl(&self) -> Result<Option<PtplProps>, Self::Error> {
@@ -243,14 +358,19 @@ where
         self: Pin<&mut Self>,
         props: P,
     ) -> Result<(), Self::Error> {
+        let _fence =
+            BdevFence::acquire(self.name(), BdevOperation::UpdateProperties)?;
         match self.shared() {
             Some(Protocol::Nvmf) => {
                 if let Some(subsystem) = NvmfSubsystem::nqn_lookup(self.name())
                 {
                     let props = UpdateProps::from(props.into());
-                    subsystem.allow_any(props.host_any());
                     subsystem
-                        .set_allowed_hosts(props.allowed_hosts())
+                        .apply_allowed_hosts(props.allowed_hosts())
+                        .await
+                        .context(ShareNvmf {})?;
+                    subsystem
+                        .apply_host_auth(props.host_auth())
                         .await
                         .context(ShareNvmf {})?;
                 }
@@ -263,19 +383,7 @@ where
 
     /// unshare the bdev regardless of current active share
     async fn unshare(self: Pin<&mut Self>) -> Result<(), Self::Error> {
-        match self.shared() {
-            Some(Protocol::Nvmf) => {
-                if let Some(ss) = NvmfSubsystem::nqn_lookup(self.name()) {
-                    ss.stop().await.context(UnshareNvmf {})?;
-                    unsafe {
-                        ss.shutdown_unsafe();
-                    }
-                }
-            }
-            Some(Protocol::Off) | None => {}
-        }
-
-        Ok(())
+        self.unshare_within(None).await
     }
 
     /// Returns the share protocol if the bdev is currently shared.
@@ -309,6 +417,15 @@ where
         }
     }
 
+    fn nvmf_controllers(&self) -> Vec<NvmfControllerInfo> {
+        match self.shared() {
+            Some(Protocol::Nvmf) => NvmfSubsystem::nqn_lookup(self.name())
+                .map(|subsystem| subsystem.controllers())
+                .unwrap_or_default(),
+            _ => vec![],
+        }
+    }
+
     /// return the URI that was used to construct the bdev
     fn bdev_uri(&self) -> Option<url::Url> {
         self.bdev_uri_original().map(|mut uri| {
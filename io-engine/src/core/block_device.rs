@@ -12,12 +12,13 @@ use async_trait::async_trait;
 use futures::channel::oneshot;
 use merge::Merge;
 use nix::errno::Errno;
+use serde::Serialize;
 use spdk_rs::ffihelper::{cb_arg, done_cb};
 use std::os::raw::c_void;
 use uuid::Uuid;
 
 /// Structure representing Bdev Io Stats.
-#[derive(Debug, Default, Clone, Copy, Merge)]
+#[derive(Debug, Default, Clone, Copy, Merge, Serialize)]
 pub struct BlockDeviceIoStats {
     #[merge(strategy = merge::num::saturating_add)]
     pub num_read_ops: u64,
@@ -84,6 +85,22 @@ pub trait BlockDevice {
     /// Checks whether target I/O type is supported by the device.
     fn io_type_supported(&self, io_type: IoType) -> bool;
 
+    /// Returns the maximum size of a single I/O in bytes, or `None` if the
+    /// device does not limit it.
+    fn max_transfer_size(&self) -> Option<u64> {
+        None
+    }
+
+    /// Returns the protection information type the device is formatted
+    /// with, 0 meaning no protection information.
+    fn pi_type(&self) -> u32 {
+        0
+    }
+
+    /// Returns the transport the device is reached over, e.g. `tcp` for
+    /// an NVMe-oF device or the bdev module name for a local device.
+    fn transport(&self) -> String;
+
     /// Obtains I/O statistics for the device.
     async fn io_stats(&self) -> Result<BlockDeviceIoStats, CoreError>;
 
@@ -0,0 +1,193 @@
+//! Clock sanity checks of the node.
+//!
+//! The events of the nodes are correlated by their wall-clock timestamps,
+//! which NTP may step or slew independently on every node. A sample of the
+//! clocks pairs the wall clock with the monotonic clock and the boot ID of
+//! the node, so that the order of what happened on a node is known for sure
+//! even when its wall clock jumped.
+//!
+//! The wall clock is also checked against the monotonic clock periodically:
+//! both advance at the same pace unless the wall clock is adjusted, so any
+//! difference between their progress is a skew, which is warned about and
+//! reported as an event once past a threshold. The monotonic clock counts
+//! the time the node is suspended, as the wall clock does.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use events_api::event::EventAction;
+use futures::FutureExt;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::{
+    core::MayastorEnvironment,
+    eventing::{io_engine_events::clock_skew_event_meta, EventWithMeta},
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+};
+
+/// File the kernel exposes the ID of the current boot in.
+const BOOT_ID_FILE: &str = "/proc/sys/kernel/random/boot_id";
+
+/// ID of the current boot of the node, empty when unknown.
+static BOOT_ID: Lazy<String> = Lazy::new(|| {
+    std::fs::read_to_string(BOOT_ID_FILE)
+        .map(|id| id.trim().to_string())
+        .unwrap_or_else(|error| {
+            warn!(%error, "Failed to read the boot ID");
+            String::new()
+        })
+});
+
+/// Last skew of the wall clock past the threshold.
+static LAST_SKEW: Lazy<Mutex<Option<ClockSkew>>> =
+    Lazy::new(|| Mutex::new(None));
+
+/// Returns the ID of the current boot of the node.
+pub fn boot_id() -> &'static str {
+    &BOOT_ID
+}
+
+/// Reads the monotonic clock, counting the time suspended.
+fn monotonic_now() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_BOOTTIME, &mut ts);
+    }
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// The clocks of the node at one point in time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ClockSample {
+    /// Wall-clock time, in RFC 3339 format.
+    pub wall_clock: String,
+    /// Wall-clock time, in nanoseconds since the Unix epoch.
+    pub wall_clock_ns: u64,
+    /// Monotonic time since the boot, suspended time included, in
+    /// nanoseconds.
+    pub monotonic_ns: u64,
+    /// ID of the boot the monotonic time is relative to.
+    pub boot_id: String,
+}
+
+impl ClockSample {
+    /// Samples the clocks.
+    pub fn now() -> Self {
+        let wall = SystemTime::now();
+        let monotonic = monotonic_now();
+        let wall_clock_ns = wall
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+        Self {
+            wall_clock: DateTime::<Utc>::from(wall)
+                .to_rfc3339_opts(SecondsFormat::Nanos, true),
+            wall_clock_ns,
+            monotonic_ns: monotonic.as_nanos() as u64,
+            boot_id: boot_id().to_string(),
+        }
+    }
+
+    /// Skew of the wall clock since an earlier sample, in nanoseconds: how
+    /// much further the wall clock went than the monotonic clock, negative
+    /// when it went back.
+    pub fn skew_since(&self, earlier: &ClockSample) -> i64 {
+        let wall = self.wall_clock_ns as i64 - earlier.wall_clock_ns as i64;
+        let monotonic = self.monotonic_ns as i64 - earlier.monotonic_ns as i64;
+        wall - monotonic
+    }
+}
+
+/// A skew of the wall clock between two samples.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClockSkew {
+    /// Sample before the skew.
+    pub before: ClockSample,
+    /// Sample after the skew.
+    pub after: ClockSample,
+    /// Skew of the wall clock, in milliseconds.
+    pub skew_ms: i64,
+}
+
+/// The clocks of the node and the last skew of its wall clock.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClockStatus {
+    pub now: ClockSample,
+    pub last_skew: Option<ClockSkew>,
+}
+
+/// Returns the clocks of the node and the last skew of its wall clock.
+pub fn clock_status() -> ClockStatus {
+    ClockStatus {
+        now: ClockSample::now(),
+        last_skew: LAST_SKEW.lock().unwrap().clone(),
+    }
+}
+
+/// Checks the skew of the wall clock since the previous sample, warning
+/// about it when past the threshold; a zero threshold disables the warning.
+pub fn check_clock_skew(
+    previous: &ClockSample,
+    threshold: Duration,
+) -> (ClockSample, Option<ClockSkew>) {
+    let now = ClockSample::now();
+    let skew = now.skew_since(previous);
+    if threshold.is_zero() || skew.unsigned_abs() < threshold.as_nanos() as u64
+    {
+        return (now, None);
+    }
+
+    let skew = ClockSkew {
+        before: previous.clone(),
+        after: now.clone(),
+        skew_ms: skew / 1_000_000,
+    };
+    warn!(
+        "Wall clock skewed by {}ms since {}, now {}: the timestamps of the \
+        events may not be comparable with the other nodes",
+        skew.skew_ms, skew.before.wall_clock, skew.after.wall_clock
+    );
+    MayastorEnvironment::global_or_default()
+        .event(EventAction::StateChange, clock_skew_event_meta(&skew))
+        .generate();
+    *LAST_SKEW.lock().unwrap() = Some(skew.clone());
+    (now, Some(skew))
+}
+
+/// Periodically checks the skew of the wall clock, unless the threshold is
+/// zero.
+pub async fn clock_skew_loop(interval: Duration, threshold: Duration) {
+    if threshold.is_zero() {
+        info!(
+            boot_id = boot_id(),
+            "Not checking the skew of the wall clock"
+        );
+        return;
+    }
+    info!(
+        boot_id = boot_id(),
+        "Checking the skew of the wall clock every {interval:?}"
+    );
+    let mut interval = tokio::time::interval(interval);
+    let mut previous = ClockSample::now();
+    loop {
+        interval.tick().await;
+        previous = check_clock_skew(&previous, threshold).0;
+    }
+}
+
+/// Registers the JSON-RPC methods of the clocks.
+pub(crate) fn register_rpc_methods() {
+    // clocks of the node, to correlate its event timestamps with those
+    // of the other nodes
+    jsonrpc_register::<(), _, _, JsonRpcError>("mayastor_clock_status", |_| {
+        async move { Ok(clock_status()) }.boxed_local()
+    });
+}
@@ -1,8 +1,13 @@
 use std::{
     env,
     ffi::CString,
+    fs::{File, OpenOptions},
     net::Ipv4Addr,
-    os::raw::{c_char, c_void},
+    os::{
+        fd::AsRawFd,
+        raw::{c_char, c_void},
+    },
+    path::{Path, PathBuf},
     pin::Pin,
     str::FromStr,
     sync::{
@@ -77,6 +82,7 @@ use crate::{
         Config,
         PoolConfig,
         Registration,
+        StartupProgress,
     },
 };
 
@@ -130,6 +136,23 @@ fn parse_crdt(src: &str) -> Result<[u16; TARGET_CRDT_LEN], String> {
     }
 }
 
+fn parse_nqn_prefix(src: &str) -> Result<String, String> {
+    subsys::validate_nqn_prefix(src).map(|_| src.to_string())
+}
+
+fn parse_port_range(src: &str) -> Result<String, String> {
+    subsys::parse_port_range(src).map(|_| src.to_string())
+}
+
+fn parse_cntlid_range(src: &str) -> Result<String, String> {
+    subsys::parse_cntlid_range(src).map(|_| src.to_string())
+}
+
+fn parse_kato(src: &str) -> Result<u32, String> {
+    let kato_ms = src.parse::<u32>().map_err(|e| e.to_string())?;
+    subsys::validate_kato(kato_ms).map(|_| kato_ms)
+}
+
 #[derive(Debug, Clone, Parser)]
 #[clap(
     name = package_description!(),
@@ -146,9 +169,13 @@ pub struct MayastorCliArgs {
     #[clap(short = 'L')]
     /// Enable logging for sub components.
     pub log_components: Vec<String>,
-    #[clap(short = 'F')]
+    #[clap(short = 'F', long = "log-format")]
     /// Log format.
     pub log_format: Option<logger::LogFormat>,
+    #[clap(long = "log-redact", value_delimiter = ',')]
+    /// Comma-separated names of the log fields whose values are redacted
+    /// from the json logs, e.g. `hostnqn,host`.
+    pub log_redact: Vec<String>,
     #[clap(short = 'm', default_value = "0x1")]
     /// The reactor mask to be used for starting up the instance
     pub reactor_mask: String,
@@ -170,6 +197,7 @@ pub struct MayastorCliArgs {
     pub mayastor_config: Option<String>,
     #[clap(long)]
     /// Path to persistence through power loss nvme reservation base directory.
+    /// Instances running side by side on a node must each be given their own.
     pub ptpl_dir: Option<String>,
     #[clap(short = 'P')]
     /// Path to pool config file.
@@ -218,6 +246,80 @@ pub struct MayastorCliArgs {
         value_parser = parse_crdt,
     )]
     pub nvmf_tgt_crdt: [u16; TARGET_CRDT_LEN],
+    /// Cores the poll groups of the NVMF target run on (e.g. "0-1,4"). All
+    /// the reactor cores are used by default.
+    #[clap(long = "tgt-cores", env = "NVMF_TGT_CORES")]
+    pub nvmf_tgt_cores: Option<String>,
+    /// Cores the poll groups of a separate NVMF target for the replica
+    /// traffic run on. When set, the replicas are exported by this target on
+    /// the replica port while the nexuses are exported on the nexus port.
+    #[clap(long = "replica-tgt-cores", env = "NVMF_REPLICA_TGT_CORES")]
+    pub nvmf_replica_tgt_cores: Option<String>,
+    /// Prefix of the NQNs of the NVMe-oF subsystems, of the form
+    /// `nqn.yyyy-mm.reverse.domain`, so that they follow the naming
+    /// conventions of the cluster.
+    #[clap(
+        long = "nqn-prefix",
+        env = "NVMF_NQN_PREFIX",
+        value_parser = parse_nqn_prefix,
+    )]
+    pub nqn_prefix: Option<String>,
+    /// Port the nexuses are exported on, 4421 by default. Instances running
+    /// side by side on a node must each be given ports of their own.
+    #[clap(long = "nvmf-nexus-port", env = "NVMF_NEXUS_PORT")]
+    pub nvmf_nexus_port: Option<u16>,
+    /// Port the replicas are exported on, 8420 by default.
+    #[clap(long = "nvmf-replica-port", env = "NVMF_REPLICA_PORT")]
+    pub nvmf_replica_port: Option<u16>,
+    /// Shared memory ID of the instance, which names its hugepage files. The
+    /// PID of the process is used when not set, with no shared memory config,
+    /// so that instances running side by side never share their hugepages.
+    #[clap(long = "shm-id", env = "SHM_ID")]
+    pub shm_id: Option<u16>,
+    /// Range of the ports (e.g. "8430-8449") the subsystems are each given a
+    /// port of their own from, for their listeners without an explicit port,
+    /// rather than the port of their target.
+    #[clap(
+        long = "nvmf-port-range",
+        env = "NVMF_PORT_RANGE",
+        value_parser = parse_port_range,
+    )]
+    pub nvmf_port_range: Option<String>,
+    /// File the ports given to the subsystems from the port range are kept
+    /// in, so that the subsystems listen on the same ports after a restart.
+    /// Instances running side by side on a node must each be given their own.
+    #[clap(long = "nvmf-port-state", env = "NVMF_PORT_STATE")]
+    pub nvmf_port_state: Option<String>,
+    /// Range of the controller IDs (e.g. "1-1000") given out by the
+    /// subsystems without an explicit range, so that the nodes of a cluster
+    /// given disjoint ranges never give out the same controller ID.
+    #[clap(
+        long = "nvmf-cntlid-range",
+        env = "NVMF_CNTLID_RANGE",
+        value_parser = parse_cntlid_range,
+    )]
+    pub nvmf_cntlid_range: Option<String>,
+    /// Keep alive timeout in milliseconds given to the controllers of the
+    /// nexuses, rather than the one the hosts ask for.
+    #[clap(
+        long = "nvmf-nexus-kato-ms",
+        env = "NVMF_NEXUS_KATO_MS",
+        value_parser = parse_kato,
+    )]
+    pub nvmf_nexus_kato_ms: Option<u32>,
+    /// Keep alive timeout in milliseconds given to the controllers of the
+    /// replicas, rather than the one the nexuses ask for.
+    #[clap(
+        long = "nvmf-replica-kato-ms",
+        env = "NVMF_REPLICA_KATO_MS",
+        value_parser = parse_kato,
+    )]
+    pub nvmf_replica_kato_ms: Option<u32>,
+    /// Grace period in milliseconds after a keep alive timeout on a
+    /// replica, past which the reservations of the host are released unless
+    /// it reconnected. The reservations are kept when not set.
+    #[clap(long = "nvmf-kato-resv-grace-ms", env = "NVMF_KATO_RESV_GRACE_MS")]
+    pub nvmf_kato_resv_grace_ms: Option<u64>,
     /// The gRPC api version.
     #[clap(
         long,
@@ -269,12 +371,144 @@ pub struct MayastorCliArgs {
     /// # Warning: Don't use this in production.
     #[clap(long, env = "MAYASTOR_DELAY", hide = true, value_parser = delay_compat)]
     pub developer_delay: bool,
-    /// Enables RDMA between initiator and Mayastor Nvmf target.
+    /// Enables RDMA between initiator and Mayastor Nvmf target. With a
+    /// separate replica target, the replica target is left to its own
+    /// configuration.
     #[clap(long = "enable-rdma", env = "ENABLE_RDMA", value_parser = delay_compat)]
     pub rdma: bool,
     /// Enables globally blob store cluster release on unmap.
     #[clap(long, env = "ENABLE_BS_CLUSTER_UNMAP", hide = true)]
     pub bs_cluster_unmap: bool,
+    /// Interval (in seconds) of the audit comparing the persisted share
+    /// state of the replicas with the NVMe-oF subsystems.
+    /// The audit is disabled when not set.
+    #[clap(
+        long = "share-audit-interval",
+        env = "SHARE_AUDIT_INTERVAL",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub share_audit_interval: Option<u64>,
+    /// Restore the persisted share state when the audit finds a drift.
+    #[clap(long = "share-audit-fix", env = "SHARE_AUDIT_FIX")]
+    pub share_audit_fix: bool,
+    /// Average latency (in microseconds) of the base device of a pool past
+    /// which the I/O admitted to the pool is throttled.
+    /// The admission control is disabled when not set.
+    #[clap(
+        long = "pool-latency-threshold-us",
+        env = "POOL_LATENCY_THRESHOLD_US"
+    )]
+    pub pool_latency_threshold_us: Option<u64>,
+    /// Interval (in milliseconds) between the samples of the latency of the
+    /// pools.
+    #[clap(
+        long = "pool-latency-interval-ms",
+        env = "POOL_LATENCY_INTERVAL_MS",
+        default_value = "1000"
+    )]
+    pub pool_latency_interval_ms: u64,
+    /// Interval (in seconds) of the cleanup of the NVMe-oF subsystems whose
+    /// bdev no longer exists.
+    /// The cleanup is disabled when not set.
+    #[clap(
+        long = "stale-subsystem-interval",
+        env = "STALE_SUBSYSTEM_INTERVAL",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub stale_subsystem_interval: Option<u64>,
+    /// Interval (in seconds) at which the rates of the reads and writes of
+    /// the hosts of the nexuses are published as events.
+    /// No such event is published when not set.
+    #[clap(
+        long = "nvmf-io-stats-interval",
+        env = "NVMF_IO_STATS_INTERVAL",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    pub nvmf_io_stats_interval: Option<u64>,
+    /// Interval (in milliseconds) between the checks for shares whose lease
+    /// has expired.
+    #[clap(
+        long = "share-lease-interval-ms",
+        env = "SHARE_LEASE_INTERVAL_MS",
+        default_value = "1000"
+    )]
+    pub share_lease_interval_ms: u64,
+    /// Maximum number of pools imported, and of replicas re-shared, at the
+    /// same time during startup.
+    #[clap(
+        long = "startup-concurrency",
+        env = "STARTUP_CONCURRENCY",
+        default_value = "16"
+    )]
+    pub startup_concurrency: usize,
+    /// Interval (in milliseconds) between the samples of the I/O buffer
+    /// pools, an event being emitted when I/O is queued for buffers.
+    #[clap(
+        long = "iobuf-monitor-interval-ms",
+        env = "IOBUF_MONITOR_INTERVAL_MS",
+        default_value = "5000"
+    )]
+    pub iobuf_monitor_interval_ms: u64,
+    /// Latency target (in microseconds) of the I/O towards the remote
+    /// replicas, past which the commands in flight on a slow replica path
+    /// are limited.
+    /// The congestion control is disabled when not set.
+    #[clap(long = "nvme-latency-target-us", env = "NVME_LATENCY_TARGET_US")]
+    pub nvme_latency_target_us: Option<u64>,
+    /// Interval (in milliseconds) between the checks of the wall clock
+    /// against the monotonic clock.
+    #[clap(
+        long = "clock-skew-interval-ms",
+        env = "CLOCK_SKEW_INTERVAL_MS",
+        default_value = "10000"
+    )]
+    pub clock_skew_interval_ms: u64,
+    /// Skew (in milliseconds) of the wall clock between two checks past
+    /// which it is warned about and an event is emitted.
+    /// The check is disabled when 0.
+    #[clap(
+        long = "clock-skew-threshold-ms",
+        env = "CLOCK_SKEW_THRESHOLD_MS",
+        default_value = "500"
+    )]
+    pub clock_skew_threshold_ms: u64,
+    /// Share of the capacity of every pool reserved for the system overhead
+    /// (metadata, clusters copied on write), in percent, unless overridden
+    /// for the pool.
+    #[clap(
+        long = "pool-reserved-pct",
+        env = "POOL_RESERVED_PCT",
+        default_value = "0",
+        value_parser = clap::value_parser!(u8).range(0..=100)
+    )]
+    pub pool_reserved_pct: u8,
+    /// Disconnect the controllers of a host NQN connecting from an address
+    /// other than that of its connected controllers, as likely used by
+    /// several hosts; such connects are only warned about otherwise.
+    #[clap(long = "reject-duplicate-hosts", env = "REJECT_DUPLICATE_HOSTS")]
+    pub reject_duplicate_hosts: bool,
+    /// Endpoint (https) the anonymized usage reports of the node are sent to.
+    /// The telemetry is disabled when not set.
+    #[clap(
+        long = "telemetry-endpoint",
+        env = "TELEMETRY_ENDPOINT",
+        requires = "telemetry_key_file"
+    )]
+    pub telemetry_endpoint: Option<String>,
+    /// Path to the file of the key the usage reports are signed with.
+    #[clap(
+        long = "telemetry-key-file",
+        env = "TELEMETRY_KEY_FILE",
+        requires = "telemetry_endpoint"
+    )]
+    pub telemetry_key_file: Option<PathBuf>,
+    /// Interval (in seconds) between two usage reports.
+    #[clap(
+        long = "telemetry-interval",
+        env = "TELEMETRY_INTERVAL",
+        default_value = "86400"
+    )]
+    pub telemetry_interval: u64,
 }
 
 fn delay_compat(s: &str) -> Result<bool, String> {
@@ -299,9 +533,16 @@ impl MayastorFeatures {
             snapshot_rebuild,
         }
     }
-    /// Get all the supported and enabled features.
+    /// Get all the supported and enabled features, ANA reporting as
+    /// configured for the nexuses once the config is loaded.
     pub fn get() -> Self {
-        MAYASTOR_FEATURES.get_or_init(Self::init_features).clone()
+        let mut features =
+            MAYASTOR_FEATURES.get_or_init(Self::init_features).clone();
+        if let Some(cfg) = subsys::config::CONFIG.get() {
+            features.asymmetric_namespace_access =
+                cfg.nexus_opts.nvmf_ana_reporting;
+        }
+        features
     }
 }
 
@@ -321,6 +562,7 @@ impl Default for MayastorCliArgs {
             no_pci: true,
             log_components: vec![],
             log_format: None,
+            log_redact: vec![],
             mayastor_config: None,
             ptpl_dir: None,
             pool_config: None,
@@ -331,6 +573,18 @@ impl Default for MayastorCliArgs {
             registration_endpoint: None,
             nvmf_tgt_interface: None,
             nvmf_tgt_crdt: [0; TARGET_CRDT_LEN],
+            nvmf_tgt_cores: None,
+            nvmf_replica_tgt_cores: None,
+            nqn_prefix: None,
+            nvmf_nexus_port: None,
+            nvmf_replica_port: None,
+            shm_id: None,
+            nvmf_port_range: None,
+            nvmf_port_state: None,
+            nvmf_cntlid_range: None,
+            nvmf_nexus_kato_ms: None,
+            nvmf_replica_kato_ms: None,
+            nvmf_kato_resv_grace_ms: None,
             api_versions: vec![ApiVersion::V0, ApiVersion::V1],
             diagnose_stack: None,
             reactor_freeze_detection: false,
@@ -344,6 +598,23 @@ impl Default for MayastorCliArgs {
             developer_delay: false,
             rdma: false,
             bs_cluster_unmap: false,
+            share_audit_interval: None,
+            share_audit_fix: false,
+            pool_latency_threshold_us: None,
+            pool_latency_interval_ms: 1000,
+            stale_subsystem_interval: None,
+            nvmf_io_stats_interval: None,
+            share_lease_interval_ms: 1000,
+            startup_concurrency: 16,
+            iobuf_monitor_interval_ms: 5000,
+            nvme_latency_target_us: None,
+            clock_skew_interval_ms: 10000,
+            clock_skew_threshold_ms: 500,
+            pool_reserved_pct: 0,
+            reject_duplicate_hosts: false,
+            telemetry_endpoint: None,
+            telemetry_key_file: None,
+            telemetry_interval: 86400,
         }
     }
 }
@@ -351,7 +622,7 @@ impl Default for MayastorCliArgs {
 impl MayastorCliArgs {
     /// Create the hostnqn for this io-engine instance.
     pub fn make_hostnqn(&self) -> Option<String> {
-        make_hostnqn(self.node_name.as_ref())
+        make_hostnqn(self.node_name.as_ref(), self.nqn_prefix.as_deref())
     }
 }
 
@@ -364,6 +635,28 @@ pub static GLOBAL_RC: Lazy<Arc<Mutex<i32>>> =
 pub static SIG_RECEIVED: Lazy<AtomicBool> =
     Lazy::new(|| AtomicBool::new(false));
 
+/// Lock files of the state paths of this instance, held until it exits.
+static STATE_LOCKS: Lazy<Mutex<Vec<File>>> =
+    Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Takes an exclusive lock on the given lock file, created if needed, for as
+/// long as the process runs. Returns false when the file is locked already,
+/// by another instance or by an earlier call.
+pub fn lock_state_path(path: &Path) -> std::io::Result<bool> {
+    let file = OpenOptions::new().create(true).write(true).open(path)?;
+    let rc =
+        unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+    if rc != 0 {
+        let error = std::io::Error::last_os_error();
+        return match error.raw_os_error() {
+            Some(libc::EWOULDBLOCK) => Ok(false),
+            _ => Err(error),
+        };
+    }
+    STATE_LOCKS.lock().unwrap().push(file);
+    Ok(true)
+}
+
 #[derive(Debug, Snafu)]
 pub enum EnvError {
     #[snafu(display("Failed to install signal handler"))]
@@ -420,12 +713,47 @@ pub struct MayastorEnvironment {
     nvmf_tgt_interface: Option<String>,
     /// NVMF target Command Retry Delay in x100 ms.
     pub nvmf_tgt_crdt: [u16; TARGET_CRDT_LEN],
+    /// Cores of the poll groups of the NVMF target.
+    pub nvmf_tgt_cores: Option<String>,
+    /// Cores of the poll groups of the separate replica NVMF target.
+    pub nvmf_replica_tgt_cores: Option<String>,
+    /// Prefix of the NQNs of the NVMe-oF subsystems.
+    pub nqn_prefix: Option<String>,
+    /// Port the nexuses are exported on.
+    pub nvmf_nexus_port: Option<u16>,
+    /// Port the replicas are exported on.
+    pub nvmf_replica_port: Option<u16>,
+    /// Range of the ports the subsystems are given a port from.
+    pub nvmf_port_range: Option<String>,
+    /// File the ports given to the subsystems are kept in.
+    pub nvmf_port_state: Option<String>,
+    /// Range of the controller IDs of the subsystems.
+    pub nvmf_cntlid_range: Option<String>,
+    /// Keep alive timeout of the controllers of the nexuses.
+    pub nvmf_nexus_kato_ms: Option<u32>,
+    /// Keep alive timeout of the controllers of the replicas.
+    pub nvmf_replica_kato_ms: Option<u32>,
+    /// Grace period before releasing the reservations of a timed out host.
+    pub nvmf_kato_resv_grace_ms: Option<u64>,
     api_versions: Vec<ApiVersion>,
     skip_sig_handler: bool,
     enable_io_all_thrd_nexus_channels: bool,
     developer_delay: bool,
-    rdma: bool,
+    /// Enables the RDMA transport of the NVMF targets.
+    pub rdma: bool,
     bs_cluster_unmap: bool,
+    /// Maximum number of pools imported, and of replicas re-shared, at the
+    /// same time during startup.
+    pub startup_concurrency: usize,
+    /// Latency target of the I/O towards the remote replicas, enabling their
+    /// congestion control.
+    pub nvme_latency_target_us: Option<u64>,
+    /// Share of the capacity of every pool reserved for the system overhead,
+    /// in percent, unless overridden for the pool.
+    pub pool_reserved_pct: u8,
+    /// Disconnect the controllers of a host NQN connecting from a second
+    /// address.
+    pub reject_duplicate_hosts: bool,
 }
 
 impl Default for MayastorEnvironment {
@@ -470,12 +798,27 @@ impl Default for MayastorEnvironment {
             nvme_ctl_io_ctx_pool_size: 65535,
             nvmf_tgt_interface: None,
             nvmf_tgt_crdt: [0; TARGET_CRDT_LEN],
+            nvmf_tgt_cores: None,
+            nvmf_replica_tgt_cores: None,
+            nqn_prefix: None,
+            nvmf_nexus_port: None,
+            nvmf_replica_port: None,
+            nvmf_port_range: None,
+            nvmf_port_state: None,
+            nvmf_cntlid_range: None,
+            nvmf_nexus_kato_ms: None,
+            nvmf_replica_kato_ms: None,
+            nvmf_kato_resv_grace_ms: None,
             api_versions: vec![ApiVersion::V0, ApiVersion::V1],
             skip_sig_handler: false,
             enable_io_all_thrd_nexus_channels: false,
             developer_delay: false,
             rdma: false,
             bs_cluster_unmap: false,
+            startup_concurrency: 16,
+            nvme_latency_target_us: None,
+            pool_reserved_pct: 0,
+            reject_duplicate_hosts: false,
         }
     }
 }
@@ -598,6 +941,7 @@ impl MayastorEnvironment {
                 args.node_name
                     .or_else(|| env::var("HOSTNAME").ok())
                     .as_ref(),
+                args.nqn_prefix.as_deref(),
             ),
             mayastor_config: args.mayastor_config,
             ptpl_dir: args.ptpl_dir,
@@ -614,11 +958,27 @@ impl MayastorEnvironment {
             nvme_ctl_io_ctx_pool_size: args.nvme_ctl_io_ctx_pool_size,
             nvmf_tgt_interface: args.nvmf_tgt_interface,
             nvmf_tgt_crdt: args.nvmf_tgt_crdt,
+            nvmf_tgt_cores: args.nvmf_tgt_cores,
+            nvmf_replica_tgt_cores: args.nvmf_replica_tgt_cores,
+            nqn_prefix: args.nqn_prefix,
+            nvmf_nexus_port: args.nvmf_nexus_port,
+            nvmf_replica_port: args.nvmf_replica_port,
+            shm_id: args.shm_id.map_or(-1, i32::from),
+            nvmf_port_range: args.nvmf_port_range,
+            nvmf_port_state: args.nvmf_port_state,
+            nvmf_cntlid_range: args.nvmf_cntlid_range,
+            nvmf_nexus_kato_ms: args.nvmf_nexus_kato_ms,
+            nvmf_replica_kato_ms: args.nvmf_replica_kato_ms,
+            nvmf_kato_resv_grace_ms: args.nvmf_kato_resv_grace_ms,
             api_versions: args.api_versions,
             skip_sig_handler: args.skip_sig_handler,
             developer_delay: args.developer_delay,
             rdma: args.rdma,
             bs_cluster_unmap: args.bs_cluster_unmap,
+            startup_concurrency: args.startup_concurrency,
+            nvme_latency_target_us: args.nvme_latency_target_us,
+            pool_reserved_pct: args.pool_reserved_pct,
+            reject_duplicate_hosts: args.reject_duplicate_hosts,
             enable_io_all_thrd_nexus_channels: args
                 .enable_io_all_thrd_nexus_channels,
             ..Default::default()
@@ -978,6 +1338,31 @@ impl MayastorEnvironment {
         self.clone().setup_static();
     }
 
+    /// Locks the state paths of the instance: the PTPL directory, which the
+    /// other state files of the node are kept in as well, and the port state
+    /// file. An instance given the same paths as another one running on the
+    /// node would overwrite its state, so it does not start.
+    fn lock_state_paths(&self) {
+        let port_state = Config::get().nexus_opts.nvmf_port_state.as_ref();
+        let locks = [
+            self.ptpl_dir.as_ref().map(|d| Path::new(d).join(".lock")),
+            port_state.map(|f| PathBuf::from(format!("{f}.lock"))),
+        ];
+        for lock in locks.into_iter().flatten() {
+            match lock_state_path(&lock) {
+                Ok(true) => {}
+                Ok(false) => panic!(
+                    "{} is locked by another instance, each instance must be \
+                    given state paths of its own",
+                    lock.display()
+                ),
+                Err(error) => {
+                    warn!("Failed to lock {}: {error}", lock.display())
+                }
+            }
+        }
+    }
+
     /// load the pool config file.
     fn load_pool_config(&self) -> Option<PoolConfig> {
         if let Some(file) = &self.pool_config {
@@ -1010,6 +1395,7 @@ impl MayastorEnvironment {
                 tracing::error!(%error, "Failed to create ptpl base path directories");
             }
         }
+        self.lock_state_paths();
 
         let pool_config = self.load_pool_config();
 
@@ -1080,8 +1466,9 @@ impl MayastorEnvironment {
 
         // load any pools that need to be created
         if let Some(config) = pool_config {
-            config.import_pools();
+            config.import_pools(self.startup_concurrency);
         }
+        StartupProgress::ready();
 
         self
     }
@@ -1156,10 +1543,16 @@ impl MayastorEnvironment {
     }
 }
 
-fn make_hostnqn(node_name: Option<&String>) -> Option<String> {
-    std::env::var("HOSTNQN").ok().or_else(|| {
-        node_name.map(|n| format!("{NVME_NQN_PREFIX}:node-name:{n}"))
-    })
+/// Makes the hostnqn of the node with the NQN prefix of the cluster, the
+/// openebs one when not set.
+fn make_hostnqn(
+    node_name: Option<&String>,
+    nqn_prefix: Option<&str>,
+) -> Option<String> {
+    let prefix = nqn_prefix.unwrap_or(NVME_NQN_PREFIX);
+    std::env::var("HOSTNQN")
+        .ok()
+        .or_else(|| node_name.map(|n| format!("{prefix}:node-name:{n}")))
 }
 
 fn print_asan_env() {
@@ -6,14 +6,19 @@ use spdk_rs::{
     libspdk::{
         spdk_bdev_fn_table,
         spdk_bdev_io,
+        spdk_bdev_io_complete,
         spdk_bdev_io_complete_nvme_status,
         spdk_io_channel,
+        SPDK_BDEV_IO_STATUS_SUCCESS,
     },
     BdevIo,
     UntypedBdev,
 };
 
-use crate::core::IoCompletionStatus;
+use crate::{
+    core::{IoCompletionStatus, Reactors},
+    sleep::mayastor_sleep,
+};
 
 use super::{
     FaultDomain,
@@ -68,23 +73,39 @@ unsafe extern "C" fn inject_submit_request(
         io.iovs(),
     );
 
-    match inj.inject(FaultIoStage::Submission, &ctx) {
-        Some(s) => {
+    if inj.inject(FaultIoStage::Submission, &ctx).is_none() {
+        ((*t.fn_table_orig).submit_request.unwrap())(chan, io_ptr);
+        return;
+    }
+
+    match inj.method {
+        FaultMethod::Status(IoCompletionStatus::NvmeError(err)) => {
             error!("Injection {inj:?}: failing I/O: {io:?}");
 
-            match s {
-                IoCompletionStatus::NvmeError(err) => {
-                    let (sct, sc) = err.as_sct_sc_codes();
-                    unsafe {
-                        spdk_bdev_io_complete_nvme_status(io_ptr, 0, sct, sc);
-                    }
-                }
-                _ => panic!("Non-NVME error is not supported"),
+            let (sct, sc) = err.as_sct_sc_codes();
+            unsafe {
+                spdk_bdev_io_complete_nvme_status(io_ptr, 0, sct, sc);
             }
         }
-        None => {
-            ((*t.fn_table_orig).submit_request.unwrap())(chan, io_ptr);
+        FaultMethod::Delay(delay) => {
+            debug!("Injection {inj:?}: delaying I/O: {io:?}");
+
+            // The original submission must happen on the I/O channel's
+            // thread, so the delayed submission is run on this reactor.
+            let submit = (*t.fn_table_orig).submit_request.unwrap();
+            Reactors::current().send_future(async move {
+                mayastor_sleep(delay).await.ok();
+                unsafe { submit(chan, io_ptr) };
+            });
+        }
+        FaultMethod::Drop => {
+            warn!("Injection {inj:?}: dropping I/O: {io:?}");
+
+            unsafe {
+                spdk_bdev_io_complete(io_ptr, SPDK_BDEV_IO_STATUS_SUCCESS);
+            }
         }
+        _ => panic!("Non-NVME error is not supported"),
     }
 }
 
@@ -102,10 +123,14 @@ pub(super) fn add_bdev_io_injection(
     if !matches!(
         inj.method,
         FaultMethod::Status(IoCompletionStatus::NvmeError(_))
+            | FaultMethod::Delay(_)
+            | FaultMethod::Drop
     ) {
         return Err(FaultInjectionError::InvalidInjection {
             name: inj.device_name.clone(),
-            msg: format!("bdev I/O supports only NVME error injection"),
+            msg: format!(
+                "bdev I/O supports only NVME error, delay and drop injections"
+            ),
         });
     }
 
@@ -1,6 +1,9 @@
 use rand::RngCore;
 use regex::Regex;
-use std::fmt::{Debug, Display, Formatter};
+use std::{
+    fmt::{Debug, Display, Formatter},
+    time::Duration,
+};
 
 use spdk_rs::NvmeStatus;
 
@@ -15,6 +18,13 @@ pub enum FaultMethod {
     Status(IoCompletionStatus),
     /// Introduces data buffer corruption.
     Data,
+    /// Delays the submission of an affected operation by the given duration.
+    /// Supported only in the bdev I/O domain.
+    Delay(Duration),
+    /// Completes an affected operation successfully without submitting it to
+    /// the device, e.g. to lose flushes.
+    /// Supported only in the bdev I/O domain.
+    Drop,
 }
 
 impl Debug for FaultMethod {
@@ -24,6 +34,8 @@ impl Debug for FaultMethod {
                 write!(f, "Status[{s:?}]")
             }
             Self::Data => f.write_str("Data"),
+            Self::Delay(d) => write!(f, "Delay[{d:?}]"),
+            Self::Drop => f.write_str("Drop"),
         }
     }
 }
@@ -58,6 +70,8 @@ impl Display for FaultMethod {
                 write!(f, "status-admin")
             }
             Self::Data => f.write_str("data"),
+            Self::Delay(d) => write!(f, "delay-{}", d.as_millis()),
+            Self::Drop => f.write_str("drop"),
             _ => f.write_str("invalid"),
         }
     }
@@ -81,9 +95,19 @@ impl FaultMethod {
                 self.inject_data_errors(state, ctx);
                 Some(IoCompletionStatus::Success)
             }
+            // Delays and drops are carried out by the bdev I/O injection
+            // itself.
+            FaultMethod::Delay(_) | FaultMethod::Drop => {
+                Some(IoCompletionStatus::Success)
+            }
         }
     }
 
+    /// True if the method is only supported in the bdev I/O domain.
+    pub(super) fn is_bdev_io_only(&self) -> bool {
+        matches!(self, FaultMethod::Delay(_) | FaultMethod::Drop)
+    }
+
     /// TODO
     fn inject_data_errors(&self, s: &mut InjectionState, ctx: &InjectIoCtx) {
         let Some(iovs) = ctx.iovs_mut() else {
@@ -102,6 +126,17 @@ impl FaultMethod {
         lazy_static::lazy_static! {
             static ref NVME_RE: Regex =
                 Regex::new(r"^status-nvme-([0-9a-f.]+)-([0-9a-f.]+)$").unwrap();
+            static ref DELAY_RE: Regex = Regex::new(r"^delay-([0-9]+)$").unwrap();
+        }
+
+        if let Some(cap) = DELAY_RE.captures(s) {
+            return cap
+                .get(1)
+                .unwrap()
+                .as_str()
+                .parse::<u64>()
+                .ok()
+                .map(|ms| Self::Delay(Duration::from_millis(ms)));
         }
 
         if let Some(cap) = NVME_RE.captures(s) {
@@ -128,6 +163,7 @@ impl FaultMethod {
                 IoSubmissionFailure::Write,
             ),
             "status-admin" => IoCompletionStatus::AdminCommandError,
+            "drop" => return Some(Self::Drop),
             _ => return None,
         };
         Some(Self::Status(r))
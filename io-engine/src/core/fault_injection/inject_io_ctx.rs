@@ -104,6 +104,7 @@ impl InjectIoCtx {
             FaultIoOperation::ReadWrite => {
                 self.io_type == IoType::Read || self.io_type == IoType::Write
             }
+            FaultIoOperation::Flush => self.io_type == IoType::Flush,
         }
     }
 
@@ -40,6 +40,14 @@ impl Injections {
     pub fn add(&mut self, inj: Injection) -> Result<(), FaultInjectionError> {
         if inj.domain == FaultDomain::BdevIo {
             add_bdev_io_injection(&inj)?;
+        } else if inj.method.is_bdev_io_only() {
+            return Err(FaultInjectionError::InvalidInjection {
+                name: inj.device_name.clone(),
+                msg: format!(
+                    "method '{}' is supported only in the bdev I/O domain",
+                    inj.method
+                ),
+            });
         }
 
         info!("Adding injected fault: '{inj:?}'");
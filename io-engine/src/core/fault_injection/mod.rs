@@ -54,6 +54,7 @@ pub enum FaultIoOperation {
     Read,
     Write,
     ReadWrite,
+    Flush,
 }
 
 impl Display for FaultIoOperation {
@@ -62,6 +63,7 @@ impl Display for FaultIoOperation {
             FaultIoOperation::Read => f.write_str("r"),
             FaultIoOperation::Write => f.write_str("w"),
             FaultIoOperation::ReadWrite => f.write_str("rw"),
+            FaultIoOperation::Flush => f.write_str("flush"),
         }
     }
 }
@@ -0,0 +1,95 @@
+//! Fencing of the share, unshare and destroy operations of the bdevs.
+//!
+//! These operations yield to the reactor while SPDK completes them, so that
+//! a confused control plane sending overlapping requests for the same bdev
+//! could interleave them, e.g. unshare a bdev while its subsystem is being
+//! started. Only one operation at a time may be in progress on a bdev, and
+//! the others fail at once instead of racing with it.
+//!
+//! A destroy unshares its bdev first: the destroy passes its own fence down
+//! to that unshare, which goes ahead under it instead of acquiring another
+//! one. Any other unshare still fails while the destroy is in progress.
+
+use std::{collections::HashMap, fmt, sync::Mutex};
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use super::CoreError;
+
+/// An operation on a bdev which excludes the others.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BdevOperation {
+    Share,
+    UpdateProperties,
+    Unshare,
+    Destroy,
+}
+
+impl fmt::Display for BdevOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            Self::Share => "share",
+            Self::UpdateProperties => "update properties",
+            Self::Unshare => "unshare",
+            Self::Destroy => "destroy",
+        };
+        write!(f, "{op}")
+    }
+}
+
+/// Operations in progress, by bdev name.
+static FENCES: Lazy<Mutex<HashMap<String, BdevOperation>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Guard of an operation in progress on a bdev, which ends when dropped.
+#[derive(Debug)]
+pub struct BdevFence {
+    name: String,
+}
+
+impl BdevFence {
+    /// Starts the given operation on the bdev, unless another one is in
+    /// progress.
+    pub fn acquire(
+        name: &str,
+        operation: BdevOperation,
+    ) -> Result<Self, CoreError> {
+        let mut fences = FENCES.lock().unwrap();
+        if let Some(operation) = fences.get(name) {
+            return Err(CoreError::OperationInProgress {
+                name: name.to_string(),
+                operation: *operation,
+            });
+        }
+        fences.insert(name.to_string(), operation);
+        Ok(Self {
+            name: name.to_string(),
+        })
+    }
+
+    /// Starts the given operation on the bdev, unless the given fence of an
+    /// operation in progress on the very bdev covers it already.
+    pub fn acquire_within(
+        name: &str,
+        operation: BdevOperation,
+        owner: Option<&Self>,
+    ) -> Result<Option<Self>, CoreError> {
+        match owner {
+            Some(fence) if fence.name == name => Ok(None),
+            _ => Self::acquire(name, operation).map(Some),
+        }
+    }
+
+    /// Returns the operation in progress on the bdev, if any.
+    pub fn in_progress(name: &str) -> Option<BdevOperation> {
+        FENCES.lock().unwrap().get(name).copied()
+    }
+}
+
+impl Drop for BdevFence {
+    fn drop(&mut self) {
+        FENCES.lock().unwrap().remove(&self.name);
+    }
+}
@@ -0,0 +1,226 @@
+//! Usage and exhaustion diagnostics of the I/O buffer pools.
+//!
+//! The data buffers of the I/O are taken from the small and large buffer
+//! pools, whose sizes are set by the `iobuf_opts` of the configuration. When
+//! a pool is exhausted, the I/O waits for a buffer to be released, which
+//! shows up as latency. The pools are sampled periodically and an event is
+//! emitted when I/O starts, and stops, being queued for buffers.
+
+use std::{ffi::CStr, os::raw::c_void, sync::Mutex, time::Duration};
+
+use events_api::event::EventAction;
+use futures::{channel::oneshot, FutureExt};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use spdk_rs::libspdk::{
+    spdk_iobuf_get_stats,
+    spdk_iobuf_module_stats,
+    spdk_iobuf_pool_stats,
+};
+
+use crate::{
+    core::{MayastorEnvironment, Reactor},
+    eventing::{io_engine_events::iobuf_state_event_meta, EventWithMeta},
+    ffihelper::cb_arg,
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+    subsys::Config,
+};
+
+/// Buffers of a pool used by a module.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct IoBufPoolStats {
+    /// Buffers taken from the per-thread caches.
+    pub cache: u64,
+    /// Buffers taken from the shared pool.
+    pub main: u64,
+    /// Buffer requests which were queued as the pool was exhausted.
+    pub retry: u64,
+}
+
+impl From<&spdk_iobuf_pool_stats> for IoBufPoolStats {
+    fn from(s: &spdk_iobuf_pool_stats) -> Self {
+        Self {
+            cache: s.cache,
+            main: s.main,
+            retry: s.retry,
+        }
+    }
+}
+
+impl IoBufPoolStats {
+    fn add(&mut self, other: &Self) {
+        self.cache += other.cache;
+        self.main += other.main;
+        self.retry += other.retry;
+    }
+}
+
+/// Buffers used by a module, e.g. the bdev layer or the NVMf TCP transport.
+#[derive(Debug, Clone, Serialize)]
+pub struct IoBufModuleStats {
+    pub module: String,
+    pub small_pool: IoBufPoolStats,
+    pub large_pool: IoBufPoolStats,
+}
+
+/// Sizes and usage of the buffer pools.
+#[derive(Debug, Clone, Serialize)]
+pub struct IoBufStats {
+    /// Number of small buffers.
+    pub small_pool_count: u64,
+    /// Number of large buffers.
+    pub large_pool_count: u64,
+    /// Size of a small buffer.
+    pub small_bufsize: u32,
+    /// Size of a large buffer.
+    pub large_bufsize: u32,
+    /// Usage of the small pool by all the modules.
+    pub small_pool: IoBufPoolStats,
+    /// Usage of the large pool by all the modules.
+    pub large_pool: IoBufPoolStats,
+    /// Whether buffer requests were queued during the last monitoring
+    /// interval.
+    pub exhausted: bool,
+    pub modules: Vec<IoBufModuleStats>,
+}
+
+/// Buffer requests queued as of the last sample, and whether requests were
+/// queued during the last interval.
+static LAST_SAMPLE: Lazy<Mutex<Option<(u64, bool)>>> =
+    Lazy::new(|| Mutex::new(None));
+
+/// Returns the usage of the buffer pools by every module.
+async fn module_stats() -> Vec<IoBufModuleStats> {
+    extern "C" fn stats_cb(
+        modules: *mut spdk_iobuf_module_stats,
+        num_modules: u32,
+        arg: *mut c_void,
+    ) {
+        let s = unsafe {
+            Box::from_raw(arg as *mut oneshot::Sender<Vec<IoBufModuleStats>>)
+        };
+        let modules = if modules.is_null() {
+            &[][..]
+        } else {
+            unsafe { std::slice::from_raw_parts(modules, num_modules as usize) }
+        };
+        let stats = modules
+            .iter()
+            .map(|m| IoBufModuleStats {
+                module: unsafe { CStr::from_ptr(m.module) }
+                    .to_string_lossy()
+                    .into_owned(),
+                small_pool: (&m.small_pool).into(),
+                large_pool: (&m.large_pool).into(),
+            })
+            .collect();
+        s.send(stats).ok();
+    }
+
+    let (s, r) = oneshot::channel::<Vec<IoBufModuleStats>>();
+    let rc = unsafe { spdk_iobuf_get_stats(Some(stats_cb), cb_arg(s)) };
+    if rc != 0 {
+        error!("Failed to get the I/O buffer stats: {rc}");
+        return vec![];
+    }
+    r.await.unwrap_or_default()
+}
+
+impl IoBufStats {
+    /// Returns the sizes and usage of the buffer pools.
+    pub async fn get() -> Self {
+        let opts = &Config::get().iobuf_opts;
+        let modules = module_stats().await;
+        let mut small_pool = IoBufPoolStats::default();
+        let mut large_pool = IoBufPoolStats::default();
+        for m in &modules {
+            small_pool.add(&m.small_pool);
+            large_pool.add(&m.large_pool);
+        }
+        let exhausted = LAST_SAMPLE
+            .lock()
+            .unwrap()
+            .map_or(false, |(_, exhausted)| exhausted);
+
+        Self {
+            small_pool_count: opts.small_pool_count,
+            large_pool_count: opts.large_pool_count,
+            small_bufsize: opts.small_bufsize,
+            large_bufsize: opts.large_bufsize,
+            small_pool,
+            large_pool,
+            exhausted,
+            modules,
+        }
+    }
+
+    /// Samples the buffer pools, emitting an event when buffer requests
+    /// start, or stop, being queued.
+    pub async fn sample() -> Self {
+        let mut stats = Self::get().await;
+        let retry = stats.small_pool.retry + stats.large_pool.retry;
+
+        let mut last = LAST_SAMPLE.lock().unwrap();
+        // the first sample only sets a baseline
+        let (queued, was_exhausted) = match *last {
+            Some((last_retry, exhausted)) => {
+                (retry.saturating_sub(last_retry), exhausted)
+            }
+            None => (0, false),
+        };
+        stats.exhausted = queued > 0;
+        *last = Some((retry, stats.exhausted));
+        drop(last);
+
+        if stats.exhausted != was_exhausted {
+            let (previous, next) = if stats.exhausted {
+                warn!(
+                    "I/O buffer pools exhausted: {queued} buffer requests \
+                    queued (small pool: {} buffers, large pool: {} buffers)",
+                    stats.small_pool_count, stats.large_pool_count
+                );
+                ("available", "exhausted")
+            } else {
+                info!("I/O buffer pools no longer exhausted");
+                ("exhausted", "available")
+            };
+            MayastorEnvironment::global_or_default()
+                .event(
+                    EventAction::StateChange,
+                    iobuf_state_event_meta(previous, next),
+                )
+                .generate();
+        }
+        stats
+    }
+}
+
+/// Periodically samples the buffer pools.
+pub async fn iobuf_monitor_loop(interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        let sample = Reactor::spawn_at_primary(async move {
+            IoBufStats::sample().await;
+        });
+        match sample {
+            Ok(rx) => {
+                if rx.await.is_err() {
+                    error!("I/O buffer sample was cancelled");
+                }
+            }
+            Err(error) => {
+                error!("Failed to sample the I/O buffer pools: {error}")
+            }
+        }
+    }
+}
+
+/// Registers the JSON-RPC methods of the I/O buffer pools.
+pub(crate) fn register_rpc_methods() {
+    // sizes and usage of the I/O buffer pools, and whether I/O was
+    // queued for buffers during the last monitoring interval
+    jsonrpc_register::<(), _, _, JsonRpcError>("mayastor_iobuf_stats", |_| {
+        async move { Ok(IoBufStats::get().await) }.boxed_local()
+    });
+}
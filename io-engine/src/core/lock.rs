@@ -82,12 +82,50 @@ impl ResourceSubsystem {
         wait_timeout: Option<Duration>,
         try_lock: bool,
     ) -> Option<ResourceLockGuard<'_>> {
-        // Calculate hash of the object to get the mutex index.
-        let mut hasher = DefaultHasher::new();
-        id.as_ref().hash(&mut hasher);
-        let mutex_id = hasher.finish() as usize % self.object_locks.len();
+        let mutex_id = self.mutex_id(id.as_ref());
         acquire_lock(&self.object_locks[mutex_id], wait_timeout, try_lock).await
     }
+
+    /// Lock several subsystem resources by their IDs and obtain their lock
+    /// guards. Resources sharing a mutex are locked once, and the mutexes
+    /// are always locked in the same order, so that callers locking
+    /// overlapping sets of resources cannot deadlock.
+    /// The timeout applies to the acquisition of all the locks.
+    pub async fn lock_resources<T: AsRef<str>>(
+        &self,
+        ids: impl IntoIterator<Item = T>,
+        wait_timeout: Option<Duration>,
+        try_lock: bool,
+    ) -> Option<Vec<ResourceLockGuard<'_>>> {
+        let mut mutex_ids = ids
+            .into_iter()
+            .map(|id| self.mutex_id(id.as_ref()))
+            .collect::<Vec<_>>();
+        mutex_ids.sort_unstable();
+        mutex_ids.dedup();
+
+        let acquire_all = async {
+            let mut guards = Vec::with_capacity(mutex_ids.len());
+            for mutex_id in mutex_ids {
+                guards.push(
+                    acquire_lock(&self.object_locks[mutex_id], None, try_lock)
+                        .await?,
+                );
+            }
+            Some(guards)
+        };
+        match wait_timeout {
+            Some(d) => tokio::time::timeout(d, acquire_all).await.ok()?,
+            None => acquire_all.await,
+        }
+    }
+
+    /// Calculate hash of the object to get the mutex index.
+    fn mutex_id(&self, id: &str) -> usize {
+        let mut hasher = DefaultHasher::new();
+        id.hash(&mut hasher);
+        hasher.finish() as usize % self.object_locks.len()
+    }
 }
 
 /// Structure that holds per-lock statistics.
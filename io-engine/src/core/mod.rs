@@ -37,12 +37,14 @@ pub use device_monitor::{
     DeviceCommand,
 };
 pub use env::{
+    lock_state_path,
     mayastor_env_stop,
     MayastorCliArgs,
     MayastorEnvironment,
     GLOBAL_RC,
     SIG_RECEIVED,
 };
+pub use fence::{BdevFence, BdevOperation};
 pub use handle::{BdevHandle, UntypedBdevHandle};
 pub use io_device::IoDevice;
 pub use logical_volume::LogicalVolume;
@@ -88,28 +90,37 @@ pub use snapshot::{
 
 use spdk_rs::libspdk::SPDK_NVME_SC_CAPACITY_EXCEEDED;
 
+pub mod accel;
+pub mod admin_ops;
 mod bdev;
 mod block_device;
+pub mod clock;
 mod descriptor;
 mod device_events;
 mod device_monitor;
 pub mod diagnostics;
 mod env;
 pub mod fault_injection;
+mod fence;
 mod handle;
 mod io_device;
 pub mod io_driver;
+pub mod iobuf;
 pub mod lock;
 pub mod logical_volume;
 pub mod mempool;
 mod nic;
+pub mod op_stats;
 pub mod partition;
+pub mod perf;
 mod reactor;
 pub mod runtime;
 pub mod segment_map;
 mod share;
 pub mod snapshot;
+pub mod telemetry;
 pub(crate) mod thread;
+pub mod volume_stats;
 pub(crate) mod wiper;
 mod work_queue;
 
@@ -347,6 +358,18 @@ pub enum CoreError {
     WipeFailed {
         source: wiper::Error,
     },
+    #[snafu(display("bdev {}: {} operation in progress", name, operation))]
+    OperationInProgress {
+        name: String,
+        operation: BdevOperation,
+    },
+    #[snafu(display(
+        "{} control-plane operations are queued, no more are admitted",
+        queued
+    ))]
+    AdminQueueFull {
+        queued: usize,
+    },
 }
 
 /// Represent error as Errno value.
@@ -460,6 +483,12 @@ impl ToErrno for CoreError {
             Self::WipeFailed {
                 ..
             } => Errno::EIO,
+            Self::OperationInProgress {
+                ..
+            } => Errno::EBUSY,
+            Self::AdminQueueFull {
+                ..
+            } => Errno::EAGAIN,
         }
     }
 }
@@ -553,3 +582,15 @@ impl MayastorBugFixes {
         }
     }
 }
+
+/// Registers the JSON-RPC methods of the core modules.
+pub(crate) fn register_rpc_methods() {
+    op_stats::register_rpc_methods();
+    perf::register_rpc_methods();
+    volume_stats::register_rpc_methods();
+    iobuf::register_rpc_methods();
+    clock::register_rpc_methods();
+    telemetry::register_rpc_methods();
+    accel::register_rpc_methods();
+    admin_ops::register_rpc_methods();
+}
@@ -0,0 +1,164 @@
+//! Duration and failure statistics of the major management operations of the
+//! io-engine, so that control-plane SLOs can be measured per node.
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use futures::FutureExt;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::jsonrpc::{jsonrpc_register, JsonRpcError};
+
+/// Upper bounds of the duration histogram buckets, in milliseconds.
+const BUCKETS_MS: [u64; 12] =
+    [1, 5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+/// A measured operation.
+#[derive(
+    Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    PoolCreate,
+    PoolDestroy,
+    ReplicaCreate,
+    ReplicaShare,
+    NexusCreate,
+    NexusPublish,
+    SubsystemStart,
+    SubsystemStop,
+    SubsystemPause,
+    SubsystemResume,
+}
+
+/// A cumulative histogram bucket.
+#[derive(Debug, Clone, Serialize)]
+pub struct Bucket {
+    /// Upper bound of the bucket in milliseconds, `None` for infinity.
+    pub le_ms: Option<u64>,
+    /// Number of operations which completed within the bound.
+    pub count: u64,
+}
+
+/// Statistics of an operation.
+#[derive(Debug, Clone, Serialize)]
+pub struct OpStats {
+    pub operation: Operation,
+    pub count: u64,
+    pub failures: u64,
+    pub total_ms: f64,
+    pub max_ms: f64,
+    pub buckets: Vec<Bucket>,
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    count: u64,
+    failures: u64,
+    total: Duration,
+    max: Duration,
+    /// Non-cumulative counts, the last one being the overflow bucket.
+    buckets: [u64; BUCKETS_MS.len() + 1],
+}
+
+static OP_STATS: Lazy<Mutex<BTreeMap<Operation, Histogram>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// Records a completed operation.
+pub fn record(op: Operation, duration: Duration, success: bool) {
+    let ms = duration.as_millis() as u64;
+    let bucket = BUCKETS_MS
+        .iter()
+        .position(|b| ms <= *b)
+        .unwrap_or(BUCKETS_MS.len());
+
+    let mut stats = OP_STATS.lock().unwrap();
+    let h = stats.entry(op).or_default();
+    h.count += 1;
+    if !success {
+        h.failures += 1;
+    }
+    h.total += duration;
+    h.max = h.max.max(duration);
+    h.buckets[bucket] += 1;
+}
+
+/// Runs the future and records its duration and whether it failed.
+pub async fn measure<F, T, E>(op: Operation, future: F) -> Result<T, E>
+where
+    F: Future<Output = Result<T, E>>,
+{
+    let start = Instant::now();
+    let result = future.await;
+    record(op, start.elapsed(), result.is_ok());
+    result
+}
+
+/// Returns the statistics of all the operations which ran at least once.
+pub fn snapshot() -> Vec<OpStats> {
+    OP_STATS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(op, h)| {
+            let mut cumulative = 0;
+            let buckets = h
+                .buckets
+                .iter()
+                .enumerate()
+                .map(|(i, n)| {
+                    cumulative += n;
+                    Bucket {
+                        le_ms: BUCKETS_MS.get(i).copied(),
+                        count: cumulative,
+                    }
+                })
+                .collect();
+            OpStats {
+                operation: *op,
+                count: h.count,
+                failures: h.failures,
+                total_ms: h.total.as_secs_f64() * 1000.0,
+                max_ms: h.max.as_secs_f64() * 1000.0,
+                buckets,
+            }
+        })
+        .collect()
+}
+
+/// Clears the statistics of all the operations.
+pub fn reset() {
+    OP_STATS.lock().unwrap().clear();
+}
+
+/// Arguments of the `mayastor_op_stats` method.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct OpStatsArgs {
+    /// Clear the statistics after reading them.
+    reset: bool,
+}
+
+/// Registers the JSON-RPC methods of the operation statistics.
+pub(crate) fn register_rpc_methods() {
+    // duration histograms and failure counters of the management
+    // operations, optionally cleared once read
+    jsonrpc_register::<OpStatsArgs, _, _, JsonRpcError>(
+        "mayastor_op_stats",
+        |args| {
+            async move {
+                let stats = snapshot();
+                if args.reset {
+                    reset();
+                }
+                Ok(stats)
+            }
+            .boxed_local()
+        },
+    );
+}
@@ -0,0 +1,357 @@
+//! Built-in performance job runner, in the spirit of SPDK's bdevperf.
+//!
+//! A perf job opens a local bdev (a nexus, a replica or any other bdev) and
+//! keeps `queue_depth` I/Os in flight against it from the current reactor for
+//! the configured runtime, so that the baseline performance of the data path
+//! can be measured without an external initiator.
+
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use futures::{future::join_all, FutureExt};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+
+use crate::{
+    core::{CoreError, UntypedBdevHandle},
+    jsonrpc::{jsonrpc_register, Code, RpcErrorCode},
+};
+
+/// Upper bound of the runtime of a perf job.
+const MAX_RUNTIME: Duration = Duration::from_secs(3600);
+
+/// Upper bound of the queue depth of a perf job.
+const MAX_QUEUE_DEPTH: u32 = 1024;
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)), context(suffix(false)))]
+pub enum Error {
+    #[snafu(display("Invalid perf job: {reason}"))]
+    InvalidJob { reason: String },
+    #[snafu(display("Failed to open bdev {bdev}: {source}"))]
+    OpenBdev { bdev: String, source: CoreError },
+    #[snafu(display("Failed to allocate I/O buffers for {bdev}"))]
+    AllocBuffer { bdev: String },
+}
+
+impl RpcErrorCode for Error {
+    fn rpc_error_code(&self) -> Code {
+        match self {
+            Self::InvalidJob {
+                ..
+            } => Code::InvalidParams,
+            Self::OpenBdev {
+                source:
+                    CoreError::BdevNotFound {
+                        ..
+                    },
+                ..
+            } => Code::NotFound,
+            _ => Code::InternalError,
+        }
+    }
+}
+
+/// Access pattern of a perf job.
+#[derive(
+    Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum PerfPattern {
+    /// Sequential reads.
+    Read,
+    /// Sequential writes.
+    Write,
+    /// Random reads.
+    #[default]
+    RandRead,
+    /// Random writes.
+    RandWrite,
+    /// Sequential mix of reads and writes.
+    Rw,
+    /// Random mix of reads and writes.
+    RandRw,
+}
+
+impl PerfPattern {
+    fn is_random(&self) -> bool {
+        matches!(self, Self::RandRead | Self::RandWrite | Self::RandRw)
+    }
+
+    /// Percentage of the I/Os which are reads.
+    fn read_pct(&self, rw_mix_read: u8) -> u8 {
+        match self {
+            Self::Read | Self::RandRead => 100,
+            Self::Write | Self::RandWrite => 0,
+            Self::Rw | Self::RandRw => rw_mix_read,
+        }
+    }
+}
+
+/// Arguments of a perf job.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PerfJob {
+    /// Name of the bdev to run the job against.
+    pub bdev: String,
+    /// Access pattern.
+    pub pattern: PerfPattern,
+    /// Percentage of reads for the mixed patterns.
+    pub rw_mix_read: u8,
+    /// Size of every I/O, in bytes.
+    pub io_size: u64,
+    /// Number of I/Os kept in flight.
+    pub queue_depth: u32,
+    /// Duration of the job, in seconds.
+    pub runtime_secs: u64,
+}
+
+impl Default for PerfJob {
+    fn default() -> Self {
+        Self {
+            bdev: String::new(),
+            pattern: PerfPattern::default(),
+            rw_mix_read: 50,
+            io_size: 4096,
+            queue_depth: 32,
+            runtime_secs: 10,
+        }
+    }
+}
+
+/// Statistics of a completed perf job.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PerfJobStats {
+    /// Name of the bdev the job ran against.
+    pub bdev: String,
+    /// Effective duration of the job, in microseconds.
+    pub runtime_us: u64,
+    pub num_read_ops: u64,
+    pub num_write_ops: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// Number of failed I/Os, which are not part of the other counters.
+    pub num_errors: u64,
+    pub iops: u64,
+    pub bandwidth_bytes_per_sec: u64,
+    /// Latencies of the successful I/Os, in microseconds.
+    pub lat_min_us: u64,
+    pub lat_avg_us: u64,
+    pub lat_max_us: u64,
+    pub lat_p50_us: u64,
+    pub lat_p99_us: u64,
+}
+
+/// Latency histogram with power of two buckets, in microseconds.
+#[derive(Debug, Default)]
+struct Histogram {
+    buckets: [u64; 64],
+    count: u64,
+    total: u64,
+    min: u64,
+    max: u64,
+}
+
+impl Histogram {
+    fn record(&mut self, lat_us: u64) {
+        self.buckets[(u64::BITS - lat_us.leading_zeros()) as usize] += 1;
+        self.min = if self.count == 0 {
+            lat_us
+        } else {
+            self.min.min(lat_us)
+        };
+        self.max = self.max.max(lat_us);
+        self.count += 1;
+        self.total += lat_us;
+    }
+
+    /// Upper bound of the bucket holding the given percentile.
+    fn percentile(&self, pct: u64) -> u64 {
+        let target = (self.count * pct).div_ceil(100);
+        let mut seen = 0;
+        for (i, n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= target && seen > 0 {
+                return ((1u64 << i) - 1).clamp(self.min, self.max);
+            }
+        }
+        self.max
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    num_read_ops: u64,
+    num_write_ops: u64,
+    num_errors: u64,
+    latency: Histogram,
+    /// Next offset of the sequential patterns, in I/O units.
+    next_slot: u64,
+}
+
+impl PerfJob {
+    fn validate(&self) -> Result<(), Error> {
+        let invalid = |reason: &str| {
+            Err(Error::InvalidJob {
+                reason: reason.to_string(),
+            })
+        };
+        if self.bdev.is_empty() {
+            return invalid("missing bdev name");
+        }
+        if self.io_size == 0 {
+            return invalid("the I/O size must not be zero");
+        }
+        if self.queue_depth == 0 || self.queue_depth > MAX_QUEUE_DEPTH {
+            return invalid(&format!(
+                "the queue depth must be between 1 and {MAX_QUEUE_DEPTH}"
+            ));
+        }
+        if self.runtime_secs == 0 || self.runtime_secs > MAX_RUNTIME.as_secs() {
+            return invalid(&format!(
+                "the runtime must be between 1 and {} seconds",
+                MAX_RUNTIME.as_secs()
+            ));
+        }
+        if self.rw_mix_read > 100 {
+            return invalid("the read percentage must not exceed 100");
+        }
+        Ok(())
+    }
+
+    /// Runs the job on the current reactor and returns its statistics.
+    pub async fn run(self) -> Result<PerfJobStats, Error> {
+        self.validate()?;
+
+        let read_pct = self.pattern.read_pct(self.rw_mix_read);
+        let handle = UntypedBdevHandle::open(&self.bdev, read_pct < 100, false)
+            .map_err(|source| Error::OpenBdev {
+                bdev: self.bdev.clone(),
+                source,
+            })?;
+        let bdev = handle.get_bdev();
+        if self.io_size % bdev.block_len() as u64 != 0 {
+            return Err(Error::InvalidJob {
+                reason: format!(
+                    "the I/O size must be a multiple of the block size {}",
+                    bdev.block_len()
+                ),
+            });
+        }
+        let slots = bdev.size_in_bytes() / self.io_size;
+        if slots == 0 {
+            return Err(Error::InvalidJob {
+                reason: format!(
+                    "the I/O size exceeds the size of bdev {}",
+                    self.bdev
+                ),
+            });
+        }
+
+        let mut bufs = Vec::with_capacity(self.queue_depth as usize);
+        for _ in 0 .. self.queue_depth {
+            bufs.push(handle.dma_malloc(self.io_size).map_err(|_| {
+                Error::AllocBuffer {
+                    bdev: self.bdev.clone(),
+                }
+            })?);
+        }
+
+        info!(
+            "Starting perf job on {}: {:?} io_size={} qd={} runtime={}s",
+            self.bdev,
+            self.pattern,
+            self.io_size,
+            self.queue_depth,
+            self.runtime_secs
+        );
+
+        let counters = Rc::new(RefCell::new(Counters::default()));
+        let start = Instant::now();
+        let deadline = start + Duration::from_secs(self.runtime_secs);
+        let random = self.pattern.is_random();
+
+        join_all(bufs.into_iter().map(|mut buf| {
+            let handle = &handle;
+            let counters = counters.clone();
+            async move {
+                while Instant::now() < deadline {
+                    let (slot, read) = {
+                        let mut rng = rand::thread_rng();
+                        let mut c = counters.borrow_mut();
+                        let slot = if random {
+                            rng.gen_range(0 .. slots)
+                        } else {
+                            let slot = c.next_slot;
+                            c.next_slot = (slot + 1) % slots;
+                            slot
+                        };
+                        (slot, rng.gen_range(0 .. 100) < read_pct)
+                    };
+
+                    let offset = slot * buf.len();
+                    let submitted = Instant::now();
+                    let result = if read {
+                        handle.read_at(offset, &mut buf).await
+                    } else {
+                        handle.write_at(offset, &buf).await
+                    };
+
+                    let mut c = counters.borrow_mut();
+                    match result {
+                        Ok(_) => {
+                            if read {
+                                c.num_read_ops += 1;
+                            } else {
+                                c.num_write_ops += 1;
+                            }
+                            c.latency
+                                .record(submitted.elapsed().as_micros() as u64);
+                        }
+                        Err(error) => {
+                            c.num_errors += 1;
+                            debug!("Perf job I/O failed: {error}");
+                        }
+                    }
+                }
+            }
+        }))
+        .await;
+
+        let runtime_us = start.elapsed().as_micros().max(1) as u64;
+        let c = counters.borrow();
+        let ops = c.num_read_ops + c.num_write_ops;
+        let bytes = ops * self.io_size;
+        let stats = PerfJobStats {
+            bdev: self.bdev.clone(),
+            runtime_us,
+            num_read_ops: c.num_read_ops,
+            num_write_ops: c.num_write_ops,
+            bytes_read: c.num_read_ops * self.io_size,
+            bytes_written: c.num_write_ops * self.io_size,
+            num_errors: c.num_errors,
+            iops: ops * 1_000_000 / runtime_us,
+            bandwidth_bytes_per_sec: bytes * 1_000_000 / runtime_us,
+            lat_min_us: c.latency.min,
+            lat_avg_us: c.latency.total.checked_div(ops).unwrap_or_default(),
+            lat_max_us: c.latency.max,
+            lat_p50_us: c.latency.percentile(50),
+            lat_p99_us: c.latency.percentile(99),
+        };
+
+        info!("Perf job on {} completed: {:?}", self.bdev, stats);
+        Ok(stats)
+    }
+}
+
+/// Registers the JSON-RPC methods of the perf jobs.
+pub(crate) fn register_rpc_methods() {
+    // run a bdevperf-like job against a local bdev and report its stats
+    jsonrpc_register::<PerfJob, _, _, Error>("mayastor_perf_run", |job| {
+        async move { job.run().await }.boxed_local()
+    });
+}
@@ -1,8 +1,18 @@
 use async_trait::async_trait;
 use pin_utils::core_reexport::fmt::Formatter;
-use std::{convert::TryFrom, fmt::Display, pin::Pin};
+use std::{convert::TryFrom, fmt::Display, pin::Pin, time::Duration};
 
-use crate::lvs::LvsError;
+use crate::{
+    lvs::LvsError,
+    subsys::{
+        HostDhChap,
+        IdentifyOverrides,
+        NvmfControllerInfo,
+        NvmfListener,
+        NvmfShareMode,
+        NvmfTransport,
+    },
+};
 
 /// Indicates what protocol the bdev is shared as.
 #[derive(Debug, Default, PartialOrd, Eq, PartialEq, Copy, Clone)]
@@ -91,6 +101,28 @@ pub struct NvmfShareProps {
     allowed_hosts: Vec<String>,
     /// Persistent-Power-Loss settings.
     ptpl: Option<PtplProps>,
+    /// Explicit NQN of the subsystem, derived from the name by default.
+    nqn: Option<String>,
+    /// Lease of the share, which must be renewed before it expires.
+    lease: Option<Duration>,
+    /// Transports to listen on at the address of the target, TCP when
+    /// neither transports nor listeners are given.
+    transports: Vec<NvmfTransport>,
+    /// Addresses to listen on in addition to those of the transports.
+    listeners: Vec<NvmfListener>,
+    /// Keys of the allowed hosts which must authenticate.
+    host_auth: Vec<HostDhChap>,
+    /// Serial number of the subsystem, derived from the UUID by default.
+    serial: Option<String>,
+    /// Model number of the subsystem, the Mayastor one by default.
+    model: Option<String>,
+    /// Overrides of the identify controller data of the subsystem.
+    identify: Option<IdentifyOverrides>,
+    /// Keep alive timeout in milliseconds, the default of the subsystem
+    /// kind when not set.
+    kato_ms: Option<u32>,
+    /// Whether the share is used by one host at a time or by several.
+    share_mode: NvmfShareMode,
 }
 impl NvmfShareProps {
     /// Returns a new `Self`.
@@ -141,6 +173,114 @@ impl NvmfShareProps {
     pub fn ptpl(&self) -> &Option<PtplProps> {
         &self.ptpl
     }
+    /// Modify the NQN of the subsystem.
+    #[must_use]
+    pub fn with_nqn(mut self, nqn: Option<String>) -> Self {
+        self.nqn = nqn;
+        self
+    }
+    /// Get the explicit NQN of the subsystem, if any.
+    pub fn nqn(&self) -> Option<&str> {
+        self.nqn.as_deref()
+    }
+    /// Modify the lease of the share.
+    #[must_use]
+    pub fn with_lease(mut self, lease: Option<Duration>) -> Self {
+        self.lease = lease;
+        self
+    }
+    /// Get the lease of the share, if any.
+    pub fn lease(&self) -> Option<Duration> {
+        self.lease
+    }
+    /// Modify the transports to listen on.
+    #[must_use]
+    pub fn with_transports(mut self, transports: Vec<NvmfTransport>) -> Self {
+        self.transports = transports;
+        self
+    }
+    /// Get the transports to listen on.
+    pub fn transports(&self) -> &[NvmfTransport] {
+        &self.transports
+    }
+    /// Modify the addresses to listen on.
+    #[must_use]
+    pub fn with_listeners(mut self, listeners: Vec<NvmfListener>) -> Self {
+        self.listeners = listeners;
+        self
+    }
+    /// Get the listeners of the share: the given addresses, followed by the
+    /// address of the target on each of the transports.
+    pub fn listeners(&self) -> Vec<NvmfListener> {
+        self.listeners
+            .iter()
+            .cloned()
+            .chain(self.transports.iter().copied().map(NvmfListener::from))
+            .collect()
+    }
+    /// Modify the keys of the allowed hosts which must authenticate.
+    #[must_use]
+    pub fn with_host_auth(mut self, host_auth: Vec<HostDhChap>) -> Self {
+        self.host_auth = host_auth;
+        self
+    }
+    /// Get the keys of the allowed hosts which must authenticate.
+    pub fn host_auth(&self) -> &[HostDhChap] {
+        &self.host_auth
+    }
+    /// Modify the serial number of the subsystem.
+    #[must_use]
+    pub fn with_serial(mut self, serial: Option<String>) -> Self {
+        self.serial = serial;
+        self
+    }
+    /// Get the explicit serial number of the subsystem, if any.
+    pub fn serial(&self) -> Option<&str> {
+        self.serial.as_deref()
+    }
+    /// Modify the model number of the subsystem.
+    #[must_use]
+    pub fn with_model(mut self, model: Option<String>) -> Self {
+        self.model = model;
+        self
+    }
+    /// Get the explicit model number of the subsystem, if any.
+    pub fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+    /// Modify the overrides of the identify controller data.
+    #[must_use]
+    pub fn with_identify(
+        mut self,
+        identify: Option<IdentifyOverrides>,
+    ) -> Self {
+        self.identify = identify;
+        self
+    }
+    /// Get the overrides of the identify controller data, if any.
+    pub fn identify(&self) -> Option<&IdentifyOverrides> {
+        self.identify.as_ref()
+    }
+    /// Modify the keep alive timeout of the subsystem, in milliseconds.
+    #[must_use]
+    pub fn with_kato(mut self, kato_ms: Option<u32>) -> Self {
+        self.kato_ms = kato_ms;
+        self
+    }
+    /// Get the keep alive timeout of the subsystem, if overridden.
+    pub fn kato(&self) -> Option<u32> {
+        self.kato_ms
+    }
+    /// Modify the share mode.
+    #[must_use]
+    pub fn with_share_mode(mut self, share_mode: NvmfShareMode) -> Self {
+        self.share_mode = share_mode;
+        self
+    }
+    /// Get the share mode.
+    pub fn share_mode(&self) -> NvmfShareMode {
+        self.share_mode
+    }
 }
 impl From<Option<NvmfShareProps>> for NvmfShareProps {
     fn from(opts: Option<NvmfShareProps>) -> Self {
@@ -152,7 +292,9 @@ impl From<Option<NvmfShareProps>> for NvmfShareProps {
 }
 impl From<NvmfShareProps> for UpdateProps {
     fn from(value: NvmfShareProps) -> Self {
-        UpdateProps::new().with_allowed_hosts(value.allowed_hosts)
+        UpdateProps::new()
+            .with_allowed_hosts(value.allowed_hosts)
+            .with_host_auth(value.host_auth)
     }
 }
 impl From<ShareProps> for NvmfShareProps {
@@ -168,6 +310,8 @@ impl From<ShareProps> for NvmfShareProps {
 pub struct UpdateProps {
     /// Hosts allowed to connect.
     allowed_hosts: Vec<String>,
+    /// Keys of the allowed hosts which must authenticate.
+    host_auth: Vec<HostDhChap>,
 }
 impl UpdateProps {
     /// Returns a new `Self`.
@@ -188,6 +332,16 @@ impl UpdateProps {
     pub fn allowed_hosts(&self) -> &Vec<String> {
         &self.allowed_hosts
     }
+    /// Modify the keys of the allowed hosts which must authenticate.
+    #[must_use]
+    pub fn with_host_auth(mut self, host_auth: Vec<HostDhChap>) -> Self {
+        self.host_auth = host_auth;
+        self
+    }
+    /// Keys of the allowed hosts which must authenticate.
+    pub fn host_auth(&self) -> &[HostDhChap] {
+        &self.host_auth
+    }
 }
 impl From<Option<UpdateProps>> for UpdateProps {
     fn from(opts: Option<UpdateProps>) -> Self {
@@ -199,7 +353,7 @@ impl From<Option<UpdateProps>> for UpdateProps {
 }
 impl From<ShareProps> for UpdateProps {
     fn from(opts: ShareProps) -> Self {
-        Self::new().with_allowed_hosts(opts.allowed_hosts())
+        Self::from(NvmfShareProps::from(opts))
     }
 }
 
@@ -231,6 +385,12 @@ pub trait Share: std::fmt::Debug {
     /// Get the currently allowed host nqn's.
     fn allowed_hosts(&self) -> Vec<String>;
 
+    /// Get the controllers of the hosts currently connected, none unless
+    /// shared over NVMf by this process.
+    fn nvmf_controllers(&self) -> Vec<NvmfControllerInfo> {
+        vec![]
+    }
+
     /// TODO
     fn bdev_uri(&self) -> Option<url::Url>;
     fn bdev_uri_str(&self) -> Option<String> {
@@ -0,0 +1,350 @@
+//! Opt-in telemetry of the usage of the node.
+//!
+//! When an endpoint is configured, a report of the aggregate usage of the
+//! node is sent to it periodically: the number of pools, replicas, volumes
+//! and shares, the capacity of the pools, the version of the engine and the
+//! features enabled. The report carries no name, UUID, address or NQN; the
+//! instance ID in it is drawn at random when the engine starts, so that the
+//! reports of an instance may be told apart from those of the others without
+//! identifying the node.
+//!
+//! The report is sent as JSON in an HTTPS POST request, signed with
+//! HMAC-SHA256 over its body with a key shared with the collector, the
+//! signature being given in the `X-Mayastor-Signature` header. The report
+//! that would be sent may be previewed at any time, whether the telemetry is
+//! enabled or not.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Mutex},
+    time::Duration,
+};
+
+use chrono::{SecondsFormat, Utc};
+use futures::FutureExt;
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use sha2::Sha256;
+use snafu::Snafu;
+use url::Url;
+use version_info::raw_version_string;
+
+use crate::{
+    bdev::nexus::{nexus_iter, ENABLE_NEXUS_RESET, ENABLE_PARTIAL_REBUILD},
+    core::{MayastorFeatures, Reactor},
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+    lvs::Lvs,
+    subsys::{NvmfSubsystem, SubType},
+};
+
+/// Header the signature of a report is sent in.
+pub const SIGNATURE_HEADER: &str = "X-Mayastor-Signature";
+
+/// Version of the layout of the reports.
+const REPORT_SCHEMA: u32 = 1;
+
+/// Timeout of the delivery of a report.
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// ID of this instance of the engine in the reports.
+static INSTANCE_ID: Lazy<String> =
+    Lazy::new(|| uuid::Uuid::new_v4().to_string());
+
+/// Telemetry configuration, none when the telemetry is disabled.
+static TELEMETRY: Lazy<Mutex<Option<TelemetryConfig>>> =
+    Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)), context(suffix(false)))]
+pub enum Error {
+    #[snafu(display("Invalid telemetry endpoint '{endpoint}': {reason}"))]
+    InvalidEndpoint { endpoint: String, reason: String },
+    #[snafu(display("Failed to read telemetry key {}: {source}", path.display()))]
+    ReadKey {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[snafu(display("Telemetry key {} is empty", path.display()))]
+    EmptyKey { path: PathBuf },
+    #[snafu(display("Failed to collect the telemetry report: {reason}"))]
+    Collect { reason: String },
+    #[snafu(display(
+        "Failed to deliver the telemetry report to {endpoint}: {reason}"
+    ))]
+    Deliver { endpoint: String, reason: String },
+}
+
+/// Where and how often the reports are sent.
+#[derive(Clone)]
+pub struct TelemetryConfig {
+    /// Endpoint of the collector.
+    endpoint: Url,
+    /// Key the reports are signed with.
+    key: Vec<u8>,
+    /// Interval between two reports.
+    interval: Duration,
+}
+
+impl std::fmt::Debug for TelemetryConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TelemetryConfig")
+            .field("endpoint", &self.endpoint.as_str())
+            .field("interval", &self.interval)
+            .finish()
+    }
+}
+
+impl TelemetryConfig {
+    /// Creates the configuration of the telemetry, reading the signing key
+    /// from a file. Only HTTPS endpoints are supported, the reports being
+    /// sent over TLS.
+    pub fn new(
+        endpoint: &str,
+        key_file: &Path,
+        interval: Duration,
+    ) -> Result<Self, Error> {
+        let invalid = |reason: String| Error::InvalidEndpoint {
+            endpoint: endpoint.to_string(),
+            reason,
+        };
+        let url = Url::parse(endpoint).map_err(|e| invalid(e.to_string()))?;
+        if url.scheme() != "https" {
+            return Err(invalid("only https endpoints are supported".into()));
+        }
+        if url.host().is_none() {
+            return Err(invalid("no host given".into()));
+        }
+
+        let key = std::fs::read(key_file).map_err(|source| Error::ReadKey {
+            path: key_file.to_path_buf(),
+            source,
+        })?;
+        // a trailing newline is not part of the key
+        let key = match key.iter().rposition(|b| !b.is_ascii_whitespace()) {
+            Some(end) => key[..= end].to_vec(),
+            None => Vec::new(),
+        };
+        if key.is_empty() {
+            return Err(Error::EmptyKey {
+                path: key_file.to_path_buf(),
+            });
+        }
+
+        Ok(Self {
+            endpoint: url,
+            key,
+            interval: interval.max(Duration::from_secs(1)),
+        })
+    }
+}
+
+/// Features of the engine enabled on the node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TelemetryFeatures {
+    pub ana_reporting: bool,
+    pub lvm: bool,
+    pub snapshot_rebuild: bool,
+    pub partial_rebuild: bool,
+    pub nexus_reset: bool,
+    pub rdma: bool,
+    pub fault_injection: bool,
+}
+
+impl TelemetryFeatures {
+    fn current() -> Self {
+        let features = MayastorFeatures::get();
+        Self {
+            ana_reporting: features.asymmetric_namespace_access,
+            lvm: features.logical_volume_manager,
+            snapshot_rebuild: features.snapshot_rebuild,
+            partial_rebuild: ENABLE_PARTIAL_REBUILD.load(Ordering::Relaxed),
+            nexus_reset: ENABLE_NEXUS_RESET.load(Ordering::Relaxed),
+            rdma: std::env::var("ENABLE_RDMA").as_deref() == Ok("true"),
+            fault_injection: cfg!(feature = "fault-injection"),
+        }
+    }
+}
+
+/// Anonymized usage report of the node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TelemetryReport {
+    /// Version of the layout of the report.
+    pub schema: u32,
+    /// Random ID of the instance of the engine.
+    pub instance_id: String,
+    /// Time of the report, in RFC 3339 format.
+    pub timestamp: String,
+    /// Version of the engine.
+    pub version: String,
+    pub features: TelemetryFeatures,
+    /// Number of pools.
+    pub pools: u64,
+    /// Total capacity of the pools, in bytes.
+    pub pool_capacity_bytes: u64,
+    /// Capacity of the pools in use, in bytes.
+    pub pool_used_bytes: u64,
+    /// Number of replicas on the pools.
+    pub replicas: u64,
+    /// Number of volumes with a target (nexus) on the node.
+    pub volumes: u64,
+    /// Total size of the volumes with a target on the node, in bytes.
+    pub volume_bytes: u64,
+    /// Number of NVMe-oF shares.
+    pub shares: u64,
+}
+
+impl TelemetryReport {
+    /// Collects the report. Must run on the primary reactor.
+    pub fn collect() -> Self {
+        let mut report = Self {
+            schema: REPORT_SCHEMA,
+            instance_id: INSTANCE_ID.clone(),
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            version: raw_version_string(),
+            features: TelemetryFeatures::current(),
+            pools: 0,
+            pool_capacity_bytes: 0,
+            pool_used_bytes: 0,
+            replicas: 0,
+            volumes: 0,
+            volume_bytes: 0,
+            shares: 0,
+        };
+
+        for lvs in Lvs::iter() {
+            report.pools += 1;
+            report.pool_capacity_bytes += lvs.capacity();
+            report.pool_used_bytes += lvs.used();
+            report.replicas += lvs.lvols().map_or(0, |l| l.count() as u64);
+        }
+        for nexus in nexus_iter() {
+            report.volumes += 1;
+            report.volume_bytes += nexus.req_size();
+        }
+        report.shares = NvmfSubsystem::first()
+            .map(|first| {
+                first
+                    .into_iter()
+                    .filter(|s| s.subtype() == SubType::Nvme)
+                    .count() as u64
+            })
+            .unwrap_or_default();
+        report
+    }
+
+    /// Body of the request the report is sent in.
+    fn body(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap_or_default()
+    }
+}
+
+/// The report that would be sent now, with its signature.
+#[derive(Debug, Clone, Serialize)]
+pub struct TelemetryPreview {
+    /// Whether the reports are sent.
+    pub enabled: bool,
+    /// Endpoint the reports are sent to, if enabled.
+    pub endpoint: Option<String>,
+    /// Interval between two reports, in seconds, if enabled.
+    pub interval_secs: Option<u64>,
+    pub report: TelemetryReport,
+    /// Signature of the body of the report, if enabled.
+    pub signature: Option<String>,
+}
+
+/// Previews the report that would be sent now, exactly as it would be sent.
+/// Must run on the primary reactor.
+pub fn telemetry_preview() -> TelemetryPreview {
+    let config = TELEMETRY.lock().unwrap().clone();
+    let report = TelemetryReport::collect();
+    TelemetryPreview {
+        enabled: config.is_some(),
+        endpoint: config.as_ref().map(|c| c.endpoint.to_string()),
+        interval_secs: config.as_ref().map(|c| c.interval.as_secs()),
+        signature: config.as_ref().map(|c| sign(&c.key, &report.body())),
+        report,
+    }
+}
+
+/// Signs a report body with HMAC-SHA256, as `sha256=<hex digest>`.
+pub fn sign(key: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key)
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Sends a report body to the endpoint of the collector, in an HTTPS POST
+/// request, failing unless the collector answers with a success. The body
+/// of the response is not read.
+async fn send(config: &TelemetryConfig, body: &[u8]) -> Result<(), Error> {
+    let failed = |reason: String| Error::Deliver {
+        endpoint: config.endpoint.to_string(),
+        reason,
+    };
+    // a redirect must not downgrade the request to plain HTTP either
+    let client = reqwest::Client::builder()
+        .https_only(true)
+        .timeout(SEND_TIMEOUT)
+        .build()
+        .map_err(|e| failed(e.to_string()))?;
+    let response = client
+        .post(config.endpoint.clone())
+        .header(CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, sign(&config.key, body))
+        .body(body.to_vec())
+        .send()
+        .await
+        .map_err(|e| failed(e.to_string()))?;
+
+    let status = response.status();
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(failed(format!("unexpected response status {status}")))
+    }
+}
+
+/// Collects a report on the primary reactor and sends it.
+async fn report_once(config: &TelemetryConfig) -> Result<(), Error> {
+    let report =
+        Reactor::spawn_at_primary(async { TelemetryReport::collect() })
+            .map_err(|e| Error::Collect {
+                reason: e.to_string(),
+            })?
+            .await
+            .map_err(|_| Error::Collect {
+                reason: "cancelled".into(),
+            })?;
+    send(config, &report.body()).await
+}
+
+/// Periodically sends the telemetry reports, until the engine stops.
+pub async fn telemetry_loop(config: TelemetryConfig) {
+    info!(
+        "Sending telemetry reports to {} every {:?}",
+        config.endpoint, config.interval
+    );
+    *TELEMETRY.lock().unwrap() = Some(config.clone());
+
+    let mut interval = tokio::time::interval(config.interval);
+    loop {
+        interval.tick().await;
+        match report_once(&config).await {
+            Ok(()) => debug!("Sent the telemetry report"),
+            Err(error) => warn!("{error}"),
+        }
+    }
+}
+
+/// Registers the JSON-RPC methods of the telemetry.
+pub(crate) fn register_rpc_methods() {
+    // the anonymized usage report that the telemetry would send now,
+    // with its signature, whether the telemetry is enabled or not
+    jsonrpc_register::<(), _, _, JsonRpcError>(
+        "mayastor_telemetry_preview",
+        |_| async move { Ok(telemetry_preview()) }.boxed_local(),
+    );
+}
@@ -0,0 +1,295 @@
+//! Volume dimensions of the nexus and replica I/O statistics.
+//!
+//! The control plane names the nexuses after their volume and records the
+//! volume of the replicas as their entity id. Together with the user labels
+//! of the volumes, this allows the statistics to be queried per application
+//! rather than per internal object name.
+//!
+//! When a ptpl directory is configured, the volume labels are kept in a file
+//! under it, so that they survive a restart.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use futures::{future::join_all, FutureExt};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use snafu::Snafu;
+
+use crate::{
+    bdev::nexus::nexus_iter,
+    core::{BdevStater, BlockDeviceIoStats, MayastorEnvironment},
+    jsonrpc::{jsonrpc_register, Code, JsonRpcError, RpcErrorCode},
+    replica_backend::{ListReplicaArgs, ReplicaFactory},
+    subsys::{load_state, save_state},
+};
+
+#[derive(Debug, Clone, Snafu)]
+#[snafu(context(suffix(false)))]
+pub enum Error {
+    #[snafu(display("Invalid volume UUID '{}'", uuid))]
+    InvalidVolumeUuid { uuid: String },
+    #[snafu(display("Volume {} has no labels", uuid))]
+    NotFound { uuid: String },
+    #[snafu(display(
+        "Failed to persist the labels of volume {}: {}",
+        uuid,
+        reason
+    ))]
+    Persist { uuid: String, reason: String },
+}
+
+impl RpcErrorCode for Error {
+    fn rpc_error_code(&self) -> Code {
+        match self {
+            Self::InvalidVolumeUuid {
+                ..
+            } => Code::InvalidParams,
+            Self::NotFound {
+                ..
+            } => Code::NotFound,
+            Self::Persist {
+                ..
+            } => Code::InternalError,
+        }
+    }
+}
+
+/// User labels of a volume.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VolumeLabels {
+    pub volume_uuid: String,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+}
+
+/// File of the volume labels, within the ptpl directory.
+const VOLUME_LABELS_FILE: &str = "volume-labels.json";
+
+type Labels = HashMap<String, BTreeMap<String, String>>;
+
+/// Labels of the volumes, by volume UUID, as loaded from their file.
+static VOLUME_LABELS: Lazy<Mutex<Labels>> = Lazy::new(|| {
+    let labels = labels_path()
+        .and_then(|path| load_state::<Labels>(&path))
+        .unwrap_or_default();
+    Mutex::new(labels)
+});
+
+/// Path of the volume labels file, if a ptpl directory is configured.
+fn labels_path() -> Option<PathBuf> {
+    MayastorEnvironment::global_or_default()
+        .ptpl_dir()
+        .map(|dir| Path::new(&dir).join(VOLUME_LABELS_FILE))
+}
+
+/// Applies a change to the volume labels, persisting them before the change
+/// takes effect so that a failure leaves the labels as they were.
+fn change_labels<T>(
+    uuid: &str,
+    f: impl FnOnce(&mut Labels) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let mut labels = VOLUME_LABELS.lock().unwrap();
+    let mut changed = labels.clone();
+    let result = f(&mut changed)?;
+    if let Some(path) = labels_path() {
+        save_state(&path, &changed).map_err(|error| Error::Persist {
+            uuid: uuid.to_string(),
+            reason: error.to_string(),
+        })?;
+    }
+    *labels = changed;
+    Ok(result)
+}
+
+/// Parses a volume UUID into its canonical form.
+fn volume_uuid(uuid: &str) -> Option<String> {
+    uuid::Uuid::parse_str(uuid).ok().map(|u| u.to_string())
+}
+
+impl VolumeLabels {
+    /// Sets the labels of a volume, replacing the previous ones.
+    pub fn set(self) -> Result<Self, Error> {
+        let uuid = volume_uuid(&self.volume_uuid).ok_or_else(|| {
+            Error::InvalidVolumeUuid {
+                uuid: self.volume_uuid.clone(),
+            }
+        })?;
+        change_labels(&uuid, |labels| {
+            labels.insert(uuid.clone(), self.labels.clone());
+            Ok(())
+        })?;
+        Ok(Self {
+            volume_uuid: uuid,
+            labels: self.labels,
+        })
+    }
+
+    /// Removes the labels of a volume.
+    pub fn remove(uuid: &str) -> Result<(), Error> {
+        let not_found = || Error::NotFound {
+            uuid: uuid.to_string(),
+        };
+        let uuid = volume_uuid(uuid).ok_or_else(not_found)?;
+        change_labels(&uuid, |labels| {
+            labels.remove(&uuid).map(|_| ()).ok_or_else(not_found)
+        })
+    }
+
+    /// Looks up the labels of a volume.
+    pub fn lookup(uuid: &str) -> Option<Self> {
+        let uuid = volume_uuid(uuid)?;
+        let labels = VOLUME_LABELS.lock().unwrap().get(&uuid).cloned()?;
+        Some(Self {
+            volume_uuid: uuid,
+            labels,
+        })
+    }
+
+    /// Returns the labels of all the volumes.
+    pub fn list() -> Vec<Self> {
+        VOLUME_LABELS
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(uuid, labels)| Self {
+                volume_uuid: uuid.clone(),
+                labels: labels.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Kind of the object the statistics are of.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumeObject {
+    Nexus,
+    Replica,
+}
+
+/// I/O statistics of a nexus or replica along with its volume dimensions.
+#[derive(Debug, Clone, Serialize)]
+pub struct VolumeIoStats {
+    pub kind: VolumeObject,
+    pub name: String,
+    pub uuid: String,
+    /// UUID of the volume the object belongs to, if known.
+    pub volume_uuid: Option<String>,
+    /// User labels of the volume.
+    pub labels: BTreeMap<String, String>,
+    pub stats: BlockDeviceIoStats,
+}
+
+impl VolumeIoStats {
+    fn new(
+        kind: VolumeObject,
+        name: String,
+        uuid: String,
+        volume: Option<&str>,
+        stats: BlockDeviceIoStats,
+    ) -> Self {
+        let volume_uuid = volume.and_then(volume_uuid);
+        let labels = volume_uuid
+            .as_ref()
+            .and_then(|uuid| VOLUME_LABELS.lock().unwrap().get(uuid).cloned())
+            .unwrap_or_default();
+        Self {
+            kind,
+            name,
+            uuid,
+            volume_uuid,
+            labels,
+            stats,
+        }
+    }
+}
+
+/// Returns the I/O statistics of the nexuses and replicas, only those of the
+/// given volume if any.
+pub async fn volume_io_stats(volume: Option<&str>) -> Vec<VolumeIoStats> {
+    let mut stats = vec![];
+
+    let nexuses = nexus_iter().map(|n| n.stats()).collect::<Vec<_>>();
+    for nexus in join_all(nexuses).await.into_iter().flatten() {
+        // the nexus is named after its volume
+        let name = nexus.name.clone();
+        stats.push(VolumeIoStats::new(
+            VolumeObject::Nexus,
+            nexus.name,
+            nexus.uuid,
+            Some(&name),
+            nexus.stats,
+        ));
+    }
+
+    let args = ListReplicaArgs::new_named(None);
+    for factory in ReplicaFactory::factories() {
+        let replicas =
+            factory.as_factory().list(&args).await.unwrap_or_default();
+        let replicas = replicas.iter().map(|r| r.stats()).collect::<Vec<_>>();
+        for replica in join_all(replicas).await.into_iter().flatten() {
+            stats.push(VolumeIoStats::new(
+                VolumeObject::Replica,
+                replica.stats.name,
+                replica.stats.uuid,
+                replica.entity_id.as_deref(),
+                replica.stats.stats,
+            ));
+        }
+    }
+
+    match volume.map(volume_uuid) {
+        Some(volume) => stats
+            .into_iter()
+            .filter(|s| volume.is_some() && s.volume_uuid == volume)
+            .collect(),
+        None => stats,
+    }
+}
+
+/// Arguments of the methods acting on a single volume.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct VolumeArgs {
+    /// UUID of the volume, all the volumes when not given.
+    volume_uuid: Option<String>,
+}
+
+/// Registers the JSON-RPC methods of the volume statistics and labels.
+pub(crate) fn register_rpc_methods() {
+    // I/O statistics of the nexuses and replicas along with the UUID and
+    // the user labels of their volume
+    jsonrpc_register::<VolumeArgs, _, _, JsonRpcError>(
+        "mayastor_volume_io_stats",
+        |args| {
+            async move {
+                let volume = args.volume_uuid.as_deref();
+                Ok(volume_io_stats(volume).await)
+            }
+            .boxed_local()
+        },
+    );
+
+    jsonrpc_register::<VolumeLabels, _, _, Error>(
+        "mayastor_volume_labels_set",
+        |labels| async move { labels.set() }.boxed_local(),
+    );
+
+    jsonrpc_register::<VolumeLabels, _, _, Error>(
+        "mayastor_volume_labels_remove",
+        |labels| {
+            async move { VolumeLabels::remove(&labels.volume_uuid) }
+                .boxed_local()
+        },
+    );
+
+    jsonrpc_register::<(), _, _, JsonRpcError>(
+        "mayastor_volume_labels_list",
+        |_| async move { Ok(VolumeLabels::list()) }.boxed_local(),
+    );
+}
@@ -11,7 +11,7 @@ use crate::{
     core::{LogicalVolume, MayastorEnvironment},
     eventing::{EventMetaGen, EventWithMeta},
     lvs::Lvol,
-    subsys::NvmfSubsystem,
+    subsys::{HostIoRates, NvmfSubsystem},
 };
 use spdk_rs::NvmfController;
 
@@ -42,6 +42,22 @@ impl HostTargetMeta for Lvol {
     }
 }
 
+/// Adds the addresses a host connected from to the metadata of a host event,
+/// the states being the address of its connected controllers and that of
+/// the new one.
+pub(crate) fn duplicate_host_event_meta(
+    mut meta: EventMeta,
+    first: &str,
+    second: &str,
+) -> EventMeta {
+    if let Some(source) = meta.source {
+        let event_source = source
+            .with_state_change_data(first.to_string(), second.to_string());
+        meta.source = Some(event_source);
+    }
+    meta
+}
+
 impl EventMetaGen for NvmfSubsystem {
     fn meta(&self) -> EventMeta {
         let nqn = self.get_nqn();
@@ -73,3 +89,27 @@ impl EventWithMeta for NvmfController {
         }
     }
 }
+
+/// Makes the event of the rates of the reads and writes of a host of a
+/// nexus, the states being its rates over the previous interval, if it
+/// submitted any, and over the last one.
+pub(crate) fn host_io_rates_event(
+    rates: &HostIoRates,
+    previous: Option<&HostIoRates>,
+) -> EventMessage {
+    let event_source =
+        EventSource::new(MayastorEnvironment::global_or_default().node_name)
+            .with_subsystem_data(&rates.nqn)
+            .with_host_initiator_data(&rates.hostnqn)
+            .with_state_change_data(
+                previous.map(|p| p.to_string()).unwrap_or_default(),
+                rates.to_string(),
+            );
+
+    EventMessage {
+        category: EventCategory::HostInitiator as i32,
+        action: EventAction::StateChange as i32,
+        target: rates.cntlid.to_string(),
+        metadata: Some(EventMeta::from_source(event_source)),
+    }
+}
@@ -9,7 +9,11 @@ use events_api::event::{
 use std::time::Duration;
 
 use crate::{
-    core::{MayastorEnvironment, Reactor},
+    core::{
+        clock::{ClockSample, ClockSkew},
+        MayastorEnvironment,
+        Reactor,
+    },
     eventing::{Event, EventWithMeta},
 };
 
@@ -21,6 +25,31 @@ pub(crate) fn io_engine_stop_event_meta(total_time: Duration) -> EventMeta {
     EventMeta::from_source(event_source)
 }
 
+/// Metadata of the state change of the I/O buffer pools.
+pub(crate) fn iobuf_state_event_meta(previous: &str, next: &str) -> EventMeta {
+    let event_source =
+        EventSource::new(MayastorEnvironment::global_or_default().node_name)
+            .with_state_change_data(previous.to_string(), next.to_string());
+
+    EventMeta::from_source(event_source)
+}
+
+/// Metadata of a skew of the wall clock, the states being the clocks before
+/// and after the skew.
+pub(crate) fn clock_skew_event_meta(skew: &ClockSkew) -> EventMeta {
+    let state = |s: &ClockSample| {
+        format!(
+            "{} (monotonic {}ns, boot {})",
+            s.wall_clock, s.monotonic_ns, s.boot_id
+        )
+    };
+    let event_source =
+        EventSource::new(MayastorEnvironment::global_or_default().node_name)
+            .with_state_change_data(state(&skew.before), state(&skew.after));
+
+    EventMeta::from_source(event_source)
+}
+
 // Io-engine event message from Mayastor env data.
 impl Event for MayastorEnvironment {
     fn event(&self, event_action: EventAction) -> EventMessage {
@@ -89,6 +89,17 @@ pub(crate) fn subsystem_pause_event_meta(
     EventMeta::from_source(event_source)
 }
 
+/// Flush violation event meta, with the healthy children a flush missed.
+pub(crate) fn flush_violation_event_meta(missed: &[String]) -> EventMeta {
+    let event_source =
+        EventSource::new(MayastorEnvironment::global_or_default().node_name)
+            .with_error_details(format!(
+                "flush completed without reaching healthy children: {}",
+                missed.join(", ")
+            ));
+    EventMeta::from_source(event_source)
+}
+
 impl<'n> Event for nexus::Nexus<'n> {
     fn event(&self, event_action: EventAction) -> EventMessage {
         let event_source = EventSource::new(
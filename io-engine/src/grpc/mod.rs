@@ -1,4 +1,3 @@
-use futures::channel::oneshot::Receiver;
 use nix::errno::Errno;
 pub use server::MayastorGrpcServer;
 use std::{
@@ -12,9 +11,9 @@ use tonic::{Request, Response, Status};
 use crate::{
     bdev_api::BdevError,
     core::{
+        admin_ops::{self, AdminOpReceiver},
         CoreError,
         MayastorFeatures,
-        Reactor,
         ResourceLockGuard,
         ResourceSubsystem,
         VerboseError,
@@ -30,6 +29,9 @@ impl From<BdevError> for tonic::Status {
             BdevError::UriSchemeUnsupported {
                 ..
             } => Status::invalid_argument(e.to_string()),
+            BdevError::UriSchemeExists {
+                ..
+            } => Status::already_exists(e.to_string()),
             BdevError::InvalidUri {
                 ..
             } => Status::invalid_argument(e.to_string()),
@@ -67,7 +69,15 @@ impl From<BdevError> for tonic::Status {
 
 impl From<CoreError> for tonic::Status {
     fn from(e: CoreError) -> Self {
-        Status::internal(e.to_string())
+        match e {
+            CoreError::OperationInProgress {
+                ..
+            } => Status::aborted(e.to_string()),
+            CoreError::AdminQueueFull {
+                ..
+            } => Status::resource_exhausted(e.to_string()),
+            e => Status::internal(e.to_string()),
+        }
     }
 }
 
@@ -100,7 +110,7 @@ pub const DEFAULT_GRPC_TIMEOUT_SEC: u64 = 15;
 
 /// Structure that holds sensitive information about the current gRPC
 /// method being executed.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct GrpcClientContext {
     /// Method arguments.
     pub args: String,
@@ -157,41 +167,54 @@ macro_rules! spdk_submit {
 
 pub type GrpcResult<T> = std::result::Result<Response<T>, Status>;
 
-/// Submit rpc code to the primary reactor.
+/// Maps the failure to submit rpc code to the primary reactor to a status.
+fn submit_status(e: CoreError) -> Status {
+    match e {
+        CoreError::AdminQueueFull {
+            ..
+        } => Status::from(e),
+        _ => Status::resource_exhausted("ENOMEM"),
+    }
+}
+
+/// Submit rpc code to the primary reactor, through the bounded executor of
+/// the control-plane operations.
+#[track_caller]
 pub fn rpc_submit<F, R, E>(
     future: F,
-) -> Result<Receiver<Result<R, E>>, tonic::Status>
+) -> Result<AdminOpReceiver<Result<R, E>>, tonic::Status>
 where
     E: Send + Debug + Display + 'static,
     F: Future<Output = Result<R, E>> + 'static,
     R: Send + Debug + 'static,
 {
-    Reactor::spawn_at_primary(future)
-        .map_err(|_| Status::resource_exhausted("ENOMEM"))
+    admin_ops::submit(None, future).map_err(submit_status)
 }
 /// Submit rpc code to the primary reactor.
 /// Similar to `rpc_submit` but with a more generic response abstraction.
-pub fn rpc_submit_ext<F, R>(future: F) -> Result<Receiver<R>, tonic::Status>
+#[track_caller]
+pub fn rpc_submit_ext<F, R>(
+    future: F,
+) -> Result<AdminOpReceiver<R>, tonic::Status>
 where
     F: Future<Output = R> + 'static,
     R: Send + Debug + 'static,
 {
-    Reactor::spawn_at_primary(future)
-        .map_err(|_| Status::resource_exhausted("ENOMEM"))
+    admin_ops::submit(None, future).map_err(submit_status)
 }
 
 /// Submit rpc code to the primary reactor.
 /// Similar to `rpc_submit_ext` but specifying a result output with tonic
 /// Status as error.
+#[track_caller]
 pub fn rpc_submit_ext2<F, R>(
     future: F,
-) -> Result<Receiver<Result<R, tonic::Status>>, tonic::Status>
+) -> Result<AdminOpReceiver<Result<R, tonic::Status>>, tonic::Status>
 where
     F: Future<Output = Result<R, tonic::Status>> + 'static,
     R: Send + Debug + 'static,
 {
-    Reactor::spawn_at_primary(future)
-        .map_err(|_| Status::resource_exhausted("ENOMEM"))
+    admin_ops::submit(None, future).map_err(submit_status)
 }
 
 /// Manage locks across multiple grpc services.
@@ -246,7 +246,9 @@ impl From<LvsError> for tonic::Status {
                     status
                 }
                 Errno::ENOMEDIUM => Status::failed_precondition(e.to_string()),
-                Errno::EMEDIUMTYPE => Status::aborted(e.to_string()),
+                Errno::EMEDIUMTYPE | Errno::EBUSY => {
+                    Status::aborted(e.to_string())
+                }
                 _ => Status::internal(e.to_string()),
             },
             LvsError::RepResize {
@@ -305,6 +307,9 @@ impl From<LvsError> for tonic::Status {
             LvsError::ResourceLockFailed {
                 ..
             } => Status::aborted(e.to_string()),
+            LvsError::Takeover {
+                ..
+            } => Status::failed_precondition(e.to_string()),
             _ => Status::internal(e.verbose()),
         }
     }
@@ -1129,6 +1134,8 @@ impl mayastor_server::Mayastor for MayastorSvc {
                         },
                         resv_type,
                         preempt_policy,
+                        ana_reporting: None,
+                        read_policy: None,
                     },
                     &args.children,
                     nexus_info_key,
@@ -32,6 +32,7 @@ fn map_fault_reason(r: FaultReason) -> ChildStateReason {
         FaultReason::CantOpen => CannotOpen,
         FaultReason::NoSpace => NoSpace,
         FaultReason::TimedOut => TimedOut,
+        FaultReason::IoTimeout => TimedOut,
         FaultReason::IoError => IoFailure,
         FaultReason::Offline => ByClient,
         FaultReason::RebuildFailed => RebuildFailed,
@@ -57,6 +58,7 @@ fn map_child_state(child: &NexusChild) -> (ChildState, ChildStateReason) {
         ChildStateClient::Faulted(r) => (
             match r {
                 FaultReason::NoSpace => Degraded,
+                FaultReason::IoTimeout => Degraded,
                 FaultReason::Offline => Degraded,
                 _ => Faulted,
             },
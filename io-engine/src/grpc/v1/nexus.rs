@@ -11,6 +11,7 @@ use crate::{
     },
     core::{
         lock::{ProtectedSubsystems, ResourceLockManager},
+        op_stats::{measure, Operation},
         Protocol,
         Share,
     },
@@ -68,6 +69,23 @@ impl NexusService {
         global_operation: bool,
         f: F,
     ) -> Result<T, Status>
+    where
+        T: Send + 'static,
+        F: core::future::Future<Output = Result<T, Status>> + Send + 'static,
+    {
+        self.serialized_resources(ctx, vec![nexus_uuid], global_operation, f)
+            .await
+    }
+
+    /// Like `serialized`, but locks several nexus resources, so that
+    /// operations on disjoint resources can proceed concurrently.
+    async fn serialized_resources<T, F>(
+        &self,
+        ctx: GrpcClientContext,
+        resources: Vec<String>,
+        global_operation: bool,
+        f: F,
+    ) -> Result<T, Status>
     where
         T: Send + 'static,
         F: core::future::Future<Output = Result<T, Status>> + Send + 'static,
@@ -92,10 +110,10 @@ impl NexusService {
                 None
             };
 
-            // Grab per-object lock before executing the future.
-            let _resource_guard = match lock_manager
+            // Grab per-object locks before executing the future.
+            let _resource_guards = match lock_manager
                 .get_subsystem(ProtectedSubsystems::NEXUS)
-                .lock_resource(nexus_uuid, Some(ctx.timeout), false)
+                .lock_resources(resources, Some(ctx.timeout), false)
                 .await {
                     Some(g) => g,
                     None => return Err(Status::deadline_exceeded(
@@ -143,6 +161,7 @@ fn map_fault_reason(r: FaultReason) -> ChildStateReason {
         FaultReason::CantOpen => CannotOpen,
         FaultReason::NoSpace => NoSpace,
         FaultReason::TimedOut => TimedOut,
+        FaultReason::IoTimeout => TimedOut,
         FaultReason::IoError => IoFailure,
         FaultReason::Offline => ByClient,
         FaultReason::RebuildFailed => RebuildFailed,
@@ -163,6 +182,7 @@ fn map_child_state(child: &NexusChild) -> (ChildState, ChildStateReason) {
         ChildStateClient::Faulted(r) => (
             match r {
                 FaultReason::NoSpace => Degraded,
+                FaultReason::IoTimeout => Degraded,
                 FaultReason::Offline => Degraded,
                 _ => Faulted,
             },
@@ -388,6 +408,65 @@ async fn nexus_add_child(
     Ok(n.into_grpc().await)
 }
 
+/// Nexus resources locked while creating or destroying a nexus: its uuid
+/// and name, and its children, regardless of the parameters of their URI.
+fn nexus_resources<'a>(
+    uuid: &str,
+    name: &str,
+    children: impl Iterator<Item = &'a str>,
+) -> Vec<String> {
+    let children =
+        children.map(|uri| uri.split('?').next().unwrap_or(uri).to_string());
+    [uuid.to_string(), name.to_string()]
+        .into_iter()
+        .chain(children)
+        .collect()
+}
+
+/// Nexus resources locked while destroying a nexus, the same as those locked
+/// while creating it, so that the destruction and the creation of a
+/// replacement nexus of the same name or children are serialized.
+async fn destroy_resources(uuid: &str) -> Result<Vec<String>, Status> {
+    let uuid = uuid.to_string();
+    let rx = rpc_submit::<_, _, nexus::Error>(async move {
+        Ok(match nexus_lookup(&uuid) {
+            Ok(n) => nexus_resources(
+                &uuid,
+                n.nexus_name(),
+                n.children_iter().map(|c| c.uri()),
+            ),
+            Err(_) => vec![uuid],
+        })
+    })?;
+    rx.await
+        .map_err(|_| Status::cancelled("cancelled"))?
+        .map_err(Status::from)
+}
+
+/// Destroys a nexus once the given resources are locked, unless the nexus
+/// has resources other than those, which are then returned to be locked.
+/// The children of the nexus no longer change once its uuid is locked.
+async fn destroy_locked(
+    args: DestroyNexusRequest,
+    locked: Vec<String>,
+) -> Result<Option<Vec<String>>, Status> {
+    let current = destroy_resources(&args.uuid).await?;
+    if current.iter().any(|r| !locked.contains(r)) {
+        return Ok(Some(current));
+    }
+
+    let rx = rpc_submit::<_, _, nexus::Error>(async move {
+        trace!("{:?}", args);
+        nexus_destroy(&args.uuid).await?;
+        Ok(())
+    })?;
+
+    rx.await
+        .map_err(|_| Status::cancelled("cancelled"))?
+        .map_err(Status::from)?;
+    Ok(None)
+}
+
 #[tonic::async_trait]
 impl NexusRpc for NexusService {
     #[named]
@@ -398,12 +477,20 @@ impl NexusRpc for NexusService {
         let ctx = GrpcClientContext::new(&request, function_name!());
         let args = request.into_inner();
 
-        self.serialized(ctx, args.uuid.clone(), true, async move {
+        // Creations of nexuses with distinct names, uuids and children do
+        // not depend on each other, so only those resources are locked
+        // rather than serializing all the creations behind the global lock.
+        let resources = nexus_resources(
+            &args.uuid,
+            &args.name,
+            args.children.iter().map(String::as_str),
+        );
+        self.serialized_resources(ctx, resources, false, async move {
             trace!("{:?}", args);
             let resv_type = NvmeReservationConv(args.resv_type).try_into()?;
             let preempt_policy =
                 NvmePreemptionConv(args.preempt_policy).try_into()?;
-            let rx = rpc_submit::<_, _, nexus::Error>(async move {
+            let create = async move {
                 // check for nexus exists, uuid & name
                 if let Some(_n) = nexus::nexus_lookup(&args.name) {
                     return Err(nexus::Error::NameExists {
@@ -439,6 +526,8 @@ impl NexusRpc for NexusService {
                         },
                         resv_type,
                         preempt_policy,
+                        ana_reporting: None,
+                        read_policy: None,
                     },
                     &args.children,
                     nexus_info_key,
@@ -448,7 +537,11 @@ impl NexusRpc for NexusService {
                 nexus.event(EventAction::Create).generate();
                 info!("Created nexus {}/{}", &args.name, &args.uuid);
                 Ok(nexus.into_grpc().await)
-            })?;
+            };
+            let rx = rpc_submit::<_, _, nexus::Error>(measure(
+                Operation::NexusCreate,
+                create,
+            ))?;
             rx.await
                 .map_err(|_| Status::cancelled("cancelled"))?
                 .map_err(Status::from)
@@ -469,19 +562,20 @@ impl NexusRpc for NexusService {
         let ctx = GrpcClientContext::new(&request, function_name!());
         let args = request.into_inner();
 
-        self.serialized(ctx, args.uuid.clone(), true, async move {
-            let rx = rpc_submit::<_, _, nexus::Error>(async move {
-                trace!("{:?}", args);
-                nexus_destroy(&args.uuid).await?;
-                Ok(())
-            })?;
-
-            rx.await
-                .map_err(|_| Status::cancelled("cancelled"))?
-                .map_err(Status::from)
-                .map(Response::new)
-        })
-        .await
+        // As for the creation, only the resources of the nexus are locked.
+        // Its children are looked up before they are locked, so the locks
+        // are taken anew should a child have been added in between.
+        let mut resources = destroy_resources(&args.uuid).await?;
+        loop {
+            let destroy = destroy_locked(args.clone(), resources.clone());
+            match self
+                .serialized_resources(ctx.clone(), resources, false, destroy)
+                .await?
+            {
+                None => return Ok(Response::new(())),
+                Some(current) => resources = current,
+            }
+        }
     }
 
     #[named]
@@ -689,7 +783,7 @@ impl NexusRpc for NexusService {
         let args = request.into_inner();
 
         self.serialized(ctx, args.uuid.clone(), false, async move {
-            let rx = rpc_submit::<_, _, nexus::Error>(async move {
+            let publish = async move {
                 trace!("{:?}", args);
                 debug!("Publishing nexus {} ...", args.uuid);
 
@@ -733,7 +827,11 @@ impl NexusRpc for NexusService {
                 Ok(PublishNexusResponse {
                     nexus: Some(nexus),
                 })
-            })?;
+            };
+            let rx = rpc_submit::<_, _, nexus::Error>(measure(
+                Operation::NexusPublish,
+                publish,
+            ))?;
             rx.await
                 .map_err(|_| Status::cancelled("cancelled"))?
                 .map_err(Status::from)
@@ -1,6 +1,7 @@
 pub use crate::pool_backend::FindPoolArgs as PoolIdProbe;
 use crate::{
     core::{
+        op_stats::{measure, Operation},
         NvmfShareProps,
         ProtectedSubsystems,
         Protocol,
@@ -183,6 +184,7 @@ impl TryFrom<CreatePoolRequest> for PoolArgs {
             disks: args.disks,
             uuid: args.uuid,
             cluster_size: args.cluster_size,
+            reserved_pct: None,
             backend: backend.into(),
         })
     }
@@ -256,6 +258,7 @@ impl TryFrom<ImportPoolRequest> for PoolArgs {
             disks: args.disks,
             uuid: args.uuid,
             cluster_size: None,
+            reserved_pct: None,
             backend: backend.into(),
         })
     }
@@ -490,12 +493,15 @@ impl PoolRpc for PoolService {
                 crate::spdk_submit!(async move {
                     info!("{:?}", request.get_ref());
 
-                    let factory = GrpcPoolFactory::new(PoolBackend::try_from(
-                        request.get_ref().pooltype,
-                    )?)?;
-                    factory
-                        .create(PoolArgs::try_from(request.into_inner())?)
-                        .await
+                    measure(Operation::PoolCreate, async move {
+                        let factory = GrpcPoolFactory::new(
+                            PoolBackend::try_from(request.get_ref().pooltype)?,
+                        )?;
+                        factory
+                            .create(PoolArgs::try_from(request.into_inner())?)
+                            .await
+                    })
+                    .await
                 })
             },
         )
@@ -513,9 +519,13 @@ impl PoolRpc for PoolService {
                 crate::spdk_submit!(async move {
                     info!("{:?}", request.get_ref());
 
-                    let pool =
-                        GrpcPoolFactory::finder(request.into_inner()).await?;
-                    pool.destroy().await.map_err(Into::into)
+                    measure(Operation::PoolDestroy, async move {
+                        let pool =
+                            GrpcPoolFactory::finder(request.into_inner())
+                                .await?;
+                        pool.destroy().await.map_err(Into::into)
+                    })
+                    .await
                 })
             },
         )
@@ -1,6 +1,7 @@
 use crate::{
     core::{
         logical_volume::LvolSpaceUsage,
+        op_stats::{measure, Operation},
         wiper::{WipeMethod, Wiper},
         Bdev,
         NvmfShareProps,
@@ -335,7 +336,14 @@ impl ReplicaGrpc {
         Ok(())
     }
     async fn resize(&mut self, resize: u64) -> Result<(), Status> {
-        // todo: shouldn't these also take the pool lock?
+        // the pool lock keeps its free capacity from changing between the
+        // checks of the resize and the resize itself
+        let pool_name = self.replica.pool_name();
+        let pool_subsystem = ResourceLockManager::get_instance()
+            .get_subsystem(ProtectedSubsystems::POOL);
+        let _lock_guard =
+            acquire_subsystem_lock(pool_subsystem, Some(&pool_name)).await?;
+
         self.replica.resize(resize).await?;
         Ok(())
     }
@@ -391,11 +399,14 @@ impl ReplicaRpc for ReplicaService {
 
                     let args = request.into_inner();
 
-                    let pool = GrpcReplicaFactory::pool_finder(
-                        FindPoolArgs::uuid_or_name(&args.pooluuid),
-                    )
-                    .await?;
-                    pool.create_replica(args).await
+                    measure(Operation::ReplicaCreate, async move {
+                        let pool = GrpcReplicaFactory::pool_finder(
+                            FindPoolArgs::uuid_or_name(&args.pooluuid),
+                        )
+                        .await?;
+                        pool.create_replica(args).await
+                    })
+                    .await
                 })
             },
         )
@@ -494,11 +505,15 @@ impl ReplicaRpc for ReplicaService {
                 crate::spdk_submit!(async move {
                     info!("{:?}", request.get_ref());
 
-                    let probe = FindReplicaArgs::new(&request.get_ref().uuid);
-                    let mut replica =
-                        GrpcReplicaFactory::finder(&probe).await?;
-                    replica.share(request.into_inner()).await?;
-                    Ok(replica.into())
+                    measure(Operation::ReplicaShare, async move {
+                        let probe =
+                            FindReplicaArgs::new(&request.get_ref().uuid);
+                        let mut replica =
+                            GrpcReplicaFactory::finder(&probe).await?;
+                        replica.share(request.into_inner()).await?;
+                        Ok(replica.into())
+                    })
+                    .await
                 })
             },
         )
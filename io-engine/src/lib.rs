@@ -51,4 +51,7 @@ pub extern "C" fn cps_init() {
     subsys::register_subsystem();
     bdev::nexus::register_module(true);
     bdev::null_ng::register();
+    bdev::nvmx::register_rpc_methods();
+    core::register_rpc_methods();
+    lvs::register_rpc_methods();
 }
@@ -1,7 +1,7 @@
 use ansi_term::{Colour, Style};
 use once_cell::sync::OnceCell;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashSet},
     ffi::CStr,
     fmt,
     fmt::{Debug, Write},
@@ -41,6 +41,40 @@ fn get_hostname() -> String {
 
 static HOSTNAME_PREFIX: OnceCell<String> = OnceCell::new();
 
+/// Node name added to the json log lines.
+static JSON_NODE: OnceCell<String> = OnceCell::new();
+
+/// Names of the event fields whose values are redacted from the json logs.
+static JSON_REDACTED: OnceCell<HashSet<String>> = OnceCell::new();
+
+/// Sets the node name added to the json log lines and the names of the event
+/// fields, e.g. `hostnqn`, whose values are redacted from them.
+/// Must be called before the logger is initialised.
+pub fn set_json_context(node: Option<&str>, redacted: &[String]) {
+    if let Some(node) = node {
+        JSON_NODE.set(node.to_string()).ok();
+    }
+    JSON_REDACTED
+        .set(redacted.iter().map(|f| f.trim().to_string()).collect())
+        .ok();
+}
+
+/// Replaces a value with a digest of it, so that log lines about the same
+/// host can still be correlated without revealing its identifier.
+fn redact(value: &serde_json::Value) -> serde_json::Value {
+    use sha2::{Digest, Sha256};
+
+    let value = match value {
+        serde_json::Value::String(s) => s.clone(),
+        v => v.to_string(),
+    };
+    let digest = Sha256::digest(value.as_bytes());
+    serde_json::Value::String(format!(
+        "redacted:{}",
+        hex::encode(&digest[.. 6])
+    ))
+}
+
 use spdk_rs::libspdk::{spdk_log_get_print_level, spdk_log_level};
 
 fn from_spdk_level(level: spdk_log_level) -> log::Level {
@@ -352,13 +386,50 @@ fn ellipsis(s: &str, w: usize) -> String {
     }
 }
 
+/// Redacts the values of the redacted fields from the formatted span fields,
+/// which are only available as text, e.g. `nexus_create{hostnqn=...}`.
+fn redact_spans(spans: String, redacted: &HashSet<String>) -> String {
+    let mut out = String::with_capacity(spans.len());
+    let mut rest = spans.as_str();
+    while let Some(pos) = rest.find('=') {
+        let (head, tail) = rest.split_at(pos);
+        let name = head
+            .rsplit(|c: char| c == '{' || c.is_whitespace())
+            .next()
+            .unwrap_or_default();
+        out.push_str(head);
+        out.push('=');
+        let tail = &tail[1 ..];
+        let end = tail
+            .find(|c: char| c == '}' || c.is_whitespace())
+            .unwrap_or(tail.len());
+        if redacted.contains(name) {
+            let value = serde_json::Value::String(tail[.. end].to_string());
+            if let serde_json::Value::String(r) = redact(&value) {
+                out.push_str(&r);
+            }
+        } else {
+            out.push_str(&tail[.. end]);
+        }
+        rest = &tail[end ..];
+    }
+    out.push_str(rest);
+    out
+}
+
 /// Input struct for json serializer.
 #[derive(Serialize)]
 struct JsonLogger {
     hostname: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    node: Option<String>,
     level: String,
     timestamp: String,
-    fields: HashMap<String, String>,
+    target: String,
+    location: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    spans: String,
+    fields: BTreeMap<String, serde_json::Value>,
 }
 
 /// Visitor struct for fetching Event fields.
@@ -380,6 +451,43 @@ impl<'a> StringVisitor<'a> {
     }
 }
 
+/// Visitor collecting the Event fields as json values.
+struct JsonVisitor<'a> {
+    fields: &'a mut BTreeMap<String, serde_json::Value>,
+}
+
+impl<'a> JsonVisitor<'a> {
+    fn insert<T: Into<serde_json::Value>>(&mut self, field: &Field, value: T) {
+        self.fields.insert(field.name().to_string(), value.into());
+    }
+}
+
+impl<'a> Visit for JsonVisitor<'a> {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, value);
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, value);
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, value);
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, value);
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, value);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, format!("{value:?}"));
+    }
+}
+
 impl LogFormat {
     /// Formats an event in default mode.
     fn default_style<S, N>(
@@ -464,7 +572,7 @@ impl LogFormat {
     /// Formats an event in json mode to stdout.
     fn json_style<S, N>(
         &self,
-        _context: &FmtContext<'_, S, N>,
+        context: &FmtContext<'_, S, N>,
         mut writer: Writer<'_>,
         event: &Event<'_>,
     ) -> std::fmt::Result
@@ -477,24 +585,33 @@ impl LogFormat {
         let fmt = FormatLevel::new(meta.level(), self.ansi);
         let now = chrono::Local::now();
 
-        let mut output_string = String::new();
-        let mut visitor = StringVisitor::new(&mut output_string);
-        event.record(&mut visitor);
-        let output = visitor.string;
-        let key = "message".to_string();
-        let output = output.trim_end_matches("; ");
-        let val = match output.strip_prefix("message = ") {
-            Some(stripped) => stripped,
-            None => output,
-        };
-        let mut msg = HashMap::new();
-        msg.insert(key, val.to_string());
+        let mut fields = BTreeMap::new();
+        event.record(&mut JsonVisitor {
+            fields: &mut fields,
+        });
+        let mut spans = CustomContext::new(context, event.parent(), false)
+            .to_string()
+            .trim_start_matches(':')
+            .to_string();
+
+        if let Some(redacted) = JSON_REDACTED.get().filter(|r| !r.is_empty()) {
+            for (name, value) in fields.iter_mut() {
+                if redacted.contains(name) {
+                    *value = redact(value);
+                }
+            }
+            spans = redact_spans(spans, redacted);
+        }
 
         let json_log = JsonLogger {
-            hostname: self.hostname().to_string(),
+            hostname: self.hostname().trim_end_matches(" :: ").to_string(),
+            node: JSON_NODE.get().cloned(),
             level: fmt.long(),
             timestamp: now.to_rfc2822(),
-            fields: msg,
+            target: meta.target().to_string(),
+            location: Location::new(meta).to_string(),
+            spans,
+            fields,
         };
         let json_str = serde_json::to_string(&json_log).unwrap_or_default();
         fmt.fmt_line(writer.by_ref(), &json_str)?;
@@ -625,6 +625,13 @@ impl LvolSnapshotOps for Lvol {
         &self,
         snap_param: SnapshotParams,
     ) -> Result<Lvol, LvsError> {
+        if self.lvs().reserve_exhausted() {
+            return Err(LvsError::SnapshotCreate {
+                source: BsError::NoSpace {},
+                msg: snap_param.name().unwrap_or_default(),
+            });
+        }
+
         extern "C" fn snapshot_create_done_cb(
             arg: *mut c_void,
             lvol_ptr: *mut spdk_lvol,
@@ -757,6 +764,13 @@ impl LvolSnapshotOps for Lvol {
         &self,
         clone_param: CloneParams,
     ) -> Result<Self::Lvol, Self::Error> {
+        if self.lvs().reserve_exhausted() {
+            return Err(LvsError::SnapshotCloneCreate {
+                source: BsError::NoSpace {},
+                msg: clone_param.clone_name().unwrap_or_default(),
+            });
+        }
+
         extern "C" fn clone_done_cb(
             arg: *mut c_void,
             lvol_ptr: *mut spdk_lvol,
@@ -0,0 +1,293 @@
+//! Admission control of the I/O submitted to saturated pools.
+//!
+//! The average latency of the I/O completed by the base device of every pool
+//! is sampled periodically. Past the threshold, the I/O rate of the device
+//! is capped below its observed rate through the bdev QoS, so that the excess
+//! I/O waits in the bdev layer, delaying its completion, instead of piling up
+//! in the device queues. The cap is relaxed step by step once the latency
+//! recovers and removed once it no longer constrains the device.
+//! An IOPS limit set on the device by the user is kept: the cap never
+//! exceeds it and it is restored once the cap is removed.
+//!
+//! Signalling the hosts through the command retry delay is not possible as
+//! the I/O of the replicas does not go through the io-engine.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use futures::{channel::oneshot, FutureExt};
+use nix::errno::Errno;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use spdk_rs::{
+    ffihelper::{cb_arg, done_errno_cb, ErrnoResult},
+    libspdk::{
+        spdk_bdev_get_qos_rate_limits,
+        spdk_bdev_set_qos_rate_limits,
+        SPDK_BDEV_QOS_NUM_RATE_LIMIT_TYPES,
+        SPDK_BDEV_QOS_RW_IOPS_RATE_LIMIT,
+    },
+};
+
+use super::Lvs;
+use crate::{
+    core::{BlockDeviceIoStats, Reactor, UntypedBdev},
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+};
+
+/// Leaves a QoS rate limit unchanged.
+const QOS_LIMIT_NOT_DEFINED: u64 = u64::MAX;
+/// Smallest IOPS limit supported by the bdev QoS, which also rounds the
+/// limits to its multiples.
+const QOS_MIN_IOPS: u64 = 1000;
+/// Share of the observed IOPS a saturated device is capped to, in percent.
+const CAP_PCT: u64 = 80;
+/// Growth of the cap at every sample once the latency recovered, in percent.
+const RELAX_PCT: u64 = 125;
+
+/// Admission control state of a pool.
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolBackpressure {
+    pub pool: String,
+    /// Average latency of the I/O completed during the last sample, in
+    /// microseconds.
+    pub latency_us: u64,
+    /// I/O completed per second during the last sample.
+    pub iops: u64,
+    /// IOPS the base device of the pool is capped to, if any.
+    pub iops_limit: Option<u64>,
+    /// IOPS limit set on the base device by the user, if any.
+    pub user_iops_limit: Option<u64>,
+}
+
+#[derive(Debug)]
+struct PoolSample {
+    at: Instant,
+    stats: BlockDeviceIoStats,
+    state: PoolBackpressure,
+    /// IOPS limit last set on the base device.
+    applied: Option<u64>,
+}
+
+static POOL_SAMPLES: Lazy<Mutex<HashMap<String, PoolSample>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Completed I/O and its cumulated latency in ticks.
+fn completed(stats: &BlockDeviceIoStats) -> (u64, u64) {
+    (
+        stats.num_read_ops + stats.num_write_ops + stats.num_unmap_ops,
+        stats.read_latency_ticks
+            + stats.write_latency_ticks
+            + stats.unmap_latency_ticks,
+    )
+}
+
+/// Rounds an IOPS limit up to what the bdev QoS supports.
+fn qos_iops(iops: u64) -> u64 {
+    iops.max(QOS_MIN_IOPS).div_ceil(QOS_MIN_IOPS) * QOS_MIN_IOPS
+}
+
+/// Returns the IOPS limit of a bdev, if any.
+fn get_iops_limit(bdev: &mut UntypedBdev) -> Option<u64> {
+    let mut limits = [0; SPDK_BDEV_QOS_NUM_RATE_LIMIT_TYPES as usize];
+    unsafe {
+        spdk_bdev_get_qos_rate_limits(
+            bdev.unsafe_inner_mut_ptr(),
+            limits.as_mut_ptr(),
+        );
+    }
+    Some(limits[SPDK_BDEV_QOS_RW_IOPS_RATE_LIMIT as usize]).filter(|l| *l > 0)
+}
+
+/// Sets the IOPS limit of a bdev, 0 removing it.
+async fn set_iops_limit(bdev: &mut UntypedBdev, iops: u64) -> ErrnoResult<()> {
+    let mut limits =
+        [QOS_LIMIT_NOT_DEFINED; SPDK_BDEV_QOS_NUM_RATE_LIMIT_TYPES as usize];
+    limits[SPDK_BDEV_QOS_RW_IOPS_RATE_LIMIT as usize] = iops;
+
+    let (s, r) = oneshot::channel::<ErrnoResult<()>>();
+    unsafe {
+        spdk_bdev_set_qos_rate_limits(
+            bdev.unsafe_inner_mut_ptr(),
+            limits.as_mut_ptr(),
+            Some(done_errno_cb),
+            cb_arg(s),
+        );
+    }
+    r.await.unwrap_or(Err(Errno::ECANCELED))
+}
+
+impl PoolBackpressure {
+    /// Samples the latency of every pool and adjusts their admission
+    /// control, returning their state.
+    pub async fn sample(threshold: Duration) -> Vec<Self> {
+        let mut states = vec![];
+        let pools = Lvs::iter().collect::<Vec<_>>();
+        for lvs in &pools {
+            if let Some(state) = Self::sample_pool(lvs, threshold).await {
+                states.push(state);
+            }
+        }
+
+        let uuids = pools.iter().map(Lvs::uuid).collect::<Vec<_>>();
+        POOL_SAMPLES
+            .lock()
+            .unwrap()
+            .retain(|uuid, _| uuids.contains(uuid));
+        states
+    }
+
+    /// Returns the state of the pools as of their last sample.
+    pub fn snapshot() -> Vec<Self> {
+        POOL_SAMPLES
+            .lock()
+            .unwrap()
+            .values()
+            .map(|s| s.state.clone())
+            .collect()
+    }
+
+    async fn sample_pool(lvs: &Lvs, threshold: Duration) -> Option<Self> {
+        let uuid = lvs.uuid();
+        let mut bdev = lvs.base_bdev();
+        let stats = bdev
+            .stats_async()
+            .await
+            .map_err(|error| {
+                error!("Failed to get the I/O stats of pool {uuid}: {error}")
+            })
+            .ok()?;
+        let now = Instant::now();
+
+        let previous = POOL_SAMPLES.lock().unwrap().remove(&uuid);
+        // a limit other than the one last set is the user's
+        let mut applied = get_iops_limit(&mut bdev);
+        let user_iops_limit = match &previous {
+            Some(p) if p.applied == applied => p.state.user_iops_limit,
+            _ => applied,
+        };
+        let mut state = PoolBackpressure {
+            pool: lvs.name().to_string(),
+            latency_us: 0,
+            iops: 0,
+            iops_limit: previous.as_ref().and_then(|p| p.state.iops_limit),
+            user_iops_limit,
+        };
+
+        // the first sample and a reset of the stats only set a baseline
+        let (ops, ticks) = completed(&stats);
+        let last = previous
+            .map(|p| (p.at, completed(&p.stats)))
+            .filter(|(_, (o, t))| ops >= *o && ticks >= *t);
+        if let Some((at, (last_ops, last_ticks))) = last {
+            let ops = (ops - last_ops) as u128;
+            let ticks = (ticks - last_ticks) as u128;
+            let elapsed_us = now.duration_since(at).as_micros().max(1);
+            state.iops = (ops * 1_000_000 / elapsed_us) as u64;
+            if ops > 0 && stats.tick_rate > 0 {
+                state.latency_us = (ticks * 1_000_000
+                    / (ops * stats.tick_rate as u128))
+                    as u64;
+            }
+
+            let limit = state.next_limit(threshold);
+            // the cap never lifts the limit of the user
+            let effective = match (limit, user_iops_limit) {
+                (Some(cap), Some(user)) => Some(cap.min(user)),
+                (cap, user) => cap.or(user),
+            };
+            let result = if effective == applied {
+                Ok(())
+            } else {
+                set_iops_limit(&mut bdev, effective.unwrap_or(0)).await
+            };
+            match result {
+                Ok(()) => {
+                    if limit != state.iops_limit {
+                        info!(
+                            "Pool {}: latency {}us at {} IOPS, IOPS limit \
+                            {:?} -> {:?}, user limit {:?}",
+                            state.pool,
+                            state.latency_us,
+                            state.iops,
+                            state.iops_limit,
+                            limit,
+                            user_iops_limit
+                        );
+                    }
+                    applied = effective;
+                    state.iops_limit = limit;
+                }
+                Err(errno) => error!(
+                    "Failed to set the IOPS limit of pool {}: {errno}",
+                    state.pool
+                ),
+            }
+        }
+
+        POOL_SAMPLES.lock().unwrap().insert(
+            uuid,
+            PoolSample {
+                at: now,
+                stats,
+                state: state.clone(),
+                applied,
+            },
+        );
+        Some(state)
+    }
+
+    /// The IOPS limit a pool should have after the given sample.
+    fn next_limit(&self, threshold: Duration) -> Option<u64> {
+        let threshold_us = threshold.as_micros() as u64;
+        if self.latency_us > threshold_us {
+            let cap = qos_iops(self.iops * CAP_PCT / 100);
+            return Some(self.iops_limit.map_or(cap, |limit| limit.min(cap)));
+        }
+
+        let limit = self.iops_limit?;
+        if self.latency_us > threshold_us / 2 {
+            return Some(limit);
+        }
+        // the cap does not constrain the device anymore
+        if limit >= qos_iops(self.iops * 2) {
+            return None;
+        }
+        Some(qos_iops(limit * RELAX_PCT / 100))
+    }
+}
+
+/// Periodically samples the latency of the pools, applying admission
+/// control past the threshold.
+pub async fn pool_backpressure_loop(threshold: Duration, interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        let sample = Reactor::spawn_at_primary(async move {
+            PoolBackpressure::sample(threshold).await;
+        });
+        match sample {
+            Ok(rx) => {
+                if rx.await.is_err() {
+                    error!("Pool backpressure sample was cancelled");
+                }
+            }
+            Err(error) => {
+                error!("Failed to sample the pool backpressure: {error}")
+            }
+        }
+    }
+}
+
+/// Registers the JSON-RPC methods of the pool backpressure.
+pub(super) fn register_rpc_methods() {
+    // latency, IOPS and admission control of the pools as of their
+    // last backpressure sample
+    jsonrpc_register::<(), _, _, JsonRpcError>(
+        "mayastor_pool_backpressure",
+        |_| async move { Ok(PoolBackpressure::snapshot()) }.boxed_local(),
+    );
+}
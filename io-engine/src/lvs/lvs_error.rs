@@ -273,6 +273,18 @@ pub enum LvsError {
     ResourceLockFailed {
         msg: String,
     },
+    #[snafu(display("Failed to take over pool {name}: {reason}"))]
+    Takeover {
+        name: String,
+        reason: String,
+    },
+    #[snafu(display(
+        "Failed to persist the reserved capacity of pool {name}: {reason}"
+    ))]
+    PersistReserved {
+        name: String,
+        reason: String,
+    },
 }
 
 /// Map CoreError to errno code.
@@ -363,6 +375,12 @@ impl ToErrno for LvsError {
             Self::ResourceLockFailed {
                 ..
             } => Errno::EBUSY,
+            Self::Takeover {
+                ..
+            } => Errno::EPERM,
+            Self::PersistReserved {
+                ..
+            } => Errno::EIO,
         }
     }
 }
@@ -1,9 +1,10 @@
 use async_trait::async_trait;
 use byte_unit::Byte;
 use events_api::event::EventAction;
-use futures::channel::oneshot;
+use futures::{channel::oneshot, FutureExt};
 use nix::errno::Errno;
 use pin_utils::core_reexport::fmt::Formatter;
+use serde::Deserialize;
 
 use std::{
     convert::TryFrom,
@@ -41,6 +42,8 @@ use crate::{
     core::{
         logical_volume::{LogicalVolume, LvolSpaceUsage},
         Bdev,
+        BdevFence,
+        BdevOperation,
         CloneXattrs,
         LvolSnapshotOps,
         NvmfShareProps,
@@ -61,7 +64,9 @@ use crate::{
         FfiResult,
         IntoCString,
     },
+    jsonrpc::{jsonrpc_register, Code, JsonRpcError},
     pool_backend::PoolBackend,
+    subsys::NvmfControllerInfo,
 };
 
 // Wipe `WIPE_SUPER_LEN` bytes if unmap is not supported.
@@ -75,6 +80,8 @@ pub enum PropValue {
     Shared(bool),
     AllowedHosts(Vec<String>),
     EntityId(String),
+    /// Priority of the re-share of the lvol at startup, the highest first.
+    RestorePriority(u8),
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -83,6 +90,7 @@ pub enum PropName {
     Shared,
     AllowedHosts,
     EntityId,
+    RestorePriority,
 }
 
 impl From<&PropValue> for PropName {
@@ -91,6 +99,7 @@ impl From<&PropValue> for PropName {
             PropValue::Shared(_) => Self::Shared,
             PropValue::AllowedHosts(_) => Self::AllowedHosts,
             PropValue::EntityId(_) => Self::EntityId,
+            PropValue::RestorePriority(_) => Self::RestorePriority,
         }
     }
 }
@@ -112,6 +121,7 @@ impl Display for PropName {
             PropName::Shared => "shared",
             PropName::AllowedHosts => "allowed-hosts",
             PropName::EntityId => "entity_id",
+            PropName::RestorePriority => "restore-priority",
         };
         write!(f, "{name}")
     }
@@ -232,18 +242,8 @@ impl Share for Lvol {
     }
 
     /// unshare the nvmf target
-    async fn unshare(mut self: Pin<&mut Self>) -> Result<(), Self::Error> {
-        Pin::new(&mut self.as_bdev()).unshare().await.map_err(|e| {
-            LvsError::LvolUnShare {
-                source: e,
-                name: self.name(),
-            }
-        })?;
-
-        self.as_mut().set(PropValue::Shared(false)).await?;
-
-        info!("{:?}: unshared", self);
-        Ok(())
+    async fn unshare(self: Pin<&mut Self>) -> Result<(), Self::Error> {
+        self.unshare_within(None).await
     }
 
     /// return the protocol this bdev is shared under
@@ -264,6 +264,10 @@ impl Share for Lvol {
         self.as_bdev().allowed_hosts()
     }
 
+    fn nvmf_controllers(&self) -> Vec<NvmfControllerInfo> {
+        self.as_bdev().nvmf_controllers()
+    }
+
     /// returns the URI that is used to construct the bdev. This is always None
     /// as lvols can not be created by URIs directly, but only through the
     /// ['Lvs'] interface.
@@ -277,6 +281,26 @@ impl Share for Lvol {
 }
 
 impl Lvol {
+    /// Unshares the nvmf target, under the given fence of the operation in
+    /// progress on the lvol which the unshare is part of, if any.
+    async fn unshare_within(
+        mut self: Pin<&mut Self>,
+        owner: Option<&BdevFence>,
+    ) -> Result<(), LvsError> {
+        Pin::new(&mut self.as_bdev())
+            .unshare_within(owner)
+            .await
+            .map_err(|e| LvsError::LvolUnShare {
+                source: e,
+                name: self.name(),
+            })?;
+
+        self.as_mut().set(PropValue::Shared(false)).await?;
+
+        info!("{:?}: unshared", self);
+        Ok(())
+    }
+
     /// TODO
     pub(super) fn from_inner_ptr(p: *mut spdk_lvol) -> Self {
         Self {
@@ -290,6 +314,14 @@ impl Lvol {
         unsafe { self.inner.as_ref() }
     }
 
+    /// Priority of the re-share of the lvol at startup, 0 when not set.
+    pub async fn restore_priority(&self) -> u8 {
+        match self.get(PropName::RestorePriority).await {
+            Ok(PropValue::RestorePriority(priority)) => priority,
+            _ => 0,
+        }
+    }
+
     pub fn ok_from(mut bdev: UntypedBdev) -> Option<Self> {
         if !Self::is_lvol(&bdev) {
             return None;
@@ -872,6 +904,16 @@ impl LvsLvol for Lvol {
                     _ => einval(),
                 }
             }
+            PropName::RestorePriority => {
+                match unsafe { CStr::from_ptr(value).to_str() }
+                    .map(str::parse::<u8>)
+                {
+                    Ok(Ok(priority)) => {
+                        Ok(PropValue::RestorePriority(priority))
+                    }
+                    _ => einval(),
+                }
+            }
         }
     }
 
@@ -889,9 +931,17 @@ impl LvsLvol for Lvol {
                 unsafe { Box::from_raw(sender as *mut oneshot::Sender<i32>) };
             sender.send(errno).unwrap();
         }
+        // the unshare below is part of the destroy
+        let bdev = self.as_bdev();
+        let fence = BdevFence::acquire(bdev.name(), BdevOperation::Destroy)
+            .map_err(|e| LvsError::RepDestroy {
+                source: BsError::VolBusy {},
+                name: self.name(),
+                msg: e.to_string(),
+            })?;
         self.reset_snapshot_tree_usage_cache(!self.is_snapshot());
         // We must always unshare before destroying bdev.
-        let _ = Pin::new(&mut self).unshare().await;
+        let _ = Pin::new(&mut self).unshare_within(Some(&fence)).await;
 
         let name = self.name();
         let ptpl = self.ptpl();
@@ -964,6 +1014,13 @@ impl LvsLvol for Lvol {
                 }
                 id.into_cstring()
             }
+            PropValue::RestorePriority(priority) => {
+                if matches!(self.get(PropName::RestorePriority).await, Ok(PropValue::RestorePriority(p)) if p == priority)
+                {
+                    return Ok(false);
+                }
+                priority.to_string().into_cstring()
+            }
         };
         let name = PropName::from(&prop).to_string().into_cstring();
         unsafe {
@@ -1104,6 +1161,20 @@ impl LvsLvol for Lvol {
     /// upon if required size is more or less than current size of
     /// the replica.
     async fn resize_replica(&mut self, resize_to: u64) -> Result<(), LvsError> {
+        let lvs = self.lvs();
+        // As it stands lvs pools can't grow, so limit the max replica size to
+        // the pool capacity.
+        if resize_to > lvs.capacity() {
+            return Err(LvsError::RepResize {
+                source: BsError::CapacityOverflow {},
+                name: self.name(),
+            });
+        }
+        let size = self.size();
+        if resize_to > size {
+            lvs.check_grow(&self.name(), resize_to - size, self.is_thin())?;
+        }
+
         let (s, r) = pair::<ErrnoResult<*mut spdk_lvol>>();
         let mut ctx = ResizeCbCtx {
             lvol: self.as_inner_ptr(),
@@ -1124,6 +1195,8 @@ impl LvsLvol for Lvol {
 
         match cb_ret {
             Ok(_) => {
+                // the namespace of the share, if any, follows the size of the
+                // bdev and its hosts are notified of the change by the target
                 info!("Resized {:?} successfully", self);
                 Ok(())
             }
@@ -1160,3 +1233,41 @@ extern "C" fn lvol_resize_cb(cb_arg: *mut c_void, errno: i32) {
         .send(errno_result_from_i32(lvol.as_inner_ptr(), retcode))
         .expect("Receiver is gone");
 }
+
+/// Arguments of the `mayastor_replica_restore_priority_set` method.
+#[derive(Debug, Deserialize)]
+struct RestorePriorityArgs {
+    /// UUID of the replica.
+    uuid: String,
+    /// Priority of the re-share of the replica at startup, the highest
+    /// first.
+    priority: u8,
+}
+
+/// Registers the JSON-RPC methods of the replicas.
+pub(super) fn register_rpc_methods() {
+    // priority of the re-share of a replica at startup, e.g. after the
+    // QoS class of its volume, so that the replicas of the critical
+    // volumes come back online first
+    jsonrpc_register::<RestorePriorityArgs, _, _, JsonRpcError>(
+        "mayastor_replica_restore_priority_set",
+        |args| {
+            async move {
+                let mut lvol = UntypedBdev::lookup_by_uuid_str(&args.uuid)
+                    .and_then(Lvol::ok_from)
+                    .ok_or_else(|| JsonRpcError {
+                        code: Code::NotFound,
+                        message: format!("replica {} not found", args.uuid),
+                    })?;
+                Pin::new(&mut lvol)
+                    .set(PropValue::RestorePriority(args.priority))
+                    .await
+                    .map_err(|e| JsonRpcError {
+                        code: Code::InternalError,
+                        message: e.to_string(),
+                    })
+            }
+            .boxed_local()
+        },
+    );
+}
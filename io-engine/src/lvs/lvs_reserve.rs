@@ -0,0 +1,331 @@
+//! Capacity of the pools reserved for the system overhead.
+//!
+//! Thin provisioned replicas may grow until their pool is full, leaving no
+//! room for the metadata of the blobstore nor for the clusters copied on
+//! write once a replica has snapshots, which then fail with ENOSPC one after
+//! the other. A share of the capacity of every pool is therefore reserved:
+//! no replica is created once the used capacity of its pool would reach into
+//! the reservation, and the reservation is reported apart from the capacity
+//! left to the replicas.
+//!
+//! The share defaults to that of the node and may be overridden per pool.
+//! The overrides are kept in the ptpl directory, so that a pool imported
+//! again after a restart, e.g. through gRPC which knows nothing about them,
+//! keeps its own share until the pool is destroyed.
+//!
+//! The snapshots and clones share their clusters with their replicas until
+//! written to, so they are not created either on a pool already using its
+//! reservation.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use futures::FutureExt;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use super::{BsError, Lvs, LvsError};
+use crate::{
+    core::MayastorEnvironment,
+    jsonrpc::{jsonrpc_register, Code, JsonRpcError},
+    subsys::{config::pool::PoolConfig, load_state, save_state},
+};
+
+/// File of the shares overriding the default of the node, within the ptpl
+/// directory.
+const RESERVED_PCT_FILE: &str = "pool-reserved.json";
+
+type Shares = HashMap<String, u8>;
+
+/// Shares overriding the default of the node, by pool name, as loaded from
+/// their file.
+static RESERVED_PCT: Lazy<Mutex<Shares>> = Lazy::new(|| {
+    let shares = reserved_pct_path()
+        .and_then(|path| load_state::<Shares>(&path))
+        .unwrap_or_default();
+    Mutex::new(shares)
+});
+
+/// Path of the file of the shares, if a ptpl directory is configured.
+fn reserved_pct_path() -> Option<PathBuf> {
+    MayastorEnvironment::global_or_default()
+        .ptpl_dir()
+        .map(|dir| Path::new(&dir).join(RESERVED_PCT_FILE))
+}
+
+/// Applies a change to the shares, persisting them before the change takes
+/// effect so that a failure leaves the shares as they were.
+fn change_shares(
+    pool: &str,
+    f: impl FnOnce(&mut Shares),
+) -> Result<(), LvsError> {
+    let mut shares = RESERVED_PCT.lock().unwrap();
+    let mut changed = shares.clone();
+    f(&mut changed);
+    if changed == *shares {
+        return Ok(());
+    }
+    if let Some(path) = reserved_pct_path() {
+        save_state(&path, &changed).map_err(|error| {
+            LvsError::PersistReserved {
+                name: pool.to_string(),
+                reason: error.to_string(),
+            }
+        })?;
+    }
+    *shares = changed;
+    Ok(())
+}
+
+/// Capacity of a pool and the share of it reserved for the system overhead.
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolCapacity {
+    pub pool: String,
+    /// Total capacity of the pool, in bytes.
+    pub capacity: u64,
+    /// Share of the capacity reserved, in percent.
+    pub reserved_pct: u8,
+    /// Whether the share overrides the default of the node.
+    pub reserved_override: bool,
+    /// Capacity reserved, in bytes.
+    pub reserved: u64,
+    /// Capacity left to the replicas, in bytes.
+    pub usable: u64,
+    /// Capacity used, in bytes.
+    pub used: u64,
+    /// Capacity committed to the replicas, in bytes.
+    pub committed: u64,
+}
+
+/// Checks that a share of the capacity is a valid percentage.
+pub(super) fn validate_reserved_pct(
+    pool: &str,
+    reserved_pct: Option<u8>,
+) -> Result<(), LvsError> {
+    match reserved_pct {
+        Some(pct) if pct > 100 => Err(LvsError::Invalid {
+            source: BsError::InvalidArgument {},
+            msg: format!(
+                "invalid reserved capacity of pool {pool}: {pct}% is over 100%"
+            ),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Returns the share reserved on the given pool when it overrides the
+/// default of the node.
+pub fn reserved_pct_override(pool: &str) -> Option<u8> {
+    RESERVED_PCT.lock().unwrap().get(pool).copied()
+}
+
+/// Forgets the share reserved on a pool which is destroyed.
+pub(super) fn forget_reserved_pct(pool: &str) {
+    if let Err(error) = change_shares(pool, |shares| {
+        shares.remove(pool);
+    }) {
+        error!(%error, "Failed to forget the reserved capacity of {pool}");
+    }
+}
+
+impl Lvs {
+    /// Returns the share of the capacity reserved on this pool, in percent.
+    pub fn reserved_pct(&self) -> u8 {
+        self.reserved_pct_override().unwrap_or_else(|| {
+            MayastorEnvironment::global_or_default().pool_reserved_pct
+        })
+    }
+
+    /// Returns the share reserved on this pool when it overrides the
+    /// default of the node.
+    pub fn reserved_pct_override(&self) -> Option<u8> {
+        reserved_pct_override(self.name())
+    }
+
+    /// Sets the share of the capacity reserved on this pool, back to the
+    /// default of the node when None.
+    pub fn set_reserved_pct(
+        &self,
+        reserved_pct: Option<u8>,
+    ) -> Result<(), LvsError> {
+        validate_reserved_pct(self.name(), reserved_pct)?;
+        change_shares(self.name(), |shares| {
+            match reserved_pct {
+                Some(pct) => shares.insert(self.name().to_string(), pct),
+                None => shares.remove(self.name()),
+            };
+        })?;
+        info!(
+            "{self:?}: reserving {}% of the capacity",
+            reserved_pct.unwrap_or_else(|| {
+                MayastorEnvironment::global_or_default().pool_reserved_pct
+            })
+        );
+        Ok(())
+    }
+
+    /// Returns the capacity reserved on this pool, in bytes.
+    pub fn reserved(&self) -> u64 {
+        self.capacity() * self.reserved_pct() as u64 / 100
+    }
+
+    /// Returns the capacity of this pool left to the replicas, in bytes.
+    pub fn usable(&self) -> u64 {
+        self.capacity() - self.reserved()
+    }
+
+    /// Returns the capacity of this pool along with its reservation.
+    pub fn capacity_info(&self) -> PoolCapacity {
+        PoolCapacity {
+            pool: self.name().to_string(),
+            capacity: self.capacity(),
+            reserved_pct: self.reserved_pct(),
+            reserved_override: self.reserved_pct_override().is_some(),
+            reserved: self.reserved(),
+            usable: self.usable(),
+            used: self.used(),
+            committed: self.committed(),
+        }
+    }
+
+    /// Checks that a replica of the given size may be created without
+    /// reaching into the reserved capacity. A thin provisioned replica takes
+    /// no capacity when created, but none is created on a pool already
+    /// using its reservation.
+    pub(super) fn check_reserved(
+        &self,
+        name: &str,
+        size: u64,
+        thin: bool,
+    ) -> Result<(), LvsError> {
+        if self.reserved_pct() == 0 {
+            return Ok(());
+        }
+        let needed = if thin { 0 } else { size };
+        if self.used() + needed > self.usable() {
+            warn!(
+                "{self:?}: not creating replica {name}, the pool would use \
+                its reserved capacity ({} bytes used, {} bytes usable)",
+                self.used(),
+                self.usable()
+            );
+            return Err(LvsError::RepCreate {
+                source: BsError::NoSpace {},
+                name: name.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns whether the pool uses its reserved capacity already, in which
+    /// case no snapshot nor clone is created on it: their clusters are
+    /// copied on write out of the capacity left.
+    pub(super) fn reserve_exhausted(&self) -> bool {
+        if self.reserved_pct() == 0 || self.used() < self.usable() {
+            return false;
+        }
+        warn!(
+            "{self:?}: the pool uses its reserved capacity ({} bytes used, \
+            {} bytes usable)",
+            self.used(),
+            self.usable()
+        );
+        true
+    }
+
+    /// Checks that a replica may grow by the given size. A thick provisioned
+    /// replica allocates the clusters it grows by at once, which must be
+    /// free without reaching into the reserved capacity; a thin provisioned
+    /// replica allocates none.
+    pub(super) fn check_grow(
+        &self,
+        name: &str,
+        grow: u64,
+        thin: bool,
+    ) -> Result<(), LvsError> {
+        if thin {
+            return Ok(());
+        }
+        let cluster_size = self.blob_cluster_size();
+        let needed = grow.div_ceil(cluster_size) * cluster_size;
+        if self.used() + needed > self.usable() {
+            warn!(
+                "{self:?}: not growing replica {name} by {needed} bytes, the \
+                pool would run out of capacity ({} bytes used, {} bytes \
+                usable)",
+                self.used(),
+                self.usable()
+            );
+            return Err(LvsError::RepResize {
+                source: BsError::NoSpace {},
+                name: name.to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Arguments of the `mayastor_pool_capacity` method.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct PoolCapacityArgs {
+    /// Name of the pool, all the pools when not given.
+    name: Option<String>,
+}
+
+/// Arguments of the `mayastor_pool_reserved_set` method.
+#[derive(Debug, Deserialize)]
+struct PoolReservedArgs {
+    /// Name of the pool.
+    name: String,
+    /// Share of the capacity reserved, in percent, back to the default of
+    /// the node when not given.
+    #[serde(default)]
+    reserved_pct: Option<u8>,
+}
+
+/// Registers the JSON-RPC methods of the pool reserve.
+pub(super) fn register_rpc_methods() {
+    // capacity of the pools, with the share of it reserved for the
+    // system overhead reported apart
+    jsonrpc_register::<PoolCapacityArgs, _, _, JsonRpcError>(
+        "mayastor_pool_capacity",
+        |args| {
+            async move {
+                Ok(Lvs::iter()
+                    .filter(|lvs| {
+                        args.name.as_deref().map_or(true, |n| n == lvs.name())
+                    })
+                    .map(|lvs| lvs.capacity_info())
+                    .collect::<Vec<_>>())
+            }
+            .boxed_local()
+        },
+    );
+
+    // share of the capacity of a pool reserved for the system overhead
+    jsonrpc_register::<PoolReservedArgs, _, _, JsonRpcError>(
+        "mayastor_pool_reserved_set",
+        |args| {
+            async move {
+                let lvs =
+                    Lvs::lookup(&args.name).ok_or_else(|| JsonRpcError {
+                        code: Code::NotFound,
+                        message: format!("pool {} not found", args.name),
+                    })?;
+                lvs.set_reserved_pct(args.reserved_pct).map_err(|e| {
+                    JsonRpcError {
+                        code: Code::InvalidParams,
+                        message: e.to_string(),
+                    }
+                })?;
+                PoolConfig::capture().export().await;
+                Ok(lvs.capacity_info())
+            }
+            .boxed_local()
+        },
+    );
+}
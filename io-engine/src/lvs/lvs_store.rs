@@ -1,4 +1,6 @@
 use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
     convert::TryFrom,
     fmt::Debug,
     os::raw::c_void,
@@ -8,7 +10,7 @@ use std::{
 
 use byte_unit::Byte;
 use events_api::event::EventAction;
-use futures::channel::oneshot;
+use futures::{channel::oneshot, stream, StreamExt};
 use nix::errno::Errno;
 use pin_utils::core_reexport::fmt::Formatter;
 use spdk_rs::libspdk::{
@@ -52,6 +54,7 @@ use crate::{
         snapshot::LvolSnapshotOps,
         Bdev,
         IoType,
+        MayastorEnvironment,
         NvmfShareProps,
         Share,
         UntypedBdev,
@@ -60,9 +63,11 @@ use crate::{
     ffihelper::{cb_arg, pair, AsStr, ErrnoResult, FfiResult, IntoCString},
     lvs::{
         lvs_lvol::{LvsLvol, WIPE_SUPER_LEN},
+        lvs_reserve::{forget_reserved_pct, validate_reserved_pct},
         LvolSnapshotDescriptor,
     },
     pool_backend::PoolArgs,
+    subsys::StartupProgress,
 };
 
 static ROUND_TO_MB: u32 = 1024 * 1024;
@@ -328,7 +333,11 @@ impl Lvs {
                 },
             })
         } else {
-            lvs.share_all().await;
+            // at startup, the lvols are shared once all the pools are
+            // imported, so that their restore priority spans the pools
+            if !StartupProgress::defer_shares() {
+                lvs.share_all().await;
+            }
             info!("{:?}: existing lvs imported successfully", lvs);
             Ok(lvs)
         }
@@ -338,6 +347,7 @@ impl Lvs {
     #[tracing::instrument(level = "debug", err)]
     pub async fn import_from_args(args: PoolArgs) -> Result<Lvs, LvsError> {
         let disk = Self::parse_disk(args.disks.clone())?;
+        validate_reserved_pct(&args.name, args.reserved_pct)?;
 
         let parsed = uri::parse(&disk).map_err(|e| LvsError::InvalidBdev {
             source: e,
@@ -388,6 +398,9 @@ impl Lvs {
         // Try to destroy the pending snapshots without catching
         // the error.
         Lvol::destroy_pending_discarded_snapshot().await;
+        if args.reserved_pct.is_some() {
+            pool.set_reserved_pct(args.reserved_pct)?;
+        }
         // if the uuid is provided for the import request check
         // for the pool uuid to make sure it is the correct one
         if let Some(uuid) = args.uuid {
@@ -507,6 +520,7 @@ impl Lvs {
     #[tracing::instrument(level = "debug", err)]
     pub async fn create_or_import(args: PoolArgs) -> Result<Lvs, LvsError> {
         let disk = Self::parse_disk(args.disks.clone())?;
+        validate_reserved_pct(&args.name, args.reserved_pct)?;
 
         info!(
             "Creating or importing lvs '{}' from '{}'...",
@@ -576,6 +590,9 @@ impl Lvs {
                     }
                     Ok(pool) => {
                         pool.event(EventAction::Create).generate();
+                        if args.reserved_pct.is_some() {
+                            pool.set_reserved_pct(args.reserved_pct)?;
+                        }
                         Ok(pool)
                     }
                 }
@@ -641,37 +658,66 @@ impl Lvs {
     /// shared over nvmf
     async fn share_all(&self) {
         if let Some(lvols) = self.lvols() {
-            for mut l in lvols {
-                let allowed_hosts = match l.get(PropName::AllowedHosts).await {
-                    Ok(PropValue::AllowedHosts(hosts)) => hosts,
-                    _ => vec![],
-                };
-
-                if let Ok(prop) = l.get(PropName::Shared).await {
-                    match prop {
-                        PropValue::Shared(true) => {
-                            let name = l.name().clone();
-                            let props = NvmfShareProps::new()
-                                .with_allowed_hosts(allowed_hosts)
-                                .with_ptpl(
-                                    l.ptpl().create().unwrap_or_default(),
-                                );
-                            if let Err(e) =
-                                Pin::new(&mut l).share_nvmf(Some(props)).await
-                            {
-                                error!(
-                                    "failed to share {} {}",
-                                    name,
-                                    e.to_string()
-                                );
-                            }
-                        }
-                        PropValue::Shared(false) => {
-                            debug!("{} not shared on disk", l.name())
-                        }
-                        _ => {}
+            Self::share_lvols(lvols.collect()).await;
+        }
+    }
+
+    /// share the lvols of all the pools who have the shared property set,
+    /// once the pools are imported at startup
+    pub(crate) async fn share_all_pools() {
+        let lvols = Self::iter()
+            .filter_map(|lvs| lvs.lvols())
+            .flatten()
+            .collect();
+        Self::share_lvols(lvols).await;
+    }
+
+    /// share the given lvols who have the shared property set, by decreasing
+    /// restore priority: all the lvols of a priority are shared before those
+    /// of the next one, up to the startup concurrency of them at the same
+    /// time
+    async fn share_lvols(lvols: Vec<Lvol>) {
+        let mut tiers: BTreeMap<Reverse<u8>, Vec<Lvol>> = BTreeMap::new();
+        for lvol in lvols {
+            let priority = lvol.restore_priority().await;
+            tiers.entry(Reverse(priority)).or_default().push(lvol);
+        }
+        let concurrency = MayastorEnvironment::global_or_default()
+            .startup_concurrency
+            .max(1);
+
+        for (Reverse(priority), lvols) in tiers {
+            debug!("sharing {} lvols of priority {priority}", lvols.len());
+            stream::iter(lvols)
+                .for_each_concurrent(concurrency, Self::share_lvol)
+                .await;
+        }
+    }
+
+    /// share an lvol if it has the shared property set
+    async fn share_lvol(mut l: Lvol) {
+        let allowed_hosts = match l.get(PropName::AllowedHosts).await {
+            Ok(PropValue::AllowedHosts(hosts)) => hosts,
+            _ => vec![],
+        };
+
+        if let Ok(prop) = l.get(PropName::Shared).await {
+            match prop {
+                PropValue::Shared(true) => {
+                    let name = l.name().clone();
+                    let props = NvmfShareProps::new()
+                        .with_allowed_hosts(allowed_hosts)
+                        .with_ptpl(l.ptpl().create().unwrap_or_default());
+                    let result = Pin::new(&mut l).share_nvmf(Some(props)).await;
+                    StartupProgress::replica_shared(result.is_ok());
+                    if let Err(e) = result {
+                        error!("failed to share {} {}", name, e.to_string());
                     }
                 }
+                PropValue::Shared(false) => {
+                    debug!("{} not shared on disk", l.name())
+                }
+                _ => {}
             }
         }
     }
@@ -710,6 +756,7 @@ impl Lvs {
             })?;
 
         info!("{}: lvs destroyed successfully", self_str);
+        forget_reserved_pct(&pool);
 
         evt.generate();
 
@@ -828,6 +875,8 @@ impl Lvs {
             });
         }
 
+        self.check_reserved(name, size, thin)?;
+
         let (s, r) = pair::<ErrnoResult<*mut spdk_lvol>>();
         let cname = name.into_cstring();
         unsafe {
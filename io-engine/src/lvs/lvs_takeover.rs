@@ -0,0 +1,146 @@
+//! Takeover of the pools of a failed node.
+//!
+//! A pool on storage several nodes have access to, e.g. a SAN or iSCSI LUN,
+//! outlives the node it was imported on. When that node fails, another node
+//! takes the pool over: it imports the pool, the blobstore of which is
+//! recovered if it was not unloaded cleanly, and shares its replicas again.
+//! The NQN of a replica derives from its name and its serial number from its
+//! UUID, both stored in the pool, so that the hosts and the nexuses find the
+//! same replicas at the address of the new node.
+//!
+//! Importing a pool the failed node still writes to would corrupt it: the
+//! caller must confirm that the node is fenced from the storage, and give the
+//! UUID of the pool so that no other pool is taken over. The persistent
+//! reservations of the replicas were kept in files on the failed node and
+//! are not taken over.
+
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+
+use super::{Lvs, LvsError};
+use crate::{
+    core::{LogicalVolume, Share},
+    jsonrpc::{jsonrpc_register, Code, JsonRpcError},
+    pool_backend::{PoolArgs, PoolBackend},
+    subsys::config::pool::PoolConfig,
+};
+
+/// A replica of a pool taken over.
+#[derive(Debug, Clone, Serialize)]
+pub struct TakenOverReplica {
+    pub name: String,
+    pub uuid: String,
+    /// URI the replica is shared at on this node, if shared.
+    pub uri: Option<String>,
+}
+
+/// Report of the takeover of a pool.
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolTakeover {
+    pub pool: String,
+    pub uuid: String,
+    pub replicas: Vec<TakenOverReplica>,
+}
+
+impl Lvs {
+    /// Takes over a pool of a failed node, which must be fenced from the
+    /// disks of the pool: the pool is imported and its replicas shared again
+    /// under the same NQNs and serial numbers.
+    pub async fn takeover(
+        args: PoolArgs,
+        fenced: bool,
+    ) -> Result<PoolTakeover, LvsError> {
+        let name = args.name.clone();
+        if !fenced {
+            return Err(LvsError::Takeover {
+                name,
+                reason: "the node the pool is taken over from must be fenced"
+                    .to_string(),
+            });
+        }
+        if args.uuid.is_none() {
+            return Err(LvsError::Takeover {
+                name,
+                reason: "the UUID of the pool must be given".to_string(),
+            });
+        }
+
+        warn!("Taking over pool '{name}' from {:?}...", args.disks);
+        let lvs = Self::import_from_args(args).await?;
+
+        let replicas = lvs
+            .lvols()
+            .map(|lvols| {
+                lvols
+                    .map(|l| TakenOverReplica {
+                        name: l.name(),
+                        uuid: l.uuid(),
+                        uri: l.share_uri(),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let takeover = PoolTakeover {
+            pool: lvs.name().to_string(),
+            uuid: lvs.uuid(),
+            replicas,
+        };
+        info!(
+            "{lvs:?}: pool taken over, {} replicas",
+            takeover.replicas.len()
+        );
+        Ok(takeover)
+    }
+}
+
+/// Arguments of the `mayastor_pool_takeover` method.
+#[derive(Debug, Deserialize)]
+struct PoolTakeoverArgs {
+    /// Name of the pool.
+    name: String,
+    /// Disks of the pool, as seen from this node.
+    disks: Vec<String>,
+    /// UUID of the pool.
+    uuid: Option<String>,
+    /// Whether the node the pool is taken over from is fenced from its
+    /// disks.
+    #[serde(default)]
+    fenced: bool,
+}
+
+/// Registers the JSON-RPC methods of the pool takeover.
+pub(super) fn register_rpc_methods() {
+    // takeover of a pool of a failed node on storage shared with this
+    // node, its replicas being shared again under the same NQNs and
+    // serial numbers; the pool is then imported at startup
+    jsonrpc_register::<PoolTakeoverArgs, _, _, JsonRpcError>(
+        "mayastor_pool_takeover",
+        |args| {
+            async move {
+                let pool = PoolArgs {
+                    name: args.name,
+                    disks: args.disks,
+                    uuid: args.uuid,
+                    cluster_size: None,
+                    reserved_pct: None,
+                    backend: PoolBackend::Lvs,
+                };
+                let takeover =
+                    Lvs::takeover(pool, args.fenced).await.map_err(|e| {
+                        JsonRpcError {
+                            code: match e {
+                                LvsError::Takeover {
+                                    ..
+                                } => Code::InvalidParams,
+                                _ => Code::InternalError,
+                            },
+                            message: e.to_string(),
+                        }
+                    })?;
+                PoolConfig::capture().export().await;
+                Ok(takeover)
+            }
+            .boxed_local()
+        },
+    );
+}
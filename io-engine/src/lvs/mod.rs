@@ -32,20 +32,26 @@ use crate::{
     },
 };
 pub use lvol_snapshot::LvolSnapshotIter;
+pub use lvs_backpressure::{pool_backpressure_loop, PoolBackpressure};
 pub use lvs_bdev::LvsBdev;
 pub use lvs_error::{BsError, ImportErrorReason, LvsError};
 pub use lvs_iter::{LvsBdevIter, LvsIter};
 pub use lvs_lvol::{Lvol, LvsLvol, PropName, PropValue};
+pub use lvs_reserve::{reserved_pct_override, PoolCapacity};
 pub use lvs_store::Lvs;
+pub use lvs_takeover::{PoolTakeover, TakenOverReplica};
 use std::{convert::TryFrom, pin::Pin};
 
 mod lvol_iter;
 mod lvol_snapshot;
+mod lvs_backpressure;
 mod lvs_bdev;
 mod lvs_error;
 mod lvs_iter;
 pub mod lvs_lvol;
+mod lvs_reserve;
 mod lvs_store;
+mod lvs_takeover;
 
 use crate::{
     core::{BdevStater, BdevStats, CoreError, UntypedBdev},
@@ -53,6 +59,14 @@ use crate::{
 };
 pub use lvol_snapshot::{LvolResult, LvolSnapshotDescriptor, LvolSnapshotOps};
 
+/// Registers the JSON-RPC methods of the pools and their replicas.
+pub(crate) fn register_rpc_methods() {
+    lvs_backpressure::register_rpc_methods();
+    lvs_reserve::register_rpc_methods();
+    lvs_lvol::register_rpc_methods();
+    lvs_takeover::register_rpc_methods();
+}
+
 #[async_trait::async_trait(?Send)]
 impl ReplicaOps for Lvol {
     fn shared(&self) -> Option<Protocol> {
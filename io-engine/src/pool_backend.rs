@@ -14,6 +14,10 @@ pub struct PoolArgs {
     pub disks: Vec<String>,
     pub uuid: Option<String>,
     pub cluster_size: Option<u32>,
+    /// Share of the capacity reserved for the system overhead, in percent,
+    /// overriding the default of the node. None keeps the share persisted
+    /// for the pool, if any.
+    pub reserved_pct: Option<u8>,
     pub backend: PoolBackend,
 }
 
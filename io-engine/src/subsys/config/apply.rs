@@ -0,0 +1,539 @@
+//! Reconciliation of the node with a desired-state document.
+//!
+//! The desired state is described by a `NodeConfig`. Applying it computes the
+//! list of actions needed to converge the current state of the node towards
+//! it and executes them. Applying the same document twice is a no-op, which
+//! makes it suitable to drive standalone io-engines from a repository of
+//! declarative documents.
+use serde::{Deserialize, Serialize};
+use std::pin::Pin;
+
+use super::node::{
+    apply_replica_share,
+    create_nexus,
+    import_pool,
+    lookup_replica,
+    same_hosts,
+    NodeConfig,
+    NodeConfigReport,
+    NodeNexus,
+    NodePool,
+    NodeReplica,
+    NodeShare,
+};
+use crate::{
+    bdev::nexus::{
+        nexus_iter,
+        nexus_lookup,
+        nexus_lookup_mut,
+        NexusReadPolicy,
+    },
+    core::{LogicalVolume, Protocol, Share, UntypedBdev},
+    lvs::{Lvol, Lvs, LvsLvol},
+};
+
+/// Arguments of a request to apply a desired state.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApplyStateArgs {
+    /// Desired state of the node.
+    pub state: NodeConfig,
+    /// Only compute the actions, without executing them.
+    pub dry_run: bool,
+    /// Destroy the replicas and nexuses which are not part of the desired
+    /// state. Pools are never destroyed.
+    pub prune: bool,
+}
+
+/// A single step towards the desired state.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ApplyAction {
+    /// Create (or import) a pool.
+    CreatePool(NodePool),
+    /// Set the share of the capacity of a pool reserved for the system
+    /// overhead, back to the default of the node when None.
+    SetPoolReservedPct {
+        name: String,
+        reserved_pct: Option<u8>,
+    },
+    /// Create a replica, sharing it if required.
+    CreateReplica(NodeReplica),
+    /// Grow a replica to the desired size.
+    ResizeReplica {
+        name: String,
+        uuid: String,
+        size: u64,
+    },
+    /// Shrink a replica to the desired size, which is not supported and
+    /// always fails.
+    ShrinkReplica {
+        name: String,
+        uuid: String,
+        size: u64,
+    },
+    /// Share a replica or update its allowed hosts.
+    ShareReplica(NodeReplica),
+    /// Unshare a replica.
+    UnshareReplica { name: String, uuid: String },
+    /// Destroy a replica which is not part of the desired state.
+    DestroyReplica { name: String, uuid: String },
+    /// Create a nexus, publishing it if required.
+    CreateNexus(NodeNexus),
+    /// Add a child to a nexus.
+    AddNexusChild { name: String, uri: String },
+    /// Remove a child from a nexus.
+    RemoveNexusChild { name: String, uri: String },
+    /// Enable or disable ANA reporting on the share of a nexus.
+    SetNexusAnaReporting { name: String, enable: bool },
+    /// Set the policy the children of a nexus to read from are selected by.
+    SetNexusReadPolicy {
+        name: String,
+        policy: NexusReadPolicy,
+    },
+    /// Publish a nexus or update its allowed hosts.
+    ShareNexus { name: String, share: NodeShare },
+    /// Unpublish a nexus.
+    UnshareNexus { name: String },
+    /// Destroy a nexus which is not part of the desired state.
+    DestroyNexus { name: String },
+}
+
+/// Outcome of a request to apply a desired state.
+#[derive(Debug, Default, Serialize)]
+pub struct ApplyStateReport {
+    /// Whether the actions were only computed.
+    pub dry_run: bool,
+    /// Actions required to reach the desired state.
+    pub actions: Vec<ApplyAction>,
+    /// Result of the execution of the actions, empty on a dry run.
+    pub report: NodeConfigReport,
+}
+
+impl ApplyAction {
+    /// Kind and name of the object the action applies to, e.g. `pool/p0`.
+    fn object(&self) -> String {
+        match self {
+            Self::CreatePool(pool) => format!("pool/{}", pool.name),
+            Self::SetPoolReservedPct {
+                name, ..
+            } => format!("pool/{name}"),
+            Self::CreateReplica(replica) | Self::ShareReplica(replica) => {
+                format!("replica/{}", replica.name)
+            }
+            Self::ResizeReplica {
+                name, ..
+            }
+            | Self::ShrinkReplica {
+                name, ..
+            }
+            | Self::UnshareReplica {
+                name, ..
+            }
+            | Self::DestroyReplica {
+                name, ..
+            } => format!("replica/{name}"),
+            Self::CreateNexus(nexus) => format!("nexus/{}", nexus.name),
+            Self::AddNexusChild {
+                name, ..
+            }
+            | Self::RemoveNexusChild {
+                name, ..
+            }
+            | Self::SetNexusAnaReporting {
+                name, ..
+            }
+            | Self::SetNexusReadPolicy {
+                name, ..
+            }
+            | Self::ShareNexus {
+                name, ..
+            }
+            | Self::UnshareNexus {
+                name,
+            }
+            | Self::DestroyNexus {
+                name,
+            } => format!("nexus/{name}"),
+        }
+    }
+
+    /// Whether the action destroys an object not part of the desired state.
+    fn is_prune(&self) -> bool {
+        matches!(
+            self,
+            Self::DestroyReplica { .. } | Self::DestroyNexus { .. }
+        )
+    }
+
+    /// Executes the action, recording its outcome in the report.
+    async fn execute(self, report: &mut NodeConfigReport) {
+        let object = self.object();
+        let result = match self {
+            Self::CreatePool(pool) => {
+                import_pool(&pool, report).await;
+                return;
+            }
+            Self::CreateReplica(replica) => {
+                create_replica(&replica, report).await;
+                return;
+            }
+            Self::SetPoolReservedPct {
+                name,
+                reserved_pct,
+            } => match Lvs::lookup(&name) {
+                Some(lvs) => lvs
+                    .set_reserved_pct(reserved_pct)
+                    .map_err(|e| e.to_string()),
+                None => Err("pool not found".to_string()),
+            },
+            Self::ShareReplica(replica) => {
+                match lookup_replica(&replica) {
+                    Some(lvol) => {
+                        apply_replica_share(lvol, &replica, report).await
+                    }
+                    None => report.failed(object, "replica not found"),
+                }
+                return;
+            }
+            Self::CreateNexus(nexus) => {
+                create_nexus(&nexus, report).await;
+                return;
+            }
+            Self::ResizeReplica {
+                uuid,
+                size,
+                ..
+            } => match lookup_lvol(&uuid) {
+                Some(mut lvol) => {
+                    lvol.resize_replica(size).await.map_err(|e| e.to_string())
+                }
+                None => Err("replica not found".to_string()),
+            },
+            Self::ShrinkReplica {
+                size, ..
+            } => Err(format!(
+                "cannot shrink the replica to {size} bytes, \
+                 replicas can only grow"
+            )),
+            Self::UnshareReplica {
+                uuid, ..
+            } => match lookup_lvol(&uuid) {
+                Some(mut lvol) => Pin::new(&mut lvol)
+                    .unshare()
+                    .await
+                    .map_err(|e| e.to_string()),
+                None => Err("replica not found".to_string()),
+            },
+            Self::DestroyReplica {
+                uuid, ..
+            } => match lookup_lvol(&uuid) {
+                Some(lvol) => lvol
+                    .destroy_replica()
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string()),
+                None => Err("replica not found".to_string()),
+            },
+            Self::AddNexusChild {
+                name,
+                uri,
+            } => match nexus_lookup_mut(&name) {
+                Some(n) => n
+                    .add_child(&uri, false)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string()),
+                None => Err("nexus not found".to_string()),
+            },
+            Self::RemoveNexusChild {
+                name,
+                uri,
+            } => match nexus_lookup_mut(&name) {
+                Some(n) => {
+                    n.remove_child(&uri).await.map_err(|e| e.to_string())
+                }
+                None => Err("nexus not found".to_string()),
+            },
+            Self::SetNexusAnaReporting {
+                name,
+                enable,
+            } => match nexus_lookup_mut(&name) {
+                Some(n) => {
+                    n.set_ana_reporting(enable).await.map_err(|e| e.to_string())
+                }
+                None => Err("nexus not found".to_string()),
+            },
+            Self::SetNexusReadPolicy {
+                name,
+                policy,
+            } => match nexus_lookup(&name) {
+                Some(n) => {
+                    n.set_read_policy(policy);
+                    Ok(())
+                }
+                None => Err("nexus not found".to_string()),
+            },
+            Self::ShareNexus {
+                name,
+                share,
+            } => match nexus_lookup_mut(&name) {
+                Some(n) => n
+                    .share_ext(Protocol::Nvmf, None, share.allowed_hosts)
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string()),
+                None => Err("nexus not found".to_string()),
+            },
+            Self::UnshareNexus {
+                name,
+            } => match nexus_lookup_mut(&name) {
+                Some(n) => n.unshare_nexus().await.map_err(|e| e.to_string()),
+                None => Err("nexus not found".to_string()),
+            },
+            Self::DestroyNexus {
+                name,
+            } => match nexus_lookup_mut(&name) {
+                Some(n) => n.destroy().await.map_err(|e| e.to_string()),
+                None => Err("nexus not found".to_string()),
+            },
+        };
+
+        match result {
+            Ok(()) => report.applied(object),
+            Err(error) => report.failed(object, error),
+        }
+    }
+}
+
+impl ApplyStateArgs {
+    /// Computes the actions needed to reach the desired state and, unless
+    /// this is a dry run, executes them in order.
+    /// A failed action does not prevent the following ones from running,
+    /// except for pruning which stops at the first object which could not
+    /// be destroyed.
+    pub async fn apply(self) -> ApplyStateReport {
+        let actions = self.state.plan(self.prune);
+        let mut report = NodeConfigReport::default();
+
+        if !self.dry_run {
+            let mut prune_failed = false;
+            for action in actions.iter().cloned() {
+                let prune = action.is_prune();
+                if prune && prune_failed {
+                    report.failed(
+                        action.object(),
+                        "skipped after a previous prune failure",
+                    );
+                    continue;
+                }
+                let failures = report.failed.len();
+                action.execute(&mut report).await;
+                prune_failed |= prune && report.failed.len() > failures;
+            }
+        }
+
+        ApplyStateReport {
+            dry_run: self.dry_run,
+            actions,
+            report,
+        }
+    }
+}
+
+impl NodeConfig {
+    /// Computes the actions needed to converge the current state of the node
+    /// towards this configuration.
+    /// Nexuses are pruned first so that their children can be released,
+    /// replicas are pruned last once no nexus refers to them anymore.
+    /// Replicas still referred to by a nexus are never pruned.
+    pub fn plan(&self, prune: bool) -> Vec<ApplyAction> {
+        let mut actions = Vec::new();
+
+        if prune {
+            actions.extend(
+                nexus_iter()
+                    .filter(|n| !self.nexuses.iter().any(|d| d.name == n.name))
+                    .map(|n| ApplyAction::DestroyNexus {
+                        name: n.name.clone(),
+                    }),
+            );
+        }
+
+        for pool in &self.pools {
+            match Lvs::lookup(&pool.name) {
+                None => actions.push(ApplyAction::CreatePool(pool.clone())),
+                Some(lvs)
+                    if lvs.reserved_pct_override() != pool.reserved_pct =>
+                {
+                    actions.push(ApplyAction::SetPoolReservedPct {
+                        name: pool.name.clone(),
+                        reserved_pct: pool.reserved_pct,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        for replica in &self.replicas {
+            let Some(lvol) = lookup_replica(replica) else {
+                actions.push(ApplyAction::CreateReplica(replica.clone()));
+                continue;
+            };
+            // the size of a replica is rounded up to the cluster size
+            let cluster = lvol.lvs().blob_cluster_size();
+            if replica.size > lvol.size() {
+                actions.push(ApplyAction::ResizeReplica {
+                    name: replica.name.clone(),
+                    uuid: replica.uuid.clone(),
+                    size: replica.size,
+                });
+            } else if replica.size.div_ceil(cluster) * cluster < lvol.size() {
+                actions.push(ApplyAction::ShrinkReplica {
+                    name: replica.name.clone(),
+                    uuid: replica.uuid.clone(),
+                    size: replica.size,
+                });
+            }
+            match (&replica.share, lvol.share_protocol()) {
+                (Some(share), Protocol::Nvmf)
+                    if same_hosts(
+                        &share.allowed_hosts,
+                        &lvol.nvmf_allowed_hosts(),
+                    ) => {}
+                (Some(_), _) => {
+                    actions.push(ApplyAction::ShareReplica(replica.clone()))
+                }
+                (None, Protocol::Nvmf) => {
+                    actions.push(ApplyAction::UnshareReplica {
+                        name: replica.name.clone(),
+                        uuid: replica.uuid.clone(),
+                    })
+                }
+                (None, Protocol::Off) => {}
+            }
+        }
+
+        for nexus in &self.nexuses {
+            let Some(n) = nexus_lookup(&nexus.name) else {
+                actions.push(ApplyAction::CreateNexus(nexus.clone()));
+                continue;
+            };
+            let current = n.child_uris();
+            actions.extend(
+                current
+                    .iter()
+                    .filter(|uri| !nexus.children.contains(uri))
+                    .map(|uri| ApplyAction::RemoveNexusChild {
+                        name: nexus.name.clone(),
+                        uri: uri.clone(),
+                    }),
+            );
+            actions.extend(
+                nexus
+                    .children
+                    .iter()
+                    .filter(|uri| !current.contains(uri))
+                    .map(|uri| ApplyAction::AddNexusChild {
+                        name: nexus.name.clone(),
+                        uri: uri.clone(),
+                    }),
+            );
+            if let Some(enable) =
+                nexus.ana_reporting.filter(|e| *e != n.ana_reporting())
+            {
+                actions.push(ApplyAction::SetNexusAnaReporting {
+                    name: nexus.name.clone(),
+                    enable,
+                });
+            }
+            if let Some(policy) =
+                nexus.read_policy.filter(|p| *p != n.read_policy())
+            {
+                actions.push(ApplyAction::SetNexusReadPolicy {
+                    name: nexus.name.clone(),
+                    policy,
+                });
+            }
+            match (&nexus.share, n.shared()) {
+                (Some(share), Some(Protocol::Nvmf))
+                    if same_hosts(&share.allowed_hosts, &n.allowed_hosts()) => {
+                }
+                (Some(share), _) => actions.push(ApplyAction::ShareNexus {
+                    name: nexus.name.clone(),
+                    share: share.clone(),
+                }),
+                (None, Some(_)) => actions.push(ApplyAction::UnshareNexus {
+                    name: nexus.name.clone(),
+                }),
+                (None, None) => {}
+            }
+        }
+
+        if prune {
+            actions.extend(
+                Lvs::iter()
+                    .flat_map(|lvs| {
+                        lvs.lvols()
+                            .map(|lvols| lvols.collect::<Vec<_>>())
+                            .unwrap_or_default()
+                    })
+                    .filter(|lvol| !lvol.is_snapshot())
+                    .filter(|lvol| {
+                        !self.replicas.iter().any(|r| r.uuid == lvol.uuid())
+                    })
+                    .filter(|lvol| !self.nexus_refers_to(&lvol.uuid()))
+                    .map(|lvol| ApplyAction::DestroyReplica {
+                        name: lvol.name(),
+                        uuid: lvol.uuid(),
+                    }),
+            );
+        }
+
+        actions
+    }
+
+    /// Checks whether a nexus kept by this configuration, either existing or
+    /// to be created, has a child referring to the replica with this uuid.
+    fn nexus_refers_to(&self, uuid: &str) -> bool {
+        let kept = |name: &str| self.nexuses.iter().any(|n| n.name == name);
+        nexus_iter()
+            .filter(|n| kept(&n.name))
+            .any(|n| n.children_iter().any(|c| c.uri().contains(uuid)))
+            || self
+                .nexuses
+                .iter()
+                .any(|n| n.children.iter().any(|uri| uri.contains(uuid)))
+    }
+}
+
+/// Looks up a replica lvol by its uuid.
+fn lookup_lvol(uuid: &str) -> Option<Lvol> {
+    UntypedBdev::lookup_by_uuid_str(uuid).and_then(Lvol::ok_from)
+}
+
+/// Creates a replica on its pool and shares it if required.
+async fn create_replica(replica: &NodeReplica, report: &mut NodeConfigReport) {
+    let object = format!("replica/{}", replica.name);
+    let Some(lvs) = Lvs::lookup(&replica.pool) else {
+        report.failed(object, format!("pool {} not found", replica.pool));
+        return;
+    };
+
+    match lvs
+        .create_lvol(
+            &replica.name,
+            replica.size,
+            Some(&replica.uuid),
+            replica.thin,
+            replica.entity_id.clone(),
+        )
+        .await
+    {
+        Ok(lvol) if replica.share.is_some() => {
+            apply_replica_share(lvol, replica, report).await
+        }
+        Ok(_) => report.applied(object),
+        Err(error) => report.failed(object, error),
+    }
+}
@@ -21,15 +21,24 @@ use spdk_rs::libspdk::{
 };
 
 use crate::{
-    jsonrpc::{jsonrpc_register, Code, RpcErrorCode},
-    subsys::config::opts::{
-        BdevOpts,
-        GetOpts,
-        IoBufOpts,
-        NexusOpts,
-        NvmeBdevOpts,
-        NvmfTgtConfig,
-        PosixSocketOpts,
+    jsonrpc::{jsonrpc_register, Code, JsonRpcError, RpcErrorCode},
+    subsys::{
+        config::{
+            apply::ApplyStateArgs,
+            node::NodeConfig,
+            opts::{
+                AccelOpts,
+                AdminOpts,
+                BdevOpts,
+                GetOpts,
+                IoBufOpts,
+                NexusOpts,
+                NvmeBdevOpts,
+                NvmfTgtConfig,
+                PosixSocketOpts,
+            },
+        },
+        StartupProgress,
     },
 };
 
@@ -42,8 +51,11 @@ impl RpcErrorCode for Error {
     }
 }
 
+pub(crate) mod apply;
+pub(crate) mod node;
 pub(crate) mod opts;
 pub(crate) mod pool;
+pub(crate) mod startup;
 
 pub static CONFIG: OnceCell<Config> = OnceCell::new();
 
@@ -78,6 +90,32 @@ impl ConfigSubsystem {
             f.boxed_local()
         });
 
+        // export the pools, replicas and nexuses of this node as a
+        // declarative document which can be imported on a replacement node
+        jsonrpc_register::<(), _, _, JsonRpcError>(
+            "mayastor_node_config_export",
+            |_| async move { Ok(NodeConfig::capture()) }.boxed_local(),
+        );
+
+        jsonrpc_register::<NodeConfig, _, _, JsonRpcError>(
+            "mayastor_node_config_import",
+            |cfg| async move { Ok(cfg.import().await) }.boxed_local(),
+        );
+
+        // reconcile the node with a desired-state document, creating,
+        // updating and optionally pruning pools, replicas and nexuses
+        jsonrpc_register::<ApplyStateArgs, _, _, JsonRpcError>(
+            "mayastor_node_config_apply",
+            |args| async move { Ok(args.apply().await) }.boxed_local(),
+        );
+
+        // progress of the import of the pools and of the re-share of their
+        // replicas at startup
+        jsonrpc_register::<(), _, _, JsonRpcError>(
+            "mayastor_startup_progress",
+            |_| async move { Ok(StartupProgress::get()) }.boxed_local(),
+        );
+
         unsafe { spdk_subsystem_init_next(0) };
     }
 
@@ -149,6 +187,10 @@ pub struct Config {
     pub socket_opts: PosixSocketOpts,
     /// iobuf specific options
     pub iobuf_opts: IoBufOpts,
+    /// accel framework options
+    pub accel_opts: AccelOpts,
+    /// limits of the executor of the control-plane operations
+    pub admin_opts: AdminOpts,
     /// Environment Abstraction Layer options.
     pub eal_opts: EalOpts,
 }
@@ -214,6 +256,8 @@ impl Config {
             nexus_opts: self.nexus_opts.get(),
             socket_opts: self.socket_opts.get(),
             iobuf_opts: self.iobuf_opts.get(),
+            accel_opts: self.accel_opts.get(),
+            admin_opts: self.admin_opts.get(),
             eal_opts: self.eal_opts.clone(),
         }
     }
@@ -245,6 +289,7 @@ impl Config {
         assert!(self.bdev_opts.set());
         assert!(self.socket_opts.set());
         assert!(self.iobuf_opts.set());
+        assert!(self.accel_opts.set());
 
         info!("{:#?}", self);
     }
@@ -0,0 +1,409 @@
+//! Declarative description of the pools, replicas and nexuses hosted by a
+//! node, together with their share settings.
+//!
+//! The document is captured from the running io-engine and can be re-applied
+//! on a replacement node which is attached to the same disks: the pools are
+//! imported, the replicas found on them are re-shared with the same allowed
+//! hosts and the nexuses are re-created and re-published.
+use serde::{Deserialize, Serialize};
+use std::pin::Pin;
+
+use crate::{
+    bdev::nexus::{
+        nexus_create_v2,
+        nexus_iter,
+        nexus_lookup,
+        nexus_lookup_mut,
+        Nexus,
+        NexusNvmeParams,
+        NexusReadPolicy,
+    },
+    core::{
+        LogicalVolume,
+        NvmfShareProps,
+        Protocol,
+        Share,
+        UntypedBdev,
+        UpdateProps,
+    },
+    lvs::{Lvol, Lvs, LvsLvol},
+    pool_backend::{PoolArgs, PoolBackend},
+};
+
+/// Declarative configuration of a node.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NodeConfig {
+    /// Pools hosted by the node.
+    pub pools: Vec<NodePool>,
+    /// Replicas living on the pools of the node.
+    pub replicas: Vec<NodeReplica>,
+    /// Nexuses running on the node.
+    pub nexuses: Vec<NodeNexus>,
+}
+
+/// Pool definition.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NodePool {
+    /// Name of the pool.
+    pub name: String,
+    /// UUID of the pool.
+    pub uuid: String,
+    /// Disk URIs backing the pool.
+    pub disks: Vec<String>,
+    /// Share of the capacity reserved for the system overhead, in percent,
+    /// when it overrides the default of the node.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reserved_pct: Option<u8>,
+}
+
+/// Replica definition.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NodeReplica {
+    /// Name of the replica.
+    pub name: String,
+    /// UUID of the replica.
+    pub uuid: String,
+    /// Name of the pool the replica lives on.
+    pub pool: String,
+    /// Size of the replica in bytes.
+    pub size: u64,
+    /// Thin provisioned replica.
+    pub thin: bool,
+    /// Identifier of the entity (volume) owning the replica.
+    pub entity_id: Option<String>,
+    /// NVMf share settings, `None` when not shared.
+    pub share: Option<NodeShare>,
+}
+
+/// Nexus definition.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NodeNexus {
+    /// Name of the nexus.
+    pub name: String,
+    /// UUID of the nexus.
+    pub uuid: String,
+    /// Requested size of the nexus in bytes.
+    pub size: u64,
+    /// Child URIs.
+    pub children: Vec<String>,
+    /// Minimum NVMe controller ID.
+    pub min_cntlid: u16,
+    /// Maximum NVMe controller ID.
+    pub max_cntlid: u16,
+    /// NVMe reservation key for the children.
+    pub resv_key: u64,
+    /// Report ANA on the NVMf share, `None` for the configured default.
+    pub ana_reporting: Option<bool>,
+    /// Policy the children to read from are selected by, `None` for the
+    /// configured default.
+    pub read_policy: Option<NexusReadPolicy>,
+    /// NVMf share settings, `None` when not published.
+    pub share: Option<NodeShare>,
+}
+
+/// NVMf share settings of a replica or a nexus.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NodeShare {
+    /// Hosts allowed to connect, any host when empty.
+    pub allowed_hosts: Vec<String>,
+}
+
+/// Outcome of applying a `NodeConfig`.
+#[derive(Debug, Default, Serialize)]
+pub struct NodeConfigReport {
+    /// Objects which have been (re)configured.
+    pub applied: Vec<String>,
+    /// Objects which were already in the desired state.
+    pub unchanged: Vec<String>,
+    /// Objects which failed to be configured.
+    pub failed: Vec<NodeConfigFailure>,
+}
+
+/// Failure to configure an object.
+#[derive(Debug, Serialize)]
+pub struct NodeConfigFailure {
+    /// Object kind and name, e.g. `pool/p0`.
+    pub object: String,
+    /// Error description.
+    pub error: String,
+}
+
+impl NodeConfigReport {
+    pub(super) fn applied(&mut self, object: String) {
+        info!("Node config: {object} applied");
+        self.applied.push(object);
+    }
+
+    pub(super) fn unchanged(&mut self, object: String) {
+        debug!("Node config: {object} unchanged");
+        self.unchanged.push(object);
+    }
+
+    pub(super) fn failed<E: ToString>(&mut self, object: String, error: E) {
+        let error = error.to_string();
+        error!("Node config: failed to apply {object}: {error}");
+        self.failed.push(NodeConfigFailure {
+            object,
+            error,
+        });
+    }
+}
+
+impl From<Lvs> for NodePool {
+    fn from(lvs: Lvs) -> Self {
+        let base = lvs.base_bdev();
+        Self {
+            name: lvs.name().to_string(),
+            uuid: lvs.uuid(),
+            disks: vec![base
+                .bdev_uri_str()
+                .unwrap_or_else(|| base.name().to_string())],
+            reserved_pct: lvs.reserved_pct_override(),
+        }
+    }
+}
+
+impl From<&NodePool> for PoolArgs {
+    fn from(pool: &NodePool) -> Self {
+        Self {
+            name: pool.name.clone(),
+            disks: pool.disks.clone(),
+            uuid: Some(pool.uuid.clone()).filter(|u| !u.is_empty()),
+            cluster_size: None,
+            reserved_pct: pool.reserved_pct,
+            backend: PoolBackend::Lvs,
+        }
+    }
+}
+
+impl From<Lvol> for NodeReplica {
+    fn from(lvol: Lvol) -> Self {
+        let share = match lvol.share_protocol() {
+            Protocol::Nvmf => Some(NodeShare {
+                allowed_hosts: lvol.nvmf_allowed_hosts(),
+            }),
+            Protocol::Off => None,
+        };
+        Self {
+            name: lvol.name(),
+            uuid: lvol.uuid(),
+            pool: lvol.pool_name(),
+            size: lvol.size(),
+            thin: lvol.is_thin(),
+            entity_id: lvol.entity_id(),
+            share,
+        }
+    }
+}
+
+impl From<&Nexus<'_>> for NodeNexus {
+    fn from(nexus: &Nexus<'_>) -> Self {
+        let share = match nexus.shared() {
+            Some(Protocol::Nvmf) => Some(NodeShare {
+                allowed_hosts: nexus.allowed_hosts(),
+            }),
+            _ => None,
+        };
+        Self {
+            name: nexus.name.clone(),
+            uuid: nexus.uuid().to_string(),
+            size: nexus.req_size(),
+            children: nexus.child_uris(),
+            min_cntlid: nexus.nvme_params.min_cntlid,
+            max_cntlid: nexus.nvme_params.max_cntlid,
+            resv_key: nexus.nvme_params.resv_key,
+            ana_reporting: nexus.nvme_params.ana_reporting,
+            read_policy: Some(nexus.read_policy()),
+            share,
+        }
+    }
+}
+
+impl NodeNexus {
+    /// NVMe parameters to create the nexus with.
+    pub(super) fn nvme_params(&self) -> NexusNvmeParams {
+        let mut params = NexusNvmeParams::default();
+        params.set_min_cntlid(self.min_cntlid);
+        params.set_max_cntlid(self.max_cntlid);
+        params.set_resv_key(self.resv_key);
+        params.set_ana_reporting(self.ana_reporting);
+        params.set_read_policy(self.read_policy);
+        params
+    }
+}
+
+impl NodeConfig {
+    /// Capture the current configuration of the node.
+    pub fn capture() -> Self {
+        let pools = Lvs::iter().map(NodePool::from).collect();
+        let replicas = Lvs::iter()
+            .flat_map(|lvs| {
+                lvs.lvols()
+                    .map(|lvols| lvols.collect::<Vec<_>>())
+                    .unwrap_or_default()
+            })
+            .filter(|lvol| !lvol.is_snapshot())
+            .map(NodeReplica::from)
+            .collect();
+        let nexuses = nexus_iter().map(NodeNexus::from).collect();
+
+        Self {
+            pools,
+            replicas,
+            nexuses,
+        }
+    }
+
+    /// Import the configuration on this node: pools are imported from their
+    /// disks, replicas are re-shared and nexuses are re-created.
+    /// Objects which already exist are left untouched, and a failure to
+    /// import one object does not prevent the others from being imported.
+    pub async fn import(self) -> NodeConfigReport {
+        let mut report = NodeConfigReport::default();
+
+        for pool in &self.pools {
+            import_pool(pool, &mut report).await;
+        }
+        for replica in &self.replicas {
+            match lookup_replica(replica) {
+                Some(lvol) => {
+                    apply_replica_share(lvol, replica, &mut report).await
+                }
+                None => report.failed(
+                    format!("replica/{}", replica.name),
+                    "replica not found on the imported pools",
+                ),
+            }
+        }
+        for nexus in &self.nexuses {
+            create_nexus(nexus, &mut report).await;
+        }
+
+        report
+    }
+}
+
+/// Imports a pool from its disks, unless the pool is already present.
+pub(super) async fn import_pool(
+    pool: &NodePool,
+    report: &mut NodeConfigReport,
+) {
+    let object = format!("pool/{}", pool.name);
+    if Lvs::lookup(&pool.name).is_some() {
+        report.unchanged(object);
+        return;
+    }
+    match Lvs::import_from_args(pool.into()).await {
+        Ok(_) => report.applied(object),
+        Err(error) => report.failed(object, error),
+    }
+}
+
+/// Compares two lists of hosts regardless of their order.
+pub(super) fn same_hosts(a: &[String], b: &[String]) -> bool {
+    let mut a = a.to_vec();
+    let mut b = b.to_vec();
+    a.sort();
+    b.sort();
+    a == b
+}
+
+/// Looks up the lvol of a replica.
+pub(super) fn lookup_replica(replica: &NodeReplica) -> Option<Lvol> {
+    UntypedBdev::lookup_by_uuid_str(&replica.uuid).and_then(Lvol::ok_from)
+}
+
+/// Shares a replica or updates its allowed hosts as described by the
+/// configuration. Replicas which are not configured as shared are left as is.
+pub(super) async fn apply_replica_share(
+    mut lvol: Lvol,
+    replica: &NodeReplica,
+    report: &mut NodeConfigReport,
+) {
+    let object = format!("replica/{}", replica.name);
+    let Some(share) = &replica.share else {
+        report.unchanged(object);
+        return;
+    };
+
+    let result = match lvol.share_protocol() {
+        Protocol::Nvmf => {
+            if same_hosts(&share.allowed_hosts, &lvol.nvmf_allowed_hosts()) {
+                report.unchanged(object);
+                return;
+            }
+            Pin::new(&mut lvol)
+                .update_properties(
+                    UpdateProps::new()
+                        .with_allowed_hosts(share.allowed_hosts.clone()),
+                )
+                .await
+                .map_err(|e| e.to_string())
+        }
+        Protocol::Off => match Share::create_ptpl(&lvol) {
+            Ok(ptpl) => {
+                let props = NvmfShareProps::new()
+                    .with_allowed_hosts(share.allowed_hosts.clone())
+                    .with_ptpl(ptpl);
+                Pin::new(&mut lvol)
+                    .share_nvmf(Some(props))
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            }
+            Err(error) => Err(error.to_string()),
+        },
+    };
+
+    match result {
+        Ok(()) => report.applied(object),
+        Err(error) => report.failed(object, error),
+    }
+}
+
+/// Creates and publishes a nexus, unless a nexus with the same name exists.
+pub(super) async fn create_nexus(
+    nexus: &NodeNexus,
+    report: &mut NodeConfigReport,
+) {
+    let object = format!("nexus/{}", nexus.name);
+    if nexus_lookup(&nexus.name).is_some() {
+        report.unchanged(object);
+        return;
+    }
+
+    if let Err(error) = nexus_create_v2(
+        &nexus.name,
+        nexus.size,
+        &nexus.uuid,
+        nexus.nvme_params(),
+        &nexus.children,
+        None,
+    )
+    .await
+    {
+        report.failed(object, error);
+        return;
+    }
+
+    if let Some(share) = &nexus.share {
+        let Some(n) = nexus_lookup_mut(&nexus.name) else {
+            report.failed(object, "nexus disappeared after creation");
+            return;
+        };
+        if let Err(error) = n
+            .share_ext(Protocol::Nvmf, None, share.allowed_hosts.clone())
+            .await
+        {
+            report.failed(object, error);
+            return;
+        }
+    }
+
+    report.applied(object);
+}
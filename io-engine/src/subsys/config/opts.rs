@@ -3,13 +3,17 @@
 //! around. If the structures change, we will know about it because we use the
 //! from trait, and we are not allowed to skip or use different types.
 
-use serde::{Deserialize, Serialize};
+use rand::Rng;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
 
 use spdk_rs::{
     ffihelper::copy_str_with_null,
     libspdk::{
+        accel_dsa_enable_probe,
+        accel_iaa_enable_probe,
         bdev_nvme_get_opts,
         bdev_nvme_set_opts,
+        spdk_accel_assign_opc,
         spdk_bdev_get_opts,
         spdk_bdev_nvme_opts,
         spdk_bdev_opts,
@@ -22,19 +26,40 @@ use spdk_rs::{
         spdk_sock_impl_get_opts,
         spdk_sock_impl_opts,
         spdk_sock_impl_set_opts,
+        SPDK_NVMF_DHCHAP_DHGROUP_2048,
+        SPDK_NVMF_DHCHAP_DHGROUP_3072,
+        SPDK_NVMF_DHCHAP_DHGROUP_4096,
+        SPDK_NVMF_DHCHAP_DHGROUP_6144,
+        SPDK_NVMF_DHCHAP_DHGROUP_8192,
+        SPDK_NVMF_DHCHAP_DHGROUP_NULL,
+        SPDK_NVMF_DHCHAP_HASH_SHA256,
+        SPDK_NVMF_DHCHAP_HASH_SHA384,
+        SPDK_NVMF_DHCHAP_HASH_SHA512,
     },
     struct_size_init,
 };
 
 use std::{
+    collections::BTreeMap,
     convert::TryFrom,
     fmt::{Debug, Display},
     mem::zeroed,
     ptr::null_mut,
     str::FromStr,
+    time::Duration,
 };
 
-use crate::core::MayastorEnvironment;
+use crate::{
+    bdev::nexus::{
+        NexusReadPolicy,
+        NexusWritePolicy,
+        NVME_MAX_CNTLID,
+        NVME_MIN_CNTLID,
+    },
+    core::{accel::accel_opcode, MayastorEnvironment},
+    ffihelper::IntoCString,
+    subsys::{validate_nqn_prefix, NvmfListener},
+};
 
 pub trait GetOpts {
     fn get(&self) -> Self;
@@ -52,9 +77,67 @@ pub struct NexusOpts {
     pub nvmf_discovery_enable: bool,
     /// nvmf port over which we export
     pub nvmf_nexus_port: u16,
-    /// NOTE: we do not (yet) differentiate between
-    /// the nexus and replica nvmf target
+    /// nvmf port over which we export the replicas, and the nexuses too unless
+    /// a separate replica target is configured
     pub nvmf_replica_port: u16,
+    /// prefix of the NQNs of the subsystems, the openebs one when not set
+    #[serde(deserialize_with = "deserialize_nqn_prefix")]
+    pub nvmf_nqn_prefix: Option<String>,
+    /// range of the ports (e.g. "8430-8449") the subsystems are each given a
+    /// port of their own from, for their listeners without an explicit port
+    pub nvmf_port_range: Option<String>,
+    /// file the ports given to the subsystems are kept in across restarts
+    pub nvmf_port_state: Option<String>,
+    /// range of the controller IDs (e.g. "1-1000") of the subsystems without
+    /// an explicit range of their own, so that the nodes of a cluster given
+    /// disjoint ranges never give out the same controller ID
+    pub nvmf_cntlid_range: Option<String>,
+    /// keep alive timeout in milliseconds given to the controllers of the
+    /// nexuses, rather than the one the hosts ask for, unless set per share
+    pub nvmf_nexus_kato_ms: Option<u32>,
+    /// keep alive timeout in milliseconds given to the controllers of the
+    /// replicas, rather than the one the nexuses ask for, unless set per
+    /// share
+    pub nvmf_replica_kato_ms: Option<u32>,
+    /// grace period in milliseconds after a keep alive timeout on a replica,
+    /// past which the reservations of the host are released unless it
+    /// reconnected; the reservations are kept when not set
+    pub nvmf_kato_resv_grace_ms: Option<u64>,
+    /// report Asymmetric Namespace Access (ANA) on the shares of the
+    /// nexuses, unless set per nexus; enabled by the NEXUS_NVMF_ANA_ENABLE=1
+    /// environment variable by default
+    pub nvmf_ana_reporting: bool,
+    /// policy the children to read from are selected by, unless set per
+    /// nexus
+    pub nexus_read_policy: NexusReadPolicy,
+    /// policy the writes are acknowledged by
+    pub nexus_write_policy: NexusWritePolicy,
+    /// time in milliseconds a child may take to complete a write
+    /// acknowledged under the quorum write policy, past which it is faulted
+    pub nexus_write_laggard_timeout_ms: u64,
+    /// time in milliseconds a child may take to complete an I/O, past which
+    /// it is faulted and the I/O retried on the other children, unless set
+    /// per nexus; the child I/Os are not timed when not set
+    pub nexus_child_io_timeout_ms: Option<u64>,
+}
+
+/// Deserializes an NQN prefix, rejecting one which is not of the form
+/// `nqn.yyyy-mm.reverse.domain`.
+fn deserialize_nqn_prefix<'de, D>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let prefix = Option::<String>::deserialize(deserializer)?;
+    if let Some(prefix) = &prefix {
+        validate_nqn_prefix(prefix).map_err(|reason| {
+            D::Error::custom(format!(
+                "invalid nvmf_nqn_prefix '{prefix}': {reason}"
+            ))
+        })?;
+    }
+    Ok(prefix)
 }
 
 /// Default nvmf port used for replicas.
@@ -65,11 +148,28 @@ const NVMF_PORT_NEXUS: u16 = 4421;
 
 impl Default for NexusOpts {
     fn default() -> Self {
+        let env = MayastorEnvironment::global_or_default();
         Self {
             nvmf_enable: true,
             nvmf_discovery_enable: true,
-            nvmf_nexus_port: NVMF_PORT_NEXUS,
-            nvmf_replica_port: NVMF_PORT_REPLICA,
+            nvmf_nexus_port: env.nvmf_nexus_port.unwrap_or(NVMF_PORT_NEXUS),
+            nvmf_replica_port: env
+                .nvmf_replica_port
+                .unwrap_or(NVMF_PORT_REPLICA),
+            nvmf_nqn_prefix: env.nqn_prefix,
+            nvmf_port_range: env.nvmf_port_range,
+            nvmf_port_state: env.nvmf_port_state,
+            nvmf_cntlid_range: env.nvmf_cntlid_range,
+            nvmf_nexus_kato_ms: env.nvmf_nexus_kato_ms,
+            nvmf_replica_kato_ms: env.nvmf_replica_kato_ms,
+            nvmf_kato_resv_grace_ms: env.nvmf_kato_resv_grace_ms,
+            nvmf_ana_reporting: std::env::var("NEXUS_NVMF_ANA_ENABLE")
+                .as_deref()
+                == Ok("1"),
+            nexus_read_policy: NexusReadPolicy::default(),
+            nexus_write_policy: NexusWritePolicy::default(),
+            nexus_write_laggard_timeout_ms: 5000,
+            nexus_child_io_timeout_ms: None,
         }
     }
 }
@@ -95,12 +195,47 @@ pub struct NvmfTgtConfig {
     pub crdt: [u16; TARGET_CRDT_LEN],
     /// TCP transport options
     pub opts: NvmfTcpTransportOpts,
+    /// RDMA transport options
+    pub rdma_opts: NvmfRdmaTransportOpts,
     /// NVMF target interface (ip, mac, name or subnet).
     pub interface: Option<String>,
-    /// Enable RDMA for NVMF target or not
+    /// Enable RDMA for NVMF target or not. With a separate replica target,
+    /// this only applies to the target exporting the nexuses
     pub rdma: Option<bool>,
+    /// Cores the poll groups of the target run on (e.g. "0-1,4"), all the
+    /// reactor cores when not set
+    pub cores: Option<String>,
+    /// Separate target for the replica traffic. When set, the target above
+    /// only exports the nexuses
+    pub replica_target: Option<NvmfReplicaTgtConfig>,
+    /// Retry policy of the subsystem state changes which find the subsystem
+    /// busy
+    pub busy_retry: NvmfBusyRetryOpts,
+    /// Max number of subsystems changing state concurrently when the state
+    /// of many subsystems changes at once, e.g. on shutdown
+    pub state_change_concurrency: usize,
+    /// Hosts and referrals of the discovery subsystems
+    pub discovery: NvmfDiscoveryConfig,
+    /// Command Retry Delays set in the failed completions of the nexuses and
+    /// of the replicas
+    pub crd: NvmfCrdPolicies,
 }
 
+/// DH-HMAC-CHAP digests the targets negotiate with the hosts which must
+/// authenticate.
+const DHCHAP_DIGESTS: u32 = (1 << SPDK_NVMF_DHCHAP_HASH_SHA256)
+    | (1 << SPDK_NVMF_DHCHAP_HASH_SHA384)
+    | (1 << SPDK_NVMF_DHCHAP_HASH_SHA512);
+
+/// DH-HMAC-CHAP Diffie-Hellman groups the targets negotiate with the hosts
+/// which must authenticate.
+const DHCHAP_DHGROUPS: u32 = (1 << SPDK_NVMF_DHCHAP_DHGROUP_NULL)
+    | (1 << SPDK_NVMF_DHCHAP_DHGROUP_2048)
+    | (1 << SPDK_NVMF_DHCHAP_DHGROUP_3072)
+    | (1 << SPDK_NVMF_DHCHAP_DHGROUP_4096)
+    | (1 << SPDK_NVMF_DHCHAP_DHGROUP_6144)
+    | (1 << SPDK_NVMF_DHCHAP_DHGROUP_8192);
+
 impl From<NvmfTgtConfig> for Box<spdk_nvmf_target_opts> {
     fn from(o: NvmfTgtConfig) -> Self {
         let mut out = struct_size_init!(
@@ -109,8 +244,8 @@ impl From<NvmfTgtConfig> for Box<spdk_nvmf_target_opts> {
                 max_subsystems: o.max_namespaces,
                 crdt: o.crdt,
                 discovery_filter: 0,
-                dhchap_digests: 0,
-                dhchap_dhgroups: 0,
+                dhchap_digests: DHCHAP_DIGESTS,
+                dhchap_dhgroups: DHCHAP_DHGROUPS,
             },
             size
         );
@@ -127,8 +262,20 @@ impl Default for NvmfTgtConfig {
             max_namespaces: 2048,
             crdt: args.nvmf_tgt_crdt,
             opts: NvmfTcpTransportOpts::default(),
+            rdma_opts: NvmfRdmaTransportOpts::default(),
             interface: None,
-            rdma: None,
+            rdma: args.rdma.then_some(true),
+            cores: args.nvmf_tgt_cores.clone(),
+            replica_target: args.nvmf_replica_tgt_cores.clone().map(|cores| {
+                NvmfReplicaTgtConfig {
+                    cores: Some(cores),
+                    ..Default::default()
+                }
+            }),
+            busy_retry: NvmfBusyRetryOpts::default(),
+            state_change_concurrency: 64,
+            discovery: NvmfDiscoveryConfig::default(),
+            crd: NvmfCrdPolicies::default(),
         }
     }
 }
@@ -139,6 +286,286 @@ impl GetOpts for NvmfTgtConfig {
     }
 }
 
+impl NvmfTgtConfig {
+    /// Options of the separate replica target, if any. The command retry
+    /// delays are shared with the main target.
+    pub fn replica_tgt_opts(&self) -> Option<Box<spdk_nvmf_target_opts>> {
+        self.replica_target.as_ref().map(|r| {
+            NvmfTgtConfig {
+                name: r.name.clone(),
+                max_namespaces: r.max_namespaces,
+                opts: r.opts,
+                rdma_opts: r.rdma_opts,
+                rdma: r.rdma,
+                cores: r.cores.clone(),
+                replica_target: None,
+                ..self.clone()
+            }
+            .into()
+        })
+    }
+}
+
+/// Settings of the separate NVMF target for the replica traffic, so that it
+/// can be isolated from the host facing nexus traffic.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NvmfReplicaTgtConfig {
+    /// name of the target to be created
+    pub name: String,
+    /// the max number of namespaces this target should allow for
+    pub max_namespaces: u32,
+    /// TCP transport options
+    pub opts: NvmfTcpTransportOpts,
+    /// RDMA transport options
+    pub rdma_opts: NvmfRdmaTransportOpts,
+    /// Enable RDMA for the replica target or not
+    pub rdma: Option<bool>,
+    /// Cores the poll groups of the target run on, all the reactor cores
+    /// when not set
+    pub cores: Option<String>,
+}
+
+impl Default for NvmfReplicaTgtConfig {
+    fn default() -> Self {
+        Self {
+            name: "mayastor_replica_target".to_string(),
+            max_namespaces: 2048,
+            opts: NvmfTcpTransportOpts::default(),
+            rdma_opts: NvmfRdmaTransportOpts::default(),
+            rdma: None,
+            cores: None,
+        }
+    }
+}
+
+/// Settings of the discovery subsystems.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NvmfDiscoveryConfig {
+    /// Only allow the hosts allowed on at least one subsystem on the
+    /// discovery subsystems, rather than any host
+    pub restrict_hosts: bool,
+    /// Discovery services of other nodes the hosts are referred to by the
+    /// discovery service of the host facing target; their port is the port
+    /// of the nexuses when not given
+    pub referrals: Vec<NvmfListener>,
+}
+
+/// Command Retry Delay (CRD) set in the failed completions of a type of
+/// subsystems, when the target set one. A CRD selects one of the delays of
+/// `crdt`, from 1 to 3, or none when 0. A class of errors without a CRD of
+/// its own keeps the CRD set by the target, as do the completions whose CRD
+/// is not the one the policy applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NvmfCrdPolicy {
+    /// set the CRDs at all; when disabled, the CRD is cleared so that the
+    /// hosts retry at once, as some of them mishandle it
+    pub enable: bool,
+    /// CRD set by the target which the policy changes, any when none
+    pub target_crd: Option<u8>,
+    /// CRD of the errors other than those below
+    pub default: Option<u8>,
+    /// CRD of the reservation conflicts
+    pub reservation_conflict: Option<u8>,
+    /// CRD of the errors for lack of space
+    pub no_space: Option<u8>,
+}
+
+impl Default for NvmfCrdPolicy {
+    fn default() -> Self {
+        Self {
+            enable: true,
+            target_crd: None,
+            default: None,
+            reservation_conflict: None,
+            no_space: None,
+        }
+    }
+}
+
+impl NvmfCrdPolicy {
+    /// Policy of the nexuses: the errors the hosts must wait longer for,
+    /// e.g. for a replica to be retired, get the second delay.
+    pub fn nexus() -> Self {
+        Self {
+            reservation_conflict: Some(2),
+            no_space: Some(2),
+            ..Default::default()
+        }
+    }
+
+    /// Policy of the replicas: all the errors the target gives the first
+    /// delay get the third one.
+    pub fn replica() -> Self {
+        Self {
+            target_crd: Some(1),
+            default: Some(3),
+            reservation_conflict: Some(3),
+            no_space: Some(3),
+            ..Default::default()
+        }
+    }
+
+    /// CRD of a failed completion the target set the given CRD in, at most
+    /// 3.
+    pub fn crd(
+        &self,
+        crd: u16,
+        reservation_conflict: bool,
+        no_space: bool,
+    ) -> u16 {
+        if !self.enable {
+            return 0;
+        }
+        if crd == 0 || self.target_crd.map_or(false, |t| u16::from(t) != crd) {
+            return crd;
+        }
+        let policy = if reservation_conflict {
+            self.reservation_conflict
+        } else if no_space {
+            self.no_space
+        } else {
+            self.default
+        };
+        policy.map_or(crd, |crd| u16::from(crd.min(3)))
+    }
+}
+
+/// Command Retry Delay policies of the nexuses and of the replicas.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NvmfCrdPolicies {
+    pub nexus: NvmfCrdPolicy,
+    pub replica: NvmfCrdPolicy,
+}
+
+impl Default for NvmfCrdPolicies {
+    fn default() -> Self {
+        Self {
+            nexus: NvmfCrdPolicy::nexus(),
+            replica: NvmfCrdPolicy::replica(),
+        }
+    }
+}
+
+/// Retry policy of the state changes (pause, resume, ...) of the subsystems
+/// which fail because another state change of the subsystem is in progress.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NvmfBusyRetryOpts {
+    /// max number of attempts of a state change, the first one included
+    pub max_attempts: u32,
+    /// delay in milliseconds before the first retry, doubled on every retry
+    pub backoff_base_ms: u64,
+    /// max delay in milliseconds before a retry
+    pub backoff_cap_ms: u64,
+    /// spread the delays randomly between half and all of their value, so
+    /// that the state changes retried together do not collide again
+    pub jitter: bool,
+}
+
+impl Default for NvmfBusyRetryOpts {
+    fn default() -> Self {
+        Self {
+            max_attempts: 10,
+            backoff_base_ms: 50,
+            backoff_cap_ms: 1000,
+            jitter: true,
+        }
+    }
+}
+
+impl NvmfBusyRetryOpts {
+    /// Max number of attempts of a state change, at least one.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts.max(1)
+    }
+
+    /// Delay before the given retry, starting from 0.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let cap = self.backoff_cap_ms.max(self.backoff_base_ms);
+        let delay = self
+            .backoff_base_ms
+            .checked_shl(retry)
+            .filter(|d| d >> retry == self.backoff_base_ms)
+            .map_or(cap, |d| d.min(cap));
+        let delay = if self.jitter && delay > 1 {
+            rand::thread_rng().gen_range(delay / 2 ..= delay)
+        } else {
+            delay
+        };
+        Duration::from_millis(delay)
+    }
+}
+
+/// Parses a core list such as "0-1,4" into the sorted list of its cores.
+pub fn parse_core_list(list: &str) -> Result<Vec<u32>, String> {
+    let mut cores = vec![];
+    for item in list.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+        let parse = |s: &str| {
+            s.trim()
+                .parse::<u32>()
+                .map_err(|e| format!("invalid core '{s}' in '{list}': {e}"))
+        };
+        match item.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse(first)?, parse(last)?);
+                if first > last {
+                    return Err(format!("invalid core range '{item}'"));
+                }
+                cores.extend(first ..= last);
+            }
+            None => cores.push(parse(item)?),
+        }
+    }
+    if cores.is_empty() {
+        return Err(format!("empty core list '{list}'"));
+    }
+    cores.sort_unstable();
+    cores.dedup();
+    Ok(cores)
+}
+
+/// Parses a port range such as "8430-8449" into its first and last ports.
+pub fn parse_port_range(range: &str) -> Result<(u16, u16), String> {
+    let parse = |s: &str| {
+        s.trim()
+            .parse::<u16>()
+            .map_err(|e| format!("invalid port '{s}' in '{range}': {e}"))
+    };
+    let (first, last) = range
+        .split_once('-')
+        .ok_or_else(|| format!("invalid port range '{range}'"))?;
+    let (first, last) = (parse(first)?, parse(last)?);
+    if first == 0 || first > last {
+        return Err(format!("invalid port range '{range}'"));
+    }
+    Ok((first, last))
+}
+
+/// Parses a controller ID range such as "1-1000" into its first and last
+/// controller IDs.
+pub fn parse_cntlid_range(range: &str) -> Result<(u16, u16), String> {
+    let parse = |s: &str| {
+        s.trim().parse::<u16>().map_err(|e| {
+            format!("invalid controller ID '{s}' in '{range}': {e}")
+        })
+    };
+    let (first, last) = range
+        .split_once('-')
+        .ok_or_else(|| format!("invalid controller ID range '{range}'"))?;
+    let (first, last) = (parse(first)?, parse(last)?);
+    if first < NVME_MIN_CNTLID || first > last || last > NVME_MAX_CNTLID {
+        return Err(format!(
+            "invalid controller ID range '{range}', must be within \
+            {NVME_MIN_CNTLID}-{NVME_MAX_CNTLID}"
+        ));
+    }
+    Ok((first, last))
+}
+
 /// Settings for the TCP transport
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[serde(default, deny_unknown_fields)]
@@ -288,6 +715,13 @@ impl Default for NvmfTcpTransportOpts {
     }
 }
 
+impl NvmfTcpTransportOpts {
+    /// Whether zero-copy operations are used when the bdev supports them.
+    pub(crate) fn zcopy(&self) -> bool {
+        self.zcopy
+    }
+}
+
 /// we cannot add derives for YAML to these structs directly, so we need to
 /// copy them. The upside though, is that if the FFI structures change, we will
 /// know about it during compile time.
@@ -319,6 +753,91 @@ impl From<NvmfTcpTransportOpts> for spdk_nvmf_transport_opts {
     }
 }
 
+/// Settings for the RDMA transport
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NvmfRdmaTransportOpts {
+    /// max queue depth
+    max_queue_depth: u16,
+    /// max qpairs per controller
+    max_qpairs_per_ctrl: u16,
+    /// encapsulated data size
+    in_capsule_data_size: u32,
+    /// max IO size
+    max_io_size: u32,
+    /// IO unit size
+    io_unit_size: u32,
+    /// max admin queue depth per admin queue
+    max_aq_depth: u32,
+    /// num of shared buffers
+    num_shared_buf: u32,
+    /// cache size
+    buf_cache_size: u32,
+    /// dif
+    dif_insert_or_strip: bool,
+    /// abort execution timeout
+    abort_timeout_sec: u32,
+    /// acceptor poll rate, microseconds
+    acceptor_poll_rate: u32,
+    /// Size of RDMA data WR pool
+    data_wr_pool_size: u32,
+}
+
+impl Default for NvmfRdmaTransportOpts {
+    fn default() -> Self {
+        Self {
+            max_queue_depth: try_from_env("NVMF_RDMA_MAX_QUEUE_DEPTH", 128),
+            in_capsule_data_size: 4096,
+            max_io_size: 131_072,
+            io_unit_size: 8192,
+            max_qpairs_per_ctrl: try_from_env(
+                "NVMF_RDMA_MAX_QPAIRS_PER_CTRL",
+                32,
+            ),
+            num_shared_buf: try_from_env("NVMF_RDMA_NUM_SHARED_BUF", 4095),
+            buf_cache_size: try_from_env("NVMF_RDMA_BUF_CACHE_SIZE", 32),
+            dif_insert_or_strip: false,
+            max_aq_depth: 32,
+            abort_timeout_sec: 1,
+            acceptor_poll_rate: try_from_env("NVMF_ACCEPTOR_POLL_RATE", 10_000),
+            data_wr_pool_size: try_from_env(
+                "NVMF_RDMA_DATA_WR_POOL_SIZE",
+                4095,
+            ),
+        }
+    }
+}
+
+/// The RDMA transport does not do zero-copy, nor has it an ACK timeout; its
+/// own options (e.g. SRQ) are left to the SPDK defaults.
+impl From<NvmfRdmaTransportOpts> for spdk_nvmf_transport_opts {
+    fn from(o: NvmfRdmaTransportOpts) -> Self {
+        struct_size_init!(
+            Self {
+                max_queue_depth: o.max_queue_depth,
+                max_qpairs_per_ctrlr: o.max_qpairs_per_ctrl,
+                in_capsule_data_size: o.in_capsule_data_size,
+                max_io_size: o.max_io_size,
+                io_unit_size: o.io_unit_size,
+                max_aq_depth: o.max_aq_depth,
+                num_shared_buffers: o.num_shared_buf,
+                buf_cache_size: o.buf_cache_size,
+                dif_insert_or_strip: o.dif_insert_or_strip,
+                reserved29: Default::default(),
+                abort_timeout_sec: o.abort_timeout_sec,
+                association_timeout: 120000,
+                transport_specific: std::ptr::null(),
+                acceptor_poll_rate: o.acceptor_poll_rate,
+                zcopy: false,
+                reserved61: Default::default(),
+                ack_timeout: 0,
+                data_wr_pool_size: o.data_wr_pool_size,
+            },
+            opts_size
+        )
+    }
+}
+
 /// generic settings for the NVMe bdev (all our replicas)
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 #[serde(default, deny_unknown_fields)]
@@ -616,6 +1135,13 @@ impl Default for PosixSocketOpts {
     }
 }
 
+impl PosixSocketOpts {
+    /// Whether the sockets accepted by the targets send with MSG_ZEROCOPY.
+    pub(crate) fn zerocopy_send_server(&self) -> bool {
+        self.enable_zerocopy_send_server
+    }
+}
+
 impl GetOpts for PosixSocketOpts {
     fn get(&self) -> Self {
         let opts = spdk_sock_impl_opts {
@@ -776,3 +1302,125 @@ impl From<&IoBufOpts> for spdk_iobuf_opts {
         )
     }
 }
+
+/// Accel framework options, offloading the checksums, copies and
+/// compression to the DSA and IAA devices when present. They only take effect
+/// at startup, before the accel framework is initialized.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccelOpts {
+    /// Offload the operations to the DSA devices.
+    pub dsa_enable: bool,
+    /// Use the DSA devices through the kernel idxd driver rather than from
+    /// user space.
+    pub dsa_kernel_mode: bool,
+    /// Offload the compression to the IAA devices.
+    pub iaa_enable: bool,
+    /// Modules the operations are assigned to, by operation name, such as
+    /// `crc32c: dsa`. An operation not assigned goes to the module of the
+    /// highest priority which supports it.
+    pub assignments: BTreeMap<String, String>,
+}
+
+/// Limits of the executor of the control-plane operations, see
+/// [`crate::core::admin_ops`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminOpts {
+    /// Operations running at a time on the primary reactor.
+    pub max_running: usize,
+    /// Operations waiting for the running ones, past which more are
+    /// rejected.
+    pub max_queued: usize,
+    /// Time in milliseconds an operation may wait in the queue before being
+    /// dropped, or run before being flagged as overdue; no timeout when not
+    /// set, the default, as the pools imported at startup may wait for long
+    /// behind the ones recovering their blobstore.
+    pub op_timeout_ms: Option<u64>,
+}
+
+impl Default for AdminOpts {
+    fn default() -> Self {
+        Self {
+            max_running: 8,
+            max_queued: 128,
+            op_timeout_ms: None,
+        }
+    }
+}
+
+impl GetOpts for AdminOpts {
+    fn get(&self) -> Self {
+        self.clone()
+    }
+}
+
+impl GetOpts for AccelOpts {
+    fn get(&self) -> Self {
+        self.clone()
+    }
+
+    fn set(&self) -> bool {
+        if self.dsa_enable
+            && unsafe { accel_dsa_enable_probe(self.dsa_kernel_mode) } != 0
+        {
+            warn!("Failed to enable the DSA accel module");
+            return false;
+        }
+        if self.iaa_enable && unsafe { accel_iaa_enable_probe() } != 0 {
+            warn!("Failed to enable the IAA accel module");
+            return false;
+        }
+        for (opcode_name, module) in &self.assignments {
+            let Some(opcode) = accel_opcode(opcode_name) else {
+                warn!("Unknown accel operation '{opcode_name}'");
+                return false;
+            };
+            let module_name = module.into_cstring();
+            if unsafe { spdk_accel_assign_opc(opcode, module_name.as_ptr()) }
+                != 0
+            {
+                warn!(
+                    "Failed to assign accel operation '{opcode_name}' to \
+                    module '{module}'"
+                );
+                return false;
+            }
+        }
+        info!("Accel options successfully applied");
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_core_list, NexusOpts};
+
+    #[test]
+    fn nqn_prefix() {
+        let opts: NexusOpts =
+            serde_yaml::from_str("nvmf_nqn_prefix: nqn.2024-01.io.example")
+                .unwrap();
+        assert_eq!(
+            opts.nvmf_nqn_prefix.as_deref(),
+            Some("nqn.2024-01.io.example")
+        );
+        for prefix in ["io.example", "nqn.2024.io.example", "nqn.2024-01"] {
+            let yaml = format!("nvmf_nqn_prefix: {prefix}");
+            assert!(serde_yaml::from_str::<NexusOpts>(&yaml).is_err());
+        }
+    }
+
+    #[test]
+    fn core_list() {
+        assert_eq!(parse_core_list("3").unwrap(), vec![3]);
+        assert_eq!(parse_core_list("0-1,4").unwrap(), vec![0, 1, 4]);
+        // the cores are sorted and deduplicated
+        assert_eq!(parse_core_list(" 4, 2-3 ,3,").unwrap(), vec![2, 3, 4]);
+        assert_eq!(parse_core_list("5-5").unwrap(), vec![5]);
+
+        for list in ["", " , ", "a", "1-", "-2", "3-1", "1,,x", "1-2-3"] {
+            assert!(parse_core_list(list).is_err(), "'{list}' was accepted");
+        }
+    }
+}
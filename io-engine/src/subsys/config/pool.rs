@@ -1,4 +1,4 @@
-use futures::channel::oneshot;
+use futures::{channel::oneshot, future, stream, StreamExt};
 use once_cell::sync::{Lazy, OnceCell};
 use serde::{Deserialize, Serialize};
 use std::{fmt::Display, fs, path::Path, sync::Mutex};
@@ -7,8 +7,9 @@ use tonic::Status;
 use crate::{
     core::{runtime, Cores, Reactor, Share, VerboseError},
     grpc::rpc_submit,
-    lvs::{Lvs, LvsBdev, LvsError},
+    lvs::{reserved_pct_override, Lvs, LvsBdev, LvsError},
     pool_backend::{PoolArgs, PoolBackend},
+    subsys::config::startup::StartupProgress,
 };
 
 static CONFIG_FILE: OnceCell<String> = OnceCell::new();
@@ -119,36 +120,52 @@ impl PoolConfig {
         }
     }
 
-    /// Create pools specified in this configuration
-    async fn create_pools(&self) -> usize {
-        let mut failures = 0;
-        if let Some(pools) = self.pools.as_ref() {
-            for pool in pools.iter() {
+    /// Create pools specified in this configuration, up to `concurrency`
+    /// of them at the same time
+    async fn create_pools(&self, concurrency: usize) -> usize {
+        let pools = self.pools.as_deref().unwrap_or_default();
+        StartupProgress::importing_pools(pools.len());
+        stream::iter(pools)
+            .map(|pool| async move {
                 info!("creating pool {}", pool.name);
-                if let Err(error) = create_pool(pool.into()).await {
+                let result = create_pool(pool.into()).await;
+                StartupProgress::pool_imported(result.is_ok());
+                if let Err(error) = &result {
                     error!(
                         "failed to create pool {}: {}",
                         pool.name,
                         error.verbose()
                     );
-                    failures += 1;
                 }
-            }
+                result
+            })
+            .buffer_unordered(concurrency.max(1))
+            .filter(|result| future::ready(result.is_err()))
+            .count()
+            .await
+    }
+
+    /// Imports the pools, up to `concurrency` of them at the same time, and
+    /// then re-shares their replicas by decreasing restore priority, as at
+    /// startup. Returns the number of pools which failed to be imported.
+    pub async fn restore(&self, concurrency: usize) -> usize {
+        let errors = self.create_pools(concurrency).await;
+        if errors != 0 {
+            warn!(
+                "Not all pools were imported successfully ({} errors)",
+                errors
+            );
         }
-        failures
+        StartupProgress::sharing_replicas();
+        Lvs::share_all_pools().await;
+        errors
     }
 
     /// Import pools
-    pub fn import_pools(self) {
+    pub fn import_pools(self, concurrency: usize) {
         assert_eq!(Cores::current(), Cores::first());
         Reactor::block_on(async move {
-            let errors = self.create_pools().await;
-            if errors != 0 {
-                warn!(
-                    "Not all pools were imported successfully ({} errors)",
-                    errors
-                );
-            }
+            self.restore(concurrency).await;
         });
     }
 }
@@ -165,6 +182,10 @@ struct Pool {
     #[serde(skip_serializing)]
     replicas: Option<Vec<Replica>>,
     backend: PoolBackend,
+    /// share of the capacity reserved for the system overhead, in percent,
+    /// when it overrides the default of the node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reserved_pct: Option<u8>,
 }
 
 /// Convert a Pool into a gRPC request payload
@@ -175,6 +196,7 @@ impl From<&Pool> for PoolArgs {
             disks: pool.disks.clone(),
             uuid: None,
             cluster_size: None,
+            reserved_pct: pool.reserved_pct,
             backend: pool.backend,
         }
     }
@@ -191,6 +213,7 @@ impl From<LvsBdev> for Pool {
                 .unwrap_or_else(|| base.name().to_string())],
             replicas: None,
             backend: PoolBackend::Lvs,
+            reserved_pct: reserved_pct_override(&lvs_bdev.name()),
         }
     }
 }
@@ -0,0 +1,158 @@
+//! Progress of the startup of the io-engine.
+//!
+//! The pools of the pool configuration are imported concurrently at startup.
+//! Once they are all imported, the replicas found on them are re-shared
+//! concurrently as well, by decreasing restore priority: the replicas of
+//! the critical volumes are shared before the others, whichever pool they
+//! are on. The number of operations in flight is bounded by the startup
+//! concurrency. The progress of those phases is recorded here so that it
+//! can be queried while the node recovers.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+/// Phase of the startup.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartupPhase {
+    /// The SPDK subsystems are being initialised.
+    #[default]
+    Initializing,
+    /// The pools are being imported.
+    ImportingPools,
+    /// The replicas of the imported pools are being re-shared.
+    SharingReplicas,
+    /// The startup is complete.
+    Ready,
+}
+
+/// Progress of the startup.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct StartupProgress {
+    pub phase: StartupPhase,
+    /// Number of pools of the pool configuration.
+    pub pools_total: usize,
+    /// Number of pools which have been imported.
+    pub pools_imported: usize,
+    /// Number of pools which failed to be imported.
+    pub pools_failed: usize,
+    /// Number of replicas which have been re-shared.
+    pub replicas_shared: usize,
+    /// Number of replicas which failed to be re-shared.
+    pub replicas_failed: usize,
+    /// Time spent in the current phase, or the duration of the whole
+    /// startup once ready.
+    pub elapsed_ms: u64,
+}
+
+struct Startup {
+    progress: StartupProgress,
+    /// Time the startup began at.
+    began: Instant,
+    /// Time the current phase started at.
+    started: Instant,
+    elapsed: Option<Duration>,
+}
+
+static STARTUP: Lazy<Mutex<Startup>> = Lazy::new(|| {
+    Mutex::new(Startup {
+        progress: StartupProgress::default(),
+        began: Instant::now(),
+        started: Instant::now(),
+        elapsed: None,
+    })
+});
+
+impl StartupProgress {
+    /// Returns the current progress of the startup.
+    pub fn get() -> Self {
+        let startup = STARTUP.lock().unwrap();
+        let elapsed =
+            startup.elapsed.unwrap_or_else(|| startup.started.elapsed());
+        Self {
+            elapsed_ms: elapsed.as_millis() as u64,
+            ..startup.progress.clone()
+        }
+    }
+
+    /// Starts the import of the given number of pools.
+    pub(crate) fn importing_pools(pools_total: usize) {
+        let mut startup = STARTUP.lock().unwrap();
+        startup.progress = Self {
+            phase: StartupPhase::ImportingPools,
+            pools_total,
+            ..Default::default()
+        };
+        startup.began = Instant::now();
+        startup.started = startup.began;
+    }
+
+    /// Records the outcome of the import of a pool.
+    pub(crate) fn pool_imported(success: bool) {
+        let mut startup = STARTUP.lock().unwrap();
+        if success {
+            startup.progress.pools_imported += 1;
+        } else {
+            startup.progress.pools_failed += 1;
+        }
+        log_progress(&startup.progress);
+    }
+
+    /// Whether the replicas of the pools being imported are to be re-shared
+    /// once all of them are imported, rather than by each pool.
+    pub(crate) fn defer_shares() -> bool {
+        STARTUP.lock().unwrap().progress.phase == StartupPhase::ImportingPools
+    }
+
+    /// Starts the re-share of the replicas of the imported pools.
+    pub(crate) fn sharing_replicas() {
+        let mut startup = STARTUP.lock().unwrap();
+        startup.progress.phase = StartupPhase::SharingReplicas;
+        startup.started = Instant::now();
+    }
+
+    /// Records the outcome of the re-share of a replica, while the replicas
+    /// are being re-shared at startup only.
+    pub(crate) fn replica_shared(success: bool) {
+        let mut startup = STARTUP.lock().unwrap();
+        if startup.progress.phase != StartupPhase::SharingReplicas {
+            return;
+        }
+        if success {
+            startup.progress.replicas_shared += 1;
+        } else {
+            startup.progress.replicas_failed += 1;
+        }
+    }
+
+    /// Completes the startup.
+    pub(crate) fn ready() {
+        let mut startup = STARTUP.lock().unwrap();
+        startup.progress.phase = StartupPhase::Ready;
+        startup.elapsed = Some(startup.began.elapsed());
+        info!(
+            "Startup complete in {:?}: {} pools imported, {} failed, \
+            {} replicas shared, {} failed",
+            startup.elapsed.unwrap_or_default(),
+            startup.progress.pools_imported,
+            startup.progress.pools_failed,
+            startup.progress.replicas_shared,
+            startup.progress.replicas_failed,
+        );
+    }
+}
+
+fn log_progress(progress: &StartupProgress) {
+    info!(
+        "Startup: {}/{} pools processed ({} failed), {} replicas shared",
+        progress.pools_imported + progress.pools_failed,
+        progress.pools_total,
+        progress.pools_failed,
+        progress.replicas_shared,
+    );
+}
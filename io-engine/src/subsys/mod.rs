@@ -2,19 +2,127 @@
 //! Main file to register additional subsystems
 
 pub use config::{
-    opts::{NexusOpts, NvmeBdevOpts},
+    apply::{ApplyAction, ApplyStateArgs, ApplyStateReport},
+    node::{NodeConfig, NodeConfigReport},
+    opts::{
+        parse_cntlid_range,
+        parse_port_range,
+        NexusOpts,
+        NvmeBdevOpts,
+        NvmfBusyRetryOpts,
+        NvmfCrdPolicies,
+        NvmfCrdPolicy,
+        NvmfDiscoveryConfig,
+    },
     pool::PoolConfig,
+    startup::{StartupPhase, StartupProgress},
     Config,
     ConfigSubsystem,
 };
 pub use nvmf::{
+    add_discovery_referral,
+    busy_retry_stats,
+    crd_policies,
+    default_kato,
+    discovery_info,
+    duplicate_hosts,
+    expand_hosts,
+    expire_share_leases,
+    import_subsystem,
+    node_cntlid_range,
+    nqn_index_stats,
+    nqn_prefix,
+    nvmf_io_stats,
+    nvmf_io_stats_loop,
+    nvmf_ports,
+    nvmf_subsystems,
+    persisted_share,
+    reconcile_subsystems,
+    register_host_event_hook,
+    remove_discovery_referral,
+    set_crd_policies,
+    set_discovery_restrict_hosts,
+    set_node_cntlid_range,
     set_snapshot_time,
+    share_audit_loop,
+    share_lease_loop,
+    share_readiness,
+    stale_subsystem_loop,
+    unregister_host_event_hook,
+    validate_kato,
+    validate_nqn,
+    validate_nqn_prefix,
+    zero_copy_stats,
+    Active as SubsystemActive,
+    BatchStateReport,
+    BusyRetryStats,
+    DiscoveryInfo,
+    DrainArgs,
+    DrainSample,
+    DrainStats,
+    DriftKind,
+    DuplicateHost,
+    DuplicateHosts,
     Error as NvmfError,
+    ExpiredShare,
+    ExportedRegistrant,
+    ExportedReservations,
+    HostDhChap,
+    HostEvent,
+    HostEventHook,
+    HostEventKind,
+    HostGroup,
+    HostIoCounters,
+    HostIoRates,
+    IdentifyOverrides,
+    InFlightCommands,
+    NqnIndexStats,
     NvmeCpl,
+    NvmfAnaState,
+    NvmfControllerInfo,
+    NvmfHostIoStats,
+    NvmfListener,
+    NvmfListenerAna,
+    NvmfNamespaceInfo,
+    NvmfPortAllocation,
     NvmfReq,
+    NvmfShareMode,
     NvmfSubsystem,
+    NvmfSubsystemHandle,
+    NvmfSubsystemInfo,
+    NvmfSubsystemIoStats,
+    NvmfTransport,
+    OutstandingCommands,
+    Paused as SubsystemPaused,
+    PersistedShare,
+    ShareAudit,
+    ShareCheck,
+    ShareDrift,
+    ShareLease,
+    ShareReadiness,
+    StaleSubsystem,
+    Stopped as SubsystemStopped,
     SubType,
+    SubsystemExport,
+    SubsystemHandle,
+    SubsystemKato,
+    SubsystemStateChange,
+    SubsystemStateFailure,
+    SubsystemZeroCopy,
     Target as NvmfTarget,
+    TargetIoCounters,
+    TargetKind as NvmfTargetKind,
+    ZeroCopyStats,
+    HOST_GROUP_PREFIX,
+    KATO_MAX_MS,
+    KATO_MIN_MS,
+};
+pub(crate) use nvmf::{
+    load_state,
+    record_identity,
+    replay_share,
+    resolve_cntlid_range,
+    save_state,
 };
 use spdk_rs::libspdk::{
     spdk_add_subsystem,
@@ -35,7 +35,8 @@ use spdk_rs::{
         spdk_nvmf_request_get_response,
         spdk_nvmf_request_get_subsystem,
         spdk_nvmf_set_custom_admin_cmd_hdlr,
-        spdk_nvmf_subsystem_get_max_nsid,
+        spdk_nvmf_subsystem_get_first_ns,
+        spdk_nvmf_subsystem_get_next_ns,
     },
     nvme_admin_opc,
 };
@@ -172,9 +173,13 @@ extern "C" fn nvmf_create_snapshot_hdlr(req: *mut spdk_nvmf_request) -> i32 {
         return -1;
     }
 
-    /* Only process this request if it has exactly one namespace */
-    if unsafe { spdk_nvmf_subsystem_get_max_nsid(subsys) } != 1 {
-        debug!("multiple namespaces");
+    /* Only process this request if it has exactly one namespace: the
+     * subsystem may hold private namespaces besides that of its bdev */
+    let ns = unsafe { spdk_nvmf_subsystem_get_first_ns(subsys) };
+    if ns.is_null()
+        || !unsafe { spdk_nvmf_subsystem_get_next_ns(subsys, ns) }.is_null()
+    {
+        debug!("no namespace or multiple namespaces");
         return -1;
     }
 
@@ -0,0 +1,265 @@
+//! ANA (Asymmetric Namespace Access) groups of the subsystems and their
+//! state on every listener.
+//!
+//! Every namespace of a subsystem belongs to an ANA group, and every
+//! listener reports a state per group to the hosts. The state of a group may
+//! differ between listeners, e.g. so that a multipath host prefers the RDMA
+//! path of a share over its TCP path.
+
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use spdk_rs::libspdk::{
+    nvmf_subsystem_find_listener,
+    spdk_nvme_ana_state,
+    spdk_nvmf_subsystem_get_first_listener,
+    spdk_nvmf_subsystem_get_first_ns,
+    spdk_nvmf_subsystem_get_next_listener,
+    spdk_nvmf_subsystem_get_next_ns,
+    spdk_nvmf_subsystem_listener_get_trid,
+    SPDK_NVME_ANA_CHANGE_STATE,
+    SPDK_NVME_ANA_INACCESSIBLE_STATE,
+    SPDK_NVME_ANA_NON_OPTIMIZED_STATE,
+    SPDK_NVME_ANA_OPTIMIZED_STATE,
+    SPDK_NVME_ANA_PERSISTENT_LOSS_STATE,
+};
+
+use super::{
+    transport::TransportId,
+    Error,
+    NvmfListener,
+    NvmfSubsystem,
+    SubsystemArgs,
+};
+use crate::{
+    bdev::nexus::NEXUS_MODULE_NAME,
+    core::Bdev,
+    ffihelper::AsStr,
+    jsonrpc::jsonrpc_register,
+};
+
+/// ANA state of a group, as reported to the hosts on a listener.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NvmfAnaState {
+    Optimized,
+    NonOptimized,
+    Inaccessible,
+    PersistentLoss,
+    Change,
+}
+
+impl NvmfAnaState {
+    fn from_spdk(state: spdk_nvme_ana_state) -> Option<Self> {
+        match state {
+            SPDK_NVME_ANA_OPTIMIZED_STATE => Some(Self::Optimized),
+            SPDK_NVME_ANA_NON_OPTIMIZED_STATE => Some(Self::NonOptimized),
+            SPDK_NVME_ANA_INACCESSIBLE_STATE => Some(Self::Inaccessible),
+            SPDK_NVME_ANA_PERSISTENT_LOSS_STATE => Some(Self::PersistentLoss),
+            SPDK_NVME_ANA_CHANGE_STATE => Some(Self::Change),
+            _ => None,
+        }
+    }
+
+    fn to_spdk(self) -> spdk_nvme_ana_state {
+        match self {
+            Self::Optimized => SPDK_NVME_ANA_OPTIMIZED_STATE,
+            Self::NonOptimized => SPDK_NVME_ANA_NON_OPTIMIZED_STATE,
+            Self::Inaccessible => SPDK_NVME_ANA_INACCESSIBLE_STATE,
+            Self::PersistentLoss => SPDK_NVME_ANA_PERSISTENT_LOSS_STATE,
+            Self::Change => SPDK_NVME_ANA_CHANGE_STATE,
+        }
+    }
+}
+
+/// ANA state of a group of a subsystem on one of its listeners.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NvmfListenerAna {
+    /// The listener.
+    pub listener: NvmfListener,
+    /// ID of the ANA group.
+    pub anagrpid: u32,
+    /// State of the group on the listener.
+    pub ana_state: NvmfAnaState,
+}
+
+impl From<&TransportId> for NvmfListener {
+    fn from(trid: &TransportId) -> Self {
+        Self {
+            transport: trid.transport(),
+            address: Some(trid.traddr.as_str().to_string()),
+            port: trid.trsvcid.as_str().parse().ok(),
+        }
+    }
+}
+
+impl NvmfSubsystem {
+    /// Checks that ANA may be reported for a share of the given bdev, the
+    /// ANA states being only managed for the nexuses.
+    pub fn validate_ana<T>(bdev: &Bdev<T>, ana: bool) -> Result<(), Error>
+    where
+        T: spdk_rs::BdevOps,
+    {
+        if ana && bdev.driver() != NEXUS_MODULE_NAME {
+            return Err(Error::InvalidAna {
+                nqn: bdev.name().to_string(),
+                reason: "ANA is only reported for nexuses".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Get the IDs of the ANA groups of the namespaces, sorted.
+    pub fn ana_groups(&self) -> Vec<u32> {
+        let mut groups = Vec::new();
+
+        let mut ns =
+            unsafe { spdk_nvmf_subsystem_get_first_ns(self.0.as_ptr()) };
+
+        while !ns.is_null() {
+            let anagrpid = unsafe { (*ns).anagrpid };
+            if !groups.contains(&anagrpid) {
+                groups.push(anagrpid);
+            }
+            ns =
+                unsafe { spdk_nvmf_subsystem_get_next_ns(self.0.as_ptr(), ns) };
+        }
+
+        groups.sort_unstable();
+        groups
+    }
+
+    /// Get the state of every ANA group on every listener.
+    pub fn ana_states(&self) -> Vec<NvmfListenerAna> {
+        let groups = self.ana_groups();
+        let mut states = Vec::new();
+
+        let mut listener =
+            unsafe { spdk_nvmf_subsystem_get_first_listener(self.0.as_ptr()) };
+
+        while !listener.is_null() {
+            let trid = TransportId(unsafe {
+                *spdk_nvmf_subsystem_listener_get_trid(listener)
+            });
+            for &anagrpid in &groups {
+                // the states of the listener are indexed by group ID - 1
+                let state = unsafe {
+                    *(*listener).ana_state.add(anagrpid as usize - 1)
+                };
+                if let Some(ana_state) = NvmfAnaState::from_spdk(state) {
+                    states.push(NvmfListenerAna {
+                        listener: NvmfListener::from(&trid),
+                        anagrpid,
+                        ana_state,
+                    });
+                }
+            }
+            listener = unsafe {
+                spdk_nvmf_subsystem_get_next_listener(self.0.as_ptr(), listener)
+            };
+        }
+
+        states
+    }
+
+    /// Sets the state of the given ANA group, of all the groups when 0, on
+    /// the given listener only; subsystem must be in paused or inactive
+    /// state.
+    pub async fn set_listener_ana(
+        &self,
+        listener: &NvmfListener,
+        anagrpid: u32,
+        ana_state: NvmfAnaState,
+    ) -> Result<(), Error> {
+        if anagrpid != 0 && !self.ana_groups().contains(&anagrpid) {
+            return Err(Error::AnaGroupNotFound {
+                nqn: self.get_nqn(),
+                anagrpid,
+            });
+        }
+
+        let trid = self.trid_of(listener)?;
+        let found = unsafe {
+            nvmf_subsystem_find_listener(self.0.as_ptr(), trid.as_ptr())
+        };
+        if found.is_null() {
+            return Err(Error::Listener {
+                nqn: self.get_nqn(),
+                trid: trid.to_string(),
+            });
+        }
+
+        self.set_listener_ana_state(&trid, ana_state.to_spdk(), anagrpid)
+            .await
+    }
+}
+
+/// Arguments of the `mayastor_subsystem_ana_set` method.
+#[derive(Debug, Deserialize)]
+struct SubsystemAnaArgs {
+    /// NQN of the subsystem.
+    nqn: String,
+    /// Listener of the subsystem the state is reported on.
+    listener: NvmfListener,
+    /// ID of the ANA group, all the groups when 0.
+    #[serde(default)]
+    anagrpid: u32,
+    /// New state of the group.
+    ana_state: NvmfAnaState,
+}
+
+/// Arguments of the `mayastor_subsystem_ana_reporting_set` method.
+#[derive(Debug, Deserialize)]
+struct SubsystemAnaReportingArgs {
+    /// NQN of the subsystem.
+    nqn: String,
+    /// Whether ANA is reported to the hosts.
+    enable: bool,
+}
+
+/// Registers the JSON-RPC methods of the ANA reporting.
+pub(super) fn register_rpc_methods() {
+    // ANA groups of a subsystem and their state on every listener, which
+    // may differ between listeners of a multipath share
+    jsonrpc_register::<SubsystemArgs, _, _, Error>(
+        "mayastor_subsystem_ana_list",
+        |args| {
+            async move {
+                Ok(NvmfSubsystem::lookup_by_nqn(&args.nqn)?.ana_states())
+            }
+            .boxed_local()
+        },
+    );
+
+    jsonrpc_register::<SubsystemAnaArgs, _, _, Error>(
+        "mayastor_subsystem_ana_set",
+        |args| {
+            async move {
+                let subsystem = NvmfSubsystem::lookup_by_nqn(&args.nqn)?;
+                subsystem.pause().await?;
+                let res = subsystem
+                    .set_listener_ana(
+                        &args.listener,
+                        args.anagrpid,
+                        args.ana_state,
+                    )
+                    .await;
+                subsystem.resume().await?;
+                res
+            }
+            .boxed_local()
+        },
+    );
+
+    // ANA reporting of an existing subsystem, toggled while it is stopped
+    jsonrpc_register::<SubsystemAnaReportingArgs, _, _, Error>(
+        "mayastor_subsystem_ana_reporting_set",
+        |args| {
+            async move {
+                NvmfSubsystem::lookup_by_nqn(&args.nqn)?
+                    .update_ana_reporting(args.enable)
+                    .await
+            }
+            .boxed_local()
+        },
+    );
+}
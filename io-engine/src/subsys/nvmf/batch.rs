@@ -0,0 +1,171 @@
+//! State changes of many subsystems at once.
+//!
+//! The subsystems change state independently of each other, so the state
+//! change of a batch of subsystems is issued concurrently rather than
+//! waiting for each subsystem in turn, which would make e.g. the shutdown of
+//! a node with thousands of replicas very slow. The number of state changes
+//! in flight is bounded, so that the poll groups are not flooded with them,
+//! and the failures are collected rather than stopping the batch.
+
+use std::time::Instant;
+
+use futures::{stream, FutureExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use spdk_rs::libspdk::spdk_nvmf_subsystem_start;
+
+use super::{Error, NvmfSubsystem, SubType};
+use crate::{
+    core::op_stats::{measure, Operation},
+    jsonrpc::jsonrpc_register,
+    subsys::Config,
+};
+
+/// State change of a batch of subsystems.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubsystemStateChange {
+    /// Start stopped subsystems, with the listeners they already have.
+    Start,
+    Stop,
+    Pause,
+    Resume,
+}
+
+/// Failure of the state change of a subsystem of a batch.
+#[derive(Debug, Clone, Serialize)]
+pub struct SubsystemStateFailure {
+    pub nqn: String,
+    pub error: String,
+}
+
+/// Outcome of the state change of a batch of subsystems.
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchStateReport {
+    pub change: SubsystemStateChange,
+    /// Number of subsystems of the batch.
+    pub total: usize,
+    /// Number of subsystems whose state changed.
+    pub succeeded: usize,
+    /// Subsystems whose state failed to change.
+    pub failures: Vec<SubsystemStateFailure>,
+    /// Duration of the batch in milliseconds.
+    pub elapsed_ms: u64,
+}
+
+impl NvmfSubsystem {
+    /// Changes the state of the given subsystems, at most `concurrency` of
+    /// them at a time, or as many as configured for the NVMF target when not
+    /// given.
+    pub async fn change_state_all(
+        subsystems: Vec<NvmfSubsystem>,
+        change: SubsystemStateChange,
+        concurrency: Option<usize>,
+    ) -> BatchStateReport {
+        let concurrency = concurrency
+            .unwrap_or(Config::get().nvmf_tgt_conf.state_change_concurrency)
+            .max(1);
+        let total = subsystems.len();
+        let start = Instant::now();
+        info!("Changing the state of {total} subsystems: {change:?}");
+
+        let failures = stream::iter(subsystems)
+            .map(|s| async move {
+                let res = match change {
+                    SubsystemStateChange::Start => s.restart().await,
+                    SubsystemStateChange::Stop => s.stop().await,
+                    SubsystemStateChange::Pause => s.pause().await,
+                    SubsystemStateChange::Resume => s.resume().await,
+                };
+                res.err().map(|e| SubsystemStateFailure {
+                    nqn: s.get_nqn(),
+                    error: e.to_string(),
+                })
+            })
+            .buffer_unordered(concurrency)
+            .filter_map(|f| async move { f })
+            .collect::<Vec<_>>()
+            .await;
+
+        let report = BatchStateReport {
+            change,
+            total,
+            succeeded: total - failures.len(),
+            failures,
+            elapsed_ms: start.elapsed().as_millis() as u64,
+        };
+        if report.failures.is_empty() {
+            info!(?report, "Changed the state of the subsystems");
+        } else {
+            error!(?report, "Failed to change the state of some subsystems");
+        }
+        report
+    }
+
+    /// Changes the state of the subsystems with the given NQNs, or of all
+    /// the subsystems but the discovery one when none is given.
+    pub async fn change_state_nqns(
+        nqns: Option<&[String]>,
+        change: SubsystemStateChange,
+        concurrency: Option<usize>,
+    ) -> Result<BatchStateReport, Error> {
+        let subsystems = match nqns {
+            Some(nqns) => nqns
+                .iter()
+                .map(|nqn| NvmfSubsystem::lookup_by_nqn(nqn))
+                .collect::<Result<Vec<_>, _>>()?,
+            None => NvmfSubsystem::first()
+                .map(|first| {
+                    first
+                        .into_iter()
+                        .filter(|s| s.subtype() != SubType::Discovery)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        };
+        Ok(Self::change_state_all(subsystems, change, concurrency).await)
+    }
+
+    /// Starts a stopped subsystem again, with the listeners it already has.
+    pub(super) async fn restart(&self) -> Result<(), Error> {
+        measure(
+            Operation::SubsystemStart,
+            self.change_state("start", |ss, cb, arg| unsafe {
+                spdk_nvmf_subsystem_start(ss, cb, arg)
+            }),
+        )
+        .await
+    }
+}
+
+/// Arguments of the `mayastor_subsystem_state_change_all` method.
+#[derive(Debug, Deserialize)]
+struct SubsystemStateChangeArgs {
+    /// State change of the subsystems.
+    change: SubsystemStateChange,
+    /// NQNs of the subsystems, all the subsystems when not given.
+    #[serde(default)]
+    nqns: Option<Vec<String>>,
+    /// Max number of subsystems changing state concurrently, as configured
+    /// for the NVMF target when not given.
+    #[serde(default)]
+    concurrency: Option<usize>,
+}
+
+/// Registers the JSON-RPC methods of the batched state changes.
+pub(super) fn register_rpc_methods() {
+    // state change of many subsystems at once, with bounded concurrency
+    jsonrpc_register::<SubsystemStateChangeArgs, _, _, Error>(
+        "mayastor_subsystem_state_change_all",
+        |args| {
+            async move {
+                NvmfSubsystem::change_state_nqns(
+                    args.nqns.as_deref(),
+                    args.change,
+                    args.concurrency,
+                )
+                .await
+            }
+            .boxed_local()
+        },
+    );
+}
@@ -0,0 +1,106 @@
+//! Retry of the state changes of the subsystems which find the subsystem
+//! busy.
+//!
+//! SPDK refuses a state change (pause, resume, ...) of a subsystem with
+//! EBUSY while another state change of the subsystem is in progress. Such a
+//! state change is retried as soon as the state change in progress
+//! completes, or once its backoff delay is over, whichever comes first; the
+//! delay covers the state changes not started through `change_state`. The
+//! retry policy is set by the `busy_retry` options of the NVMF target.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+    time::Duration,
+};
+
+use futures::{channel::oneshot, future::select, FutureExt};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::{
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+    sleep::mayastor_sleep,
+};
+
+/// Counters of the state changes which found their subsystem busy, since
+/// the start of the io-engine.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct BusyRetryStats {
+    /// State changes which found the subsystem busy at least once.
+    pub busy: u64,
+    /// Retries of these state changes.
+    pub retries: u64,
+    /// State changes which still found the subsystem busy on their last
+    /// attempt, and failed.
+    pub exhausted: u64,
+    /// State changes which found the subsystem busy, by operation.
+    pub busy_by_op: BTreeMap<String, u64>,
+}
+
+static STATS: Lazy<Mutex<BusyRetryStats>> =
+    Lazy::new(|| Mutex::new(BusyRetryStats::default()));
+
+/// State changes waiting for the state change in progress to complete, by
+/// NQN of the subsystem.
+static WAITERS: Lazy<Mutex<HashMap<String, Vec<oneshot::Sender<()>>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Returns the counters of the state changes which found their subsystem
+/// busy.
+pub fn busy_retry_stats() -> BusyRetryStats {
+    STATS.lock().unwrap().clone()
+}
+
+/// Counts a state change which found the subsystem busy on its first
+/// attempt.
+pub(crate) fn record_busy(op: &str) {
+    let mut stats = STATS.lock().unwrap();
+    stats.busy += 1;
+    *stats.busy_by_op.entry(op.to_string()).or_default() += 1;
+}
+
+/// Counts a state change which found the subsystem busy on its last
+/// attempt.
+pub(crate) fn record_exhausted() {
+    STATS.lock().unwrap().exhausted += 1;
+}
+
+/// Waits for the state change in progress on the subsystem to complete, for
+/// the given delay at most.
+pub(crate) async fn wait_busy(nqn: &str, delay: Duration) {
+    STATS.lock().unwrap().retries += 1;
+
+    let (s, r) = oneshot::channel();
+    WAITERS
+        .lock()
+        .unwrap()
+        .entry(nqn.to_string())
+        .or_default()
+        .push(s);
+    select(r, mayastor_sleep(delay)).await;
+}
+
+/// Wakes the state changes waiting on the subsystem once a state change of
+/// the subsystem completed.
+pub(crate) fn state_changed(nqn: &str) {
+    let waiters = WAITERS.lock().unwrap().remove(nqn).unwrap_or_default();
+    for w in waiters {
+        w.send(()).ok();
+    }
+}
+
+/// Forgets the state changes waiting on a subsystem which is being
+/// destroyed, which wakes them.
+pub(crate) fn forget_busy_waiters(nqn: &str) {
+    WAITERS.lock().unwrap().remove(nqn);
+}
+
+/// Registers the JSON-RPC methods of the busy retries.
+pub(super) fn register_rpc_methods() {
+    // state changes of the subsystems which found the subsystem busy
+    jsonrpc_register::<(), _, _, JsonRpcError>(
+        "mayastor_subsystem_busy_stats",
+        |_| async move { Ok(busy_retry_stats()) }.boxed_local(),
+    );
+}
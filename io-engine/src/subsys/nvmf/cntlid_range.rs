@@ -0,0 +1,102 @@
+//! Controller ID range of the node.
+//!
+//! The hosts tell the controllers of a subsystem apart by their ID, which
+//! every subsystem gives out from the full range by default. When a volume
+//! moves between nodes, e.g. its nexus is recreated on another node while a
+//! host is still connected to the previous one, the host may be given the
+//! same controller ID twice for the same subsystem NQN and reject the new
+//! path. Giving every node of the cluster a disjoint range of the controller
+//! IDs avoids such clashes.
+//!
+//! The range of the node applies to the subsystems shared without a range of
+//! their own, or with the full range which the nexuses default to; a
+//! narrower range given explicitly is kept as is.
+
+use std::sync::Mutex;
+
+use futures::FutureExt;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+use super::Error;
+use crate::{
+    bdev::nexus::{NVME_MAX_CNTLID, NVME_MIN_CNTLID},
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+    subsys::{config::opts::parse_cntlid_range, Config},
+};
+
+/// Controller ID range of the node, as configured at startup unless changed
+/// since.
+static CNTLID_RANGE: Lazy<Mutex<Option<(u16, u16)>>> = Lazy::new(|| {
+    let range = Config::get().nexus_opts.nvmf_cntlid_range.clone();
+    Mutex::new(range.and_then(|range| {
+        parse_cntlid_range(&range)
+            .map_err(|error| warn!("Ignoring {error}"))
+            .ok()
+    }))
+});
+
+/// Returns the controller ID range of the node, if any.
+pub fn node_cntlid_range() -> Option<(u16, u16)> {
+    *CNTLID_RANGE.lock().unwrap()
+}
+
+/// Sets the controller ID range of the node, e.g. "1-1000", or removes it
+/// when None. Only the subsystems shared from now on use the new range.
+pub fn set_node_cntlid_range(
+    range: Option<&str>,
+) -> Result<Option<(u16, u16)>, Error> {
+    let range = range
+        .map(|range| {
+            parse_cntlid_range(range).map_err(|reason| {
+                Error::InvalidCntlidRange {
+                    range: range.to_string(),
+                    reason,
+                }
+            })
+        })
+        .transpose()?;
+    info!("Controller ID range of the node set to {range:?}");
+    *CNTLID_RANGE.lock().unwrap() = range;
+    Ok(range)
+}
+
+/// Returns the controller ID range of a subsystem shared with the given
+/// range, if any.
+pub(crate) fn resolve_cntlid_range(
+    range: Option<(u16, u16)>,
+) -> Option<(u16, u16)> {
+    match range {
+        Some(range) if range != (NVME_MIN_CNTLID, NVME_MAX_CNTLID) => {
+            Some(range)
+        }
+        range => node_cntlid_range().or(range),
+    }
+}
+
+/// Arguments of the `mayastor_nvmf_cntlid_range_set` method.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct CntlidRangeArgs {
+    /// Controller ID range of the node, e.g. "1-1000", removed when not
+    /// given.
+    range: Option<String>,
+}
+
+/// Registers the JSON-RPC methods of the controller ID range.
+pub(super) fn register_rpc_methods() {
+    // controller ID range given to the subsystems shared without a range
+    // of their own
+    jsonrpc_register::<(), _, _, JsonRpcError>(
+        "mayastor_nvmf_cntlid_range",
+        |_| async move { Ok(node_cntlid_range()) }.boxed_local(),
+    );
+
+    jsonrpc_register::<CntlidRangeArgs, _, _, Error>(
+        "mayastor_nvmf_cntlid_range_set",
+        |args| {
+            async move { set_node_cntlid_range(args.range.as_deref()) }
+                .boxed_local()
+        },
+    );
+}
@@ -0,0 +1,54 @@
+//! Command Retry Delay (CRD) policies of the subsystems.
+//!
+//! The targets set a CRD in the failed completions they retry after a delay,
+//! which the completion error callbacks of the nexuses and of the replicas
+//! then change, so that the hosts wait longer on the errors which take
+//! longer to clear. The policies are configured at startup and may be
+//! changed at runtime, e.g. to stop setting the CRD for initiators which
+//! mishandle it.
+
+use std::sync::Mutex;
+
+use futures::FutureExt;
+use once_cell::sync::Lazy;
+
+use crate::{
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+    subsys::{config::opts::NvmfCrdPolicies, Config},
+};
+
+/// CRD policies, as configured at startup unless changed since.
+static CRD_POLICIES: Lazy<Mutex<NvmfCrdPolicies>> =
+    Lazy::new(|| Mutex::new(Config::get().nvmf_tgt_conf.crd));
+
+/// Returns the CRD policies of the nexuses and of the replicas.
+pub fn crd_policies() -> NvmfCrdPolicies {
+    *CRD_POLICIES.lock().unwrap()
+}
+
+/// Sets the CRD policies of the nexuses and of the replicas, which apply to
+/// the completions from now on.
+pub fn set_crd_policies(policies: NvmfCrdPolicies) {
+    info!("Command retry delay policies set to {policies:?}");
+    *CRD_POLICIES.lock().unwrap() = policies;
+}
+
+/// Registers the JSON-RPC methods of the command retry delays.
+pub(super) fn register_rpc_methods() {
+    // command retry delays set in the failed completions of the nexuses
+    // and of the replicas
+    jsonrpc_register::<(), _, _, JsonRpcError>("mayastor_crd_policies", |_| {
+        async move { Ok(crd_policies()) }.boxed_local()
+    });
+
+    jsonrpc_register::<NvmfCrdPolicies, _, _, JsonRpcError>(
+        "mayastor_crd_policies_set",
+        |args| {
+            async move {
+                set_crd_policies(args);
+                Ok(crd_policies())
+            }
+            .boxed_local()
+        },
+    );
+}
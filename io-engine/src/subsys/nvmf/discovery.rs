@@ -0,0 +1,323 @@
+//! Management of the discovery subsystems.
+//!
+//! The discovery log page read by a host only lists the subsystems the host
+//! is allowed on, but any host may connect to the discovery subsystems. When
+//! restricted, the discovery subsystems only allow the hosts allowed on at
+//! least one subsystem, or any host while one of the subsystems allows any
+//! host. Their hosts follow those of the subsystems as they change.
+//!
+//! The discovery service of the host facing target may also refer the hosts
+//! to the discovery services of other io-engine nodes, so that the hosts
+//! find all the paths of their multipath shares from a single endpoint.
+
+use std::{
+    collections::BTreeSet,
+    ffi::CStr,
+    ptr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+use futures::FutureExt;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use spdk_rs::{
+    ffihelper::copy_str_with_null,
+    libspdk::{
+        spdk_nvmf_referral_opts,
+        spdk_nvmf_subsystem_add_host,
+        spdk_nvmf_subsystem_get_allow_any_host,
+        spdk_nvmf_subsystem_remove_host,
+        spdk_nvmf_subsystem_set_allow_any_host,
+        spdk_nvmf_tgt_add_referral,
+        spdk_nvmf_tgt_remove_referral,
+        SPDK_NVMF_DISCOVERY_NQN,
+    },
+    struct_size_init,
+};
+
+use super::{
+    transport::TransportId,
+    Error,
+    NvmfListener,
+    NvmfSubsystem,
+    SubType,
+    TargetKind,
+    NVMF_TGT,
+};
+use crate::{
+    ffihelper::IntoCString,
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+    subsys::Config,
+};
+
+/// Whether the discovery subsystems only allow the hosts allowed on at least
+/// one subsystem.
+static RESTRICT_HOSTS: AtomicBool = AtomicBool::new(false);
+
+/// Discovery services of other nodes the hosts are referred to.
+static REFERRALS: Lazy<Mutex<Vec<NvmfListener>>> =
+    Lazy::new(|| Mutex::new(Vec::new()));
+
+/// Hosts allowed on the discovery subsystems, and their referrals.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveryInfo {
+    /// Whether the discovery subsystems only allow the hosts allowed on at
+    /// least one subsystem.
+    pub restrict_hosts: bool,
+    /// Whether any host is allowed on the discovery subsystems.
+    pub allow_any_host: bool,
+    /// Hosts allowed on the discovery subsystems, unless any host is.
+    pub hosts: Vec<String>,
+    /// Discovery services of other nodes the hosts are referred to.
+    pub referrals: Vec<NvmfListener>,
+}
+
+/// Sets the discovery subsystems up as configured, once they are created.
+pub(crate) fn init_discovery() {
+    let cfg = &Config::get().nvmf_tgt_conf.discovery;
+    RESTRICT_HOSTS.store(cfg.restrict_hosts, Ordering::SeqCst);
+    sync_discovery_hosts();
+    for referral in &cfg.referrals {
+        if let Err(error) = add_discovery_referral(referral.clone()) {
+            error!("Failed to add discovery referral: {error}");
+        }
+    }
+}
+
+/// Returns the hosts allowed on the discovery subsystems, and their
+/// referrals.
+pub fn discovery_info() -> DiscoveryInfo {
+    let discovery = discovery_subsystems();
+    let allow_any_host = discovery.first().map_or(true, |d| unsafe {
+        spdk_nvmf_subsystem_get_allow_any_host(d.0.as_ptr())
+    });
+    DiscoveryInfo {
+        restrict_hosts: RESTRICT_HOSTS.load(Ordering::SeqCst),
+        allow_any_host,
+        hosts: discovery
+            .first()
+            .map(|d| d.allowed_hosts())
+            .unwrap_or_default(),
+        referrals: REFERRALS.lock().unwrap().clone(),
+    }
+}
+
+/// Restricts the discovery subsystems to the hosts allowed on at least one
+/// subsystem, or allows any host on them again.
+pub fn set_discovery_restrict_hosts(enable: bool) -> DiscoveryInfo {
+    info!("Restricting the hosts of the discovery subsystems: {enable}");
+    RESTRICT_HOSTS.store(enable, Ordering::SeqCst);
+    update_discovery_hosts(enable);
+    discovery_info()
+}
+
+/// The discovery subsystems of the targets.
+fn discovery_subsystems() -> Vec<NvmfSubsystem> {
+    NvmfSubsystem::first()
+        .map(|first| {
+            first
+                .into_iter()
+                .filter(|s| s.subtype() == SubType::Discovery)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Updates the hosts allowed on the discovery subsystems after the hosts of
+/// a subsystem changed. Nothing is done unless restricted, the discovery
+/// subsystems then allowing any host.
+pub(crate) fn sync_discovery_hosts() {
+    if RESTRICT_HOSTS.load(Ordering::SeqCst) {
+        update_discovery_hosts(true);
+    }
+}
+
+/// Allows the hosts allowed on at least one subsystem on the discovery
+/// subsystems when restricted, or any host otherwise.
+fn update_discovery_hosts(restrict: bool) {
+    let Some(first) = NvmfSubsystem::first() else {
+        return;
+    };
+
+    let mut allow_any = !restrict;
+    let mut hosts = BTreeSet::new();
+    let mut discovery = vec![];
+    for subsystem in first.into_iter() {
+        if subsystem.subtype() == SubType::Discovery {
+            discovery.push(subsystem);
+            continue;
+        }
+        if allow_any || unsafe { subsystem.0.as_ref().destroying } {
+            continue;
+        }
+        allow_any = unsafe {
+            spdk_nvmf_subsystem_get_allow_any_host(subsystem.0.as_ptr())
+        };
+        hosts.extend(subsystem.allowed_hosts());
+    }
+
+    // the hosts are updated directly, so that the discovery subsystems are
+    // not synced again
+    for subsystem in discovery {
+        let ss = subsystem.0.as_ptr();
+        unsafe { spdk_nvmf_subsystem_set_allow_any_host(ss, allow_any) };
+
+        let current = subsystem.allowed_hosts();
+        for host in current.iter().filter(|h| allow_any || !hosts.contains(*h))
+        {
+            let host = host.clone().into_cstring();
+            if unsafe { spdk_nvmf_subsystem_remove_host(ss, host.as_ptr()) }
+                != 0
+            {
+                error!(?subsystem, ?host, "Failed to remove discovery host");
+            }
+        }
+        if allow_any {
+            continue;
+        }
+        for host in hosts.iter().filter(|h| !current.contains(*h)) {
+            let host = host.clone().into_cstring();
+            if unsafe {
+                spdk_nvmf_subsystem_add_host(ss, host.as_ptr(), ptr::null_mut())
+            } != 0
+            {
+                error!(?subsystem, ?host, "Failed to add discovery host");
+            }
+        }
+    }
+}
+
+/// Checks a referral, giving it the port of the host facing target when it
+/// has none.
+fn resolve_referral(mut referral: NvmfListener) -> Result<NvmfListener, Error> {
+    let invalid = |reason: &str| Error::InvalidReferral {
+        referral: format!("{referral:?}"),
+        reason: reason.to_string(),
+    };
+    if referral.address.is_none() {
+        return Err(invalid("the address of the referral is required"));
+    }
+    NvmfListener::validate(TargetKind::Nexus, std::slice::from_ref(&referral))
+        .map_err(|e| invalid(&e.to_string()))?;
+    referral.port.get_or_insert(TargetKind::Nexus.port());
+    Ok(referral)
+}
+
+/// The referral options of SPDK for a referral.
+fn referral_opts(
+    referral: &NvmfListener,
+) -> Result<spdk_nvmf_referral_opts, Error> {
+    let TransportId(mut trid) =
+        referral.trid(TargetKind::Nexus, TargetKind::Nexus.port())?;
+    let nqn = CStr::from_bytes_with_nul(SPDK_NVMF_DISCOVERY_NQN)
+        .unwrap()
+        .to_str()
+        .unwrap();
+    copy_str_with_null(nqn, &mut trid.subnqn);
+    Ok(struct_size_init!(
+        spdk_nvmf_referral_opts {
+            trid,
+            secure_channel: false,
+        },
+        size
+    ))
+}
+
+/// Refers the hosts of the discovery service of the host facing target to
+/// the discovery service of another node.
+pub fn add_discovery_referral(
+    referral: NvmfListener,
+) -> Result<DiscoveryInfo, Error> {
+    let referral = resolve_referral(referral)?;
+    let mut referrals = REFERRALS.lock().unwrap();
+    if referrals.contains(&referral) {
+        return Err(Error::ReferralExists {
+            referral: format!("{referral:?}"),
+        });
+    }
+
+    let opts = referral_opts(&referral)?;
+    let tgt = NVMF_TGT.with(|t| t.borrow().tgt_of(TargetKind::Nexus));
+    let rc = unsafe { spdk_nvmf_tgt_add_referral(tgt, &opts) };
+    if rc != 0 {
+        return Err(Error::InvalidReferral {
+            referral: format!("{referral:?}"),
+            reason: format!("failed to add the referral: {rc}"),
+        });
+    }
+    info!(?referral, "Added discovery referral");
+    referrals.push(referral);
+    drop(referrals);
+
+    Ok(discovery_info())
+}
+
+/// Stops referring the hosts to the discovery service of another node.
+pub fn remove_discovery_referral(
+    referral: NvmfListener,
+) -> Result<DiscoveryInfo, Error> {
+    let referral = resolve_referral(referral)?;
+    let mut referrals = REFERRALS.lock().unwrap();
+    let Some(pos) = referrals.iter().position(|r| r == &referral) else {
+        return Err(Error::ReferralNotFound {
+            referral: format!("{referral:?}"),
+        });
+    };
+
+    let opts = referral_opts(&referral)?;
+    let tgt = NVMF_TGT.with(|t| t.borrow().tgt_of(TargetKind::Nexus));
+    let rc = unsafe { spdk_nvmf_tgt_remove_referral(tgt, &opts) };
+    if rc != 0 {
+        warn!(
+            ?referral,
+            rc, "Failed to remove discovery referral from SPDK"
+        );
+    }
+    info!(?referral, "Removed discovery referral");
+    referrals.remove(pos);
+    drop(referrals);
+
+    Ok(discovery_info())
+}
+
+/// Arguments of the `mayastor_discovery_restrict_hosts_set` method.
+#[derive(Debug, Deserialize)]
+struct DiscoveryRestrictArgs {
+    /// Only allow the hosts allowed on at least one subsystem on the
+    /// discovery subsystems.
+    enable: bool,
+}
+
+/// Registers the JSON-RPC methods of the discovery subsystems.
+pub(super) fn register_rpc_methods() {
+    // hosts allowed on the discovery subsystems and referrals to the
+    // discovery services of other nodes
+    jsonrpc_register::<(), _, _, JsonRpcError>("mayastor_discovery", |_| {
+        async move { Ok(discovery_info()) }.boxed_local()
+    });
+
+    jsonrpc_register::<DiscoveryRestrictArgs, _, _, JsonRpcError>(
+        "mayastor_discovery_restrict_hosts_set",
+        |args| {
+            async move { Ok(set_discovery_restrict_hosts(args.enable)) }
+                .boxed_local()
+        },
+    );
+
+    jsonrpc_register::<NvmfListener, _, _, Error>(
+        "mayastor_discovery_referral_add",
+        |referral| {
+            async move { add_discovery_referral(referral) }.boxed_local()
+        },
+    );
+
+    jsonrpc_register::<NvmfListener, _, _, Error>(
+        "mayastor_discovery_referral_remove",
+        |referral| {
+            async move { remove_discovery_referral(referral) }.boxed_local()
+        },
+    );
+}
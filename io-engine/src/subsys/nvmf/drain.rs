@@ -0,0 +1,263 @@
+//! Administrative drain of the I/O of a single subsystem.
+//!
+//! Draining a subsystem pauses its namespace so that no new command is
+//! admitted, then waits for the commands in flight to complete, sampling the
+//! number of outstanding commands on every poll group of its target along the
+//! way. It is a building block for the operations which need a quiesced
+//! subsystem, such as snapshots, ANA state changes and migrations.
+
+use std::time::{Duration, Instant};
+
+use futures::{
+    channel::oneshot,
+    future::{select, Either},
+    FutureExt,
+};
+use serde::{Deserialize, Serialize};
+use spdk_rs::libspdk::spdk_nvmf_poll_group;
+
+use super::{Error, NvmfSubsystem, SubsystemArgs, NVMF_PGS};
+use crate::{
+    core::{Reactor, Reactors},
+    jsonrpc::jsonrpc_register,
+    sleep::mayastor_sleep,
+};
+
+/// Number of commands of a subsystem, summed over the poll groups.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct OutstandingCommands {
+    /// I/O commands submitted to the namespaces and not yet completed.
+    pub io: u64,
+    /// Admin and fabrics commands not yet completed.
+    pub admin: u64,
+    /// Commands queued by the poll groups while the subsystem is paused.
+    pub queued: u64,
+}
+
+impl OutstandingCommands {
+    /// Whether no command is in flight, queued commands being held back by
+    /// the pause itself.
+    pub fn is_drained(&self) -> bool {
+        self.io == 0 && self.admin == 0
+    }
+}
+
+impl std::ops::Add for OutstandingCommands {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            io: self.io + other.io,
+            admin: self.admin + other.admin,
+            queued: self.queued + other.queued,
+        }
+    }
+}
+
+/// Outstanding commands at a point of the drain.
+#[derive(Debug, Clone, Serialize)]
+pub struct DrainSample {
+    /// Time since the start of the drain, in milliseconds.
+    pub elapsed_ms: u64,
+    #[serde(flatten)]
+    pub outstanding: OutstandingCommands,
+}
+
+/// Arguments of a subsystem drain.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DrainArgs {
+    /// NQN of the subsystem.
+    pub nqn: String,
+    /// How long to wait for the outstanding commands, in milliseconds.
+    pub timeout_ms: u64,
+    /// Interval between the samples of the outstanding commands, in
+    /// milliseconds.
+    pub sample_interval_ms: u64,
+}
+
+impl Default for DrainArgs {
+    fn default() -> Self {
+        Self {
+            nqn: String::new(),
+            timeout_ms: 30_000,
+            sample_interval_ms: 100,
+        }
+    }
+}
+
+/// Outcome of a subsystem drain.
+#[derive(Debug, Clone, Serialize)]
+pub struct DrainStats {
+    pub nqn: String,
+    /// Whether all the outstanding commands completed before the timeout.
+    /// Otherwise, the subsystem completes its pause in the background.
+    pub drained: bool,
+    /// Duration of the drain, in milliseconds.
+    pub elapsed_ms: u64,
+    /// Outstanding commands when the drain started.
+    pub initial: OutstandingCommands,
+    /// Outstanding commands whenever they changed during the drain.
+    pub samples: Vec<DrainSample>,
+}
+
+impl NvmfSubsystem {
+    /// Looks up a subsystem by its NQN.
+    pub fn lookup_by_nqn(nqn: &str) -> Result<Self, Error> {
+        Self::find(nqn).ok_or_else(|| Error::NotFound {
+            nqn: nqn.to_string(),
+        })
+    }
+
+    /// Counts the outstanding commands of the subsystem on every poll group
+    /// of its target, on the thread of the poll group.
+    pub async fn outstanding_commands(&self) -> OutstandingCommands {
+        let kind = self.target_kind();
+        let id = unsafe { self.0.as_ref().id };
+        let pgs = NVMF_PGS.with(|pgs| {
+            pgs.borrow()
+                .iter()
+                .filter(|pg| pg.kind == kind)
+                .cloned()
+                .collect::<Vec<_>>()
+        });
+
+        let mut total = OutstandingCommands::default();
+        for pg in pgs {
+            let group = pg.group_ptr();
+            let count = Reactor::spawn_at(&pg.thread, async move {
+                count_outstanding(group, id)
+            });
+            match count {
+                Ok(rx) => total = total + rx.await.unwrap_or_default(),
+                Err(error) => {
+                    error!("Failed to count the commands on {pg:?}: {error}")
+                }
+            }
+        }
+        total
+    }
+
+    /// Drains the I/O of the subsystem: pauses it so that no new command is
+    /// admitted and waits for the outstanding commands to complete. The
+    /// subsystem stays paused until it is resumed.
+    pub async fn drain(
+        &self,
+        timeout: Duration,
+        sample_interval: Duration,
+    ) -> Result<DrainStats, Error> {
+        let nqn = self.get_nqn();
+        let start = Instant::now();
+        let initial = self.outstanding_commands().await;
+        info!("Draining subsystem {nqn}: {initial:?} outstanding");
+
+        let mut samples: Vec<DrainSample> = vec![];
+        let mut last = initial;
+
+        // the pause runs in the background so that it is not cancelled when
+        // the drain times out
+        let (tx, mut pause) = oneshot::channel();
+        let subsystem = NvmfSubsystem(self.0);
+        Reactors::current()
+            .spawn_local(async move {
+                tx.send(subsystem.pause().await).ok();
+            })
+            .detach();
+
+        let drained = loop {
+            let tick = mayastor_sleep(sample_interval);
+            match select(&mut pause, tick).await {
+                Either::Left((result, _)) => {
+                    result.expect("pause sender gone")?;
+                    break true;
+                }
+                Either::Right(_) => {
+                    let outstanding = self.outstanding_commands().await;
+                    if outstanding != last {
+                        samples.push(DrainSample {
+                            elapsed_ms: start.elapsed().as_millis() as u64,
+                            outstanding,
+                        });
+                        last = outstanding;
+                    }
+                    if start.elapsed() >= timeout {
+                        break false;
+                    }
+                }
+            }
+        };
+
+        let stats = DrainStats {
+            nqn,
+            drained,
+            elapsed_ms: start.elapsed().as_millis() as u64,
+            initial,
+            samples,
+        };
+        if drained {
+            info!("Drained subsystem {} in {}ms", stats.nqn, stats.elapsed_ms);
+        } else {
+            warn!(
+                "Subsystem {} not drained after {}ms: {:?} outstanding",
+                stats.nqn, stats.elapsed_ms, last
+            );
+        }
+        Ok(stats)
+    }
+}
+
+/// Counts the outstanding commands of the subsystem with the given id on a
+/// poll group. Must run on the thread of the poll group.
+fn count_outstanding(
+    group: *mut spdk_nvmf_poll_group,
+    id: u32,
+) -> OutstandingCommands {
+    let mut count = OutstandingCommands::default();
+    unsafe {
+        if id >= (*group).num_sgroups {
+            return count;
+        }
+        let sgroup = &*(*group).sgroups.add(id as usize);
+        count.admin = sgroup.mgmt_io_outstanding;
+        for i in 0 .. sgroup.num_ns as usize {
+            count.io += (*sgroup.ns_info.add(i)).io_outstanding;
+        }
+        let mut req = sgroup.queued.tqh_first;
+        while !req.is_null() {
+            count.queued += 1;
+            req = (*req).link.tqe_next;
+        }
+    }
+    count
+}
+
+/// Registers the JSON-RPC methods of the subsystem drain.
+pub(super) fn register_rpc_methods() {
+    // stop admitting new commands to a subsystem and wait for the
+    // outstanding ones, reporting their counts; the subsystem stays
+    // paused until resumed
+    jsonrpc_register::<DrainArgs, _, _, Error>(
+        "mayastor_subsystem_drain",
+        |args| {
+            async move {
+                NvmfSubsystem::lookup_by_nqn(&args.nqn)?
+                    .drain(
+                        Duration::from_millis(args.timeout_ms),
+                        Duration::from_millis(args.sample_interval_ms.max(1)),
+                    )
+                    .await
+            }
+            .boxed_local()
+        },
+    );
+
+    jsonrpc_register::<SubsystemArgs, _, _, Error>(
+        "mayastor_subsystem_resume",
+        |args| {
+            async move {
+                NvmfSubsystem::lookup_by_nqn(&args.nqn)?.resume().await
+            }
+            .boxed_local()
+        },
+    );
+}
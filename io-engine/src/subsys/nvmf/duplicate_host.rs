@@ -0,0 +1,206 @@
+//! Detection of the host NQNs connecting from several addresses.
+//!
+//! A host NQN identifies a single host, but cloning the image of a VM
+//! without generating a new NQN gives several hosts the same one. The target
+//! then takes them for one host: their reservations are mixed up, and their
+//! paths are taken for paths to the same multipath device. Whenever a host
+//! NQN connects a controller from a transport address other than those its
+//! connected controllers use, on any subsystem of the node, the connect is
+//! warned about and an event emitted with both addresses.
+//!
+//! A host with several interfaces may connect from several addresses on
+//! purpose, so the new controller is only disconnected when the node is set
+//! to reject the duplicate hosts.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use events_api::event::{EventAction, EventMeta};
+use futures::FutureExt;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use spdk_rs::{
+    libspdk::{
+        spdk_nvme_transport_id,
+        spdk_nvmf_qpair_disconnect,
+        spdk_nvmf_qpair_get_peer_trid,
+    },
+    NvmfController,
+};
+
+use crate::{
+    core::MayastorEnvironment,
+    eventing::{host_events::duplicate_host_event_meta, EventWithMeta},
+    ffihelper::AsStr,
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+};
+
+/// Addresses the controllers of the hosts connected from, by host NQN and
+/// by subsystem NQN and controller ID.
+static HOST_ADDRESSES: Lazy<
+    Mutex<HashMap<String, BTreeMap<(String, u16), String>>>,
+> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Connects from a second address detected.
+static DETECTED: AtomicU64 = AtomicU64::new(0);
+/// Controllers disconnected as connected from a second address.
+static REJECTED: AtomicU64 = AtomicU64::new(0);
+
+/// A host NQN connected from several addresses.
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateHost {
+    pub hostnqn: String,
+    /// Addresses the controllers of the host connected from.
+    pub addresses: Vec<String>,
+    /// Subsystems the host is connected to.
+    pub subsystems: Vec<String>,
+}
+
+/// Report of the host NQNs connected from several addresses.
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateHosts {
+    /// Whether the controllers connected from a second address are
+    /// disconnected.
+    pub reject: bool,
+    /// Connects from a second address detected.
+    pub detected: u64,
+    /// Controllers disconnected as connected from a second address.
+    pub rejected: u64,
+    /// Host NQNs currently connected from several addresses.
+    pub hosts: Vec<DuplicateHost>,
+}
+
+/// Returns the host NQNs connected from several addresses.
+pub fn duplicate_hosts() -> DuplicateHosts {
+    let hosts = HOST_ADDRESSES
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|(hostnqn, ctrlrs)| {
+            let addresses = ctrlrs.values().cloned().collect::<BTreeSet<_>>();
+            (addresses.len() > 1).then(|| DuplicateHost {
+                hostnqn: hostnqn.clone(),
+                addresses: addresses.into_iter().collect(),
+                subsystems: ctrlrs
+                    .keys()
+                    .map(|(nqn, _)| nqn.clone())
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .collect(),
+            })
+        })
+        .collect();
+
+    DuplicateHosts {
+        reject: MayastorEnvironment::global_or_default().reject_duplicate_hosts,
+        detected: DETECTED.load(Ordering::Relaxed),
+        rejected: REJECTED.load(Ordering::Relaxed),
+        hosts,
+    }
+}
+
+/// Returns the address the controller of a host connected from.
+fn peer_address(ctrlr: &NvmfController) -> Option<String> {
+    let qpair = unsafe { (*ctrlr.0.as_ptr()).admin_qpair };
+    if qpair.is_null() {
+        return None;
+    }
+    let mut trid = spdk_nvme_transport_id::default();
+    (unsafe { spdk_nvmf_qpair_get_peer_trid(qpair, &mut trid) } == 0)
+        .then(|| trid.traddr.as_str().to_string())
+}
+
+/// Records the address a controller of a host connected to a subsystem
+/// from, warning about the host and emitting an event when its other
+/// controllers connected from another address. The controller is then
+/// disconnected if the node rejects the duplicate hosts.
+pub(super) fn host_connected(
+    nqn: &str,
+    ctrlr: &NvmfController,
+    meta: EventMeta,
+) {
+    let Some(address) = peer_address(ctrlr) else {
+        return;
+    };
+    let hostnqn = ctrlr.hostnqn();
+    let cntlid = unsafe { (*ctrlr.0.as_ptr()).cntlid };
+    let reject =
+        MayastorEnvironment::global_or_default().reject_duplicate_hosts;
+
+    let first = {
+        let mut hosts = HOST_ADDRESSES.lock().unwrap();
+        let ctrlrs = hosts.entry(hostnqn.clone()).or_default();
+        let first = ctrlrs.values().find(|a| **a != address).cloned();
+        if first.is_none() || !reject {
+            ctrlrs.insert((nqn.to_string(), cntlid), address.clone());
+        }
+        first
+    };
+    let Some(first) = first else {
+        return;
+    };
+
+    DETECTED.fetch_add(1, Ordering::Relaxed);
+    warn!(
+        "Host '{hostnqn}' connected to {nqn} from {address} while connected \
+        from {first}: its NQN may be used by several hosts{}",
+        if reject { ", disconnecting" } else { "" }
+    );
+    ctrlr
+        .event(
+            EventAction::StateChange,
+            duplicate_host_event_meta(meta, &first, &address),
+        )
+        .generate();
+
+    if reject {
+        REJECTED.fetch_add(1, Ordering::Relaxed);
+        let rc = unsafe {
+            spdk_nvmf_qpair_disconnect((*ctrlr.0.as_ptr()).admin_qpair)
+        };
+        if rc != 0 {
+            error!(
+                "Failed to disconnect controller {cntlid} of host \
+                '{hostnqn}' from {nqn}: {rc}"
+            );
+        }
+    }
+}
+
+/// Forgets the address of a controller which disconnected from a subsystem.
+pub(super) fn host_disconnected(nqn: &str, ctrlr: &NvmfController) {
+    let hostnqn = ctrlr.hostnqn();
+    let cntlid = unsafe { (*ctrlr.0.as_ptr()).cntlid };
+    let mut hosts = HOST_ADDRESSES.lock().unwrap();
+    if let Some(ctrlrs) = hosts.get_mut(&hostnqn) {
+        ctrlrs.remove(&(nqn.to_string(), cntlid));
+        if ctrlrs.is_empty() {
+            hosts.remove(&hostnqn);
+        }
+    }
+}
+
+/// Forgets the addresses of the controllers of a subsystem which is being
+/// destroyed.
+pub(crate) fn forget_duplicate_hosts(nqn: &str) {
+    let mut hosts = HOST_ADDRESSES.lock().unwrap();
+    for ctrlrs in hosts.values_mut() {
+        ctrlrs.retain(|(n, _), _| n != nqn);
+    }
+    hosts.retain(|_, ctrlrs| !ctrlrs.is_empty());
+}
+
+/// Registers the JSON-RPC methods of the duplicate hosts.
+pub(super) fn register_rpc_methods() {
+    // host NQNs connected from several addresses, likely used by
+    // several hosts
+    jsonrpc_register::<(), _, _, JsonRpcError>(
+        "mayastor_duplicate_hosts",
+        |_| async move { Ok(duplicate_hosts()) }.boxed_local(),
+    );
+}
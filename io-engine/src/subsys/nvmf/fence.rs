@@ -0,0 +1,172 @@
+//! Fencing of hosts from the subsystems.
+//!
+//! When a node is evicted, its hosts must be guaranteed to no longer write to
+//! the shares it used, even if they come back. A fenced host is removed from
+//! the allowed hosts of the subsystem and disconnected; the target then
+//! rejects its connects as for any host which is not allowed. It may not be
+//! allowed again, whether directly or through a host group, until unfenced.
+//! As this relies on the allowed hosts, only a subsystem which restricts its
+//! hosts may fence one.
+//!
+//! The registrations of the fenced host with the namespaces of the subsystem
+//! are removed as well, releasing its reservations, so that they do not
+//! block the other hosts. They can only be found while the host is
+//! connected, as they only carry its host ID.
+//!
+//! When a ptpl directory is configured, the fenced hosts are kept in a file
+//! under it, so that they stay fenced when their subsystems are created
+//! again after a restart.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use once_cell::sync::Lazy;
+use spdk_rs::libspdk::spdk_nvmf_subsystem_get_allow_any_host;
+
+use super::{
+    share_state::{load_state, save_state},
+    Error,
+    NvmfSubsystem,
+};
+use crate::core::MayastorEnvironment;
+
+/// File of the fenced hosts, within the ptpl directory.
+const FENCED_HOSTS_FILE: &str = "nvmf-fenced-hosts.json";
+
+type Fences = BTreeMap<String, BTreeSet<String>>;
+
+/// Fenced hosts, by subsystem NQN, as loaded from their file.
+static FENCED_HOSTS: Lazy<Mutex<Fences>> = Lazy::new(|| {
+    let fences = fences_path()
+        .and_then(|path| load_state::<Fences>(&path))
+        .unwrap_or_default();
+    if !fences.is_empty() {
+        info!("Loaded the fenced hosts of {} subsystems", fences.len());
+    }
+    Mutex::new(fences)
+});
+
+/// Path of the fenced hosts file, if a ptpl directory is configured.
+fn fences_path() -> Option<PathBuf> {
+    MayastorEnvironment::global_or_default()
+        .ptpl_dir()
+        .map(|dir| Path::new(&dir).join(FENCED_HOSTS_FILE))
+}
+
+/// Applies a change to the fenced hosts of a subsystem, persisting them
+/// before the change takes effect so that a failure leaves them as they
+/// were.
+fn change_fences<T>(
+    nqn: &str,
+    f: impl FnOnce(&mut Fences) -> T,
+) -> Result<T, Error> {
+    let mut fences = FENCED_HOSTS.lock().unwrap();
+    let mut changed = fences.clone();
+    let result = f(&mut changed);
+    if changed != *fences {
+        if let Some(path) = fences_path() {
+            save_state(&path, &changed).map_err(|error| {
+                Error::PersistFence {
+                    nqn: nqn.to_string(),
+                    reason: error.to_string(),
+                }
+            })?;
+        }
+        *fences = changed;
+    }
+    Ok(result)
+}
+
+/// Forgets the fenced hosts of a subsystem which is being destroyed.
+pub(crate) fn forget_fence(nqn: &str) {
+    if let Err(error) = change_fences(nqn, |fences| fences.remove(nqn)) {
+        error!(%error, "Failed to forget the fenced hosts");
+    }
+}
+
+/// Determines if a host is fenced from a subsystem.
+pub(crate) fn is_fenced(nqn: &str, host: &str) -> bool {
+    FENCED_HOSTS
+        .lock()
+        .unwrap()
+        .get(nqn)
+        .map_or(false, |hosts| hosts.contains(host))
+}
+
+impl NvmfSubsystem {
+    /// Fences a host from the subsystem: it is disallowed and disconnected,
+    /// its registrations are removed, and it may not be allowed again until
+    /// unfenced.
+    pub async fn fence_host(&self, host: &str) -> Result<(), Error> {
+        let nqn = self.get_nqn();
+        if unsafe { spdk_nvmf_subsystem_get_allow_any_host(self.0.as_ptr()) } {
+            return Err(Error::InvalidFence {
+                nqn,
+                reason: "any host is allowed".to_string(),
+            });
+        }
+
+        // fenced first, so that the host may not be allowed again meanwhile
+        change_fences(&nqn, |fences| {
+            fences
+                .entry(nqn.clone())
+                .or_default()
+                .insert(host.to_string())
+        })?;
+
+        if self.allowed_hosts().iter().any(|h| h == host) {
+            self.disallow_host(host)?;
+        }
+        // the IDs of the host are gone along with its controllers
+        let hostids = self.connected_hostids(host);
+        if !hostids.is_empty() {
+            self.disconnect_host(host).await?;
+        }
+        for hostid in hostids {
+            let preempted = self.preempt_host_registrations(hostid).await?;
+            if preempted > 0 {
+                info!(
+                    nqn,
+                    host,
+                    preempted,
+                    "Removed the registrations of the fenced host"
+                );
+            }
+        }
+        info!("Host '{host}' fenced from subsystem '{nqn}'");
+        Ok(())
+    }
+
+    /// Unfences a host from the subsystem, which does not allow it again.
+    /// Returns whether it was fenced.
+    pub fn unfence_host(&self, host: &str) -> Result<bool, Error> {
+        let nqn = self.get_nqn();
+        let removed = change_fences(&nqn, |fences| {
+            let Some(hosts) = fences.get_mut(&nqn) else {
+                return false;
+            };
+            let removed = hosts.remove(host);
+            if hosts.is_empty() {
+                fences.remove(&nqn);
+            }
+            removed
+        })?;
+        if removed {
+            info!("Host '{host}' unfenced from subsystem '{nqn}'");
+        }
+        Ok(removed)
+    }
+
+    /// Returns the hosts fenced from the subsystem.
+    pub fn fenced_hosts(&self) -> Vec<String> {
+        FENCED_HOSTS
+            .lock()
+            .unwrap()
+            .get(&self.get_nqn())
+            .map(|hosts| hosts.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
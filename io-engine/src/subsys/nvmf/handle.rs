@@ -0,0 +1,192 @@
+//! Handle on a subsystem typed after its state.
+//!
+//! SPDK only destroys a subsystem which is stopped, and only removes its
+//! namespaces while it is paused or stopped. A `NvmfSubsystemHandle` carries
+//! the state of its subsystem in its type, and every state change consumes
+//! it, so that a subsystem is only destroyed from a state which allows it:
+//! an active subsystem must be paused or stopped first.
+//!
+//! The destruction removes the namespaces of the subsystem, and completes
+//! once SPDK is done with it, i.e. once its controllers are disconnected.
+
+use std::{marker::PhantomData, os::raw::c_void};
+
+use futures::channel::oneshot;
+use nix::errno::Errno;
+use spdk_rs::libspdk::{
+    spdk_nvmf_subsystem_remove_ns,
+    SPDK_NVMF_SUBSYSTEM_ACTIVE,
+    SPDK_NVMF_SUBSYSTEM_INACTIVE,
+    SPDK_NVMF_SUBSYSTEM_PAUSED,
+};
+
+use super::{Error, NvmfSubsystem};
+use crate::ffihelper::cb_arg;
+
+/// State of an active subsystem.
+#[derive(Debug)]
+pub struct Active;
+
+/// State of a paused subsystem.
+#[derive(Debug)]
+pub struct Paused;
+
+/// State of a stopped subsystem.
+#[derive(Debug)]
+pub struct Stopped;
+
+/// Handle on a subsystem in the state `S`.
+#[derive(Debug)]
+pub struct NvmfSubsystemHandle<S> {
+    subsystem: NvmfSubsystem,
+    _state: PhantomData<S>,
+}
+
+/// Handle on a subsystem in its current state.
+#[derive(Debug)]
+pub enum SubsystemHandle {
+    Active(NvmfSubsystemHandle<Active>),
+    Paused(NvmfSubsystemHandle<Paused>),
+    Stopped(NvmfSubsystemHandle<Stopped>),
+}
+
+impl<S> NvmfSubsystemHandle<S> {
+    fn new(subsystem: NvmfSubsystem) -> Self {
+        Self {
+            subsystem,
+            _state: PhantomData,
+        }
+    }
+
+    /// The subsystem of the handle.
+    pub fn subsystem(&self) -> &NvmfSubsystem {
+        &self.subsystem
+    }
+
+    /// NQN of the subsystem.
+    pub fn nqn(&self) -> String {
+        self.subsystem.get_nqn()
+    }
+}
+
+impl NvmfSubsystemHandle<Active> {
+    /// Pauses the subsystem.
+    pub async fn pause(self) -> Result<NvmfSubsystemHandle<Paused>, Error> {
+        self.subsystem.pause().await?;
+        Ok(NvmfSubsystemHandle::new(self.subsystem))
+    }
+
+    /// Stops the subsystem.
+    pub async fn stop(self) -> Result<NvmfSubsystemHandle<Stopped>, Error> {
+        self.subsystem.stop().await?;
+        Ok(NvmfSubsystemHandle::new(self.subsystem))
+    }
+}
+
+impl NvmfSubsystemHandle<Paused> {
+    /// Resumes the subsystem.
+    pub async fn resume(self) -> Result<NvmfSubsystemHandle<Active>, Error> {
+        self.subsystem.resume().await?;
+        Ok(NvmfSubsystemHandle::new(self.subsystem))
+    }
+
+    /// Stops the subsystem.
+    pub async fn stop(self) -> Result<NvmfSubsystemHandle<Stopped>, Error> {
+        self.subsystem.stop().await?;
+        Ok(NvmfSubsystemHandle::new(self.subsystem))
+    }
+
+    /// Removes the namespaces of the subsystem while no command reaches
+    /// them, then stops and destroys it.
+    pub async fn destroy(self) -> Result<(), Error> {
+        self.subsystem.remove_namespaces();
+        self.stop().await?.destroy().await
+    }
+}
+
+impl NvmfSubsystemHandle<Stopped> {
+    /// Starts the subsystem again, with the listeners it already has.
+    pub async fn start(self) -> Result<NvmfSubsystemHandle<Active>, Error> {
+        self.subsystem.restart().await?;
+        Ok(NvmfSubsystemHandle::new(self.subsystem))
+    }
+
+    /// Removes the namespaces of the subsystem and destroys it, completing
+    /// once SPDK is done with it.
+    pub async fn destroy(self) -> Result<(), Error> {
+        extern "C" fn destroy_cb(arg: *mut c_void) {
+            let s = unsafe { Box::from_raw(arg as *mut oneshot::Sender<()>) };
+            s.send(()).ok();
+        }
+
+        let subsystem = self.subsystem;
+        subsystem.remove_namespaces();
+
+        let (s, r) = oneshot::channel::<()>();
+        let arg = cb_arg(s);
+        let rc = unsafe { subsystem.destroy_with(Some(destroy_cb), arg) };
+        if rc != -libc::EINPROGRESS {
+            // the callback is only called when the destruction is ongoing
+            drop(unsafe { Box::from_raw(arg as *mut oneshot::Sender<()>) });
+        }
+
+        match rc {
+            0 => Ok(()),
+            e if e == -libc::EINPROGRESS => {
+                r.await.ok();
+                Ok(())
+            }
+            e => Err(Error::Subsystem {
+                source: Errno::from_i32(-e),
+                nqn: subsystem.get_nqn(),
+                msg: "failed to destroy".to_string(),
+            }),
+        }
+    }
+}
+
+impl NvmfSubsystem {
+    /// Returns a handle on the subsystem typed after its current state.
+    /// Fails while the subsystem is changing state.
+    pub fn into_handle(self) -> Result<SubsystemHandle, Error> {
+        match unsafe { self.0.as_ref().state } {
+            SPDK_NVMF_SUBSYSTEM_ACTIVE => {
+                Ok(SubsystemHandle::Active(NvmfSubsystemHandle::new(self)))
+            }
+            SPDK_NVMF_SUBSYSTEM_PAUSED => {
+                Ok(SubsystemHandle::Paused(NvmfSubsystemHandle::new(self)))
+            }
+            SPDK_NVMF_SUBSYSTEM_INACTIVE => {
+                Ok(SubsystemHandle::Stopped(NvmfSubsystemHandle::new(self)))
+            }
+            _ => Err(Error::SubsystemBusy {
+                nqn: self.get_nqn(),
+                op: "get a handle on".to_string(),
+            }),
+        }
+    }
+
+    /// Pauses the subsystem if it is active, removes its namespaces, then
+    /// stops and destroys it.
+    pub async fn shutdown(&self) -> Result<(), Error> {
+        match NvmfSubsystem(self.0).into_handle()? {
+            SubsystemHandle::Active(h) => h.pause().await?.destroy().await,
+            SubsystemHandle::Paused(h) => h.destroy().await,
+            SubsystemHandle::Stopped(h) => h.destroy().await,
+        }
+    }
+
+    /// Removes all the namespaces of the subsystem, which must be paused or
+    /// stopped.
+    fn remove_namespaces(&self) {
+        self.stop_counting_host_io();
+        for ns in self.namespaces() {
+            let rc = unsafe {
+                spdk_nvmf_subsystem_remove_ns(self.0.as_ptr(), ns.nsid)
+            };
+            if rc != 0 {
+                error!(?self, nsid = ns.nsid, "failed to remove namespace");
+            }
+        }
+    }
+}
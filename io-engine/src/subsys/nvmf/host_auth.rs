@@ -0,0 +1,257 @@
+//! In-band authentication (DH-HMAC-CHAP) of the allowed hosts.
+//!
+//! A share may require some of its allowed hosts to authenticate when they
+//! connect, and optionally to authenticate the controller in turn, rather
+//! than relying on their NQN only. The secrets are not passed along with the
+//! share: they are loaded into the SPDK keyring beforehand, e.g. with the
+//! `keyring_file_add_key` method, and the share refers to them by key name.
+//!
+//! The key names of the hosts are kept with the share state of the
+//! subsystem, so that a share created again after a restart still requires
+//! the hosts to authenticate; it fails if their keys are not back in the
+//! keyring by then.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    ptr::NonNull,
+    sync::Mutex,
+};
+
+use futures::FutureExt;
+use nix::errno::Errno;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use spdk_rs::{
+    libspdk::{
+        spdk_key,
+        spdk_keyring_get_key,
+        spdk_keyring_put_key,
+        spdk_nvmf_host_opts,
+        spdk_nvmf_subsystem_add_host_ext,
+    },
+    struct_size_init,
+};
+
+use super::{
+    discovery::sync_discovery_hosts,
+    share_state::record_host_auth,
+    Error,
+    NvmfSubsystem,
+    SubsystemArgs,
+};
+use crate::{
+    ffihelper::{FfiResult, IntoCString},
+    jsonrpc::jsonrpc_register,
+};
+
+/// DH-HMAC-CHAP keys an allowed host authenticates with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HostDhChap {
+    /// NQN of the host.
+    pub host: String,
+    /// Name of the keyring key the host authenticates with.
+    pub key: String,
+    /// Name of the keyring key the controller authenticates with, when the
+    /// host requires bidirectional authentication.
+    #[serde(default)]
+    pub ctrlr_key: Option<String>,
+}
+
+/// Keys of the hosts of the subsystems, by subsystem NQN.
+static SUBSYSTEM_AUTH: Lazy<Mutex<HashMap<String, Vec<HostDhChap>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Forgets the keys of the hosts of a subsystem which is being destroyed.
+pub(crate) fn forget_host_auth(nqn: &str) {
+    SUBSYSTEM_AUTH.lock().unwrap().remove(nqn);
+}
+
+/// Keys of a host of a subsystem, if it must authenticate.
+pub(crate) fn host_keys(nqn: &str, host: &str) -> Option<HostDhChap> {
+    SUBSYSTEM_AUTH
+        .lock()
+        .unwrap()
+        .get(nqn)
+        .and_then(|auth| auth.iter().find(|a| a.host == host).cloned())
+}
+
+/// Reference to a key of the SPDK keyring, released when dropped.
+struct KeyringKey(NonNull<spdk_key>);
+
+impl KeyringKey {
+    /// Looks up a key by name.
+    fn get(name: &str) -> Result<Self, Error> {
+        let key = unsafe { spdk_keyring_get_key(name.into_cstring().as_ptr()) };
+        NonNull::new(key)
+            .map(Self)
+            .ok_or_else(|| Error::KeyNotFound {
+                name: name.to_string(),
+            })
+    }
+}
+
+impl Drop for KeyringKey {
+    fn drop(&mut self) {
+        unsafe { spdk_keyring_put_key(self.0.as_ptr()) }
+    }
+}
+
+impl HostDhChap {
+    /// Checks that the hosts to authenticate are given once, are among the
+    /// given allowed hosts and that their keys are in the keyring.
+    pub fn validate(auth: &[Self], allowed: &[String]) -> Result<(), Error> {
+        let mut hosts = BTreeSet::new();
+        for a in auth {
+            let reason = if !hosts.insert(a.host.as_str()) {
+                "the host is given more than once"
+            } else if !allowed.contains(&a.host) {
+                "the host is not an allowed host of the share"
+            } else {
+                KeyringKey::get(&a.key)?;
+                if let Some(ctrlr_key) = &a.ctrlr_key {
+                    KeyringKey::get(ctrlr_key)?;
+                }
+                continue;
+            };
+            return Err(Error::InvalidHostAuth {
+                host: a.host.clone(),
+                reason: reason.to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl NvmfSubsystem {
+    /// Get the keys of the hosts which must authenticate.
+    pub fn host_auth(&self) -> Vec<HostDhChap> {
+        SUBSYSTEM_AUTH
+            .lock()
+            .unwrap()
+            .get(&self.get_nqn())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Requires the given allowed hosts to authenticate with their keys, and
+    /// the others to no longer authenticate. The hosts whose keys change are
+    /// disconnected so that they connect again with the new keys.
+    pub async fn apply_host_auth(
+        &self,
+        auth: &[HostDhChap],
+    ) -> Result<(), Error> {
+        let allowed = if unsafe { self.0.as_ref().allow_any_host } {
+            vec![]
+        } else {
+            self.allowed_hosts()
+        };
+        HostDhChap::validate(auth, &allowed)?;
+
+        let nqn = self.get_nqn();
+        let previous = self.host_auth();
+        let keys = |auth: &[HostDhChap], host: &str| {
+            auth.iter().find(|a| a.host == host).cloned()
+        };
+        let changed = allowed
+            .into_iter()
+            .filter(|host| keys(&previous, host) != keys(auth, host))
+            .collect::<Vec<_>>();
+
+        // the keys of a host are set when it is added
+        let result = changed.iter().try_for_each(|host| {
+            self.remove_host(host)?;
+            self.add_host_with(&nqn, host, keys(auth, host).as_ref())
+        });
+        sync_discovery_hosts();
+        result?;
+
+        {
+            let mut subsystem_auth = SUBSYSTEM_AUTH.lock().unwrap();
+            if auth.is_empty() {
+                subsystem_auth.remove(&nqn);
+            } else {
+                subsystem_auth.insert(nqn.clone(), auth.to_vec());
+            }
+        }
+        record_host_auth(&nqn, auth);
+
+        for host in changed {
+            self.disconnect_host(&host).await?;
+        }
+        Ok(())
+    }
+
+    /// Allows a host to connect to the subsystem, authenticating with the
+    /// given keys.
+    pub(super) fn allow_host_with_keys(
+        &self,
+        auth: &HostDhChap,
+    ) -> Result<(), Error> {
+        let key = KeyringKey::get(&auth.key)?;
+        let ctrlr_key =
+            auth.ctrlr_key.as_deref().map(KeyringKey::get).transpose()?;
+
+        // the subsystem takes its own references to the keys
+        let mut opts = struct_size_init!(
+            spdk_nvmf_host_opts {
+                params: std::ptr::null(),
+                dhchap_key: key.0.as_ptr(),
+                dhchap_ctrlr_key: ctrlr_key
+                    .as_ref()
+                    .map_or(std::ptr::null_mut(), |k| k.0.as_ptr()),
+            },
+            size
+        );
+        let host = Self::cstr(&auth.host)?;
+        unsafe {
+            spdk_nvmf_subsystem_add_host_ext(
+                self.0.as_ptr(),
+                host.as_ptr(),
+                &mut opts,
+            )
+        }
+        .to_result(|errno| Error::Subsystem {
+            source: Errno::from_i32(errno),
+            nqn: self.get_nqn(),
+            msg: format!("failed to add authenticated host: {host:?}"),
+        })
+    }
+}
+
+/// Arguments of the `mayastor_subsystem_host_auth_set` method.
+#[derive(Debug, Deserialize)]
+struct HostAuthArgs {
+    /// NQN of the subsystem.
+    nqn: String,
+    /// Keys of the allowed hosts which must authenticate, the others no
+    /// longer authenticate.
+    #[serde(default)]
+    host_auth: Vec<HostDhChap>,
+}
+
+/// Registers the JSON-RPC methods of the host authentication.
+pub(super) fn register_rpc_methods() {
+    // DH-HMAC-CHAP keys the allowed hosts of a share authenticate with
+    jsonrpc_register::<HostAuthArgs, _, _, Error>(
+        "mayastor_subsystem_host_auth_set",
+        |args| {
+            async move {
+                NvmfSubsystem::lookup_by_nqn(&args.nqn)?
+                    .apply_host_auth(&args.host_auth)
+                    .await
+            }
+            .boxed_local()
+        },
+    );
+
+    jsonrpc_register::<SubsystemArgs, _, _, Error>(
+        "mayastor_subsystem_host_auth_list",
+        |args| {
+            async move {
+                Ok(NvmfSubsystem::lookup_by_nqn(&args.nqn)?.host_auth())
+            }
+            .boxed_local()
+        },
+    );
+}
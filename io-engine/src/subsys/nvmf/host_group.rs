@@ -0,0 +1,390 @@
+//! Host groups: named sets of host NQNs.
+//!
+//! The allowed hosts of a share may refer to a host group as
+//! `hostgroup:<name>`, so that allowing a cluster of hosts onto many volumes
+//! does not require an allowed host entry per host and volume. Whenever the
+//! members of a group change, the allowed hosts of all the subsystems
+//! referring to it are updated.
+//!
+//! When a ptpl directory is configured, the host groups are kept in a file
+//! under it, so that the shares which refer to them can be created again
+//! after a restart.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use futures::FutureExt;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    share_mode::NvmfShareMode,
+    share_state::{load_state, record_allowed_hosts, save_state},
+    subsystem::validate_nqn,
+    Error,
+    NvmfSubsystem,
+};
+use crate::{
+    core::MayastorEnvironment,
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+};
+
+/// Prefix of the allowed hosts which refer to a host group.
+pub const HOST_GROUP_PREFIX: &str = "hostgroup:";
+
+/// A named set of host NQNs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HostGroup {
+    pub name: String,
+    #[serde(default)]
+    pub hosts: BTreeSet<String>,
+}
+
+/// File of the host groups, within the ptpl directory.
+const HOST_GROUPS_FILE: &str = "nvmf-host-groups.json";
+
+type Groups = BTreeMap<String, BTreeSet<String>>;
+
+/// Members of the host groups, by group name, as loaded from their file.
+static HOST_GROUPS: Lazy<Mutex<Groups>> = Lazy::new(|| {
+    let groups = groups_path()
+        .and_then(|path| load_state::<Groups>(&path))
+        .unwrap_or_default();
+    if !groups.is_empty() {
+        info!("Loaded {} persisted host groups", groups.len());
+    }
+    Mutex::new(groups)
+});
+
+/// Allowed hosts of the subsystems referring to host groups, as requested
+/// by their shares, by subsystem NQN.
+static SUBSYSTEM_HOSTS: Lazy<Mutex<HashMap<String, Vec<String>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Path of the host groups file, if a ptpl directory is configured.
+fn groups_path() -> Option<PathBuf> {
+    MayastorEnvironment::global_or_default()
+        .ptpl_dir()
+        .map(|dir| Path::new(&dir).join(HOST_GROUPS_FILE))
+}
+
+/// Applies a change to the host groups, persisting them before the change
+/// takes effect so that a failure leaves the groups as they were.
+fn change_groups<T>(
+    name: &str,
+    f: impl FnOnce(&mut Groups) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let mut groups = HOST_GROUPS.lock().unwrap();
+    let mut changed = groups.clone();
+    let result = f(&mut changed)?;
+    if let Some(path) = groups_path() {
+        save_state(&path, &changed).map_err(|error| {
+            Error::PersistHostGroup {
+                name: name.to_string(),
+                reason: error.to_string(),
+            }
+        })?;
+    }
+    *groups = changed;
+    Ok(result)
+}
+
+/// Name of the host group an allowed host refers to, if any.
+fn group_name(host: &str) -> Option<&str> {
+    host.strip_prefix(HOST_GROUP_PREFIX)
+}
+
+/// Replaces the references to host groups by their members, removing the
+/// duplicates.
+pub fn expand_hosts(hosts: &[String]) -> Result<Vec<String>, Error> {
+    let groups = HOST_GROUPS.lock().unwrap();
+    let mut expanded = BTreeSet::new();
+    for host in hosts {
+        match group_name(host) {
+            Some(name) => {
+                let members = groups.get(name).ok_or_else(|| {
+                    Error::HostGroupNotFound {
+                        name: name.to_string(),
+                    }
+                })?;
+                expanded.extend(members.iter().cloned());
+            }
+            None => {
+                expanded.insert(host.clone());
+            }
+        }
+    }
+    Ok(expanded.into_iter().collect())
+}
+
+/// Forgets the allowed hosts of a subsystem which is being destroyed.
+pub(crate) fn forget_subsystem(nqn: &str) {
+    SUBSYSTEM_HOSTS.lock().unwrap().remove(nqn);
+}
+
+impl HostGroup {
+    /// Creates a host group.
+    pub fn create(self) -> Result<Self, Error> {
+        validate_group_name(&self.name)?;
+        validate_hosts(self.hosts.iter())?;
+
+        change_groups(&self.name, |groups| {
+            if groups.contains_key(&self.name) {
+                return Err(Error::HostGroupExists {
+                    name: self.name.clone(),
+                });
+            }
+            groups.insert(self.name.clone(), self.hosts.clone());
+            Ok(())
+        })?;
+        info!(
+            "Created host group {} with {} hosts",
+            self.name,
+            self.hosts.len()
+        );
+        Ok(self)
+    }
+
+    /// Deletes a host group, which must not be referred to by any share.
+    pub fn delete(name: &str) -> Result<(), Error> {
+        let reference = format!("{HOST_GROUP_PREFIX}{name}");
+        if SUBSYSTEM_HOSTS
+            .lock()
+            .unwrap()
+            .values()
+            .any(|hosts| hosts.contains(&reference))
+        {
+            return Err(Error::HostGroupInUse {
+                name: name.to_string(),
+            });
+        }
+
+        change_groups(name, |groups| {
+            groups.remove(name).ok_or_else(|| Error::HostGroupNotFound {
+                name: name.to_string(),
+            })
+        })?;
+        info!("Deleted host group {name}");
+        Ok(())
+    }
+
+    /// Looks up a host group by its name.
+    pub fn lookup(name: &str) -> Option<Self> {
+        HOST_GROUPS.lock().unwrap().get(name).map(|hosts| Self {
+            name: name.to_string(),
+            hosts: hosts.clone(),
+        })
+    }
+
+    /// Returns all the host groups.
+    pub fn list() -> Vec<Self> {
+        HOST_GROUPS
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, hosts)| Self {
+                name: name.clone(),
+                hosts: hosts.clone(),
+            })
+            .collect()
+    }
+
+    /// Adds hosts to a group, allowing them on the subsystems referring to
+    /// the group.
+    pub async fn add_hosts(
+        name: &str,
+        hosts: &BTreeSet<String>,
+    ) -> Result<Self, Error> {
+        validate_hosts(hosts.iter())?;
+        Self::update(name, |members| members.extend(hosts.iter().cloned()))
+            .await
+    }
+
+    /// Removes hosts from a group, disallowing and disconnecting them from
+    /// the subsystems referring to the group unless allowed otherwise.
+    pub async fn remove_hosts(
+        name: &str,
+        hosts: &BTreeSet<String>,
+    ) -> Result<Self, Error> {
+        Self::update(name, |members| members.retain(|h| !hosts.contains(h)))
+            .await
+    }
+
+    /// Updates the members of a group and the allowed hosts of the
+    /// subsystems referring to it.
+    async fn update(
+        name: &str,
+        f: impl FnOnce(&mut BTreeSet<String>),
+    ) -> Result<Self, Error> {
+        let group = change_groups(name, |groups| {
+            let hosts = groups.get_mut(name).ok_or_else(|| {
+                Error::HostGroupNotFound {
+                    name: name.to_string(),
+                }
+            })?;
+            f(hosts);
+            Ok(Self {
+                name: name.to_string(),
+                hosts: hosts.clone(),
+            })
+        })?;
+
+        let reference = format!("{HOST_GROUP_PREFIX}{name}");
+        let subsystems = SUBSYSTEM_HOSTS
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, hosts)| hosts.contains(&reference))
+            .map(|(nqn, hosts)| (nqn.clone(), hosts.clone()))
+            .collect::<Vec<_>>();
+
+        let mut result = Ok(());
+        for (nqn, hosts) in subsystems {
+            let Ok(subsystem) = NvmfSubsystem::lookup_by_nqn(&nqn) else {
+                forget_subsystem(&nqn);
+                continue;
+            };
+            if let Err(error) = subsystem.apply_allowed_hosts(&hosts).await {
+                error!(
+                    "Failed to update the allowed hosts of {nqn} from host \
+                    group {name}: {error}"
+                );
+                result = result.and(Err(error));
+            }
+        }
+        result.map(|_| group)
+    }
+}
+
+impl NvmfSubsystem {
+    /// Allows the given hosts to connect to the subsystem, or any host when
+    /// none is given. References to host groups are replaced by their
+    /// members. The previously allowed hosts are disallowed and disconnected.
+    pub async fn apply_allowed_hosts(
+        &self,
+        hosts: &[String],
+    ) -> Result<(), Error> {
+        let nqn = self.get_nqn();
+        if hosts.is_empty() {
+            if self.share_mode() == NvmfShareMode::Shared {
+                return Err(Error::InvalidShareMode {
+                    nqn,
+                    reason: "any host may not share it".to_string(),
+                });
+            }
+            if let Some(host) = self.fenced_hosts().into_iter().next() {
+                return Err(Error::HostFenced {
+                    nqn,
+                    host,
+                });
+            }
+            forget_subsystem(&nqn);
+            self.allow_any(true);
+            record_allowed_hosts(&nqn, hosts);
+            return Ok(());
+        }
+
+        // fenced hosts stay disallowed, even when allowed by a host group
+        let fenced = self.fenced_hosts();
+        let expanded = expand_hosts(hosts)?
+            .into_iter()
+            .filter(|h| !fenced.contains(h))
+            .collect::<Vec<_>>();
+        self.allow_any(false);
+        if expanded.is_empty() {
+            // the groups are empty or the hosts fenced, no host is allowed
+            for host in self.allowed_hosts() {
+                self.disallow_host(&host)?;
+                self.disconnect_host(&host).await?;
+            }
+        } else {
+            self.set_allowed_hosts(&expanded).await?;
+        }
+
+        record_allowed_hosts(&nqn, hosts);
+        let mut subsystem_hosts = SUBSYSTEM_HOSTS.lock().unwrap();
+        if hosts.iter().any(|h| group_name(h).is_some()) {
+            subsystem_hosts.insert(nqn, hosts.to_vec());
+        } else {
+            subsystem_hosts.remove(&nqn);
+        }
+        Ok(())
+    }
+}
+
+fn validate_group_name(name: &str) -> Result<(), Error> {
+    let reason = if name.is_empty() {
+        "empty name"
+    } else if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        "only alphanumeric characters, '-', '_' and '.' are allowed"
+    } else {
+        return Ok(());
+    };
+    Err(Error::InvalidHostGroup {
+        name: name.to_string(),
+        reason: reason.to_string(),
+    })
+}
+
+fn validate_hosts<'a>(
+    mut hosts: impl Iterator<Item = &'a String>,
+) -> Result<(), Error> {
+    hosts.try_for_each(|host| {
+        validate_nqn(host).map_err(|reason| Error::InvalidNqn {
+            nqn: host.clone(),
+            reason,
+        })
+    })
+}
+
+/// Arguments of the methods acting on a single host group.
+#[derive(Debug, Deserialize)]
+struct HostGroupArgs {
+    /// Name of the host group.
+    name: String,
+}
+
+/// Registers the JSON-RPC methods of the host groups.
+pub(super) fn register_rpc_methods() {
+    // named sets of hosts which the allowed hosts of the shares can
+    // refer to
+    jsonrpc_register::<HostGroup, _, _, Error>(
+        "mayastor_host_group_create",
+        |group| async move { group.create() }.boxed_local(),
+    );
+
+    jsonrpc_register::<HostGroupArgs, _, _, Error>(
+        "mayastor_host_group_delete",
+        |args| async move { HostGroup::delete(&args.name) }.boxed_local(),
+    );
+
+    jsonrpc_register::<(), _, _, JsonRpcError>(
+        "mayastor_host_group_list",
+        |_| async move { Ok(HostGroup::list()) }.boxed_local(),
+    );
+
+    jsonrpc_register::<HostGroup, _, _, Error>(
+        "mayastor_host_group_add_hosts",
+        |group| {
+            async move { HostGroup::add_hosts(&group.name, &group.hosts).await }
+                .boxed_local()
+        },
+    );
+
+    jsonrpc_register::<HostGroup, _, _, Error>(
+        "mayastor_host_group_remove_hosts",
+        |group| {
+            async move {
+                HostGroup::remove_hosts(&group.name, &group.hosts).await
+            }
+            .boxed_local()
+        },
+    );
+}
@@ -0,0 +1,89 @@
+//! Hooks on the host events of the subsystems.
+//!
+//! Modules interested in the hosts connecting to, disconnecting from or
+//! timing out on the subsystems, e.g. to audit or fence them, register a
+//! hook rather than being called from the subsystem event handler. A hook is
+//! an async callback, run on the reactor of the event once the built-in
+//! handling of the event is done. The controller of the event may be gone by
+//! the time a hook runs, so a hook is given a copy of what identifies it.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use futures::future::LocalBoxFuture;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::core::Reactors;
+
+/// Kind of a host event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HostEventKind {
+    /// The host connected a controller.
+    Connect,
+    /// The host disconnected a controller.
+    Disconnect,
+    /// The keep alive timeout of a controller of the host expired.
+    KeepAliveTimeout,
+}
+
+/// Host event of a subsystem, as given to the hooks.
+#[derive(Debug, Clone, Serialize)]
+pub struct HostEvent {
+    /// Kind of the event.
+    pub kind: HostEventKind,
+    /// NQN of the subsystem.
+    pub nqn: String,
+    /// Name of the bdev of the subsystem, if found.
+    pub bdev: Option<String>,
+    /// NQN of the host.
+    pub hostnqn: String,
+    /// Controller ID of the host.
+    pub cntlid: u16,
+}
+
+/// Async callback on the host events of the subsystems.
+pub type HostEventHook =
+    Arc<dyn Fn(HostEvent) -> LocalBoxFuture<'static, ()> + Send + Sync>;
+
+/// Hooks on the host events, by name.
+static HOST_EVENT_HOOKS: Lazy<Mutex<BTreeMap<String, HostEventHook>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// Registers a hook on the host events under the given name, replacing the
+/// hook registered under that name, if any.
+pub fn register_host_event_hook(name: &str, hook: HostEventHook) {
+    HOST_EVENT_HOOKS
+        .lock()
+        .unwrap()
+        .insert(name.to_string(), hook);
+}
+
+/// Unregisters the hook registered under the given name, returning whether
+/// there was one.
+pub fn unregister_host_event_hook(name: &str) -> bool {
+    HOST_EVENT_HOOKS.lock().unwrap().remove(name).is_some()
+}
+
+/// Runs the hooks on a host event, in the order of their names, on the
+/// reactor of the event.
+pub(crate) fn run_host_event_hooks(event: HostEvent) {
+    let hooks = HOST_EVENT_HOOKS
+        .lock()
+        .unwrap()
+        .values()
+        .cloned()
+        .collect::<Vec<_>>();
+    if hooks.is_empty() {
+        return;
+    }
+
+    Reactors::current().send_future(async move {
+        for hook in hooks {
+            hook(event.clone()).await;
+        }
+    });
+}
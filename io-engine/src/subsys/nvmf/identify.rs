@@ -0,0 +1,284 @@
+//! Overrides of the identify controller data of the subsystems.
+//!
+//! Some initiators misbehave with what the controllers report about
+//! themselves, e.g. issue commands the namespace would rather they did not,
+//! or size their I/O or atomic writes after limits they handle poorly. A
+//! share may override a few fields of the identify controller data to work
+//! around such quirks: the data is built by SPDK as usual and patched before
+//! being returned to the host.
+//!
+//! Only limits may be lowered and optional commands hidden, so that a host
+//! is never told about a capability the controller does not have: an
+//! override above the value SPDK reports for the device is ignored. The
+//! overrides live in memory only and apply to the identify commands received
+//! from then on, i.e. to the hosts connecting afterwards.
+
+use std::{
+    collections::HashMap,
+    mem::{size_of, zeroed},
+    ptr::NonNull,
+    sync::Mutex,
+};
+
+use futures::FutureExt;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use spdk_rs::libspdk::{
+    nvme_cmd_cdw10_get,
+    spdk_nvme_ctrlr_data,
+    spdk_nvmf_ctrlr_identify_ctrlr,
+    spdk_nvmf_request,
+    spdk_nvmf_request_copy_from_buf,
+    spdk_nvmf_request_get_cmd,
+    spdk_nvmf_request_get_subsystem,
+    spdk_nvmf_set_custom_admin_cmd_hdlr,
+    spdk_nvmf_subsystem_get_nqn,
+};
+
+use super::{Error, NvmfReq, NvmfSubsystem, SubsystemArgs};
+use crate::{ffihelper::AsStr, jsonrpc::jsonrpc_register};
+
+/// Opcode of the identify admin command.
+const OPC_IDENTIFY: u8 = 0x06;
+/// Controller or Namespace Structure of the identify controller data.
+const CNS_CTRLR: u32 = 0x01;
+
+/// Offsets of the fields in the identify controller data.
+const MDTS_OFFSET: usize = 77;
+const ONCS_OFFSET: usize = 520;
+const AWUN_OFFSET: usize = 526;
+const AWUPF_OFFSET: usize = 528;
+const ACWU_OFFSET: usize = 532;
+
+/// Overrides of fields of the identify controller data.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdentifyOverrides {
+    /// Maximum Data Transfer Size, as a power of two of the minimum memory
+    /// page size; may only be lowered.
+    pub mdts: Option<u8>,
+    /// Atomic Write Unit Normal, in logical blocks, 0's based; may only be
+    /// lowered.
+    pub awun: Option<u16>,
+    /// Atomic Write Unit Power Fail, in logical blocks, 0's based; may only
+    /// be lowered and may not exceed the atomic write unit normal.
+    pub awupf: Option<u16>,
+    /// Atomic Compare & Write Unit, in logical blocks, 0's based; may only
+    /// be lowered.
+    pub acwu: Option<u16>,
+    /// Bits of the Optional NVM Command Support to clear, hiding the
+    /// commands from the hosts.
+    pub oncs_clear: u16,
+}
+
+impl IdentifyOverrides {
+    /// Whether no field is overridden.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Validates the overrides.
+    pub fn validate(&self) -> Result<(), Error> {
+        if let (Some(awun), Some(awupf)) = (self.awun, self.awupf) {
+            if awupf > awun {
+                return Err(Error::InvalidIdentifyOverride {
+                    reason: format!("awupf {awupf} exceeds awun {awun}"),
+                });
+            }
+        }
+        if let Some(mdts) = self.mdts.filter(|mdts| *mdts > 31) {
+            return Err(Error::InvalidIdentifyOverride {
+                reason: format!("mdts {mdts} exceeds 31"),
+            });
+        }
+        Ok(())
+    }
+
+    /// Patches the identify controller data.
+    fn apply(&self, nqn: &str, data: &mut [u8]) {
+        let read16 = |data: &[u8], offset: usize| {
+            u16::from_le_bytes([data[offset], data[offset + 1]])
+        };
+        let write16 = |data: &mut [u8], offset: usize, value: u16| {
+            data[offset .. offset + 2].copy_from_slice(&value.to_le_bytes());
+        };
+
+        if let Some(mdts) = self.mdts {
+            // 0 means no limit, which any limit lowers
+            let current = data[MDTS_OFFSET];
+            if current == 0 || mdts <= current {
+                data[MDTS_OFFSET] = mdts;
+            } else {
+                warn!(
+                    nqn,
+                    "Not raising the mdts of the controller from {current} \
+                    to {mdts}"
+                );
+            }
+        }
+        // the atomic write units are 0's based, 0 being a single block, and
+        // may not be raised above what the device guarantees
+        let lower16 = |data: &mut [u8], offset, field, value: Option<u16>| {
+            let Some(value) = value else {
+                return;
+            };
+            let current = read16(data, offset);
+            if value <= current {
+                write16(data, offset, value);
+            } else {
+                warn!(
+                    nqn,
+                    "Not raising the {field} of the controller from \
+                    {current} to {value}"
+                );
+            }
+        };
+        lower16(data, AWUN_OFFSET, "awun", self.awun);
+        lower16(data, AWUPF_OFFSET, "awupf", self.awupf);
+        lower16(data, ACWU_OFFSET, "acwu", self.acwu);
+        // which keeps the power fail unit within the normal one
+        let awun = read16(data, AWUN_OFFSET);
+        if read16(data, AWUPF_OFFSET) > awun {
+            write16(data, AWUPF_OFFSET, awun);
+        }
+        let oncs = read16(data, ONCS_OFFSET);
+        write16(data, ONCS_OFFSET, oncs & !self.oncs_clear);
+    }
+}
+
+/// Overrides of the identify controller data, by subsystem NQN.
+static IDENTIFY_OVERRIDES: Lazy<Mutex<HashMap<String, IdentifyOverrides>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Forgets the overrides of a subsystem which is being destroyed.
+pub(crate) fn forget_identify(nqn: &str) {
+    IDENTIFY_OVERRIDES.lock().unwrap().remove(nqn);
+}
+
+impl NvmfSubsystem {
+    /// Sets the overrides of the identify controller data of the subsystem,
+    /// removing them when empty.
+    pub fn set_identify_overrides(
+        &self,
+        overrides: &IdentifyOverrides,
+    ) -> Result<(), Error> {
+        let nqn = self.get_nqn();
+        overrides.validate()?;
+
+        let mut all = IDENTIFY_OVERRIDES.lock().unwrap();
+        if overrides.is_empty() {
+            if all.remove(&nqn).is_some() {
+                info!(nqn, "Removed the identify controller overrides");
+            }
+        } else {
+            info!(nqn, ?overrides, "Overriding the identify controller data");
+            all.insert(nqn, overrides.clone());
+        }
+        Ok(())
+    }
+
+    /// Get the overrides of the identify controller data of the subsystem.
+    pub fn identify_overrides(&self) -> IdentifyOverrides {
+        IDENTIFY_OVERRIDES
+            .lock()
+            .unwrap()
+            .get(&self.get_nqn())
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// NVMf custom command handler for the identify command.
+/// Called from nvmf_ctrlr_process_admin_cmd
+/// Return: <0 to leave the command to the default handler
+extern "C" fn nvmf_identify_hdlr(req: *mut spdk_nvmf_request) -> i32 {
+    let cns =
+        unsafe { *nvme_cmd_cdw10_get(spdk_nvmf_request_get_cmd(req)) } & 0xff;
+    if cns != CNS_CTRLR {
+        return -1;
+    }
+
+    let subsys = unsafe { spdk_nvmf_request_get_subsystem(req) };
+    if subsys.is_null() {
+        return -1;
+    }
+    let nqn = unsafe { spdk_nvmf_subsystem_get_nqn(subsys).as_str() };
+    let Some(overrides) = IDENTIFY_OVERRIDES.lock().unwrap().get(nqn).cloned()
+    else {
+        return -1;
+    };
+
+    // the data SPDK would have returned, to be patched
+    let mut cdata: spdk_nvme_ctrlr_data = unsafe { zeroed() };
+    unsafe {
+        spdk_nvmf_ctrlr_identify_ctrlr((*(*req).qpair).ctrlr, &mut cdata);
+    }
+
+    let data = unsafe {
+        std::slice::from_raw_parts_mut(
+            &mut cdata as *mut _ as *mut u8,
+            size_of::<spdk_nvme_ctrlr_data>(),
+        )
+    };
+    overrides.apply(nqn, data);
+
+    unsafe {
+        spdk_nvmf_request_copy_from_buf(
+            req,
+            data.as_ptr() as _,
+            data.len() as u64,
+        );
+    }
+    let mut rsp = NvmfReq(NonNull::new(req).unwrap()).response();
+    let status = rsp.status();
+    status.set_sct(0); // SPDK_NVME_SCT_GENERIC
+    status.set_sc(0); // SPDK_NVME_SC_SUCCESS
+    0 // SPDK_NVMF_REQUEST_EXEC_STATUS_COMPLETE
+}
+
+/// Register the identify command handler applying the overrides.
+pub fn setup_identify_hdlr() {
+    unsafe {
+        spdk_nvmf_set_custom_admin_cmd_hdlr(
+            OPC_IDENTIFY,
+            Some(nvmf_identify_hdlr),
+        );
+    }
+}
+
+/// Arguments of the `mayastor_subsystem_identify_set` method.
+#[derive(Debug, Deserialize)]
+struct SubsystemIdentifyArgs {
+    /// NQN of the subsystem.
+    nqn: String,
+    /// Overrides of the identify controller data, removed when not given.
+    #[serde(default)]
+    overrides: IdentifyOverrides,
+}
+
+/// Registers the JSON-RPC methods of the identify overrides.
+pub(super) fn register_rpc_methods() {
+    // fields of the identify controller data overridden for a subsystem,
+    // to work around the quirks of some initiators
+    jsonrpc_register::<SubsystemIdentifyArgs, _, _, Error>(
+        "mayastor_subsystem_identify_set",
+        |args| {
+            async move {
+                NvmfSubsystem::lookup_by_nqn(&args.nqn)?
+                    .set_identify_overrides(&args.overrides)
+            }
+            .boxed_local()
+        },
+    );
+
+    jsonrpc_register::<SubsystemArgs, _, _, Error>(
+        "mayastor_subsystem_identify",
+        |args| {
+            async move {
+                Ok(NvmfSubsystem::lookup_by_nqn(&args.nqn)?
+                    .identify_overrides())
+            }
+            .boxed_local()
+        },
+    );
+}
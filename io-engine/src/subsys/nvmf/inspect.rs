@@ -0,0 +1,316 @@
+//! Inspection of the NVMe-oF subsystems.
+//!
+//! Describes every subsystem of the targets as SPDK sees it: its identity,
+//! the hosts allowed to connect, its listeners and their ANA states, its
+//! namespaces and the controllers of the hosts connected to it, so that the
+//! state of the shares of a node can be looked at without scraping its logs.
+//!
+//! SPDK does not keep the time a controller connected at, so the
+//! connections are timestamped by a hook on the host events of the
+//! subsystems.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::FutureExt;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use spdk_rs::libspdk::{
+    spdk_bit_array_count_set,
+    spdk_nvmf_ctrlr,
+    spdk_nvmf_ns_get_bdev,
+    spdk_nvmf_subsystem_get_first_ns,
+    spdk_nvmf_subsystem_get_next_ns,
+};
+
+use super::{
+    register_host_event_hook,
+    Error,
+    HostEvent,
+    HostEventKind,
+    NvmfListener,
+    NvmfListenerAna,
+    NvmfShareMode,
+    NvmfSubsystem,
+    SubsystemArgs,
+    TargetKind,
+};
+use crate::{
+    core::UntypedBdev,
+    ffihelper::AsStr,
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+};
+
+/// A namespace of a subsystem.
+#[derive(Debug, Clone, Serialize)]
+pub struct NvmfNamespaceInfo {
+    /// ID of the namespace.
+    pub nsid: u32,
+    /// Name of the bdev behind the namespace.
+    pub bdev: Option<String>,
+    /// ID of the ANA group the namespace belongs to.
+    pub anagrpid: u32,
+    /// Hosts the namespace is visible to when it is private, all the hosts
+    /// of the subsystem otherwise.
+    pub visible_hosts: Option<Vec<String>>,
+}
+
+/// A controller of a host connected to a subsystem.
+#[derive(Debug, Clone, Serialize)]
+pub struct NvmfControllerInfo {
+    /// ID of the controller.
+    pub cntlid: u16,
+    /// NQN of the host.
+    pub hostnqn: String,
+    /// ID of the host, which its reservations are registered with.
+    pub hostid: String,
+    /// Number of queue pairs of the controller, including the admin one.
+    pub num_qpairs: u32,
+    /// Time the host connected at, in RFC 3339 format, if known.
+    pub connected_at: Option<String>,
+    /// Age of the connection in seconds, if known.
+    pub connection_age_secs: Option<u64>,
+    /// Keep alive timeout of the controller, in milliseconds.
+    pub kato_ms: u32,
+}
+
+/// Time a controller connected at.
+#[derive(Debug, Clone, Copy)]
+struct Connection {
+    at: DateTime<Utc>,
+    since: Instant,
+}
+
+/// Connections of the controllers, by NQN of their subsystem and then ID, so
+/// that those of a subsystem are forgotten without walking them all.
+static CONNECTIONS: Lazy<Mutex<HashMap<String, HashMap<u16, Connection>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Name of the hook timestamping the connections of the controllers.
+const CONNECTIONS_HOOK: &str = "inspect";
+
+/// Registers the hook timestamping the connections of the controllers.
+pub(super) fn register_connections_hook() {
+    register_host_event_hook(
+        CONNECTIONS_HOOK,
+        Arc::new(|event| {
+            async move { record_connection(&event) }.boxed_local()
+        }),
+    );
+}
+
+/// Records or forgets the connection of a controller on a host event.
+fn record_connection(event: &HostEvent) {
+    match event.kind {
+        // the subsystem may be gone by the time the hook runs
+        HostEventKind::Connect
+            if NvmfSubsystem::lookup_by_nqn(&event.nqn).is_ok() =>
+        {
+            controller_connected(&event.nqn, event.cntlid);
+        }
+        HostEventKind::Disconnect => {
+            controller_disconnected(&event.nqn, event.cntlid);
+        }
+        _ => {}
+    }
+}
+
+/// Records the time a controller connected to a subsystem at.
+fn controller_connected(nqn: &str, cntlid: u16) {
+    let connection = Connection {
+        at: Utc::now(),
+        since: Instant::now(),
+    };
+    CONNECTIONS
+        .lock()
+        .unwrap()
+        .entry(nqn.to_string())
+        .or_default()
+        .insert(cntlid, connection);
+}
+
+/// Forgets a controller which disconnected from a subsystem.
+fn controller_disconnected(nqn: &str, cntlid: u16) {
+    let mut connections = CONNECTIONS.lock().unwrap();
+    if let Some(ctrlrs) = connections.get_mut(nqn) {
+        ctrlrs.remove(&cntlid);
+        if ctrlrs.is_empty() {
+            connections.remove(nqn);
+        }
+    }
+}
+
+/// Forgets the controllers of a subsystem which is being destroyed.
+pub(crate) fn forget_controllers(nqn: &str) {
+    CONNECTIONS.lock().unwrap().remove(nqn);
+}
+
+/// Description of a subsystem.
+#[derive(Debug, Clone, Serialize)]
+pub struct NvmfSubsystemInfo {
+    pub nqn: String,
+    /// Subtype of the subsystem, NVMe or Discovery.
+    pub subtype: String,
+    /// Kind of the target the subsystem belongs to.
+    pub kind: TargetKind,
+    pub serial: String,
+    pub model: String,
+    /// Range the IDs of the controllers are given out from.
+    pub min_cntlid: u16,
+    pub max_cntlid: u16,
+    /// Whether any host may connect, regardless of the allowed hosts.
+    pub allow_any_host: bool,
+    pub allowed_hosts: Vec<String>,
+    /// Hosts which may not be allowed until unfenced.
+    pub fenced_hosts: Vec<String>,
+    pub listeners: Vec<NvmfListener>,
+    /// Whether ANA is reported to the hosts.
+    pub ana_reporting: bool,
+    /// Whether the subsystem is used by one host at a time or by several.
+    pub share_mode: NvmfShareMode,
+    /// State of every ANA group on every listener.
+    pub ana_states: Vec<NvmfListenerAna>,
+    pub namespaces: Vec<NvmfNamespaceInfo>,
+    pub controllers: Vec<NvmfControllerInfo>,
+}
+
+impl NvmfSubsystem {
+    /// Get the namespaces of the subsystem.
+    pub fn namespaces(&self) -> Vec<NvmfNamespaceInfo> {
+        let mut namespaces = Vec::new();
+
+        let mut ns =
+            unsafe { spdk_nvmf_subsystem_get_first_ns(self.0.as_ptr()) };
+
+        while !ns.is_null() {
+            let bdev = UntypedBdev::checked_from_ptr(unsafe {
+                spdk_nvmf_ns_get_bdev(ns)
+            })
+            .map(|b| b.name().to_string());
+            let nsid = unsafe { (*ns).nsid };
+            namespaces.push(NvmfNamespaceInfo {
+                nsid,
+                bdev,
+                anagrpid: unsafe { (*ns).anagrpid },
+                visible_hosts: self.namespace_visible_hosts(nsid),
+            });
+            ns =
+                unsafe { spdk_nvmf_subsystem_get_next_ns(self.0.as_ptr(), ns) };
+        }
+
+        namespaces
+    }
+
+    /// Get the controllers of the hosts connected to the subsystem.
+    pub fn controllers(&self) -> Vec<NvmfControllerInfo> {
+        let nqn = self.get_nqn();
+        let connections = CONNECTIONS.lock().unwrap();
+        let connections = connections.get(&nqn);
+        let mut controllers = Vec::new();
+
+        let mut ctrlr: *mut spdk_nvmf_ctrlr =
+            unsafe { self.0.as_ref().ctrlrs.tqh_first };
+
+        while !ctrlr.is_null() {
+            let cntlid = unsafe { (*ctrlr).cntlid };
+            let connection = connections.and_then(|c| c.get(&cntlid));
+            unsafe {
+                controllers.push(NvmfControllerInfo {
+                    cntlid,
+                    hostnqn: (*ctrlr).hostnqn.as_str().to_string(),
+                    hostid: uuid::Uuid::from_bytes((*ctrlr).hostid.u.raw)
+                        .to_string(),
+                    num_qpairs: spdk_bit_array_count_set((*ctrlr).qpair_mask),
+                    connected_at: connection.map(|c| {
+                        c.at.to_rfc3339_opts(SecondsFormat::Secs, true)
+                    }),
+                    connection_age_secs: connection
+                        .map(|c| c.since.elapsed().as_secs()),
+                    kato_ms: (*ctrlr).feat.keep_alive_timer.bits.kato,
+                });
+                ctrlr = (*ctrlr).link.tqe_next;
+            }
+        }
+
+        controllers
+    }
+
+    /// Describe the subsystem.
+    pub fn info(&self) -> NvmfSubsystemInfo {
+        let (serial, model, min_cntlid, max_cntlid, allow_any_host) = unsafe {
+            let ss = self.0.as_ref();
+            (
+                ss.sn.as_str().to_string(),
+                ss.mn.as_str().to_string(),
+                ss.min_cntlid,
+                ss.max_cntlid,
+                ss.allow_any_host,
+            )
+        };
+        let listeners = self
+            .listeners_to_vec()
+            .unwrap_or_default()
+            .iter()
+            .map(NvmfListener::from)
+            .collect();
+
+        NvmfSubsystemInfo {
+            nqn: self.get_nqn(),
+            subtype: self.subtype().to_string(),
+            kind: self.target_kind(),
+            serial,
+            model,
+            min_cntlid,
+            max_cntlid,
+            allow_any_host,
+            allowed_hosts: self.allowed_hosts(),
+            fenced_hosts: self.fenced_hosts(),
+            listeners,
+            ana_reporting: self.ana_reporting(),
+            share_mode: self.share_mode(),
+            ana_states: self.ana_states(),
+            namespaces: self.namespaces(),
+            controllers: self.controllers(),
+        }
+    }
+}
+
+/// Describe all the subsystems of the targets.
+pub fn nvmf_subsystems() -> Vec<NvmfSubsystemInfo> {
+    NvmfSubsystem::first()
+        .map(|first| first.into_iter().map(|s| s.info()).collect())
+        .unwrap_or_default()
+}
+
+/// Registers the JSON-RPC methods of the subsystem inspection.
+pub(super) fn register_rpc_methods() {
+    // all the subsystems as SPDK sees them, for support tooling
+    jsonrpc_register::<(), _, _, JsonRpcError>(
+        "mayastor_subsystem_list",
+        |_| async move { Ok(nvmf_subsystems()) }.boxed_local(),
+    );
+
+    jsonrpc_register::<SubsystemArgs, _, _, Error>(
+        "mayastor_subsystem_get",
+        |args| {
+            async move { Ok(NvmfSubsystem::lookup_by_nqn(&args.nqn)?.info()) }
+                .boxed_local()
+        },
+    );
+
+    // controllers of the hosts connected to a subsystem, to see which
+    // initiators hold a volume open
+    jsonrpc_register::<SubsystemArgs, _, _, Error>(
+        "mayastor_subsystem_controllers",
+        |args| {
+            async move {
+                Ok(NvmfSubsystem::lookup_by_nqn(&args.nqn)?.controllers())
+            }
+            .boxed_local()
+        },
+    );
+}
@@ -0,0 +1,545 @@
+//! I/O statistics of the NVMe-oF subsystems and of their hosts.
+//!
+//! The I/O of a subsystem is counted on the bdev channels the target submits
+//! the commands of its namespaces with, one per poll group, so that the I/O
+//! the bdevs get from elsewhere, e.g. from a rebuild, is left out. SPDK does
+//! not count the commands of the hosts separately: the reads and writes of
+//! the hosts of a nexus are counted by the nexus, which finds the controller
+//! of the host a read or write comes from. For the other shares, and for the
+//! other commands, only the commands in flight of the hosts are known, found
+//! on the queue pairs of their controllers on every poll group.
+//!
+//! The rates of the hosts of the nexuses may be published as events at a
+//! regular interval, so that the initiators keeping a subsystem busy at the
+//! expense of the others can be told apart.
+
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    ops::{Add, AddAssign},
+    time::{Duration, Instant},
+};
+
+use futures::FutureExt;
+use serde::Serialize;
+use spdk_rs::{
+    libspdk::{
+        spdk_bdev,
+        spdk_bdev_desc,
+        spdk_bdev_get_io_stat,
+        spdk_bdev_io_stat,
+        spdk_nvmf_ns_get_bdev,
+        spdk_nvmf_poll_group,
+        spdk_nvmf_request_get_cmd,
+        spdk_nvmf_subsystem,
+        spdk_nvmf_subsystem_get_first_ns,
+        spdk_nvmf_subsystem_get_next_ns,
+    },
+    nvme_nvm_opcode,
+};
+
+use super::{
+    subsystem::NqnTarget,
+    Error,
+    NvmfSubsystem,
+    OutstandingCommands,
+    SubType,
+    SubsystemArgs,
+    NVMF_PGS,
+};
+use crate::{
+    bdev::Nexus,
+    core::{IoType, Reactor},
+    eventing::host_events::host_io_rates_event,
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+};
+
+/// Commands in flight of a controller of a host.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct InFlightCommands {
+    /// I/O commands submitted and not yet completed.
+    pub queue_depth: u64,
+    /// Read commands among them.
+    pub reads: u64,
+    /// Write commands among them.
+    pub writes: u64,
+    /// Bytes the read commands transfer.
+    pub read_bytes: u64,
+    /// Bytes the write commands transfer.
+    pub write_bytes: u64,
+}
+
+impl Add for InFlightCommands {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            queue_depth: self.queue_depth + other.queue_depth,
+            reads: self.reads + other.reads,
+            writes: self.writes + other.writes,
+            read_bytes: self.read_bytes + other.read_bytes,
+            write_bytes: self.write_bytes + other.write_bytes,
+        }
+    }
+}
+
+/// Reads and writes submitted by a host since its controller connected.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct HostIoCounters {
+    pub num_read_ops: u64,
+    pub num_write_ops: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+}
+
+impl HostIoCounters {
+    /// Accounts for a read or write of the given size.
+    pub(crate) fn account(&mut self, io_type: IoType, num_bytes: u64) {
+        match io_type {
+            IoType::Read => {
+                self.num_read_ops += 1;
+                self.bytes_read += num_bytes;
+            }
+            IoType::Write => {
+                self.num_write_ops += 1;
+                self.bytes_written += num_bytes;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl AddAssign for HostIoCounters {
+    fn add_assign(&mut self, other: Self) {
+        self.num_read_ops += other.num_read_ops;
+        self.num_write_ops += other.num_write_ops;
+        self.bytes_read += other.bytes_read;
+        self.bytes_written += other.bytes_written;
+    }
+}
+
+/// I/O completed through a subsystem since its namespaces were added.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TargetIoCounters {
+    pub num_read_ops: u64,
+    pub num_write_ops: u64,
+    pub num_unmap_ops: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub bytes_unmapped: u64,
+    /// Sum of the latencies of the reads, in microseconds.
+    pub read_latency_us: u64,
+    /// Sum of the latencies of the writes, in microseconds.
+    pub write_latency_us: u64,
+}
+
+impl TargetIoCounters {
+    /// Counters of the I/O statistics of a bdev channel.
+    fn from_stat(stat: &spdk_bdev_io_stat) -> Self {
+        let us = |ticks: u64| {
+            (ticks as u128 * 1_000_000 / stat.ticks_rate.max(1) as u128) as u64
+        };
+        Self {
+            num_read_ops: stat.num_read_ops,
+            num_write_ops: stat.num_write_ops,
+            num_unmap_ops: stat.num_unmap_ops,
+            bytes_read: stat.bytes_read,
+            bytes_written: stat.bytes_written,
+            bytes_unmapped: stat.bytes_unmapped,
+            read_latency_us: us(stat.read_latency_ticks),
+            write_latency_us: us(stat.write_latency_ticks),
+        }
+    }
+}
+
+impl Add for TargetIoCounters {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            num_read_ops: self.num_read_ops + other.num_read_ops,
+            num_write_ops: self.num_write_ops + other.num_write_ops,
+            num_unmap_ops: self.num_unmap_ops + other.num_unmap_ops,
+            bytes_read: self.bytes_read + other.bytes_read,
+            bytes_written: self.bytes_written + other.bytes_written,
+            bytes_unmapped: self.bytes_unmapped + other.bytes_unmapped,
+            read_latency_us: self.read_latency_us + other.read_latency_us,
+            write_latency_us: self.write_latency_us + other.write_latency_us,
+        }
+    }
+}
+
+/// I/O statistics of a controller of a host connected to a subsystem.
+#[derive(Debug, Clone, Serialize)]
+pub struct NvmfHostIoStats {
+    /// ID of the controller.
+    pub cntlid: u16,
+    /// NQN of the host.
+    pub hostnqn: String,
+    /// Reads and writes submitted by the host, only counted for the hosts
+    /// of a nexus.
+    pub io: Option<HostIoCounters>,
+    #[serde(flatten)]
+    pub in_flight: InFlightCommands,
+}
+
+/// I/O statistics of a subsystem.
+#[derive(Debug, Clone, Serialize)]
+pub struct NvmfSubsystemIoStats {
+    pub nqn: String,
+    /// Name of the bdev behind the namespace of the subsystem.
+    pub bdev: Option<String>,
+    /// I/O completed through the subsystem, on all its namespaces.
+    #[serde(flatten)]
+    pub io: TargetIoCounters,
+    /// Commands of the subsystem not yet completed.
+    pub outstanding: OutstandingCommands,
+    /// Statistics of the controllers of the connected hosts.
+    pub hosts: Vec<NvmfHostIoStats>,
+}
+
+/// Rates of the reads and writes of a host of a nexus over an interval.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HostIoRates {
+    /// NQN of the subsystem.
+    pub nqn: String,
+    /// ID of the controller of the host.
+    pub cntlid: u16,
+    /// NQN of the host.
+    pub hostnqn: String,
+    pub read_iops: u64,
+    pub write_iops: u64,
+    /// Bytes read per second.
+    pub read_bps: u64,
+    /// Bytes written per second.
+    pub write_bps: u64,
+}
+
+impl Display for HostIoRates {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "read {} IOPS {} B/s, write {} IOPS {} B/s",
+            self.read_iops, self.read_bps, self.write_iops, self.write_bps
+        )
+    }
+}
+
+impl HostIoRates {
+    /// Computes the rates of the hosts of the nexuses from two samples of
+    /// the statistics of the subsystems taken the given time apart. A
+    /// controller connected in between counts from zero, and the hosts
+    /// which submitted no read or write are left out.
+    pub fn from_samples(
+        before: &[NvmfSubsystemIoStats],
+        after: &[NvmfSubsystemIoStats],
+        elapsed: Duration,
+    ) -> Vec<Self> {
+        let ms = elapsed.as_millis().max(1) as u64;
+        let rate =
+            |after: u64, before: u64| after.saturating_sub(before) * 1000 / ms;
+
+        let mut rates = vec![];
+        for subsystem in after {
+            let previous = before.iter().find(|s| s.nqn == subsystem.nqn);
+            for host in &subsystem.hosts {
+                let Some(io) = host.io else {
+                    continue;
+                };
+                let b = previous
+                    .and_then(|s| {
+                        s.hosts.iter().find(|h| h.cntlid == host.cntlid)
+                    })
+                    .and_then(|h| h.io)
+                    .unwrap_or_default();
+                if io == b {
+                    continue;
+                }
+                rates.push(Self {
+                    nqn: subsystem.nqn.clone(),
+                    cntlid: host.cntlid,
+                    hostnqn: host.hostnqn.clone(),
+                    read_iops: rate(io.num_read_ops, b.num_read_ops),
+                    write_iops: rate(io.num_write_ops, b.num_write_ops),
+                    read_bps: rate(io.bytes_read, b.bytes_read),
+                    write_bps: rate(io.bytes_written, b.bytes_written),
+                });
+            }
+        }
+        rates
+    }
+}
+
+/// I/O statistics of a subsystem on a poll group.
+#[derive(Debug, Default)]
+struct PollGroupIoStats {
+    io: TargetIoCounters,
+    in_flight: HashMap<u16, InFlightCommands>,
+}
+
+impl NvmfSubsystem {
+    /// Collects the I/O statistics of the subsystem on every poll group of
+    /// its target, on the thread of the poll group.
+    async fn poll_group_io_stats(&self) -> PollGroupIoStats {
+        let kind = self.target_kind();
+        let subsystem = self.0.as_ptr();
+        let mut namespaces: Vec<(u32, *mut spdk_bdev)> = vec![];
+        unsafe {
+            let mut ns = spdk_nvmf_subsystem_get_first_ns(subsystem);
+            while !ns.is_null() {
+                namespaces.push(((*ns).nsid, spdk_nvmf_ns_get_bdev(ns)));
+                ns = spdk_nvmf_subsystem_get_next_ns(subsystem, ns);
+            }
+        }
+        let pgs = NVMF_PGS.with(|pgs| {
+            pgs.borrow()
+                .iter()
+                .filter(|pg| pg.kind == kind)
+                .cloned()
+                .collect::<Vec<_>>()
+        });
+
+        let mut total = PollGroupIoStats::default();
+        for pg in pgs {
+            let group = pg.group_ptr();
+            let namespaces = namespaces.clone();
+            let stats = Reactor::spawn_at(&pg.thread, async move {
+                PollGroupIoStats {
+                    io: count_target_io(group, subsystem, &namespaces),
+                    in_flight: count_in_flight(group, subsystem),
+                }
+            });
+            match stats {
+                Ok(rx) => {
+                    let stats = rx.await.unwrap_or_default();
+                    total.io = total.io + stats.io;
+                    for (cntlid, count) in stats.in_flight {
+                        let entry = total.in_flight.entry(cntlid).or_default();
+                        *entry = *entry + count;
+                    }
+                }
+                Err(error) => {
+                    error!("Failed to count the commands on {pg:?}: {error}")
+                }
+            }
+        }
+        total
+    }
+
+    /// Collects the I/O statistics of the subsystem and of the controllers
+    /// of its hosts.
+    pub async fn io_stats(&self) -> NvmfSubsystemIoStats {
+        let nqn = self.get_nqn();
+        let outstanding = self.outstanding_commands().await;
+        let pg_stats = self.poll_group_io_stats().await;
+        let host_io = match NqnTarget::lookup(&nqn) {
+            NqnTarget::Nexus(nexus) => Some(nexus.host_io_stats().await),
+            _ => None,
+        };
+
+        let hosts = self
+            .controllers()
+            .into_iter()
+            .map(|ctrlr| NvmfHostIoStats {
+                cntlid: ctrlr.cntlid,
+                io: host_io.as_ref().map(|io| {
+                    io.get(&ctrlr.cntlid).copied().unwrap_or_default()
+                }),
+                in_flight: pg_stats
+                    .in_flight
+                    .get(&ctrlr.cntlid)
+                    .copied()
+                    .unwrap_or_default(),
+                hostnqn: ctrlr.hostnqn,
+            })
+            .collect();
+
+        NvmfSubsystemIoStats {
+            nqn,
+            bdev: self.bdev().map(|b| b.name().to_string()),
+            io: pg_stats.io,
+            outstanding,
+            hosts,
+        }
+    }
+
+    /// Returns the descriptor of the namespace the subsystem shares its bdev
+    /// as, null if none.
+    fn shared_ns_desc(&self) -> *mut spdk_bdev_desc {
+        unsafe {
+            let ns = spdk_nvmf_subsystem_get_first_ns(self.0.as_ptr());
+            if ns.is_null() {
+                std::ptr::null_mut()
+            } else {
+                (*ns).desc
+            }
+        }
+    }
+
+    /// Has the nexus shared by the subsystem count the reads and writes of
+    /// its hosts, upon the connection of a host.
+    pub(super) fn count_host_io(&self, nexus: &Nexus) {
+        nexus.set_nvmf_desc(self.shared_ns_desc());
+    }
+
+    /// Stops the nexus shared by the subsystem from counting the reads and
+    /// writes of its hosts, before the namespace it is shared as is removed.
+    pub(super) fn stop_counting_host_io(&self) {
+        if let NqnTarget::Nexus(nexus) = NqnTarget::lookup(&self.get_nqn()) {
+            nexus.clear_nvmf_desc(self.shared_ns_desc());
+        }
+    }
+}
+
+/// Collects the I/O statistics of all the NVMe subsystems of the targets.
+pub async fn nvmf_io_stats() -> Vec<NvmfSubsystemIoStats> {
+    let subsystems = NvmfSubsystem::first()
+        .map(|first| {
+            first
+                .into_iter()
+                .filter(|s| s.subtype() == SubType::Nvme)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let mut stats = vec![];
+    for subsystem in subsystems {
+        stats.push(subsystem.io_stats().await);
+    }
+    stats
+}
+
+/// Publishes an event with the rates of the reads and writes of every host
+/// of a nexus which submitted any over the interval, along with its rates
+/// over the previous one. Runs until the io-engine stops.
+pub async fn nvmf_io_stats_loop(interval: Duration) {
+    if interval.is_zero() {
+        warn!("NVMf I/O statistics interval is zero, no event is published");
+        return;
+    }
+    let mut interval = tokio::time::interval(interval);
+    let mut sample: Option<(Instant, Vec<NvmfSubsystemIoStats>)> = None;
+    let mut previous: Vec<HostIoRates> = vec![];
+    loop {
+        interval.tick().await;
+        let stats = match Reactor::spawn_at_primary(nvmf_io_stats()) {
+            Ok(rx) => match rx.await {
+                Ok(stats) => stats,
+                Err(_) => {
+                    error!("Collection of the NVMf I/O statistics cancelled");
+                    continue;
+                }
+            },
+            Err(error) => {
+                error!("Failed to collect the NVMf I/O statistics: {error}");
+                continue;
+            }
+        };
+        let now = Instant::now();
+
+        if let Some((at, before)) = sample.take() {
+            let rates = HostIoRates::from_samples(&before, &stats, now - at);
+            for rate in &rates {
+                let last = previous
+                    .iter()
+                    .find(|p| p.nqn == rate.nqn && p.cntlid == rate.cntlid);
+                host_io_rates_event(rate, last).generate();
+            }
+            previous = rates;
+        }
+        sample = Some((now, stats));
+    }
+}
+
+/// Sums the I/O statistics of the bdev channels of the given namespaces of
+/// a subsystem on a poll group. Must run on the thread of the poll group.
+fn count_target_io(
+    group: *mut spdk_nvmf_poll_group,
+    subsystem: *mut spdk_nvmf_subsystem,
+    namespaces: &[(u32, *mut spdk_bdev)],
+) -> TargetIoCounters {
+    let mut total = TargetIoCounters::default();
+    unsafe {
+        let id = (*subsystem).id;
+        if id >= (*group).num_sgroups {
+            return total;
+        }
+        let sgroup = &*(*group).sgroups.add(id as usize);
+        for (nsid, bdev) in namespaces {
+            // the namespaces are indexed by ID on the poll groups
+            let idx = nsid.saturating_sub(1);
+            if *nsid == 0 || idx >= sgroup.num_ns {
+                continue;
+            }
+            let channel = (*sgroup.ns_info.add(idx as usize)).channel;
+            if channel.is_null() {
+                continue;
+            }
+            let mut stat = spdk_bdev_io_stat::default();
+            spdk_bdev_get_io_stat(*bdev, channel, &mut stat);
+            total = total + TargetIoCounters::from_stat(&stat);
+        }
+    }
+    total
+}
+
+/// Counts the I/O commands in flight of the controllers of a subsystem on a
+/// poll group, by controller ID. Must run on the thread of the poll group.
+fn count_in_flight(
+    group: *mut spdk_nvmf_poll_group,
+    subsystem: *mut spdk_nvmf_subsystem,
+) -> HashMap<u16, InFlightCommands> {
+    let mut counts: HashMap<u16, InFlightCommands> = HashMap::new();
+    unsafe {
+        let mut qpair = (*group).qpairs.tqh_first;
+        while !qpair.is_null() {
+            let ctrlr = (*qpair).ctrlr;
+            // the admin queue pairs carry no I/O
+            if !ctrlr.is_null()
+                && (*ctrlr).subsys == subsystem
+                && (*qpair).qid != 0
+            {
+                let count = counts.entry((*ctrlr).cntlid).or_default();
+                let mut req = (*qpair).outstanding.tqh_first;
+                while !req.is_null() {
+                    count.queue_depth += 1;
+                    let length = u64::from((*req).length);
+                    match (*spdk_nvmf_request_get_cmd(req)).opc() as u8 {
+                        nvme_nvm_opcode::READ => {
+                            count.reads += 1;
+                            count.read_bytes += length;
+                        }
+                        nvme_nvm_opcode::WRITE => {
+                            count.writes += 1;
+                            count.write_bytes += length;
+                        }
+                        _ => {}
+                    }
+                    req = (*req).link.tqe_next;
+                }
+            }
+            qpair = (*qpair).link.tqe_next;
+        }
+    }
+    counts
+}
+
+/// Registers the JSON-RPC methods of the I/O statistics.
+pub(super) fn register_rpc_methods() {
+    // I/O of the subsystems and commands in flight of their hosts, to
+    // find the initiators starving the others
+    jsonrpc_register::<(), _, _, JsonRpcError>(
+        "mayastor_nvmf_io_stats",
+        |_| async move { Ok(nvmf_io_stats().await) }.boxed_local(),
+    );
+
+    jsonrpc_register::<SubsystemArgs, _, _, Error>(
+        "mayastor_subsystem_io_stats",
+        |args| {
+            async move {
+                Ok(NvmfSubsystem::lookup_by_nqn(&args.nqn)?.io_stats().await)
+            }
+            .boxed_local()
+        },
+    );
+}
@@ -0,0 +1,287 @@
+//! Keep alive timeout (KATO) policy of the subsystems.
+//!
+//! A host asks for a keep alive timeout when it connects, after which the
+//! controller is torn down unless the host sent a keep alive command. The
+//! nexuses and the replicas may each be given a timeout of their own, in the
+//! config file or on the command line, which replaces the one the hosts ask
+//! for; a share may override it in turn. A timeout shorter than the keep
+//! alive period of the hosts makes the target drop live hosts, so it should
+//! only be used to detect dead hosts sooner than they would be otherwise.
+//!
+//! The timeout applies to the controllers created from then on; those of the
+//! hosts already connected keep theirs.
+//!
+//! SPDK starts the keep alive poller of a controller, on the thread of its
+//! admin queue pair, with the timeout the host asked for, and starts none
+//! when the host asked for none. The timeout is therefore applied on that
+//! thread: before the poller is started when the admin queue pair is not
+//! added yet, or else by replacing the poller of SPDK, or starting one,
+//! with one checking the timeout given. A controller whose timeout expires
+//! is handled as those SPDK detects, through a keep alive timeout event,
+//! and disconnected.
+
+use std::{collections::HashMap, os::raw::c_void, ptr::NonNull, sync::Mutex};
+
+use futures::FutureExt;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use spdk_rs::{
+    libspdk::{
+        spdk_get_ticks,
+        spdk_get_ticks_hz,
+        spdk_nvmf_ctrlr,
+        spdk_nvmf_qpair_disconnect,
+        spdk_poller_register,
+        spdk_poller_unregister,
+        spdk_thread_send_msg,
+    },
+    NvmfController,
+    NvmfSubsystemEvent,
+};
+
+use super::{Error, NvmfSubsystem, SubsystemArgs, TargetKind};
+use crate::{
+    bdev::nexus::NEXUS_MODULE_NAME,
+    jsonrpc::jsonrpc_register,
+    subsys::Config,
+};
+
+/// Shortest keep alive timeout, as the controllers report a keep alive
+/// granularity of one second.
+pub const KATO_MIN_MS: u32 = 1_000;
+/// Longest keep alive timeout, past which a dead host would hold on to its
+/// controller for too long.
+pub const KATO_MAX_MS: u32 = 3_600_000;
+
+/// Keep alive timeout of a subsystem.
+#[derive(Debug, Clone, Serialize)]
+pub struct SubsystemKato {
+    /// NQN of the subsystem.
+    pub nqn: String,
+    /// Timeout given to the controllers, in milliseconds, the one the hosts
+    /// ask for when not set.
+    pub kato_ms: Option<u32>,
+    /// Whether the timeout is overridden for the subsystem rather than the
+    /// default of its kind.
+    pub kato_override: bool,
+}
+
+/// Keep alive timeouts overridden for the subsystems, by subsystem NQN.
+static SUBSYSTEM_KATO: Lazy<Mutex<HashMap<String, u32>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Forgets the keep alive timeout of a subsystem which is being destroyed.
+pub(crate) fn forget_kato(nqn: &str) {
+    SUBSYSTEM_KATO.lock().unwrap().remove(nqn);
+}
+
+/// Returns the keep alive timeout of a controller, in milliseconds.
+pub(crate) fn ctrlr_kato(ctrlr: &NvmfController) -> u32 {
+    unsafe { (*ctrlr.0.as_ptr()).feat.keep_alive_timer.bits.kato }
+}
+
+/// Keep alive timeout to apply to a controller on the thread of its admin
+/// queue pair.
+struct KatoCtx {
+    ctrlr: NonNull<spdk_nvmf_ctrlr>,
+    kato_ms: u32,
+}
+
+/// Applies the keep alive timeout of a controller, on the thread of its
+/// admin queue pair.
+extern "C" fn kato_apply(arg: *mut c_void) {
+    let ctx = unsafe { Box::from_raw(arg as *mut KatoCtx) };
+    let ctrlr = ctx.ctrlr.as_ptr();
+    unsafe {
+        (*ctrlr).feat.keep_alive_timer.bits.kato = ctx.kato_ms;
+        // SPDK starts its poller with the timeout just set once the admin
+        // queue pair is added
+        if (*ctrlr).admin_qpair.is_null() {
+            return;
+        }
+        spdk_poller_unregister(&mut (*ctrlr).keep_alive_poller);
+        (*ctrlr).keep_alive_poller = spdk_poller_register(
+            Some(kato_poll),
+            ctrlr as *mut c_void,
+            ctx.kato_ms as u64 * 1000,
+        );
+    }
+}
+
+/// Disconnects a controller which sent no keep alive command within its
+/// timeout, as the poller of SPDK does.
+extern "C" fn kato_poll(arg: *mut c_void) -> i32 {
+    let ctrlr = arg as *mut spdk_nvmf_ctrlr;
+    unsafe {
+        let kato_ms = (*ctrlr).feat.keep_alive_timer.bits.kato as u64;
+        let expiry = (*ctrlr).last_keep_alive_tick
+            + kato_ms * spdk_get_ticks_hz() / 1000;
+        if spdk_get_ticks() <= expiry {
+            return 0;
+        }
+        warn!(
+            cntlid = (*ctrlr).cntlid,
+            kato_ms, "Keep alive timeout expired, disconnecting the controller"
+        );
+        spdk_poller_unregister(&mut (*ctrlr).keep_alive_poller);
+        // handled as the timeouts detected by SPDK, e.g. releasing the
+        // reservations of the host
+        NvmfSubsystem::from((*ctrlr).subsys).handle_event(
+            NvmfSubsystemEvent::HostKeepAliveTimeout(NvmfController(
+                NonNull::new_unchecked(ctrlr),
+            )),
+        );
+        let rc = spdk_nvmf_qpair_disconnect((*ctrlr).admin_qpair);
+        if rc != 0 {
+            error!(
+                cntlid = (*ctrlr).cntlid,
+                "Failed to disconnect the controller: {rc}"
+            );
+        }
+    }
+    1
+}
+
+/// Validates a keep alive timeout, in milliseconds.
+pub fn validate_kato(kato_ms: u32) -> Result<(), String> {
+    if !(KATO_MIN_MS ..= KATO_MAX_MS).contains(&kato_ms) {
+        return Err(format!(
+            "keep alive timeout {kato_ms}ms not within \
+            {KATO_MIN_MS}ms..={KATO_MAX_MS}ms"
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the keep alive timeout of the subsystems of the given kind,
+/// if configured.
+pub fn default_kato(kind: TargetKind) -> Option<u32> {
+    let opts = &Config::get().nexus_opts;
+    let kato_ms = match kind {
+        TargetKind::Nexus => opts.nvmf_nexus_kato_ms,
+        TargetKind::Replica => opts.nvmf_replica_kato_ms,
+    }?;
+    validate_kato(kato_ms)
+        .map_err(|error| warn!("Ignoring the {kind:?} {error}"))
+        .ok()
+        .map(|_| kato_ms)
+}
+
+impl NvmfSubsystem {
+    /// Overrides the keep alive timeout of the subsystem, or goes back to
+    /// the default of its kind when None.
+    pub fn set_kato(&self, kato_ms: Option<u32>) -> Result<(), Error> {
+        let nqn = self.get_nqn();
+        let mut all = SUBSYSTEM_KATO.lock().unwrap();
+        match kato_ms {
+            Some(kato_ms) => {
+                validate_kato(kato_ms).map_err(|reason| {
+                    Error::InvalidKato {
+                        kato_ms,
+                        reason,
+                    }
+                })?;
+                info!(nqn, kato_ms, "Keep alive timeout overridden");
+                all.insert(nqn, kato_ms);
+            }
+            None => {
+                if all.remove(&nqn).is_some() {
+                    info!(nqn, "Keep alive timeout override removed");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the keep alive timeout of the subsystem.
+    pub fn kato(&self) -> SubsystemKato {
+        let nqn = self.get_nqn();
+        let kato_ms = SUBSYSTEM_KATO.lock().unwrap().get(&nqn).copied();
+        // the subsystems are nexuses or replicas by their bdev, whichever
+        // target they are on
+        let kind = match self.bdev() {
+            Some(b) if b.driver() == NEXUS_MODULE_NAME => TargetKind::Nexus,
+            _ => TargetKind::Replica,
+        };
+        SubsystemKato {
+            kato_override: kato_ms.is_some(),
+            kato_ms: kato_ms.or_else(|| default_kato(kind)),
+            nqn,
+        }
+    }
+
+    /// Gives the keep alive timeout of the subsystem to the controller of a
+    /// host which just connected, if the timeout is set, on the thread of
+    /// its admin queue pair.
+    pub(crate) fn apply_kato(&self, ctrlr: &NvmfController) {
+        let Some(kato_ms) = self.kato().kato_ms else {
+            return;
+        };
+        let requested = ctrlr_kato(ctrlr);
+        if requested == kato_ms {
+            return;
+        }
+        info!(
+            nqn = self.get_nqn(),
+            host = ctrlr.hostnqn(),
+            requested,
+            kato_ms,
+            "Replacing the keep alive timeout asked for by the host"
+        );
+        let ctx = Box::into_raw(Box::new(KatoCtx {
+            ctrlr: ctrlr.0,
+            kato_ms,
+        }));
+        let rc = unsafe {
+            spdk_thread_send_msg(
+                (*ctrlr.0.as_ptr()).thread,
+                Some(kato_apply),
+                ctx as *mut c_void,
+            )
+        };
+        if rc != 0 {
+            drop(unsafe { Box::from_raw(ctx) });
+            error!(
+                nqn = self.get_nqn(),
+                host = ctrlr.hostnqn(),
+                "Failed to apply the keep alive timeout: {rc}"
+            );
+        }
+    }
+}
+
+/// Arguments of the `mayastor_subsystem_kato_set` method.
+#[derive(Debug, Deserialize)]
+struct SubsystemKatoArgs {
+    /// NQN of the subsystem.
+    nqn: String,
+    /// Keep alive timeout in milliseconds, back to the default of the
+    /// subsystem kind when not given.
+    #[serde(default)]
+    kato_ms: Option<u32>,
+}
+
+/// Registers the JSON-RPC methods of the keep alive timeout.
+pub(super) fn register_rpc_methods() {
+    // keep alive timeout given to the controllers of the hosts which
+    // connect to a subsystem from then on
+    jsonrpc_register::<SubsystemKatoArgs, _, _, Error>(
+        "mayastor_subsystem_kato_set",
+        |args| {
+            async move {
+                let subsystem = NvmfSubsystem::lookup_by_nqn(&args.nqn)?;
+                subsystem.set_kato(args.kato_ms)?;
+                Ok(subsystem.kato())
+            }
+            .boxed_local()
+        },
+    );
+
+    jsonrpc_register::<SubsystemArgs, _, _, Error>(
+        "mayastor_subsystem_kato",
+        |args| {
+            async move { Ok(NvmfSubsystem::lookup_by_nqn(&args.nqn)?.kato()) }
+                .boxed_local()
+        },
+    );
+}
@@ -0,0 +1,396 @@
+//! Migration of the subsystems between nodes.
+//!
+//! To move the target of a volume to another node without recreating its
+//! state by hand, the subsystem is paused on the source node, so that no
+//! command is admitted, and its definition exported: its identity, the hosts
+//! allowed to connect, its listeners and the reservations of the namespace
+//! of its bdev. Its private namespaces are not exported.
+//! Once its bdev exists on the destination node, the definition is imported
+//! there: the reservations of the namespace of the bdev are written to a
+//! file in the format of the reservations persisted through power loss
+//! (PTPL), which SPDK loads as it adds the namespace, and the bdev is then
+//! shared with the exported identity and hosts. The reservations are thus in
+//! place before any listener is added, and persisted through power loss
+//! from then on. The source subsystem stays paused until it is destroyed, or
+//! resumed if the migration is aborted.
+//!
+//! SPDK does not load the generation of the reservations, which starts over
+//! on the destination node. The keys of the hosts which must authenticate
+//! are not exported, and the exported listeners are moved to the address of
+//! the destination target unless given explicitly.
+
+use std::pin::Pin;
+
+use futures::FutureExt;
+
+use serde::{Deserialize, Serialize};
+use spdk_rs::libspdk::{
+    spdk_nvmf_ns,
+    spdk_nvmf_registrant,
+    spdk_nvmf_subsystem_get_first_ns,
+    SPDK_NVMF_SUBSYSTEM_PAUSED,
+};
+
+use super::{
+    Error,
+    IdentifyOverrides,
+    NvmfListener,
+    NvmfShareMode,
+    NvmfSubsystem,
+    SubsystemArgs,
+};
+use crate::{
+    bdev::PtplFileOps,
+    core::{NvmfShareProps, PtplProps, Share, UntypedBdev},
+    ffihelper::AsStr,
+    jsonrpc::jsonrpc_register,
+};
+
+/// A host registered with a namespace.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedRegistrant {
+    /// ID of the host.
+    pub hostid: String,
+    /// Reservation key of the host.
+    pub rkey: u64,
+}
+
+/// Reservations of a namespace.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedReservations {
+    /// ID of the namespace.
+    pub nsid: u32,
+    /// Type of the reservation, none when 0.
+    pub rtype: u32,
+    /// Key of the reservation holder.
+    pub crkey: u64,
+    /// ID of the host holding the reservation, if any.
+    pub holder: Option<String>,
+    /// Generation of the reservations.
+    pub generation: u32,
+    /// Whether the reservations persist through power loss.
+    pub ptpl_activated: bool,
+    pub registrants: Vec<ExportedRegistrant>,
+}
+
+/// Definition of a subsystem, as exported from its node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubsystemExport {
+    pub nqn: String,
+    /// Name of the shared bdev.
+    pub bdev: String,
+    pub serial: String,
+    pub model: String,
+    /// Range the IDs of the controllers are given out from.
+    pub min_cntlid: u16,
+    pub max_cntlid: u16,
+    /// Hosts allowed to connect, any host when empty.
+    pub allowed_hosts: Vec<String>,
+    /// Hosts fenced from the subsystem.
+    pub fenced_hosts: Vec<String>,
+    pub listeners: Vec<NvmfListener>,
+    pub ana_reporting: bool,
+    pub share_mode: NvmfShareMode,
+    /// Keep alive timeout, when overridden.
+    pub kato_ms: Option<u32>,
+    pub identify: IdentifyOverrides,
+    pub reservations: Vec<ExportedReservations>,
+}
+
+impl NvmfSubsystem {
+    /// Pauses the subsystem and exports its definition, to import it on
+    /// another node. The subsystem stays paused until resumed, and may be
+    /// exported again meanwhile.
+    pub async fn export(&self) -> Result<SubsystemExport, Error> {
+        let nqn = self.get_nqn();
+        let bdev = self.bdev().ok_or_else(|| Error::InvalidExport {
+            nqn: nqn.clone(),
+            reason: "the subsystem has no namespace".to_string(),
+        })?;
+
+        if unsafe { self.0.as_ref().state } != SPDK_NVMF_SUBSYSTEM_PAUSED {
+            self.pause().await?;
+        }
+
+        let (serial, model, min_cntlid, max_cntlid, allow_any_host) = unsafe {
+            let ss = self.0.as_ref();
+            (
+                ss.sn.as_str().to_string(),
+                ss.mn.as_str().to_string(),
+                ss.min_cntlid,
+                ss.max_cntlid,
+                ss.allow_any_host,
+            )
+        };
+        let kato = self.kato();
+        let export = SubsystemExport {
+            nqn,
+            bdev: bdev.name().to_string(),
+            serial,
+            model,
+            min_cntlid,
+            max_cntlid,
+            allowed_hosts: if allow_any_host {
+                vec![]
+            } else {
+                self.allowed_hosts()
+            },
+            fenced_hosts: self.fenced_hosts(),
+            listeners: self
+                .listeners_to_vec()
+                .unwrap_or_default()
+                .iter()
+                .map(NvmfListener::from)
+                .collect(),
+            ana_reporting: self.ana_reporting(),
+            share_mode: self.share_mode(),
+            kato_ms: kato.kato_override.then_some(kato.kato_ms).flatten(),
+            identify: self.identify_overrides(),
+            reservations: self.export_reservations(),
+        };
+        info!(?export, "Exported subsystem {}", export.nqn);
+        Ok(export)
+    }
+
+    /// Exports the reservations of the namespace of the bdev of the
+    /// subsystem, its first one: private namespaces are not exported.
+    fn export_reservations(&self) -> Vec<ExportedReservations> {
+        let ns = unsafe { spdk_nvmf_subsystem_get_first_ns(self.0.as_ptr()) };
+        if ns.is_null() {
+            Vec::new()
+        } else {
+            vec![unsafe { export_ns_reservations(&*ns) }]
+        }
+    }
+}
+
+/// Imports the definition of a subsystem exported from another node by
+/// sharing its bdev, which must exist on this node, along with the
+/// reservations of its namespace. The listeners are those exported on the
+/// address of the target of this node, unless given.
+pub async fn import_subsystem(
+    export: &SubsystemExport,
+    listeners: Option<Vec<NvmfListener>>,
+) -> Result<NvmfSubsystem, Error> {
+    let mut bdev =
+        UntypedBdev::lookup_by_name(&export.bdev).ok_or_else(|| {
+            Error::ImportBdevNotFound {
+                nqn: export.nqn.clone(),
+                bdev: export.bdev.clone(),
+            }
+        })?;
+
+    let listeners = listeners.unwrap_or_else(|| {
+        export
+            .listeners
+            .iter()
+            .map(|l| NvmfListener {
+                address: None,
+                ..l.clone()
+            })
+            .collect()
+    });
+    // the reservations are loaded as the namespace is added, before the
+    // subsystem is started
+    let ptpl = import_reservations(export, &bdev)?;
+    let allowed_hosts = export
+        .allowed_hosts
+        .iter()
+        .filter(|h| !export.fenced_hosts.contains(h))
+        .cloned()
+        .collect();
+    let props = NvmfShareProps::new()
+        .with_nqn(Some(export.nqn.clone()))
+        .with_serial(Some(export.serial.clone()))
+        .with_model(Some(export.model.clone()))
+        .with_range(Some((export.min_cntlid, export.max_cntlid)))
+        .with_allowed_hosts(allowed_hosts)
+        .with_ptpl(ptpl)
+        .with_listeners(listeners)
+        .with_ana(export.ana_reporting)
+        .with_share_mode(export.share_mode)
+        .with_kato(export.kato_ms)
+        .with_identify(
+            (!export.identify.is_empty()).then(|| export.identify.clone()),
+        );
+    Pin::new(&mut bdev)
+        .share_nvmf(Some(props))
+        .await
+        .map_err(|e| Error::Share {
+            bdev: export.bdev.clone(),
+            msg: e.to_string(),
+        })?;
+
+    let subsystem = NvmfSubsystem::lookup_by_nqn(&export.nqn)?;
+    for host in &export.fenced_hosts {
+        subsystem.fence_host(host).await?;
+    }
+
+    info!("Imported subsystem {} on bdev {}", export.nqn, export.bdev);
+    Ok(subsystem)
+}
+
+/// Exports the reservations of a namespace.
+unsafe fn export_ns_reservations(ns: &spdk_nvmf_ns) -> ExportedReservations {
+    let mut registrants = Vec::new();
+    let mut holder = None;
+    let mut reg: *mut spdk_nvmf_registrant = ns.registrants.tqh_first;
+    while !reg.is_null() {
+        let hostid = uuid::Uuid::from_bytes((*reg).hostid.u.raw).to_string();
+        if reg == ns.holder {
+            holder = Some(hostid.clone());
+        }
+        registrants.push(ExportedRegistrant {
+            hostid,
+            rkey: (*reg).rkey,
+        });
+        reg = (*reg).link.tqe_next;
+    }
+    ExportedReservations {
+        nsid: ns.nsid,
+        rtype: ns.rtype,
+        crkey: ns.crkey,
+        holder,
+        generation: ns.gen,
+        ptpl_activated: ns.ptpl_activated,
+        registrants,
+    }
+}
+
+/// File the reservations of the namespace of an imported bdev are loaded
+/// from, and persisted to through power loss.
+struct ImportPtpl {
+    uuid: String,
+}
+
+impl PtplFileOps for ImportPtpl {
+    fn destroy(&self) -> Result<(), std::io::Error> {
+        if let Some(path) = self.path() {
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn subpath(&self) -> std::path::PathBuf {
+        std::path::PathBuf::from("import/")
+            .join(&self.uuid)
+            .with_extension("json")
+    }
+}
+
+/// A registrant, as persisted through power loss by SPDK.
+#[derive(Serialize)]
+struct PtplRegistrant {
+    rkey: u64,
+    host_uuid: String,
+}
+
+/// Reservations of a namespace, as persisted through power loss by SPDK.
+#[derive(Serialize)]
+struct PtplReservations {
+    /// SPDK only loads the reservations persisted through power loss.
+    ptpl: bool,
+    rtype: u32,
+    crkey: u64,
+    bdev_uuid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    holder_uuid: Option<String>,
+    registrants: Vec<PtplRegistrant>,
+}
+
+/// Writes the exported reservations of the namespace of the bdev to the
+/// file SPDK loads them from as the namespace is added. The file is written
+/// even without any registrant, so that none is left from a previous import.
+fn import_reservations(
+    export: &SubsystemExport,
+    bdev: &UntypedBdev,
+) -> Result<Option<PtplProps>, Error> {
+    let error = |reason: String| Error::ImportReservations {
+        nqn: export.nqn.clone(),
+        reason,
+    };
+    // the namespace of the bdev is the first one
+    let exported = export.reservations.first();
+    let registrants = exported.map_or(&[][..], |r| &r.registrants);
+    for r in registrants {
+        uuid::Uuid::parse_str(&r.hostid).map_err(|e| Error::InvalidExport {
+            nqn: export.nqn.clone(),
+            reason: format!("invalid host ID '{}': {e}", r.hostid),
+        })?;
+    }
+
+    let ptpl = ImportPtpl {
+        uuid: bdev.uuid_as_string(),
+    };
+    let Some(props) = ptpl.create().map_err(|e| error(e.to_string()))? else {
+        if registrants.is_empty() {
+            return Ok(None);
+        }
+        return Err(error("no PTPL directory is configured".to_string()));
+    };
+
+    // a reservation is only held along with its holder
+    let (rtype, crkey, holder) = match exported {
+        Some(r) if r.holder.is_some() => (r.rtype, r.crkey, r.holder.clone()),
+        _ => (0, 0, None),
+    };
+    let reservations = PtplReservations {
+        ptpl: true,
+        rtype,
+        crkey,
+        bdev_uuid: bdev.uuid_as_string(),
+        holder_uuid: holder,
+        registrants: registrants
+            .iter()
+            .map(|r| PtplRegistrant {
+                rkey: r.rkey,
+                host_uuid: r.hostid.clone(),
+            })
+            .collect(),
+    };
+    let data = serde_json::to_vec_pretty(&reservations)
+        .map_err(|e| error(e.to_string()))?;
+    std::fs::write(props.path(), data).map_err(|e| error(e.to_string()))?;
+    Ok(Some(props))
+}
+
+/// Arguments of the `mayastor_subsystem_import` method.
+#[derive(Debug, Deserialize)]
+struct SubsystemImportArgs {
+    /// Definition of the subsystem exported from its node.
+    export: SubsystemExport,
+    /// Listeners of the subsystem, those exported on the address of the
+    /// target when not given.
+    #[serde(default)]
+    listeners: Option<Vec<NvmfListener>>,
+}
+
+/// Registers the JSON-RPC methods of the subsystem migration.
+pub(super) fn register_rpc_methods() {
+    // pause a subsystem and export its definition, with the
+    // reservations of its namespaces, to move it to another node; the
+    // subsystem stays paused until resumed or unshared
+    jsonrpc_register::<SubsystemArgs, _, _, Error>(
+        "mayastor_subsystem_export",
+        |args| {
+            async move {
+                NvmfSubsystem::lookup_by_nqn(&args.nqn)?.export().await
+            }
+            .boxed_local()
+        },
+    );
+
+    jsonrpc_register::<SubsystemImportArgs, _, _, Error>(
+        "mayastor_subsystem_import",
+        |args| {
+            async move {
+                import_subsystem(&args.export, args.listeners)
+                    .await
+                    .map(|s| s.get_nqn())
+            }
+            .boxed_local()
+        },
+    );
+}
@@ -3,25 +3,122 @@
 //! but also, if desired a nexus device. A target makes use of
 //! several transports, what transports that exactly is -- is flexible.
 //!
-//! In our case we currently only deal with TCP. We create two transports
-//! one for the frontend (nexus) and one for the backend (replica)
+//! In our case we currently only deal with TCP. We listen on two ports, one
+//! for the frontend (nexus) and one for the backend (replica). Optionally, a
+//! separate target with its own transport and cores serves the backend so
+//! that the replication load is isolated from the host I/O.
 //!
 //! As connections come on, we randomly schedule them across cores by putting
 //! the qpair in a poll group that is allocated during reactor start.
 use std::{cell::RefCell, mem::zeroed};
 
 use nix::errno::Errno;
+use serde::Deserialize;
 use snafu::Snafu;
 
 pub use admin_cmd::{set_snapshot_time, NvmeCpl, NvmfReq};
+pub use ana::{NvmfAnaState, NvmfListenerAna};
+pub use batch::{
+    BatchStateReport,
+    SubsystemStateChange,
+    SubsystemStateFailure,
+};
+pub use busy_retry::{busy_retry_stats, BusyRetryStats};
+pub(crate) use cntlid_range::resolve_cntlid_range;
+pub use cntlid_range::{node_cntlid_range, set_node_cntlid_range};
+pub use crd::{crd_policies, set_crd_policies};
+pub use discovery::{
+    add_discovery_referral,
+    discovery_info,
+    remove_discovery_referral,
+    set_discovery_restrict_hosts,
+    DiscoveryInfo,
+};
+pub use drain::{DrainArgs, DrainSample, DrainStats, OutstandingCommands};
+pub use duplicate_host::{duplicate_hosts, DuplicateHost, DuplicateHosts};
+pub use handle::{
+    Active,
+    NvmfSubsystemHandle,
+    Paused,
+    Stopped,
+    SubsystemHandle,
+};
+pub use host_auth::HostDhChap;
+pub use host_group::{expand_hosts, HostGroup, HOST_GROUP_PREFIX};
+pub use host_hooks::{
+    register_host_event_hook,
+    unregister_host_event_hook,
+    HostEvent,
+    HostEventHook,
+    HostEventKind,
+};
+pub use identify::IdentifyOverrides;
+pub use inspect::{
+    nvmf_subsystems,
+    NvmfControllerInfo,
+    NvmfNamespaceInfo,
+    NvmfSubsystemInfo,
+};
+pub use io_stats::{
+    nvmf_io_stats,
+    nvmf_io_stats_loop,
+    HostIoCounters,
+    HostIoRates,
+    InFlightCommands,
+    NvmfHostIoStats,
+    NvmfSubsystemIoStats,
+    TargetIoCounters,
+};
+pub use kato::{
+    default_kato,
+    validate_kato,
+    SubsystemKato,
+    KATO_MAX_MS,
+    KATO_MIN_MS,
+};
+pub use migrate::{
+    import_subsystem,
+    ExportedRegistrant,
+    ExportedReservations,
+    SubsystemExport,
+};
+pub use nqn_index::{nqn_index_stats, NqnIndexStats};
 use poll_groups::PollGroup;
+pub use port_pool::{nvmf_ports, NvmfPortAllocation};
+pub use readiness::{share_readiness, ShareCheck, ShareReadiness};
+pub use share_audit::{share_audit_loop, DriftKind, ShareAudit, ShareDrift};
+pub use share_lease::{
+    expire_share_leases,
+    share_lease_loop,
+    ExpiredShare,
+    ShareLease,
+};
+pub use share_mode::NvmfShareMode;
+pub(crate) use share_state::{
+    load_state,
+    record_identity,
+    replay_share,
+    save_state,
+};
+pub use share_state::{persisted_share, PersistedShare};
 use spdk_rs::libspdk::{
     spdk_subsystem,
     spdk_subsystem_fini_next,
     spdk_subsystem_init_next,
 };
-pub use subsystem::{NvmfSubsystem, SubType};
-pub use target::Target;
+pub use stale::{reconcile_subsystems, stale_subsystem_loop, StaleSubsystem};
+pub use subsystem::{
+    nqn_prefix,
+    validate_model,
+    validate_nqn,
+    validate_nqn_prefix,
+    validate_serial,
+    NvmfSubsystem,
+    SubType,
+};
+pub use target::{Target, TargetKind};
+pub use transport::{NvmfListener, NvmfTransport};
+pub use zero_copy::{zero_copy_stats, SubsystemZeroCopy, ZeroCopyStats};
 
 use crate::{
     jsonrpc::{Code, RpcErrorCode},
@@ -29,10 +126,39 @@ use crate::{
 };
 
 mod admin_cmd;
+mod ana;
+mod batch;
+mod busy_retry;
+mod cntlid_range;
+mod crd;
+mod discovery;
+mod drain;
+mod duplicate_host;
+mod fence;
+mod handle;
+mod host_auth;
+mod host_group;
+mod host_hooks;
+mod identify;
+mod inspect;
+mod io_stats;
+mod kato;
+mod migrate;
+mod nqn_index;
+mod ns_visibility;
 mod poll_groups;
+mod port_pool;
+mod readiness;
+mod resv_release;
+mod share_audit;
+mod share_lease;
+mod share_mode;
+mod share_state;
+mod stale;
 mod subsystem;
 mod target;
 mod transport;
+mod zero_copy;
 
 // wrapper around our NVMF subsystem used for registration
 pub struct Nvmf(pub(crate) *mut spdk_subsystem);
@@ -45,7 +171,90 @@ impl Default for Nvmf {
 
 impl RpcErrorCode for Error {
     fn rpc_error_code(&self) -> Code {
-        Code::InternalError
+        match self {
+            Self::NotFound {
+                ..
+            } => Code::NotFound,
+            Self::InvalidNqn {
+                ..
+            } => Code::InvalidParams,
+            Self::NqnInUse {
+                ..
+            } => Code::AlreadyExists,
+            Self::HostGroupNotFound {
+                ..
+            }
+            | Self::LeaseNotFound {
+                ..
+            }
+            | Self::KeyNotFound {
+                ..
+            }
+            | Self::AnaGroupNotFound {
+                ..
+            }
+            | Self::ReferralNotFound {
+                ..
+            }
+            | Self::ImportBdevNotFound {
+                ..
+            }
+            | Self::NamespaceNotFound {
+                ..
+            }
+            | Self::BdevNotFound {
+                ..
+            } => Code::NotFound,
+            Self::HostGroupExists {
+                ..
+            }
+            | Self::ReferralExists {
+                ..
+            } => Code::AlreadyExists,
+            Self::HostGroupInUse {
+                ..
+            }
+            | Self::InvalidHostGroup {
+                ..
+            }
+            | Self::InvalidHostAuth {
+                ..
+            }
+            | Self::InvalidIdentity {
+                ..
+            }
+            | Self::InvalidCntlidRange {
+                ..
+            }
+            | Self::InvalidIdentifyOverride {
+                ..
+            }
+            | Self::InvalidKato {
+                ..
+            }
+            | Self::InvalidReferral {
+                ..
+            }
+            | Self::InvalidShareMode {
+                ..
+            }
+            | Self::InvalidAna {
+                ..
+            }
+            | Self::HostFenced {
+                ..
+            }
+            | Self::InvalidFence {
+                ..
+            }
+            | Self::InvalidExport {
+                ..
+            }
+            | Self::InvalidNamespace {
+                ..
+            } => Code::InvalidParams,
+            _ => Code::InternalError,
+        }
     }
 }
 
@@ -84,12 +293,130 @@ pub enum Error {
     Listener { nqn: String, trid: String },
     #[snafu(display("Interior nul byte found for host {}", host))]
     HostCstrNul { host: String },
+    #[snafu(display("Subsystem {} not found", nqn))]
+    NotFound { nqn: String },
+    #[snafu(display("Invalid NQN '{}': {}", nqn, reason))]
+    InvalidNqn { nqn: String, reason: String },
+    #[snafu(display("NQN {} is already in use", nqn))]
+    NqnInUse { nqn: String },
+    #[snafu(display("Host group {} not found", name))]
+    HostGroupNotFound { name: String },
+    #[snafu(display("Host group {} already exists", name))]
+    HostGroupExists { name: String },
+    #[snafu(display("Host group {} is referred to by a share", name))]
+    HostGroupInUse { name: String },
+    #[snafu(display("Invalid host group name '{}': {}", name, reason))]
+    InvalidHostGroup { name: String, reason: String },
+    #[snafu(display("Failed to persist host group {}: {}", name, reason))]
+    PersistHostGroup { name: String, reason: String },
+    #[snafu(display("Share {} has no lease", nqn))]
+    LeaseNotFound { nqn: String },
+    #[snafu(display("Key {} not found in the keyring", name))]
+    KeyNotFound { name: String },
+    #[snafu(display("Invalid authentication of host '{}': {}", host, reason))]
+    InvalidHostAuth { host: String, reason: String },
+    #[snafu(display("ANA group {} not found in subsystem {}", anagrpid, nqn))]
+    AnaGroupNotFound { nqn: String, anagrpid: u32 },
+    #[snafu(display("No free port left in range {} for {}", range, nqn))]
+    NoFreePort { nqn: String, range: String },
+    #[snafu(display("Invalid controller ID range '{}': {}", range, reason))]
+    InvalidCntlidRange { range: String, reason: String },
+    #[snafu(display("Invalid {} '{}': {}", field, value, reason))]
+    InvalidIdentity {
+        field: String,
+        value: String,
+        reason: String,
+    },
+    #[snafu(display("Invalid identify controller override: {}", reason))]
+    InvalidIdentifyOverride { reason: String },
+    #[snafu(display("Invalid keep alive timeout {}ms: {}", kato_ms, reason))]
+    InvalidKato { kato_ms: u32, reason: String },
+    #[snafu(display("Invalid discovery referral {}: {}", referral, reason))]
+    InvalidReferral { referral: String, reason: String },
+    #[snafu(display("Discovery referral {} already exists", referral))]
+    ReferralExists { referral: String },
+    #[snafu(display("Discovery referral {} not found", referral))]
+    ReferralNotFound { referral: String },
+    #[snafu(display("Invalid share mode of {}: {}", nqn, reason))]
+    InvalidShareMode { nqn: String, reason: String },
+    #[snafu(display("Invalid ANA reporting of {}: {}", nqn, reason))]
+    InvalidAna { nqn: String, reason: String },
+    #[snafu(display("Host '{}' is fenced from {}", host, nqn))]
+    HostFenced { nqn: String, host: String },
+    #[snafu(display("Cannot fence a host from {}: {}", nqn, reason))]
+    InvalidFence { nqn: String, reason: String },
+    #[snafu(display(
+        "Failed to persist the fenced hosts of {}: {}",
+        nqn,
+        reason
+    ))]
+    PersistFence { nqn: String, reason: String },
+    #[snafu(display("Bdev {} to import subsystem {} not found", bdev, nqn))]
+    ImportBdevNotFound { nqn: String, bdev: String },
+    #[snafu(display("Invalid export of subsystem {}: {}", nqn, reason))]
+    InvalidExport { nqn: String, reason: String },
+    #[snafu(display(
+        "Failed to write the reservations to import for {}: {}",
+        nqn,
+        reason
+    ))]
+    ImportReservations { nqn: String, reason: String },
+    #[snafu(display("Bdev {} not found", bdev))]
+    BdevNotFound { bdev: String },
+    #[snafu(display("Namespace {} of subsystem {} not found", nsid, nqn))]
+    NamespaceNotFound { nqn: String, nsid: u32 },
+    #[snafu(display("Invalid namespace {} of {}: {}", nsid, nqn, reason))]
+    InvalidNamespace {
+        nqn: String,
+        nsid: u32,
+        reason: String,
+    },
+    #[snafu(display(
+        "Failed to release the reservations of a host of {}: {}",
+        nqn,
+        reason
+    ))]
+    ResvRelease { nqn: String, reason: String },
 }
 
 thread_local! {
     pub (crate) static NVMF_PGS: RefCell<Vec<PollGroup>> = RefCell::new(Vec::new());
 }
 
+/// Arguments of the methods acting on a single subsystem.
+#[derive(Debug, Deserialize)]
+struct SubsystemArgs {
+    /// NQN of the subsystem.
+    nqn: String,
+}
+
+/// Registers the JSON-RPC methods of the NVMf target.
+fn register_rpc_methods() {
+    share_audit::register_rpc_methods();
+    drain::register_rpc_methods();
+    host_group::register_rpc_methods();
+    stale::register_rpc_methods();
+    share_lease::register_rpc_methods();
+    zero_copy::register_rpc_methods();
+    host_auth::register_rpc_methods();
+    ana::register_rpc_methods();
+    port_pool::register_rpc_methods();
+    inspect::register_rpc_methods();
+    cntlid_range::register_rpc_methods();
+    identify::register_rpc_methods();
+    kato::register_rpc_methods();
+    busy_retry::register_rpc_methods();
+    batch::register_rpc_methods();
+    discovery::register_rpc_methods();
+    readiness::register_rpc_methods();
+    nqn_index::register_rpc_methods();
+    crd::register_rpc_methods();
+    io_stats::register_rpc_methods();
+    migrate::register_rpc_methods();
+    ns_visibility::register_rpc_methods();
+    duplicate_host::register_rpc_methods();
+}
+
 impl Nvmf {
     /// initialize a new subsystem that handles NVMF (confusing names, cannot
     /// help it)
@@ -98,8 +425,13 @@ impl Nvmf {
 
         // this code only ever gets run on the first core
 
+        register_rpc_methods();
+
         // set up custom NVMe Admin command handler
         admin_cmd::setup_create_snapshot_hdlr();
+        identify::setup_identify_hdlr();
+        // and the hooks on the host events
+        inspect::register_connections_hook();
 
         if Config::get().nexus_opts.nvmf_enable {
             NVMF_TGT.with(|tgt| tgt.borrow_mut().next_state());
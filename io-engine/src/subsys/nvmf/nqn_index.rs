@@ -0,0 +1,85 @@
+//! Index of the bdevs of the subsystems by NQN.
+//!
+//! Every host connect and disconnect raises a subsystem event, whose target,
+//! a nexus or a replica, is looked up by the NQN of the subsystem. Rather
+//! than walking all the bdevs of the node for every event, the name of the
+//! bdev of a subsystem is indexed by its NQN as its namespace is added, and
+//! removed from the index as the subsystem is destroyed. The bdev itself is
+//! still looked up by name, so that a bdev removed under the subsystem is
+//! never returned.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use futures::FutureExt;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use crate::jsonrpc::{jsonrpc_register, JsonRpcError};
+
+/// Names of the bdevs of the subsystems, by subsystem NQN.
+static NQN_BDEVS: Lazy<Mutex<HashMap<String, String>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Lookups which found the NQN in the index.
+static HITS: AtomicU64 = AtomicU64::new(0);
+/// Lookups which did not, falling back to deriving the bdev from the NQN.
+static MISSES: AtomicU64 = AtomicU64::new(0);
+
+/// Statistics of the index of the bdevs of the subsystems.
+#[derive(Debug, Clone, Serialize)]
+pub struct NqnIndexStats {
+    /// Subsystems in the index.
+    pub entries: usize,
+    /// Lookups which found the NQN in the index.
+    pub hits: u64,
+    /// Lookups which did not.
+    pub misses: u64,
+}
+
+/// Indexes the bdev of a subsystem.
+pub(crate) fn index_nqn(nqn: &str, bdev: &str) {
+    NQN_BDEVS
+        .lock()
+        .unwrap()
+        .insert(nqn.to_string(), bdev.to_string());
+}
+
+/// Removes a subsystem which is being destroyed from the index.
+pub(crate) fn forget_nqn(nqn: &str) {
+    NQN_BDEVS.lock().unwrap().remove(nqn);
+}
+
+/// Returns the name of the bdev of a subsystem, if indexed.
+pub(crate) fn indexed_bdev(nqn: &str) -> Option<String> {
+    let name = NQN_BDEVS.lock().unwrap().get(nqn).cloned();
+    match name {
+        Some(_) => HITS.fetch_add(1, Ordering::Relaxed),
+        None => MISSES.fetch_add(1, Ordering::Relaxed),
+    };
+    name
+}
+
+/// Returns the statistics of the index.
+pub fn nqn_index_stats() -> NqnIndexStats {
+    NqnIndexStats {
+        entries: NQN_BDEVS.lock().unwrap().len(),
+        hits: HITS.load(Ordering::Relaxed),
+        misses: MISSES.load(Ordering::Relaxed),
+    }
+}
+
+/// Registers the JSON-RPC methods of the NQN index.
+pub(super) fn register_rpc_methods() {
+    // statistics of the index the targets of the subsystem events are
+    // looked up in
+    jsonrpc_register::<(), _, _, JsonRpcError>(
+        "mayastor_nqn_index_stats",
+        |_| async move { Ok(nqn_index_stats()) }.boxed_local(),
+    );
+}
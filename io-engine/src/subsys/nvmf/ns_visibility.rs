@@ -0,0 +1,315 @@
+//! Private namespaces of the subsystems.
+//!
+//! A subsystem shares its bdev as namespace 1, which every host allowed to
+//! connect sees. To pack the volumes of several tenants into fewer
+//! subsystems, more bdevs may be attached to a subsystem as private
+//! namespaces: these are added without being visible to any host, and then
+//! made visible to the hosts entitled to them only, so that a host never
+//! sees the namespaces of another. The hosts must still be allowed to
+//! connect to the subsystem.
+//!
+//! The subsystem is paused while its namespaces or their visibility change;
+//! the controllers of the hosts concerned are notified that their namespaces
+//! changed. Private namespaces are not persisted nor exported: they are
+//! gone, and must be attached again, once the subsystem is destroyed.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::Mutex,
+};
+
+use futures::FutureExt;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use spdk_rs::libspdk::{
+    spdk_nvmf_ns_add_host,
+    spdk_nvmf_ns_remove_host,
+    spdk_nvmf_subsystem_remove_ns,
+};
+
+use super::{Error, NvmfSubsystem};
+use crate::{core::UntypedBdev, jsonrpc::jsonrpc_register};
+
+/// Hosts the private namespaces are visible to, by subsystem NQN and
+/// namespace ID.
+static PRIVATE_NAMESPACES: Lazy<
+    Mutex<HashMap<String, BTreeMap<u32, BTreeSet<String>>>>,
+> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Forgets the private namespaces of a subsystem which is being destroyed.
+pub(crate) fn forget_ns_visibility(nqn: &str) {
+    PRIVATE_NAMESPACES.lock().unwrap().remove(nqn);
+}
+
+impl NvmfSubsystem {
+    /// Attaches a bdev to the subsystem as a private namespace, visible to
+    /// the given hosts only, and returns its ID.
+    pub async fn attach_private_namespace(
+        &self,
+        bdev: &UntypedBdev,
+        hosts: &[String],
+    ) -> Result<u32, Error> {
+        for host in hosts {
+            Self::cstr(host)?;
+        }
+
+        self.pause().await?;
+        let attached =
+            self.add_namespace_with(bdev, None, false).and_then(|nsid| {
+                let visible = hosts.iter().try_for_each(|host| {
+                    self.set_ns_host_visible(nsid, host, true)
+                });
+                if visible.is_err() {
+                    unsafe {
+                        spdk_nvmf_subsystem_remove_ns(self.0.as_ptr(), nsid)
+                    };
+                }
+                visible.map(|_| nsid)
+            });
+        self.resume().await?;
+        let nsid = attached?;
+
+        PRIVATE_NAMESPACES
+            .lock()
+            .unwrap()
+            .entry(self.get_nqn())
+            .or_default()
+            .insert(nsid, hosts.iter().cloned().collect());
+        info!(
+            "Bdev {} attached to subsystem {} as private namespace {nsid}",
+            bdev.name(),
+            self.get_nqn()
+        );
+        Ok(nsid)
+    }
+
+    /// Detaches a private namespace from the subsystem.
+    pub async fn detach_private_namespace(
+        &self,
+        nsid: u32,
+    ) -> Result<(), Error> {
+        self.private_namespace_hosts(nsid)?;
+
+        self.pause().await?;
+        let rc =
+            unsafe { spdk_nvmf_subsystem_remove_ns(self.0.as_ptr(), nsid) };
+        self.resume().await?;
+        if rc != 0 {
+            return Err(Error::InvalidNamespace {
+                nqn: self.get_nqn(),
+                nsid,
+                reason: format!("failed to remove the namespace: {rc}"),
+            });
+        }
+
+        let nqn = self.get_nqn();
+        let mut namespaces = PRIVATE_NAMESPACES.lock().unwrap();
+        if let Some(nss) = namespaces.get_mut(&nqn) {
+            nss.remove(&nsid);
+            if nss.is_empty() {
+                namespaces.remove(&nqn);
+            }
+        }
+        info!("Private namespace {nsid} detached from subsystem {nqn}");
+        Ok(())
+    }
+
+    /// Makes a private namespace of the subsystem visible to a host, or
+    /// hides it from the host.
+    pub async fn set_namespace_visible(
+        &self,
+        nsid: u32,
+        host: &str,
+        visible: bool,
+    ) -> Result<(), Error> {
+        let hosts = self.private_namespace_hosts(nsid)?;
+        if hosts.contains(host) == visible {
+            return Ok(());
+        }
+
+        self.pause().await?;
+        let result = self.set_ns_host_visible(nsid, host, visible);
+        self.resume().await?;
+        result?;
+
+        if let Some(hosts) = PRIVATE_NAMESPACES
+            .lock()
+            .unwrap()
+            .get_mut(&self.get_nqn())
+            .and_then(|nss| nss.get_mut(&nsid))
+        {
+            if visible {
+                hosts.insert(host.to_string());
+            } else {
+                hosts.remove(host);
+            }
+        }
+        info!(
+            "Private namespace {nsid} of subsystem {} {} host '{host}'",
+            self.get_nqn(),
+            if visible { "visible to" } else { "hidden from" }
+        );
+        Ok(())
+    }
+
+    /// Returns the hosts a namespace of the subsystem is visible to, if it
+    /// is private, or `None` if it is visible to all of them.
+    pub fn namespace_visible_hosts(&self, nsid: u32) -> Option<Vec<String>> {
+        PRIVATE_NAMESPACES
+            .lock()
+            .unwrap()
+            .get(&self.get_nqn())
+            .and_then(|nss| nss.get(&nsid))
+            .map(|hosts| hosts.iter().cloned().collect())
+    }
+
+    /// Returns the hosts a private namespace of the subsystem is visible
+    /// to, or an error if the namespace is not private.
+    fn private_namespace_hosts(
+        &self,
+        nsid: u32,
+    ) -> Result<BTreeSet<String>, Error> {
+        let nqn = self.get_nqn();
+        if let Some(hosts) = PRIVATE_NAMESPACES
+            .lock()
+            .unwrap()
+            .get(&nqn)
+            .and_then(|nss| nss.get(&nsid))
+        {
+            return Ok(hosts.clone());
+        }
+
+        if self.namespaces().iter().any(|ns| ns.nsid == nsid) {
+            Err(Error::InvalidNamespace {
+                nqn,
+                nsid,
+                reason: "the namespace is visible to all the hosts".to_string(),
+            })
+        } else {
+            Err(Error::NamespaceNotFound {
+                nqn,
+                nsid,
+            })
+        }
+    }
+
+    /// Makes a namespace of the subsystem, which must be paused, visible to
+    /// a host or hides it from the host.
+    fn set_ns_host_visible(
+        &self,
+        nsid: u32,
+        host: &str,
+        visible: bool,
+    ) -> Result<(), Error> {
+        let hostnqn = Self::cstr(host)?;
+        let rc = unsafe {
+            if visible {
+                spdk_nvmf_ns_add_host(
+                    self.0.as_ptr(),
+                    nsid,
+                    hostnqn.as_ptr(),
+                    0,
+                )
+            } else {
+                spdk_nvmf_ns_remove_host(
+                    self.0.as_ptr(),
+                    nsid,
+                    hostnqn.as_ptr(),
+                    0,
+                )
+            }
+        };
+        if rc != 0 {
+            return Err(Error::InvalidNamespace {
+                nqn: self.get_nqn(),
+                nsid,
+                reason: format!(
+                    "failed to change its visibility to host '{host}': {rc}"
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Arguments of the `mayastor_subsystem_ns_attach` method.
+#[derive(Debug, Deserialize)]
+struct NamespaceAttachArgs {
+    /// NQN of the subsystem.
+    nqn: String,
+    /// Name of the bdev to attach as a private namespace.
+    bdev: String,
+    /// Hosts the namespace is visible to.
+    #[serde(default)]
+    hosts: Vec<String>,
+}
+
+/// Arguments of the methods acting on a namespace of a subsystem.
+#[derive(Debug, Deserialize)]
+struct NamespaceArgs {
+    /// NQN of the subsystem.
+    nqn: String,
+    /// ID of the namespace.
+    nsid: u32,
+}
+
+/// Arguments of the `mayastor_subsystem_ns_visibility_set` method.
+#[derive(Debug, Deserialize)]
+struct NamespaceVisibilityArgs {
+    /// NQN of the subsystem.
+    nqn: String,
+    /// ID of the private namespace.
+    nsid: u32,
+    /// NQN of the host.
+    host: String,
+    /// Whether the namespace is visible to the host.
+    visible: bool,
+}
+
+/// Registers the JSON-RPC methods of the private namespaces.
+pub(super) fn register_rpc_methods() {
+    // attach a bdev to a subsystem as a private namespace, visible to
+    // the given hosts only, so that the volumes of several tenants may
+    // share a subsystem; returns the ID of the namespace
+    jsonrpc_register::<NamespaceAttachArgs, _, _, Error>(
+        "mayastor_subsystem_ns_attach",
+        |args| {
+            async move {
+                let bdev = UntypedBdev::lookup_by_name(&args.bdev).ok_or(
+                    Error::BdevNotFound {
+                        bdev: args.bdev.clone(),
+                    },
+                )?;
+                NvmfSubsystem::lookup_by_nqn(&args.nqn)?
+                    .attach_private_namespace(&bdev, &args.hosts)
+                    .await
+            }
+            .boxed_local()
+        },
+    );
+
+    jsonrpc_register::<NamespaceArgs, _, _, Error>(
+        "mayastor_subsystem_ns_detach",
+        |args| {
+            async move {
+                NvmfSubsystem::lookup_by_nqn(&args.nqn)?
+                    .detach_private_namespace(args.nsid)
+                    .await
+            }
+            .boxed_local()
+        },
+    );
+
+    // make a private namespace visible to a host, or hide it from it
+    jsonrpc_register::<NamespaceVisibilityArgs, _, _, Error>(
+        "mayastor_subsystem_ns_visibility_set",
+        |args| {
+            async move {
+                NvmfSubsystem::lookup_by_nqn(&args.nqn)?
+                    .set_namespace_visible(args.nsid, &args.host, args.visible)
+                    .await
+            }
+            .boxed_local()
+        },
+    );
+}
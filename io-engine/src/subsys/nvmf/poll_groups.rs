@@ -4,7 +4,7 @@ use spdk_rs::libspdk::{
     spdk_nvmf_tgt,
 };
 
-use crate::core::Mthread;
+use crate::{core::Mthread, subsys::nvmf::target::TargetKind};
 
 #[derive(Clone, Debug)]
 struct Pg(*mut spdk_nvmf_poll_group);
@@ -13,13 +13,16 @@ struct Pg(*mut spdk_nvmf_poll_group);
 #[derive(Clone, Debug)]
 pub(crate) struct PollGroup {
     pub thread: Mthread,
+    /// the kind of the target the poll group belongs to
+    pub kind: TargetKind,
     group: Pg,
 }
 
 impl PollGroup {
-    pub fn new(tgt: *mut spdk_nvmf_tgt, mt: Mthread) -> Self {
+    pub fn new(tgt: *mut spdk_nvmf_tgt, kind: TargetKind, mt: Mthread) -> Self {
         Self {
             thread: mt,
+            kind,
             group: Pg(unsafe { spdk_nvmf_poll_group_create(tgt) }),
         }
     }
@@ -0,0 +1,167 @@
+//! Pool of the listener ports of the subsystems.
+//!
+//! By default, the listeners of the subsystems without an explicit port all
+//! share the port of their target. When a port range is configured, every
+//! subsystem is given a port of its own from the range for them instead, so
+//! that several target personalities running on a node do not conflict over
+//! a single well-known port.
+//!
+//! When a state file is configured, the ports given out are kept in it, so
+//! that a subsystem created again after a restart, e.g. a replica re-shared
+//! at startup, listens on the same port and its URI does not change.
+
+use std::{collections::BTreeMap, fs, sync::Mutex};
+
+use futures::FutureExt;
+use nix::errno::Errno;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use super::{target::TargetKind, Error};
+use crate::{
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+    subsys::{config::opts::parse_port_range, Config},
+};
+
+/// A port given to a subsystem from the port range.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NvmfPortAllocation {
+    /// NQN of the subsystem.
+    pub nqn: String,
+    /// Kind of the target the subsystem belongs to.
+    pub kind: TargetKind,
+    /// Port the subsystem listens on.
+    pub port: u16,
+}
+
+/// Ports given to the subsystems, by NQN, as loaded from the state file.
+static PORTS: Lazy<Mutex<BTreeMap<String, NvmfPortAllocation>>> =
+    Lazy::new(|| Mutex::new(load()));
+
+/// Loads the ports given out before a restart, if any.
+fn load() -> BTreeMap<String, NvmfPortAllocation> {
+    let Some(path) = Config::get().nexus_opts.nvmf_port_state.clone() else {
+        return BTreeMap::new();
+    };
+    let ports = match fs::read(&path) {
+        Ok(bytes) => serde_json::from_slice::<Vec<NvmfPortAllocation>>(&bytes)
+            .unwrap_or_else(|error| {
+                warn!(%error, "Ignoring invalid port state file '{path}'");
+                Vec::new()
+            }),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            Vec::new()
+        }
+        Err(error) => {
+            warn!(%error, "Failed to read port state file '{path}'");
+            Vec::new()
+        }
+    };
+    ports.into_iter().map(|p| (p.nqn.clone(), p)).collect()
+}
+
+/// Saves the ports given out, if a state file is configured. A failure is
+/// only logged as the subsystems keep listening on their ports.
+fn persist(ports: &BTreeMap<String, NvmfPortAllocation>) {
+    let Some(path) = Config::get().nexus_opts.nvmf_port_state.clone() else {
+        return;
+    };
+    let ports = ports.values().collect::<Vec<_>>();
+    let result = serde_json::to_vec_pretty(&ports)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| fs::write(&path, bytes).map_err(|e| e.to_string()));
+    if let Err(error) = result {
+        error!(%error, "Failed to save port state file '{path}'");
+    }
+}
+
+/// Returns the port of the given subsystem from the port range, giving it
+/// one if it has none yet, or None when no port range is configured.
+pub(crate) fn allocate_port(
+    nqn: &str,
+    kind: TargetKind,
+) -> Result<Option<u16>, Error> {
+    let mut ports = PORTS.lock().unwrap();
+    let Some(port) = find_port(&ports, nqn)? else {
+        return Ok(None);
+    };
+
+    let allocation = NvmfPortAllocation {
+        nqn: nqn.to_string(),
+        kind,
+        port,
+    };
+    if ports.get(nqn) != Some(&allocation) {
+        ports.insert(nqn.to_string(), allocation);
+        persist(&ports);
+    }
+    Ok(Some(port))
+}
+
+/// Returns the port the given subsystem would get from the port range,
+/// without giving it, or None when no port range is configured.
+pub(crate) fn peek_port(nqn: &str) -> Result<Option<u16>, Error> {
+    find_port(&PORTS.lock().unwrap(), nqn)
+}
+
+/// Finds the port of the given subsystem in the port range: the port it
+/// already has, or the first one given to no other subsystem.
+fn find_port(
+    ports: &BTreeMap<String, NvmfPortAllocation>,
+    nqn: &str,
+) -> Result<Option<u16>, Error> {
+    let cfg = Config::get();
+    let Some(range) = &cfg.nexus_opts.nvmf_port_range else {
+        return Ok(None);
+    };
+    let (first, last) =
+        parse_port_range(range).map_err(|msg| Error::Transport {
+            source: Errno::EINVAL,
+            msg,
+        })?;
+
+    // the ports of the targets are never given out
+    let targets = [
+        cfg.nexus_opts.nvmf_nexus_port,
+        cfg.nexus_opts.nvmf_replica_port,
+    ];
+
+    // a subsystem keeps its port, as long as it is in the range
+    let previous = ports
+        .get(nqn)
+        .map(|a| a.port)
+        .filter(|port| (first ..= last).contains(port));
+    previous
+        .into_iter()
+        .chain(first ..= last)
+        .find(|port| {
+            !targets.contains(port)
+                && !ports.values().any(|a| a.nqn != nqn && a.port == *port)
+        })
+        .map(Some)
+        .ok_or_else(|| Error::NoFreePort {
+            nqn: nqn.to_string(),
+            range: range.clone(),
+        })
+}
+
+/// Gives the port of a subsystem which is being destroyed back to the pool.
+pub(crate) fn release_port(nqn: &str) {
+    let mut ports = PORTS.lock().unwrap();
+    if ports.remove(nqn).is_some() {
+        persist(&ports);
+    }
+}
+
+/// Returns the ports given to the subsystems from the port range.
+pub fn nvmf_ports() -> Vec<NvmfPortAllocation> {
+    PORTS.lock().unwrap().values().cloned().collect()
+}
+
+/// Registers the JSON-RPC methods of the port pool.
+pub(super) fn register_rpc_methods() {
+    // ports given to the subsystems from the port range
+    jsonrpc_register::<(), _, _, JsonRpcError>("mayastor_nvmf_ports", |_| {
+        async move { Ok(nvmf_ports()) }.boxed_local()
+    });
+}
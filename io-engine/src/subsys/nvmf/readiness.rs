@@ -0,0 +1,261 @@
+//! Read-only check of a share before it is attempted.
+//!
+//! Sharing a bdev goes through several steps, any of which may fail half
+//! way: a host group may be unknown, a listener address invalid or the port
+//! range exhausted. The check goes through the same steps without changing
+//! anything, neither creating the subsystem nor giving it a port, and
+//! reports the outcome of every step, so that a publish can be validated
+//! end to end before any resource is taken.
+
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    expand_hosts,
+    port_pool::peek_port,
+    replay_share,
+    subsystem::make_nqn,
+    validate_kato,
+    validate_nqn,
+    HostDhChap,
+    IdentifyOverrides,
+    NvmfListener,
+    NvmfShareMode,
+    NvmfSubsystem,
+    NvmfTransport,
+    TargetKind,
+    NVMF_TGT,
+};
+use crate::{
+    core::{NvmfShareProps, UntypedBdev},
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+};
+
+/// Outcome of a step of a share.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareCheck {
+    /// Name of the step.
+    pub check: String,
+    /// Whether the step would succeed.
+    pub ok: bool,
+    /// Why the step would fail, or what it would do.
+    pub detail: Option<String>,
+}
+
+/// Readiness of a bdev to be shared with the given properties.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareReadiness {
+    /// Name of the bdev.
+    pub bdev: String,
+    /// NQN the subsystem would have.
+    pub nqn: String,
+    /// Whether all the steps would succeed.
+    pub ready: bool,
+    /// Outcome of every step, in the order of the share.
+    pub checks: Vec<ShareCheck>,
+}
+
+impl ShareReadiness {
+    fn check(&mut self, check: &str, result: Result<Option<String>, String>) {
+        let (ok, detail) = match result {
+            Ok(detail) => (true, detail),
+            Err(reason) => (false, Some(reason)),
+        };
+        self.ready &= ok;
+        self.checks.push(ShareCheck {
+            check: check.to_string(),
+            ok,
+            detail,
+        });
+    }
+}
+
+/// Checks whether the given bdev can be shared with the given properties,
+/// without changing anything. The persisted share state is replayed over
+/// them as by the share itself.
+pub fn share_readiness(
+    name: &str,
+    props: Option<NvmfShareProps>,
+) -> ShareReadiness {
+    let props = replay_share(name, NvmfShareProps::from(props)).props;
+    let nqn = props.nqn().map_or_else(|| make_nqn(name), str::to_string);
+    let mut readiness = ShareReadiness {
+        bdev: name.to_string(),
+        nqn: nqn.clone(),
+        ready: true,
+        checks: Vec::new(),
+    };
+
+    readiness.check(
+        "target",
+        if NVMF_TGT.with(|t| t.borrow().is_running()) {
+            Ok(None)
+        } else {
+            Err("the NVMF target is not running".to_string())
+        },
+    );
+
+    let bdev = UntypedBdev::lookup_by_name(name);
+    readiness.check(
+        "bdev",
+        match &bdev {
+            None => Err(format!("bdev '{name}' not found")),
+            Some(bdev) if bdev.is_claimed() => {
+                Err(format!("bdev '{name}' is already shared"))
+            }
+            Some(_) => Ok(None),
+        },
+    );
+
+    readiness.check(
+        "nqn",
+        validate_nqn(&nqn).and_then(|_| match NvmfSubsystem::find(&nqn) {
+            Some(_) => Err(format!("NQN '{nqn}' is already in use")),
+            None => Ok(None),
+        }),
+    );
+
+    readiness.check(
+        "hosts",
+        expand_hosts(props.allowed_hosts())
+            .and_then(|hosts| {
+                HostDhChap::validate(props.host_auth(), &hosts)?;
+                Ok(Some(if hosts.is_empty() {
+                    "any host is allowed".to_string()
+                } else {
+                    format!("{} hosts allowed", hosts.len())
+                }))
+            })
+            .map_err(|e| e.to_string()),
+    );
+
+    let listeners = props.listeners();
+    readiness.check(
+        "listeners",
+        NvmfListener::validate(TargetKind::of_bdev(name), &listeners)
+            .map(|_| None)
+            .map_err(|e| e.to_string()),
+    );
+
+    // only the listeners without an explicit port are given one
+    readiness.check(
+        "port",
+        if listeners.iter().all(|l| l.port.is_some()) {
+            Ok(None)
+        } else {
+            peek_port(&nqn)
+                .map(|port| port.map(|port| format!("port {port}")))
+                .map_err(|e| e.to_string())
+        },
+    );
+
+    let identify = props.identify().map_or(Ok(()), |i| i.validate());
+    readiness.check(
+        "identity",
+        NvmfSubsystem::validate_identity(props.serial(), props.model())
+            .and(identify)
+            .map(|_| None)
+            .map_err(|e| e.to_string())
+            .and_then(|_| match props.kato() {
+                Some(kato_ms) => validate_kato(kato_ms).map(|_| None),
+                None => Ok(None),
+            }),
+    );
+
+    readiness.check(
+        "share_mode",
+        match &bdev {
+            Some(bdev) => props
+                .share_mode()
+                .validate(bdev, props.allowed_hosts())
+                .map(|_| None)
+                .map_err(|e| e.to_string()),
+            None => Ok(None),
+        },
+    );
+
+    readiness.check(
+        "ana",
+        match &bdev {
+            Some(bdev) => NvmfSubsystem::validate_ana(bdev, props.ana())
+                .map(|_| None)
+                .map_err(|e| e.to_string()),
+            None => Ok(None),
+        },
+    );
+
+    readiness
+}
+
+/// Arguments of the `mayastor_share_validate` method.
+#[derive(Debug, Deserialize)]
+struct ShareValidateArgs {
+    /// Name of the bdev to share.
+    bdev: String,
+    /// Explicit NQN of the subsystem, derived from the name when not given.
+    #[serde(default)]
+    nqn: Option<String>,
+    /// Hosts allowed to connect, any host when empty.
+    #[serde(default)]
+    allowed_hosts: Vec<String>,
+    /// Keys of the allowed hosts which must authenticate.
+    #[serde(default)]
+    host_auth: Vec<HostDhChap>,
+    /// Addresses to listen on.
+    #[serde(default)]
+    listeners: Vec<NvmfListener>,
+    /// Transports to listen on at the address of the target.
+    #[serde(default)]
+    transports: Vec<NvmfTransport>,
+    /// Whether ANA is reported to the hosts.
+    #[serde(default)]
+    ana: bool,
+    /// Serial number of the subsystem.
+    #[serde(default)]
+    serial: Option<String>,
+    /// Model number of the subsystem.
+    #[serde(default)]
+    model: Option<String>,
+    /// Overrides of the identify controller data.
+    #[serde(default)]
+    identify: Option<IdentifyOverrides>,
+    /// Keep alive timeout in milliseconds.
+    #[serde(default)]
+    kato_ms: Option<u32>,
+    /// Whether the share is used by one host at a time or by several.
+    #[serde(default)]
+    share_mode: NvmfShareMode,
+}
+
+impl From<ShareValidateArgs> for NvmfShareProps {
+    fn from(args: ShareValidateArgs) -> Self {
+        NvmfShareProps::new()
+            .with_nqn(args.nqn)
+            .with_allowed_hosts(args.allowed_hosts)
+            .with_host_auth(args.host_auth)
+            .with_listeners(args.listeners)
+            .with_transports(args.transports)
+            .with_ana(args.ana)
+            .with_serial(args.serial)
+            .with_model(args.model)
+            .with_identify(args.identify)
+            .with_kato(args.kato_ms)
+            .with_share_mode(args.share_mode)
+    }
+}
+
+/// Registers the JSON-RPC methods of the share readiness.
+pub(super) fn register_rpc_methods() {
+    // goes through the steps of a share without changing anything, to
+    // validate a publish before any resource is taken
+    jsonrpc_register::<ShareValidateArgs, _, _, JsonRpcError>(
+        "mayastor_share_validate",
+        |args| {
+            async move {
+                let bdev = args.bdev.clone();
+                Ok(share_readiness(&bdev, Some(args.into())))
+            }
+            .boxed_local()
+        },
+    );
+}
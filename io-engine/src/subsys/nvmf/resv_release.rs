@@ -0,0 +1,395 @@
+//! Release of the reservations of the hosts which timed out on a replica.
+//!
+//! The nexuses register with the replicas they write to, and hold a
+//! reservation on them, so that a replica is only written to by its current
+//! nexus. When the node of a nexus dies, its registration stays behind on
+//! the replicas, which may block the failover to a new nexus. When enabled,
+//! the registrations of a host whose keep alive timed out on a replica are
+//! removed after a grace period, unless the host reconnected in the
+//! meantime; its reservation is released, or passed on to another
+//! registrant for the reservation types of all the registrants.
+//!
+//! The registrations are removed through the reservation commands of SPDK,
+//! so that the persisted reservation state (PTPL) of the replica is updated
+//! and its other registrants are notified: the io-engine connects to the
+//! subsystem as a host of its own, registers, preempts the key of the timed
+//! out host, releasing the reservation it takes over if the host held it
+//! alone, and unregisters. The preemption removes the registrations of all
+//! the hosts registered with that key.
+//!
+//! The registrations of a host fenced from a subsystem are removed the same
+//! way, right after it is disconnected.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use once_cell::sync::Lazy;
+use spdk_rs::{
+    libspdk::{
+        spdk_nvme_cmd,
+        spdk_nvmf_ctrlr,
+        spdk_nvmf_ns_get_id,
+        spdk_nvmf_registrant,
+        spdk_nvmf_subsystem_get_allow_any_host,
+        spdk_nvmf_subsystem_get_first_ns,
+        spdk_nvmf_subsystem_get_next_ns,
+    },
+    nvme_nvm_opcode,
+    nvme_reservation_acquire_action,
+    nvme_reservation_register_action,
+    NvmfController,
+};
+
+use super::{subsystem::nqn_prefix, Error, NvmfSubsystem};
+use crate::{
+    bdev::{
+        device_create,
+        device_destroy,
+        device_open,
+        nexus::NvmeReservation,
+    },
+    core::{BlockDeviceHandle, CoreError, Reactors},
+    ffihelper::AsStr,
+    sleep::mayastor_sleep,
+    subsys::Config,
+};
+
+/// Reservation key the io-engine registers with to preempt the timed out
+/// hosts.
+const PREEMPT_KEY: u64 = 0x6d61_7961_7374_6f72;
+
+/// ID of the host the io-engine preempts with, apart from the ID of the
+/// node which its nexuses register with.
+const PREEMPT_HOSTID: &str = "6d617961-7374-6f72-2d70-7265656d7074";
+
+/// Release action of the Reservation Release command.
+const RELEASE: u8 = 0;
+
+/// Registration of a host with a namespace of a subsystem.
+#[derive(Debug, Clone, Copy)]
+struct Registration {
+    nsid: u32,
+    rkey: u64,
+    /// Whether the host holds the reservation of the namespace.
+    holder: bool,
+    /// Type of the reservation of the namespace, 0 when none is held.
+    rtype: u8,
+}
+
+/// Pending releases, by NQN of the subsystem and NQN of the host, with a
+/// token telling a release from those scheduled before it.
+static PENDING: Lazy<Mutex<HashMap<(String, String), u64>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Token of the next release.
+static NEXT_TOKEN: AtomicU64 = AtomicU64::new(0);
+
+/// Forgets the pending releases of a subsystem which is being destroyed.
+pub(crate) fn forget_resv_release(nqn: &str) {
+    PENDING.lock().unwrap().retain(|(n, _), _| n != nqn);
+}
+
+/// Cancels the pending release of a host which connected to a subsystem.
+pub(crate) fn cancel_resv_release(nqn: &str, host: &str) {
+    let key = (nqn.to_string(), host.to_string());
+    if PENDING.lock().unwrap().remove(&key).is_some() {
+        info!(nqn, host, "Host reconnected, reservations kept");
+    }
+}
+
+/// Returns the ID of the host of a controller.
+fn ctrlr_hostid(ctrlr: *mut spdk_nvmf_ctrlr) -> [u8; 16] {
+    unsafe { (*ctrlr).hostid.u.raw }
+}
+
+impl NvmfSubsystem {
+    /// Schedules the release of the reservations of the host of a
+    /// controller which timed out, once the grace period is over, if
+    /// enabled.
+    pub(crate) fn schedule_resv_release(&self, ctrlr: &NvmfController) {
+        let Some(grace_ms) = Config::get().nexus_opts.nvmf_kato_resv_grace_ms
+        else {
+            return;
+        };
+
+        let nqn = self.get_nqn();
+        let host = ctrlr.hostnqn();
+        let hostid = ctrlr_hostid(ctrlr.0.as_ptr());
+        let token = NEXT_TOKEN.fetch_add(1, Ordering::SeqCst);
+        PENDING
+            .lock()
+            .unwrap()
+            .insert((nqn.clone(), host.clone()), token);
+        info!(
+            nqn,
+            host,
+            grace_ms,
+            "Reservations of the host released unless it reconnects"
+        );
+
+        Reactors::master().send_future(async move {
+            if mayastor_sleep(Duration::from_millis(grace_ms))
+                .await
+                .is_err()
+            {
+                return;
+            }
+
+            // unless cancelled, forgotten or rescheduled since
+            let key = (nqn.clone(), host.clone());
+            {
+                let mut pending = PENDING.lock().unwrap();
+                if pending.get(&key) != Some(&token) {
+                    return;
+                }
+                pending.remove(&key);
+            }
+
+            let Ok(subsystem) = NvmfSubsystem::lookup_by_nqn(&nqn) else {
+                return;
+            };
+            match subsystem.release_host_reservations(hostid).await {
+                Ok(0) => {}
+                Ok(released) => warn!(
+                    nqn,
+                    host,
+                    released,
+                    "Released the reservations of the timed out host"
+                ),
+                Err(error) => error!(
+                    nqn,
+                    host,
+                    %error,
+                    "Failed to release the reservations of the timed out host"
+                ),
+            }
+        });
+    }
+
+    /// Removes the registrations of a host from the namespaces of the
+    /// subsystem, releasing its reservations, unless the host is connected.
+    /// Returns the number of namespaces the host was registered with.
+    pub async fn release_host_reservations(
+        &self,
+        hostid: [u8; 16],
+    ) -> Result<usize, Error> {
+        if self.host_connected(hostid) {
+            return Ok(0);
+        }
+        self.preempt_host_registrations(hostid).await
+    }
+
+    /// Removes the registrations of a host from the namespaces of the
+    /// subsystem, releasing its reservations, whether it is connected or
+    /// not. Returns the number of namespaces the host was registered with.
+    pub(crate) async fn preempt_host_registrations(
+        &self,
+        hostid: [u8; 16],
+    ) -> Result<usize, Error> {
+        let registrations = self.host_registrations(hostid);
+        if registrations.is_empty() {
+            return Ok(0);
+        }
+
+        let nqn = self.get_nqn();
+        let host = format!("{}:resv-release", nqn_prefix());
+        let Some(uri) = self.uri_endpoints().and_then(|u| u.into_iter().next())
+        else {
+            return Err(Error::ResvRelease {
+                nqn,
+                reason: "the subsystem has no listener".to_string(),
+            });
+        };
+        let uri = format!("{uri}?hostnqn={host}&hostid={PREEMPT_HOSTID}");
+
+        // the preempting host is allowed for the time of the release only,
+        // without being recorded with the allowed hosts of the share
+        let allow =
+            !unsafe { spdk_nvmf_subsystem_get_allow_any_host(self.0.as_ptr()) }
+                && !self.allowed_hosts().contains(&host);
+        if allow {
+            self.add_host_with(&nqn, &host, None)?;
+        }
+        let result = preempt_registrations(&uri, &registrations).await;
+        if allow {
+            self.remove_host(&host)?;
+        }
+        result.map_err(|reason| Error::ResvRelease {
+            nqn,
+            reason,
+        })?;
+
+        Ok(registrations.len())
+    }
+
+    /// Returns the registrations of a host with the namespaces of the
+    /// subsystem.
+    fn host_registrations(&self, hostid: [u8; 16]) -> Vec<Registration> {
+        let mut registrations = Vec::new();
+        let mut ns =
+            unsafe { spdk_nvmf_subsystem_get_first_ns(self.0.as_ptr()) };
+        while !ns.is_null() {
+            let mut reg: *mut spdk_nvmf_registrant =
+                unsafe { (*ns).registrants.tqh_first };
+            while !reg.is_null() {
+                if unsafe { (*reg).hostid.u.raw } == hostid {
+                    registrations.push(unsafe {
+                        Registration {
+                            nsid: spdk_nvmf_ns_get_id(ns),
+                            rkey: (*reg).rkey,
+                            holder: (*ns).holder == reg,
+                            rtype: (*ns).rtype as u8,
+                        }
+                    });
+                    break;
+                }
+                reg = unsafe { (*reg).link.tqe_next };
+            }
+            ns =
+                unsafe { spdk_nvmf_subsystem_get_next_ns(self.0.as_ptr(), ns) };
+        }
+        registrations
+    }
+
+    /// Returns the IDs of the connected controllers of a host, by NQN.
+    /// The registrations only carry the ID of their host, so the
+    /// registrations of a host which is not connected cannot be found.
+    pub(crate) fn connected_hostids(&self, host: &str) -> Vec<[u8; 16]> {
+        let mut hostids = Vec::new();
+        let mut ctrlr = unsafe { self.0.as_ref().ctrlrs.tqh_first };
+        while !ctrlr.is_null() {
+            let hostid = ctrlr_hostid(ctrlr);
+            if unsafe { (*ctrlr).hostnqn.as_str() } == host
+                && !hostids.contains(&hostid)
+            {
+                hostids.push(hostid);
+            }
+            ctrlr = unsafe { (*ctrlr).link.tqe_next };
+        }
+        hostids
+    }
+
+    /// Whether a controller of the host is connected to the subsystem.
+    fn host_connected(&self, hostid: [u8; 16]) -> bool {
+        let mut ctrlr = unsafe { self.0.as_ref().ctrlrs.tqh_first };
+        while !ctrlr.is_null() {
+            if ctrlr_hostid(ctrlr) == hostid {
+                return true;
+            }
+            ctrlr = unsafe { (*ctrlr).link.tqe_next };
+        }
+        false
+    }
+}
+
+/// Connects to the subsystem at the given URI and preempts the given
+/// registrations.
+async fn preempt_registrations(
+    uri: &str,
+    registrations: &[Registration],
+) -> Result<(), String> {
+    let name = device_create(uri).await.map_err(|e| e.to_string())?;
+    let result = async {
+        let handle = device_open(&name, true)?.into_handle()?;
+        for registration in registrations {
+            preempt(&*handle, registration).await?;
+        }
+        Ok::<_, CoreError>(())
+    }
+    .await;
+    if let Err(error) = device_destroy(uri).await {
+        warn!(%error, "Failed to disconnect from '{uri}'");
+    }
+    result.map_err(|e| e.to_string())
+}
+
+/// Removes a registration by preempting its key, releasing the reservation
+/// taken over from its host if it held it alone.
+async fn preempt(
+    handle: &dyn BlockDeviceHandle,
+    reg: &Registration,
+) -> Result<(), CoreError> {
+    let all_regs = reg.rtype == NvmeReservation::WriteExclusiveAllRegs as u8
+        || reg.rtype == NvmeReservation::ExclusiveAccessAllRegs as u8;
+    // the type is only used when the reservation is taken over
+    let rtype = if reg.holder {
+        reg.rtype
+    } else {
+        NvmeReservation::WriteExclusive as u8
+    };
+
+    let register = |current_key, new_key, action: u8| {
+        let mut cmd = resv_cmd(nvme_nvm_opcode::RESERVATION_REGISTER, reg.nsid);
+        unsafe {
+            cmd.__bindgen_anon_1
+                .cdw10_bits
+                .resv_register
+                .set_rrega(action.into());
+        }
+        (cmd, current_key, new_key)
+    };
+    let (cmd, ck, nk) = register(
+        0,
+        PREEMPT_KEY,
+        nvme_reservation_register_action::REGISTER_KEY,
+    );
+    resv_passthru(handle, &cmd, ck, nk).await?;
+
+    let mut cmd = resv_cmd(nvme_nvm_opcode::RESERVATION_ACQUIRE, reg.nsid);
+    unsafe {
+        let acquire = &mut cmd.__bindgen_anon_1.cdw10_bits.resv_acquire;
+        acquire.set_racqa(nvme_reservation_acquire_action::PREEMPT.into());
+        acquire.set_rtype(rtype.into());
+    }
+    resv_passthru(handle, &cmd, PREEMPT_KEY, reg.rkey).await?;
+
+    if reg.holder && !all_regs {
+        let mut cmd = resv_cmd(nvme_nvm_opcode::RESERVATION_RELEASE, reg.nsid);
+        unsafe {
+            let release = &mut cmd.__bindgen_anon_1.cdw10_bits.resv_acquire;
+            release.set_racqa(RELEASE.into());
+            release.set_rtype(rtype.into());
+        }
+        resv_passthru(handle, &cmd, PREEMPT_KEY, 0).await?;
+    }
+
+    let (cmd, ck, nk) = register(
+        PREEMPT_KEY,
+        0,
+        nvme_reservation_register_action::UNREGISTER_KEY,
+    );
+    resv_passthru(handle, &cmd, ck, nk).await
+}
+
+/// Returns a reservation command for the given namespace.
+fn resv_cmd(opcode: u8, nsid: u32) -> spdk_nvme_cmd {
+    let mut cmd = spdk_nvme_cmd::default();
+    cmd.set_opc(opcode.into());
+    cmd.nsid = nsid;
+    cmd
+}
+
+/// Sends a reservation command with its two keys as payload.
+async fn resv_passthru(
+    handle: &dyn BlockDeviceHandle,
+    cmd: &spdk_nvme_cmd,
+    key: u64,
+    other_key: u64,
+) -> Result<(), CoreError> {
+    let mut buffer =
+        handle
+            .dma_malloc(16)
+            .map_err(|_| CoreError::DmaAllocationFailed {
+                size: 16,
+            })?;
+    let (k, o) = buffer.as_mut_slice().split_at_mut(8);
+    k.copy_from_slice(&key.to_le_bytes());
+    o.copy_from_slice(&other_key.to_le_bytes());
+    handle.io_passthru(cmd, Some(&mut buffer)).await
+}
@@ -0,0 +1,363 @@
+//! Self-audit of the share state of the replicas and the nexuses.
+//!
+//! Whether a replica is shared and which hosts are allowed to connect to it
+//! is persisted on its lvol, while the effective state lives in the SPDK
+//! NVMe-oF subsystems. The audit compares both, reports any drift as replica
+//! events and can optionally restore the persisted state, so that bugs where
+//! the two diverge do not go unnoticed.
+//! A nexus is compared with the target it was published with, its drift is
+//! reported as nexus events but never corrected, as the share properties of
+//! a nexus are not persisted.
+
+use std::{pin::Pin, time::Duration};
+
+use events_api::event::{EventAction, EventMessage};
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    host_group::expand_hosts,
+    subsystem::{make_nqn, NqnTarget},
+    NvmfSubsystem,
+    SubType,
+};
+use crate::{
+    bdev::{
+        nexus::{nexus_iter, Nexus},
+        PtplFileOps,
+    },
+    core::{LogicalVolume, NvmfShareProps, Reactor, Share},
+    eventing::Event,
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+    lvs::{Lvol, Lvs, LvsLvol, PropName, PropValue},
+};
+
+/// A difference between the persisted and the effective share state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DriftKind {
+    /// The replica is shared on disk but has no subsystem.
+    NotPublished,
+    /// The replica is not shared on disk but has a subsystem.
+    UnexpectedlyPublished,
+    /// The subsystem allows other hosts than the persisted ones.
+    AllowedHosts {
+        expected: Vec<String>,
+        actual: Vec<String>,
+    },
+    /// The subsystem has no listener.
+    NoListener,
+    /// The subsystem has neither a replica nor a nexus behind it.
+    Orphaned,
+}
+
+impl DriftKind {
+    /// Expected and actual states, as reported in the drift events.
+    fn states(&self) -> (String, String) {
+        let (expected, actual) = match self {
+            Self::NotPublished => ("published", "unpublished"),
+            Self::UnexpectedlyPublished => ("unpublished", "published"),
+            Self::AllowedHosts {
+                expected,
+                actual,
+            } => {
+                return (expected.join(","), actual.join(","));
+            }
+            Self::NoListener => ("listening", "not-listening"),
+            Self::Orphaned => ("absent", "published"),
+        };
+        (expected.to_string(), actual.to_string())
+    }
+}
+
+/// A drift found by the audit.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareDrift {
+    /// NQN of the subsystem.
+    pub nqn: String,
+    /// Uuid of the replica, if any.
+    pub replica: Option<String>,
+    /// Uuid of the nexus, if any.
+    pub nexus: Option<String>,
+    #[serde(flatten)]
+    pub kind: DriftKind,
+    /// Whether the persisted state was restored.
+    pub corrected: bool,
+}
+
+/// Arguments of a share audit.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ShareAudit {
+    /// Restore the persisted state where possible.
+    pub fix: bool,
+}
+
+impl ShareAudit {
+    /// Audits the share state of every replica and subsystem.
+    pub async fn run(&self) -> Vec<ShareDrift> {
+        let mut drifts = vec![];
+
+        let lvols = Lvs::iter()
+            .flat_map(|lvs| {
+                lvs.lvols()
+                    .map(|lvols| lvols.collect::<Vec<_>>())
+                    .unwrap_or_default()
+            })
+            .filter(|lvol| !lvol.is_snapshot())
+            .collect::<Vec<_>>();
+        for lvol in lvols {
+            drifts.extend(self.audit_replica(lvol).await);
+        }
+
+        for nexus in nexus_iter() {
+            drifts.extend(Self::audit_nexus(nexus));
+        }
+
+        if let Some(first) = NvmfSubsystem::first() {
+            for subsystem in first.into_iter() {
+                if subsystem.subtype() == SubType::Discovery {
+                    continue;
+                }
+                let nqn = subsystem.get_nqn();
+                if matches!(NqnTarget::lookup(&nqn), NqnTarget::None) {
+                    warn!("Share audit: subsystem {nqn} has no target");
+                    drifts.push(ShareDrift {
+                        nqn,
+                        replica: None,
+                        nexus: None,
+                        kind: DriftKind::Orphaned,
+                        corrected: false,
+                    });
+                }
+            }
+        }
+
+        drifts
+    }
+
+    /// Audits the share state of a replica.
+    async fn audit_replica(&self, mut lvol: Lvol) -> Vec<ShareDrift> {
+        let name = lvol.as_bdev().name().to_string();
+        let subsystem = NvmfSubsystem::nqn_lookup(&name);
+        let nqn = subsystem
+            .as_ref()
+            .map_or_else(|| make_nqn(&name), NvmfSubsystem::get_nqn);
+        let shared = matches!(
+            lvol.get(PropName::Shared).await,
+            Ok(PropValue::Shared(true))
+        );
+        let persisted = match lvol.get(PropName::AllowedHosts).await {
+            Ok(PropValue::AllowedHosts(hosts)) => hosts,
+            _ => vec![],
+        };
+        // the host groups are compared by their members
+        let mut expected =
+            expand_hosts(&persisted).unwrap_or_else(|_| persisted.clone());
+        expected.sort();
+
+        let mut drifts = vec![];
+        match (shared, subsystem) {
+            (false, None) => {}
+            (true, None) => {
+                let corrected = self.fix && {
+                    let props = NvmfShareProps::new()
+                        .with_allowed_hosts(persisted.clone())
+                        .with_ptpl(lvol.ptpl().create().unwrap_or_default());
+                    Pin::new(&mut lvol)
+                        .share_nvmf(Some(props))
+                        .await
+                        .map_err(|error| {
+                            error!(
+                                "Share audit: failed to share {name}: {error}"
+                            )
+                        })
+                        .is_ok()
+                };
+                drifts.push((DriftKind::NotPublished, corrected));
+            }
+            (false, Some(_)) => {
+                let corrected = self.fix && {
+                    // unshare the bdev only, the lvol is already unshared
+                    // on disk
+                    let mut bdev = lvol.as_bdev();
+                    Pin::new(&mut bdev)
+                        .unshare()
+                        .await
+                        .map_err(|error| {
+                            error!(
+                                "Share audit: failed to unshare {name}: {error}"
+                            )
+                        })
+                        .is_ok()
+                };
+                drifts.push((DriftKind::UnexpectedlyPublished, corrected));
+            }
+            (true, Some(subsystem)) => {
+                if subsystem.uri_endpoints().is_none() {
+                    drifts.push((DriftKind::NoListener, false));
+                }
+
+                let mut actual = subsystem.allowed_hosts();
+                actual.sort();
+                if actual != expected {
+                    let corrected = self.fix
+                        && Self::restore_hosts(&subsystem, &persisted, &actual)
+                            .await;
+                    drifts.push((
+                        DriftKind::AllowedHosts {
+                            expected,
+                            actual,
+                        },
+                        corrected,
+                    ));
+                }
+            }
+        }
+
+        Self::report(drifts, &nqn, Target::Replica(lvol.uuid()), || {
+            lvol.event(EventAction::StateChange)
+        })
+    }
+
+    /// Audits the share state of a nexus against the target it was
+    /// published with.
+    fn audit_nexus(nexus: &Nexus) -> Vec<ShareDrift> {
+        let subsystem = NvmfSubsystem::nqn_lookup(nexus.nexus_name());
+        let nqn = subsystem.as_ref().map_or_else(
+            || make_nqn(nexus.nexus_name()),
+            NvmfSubsystem::get_nqn,
+        );
+
+        let drifts = match (nexus.is_nvmf_target(), subsystem) {
+            (false, None) => vec![],
+            (true, None) => vec![(DriftKind::NotPublished, false)],
+            (false, Some(_)) => vec![(DriftKind::UnexpectedlyPublished, false)],
+            (true, Some(subsystem)) if subsystem.uri_endpoints().is_none() => {
+                vec![(DriftKind::NoListener, false)]
+            }
+            (true, Some(_)) => vec![],
+        };
+
+        let uuid = nexus.uuid().to_string();
+        Self::report(drifts, &nqn, Target::Nexus(uuid), || {
+            Event::event(nexus, EventAction::StateChange)
+        })
+    }
+
+    /// Reports the drifts found on a replica or a nexus as state change
+    /// events of that target.
+    fn report(
+        drifts: Vec<(DriftKind, bool)>,
+        nqn: &str,
+        target: Target,
+        event: impl Fn() -> EventMessage,
+    ) -> Vec<ShareDrift> {
+        drifts
+            .into_iter()
+            .map(|(kind, corrected)| {
+                let (expected, actual) = kind.states();
+                warn!(
+                    "Share audit: {target} drifted: {kind:?}, corrected: \
+                    {corrected}"
+                );
+                let mut event = event();
+                if let Some(meta) = event.metadata.as_mut() {
+                    meta.source = meta
+                        .source
+                        .take()
+                        .map(|s| s.with_state_change_data(expected, actual));
+                }
+                event.generate();
+
+                let (replica, nexus) = match &target {
+                    Target::Replica(uuid) => (Some(uuid.clone()), None),
+                    Target::Nexus(uuid) => (None, Some(uuid.clone())),
+                };
+                ShareDrift {
+                    nqn: nqn.to_string(),
+                    replica,
+                    nexus,
+                    kind,
+                    corrected,
+                }
+            })
+            .collect()
+    }
+
+    /// Restores the persisted allowed hosts of a subsystem.
+    async fn restore_hosts(
+        subsystem: &NvmfSubsystem,
+        expected: &[String],
+        actual: &[String],
+    ) -> bool {
+        let result = if expected.is_empty() {
+            subsystem.allow_any(true);
+            subsystem.disallow_hosts(actual)
+        } else {
+            subsystem.apply_allowed_hosts(expected).await
+        };
+        result
+            .map_err(|error| {
+                error!(
+                    "Share audit: failed to restore the allowed hosts of {}: \
+                    {error}",
+                    subsystem.get_nqn()
+                )
+            })
+            .is_ok()
+    }
+}
+
+/// Uuid of the replica or nexus a drift is found on.
+enum Target {
+    Replica(String),
+    Nexus(String),
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Replica(uuid) => write!(f, "replica {uuid}"),
+            Self::Nexus(uuid) => write!(f, "nexus {uuid}"),
+        }
+    }
+}
+
+/// Periodically audits the share state, correcting the drift if requested.
+/// The audit is disabled by a zero interval.
+pub async fn share_audit_loop(interval: Duration, fix: bool) {
+    if interval.is_zero() {
+        warn!("Share audit interval is zero, the audit is disabled");
+        return;
+    }
+    let mut interval = tokio::time::interval(interval);
+    // the first tick completes immediately, let the pools be imported first
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let audit = ShareAudit {
+            fix,
+        };
+        match Reactor::spawn_at_primary(async move { audit.run().await }) {
+            Ok(rx) => match rx.await {
+                Ok(drifts) if !drifts.is_empty() => {
+                    warn!("Share audit found {} drift(s)", drifts.len());
+                }
+                Ok(_) => debug!("Share audit found no drift"),
+                Err(_) => error!("Share audit was cancelled"),
+            },
+            Err(error) => error!("Failed to start the share audit: {error}"),
+        }
+    }
+}
+
+/// Registers the JSON-RPC methods of the share audit.
+pub(super) fn register_rpc_methods() {
+    // compare the share state of the replicas and the nexuses with the
+    // NVMe-oF subsystems, optionally restoring that of the replicas
+    jsonrpc_register::<ShareAudit, _, _, JsonRpcError>(
+        "mayastor_share_audit",
+        |audit| async move { Ok(audit.run().await) }.boxed_local(),
+    );
+}
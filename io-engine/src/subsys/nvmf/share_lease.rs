@@ -0,0 +1,276 @@
+//! Share leases.
+//!
+//! A share may be created with a lease, which the control plane must renew
+//! before it expires. Once its lease has expired, the share is removed and an
+//! event emitted, so that temporary shares, such as those of rebuild sources,
+//! do not keep exposing data when their owner is gone.
+//!
+//! The ttl of a lease is persisted with the share state, so that a share
+//! created again after a restart gets its lease back, starting over.
+
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use events_api::event::EventAction;
+use futures::FutureExt;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use super::{share_state::record_lease, Error, NvmfSubsystem, SubsystemArgs};
+use crate::{
+    core::{MayastorEnvironment, Reactor, Share},
+    eventing::{Event, EventMetaGen, EventWithMeta},
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+    lvs::Lvol,
+};
+
+/// The lease of a share.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShareLease {
+    pub nqn: String,
+    /// Name of the shared bdev.
+    pub bdev: String,
+    /// Duration the lease is extended by on each renewal.
+    pub ttl_ms: u64,
+    /// Time left before the lease expires.
+    pub remaining_ms: u64,
+}
+
+/// A share removed because its lease expired.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpiredShare {
+    pub nqn: String,
+    /// Name of the shared bdev.
+    pub bdev: String,
+    /// Why the share could not be removed.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Lease {
+    bdev: String,
+    ttl: Duration,
+    expiry: Instant,
+}
+
+impl Lease {
+    fn to_share_lease(&self, nqn: &str, now: Instant) -> ShareLease {
+        ShareLease {
+            nqn: nqn.to_string(),
+            bdev: self.bdev.clone(),
+            ttl_ms: self.ttl.as_millis() as u64,
+            remaining_ms: self.expiry.saturating_duration_since(now).as_millis()
+                as u64,
+        }
+    }
+}
+
+/// Leases of the shares, by subsystem NQN.
+static LEASES: Lazy<Mutex<HashMap<String, Lease>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Forgets the lease of a subsystem which is being destroyed.
+pub(crate) fn forget_lease(nqn: &str) {
+    LEASES.lock().unwrap().remove(nqn);
+}
+
+impl ShareLease {
+    /// Extends the lease of a share by its ttl.
+    pub fn renew(nqn: &str) -> Result<Self, Error> {
+        let now = Instant::now();
+        let mut leases = LEASES.lock().unwrap();
+        let lease =
+            leases.get_mut(nqn).ok_or_else(|| Error::LeaseNotFound {
+                nqn: nqn.to_string(),
+            })?;
+        lease.expiry = now + lease.ttl;
+        Ok(lease.to_share_lease(nqn, now))
+    }
+
+    /// Looks up the lease of a share by its NQN.
+    pub fn lookup(nqn: &str) -> Option<Self> {
+        let now = Instant::now();
+        LEASES
+            .lock()
+            .unwrap()
+            .get(nqn)
+            .map(|lease| lease.to_share_lease(nqn, now))
+    }
+
+    /// Returns the leases of all the shares.
+    pub fn list() -> Vec<Self> {
+        let now = Instant::now();
+        LEASES
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(nqn, lease)| lease.to_share_lease(nqn, now))
+            .collect()
+    }
+}
+
+impl NvmfSubsystem {
+    /// Sets the lease of the share, expiring after the given ttl unless
+    /// renewed, or removes it when no ttl is given.
+    pub fn set_lease(&self, ttl: Option<Duration>) -> Option<ShareLease> {
+        let nqn = self.get_nqn();
+        record_lease(&nqn, ttl);
+        let Some(ttl) = ttl else {
+            forget_lease(&nqn);
+            return None;
+        };
+
+        let now = Instant::now();
+        let lease = Lease {
+            bdev: self
+                .bdev()
+                .map(|b| b.name().to_string())
+                .unwrap_or_default(),
+            ttl,
+            expiry: now + ttl,
+        };
+        let share_lease = lease.to_share_lease(&nqn, now);
+        LEASES.lock().unwrap().insert(nqn, lease);
+        Some(share_lease)
+    }
+}
+
+/// Removes the shares whose lease has expired, returning them. A share which
+/// could not be removed keeps its lease, so the removal is retried.
+pub async fn expire_share_leases() -> Vec<ExpiredShare> {
+    let now = Instant::now();
+    let expired = {
+        let mut leases = LEASES.lock().unwrap();
+        let nqns = leases
+            .iter()
+            .filter(|(_, lease)| lease.expiry <= now)
+            .map(|(nqn, _)| nqn.clone())
+            .collect::<Vec<_>>();
+        nqns.into_iter()
+            .filter_map(|nqn| leases.remove(&nqn).map(|lease| (nqn, lease)))
+            .collect::<Vec<_>>()
+    };
+
+    let mut unshared = vec![];
+    for (nqn, lease) in expired {
+        let Ok(subsystem) = NvmfSubsystem::lookup_by_nqn(&nqn) else {
+            continue;
+        };
+        warn!("Lease of share {nqn} expired, unsharing {}", lease.bdev);
+
+        let error = unshare_expired(&subsystem).await.err();
+        if let Some(error) = &error {
+            error!("Failed to unshare {nqn} after its lease expired: {error}");
+            LEASES.lock().unwrap().insert(nqn.clone(), lease.clone());
+        }
+        unshared.push(ExpiredShare {
+            nqn,
+            bdev: lease.bdev,
+            error,
+        });
+    }
+    unshared
+}
+
+/// Unshares the bdev of a subsystem whose lease expired, emitting an event.
+async fn unshare_expired(subsystem: &NvmfSubsystem) -> Result<(), String> {
+    let meta = subsystem.meta();
+    let Some(mut bdev) = subsystem.bdev() else {
+        subsystem.shutdown().await.map_err(|e| e.to_string())?;
+        MayastorEnvironment::global_or_default()
+            .event(EventAction::Delete, meta)
+            .generate();
+        return Ok(());
+    };
+
+    match Lvol::ok_from(bdev) {
+        Some(mut lvol) => {
+            Pin::new(&mut lvol)
+                .unshare()
+                .await
+                .map_err(|e| e.to_string())?;
+            let mut event = lvol.event(EventAction::StateChange);
+            if let Some(meta) = event.metadata.as_mut() {
+                meta.source = meta.source.take().map(|s| {
+                    s.with_state_change_data(
+                        "leased".to_string(),
+                        "expired".to_string(),
+                    )
+                });
+            }
+            event.generate();
+        }
+        None => {
+            Pin::new(&mut bdev)
+                .unshare()
+                .await
+                .map_err(|e| e.to_string())?;
+            MayastorEnvironment::global_or_default()
+                .event(EventAction::Delete, meta)
+                .generate();
+        }
+    }
+    Ok(())
+}
+
+/// Periodically removes the shares whose lease has expired.
+pub async fn share_lease_loop(interval: Duration) {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        match Reactor::spawn_at_primary(expire_share_leases()) {
+            Ok(rx) => match rx.await {
+                Ok(expired) if !expired.is_empty() => {
+                    warn!(
+                        "Unshared {} share(s) with expired lease",
+                        expired.len()
+                    );
+                }
+                Ok(_) => {}
+                Err(_) => error!("Share lease expiry was cancelled"),
+            },
+            Err(error) => {
+                error!("Failed to start the share lease expiry: {error}")
+            }
+        }
+    }
+}
+
+/// Arguments of the `mayastor_share_lease_set` method.
+#[derive(Debug, Deserialize)]
+struct ShareLeaseArgs {
+    /// NQN of the subsystem.
+    nqn: String,
+    /// Lease ttl, the lease is removed when not given.
+    #[serde(default)]
+    ttl_ms: Option<u64>,
+}
+
+/// Registers the JSON-RPC methods of the share leases.
+pub(super) fn register_rpc_methods() {
+    // leases of the shares which must be renewed by the control plane
+    jsonrpc_register::<ShareLeaseArgs, _, _, Error>(
+        "mayastor_share_lease_set",
+        |args| {
+            async move {
+                let ttl = args.ttl_ms.map(Duration::from_millis);
+                Ok(NvmfSubsystem::lookup_by_nqn(&args.nqn)?.set_lease(ttl))
+            }
+            .boxed_local()
+        },
+    );
+
+    jsonrpc_register::<SubsystemArgs, _, _, Error>(
+        "mayastor_share_lease_renew",
+        |args| async move { ShareLease::renew(&args.nqn) }.boxed_local(),
+    );
+
+    jsonrpc_register::<(), _, _, JsonRpcError>(
+        "mayastor_share_lease_list",
+        |_| async move { Ok(ShareLease::list()) }.boxed_local(),
+    );
+}
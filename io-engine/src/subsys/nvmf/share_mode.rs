@@ -0,0 +1,98 @@
+//! Share mode of the subsystems.
+//!
+//! A share is exclusive by default: it is used by one host at a time, its
+//! other allowed hosts being those it may fail over to. A shared share is
+//! used by several hosts concurrently, for clustered applications which
+//! manage the coherence of their data themselves, e.g. clustered file
+//! systems. The io-engine gives them the following contract:
+//!
+//! - only the hosts explicitly allowed connect, never any host, as the allowed
+//!   hosts are the members of the cluster;
+//! - the shared bdev has no volatile write cache, so that a write completed to
+//!   a host is seen by the others without any flush; there is no write-back
+//!   caching anywhere in between;
+//! - the hosts arbitrate their access with NVMe reservations, which are kept
+//!   across restarts when the share persists them, and the reservations of a
+//!   host which timed out are released after the configured grace period.
+//!
+//! The io-engine does not otherwise order the I/O of the hosts.
+
+use std::{collections::HashMap, sync::Mutex};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use spdk_rs::libspdk::spdk_bdev_has_write_cache;
+
+use super::{Error, NvmfSubsystem};
+use crate::core::Bdev;
+
+/// Mode of a share.
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum NvmfShareMode {
+    /// Used by one host at a time.
+    #[default]
+    Exclusive,
+    /// Used by several hosts concurrently, arbitrating with reservations.
+    Shared,
+}
+
+impl NvmfShareMode {
+    /// Checks that a bdev may be shared in this mode with the given hosts,
+    /// host groups included.
+    pub fn validate<T>(
+        self,
+        bdev: &Bdev<T>,
+        hosts: &[String],
+    ) -> Result<(), Error>
+    where
+        T: spdk_rs::BdevOps,
+    {
+        if self == Self::Exclusive {
+            return Ok(());
+        }
+        let invalid = |reason: &str| Error::InvalidShareMode {
+            nqn: bdev.name().to_string(),
+            reason: reason.to_string(),
+        };
+        if hosts.is_empty() {
+            return Err(invalid("the hosts sharing it must be allowed"));
+        }
+        if unsafe { spdk_bdev_has_write_cache(bdev.unsafe_inner_ptr()) } {
+            return Err(invalid("the bdev has a volatile write cache"));
+        }
+        Ok(())
+    }
+}
+
+/// Subsystems shared by several hosts, by subsystem NQN.
+static SHARED_SUBSYSTEMS: Lazy<Mutex<HashMap<String, NvmfShareMode>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Forgets the share mode of a subsystem which is being destroyed.
+pub(crate) fn forget_share_mode(nqn: &str) {
+    SHARED_SUBSYSTEMS.lock().unwrap().remove(nqn);
+}
+
+impl NvmfSubsystem {
+    /// Sets the share mode of the subsystem.
+    pub fn set_share_mode(&self, mode: NvmfShareMode) {
+        let mut shared = SHARED_SUBSYSTEMS.lock().unwrap();
+        match mode {
+            NvmfShareMode::Exclusive => shared.remove(&self.get_nqn()),
+            mode => shared.insert(self.get_nqn(), mode),
+        };
+    }
+
+    /// Returns the share mode of the subsystem.
+    pub fn share_mode(&self) -> NvmfShareMode {
+        SHARED_SUBSYSTEMS
+            .lock()
+            .unwrap()
+            .get(&self.get_nqn())
+            .copied()
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,304 @@
+//! Persistence of the allowed hosts, host keys, listeners, leases and
+//! identities of the subsystems.
+//!
+//! The allowed hosts of a share may change after it is created, e.g. when a
+//! volume is republished to another host, and are otherwise lost when the
+//! io-engine restarts. When a ptpl directory is configured, the allowed
+//! hosts, the names of the keys they authenticate with, the listeners, the
+//! lease and the serial and model numbers of every subsystem are kept in a
+//! file of their own under it, written atomically, and replayed when the
+//! subsystem is created again after a restart, for what the share does not
+//! set itself. The file is removed when the subsystem is destroyed.
+//!
+//! The states are kept in memory as well, and written by a blocking thread
+//! of the runtime so that syncing them to disk does not stall the reactor.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+use once_cell::sync::Lazy;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{subsystem::make_nqn, HostDhChap, NvmfListener};
+use crate::core::{runtime, MayastorEnvironment, NvmfShareProps};
+
+/// Directory of the share states, within the ptpl directory.
+const SHARE_STATE_DIR: &str = "nvmf-share";
+
+/// Share states loaded or updated since the start, by subsystem NQN.
+static STATES: Lazy<Mutex<HashMap<String, PersistedShare>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Serialises the writes of the share states.
+static WRITE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Allowed hosts and listeners of a subsystem, as last set.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PersistedShare {
+    /// NQN of the subsystem.
+    pub nqn: String,
+    /// Hosts allowed to connect, host groups included, any host when empty.
+    pub allowed_hosts: Vec<String>,
+    /// Hosts disallowed one by one since the allowed hosts were set.
+    pub disallowed_hosts: Vec<String>,
+    /// Names of the keys of the allowed hosts which must authenticate.
+    pub host_auth: Vec<HostDhChap>,
+    /// Addresses the subsystem listens on.
+    pub listeners: Vec<NvmfListener>,
+    /// Ttl of the lease of the share in milliseconds, if any.
+    pub lease_ms: Option<u64>,
+    /// Serial number overriding the one derived from the UUID, if any.
+    pub serial: Option<String>,
+    /// Model number overriding the Mayastor one, if any.
+    pub model: Option<String>,
+}
+
+/// Path of the share state of a subsystem, if a ptpl directory is
+/// configured.
+fn state_path(nqn: &str) -> Option<PathBuf> {
+    MayastorEnvironment::global_or_default()
+        .ptpl_dir()
+        .map(|dir| {
+            Path::new(&dir)
+                .join(SHARE_STATE_DIR)
+                .join(format!("{nqn}.json"))
+        })
+}
+
+/// Loads a persisted state, if any.
+pub(crate) fn load_state<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            return None
+        }
+        Err(error) => {
+            warn!(%error, "Failed to read state '{}'", path.display());
+            return None;
+        }
+    };
+    serde_json::from_slice(&bytes)
+        .map_err(|error| {
+            warn!(%error, "Ignoring invalid state '{}'", path.display());
+        })
+        .ok()
+}
+
+/// Writes a persisted state to a temporary file renamed over the previous
+/// one, so that a crash leaves either of them whole.
+pub(crate) fn save_state<T: Serialize>(
+    path: &Path,
+    state: &T,
+) -> std::io::Result<()> {
+    let tmp = write_tmp_state(path, state)?;
+    fs::rename(&tmp, path)?;
+    sync_parent(path)
+}
+
+/// Writes a persisted state to the temporary file it is renamed from, and
+/// returns its path.
+fn write_tmp_state<T: Serialize>(
+    path: &Path,
+    state: &T,
+) -> std::io::Result<PathBuf> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let bytes = serde_json::to_vec_pretty(state)?;
+    let tmp = path.with_extension("tmp");
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(&bytes)?;
+    file.sync_all()?;
+    Ok(tmp)
+}
+
+/// Syncs the directory of a renamed file, for the rename to survive a
+/// crash.
+fn sync_parent(path: &Path) -> std::io::Result<()> {
+    match path.parent() {
+        Some(dir) => fs::File::open(dir)?.sync_all(),
+        None => Ok(()),
+    }
+}
+
+/// Writes the current share state of a subsystem, unless it was forgotten
+/// in the meantime. The state is only renamed in place under the lock of
+/// the states, so that it cannot outlive its removal.
+fn write_share_state(nqn: &str, path: &Path) -> std::io::Result<()> {
+    let _guard = WRITE_LOCK.lock().unwrap();
+    let Some(share) = STATES.lock().unwrap().get(nqn).cloned() else {
+        return Ok(());
+    };
+    let tmp = write_tmp_state(path, &share)?;
+    {
+        let states = STATES.lock().unwrap();
+        if !states.contains_key(nqn) {
+            return fs::remove_file(&tmp);
+        }
+        fs::rename(&tmp, path)?;
+    }
+    sync_parent(path)
+}
+
+/// Updates the share state of a subsystem, which is written in the
+/// background. A failure is only logged as the subsystem itself is already
+/// updated.
+fn update(nqn: &str, f: impl FnOnce(&mut PersistedShare)) {
+    let Some(path) = state_path(nqn) else {
+        return;
+    };
+    {
+        let mut states = STATES.lock().unwrap();
+        let share = states.entry(nqn.to_string()).or_insert_with(|| {
+            load_state(&path).unwrap_or_else(|| PersistedShare {
+                nqn: nqn.to_string(),
+                ..Default::default()
+            })
+        });
+        f(share);
+    }
+    let nqn = nqn.to_string();
+    runtime::spawn_blocking(move || {
+        if let Err(error) = write_share_state(&nqn, &path) {
+            error!(%error, "Failed to save share state '{}'", path.display());
+        }
+    });
+}
+
+/// Records the allowed hosts of a subsystem.
+pub(crate) fn record_allowed_hosts(nqn: &str, hosts: &[String]) {
+    update(nqn, |share| {
+        share.allowed_hosts = hosts.to_vec();
+        share.disallowed_hosts.clear();
+    });
+}
+
+/// Records a host allowed on a subsystem which does not allow any host.
+pub(crate) fn record_allowed_host(nqn: &str, host: &str) {
+    update(nqn, |share| {
+        if share.disallowed_hosts.iter().any(|h| h == host) {
+            share.disallowed_hosts.retain(|h| h != host);
+        } else if !share.allowed_hosts.iter().any(|h| h == host) {
+            share.allowed_hosts.push(host.to_string());
+        }
+    });
+}
+
+/// Records a host disallowed from a subsystem. The allowed hosts are kept
+/// as set, an empty list meaning any host.
+pub(crate) fn record_disallowed_host(nqn: &str, host: &str) {
+    update(nqn, |share| {
+        if !share.disallowed_hosts.iter().any(|h| h == host) {
+            share.disallowed_hosts.push(host.to_string());
+        }
+    });
+}
+
+/// Records the names of the keys of the hosts of a subsystem.
+pub(crate) fn record_host_auth(nqn: &str, auth: &[HostDhChap]) {
+    update(nqn, |share| share.host_auth = auth.to_vec());
+}
+
+/// Records the listeners of a subsystem.
+pub(crate) fn record_listeners(nqn: &str, listeners: &[NvmfListener]) {
+    update(nqn, |share| share.listeners = listeners.to_vec());
+}
+
+/// Records the lease ttl of a subsystem.
+pub(crate) fn record_lease(nqn: &str, ttl: Option<Duration>) {
+    update(nqn, |share| {
+        share.lease_ms = ttl.map(|t| t.as_millis() as u64)
+    });
+}
+
+/// Records the serial and model numbers of a subsystem.
+pub(crate) fn record_identity(
+    nqn: &str,
+    serial: Option<&str>,
+    model: Option<&str>,
+) {
+    update(nqn, |share| {
+        share.serial = serial.map(str::to_string);
+        share.model = model.map(str::to_string);
+    });
+}
+
+/// Returns the share state of a subsystem, if any.
+pub fn persisted_share(nqn: &str) -> Option<PersistedShare> {
+    let path = state_path(nqn)?;
+    let states = STATES.lock().unwrap();
+    match states.get(nqn) {
+        Some(share) => Some(share.clone()),
+        None => load_state(&path),
+    }
+}
+
+/// Share properties with the share state recorded for its subsystem
+/// replayed over them, and the hosts to disallow once they are applied.
+pub(crate) struct ReplayedShare {
+    /// Properties of the share.
+    pub(crate) props: NvmfShareProps,
+    /// Hosts disallowed one by one after the allowed hosts were recorded.
+    pub(crate) disallowed_hosts: Vec<String>,
+}
+
+/// Replays the share state recorded for the subsystem of a share when it is
+/// created again after a restart, for what the share does not set itself:
+/// the allowed hosts, with their keys, when it gives none, and the listeners
+/// when it gives neither listeners nor transports. A recorded lease starts
+/// over with its whole ttl, and the recorded serial and model numbers apply
+/// unless new ones are given.
+pub(crate) fn replay_share(name: &str, props: NvmfShareProps) -> ReplayedShare {
+    let nqn = props.nqn().map_or_else(|| make_nqn(name), str::to_string);
+    let Some(share) = persisted_share(&nqn) else {
+        return ReplayedShare {
+            props,
+            disallowed_hosts: Vec::new(),
+        };
+    };
+    info!(?share, "Replaying the persisted share state of '{name}'");
+    let lease = props.lease().or(share.lease_ms.map(Duration::from_millis));
+    let serial = props.serial().map(str::to_string).or(share.serial);
+    let model = props.model().map(str::to_string).or(share.model);
+    let mut props = props
+        .with_serial(serial)
+        .with_model(model)
+        .with_lease(lease);
+    let mut disallowed_hosts = Vec::new();
+    if props.host_any() {
+        props = props
+            .with_allowed_hosts(share.allowed_hosts)
+            .with_host_auth(share.host_auth);
+        disallowed_hosts = share.disallowed_hosts;
+    }
+    if props.listeners().is_empty() {
+        props = props.with_listeners(share.listeners);
+    }
+    ReplayedShare {
+        props,
+        disallowed_hosts,
+    }
+}
+
+/// Removes the share state of a subsystem which is being destroyed.
+pub(crate) fn forget_share_state(nqn: &str) {
+    let Some(path) = state_path(nqn) else {
+        return;
+    };
+    let mut states = STATES.lock().unwrap();
+    states.remove(nqn);
+    match fs::remove_file(&path) {
+        Ok(()) => {}
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+        Err(error) => {
+            error!(%error, "Failed to remove share state '{}'", path.display());
+        }
+    }
+}
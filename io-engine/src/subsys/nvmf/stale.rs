@@ -0,0 +1,115 @@
+//! Cleanup of the stale subsystems.
+//!
+//! When the bdev of a subsystem is removed, SPDK removes its namespace but
+//! leaves the subsystem running, so a failed destroy path can leave behind a
+//! subsystem without namespace whose NQN is still visible through discovery.
+//! The reconciler stops and destroys such subsystems.
+
+use std::time::Duration;
+
+use events_api::event::EventAction;
+use futures::FutureExt;
+use serde::Serialize;
+use spdk_rs::libspdk::SPDK_NVMF_SUBSYSTEM_ACTIVE;
+
+use super::{NvmfSubsystem, SubType};
+use crate::{
+    core::{MayastorEnvironment, Reactor},
+    eventing::{EventMetaGen, EventWithMeta},
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+};
+
+/// A stale subsystem found by the reconciler.
+#[derive(Debug, Clone, Serialize)]
+pub struct StaleSubsystem {
+    pub nqn: String,
+    /// Whether the subsystem was destroyed.
+    pub destroyed: bool,
+    /// Why the subsystem could not be destroyed.
+    pub error: Option<String>,
+}
+
+impl NvmfSubsystem {
+    /// Whether the subsystem is running without namespace, its bdev having
+    /// been removed. Subsystems which are being set up are not started yet.
+    pub fn is_stale(&self) -> bool {
+        self.subtype() == SubType::Nvme
+            && self.bdev().is_none()
+            && unsafe { self.0.as_ref().state } == SPDK_NVMF_SUBSYSTEM_ACTIVE
+    }
+
+    /// Stops and destroys a stale subsystem, emitting a delete event.
+    async fn destroy_stale(&self) -> StaleSubsystem {
+        let nqn = self.get_nqn();
+        warn!("Destroying stale subsystem {nqn}: its bdev no longer exists");
+
+        let mut stale = StaleSubsystem {
+            nqn,
+            destroyed: false,
+            error: None,
+        };
+        let meta = self.meta();
+        match self.shutdown().await {
+            Ok(()) => {
+                stale.destroyed = true;
+                MayastorEnvironment::global_or_default()
+                    .event(EventAction::Delete, meta)
+                    .generate();
+            }
+            Err(error) => {
+                error!(
+                    "Failed to destroy stale subsystem {}: {error}",
+                    stale.nqn
+                );
+                stale.error = Some(error.to_string());
+            }
+        }
+        stale
+    }
+}
+
+/// Destroys the stale subsystems, returning them.
+pub async fn reconcile_subsystems() -> Vec<StaleSubsystem> {
+    let stale = NvmfSubsystem::first()
+        .map(|first| first.into_iter().filter(|s| s.is_stale()).collect())
+        .unwrap_or_else(Vec::new);
+
+    let mut destroyed = vec![];
+    for subsystem in stale {
+        destroyed.push(subsystem.destroy_stale().await);
+    }
+    destroyed
+}
+
+/// Periodically destroys the stale subsystems.
+pub async fn stale_subsystem_loop(interval: Duration) {
+    if interval.is_zero() {
+        warn!("Stale subsystem interval is zero, the cleanup is disabled");
+        return;
+    }
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        match Reactor::spawn_at_primary(reconcile_subsystems()) {
+            Ok(rx) => match rx.await {
+                Ok(stale) if !stale.is_empty() => {
+                    warn!("Found {} stale subsystem(s)", stale.len());
+                }
+                Ok(_) => {}
+                Err(_) => error!("Stale subsystem cleanup was cancelled"),
+            },
+            Err(error) => {
+                error!("Failed to start the stale subsystem cleanup: {error}")
+            }
+        }
+    }
+}
+
+/// Registers the JSON-RPC methods of the stale subsystems.
+pub(super) fn register_rpc_methods() {
+    // stop and destroy the subsystems whose bdev no longer exists
+    jsonrpc_register::<(), _, _, JsonRpcError>(
+        "mayastor_subsystem_reconcile",
+        |_| async move { Ok(reconcile_subsystems().await) }.boxed_local(),
+    );
+}
@@ -1,18 +1,23 @@
 use std::{
+    collections::{HashMap, HashSet},
     convert::TryFrom,
     ffi::{c_void, CString},
     fmt::{self, Debug, Display, Formatter},
     mem::zeroed,
     ptr::{self, NonNull},
+    sync::Mutex,
+    time::Instant,
 };
 
 use futures::channel::oneshot;
 use nix::errno::Errno;
+use once_cell::sync::Lazy;
 
 use spdk_rs::{
     libspdk::{
         nvmf_subsystem_find_listener,
         nvmf_subsystem_set_cntlid_range,
+        spdk_nvmf_ctrlr,
         spdk_nvmf_ctrlr_set_cpl_error_cb,
         spdk_nvmf_ns_get_bdev,
         spdk_nvmf_ns_opts,
@@ -23,6 +28,7 @@ use spdk_rs::{
         spdk_nvmf_subsystem_add_ns_ext,
         spdk_nvmf_subsystem_create,
         spdk_nvmf_subsystem_destroy,
+        spdk_nvmf_subsystem_destroy_cb,
         spdk_nvmf_subsystem_disconnect_host,
         spdk_nvmf_subsystem_event,
         spdk_nvmf_subsystem_get_first,
@@ -48,9 +54,11 @@ use spdk_rs::{
         spdk_nvmf_subsystem_state_change_done,
         spdk_nvmf_subsystem_stop,
         spdk_nvmf_tgt,
+        spdk_nvmf_tgt_find_subsystem,
         SPDK_NVME_SCT_GENERIC,
         SPDK_NVME_SC_CAPACITY_EXCEEDED,
         SPDK_NVME_SC_RESERVATION_CONFLICT,
+        SPDK_NVMF_SUBSYSTEM_INACTIVE,
         SPDK_NVMF_SUBTYPE_DISCOVERY,
         SPDK_NVMF_SUBTYPE_NVME,
     },
@@ -63,18 +71,81 @@ use spdk_rs::{
 use crate::{
     bdev::{nexus::NEXUS_MODULE_NAME, nvmx::NVME_CONTROLLERS, Nexus},
     constants::{NVME_CONTROLLER_MODEL_ID, NVME_NQN_PREFIX},
-    core::{Bdev, Reactors, UntypedBdev},
+    core::{
+        op_stats::{measure, Operation},
+        Bdev,
+        LogicalVolume,
+        Reactors,
+        UntypedBdev,
+    },
     eventing::{host_events::HostTargetMeta, EventMetaGen, EventWithMeta},
     ffihelper::{cb_arg, done_cb, AsStr, FfiResult, IntoCString},
     lvs::Lvol,
     subsys::{
         make_subsystem_serial,
-        nvmf::{transport::TransportId, Error, NVMF_TGT},
+        nvmf::{
+            batch::SubsystemStateChange,
+            busy_retry::{
+                forget_busy_waiters,
+                record_busy,
+                record_exhausted,
+                state_changed,
+                wait_busy,
+            },
+            crd::crd_policies,
+            discovery::sync_discovery_hosts,
+            duplicate_host::{
+                forget_duplicate_hosts,
+                host_connected,
+                host_disconnected,
+            },
+            fence::{forget_fence, is_fenced},
+            host_auth::{forget_host_auth, host_keys, HostDhChap},
+            host_group::forget_subsystem,
+            host_hooks::{run_host_event_hooks, HostEvent, HostEventKind},
+            identify::forget_identify,
+            inspect::forget_controllers,
+            kato::{ctrlr_kato, forget_kato},
+            nqn_index::{forget_nqn, index_nqn, indexed_bdev},
+            ns_visibility::forget_ns_visibility,
+            port_pool::{allocate_port, release_port},
+            resv_release::{cancel_resv_release, forget_resv_release},
+            share_lease::forget_lease,
+            share_mode::forget_share_mode,
+            share_state::{
+                forget_share_state,
+                record_allowed_host,
+                record_disallowed_host,
+                record_listeners,
+            },
+            target::TargetKind,
+            transport::{NvmfListener, TransportId},
+            Error,
+            NVMF_TGT,
+        },
         Config,
     },
 };
 use events_api::event::EventAction;
 
+/// NQNs of the subsystems created with an explicit NQN, by bdev name, so
+/// that they are found without walking all the subsystems.
+static EXPLICIT_NQNS: Lazy<Mutex<HashMap<String, String>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Forgets the explicit NQN of a subsystem which is being destroyed, by the
+/// name of its bdev, or by walking them all when its bdev is already gone.
+fn forget_explicit_nqn(bdev: Option<&str>, nqn: &str) {
+    let mut nqns = EXPLICIT_NQNS.lock().unwrap();
+    match bdev {
+        Some(bdev) if nqns.get(bdev).is_some_and(|n| n == nqn) => {
+            nqns.remove(bdev);
+        }
+        Some(_) => {}
+        None => nqns.retain(|_, n| n != nqn),
+    }
+}
+
 /// TODO
 #[derive(Debug, PartialOrd, PartialEq)]
 pub enum SubType {
@@ -92,18 +163,32 @@ impl Display for SubType {
 }
 
 pub struct NvmfSubsystem(pub(crate) NonNull<spdk_nvmf_subsystem>);
-pub struct NvmfSubsystemIterator(*mut spdk_nvmf_subsystem);
+
+/// Iterates over the subsystems of the given targets, one after the other.
+pub struct NvmfSubsystemIterator {
+    current: *mut spdk_nvmf_subsystem,
+    targets: std::vec::IntoIter<*mut spdk_nvmf_tgt>,
+}
+
+impl NvmfSubsystemIterator {
+    fn new(targets: Vec<*mut spdk_nvmf_tgt>) -> Self {
+        Self {
+            current: ptr::null_mut(),
+            targets: targets.into_iter(),
+        }
+    }
+}
 
 impl Iterator for NvmfSubsystemIterator {
     type Item = NvmfSubsystem;
     fn next(&mut self) -> Option<Self::Item> {
-        if self.0.is_null() {
-            None
-        } else {
-            let current = self.0;
-            self.0 = unsafe { spdk_nvmf_subsystem_get_next(current) };
-            NonNull::new(current).map(NvmfSubsystem)
+        while self.current.is_null() {
+            let tgt = self.targets.next()?;
+            self.current = unsafe { spdk_nvmf_subsystem_get_first(tgt) };
         }
+        let current = self.current;
+        self.current = unsafe { spdk_nvmf_subsystem_get_next(current) };
+        NonNull::new(current).map(NvmfSubsystem)
     }
 }
 
@@ -112,11 +197,7 @@ impl IntoIterator for NvmfSubsystem {
     type IntoIter = NvmfSubsystemIterator;
 
     fn into_iter(self) -> Self::IntoIter {
-        NVMF_TGT.with(|t| {
-            NvmfSubsystemIterator(unsafe {
-                spdk_nvmf_subsystem_get_first(t.borrow().tgt.as_ptr())
-            })
-        })
+        NVMF_TGT.with(|t| NvmfSubsystemIterator::new(t.borrow().targets()))
     }
 }
 
@@ -148,6 +229,7 @@ impl NvmfSubsystem {
     pub fn try_from_with<T>(
         bdev: &Bdev<T>,
         ptpl: Option<&std::path::PathBuf>,
+        nqn: Option<&str>,
     ) -> Result<Self, Error>
     where
         T: spdk_rs::BdevOps,
@@ -157,7 +239,7 @@ impl NvmfSubsystem {
                 msg: "already shared".to_string(),
             });
         }
-        let ss = NvmfSubsystem::new(bdev.name())?;
+        let ss = NvmfSubsystem::new_with_nqn(bdev.name(), nqn)?;
         ss.set_ana_reporting(false)?;
         ss.allow_any(false);
         if let Err(e) = ss.add_namespace(bdev, ptpl) {
@@ -173,7 +255,7 @@ impl NvmfSubsystem {
     where
         T: spdk_rs::BdevOps,
     {
-        Self::try_from_with(bdev, None)
+        Self::try_from_with(bdev, None, None)
     }
 }
 
@@ -240,7 +322,13 @@ impl NvmfSubsystem {
         _cb_arg: *mut c_void,
     ) {
         let s = NvmfSubsystem::from(subsys);
-        let event = NvmfSubsystemEvent::from_cb_args(event, ctx);
+        s.handle_event(NvmfSubsystemEvent::from_cb_args(event, ctx));
+    }
+
+    /// Handles an event of the subsystem, raised by SPDK or by the keep
+    /// alive poller replacing that of SPDK.
+    pub(super) fn handle_event(self, event: NvmfSubsystemEvent) {
+        let s = self;
 
         debug!("NVMF subsystem event {s:?}: {event:?}");
 
@@ -258,9 +346,39 @@ impl NvmfSubsystem {
             NqnTarget::None => s.meta(),
         };
 
+        // the hooks run once the event is handled, with a copy of it
+        let bdev = match nqn_tgt {
+            NqnTarget::Nexus(n) => Some(n.name.clone()),
+            NqnTarget::Replica(ref r) => Some(r.name()),
+            NqnTarget::None => None,
+        };
+        let hook_event = |kind, c: &NvmfController| HostEvent {
+            kind,
+            nqn: s.get_nqn(),
+            bdev: bdev.clone(),
+            hostnqn: c.hostnqn(),
+            cntlid: unsafe { (*c.0.as_ptr()).cntlid },
+        };
+
+        let hook_event = match &event {
+            NvmfSubsystemEvent::HostConnect(c) => {
+                Some(hook_event(HostEventKind::Connect, c))
+            }
+            NvmfSubsystemEvent::HostDisconnect(c) => {
+                Some(hook_event(HostEventKind::Disconnect, c))
+            }
+            NvmfSubsystemEvent::HostKeepAliveTimeout(c) => {
+                Some(hook_event(HostEventKind::KeepAliveTimeout, c))
+            }
+            NvmfSubsystemEvent::Unknown => None,
+        };
+
         match event {
             NvmfSubsystemEvent::HostConnect(c) => {
-                c.event(EventAction::NvmeConnect, event_meta).generate();
+                c.event(EventAction::NvmeConnect, event_meta.clone())
+                    .generate();
+                host_connected(&s.get_nqn(), &c, event_meta);
+                s.apply_kato(&c);
 
                 match nqn_tgt {
                     NqnTarget::Nexus(n) => s.host_connect_nexus(c, n),
@@ -270,6 +388,7 @@ impl NvmfSubsystem {
             }
             NvmfSubsystemEvent::HostDisconnect(c) => {
                 c.event(EventAction::NvmeDisconnect, event_meta).generate();
+                host_disconnected(&s.get_nqn(), &c);
 
                 match nqn_tgt {
                     NqnTarget::Nexus(n) => s.host_disconnect_nexus(c, n),
@@ -289,6 +408,10 @@ impl NvmfSubsystem {
             }
             NvmfSubsystemEvent::Unknown => {} // ignore unknown events
         }
+
+        if let Some(e) = hook_event {
+            run_host_event_hooks(e);
+        }
     }
 
     /// Completion error callback for nexuses.
@@ -300,18 +423,18 @@ impl NvmfSubsystem {
         let cpl = req.nvme_cpl_mut();
         let mut status = cpl.status();
 
-        if status.crd() == 0 {
-            return;
-        }
-
-        // Use CRD #2 for certain errors.
-        match status.status() {
-            NvmeStatus::Generic(SPDK_NVME_SC_RESERVATION_CONFLICT)
-            | NvmeStatus::Generic(SPDK_NVME_SC_CAPACITY_EXCEEDED) => {
-                status.set_crd(2);
-            }
-            _ => {}
-        }
+        // Use the CRD of the nexus policy, by default #2 for certain errors.
+        status.set_crd(crd_policies().nexus.crd(
+            status.crd(),
+            matches!(
+                status.status(),
+                NvmeStatus::Generic(SPDK_NVME_SC_RESERVATION_CONFLICT)
+            ),
+            matches!(
+                status.status(),
+                NvmeStatus::Generic(SPDK_NVME_SC_CAPACITY_EXCEEDED)
+            ),
+        ));
 
         cpl.set_status(status);
     }
@@ -326,6 +449,7 @@ impl NvmfSubsystem {
         );
 
         nex.add_initiator(&ctrlr.hostnqn());
+        self.count_host_io(nex);
 
         unsafe {
             spdk_nvmf_ctrlr_set_cpl_error_cb(
@@ -346,6 +470,7 @@ impl NvmfSubsystem {
         );
 
         nex.rm_initiator(&ctrlr.hostnqn());
+        nex.forget_host_io_stats(unsafe { (*ctrlr.0.as_ptr()).cntlid });
 
         unsafe {
             spdk_nvmf_ctrlr_set_cpl_error_cb(
@@ -359,9 +484,10 @@ impl NvmfSubsystem {
     /// Called upon a host keep alive timeout (KATO) on a nexus.
     fn host_kato_nexus(&self, ctrlr: NvmfController, nex: &Nexus) {
         warn!(
-            "Host '{host}': keep alive timeout on subsystem '{subsys}' on \
-            nexus '{nex:?}'",
+            "Host '{host}': keep alive timeout ({kato}ms) on subsystem \
+            '{subsys}' on nexus '{nex:?}'",
             host = ctrlr.hostnqn(),
+            kato = ctrlr_kato(&ctrlr),
             subsys = self.get_nqn(),
         );
 
@@ -378,10 +504,19 @@ impl NvmfSubsystem {
 
         let mut status = cpl.status();
 
-        // Change CRD for replica to 3.
-        if status.crd() == 1 {
-            status.set_crd(3);
-        }
+        // Use the CRD of the replica policy, by default #3.
+        status.set_crd(crd_policies().replica.crd(
+            status.crd(),
+            matches!(
+                status.status(),
+                NvmeStatus::Generic(SPDK_NVME_SC_RESERVATION_CONFLICT)
+            ),
+            status.status().is_no_space()
+                || matches!(
+                    status.status(),
+                    NvmeStatus::Generic(SPDK_NVME_SC_CAPACITY_EXCEEDED)
+                ),
+        ));
 
         // Correct vendor-specific ENOSPC error.
         if status.status().is_no_space() {
@@ -401,6 +536,8 @@ impl NvmfSubsystem {
             subsys = self.get_nqn(),
         );
 
+        cancel_resv_release(&self.get_nqn(), &ctrlr.hostnqn());
+
         unsafe {
             spdk_nvmf_ctrlr_set_cpl_error_cb(
                 ctrlr.0.as_ptr(),
@@ -431,25 +568,55 @@ impl NvmfSubsystem {
     /// Called upon a host keep alive timeout (KATO) on a replica.
     fn host_kato_replica(&self, ctrlr: NvmfController, lvol: Lvol) {
         warn!(
-            "Host '{host}': keep alive timeout on subsystem '{subsys}' on \
-            replica '{lvol:?}'",
+            "Host '{host}': keep alive timeout ({kato}ms) on subsystem \
+            '{subsys}' on replica '{lvol:?}'",
             host = ctrlr.hostnqn(),
+            kato = ctrlr_kato(&ctrlr),
             subsys = self.get_nqn(),
         );
+
+        self.schedule_resv_release(&ctrlr);
     }
 
-    /// create a new subsystem where the NQN is based on the UUID
+    /// create a new subsystem where the NQN is based on the UUID, on the
+    /// target serving the traffic of its bdev: nexuses are exported by the
+    /// nexus target, any other bdev by the replica target
     pub fn new(uuid: &str) -> Result<Self, Error> {
-        let nqn = make_nqn(uuid).into_cstring();
+        Self::new_with_nqn(uuid, None)
+    }
+
+    /// create a new subsystem with the given NQN, which must be valid and not
+    /// in use yet, or with an NQN based on the UUID
+    pub fn new_with_nqn(uuid: &str, nqn: Option<&str>) -> Result<Self, Error> {
+        let explicit = nqn.is_some();
+        let nqn = match nqn {
+            Some(nqn) => {
+                validate_nqn(nqn).map_err(|reason| Error::InvalidNqn {
+                    nqn: nqn.to_string(),
+                    reason,
+                })?;
+                if Self::lookup_by_nqn(nqn).is_ok() {
+                    return Err(Error::NqnInUse {
+                        nqn: nqn.to_string(),
+                    });
+                }
+                nqn.to_string()
+            }
+            None => make_nqn(uuid),
+        };
+        let explicit_nqn = explicit.then(|| nqn.clone());
+        let bdev = Bdev::<()>::lookup_by_name(uuid);
+        let kind = TargetKind::of_bdev(uuid);
+        let nqn = nqn.into_cstring();
         let ss = NVMF_TGT
             .with(|t| {
-                let tgt = t.borrow().tgt.as_ptr();
+                let tgt = t.borrow().tgt_of(kind);
                 unsafe {
                     spdk_nvmf_subsystem_create(
                         tgt,
                         nqn.as_ptr(),
                         SPDK_NVMF_SUBTYPE_NVME,
-                        1,
+                        MAX_NAMESPACES,
                     )
                 }
             })
@@ -470,7 +637,7 @@ impl NvmfSubsystem {
 
         // Use truncated SHA256 digest of Bdev UUID or name for subsystem
         // serial number.
-        let sn = if let Some(nn) = Bdev::<()>::lookup_by_name(uuid) {
+        let sn = if let Some(nn) = bdev {
             make_sn(nn.uuid().as_bytes())
         } else {
             make_sn(uuid)
@@ -491,6 +658,9 @@ impl NvmfSubsystem {
                 msg: "failed to set model number".into(),
             })?;
 
+        if let Some(nqn) = explicit_nqn {
+            EXPLICIT_NQNS.lock().unwrap().insert(uuid.to_string(), nqn);
+        }
         Ok(NvmfSubsystem(ss))
     }
 
@@ -513,6 +683,23 @@ impl NvmfSubsystem {
         bdev: &Bdev<T>,
         ptpl: Option<&std::path::PathBuf>,
     ) -> Result<(), Error>
+    where
+        T: spdk_rs::BdevOps,
+    {
+        self.add_namespace_with(bdev, ptpl, true)?;
+        index_nqn(&self.get_nqn(), bdev.name());
+        Ok(())
+    }
+
+    /// Adds the given bdev as a namespace of the subsystem, visible to all
+    /// of its hosts or to none until made visible to some, and returns its
+    /// ID.
+    pub(super) fn add_namespace_with<T>(
+        &self,
+        bdev: &Bdev<T>,
+        ptpl: Option<&std::path::PathBuf>,
+        auto_visible: bool,
+    ) -> Result<u32, Error>
     where
         T: spdk_rs::BdevOps,
     {
@@ -524,7 +711,7 @@ impl NvmfSubsystem {
                 uuid: Default::default(),
                 reserved44: unsafe { zeroed() },
                 anagrpid: 0,
-                no_auto_visible: false,
+                no_auto_visible: !auto_visible,
                 reserved61: unsafe { zeroed() },
                 transport_specific: ptr::null(),
             },
@@ -549,8 +736,8 @@ impl NvmfSubsystem {
             )
         };
 
-        // the first namespace should be 1 and we do not (currently) use
-        // more than one namespace
+        // the first namespace should be 1, the namespace of the share; any
+        // other one is a private namespace
 
         if ns_id < 1 {
             Err(Error::Namespace {
@@ -559,35 +746,85 @@ impl NvmfSubsystem {
             })
         } else {
             debug!(?bdev, ?ns_id, "added as namespace");
-            Ok(())
+            Ok(ns_id)
         }
     }
 
-    /// Removes the namespace and destroys the subsystem.
+    /// Removes the namespaces and destroys the subsystem.
     ///
     /// # Safety
     ///
     /// The subsystem must paused or stopped.
-    pub unsafe fn shutdown_unsafe(&self) -> i32 {
-        if spdk_nvmf_subsystem_remove_ns(self.0.as_ptr(), 1) != 0 {
-            error!(?self, "failed to remove namespace while destroying");
+    unsafe fn shutdown_unsafe(&self) -> i32 {
+        self.stop_counting_host_io();
+        for ns in self.namespaces() {
+            if spdk_nvmf_subsystem_remove_ns(self.0.as_ptr(), ns.nsid) != 0 {
+                error!(
+                    ?self,
+                    nsid = ns.nsid,
+                    "failed to remove namespace while destroying"
+                );
+            }
         }
 
         self.destroy_unsafe()
     }
 
+    /// Destroys a subsystem which failed to be configured, before it was
+    /// ever started.
+    pub fn discard(self) {
+        if unsafe { self.shutdown_unsafe() } != 0 {
+            error!(?self, "failed to discard the subsystem");
+        }
+    }
+
     /// Destroys the SPDK object for subsystem.
     ///
     /// # Safety
     ///
     /// The subsystem must paused or stopped.
     unsafe fn destroy_unsafe(&self) -> i32 {
+        self.destroy_with(None, std::ptr::null_mut())
+    }
+
+    /// Destroys the SPDK object for subsystem, calling the callback once
+    /// done when the destruction is ongoing, i.e. when -EINPROGRESS is
+    /// returned.
+    ///
+    /// # Safety
+    ///
+    /// The subsystem must be stopped.
+    pub(super) unsafe fn destroy_with(
+        &self,
+        cb: spdk_nvmf_subsystem_destroy_cb,
+        arg: *mut c_void,
+    ) -> i32 {
         if (*self.0.as_ptr()).destroying {
             warn!("Subsystem destruction already started");
             return -libc::EALREADY;
         }
 
-        spdk_nvmf_subsystem_destroy(self.0.as_ptr(), None, std::ptr::null_mut())
+        let nqn = self.get_nqn();
+        forget_subsystem(&nqn);
+        forget_lease(&nqn);
+        forget_host_auth(&nqn);
+        release_port(&nqn);
+        forget_controllers(&nqn);
+        forget_duplicate_hosts(&nqn);
+        forget_identify(&nqn);
+        forget_kato(&nqn);
+        forget_resv_release(&nqn);
+        forget_busy_waiters(&nqn);
+        forget_share_state(&nqn);
+        forget_share_mode(&nqn);
+        forget_fence(&nqn);
+        forget_ns_visibility(&nqn);
+        forget_nqn(&nqn);
+        forget_explicit_nqn(self.bdev().as_ref().map(|b| b.name()), &nqn);
+        NVMF_TGT.with(|t| t.borrow_mut().release_listeners(&nqn));
+        let rc = spdk_nvmf_subsystem_destroy(self.0.as_ptr(), cb, arg);
+        sync_discovery_hosts();
+        rc
     }
 
     /// Get NVMe subsystem's NQN
@@ -599,7 +836,7 @@ impl NvmfSubsystem {
         }
     }
 
-    fn cstr(host: &str) -> Result<CString, Error> {
+    pub(super) fn cstr(host: &str) -> Result<CString, Error> {
         CString::new(host).map_err(|_| Error::HostCstrNul {
             host: host.to_string(),
         })
@@ -610,6 +847,9 @@ impl NvmfSubsystem {
         unsafe {
             spdk_nvmf_subsystem_set_allow_any_host(self.0.as_ptr(), enable);
         }
+        if self.subtype() != SubType::Discovery {
+            sync_discovery_hosts();
+        }
     }
 
     /// Get a list with all the host nqn's allowed to connect to this subsystem.
@@ -632,12 +872,29 @@ impl NvmfSubsystem {
         hosts
     }
 
+    /// Get the NQNs of the hosts with a controller connected to the
+    /// subsystem, whether registered or not.
+    pub fn connected_hosts(&self) -> Vec<String> {
+        let mut hosts = Vec::<String>::new();
+
+        let mut ctrlr: *mut spdk_nvmf_ctrlr =
+            unsafe { self.0.as_ref().ctrlrs.tqh_first };
+
+        while !ctrlr.is_null() {
+            let host_str = unsafe { (*ctrlr).hostnqn.as_str() };
+            if !hosts.iter().any(|h| h == host_str) {
+                hosts.push(host_str.to_string());
+            }
+            ctrlr = unsafe { (*ctrlr).link.tqe_next };
+        }
+
+        hosts
+    }
+
     /// Sets the allowed hosts to connect to the subsystem.
-    /// It also disallows and disconnects any previously registered host.
-    /// # Warning
-    ///
-    /// It does not disconnect non-registered hosts, eg: hosts which
-    /// were connected before the allowed_hosts was configured.
+    /// It also disallows and disconnects any previously registered host, and
+    /// disconnects any connected host which is not allowed, eg: hosts which
+    /// connected while any host was allowed.
     pub async fn set_allowed_hosts<H: AsRef<str>>(
         &self,
         hosts: &[H],
@@ -648,6 +905,7 @@ impl NvmfSubsystem {
 
         let hosts = hosts.iter().map(AsRef::as_ref).collect::<Vec<&str>>();
         self.allow_hosts(&hosts)?;
+        let hosts = hosts.into_iter().collect::<HashSet<&str>>();
 
         let mut host =
             unsafe { spdk_nvmf_subsystem_get_first_host(self.0.as_ptr()) };
@@ -669,26 +927,75 @@ impl NvmfSubsystem {
             }
         }
 
+        // hosts which connected without being registered are not in the
+        // list of registered hosts, only in the list of controllers
+        let unregistered = self
+            .connected_hosts()
+            .into_iter()
+            .filter(|host| {
+                !hosts.contains(&host.as_str())
+                    && !hosts_to_disconnect.contains(host)
+            })
+            .collect::<Vec<_>>();
+
+        self.disallow_hosts(&hosts_to_disconnect)?;
         for host in hosts_to_disconnect {
-            self.disallow_host(&host)?;
-            // note this only disconnects previously registered hosts
-            // todo: disconnect any connected host which is not allowed
+            self.disconnect_host(&host).await?;
+        }
+
+        for host in unregistered {
             self.disconnect_host(&host).await?;
         }
 
         Ok(())
     }
 
-    /// Allows the specified hosts to connect to the subsystem.
+    /// Allows the specified hosts to connect to the subsystem. The hosts of
+    /// the discovery subsystems are only synced once all of them are added.
     pub fn allow_hosts(&self, hosts: &[&str]) -> Result<(), Error> {
-        for host in hosts {
-            self.allow_host(host)?;
+        let nqn = self.get_nqn();
+        let result =
+            hosts.iter().try_for_each(|host| self.add_host(&nqn, host));
+        sync_discovery_hosts();
+        result
+    }
+
+    /// Allows a host to connect to the subsystem, with its keys if it must
+    /// authenticate, which is recorded unless any host is allowed.
+    pub fn allow_host(&self, host: &str) -> Result<(), Error> {
+        let nqn = self.get_nqn();
+        self.add_host(&nqn, host)?;
+        if !unsafe { self.0.as_ref().allow_any_host } {
+            record_allowed_host(&nqn, host);
         }
+        sync_discovery_hosts();
         Ok(())
     }
 
-    /// Allows a host to connect to the subsystem.
-    pub fn allow_host(&self, host: &str) -> Result<(), Error> {
+    /// Adds an allowed host to the subsystem of the given NQN, without
+    /// syncing the discovery subsystems.
+    fn add_host(&self, nqn: &str, host: &str) -> Result<(), Error> {
+        self.add_host_with(nqn, host, host_keys(nqn, host).as_ref())
+    }
+
+    /// Adds an allowed host to the subsystem of the given NQN, which must
+    /// authenticate with the given keys, if any, without syncing the
+    /// discovery subsystems.
+    pub(super) fn add_host_with(
+        &self,
+        nqn: &str,
+        host: &str,
+        keys: Option<&HostDhChap>,
+    ) -> Result<(), Error> {
+        if is_fenced(nqn, host) {
+            return Err(Error::HostFenced {
+                nqn: nqn.to_string(),
+                host: host.to_string(),
+            });
+        }
+        if let Some(auth) = keys {
+            return self.allow_host_with_keys(auth);
+        }
         let host = Self::cstr(host)?;
         unsafe {
             spdk_nvmf_subsystem_add_host(
@@ -699,21 +1006,30 @@ impl NvmfSubsystem {
         }
         .to_result(|errno| Error::Subsystem {
             source: Errno::from_i32(errno),
-            nqn: self.get_nqn(),
+            nqn: nqn.to_string(),
             msg: format!("failed to add allowed host: {host:?}"),
         })
     }
 
-    /// Disallow hosts from connecting to the subsystem.
+    /// Disallow hosts from connecting to the subsystem. The hosts of the
+    /// discovery subsystems are only synced once all of them are removed.
     pub fn disallow_hosts(&self, hosts: &[String]) -> Result<(), Error> {
-        for host in hosts {
-            self.disallow_host(host)?;
-        }
-        Ok(())
+        let result = hosts.iter().try_for_each(|host| self.remove_host(host));
+        sync_discovery_hosts();
+        result
     }
 
-    /// Disallow a host from connecting to the subsystem.
+    /// Disallow a host from connecting to the subsystem, which is recorded.
     pub fn disallow_host(&self, host: &str) -> Result<(), Error> {
+        self.remove_host(host)?;
+        record_disallowed_host(&self.get_nqn(), host);
+        sync_discovery_hosts();
+        Ok(())
+    }
+
+    /// Removes an allowed host from the subsystem, without syncing the
+    /// discovery subsystems.
+    pub(super) fn remove_host(&self, host: &str) -> Result<(), Error> {
         let host = Self::cstr(host)?;
         unsafe {
             spdk_nvmf_subsystem_remove_host(self.0.as_ptr(), host.as_ptr())
@@ -722,8 +1038,7 @@ impl NvmfSubsystem {
             source: Errno::from_i32(errno),
             nqn: self.get_nqn(),
             msg: format!("failed to remove allowed host: {host:?}"),
-        })?;
-        Ok(())
+        })
     }
 
     /// Disconnect host from the subsystem.
@@ -753,18 +1068,14 @@ impl NvmfSubsystem {
         })
     }
 
-    /// enable Asymmetric Namespace Access (ANA) reporting
+    /// check if Asymmetric Namespace Access (ANA) is reported to the hosts
+    pub fn ana_reporting(&self) -> bool {
+        unsafe { self.0.as_ref().flags.ana_reporting() }
+    }
+
+    /// enable Asymmetric Namespace Access (ANA) reporting; subsystem must be
+    /// in inactive state
     pub fn set_ana_reporting(&self, enable: bool) -> Result<(), Error> {
-        match std::env::var("NEXUS_NVMF_ANA_ENABLE") {
-            Ok(s) => {
-                if s != "1" {
-                    return Ok(());
-                }
-            }
-            Err(_) => {
-                return Ok(());
-            }
-        }
         unsafe {
             spdk_nvmf_subsystem_set_ana_reporting(self.0.as_ptr(), enable)
         }
@@ -776,6 +1087,32 @@ impl NvmfSubsystem {
         Ok(())
     }
 
+    /// enable or disable ANA reporting on a subsystem in any state; an
+    /// active subsystem is stopped meanwhile, disconnecting the hosts which
+    /// see the change once they reconnect
+    pub async fn update_ana_reporting(
+        &self,
+        enable: bool,
+    ) -> Result<(), Error> {
+        if self.ana_reporting() == enable {
+            return Ok(());
+        }
+        if unsafe { self.0.as_ref().state } == SPDK_NVMF_SUBSYSTEM_INACTIVE {
+            return self.set_ana_reporting(enable);
+        }
+
+        self.stop().await?;
+        let res = self.set_ana_reporting(enable);
+        measure(
+            Operation::SubsystemStart,
+            self.change_state("start", |ss, cb, arg| unsafe {
+                spdk_nvmf_subsystem_start(ss, cb, arg)
+            }),
+        )
+        .await?;
+        res
+    }
+
     /// set controller ID range
     pub fn set_cntlid_range(
         &self,
@@ -797,24 +1134,73 @@ impl NvmfSubsystem {
         Ok(())
     }
 
+    /// Validates the serial and model numbers given to a share, if any.
+    pub fn validate_identity(
+        serial: Option<&str>,
+        model: Option<&str>,
+    ) -> Result<(), Error> {
+        let invalid =
+            |field: &str, value: &str, reason| Error::InvalidIdentity {
+                field: field.to_string(),
+                value: value.to_string(),
+                reason,
+            };
+        if let Some(serial) = serial {
+            validate_serial(serial)
+                .map_err(|reason| invalid("serial number", serial, reason))?;
+        }
+        if let Some(model) = model {
+            validate_model(model)
+                .map_err(|reason| invalid("model number", model, reason))?;
+        }
+        Ok(())
+    }
+
+    /// Overrides the serial number derived from the UUID of the bdev;
+    /// subsystem must be in inactive state.
+    pub fn set_serial(&self, serial: &str) -> Result<(), Error> {
+        Self::validate_identity(Some(serial), None)?;
+        let sn = serial.into_cstring();
+        unsafe { spdk_nvmf_subsystem_set_sn(self.0.as_ptr(), sn.as_ptr()) }
+            .to_result(|e| Error::Subsystem {
+                source: Errno::from_i32(e),
+                nqn: self.get_nqn(),
+                msg: format!("failed to set serial '{serial}'"),
+            })?;
+        Ok(())
+    }
+
+    /// Overrides the default model number; subsystem must be in inactive
+    /// state.
+    pub fn set_model(&self, model: &str) -> Result<(), Error> {
+        Self::validate_identity(None, Some(model))?;
+        let mn = model.into_cstring();
+        unsafe { spdk_nvmf_subsystem_set_mn(self.0.as_ptr(), mn.as_ptr()) }
+            .to_result(|e| Error::Subsystem {
+                source: Errno::from_i32(e),
+                nqn: self.get_nqn(),
+                msg: format!("failed to set model number '{model}'"),
+            })?;
+        Ok(())
+    }
+
     // we currently allow all listeners to the subsystem
-    async fn add_listener(&self) -> Result<(), Error> {
+    async fn add_listener(&self, listener: &NvmfListener) -> Result<(), Error> {
         extern "C" fn listen_cb(arg: *mut c_void, status: i32) {
             let s = unsafe { Box::from_raw(arg as *mut oneshot::Sender<i32>) };
             s.send(status).unwrap();
         }
 
-        let cfg = Config::get();
-
-        // dont yet enable both ports, IOW just add one transportID now
-
-        let trid_replica = TransportId::new(cfg.nexus_opts.nvmf_replica_port);
+        let kind = self.target_kind();
+        let trid = self.trid_of(listener)?;
+        let nqn = self.get_nqn();
+        NVMF_TGT.with(|t| t.borrow_mut().listen_on(kind, &trid, &nqn))?;
 
         let (s, r) = oneshot::channel::<i32>();
         unsafe {
             spdk_nvmf_subsystem_add_listener(
                 self.0.as_ptr(),
-                trid_replica.as_ptr(),
+                trid.as_ptr(),
                 Some(listen_cb),
                 cb_arg(s),
             );
@@ -829,7 +1215,7 @@ impl NvmfSubsystem {
     }
 
     /// TODO
-    async fn change_state(
+    pub(super) async fn change_state(
         &self,
         op: &str,
         f: impl Fn(
@@ -847,7 +1233,11 @@ impl NvmfSubsystem {
             s.send(status).unwrap();
         }
 
-        info!(?self, "Subsystem {} in progress...", op);
+        let nqn = self.get_nqn();
+        let start = Instant::now();
+        info!(?self, nqn = %nqn, op, "Subsystem {} in progress...", op);
+
+        let policy = &Config::get().nvmf_tgt_conf.busy_retry;
 
         let res = {
             let mut n = 0;
@@ -857,32 +1247,39 @@ impl NvmfSubsystem {
 
                 let rc = -f(self.0.as_ptr(), Some(state_change_cb), cb_arg(s));
 
-                if rc != libc::EBUSY || n >= 3 {
+                if rc != libc::EBUSY {
+                    break (rc, r);
+                }
+                if n == 0 {
+                    record_busy(op);
+                }
+                if n + 1 >= policy.max_attempts() {
+                    record_exhausted();
                     break (rc, r);
                 }
 
+                let delay = policy.backoff(n);
                 n += 1;
 
                 warn!(
-                    "Failed to {} '{}': subsystem is busy, retrying {}...",
-                    op,
-                    self.get_nqn(),
-                    n
+                    "Failed to {} '{}': subsystem is busy, retrying {} in \
+                    {:?}...",
+                    op, nqn, n, delay
                 );
 
-                crate::sleep::mayastor_sleep(std::time::Duration::from_millis(
-                    100,
-                ))
-                .await
-                .unwrap();
+                wait_busy(&nqn, delay).await;
             };
 
             match rc {
-                0 => r.await.unwrap().to_result(|e| Error::Subsystem {
-                    source: Errno::from_i32(e),
-                    nqn: self.get_nqn(),
-                    msg: format!("{op} failed"),
-                }),
+                0 => {
+                    let status = r.await.unwrap();
+                    state_changed(&nqn);
+                    status.to_result(|e| Error::Subsystem {
+                        source: Errno::from_i32(e),
+                        nqn: self.get_nqn(),
+                        msg: format!("{op} failed"),
+                    })
+                }
                 libc::EBUSY => Err(Error::SubsystemBusy {
                     nqn: self.get_nqn(),
                     op: op.to_owned(),
@@ -895,10 +1292,26 @@ impl NvmfSubsystem {
             }
         };
 
+        let duration_ms = start.elapsed().as_millis() as u64;
         if let Err(ref e) = res {
-            error!(?self, "Subsystem {} failed: {}", op, e.to_string());
+            error!(
+                ?self,
+                nqn = %nqn,
+                op,
+                duration_ms,
+                "Subsystem {} failed: {}",
+                op,
+                e.to_string()
+            );
         } else {
-            info!(?self, "Subsystem {} completed: Ok", op);
+            info!(
+                ?self,
+                nqn = %nqn,
+                op,
+                duration_ms,
+                "Subsystem {} completed: Ok",
+                op
+            );
         }
 
         res
@@ -908,13 +1321,42 @@ impl NvmfSubsystem {
     /// failure to ensure the state is not in limbo and to avoid leaking
     /// resources
     pub async fn start(self) -> Result<String, Error> {
-        self.add_listener().await?;
+        self.start_with(&[NvmfListener::default()]).await
+    }
 
-        if let Err(e) = self
-            .change_state("start", |ss, cb, arg| unsafe {
+    /// start the subsystem with the given listeners, or listening on TCP on
+    /// the address of the target when none is given
+    pub async fn start_with(
+        self,
+        listeners: &[NvmfListener],
+    ) -> Result<String, Error> {
+        let mut unique: Vec<&NvmfListener> = vec![];
+        for listener in listeners {
+            if !unique.contains(&listener) {
+                unique.push(listener);
+            }
+        }
+        let default = NvmfListener::default();
+        if unique.is_empty() {
+            unique.push(&default);
+        }
+        for listener in unique {
+            if let Err(e) = self.add_listener(listener).await {
+                // e.g. no port is left in the port range
+                unsafe {
+                    self.shutdown_unsafe();
+                }
+                return Err(e);
+            }
+        }
+
+        if let Err(e) = measure(
+            Operation::SubsystemStart,
+            self.change_state("start", |ss, cb, arg| unsafe {
                 spdk_nvmf_subsystem_start(ss, cb, arg)
-            })
-            .await
+            }),
+        )
+        .await
         {
             error!(
                 "Failed to start subsystem '{}': {}; destroying it",
@@ -928,70 +1370,126 @@ impl NvmfSubsystem {
 
             Err(e)
         } else {
+            if self.subtype() != SubType::Discovery {
+                record_listeners(&self.get_nqn(), listeners);
+            }
             Ok(self.get_nqn())
         }
     }
 
     /// stop the subsystem
     pub async fn stop(&self) -> Result<(), Error> {
-        self.change_state("stop", |ss, cb, arg| unsafe {
-            spdk_nvmf_subsystem_stop(ss, cb, arg)
-        })
+        measure(
+            Operation::SubsystemStop,
+            self.change_state("stop", |ss, cb, arg| unsafe {
+                spdk_nvmf_subsystem_stop(ss, cb, arg)
+            }),
+        )
         .await
     }
 
     /// transition the subsystem to paused state
     /// intended to be a temporary state while changes are made
     pub async fn pause(&self) -> Result<(), Error> {
-        self.change_state("pause", |ss, cb, arg| unsafe {
-            spdk_nvmf_subsystem_pause(ss, 1, cb, arg)
-        })
+        measure(
+            Operation::SubsystemPause,
+            self.change_state("pause", |ss, cb, arg| unsafe {
+                spdk_nvmf_subsystem_pause(ss, 1, cb, arg)
+            }),
+        )
         .await
     }
 
     /// transition the subsystem to active state
     pub async fn resume(&self) -> Result<(), Error> {
-        self.change_state("resume", |ss, cb, arg| unsafe {
-            spdk_nvmf_subsystem_resume(ss, cb, arg)
-        })
+        measure(
+            Operation::SubsystemResume,
+            self.change_state("resume", |ss, cb, arg| unsafe {
+                spdk_nvmf_subsystem_resume(ss, cb, arg)
+            }),
+        )
         .await
     }
 
-    /// get ANA state
+    /// The kind of traffic of the target the subsystem belongs to.
+    pub fn target_kind(&self) -> TargetKind {
+        let nqn = self.get_nqn().into_cstring();
+        NVMF_TGT.with(|t| t.borrow().kind_of(self.0.as_ptr(), &nqn))
+    }
+
+    /// The transport ID the subsystem listens on.
+    fn listener_trid(&self) -> TransportId {
+        TransportId::new(self.target_kind().port())
+    }
+
+    /// The transport ID of the given listener: on the port of the subsystem
+    /// from the port range, if any, or on the port of the target the
+    /// subsystem belongs to, unless the listener has its own port.
+    pub(super) fn trid_of(
+        &self,
+        listener: &NvmfListener,
+    ) -> Result<TransportId, Error> {
+        let kind = self.target_kind();
+        let port = match listener.port {
+            Some(port) => port,
+            None => allocate_port(&self.get_nqn(), kind)?
+                .unwrap_or_else(|| kind.port()),
+        };
+        listener.trid(kind, port)
+    }
+
+    /// get ANA state, as reported on the first listener
     pub async fn get_ana_state(&self) -> Result<u32, Error> {
-        let cfg = Config::get();
-        let trid_replica = TransportId::new(cfg.nexus_opts.nvmf_replica_port);
+        let trid = self
+            .listeners_to_vec()
+            .and_then(|listeners| listeners.into_iter().next())
+            .unwrap_or_else(|| self.listener_trid());
         let listener = unsafe {
-            nvmf_subsystem_find_listener(self.0.as_ptr(), trid_replica.as_ptr())
+            nvmf_subsystem_find_listener(self.0.as_ptr(), trid.as_ptr())
         };
         if listener.is_null() {
             Err(Error::Listener {
                 nqn: self.get_nqn(),
-                trid: trid_replica.to_string(),
+                trid: trid.to_string(),
             })
         } else {
             Ok(unsafe { *(*listener).ana_state })
         }
     }
 
-    /// set ANA state: optimized, non_optimized, inaccessible
-    /// subsystem must be in paused or inactive state
+    /// set ANA state: optimized, non_optimized, inaccessible, on all the
+    /// listeners; subsystem must be in paused or inactive state
     pub async fn set_ana_state(&self, ana_state: u32) -> Result<(), Error> {
+        let listeners = self
+            .listeners_to_vec()
+            .unwrap_or_else(|| vec![self.listener_trid()]);
+        for trid in listeners {
+            self.set_listener_ana_state(&trid, ana_state, 0).await?;
+        }
+        Ok(())
+    }
+
+    /// set the ANA state of the given group, of all the groups when 0, on
+    /// the given listener
+    pub(super) async fn set_listener_ana_state(
+        &self,
+        trid: &TransportId,
+        ana_state: u32,
+        anagrpid: u32,
+    ) -> Result<(), Error> {
         extern "C" fn set_ana_state_cb(arg: *mut c_void, status: i32) {
             let s = unsafe { Box::from_raw(arg as *mut oneshot::Sender<i32>) };
             s.send(status).unwrap();
         }
-        let cfg = Config::get();
-        let trid_replica = TransportId::new(cfg.nexus_opts.nvmf_replica_port);
 
         let (s, r) = oneshot::channel::<i32>();
 
         unsafe {
             spdk_nvmf_subsystem_set_ana_state(
                 self.0.as_ptr(),
-                trid_replica.as_ptr(),
+                trid.as_ptr(),
                 ana_state,
-                0,
+                anagrpid,
                 Some(set_ana_state_cb),
                 cb_arg(s),
             );
@@ -1018,51 +1516,47 @@ impl NvmfSubsystem {
         });
     }
 
-    /// stop all subsystems
-    pub async fn stop_all(tgt: *mut spdk_nvmf_tgt) {
-        let subsystem = unsafe {
-            NonNull::new(spdk_nvmf_subsystem_get_first(tgt)).map(NvmfSubsystem)
-        };
-
-        if let Some(subsystem) = subsystem {
-            for s in subsystem.into_iter() {
-                if let Err(e) = s.stop().await {
-                    error!(
-                        "Failed to stop subsystem '{}': {}",
-                        s.get_nqn(),
-                        e.to_string()
-                    );
-                }
-            }
-        }
+    /// stop all subsystems of the given targets
+    pub async fn stop_all(targets: &[*mut spdk_nvmf_tgt]) {
+        let subsystems =
+            NvmfSubsystemIterator::new(targets.to_vec()).collect::<Vec<_>>();
+        Self::change_state_all(subsystems, SubsystemStateChange::Stop, None)
+            .await;
     }
 
     /// Get the first subsystem within the system
     pub fn first() -> Option<NvmfSubsystem> {
-        NVMF_TGT.with(|t| {
-            let ss = unsafe {
-                spdk_nvmf_subsystem_get_first(t.borrow().tgt.as_ptr())
-            };
+        NVMF_TGT
+            .with(|t| NvmfSubsystemIterator::new(t.borrow().targets()))
+            .next()
+    }
 
-            if ss.is_null() {
-                None
-            } else {
-                Some(NvmfSubsystem(NonNull::new(ss).unwrap()))
-            }
+    /// lookup a subsystem by its UUID, or by the name of its bdev when it
+    /// was created with an explicit NQN
+    pub fn nqn_lookup(uuid: &str) -> Option<NvmfSubsystem> {
+        Self::find(&make_nqn(uuid)).or_else(|| {
+            let nqn = EXPLICIT_NQNS.lock().unwrap().get(uuid).cloned()?;
+            Self::find(&nqn)
         })
     }
 
-    /// lookup a subsystem by its UUID
-    pub fn nqn_lookup(uuid: &str) -> Option<NvmfSubsystem> {
-        let nqn = make_nqn(uuid);
-        NvmfSubsystem::first()
-            .unwrap()
+    /// find a subsystem by its NQN on the targets, in the order they are
+    /// iterated, without walking all the subsystems
+    pub(super) fn find(nqn: &str) -> Option<NvmfSubsystem> {
+        let nqn = CString::new(nqn).ok()?;
+        NVMF_TGT
+            .with(|t| t.borrow().targets())
             .into_iter()
-            .find(|s| s.get_nqn() == nqn)
+            .find_map(|tgt| {
+                NonNull::new(unsafe {
+                    spdk_nvmf_tgt_find_subsystem(tgt, nqn.as_ptr())
+                })
+            })
+            .map(NvmfSubsystem)
     }
 
-    /// get the bdev associated with this subsystem -- we implicitly assume the
-    /// first namespace
+    /// get the bdev associated with this subsystem -- the bdev it is shared
+    /// for is always the first namespace, private namespaces come after it
     pub fn bdev(&self) -> Option<UntypedBdev> {
         let ns = unsafe { spdk_nvmf_subsystem_get_first_ns(self.0.as_ptr()) };
 
@@ -1073,7 +1567,7 @@ impl NvmfSubsystem {
         Bdev::checked_from_ptr(unsafe { spdk_nvmf_ns_get_bdev(ns) })
     }
 
-    fn listeners_to_vec(&self) -> Option<Vec<TransportId>> {
+    pub(super) fn listeners_to_vec(&self) -> Option<Vec<TransportId>> {
         unsafe {
             let mut listener =
                 spdk_nvmf_subsystem_get_first_listener(self.0.as_ptr());
@@ -1125,9 +1619,100 @@ impl NvmfSubsystem {
     }
 }
 
+/// Maximum number of namespaces of a subsystem: the namespace of its bdev
+/// and the private namespaces attached to it.
+const MAX_NAMESPACES: u32 = 32;
+/// Maximum length of an NQN, as per the NVMe specification.
+const NQN_MAX_LEN: usize = 223;
+/// Maximum length of the serial number of a controller.
+const SN_MAX_LEN: usize = 20;
+/// Maximum length of the model number of a controller.
+const MN_MAX_LEN: usize = 40;
+
+/// Validates a field of the identify controller data, which holds printable
+/// ASCII characters only.
+fn validate_ident(value: &str, max_len: usize) -> Result<(), String> {
+    if value.is_empty() {
+        return Err("must not be empty".to_string());
+    }
+    if value.len() > max_len {
+        return Err(format!("longer than {max_len} characters"));
+    }
+    if !value.chars().all(|c| c.is_ascii() && !c.is_ascii_control()) {
+        return Err("must only hold printable ASCII characters".to_string());
+    }
+    Ok(())
+}
+
+/// Validates the serial number of a subsystem.
+pub fn validate_serial(serial: &str) -> Result<(), String> {
+    validate_ident(serial, SN_MAX_LEN)
+}
+
+/// Validates the model number of a subsystem.
+pub fn validate_model(model: &str) -> Result<(), String> {
+    validate_ident(model, MN_MAX_LEN)
+}
+
+/// The prefix of the NQNs of the subsystems, as configured for the cluster.
+pub fn nqn_prefix() -> String {
+    Config::get()
+        .nexus_opts
+        .nvmf_nqn_prefix
+        .clone()
+        .unwrap_or_else(|| NVME_NQN_PREFIX.to_string())
+}
+
+/// Validates an NQN prefix of the form `nqn.yyyy-mm.reverse.domain`.
+pub fn validate_nqn_prefix(prefix: &str) -> Result<(), String> {
+    let Some(rest) = prefix.strip_prefix("nqn.") else {
+        return Err("must start with 'nqn.'".to_string());
+    };
+    let (date, domain) = rest
+        .split_once('.')
+        .ok_or_else(|| "missing the reverse domain name".to_string())?;
+    let valid_date = date.len() == 7
+        && date.char_indices().all(|(i, c)| match i {
+            4 => c == '-',
+            _ => c.is_ascii_digit(),
+        });
+    if !valid_date {
+        return Err(format!("invalid date '{date}', expected yyyy-mm"));
+    }
+    let valid_label = |label: &str| {
+        label.starts_with(|c: char| c.is_ascii_alphabetic())
+            && label.ends_with(|c: char| c.is_ascii_alphanumeric())
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+    if !domain.split('.').all(valid_label) {
+        return Err(format!("invalid reverse domain name '{domain}'"));
+    }
+    if prefix.len() >= NQN_MAX_LEN {
+        return Err(format!("longer than {NQN_MAX_LEN} bytes"));
+    }
+    Ok(())
+}
+
+/// Validates a full NQN: a valid prefix followed by `:` and a non-empty
+/// string, or a UUID based NQN.
+pub fn validate_nqn(nqn: &str) -> Result<(), String> {
+    if nqn.len() > NQN_MAX_LEN {
+        return Err(format!("longer than {NQN_MAX_LEN} bytes"));
+    }
+    if let Some(uuid) = nqn.strip_prefix("nqn.2014-08.org.nvmexpress:uuid:") {
+        return uuid::Uuid::parse_str(uuid)
+            .map(|_| ())
+            .map_err(|error| format!("invalid uuid: {error}"));
+    }
+    match nqn.split_once(':') {
+        Some((prefix, name)) if !name.is_empty() => validate_nqn_prefix(prefix),
+        _ => Err("missing the ':' separated name".to_string()),
+    }
+}
+
 /// Makes an NQN froma UUID.
-fn make_nqn(id: &str) -> String {
-    format!("{NVME_NQN_PREFIX}:{id}")
+pub(crate) fn make_nqn(id: &str) -> String {
+    format!("{}:{id}", nqn_prefix())
 }
 
 /// NQN target.
@@ -1139,31 +1724,32 @@ pub enum NqnTarget<'a> {
 
 impl<'a> NqnTarget<'a> {
     pub fn lookup(nqn: &str) -> Self {
-        let Some(bdev) = UntypedBdev::bdev_first() else {
-            return Self::None;
+        // the bdev of a subsystem is indexed as its namespace is added;
+        // otherwise the bdev of a subsystem with an explicit NQN is found
+        // through the subsystem
+        let name = match indexed_bdev(nqn) {
+            Some(name) => name,
+            None => match nqn.strip_prefix(&format!("{}:", nqn_prefix())) {
+                Some(name) if !name.contains(':') => name.to_string(),
+                _ => match NvmfSubsystem::lookup_by_nqn(nqn)
+                    .ok()
+                    .and_then(|s| s.bdev())
+                {
+                    Some(b) => b.name().to_string(),
+                    None => return Self::None,
+                },
+            },
         };
 
-        let parts: Vec<&str> = nqn.split(':').collect();
-        if parts.len() != 2 || parts[0] != NVME_NQN_PREFIX {
+        let Some(b) = UntypedBdev::lookup_by_name(&name) else {
             return Self::None;
-        }
-
-        let name = parts[1];
-
-        for b in bdev.into_iter() {
-            match b.driver() {
-                NEXUS_MODULE_NAME if b.name() == name => {
-                    return Self::Nexus(unsafe {
-                        Nexus::unsafe_from_untyped_bdev(*b)
-                    });
-                }
-                "lvol" if b.name() == name => {
-                    return Lvol::try_from(b).map_or(Self::None, Self::Replica)
-                }
-                _ => {}
+        };
+        match b.driver() {
+            NEXUS_MODULE_NAME => {
+                Self::Nexus(unsafe { Nexus::unsafe_from_untyped_bdev(*b) })
             }
+            "lvol" => Lvol::try_from(b).map_or(Self::None, Self::Replica),
+            _ => Self::None,
         }
-
-        Self::None
     }
 }
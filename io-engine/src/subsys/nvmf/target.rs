@@ -1,23 +1,26 @@
 use std::{
     cell::RefCell,
+    collections::{BTreeSet, HashMap, HashSet},
     ffi::{c_void, CString},
     mem::zeroed,
-    ptr::{null, NonNull},
+    path::{Path, PathBuf},
+    ptr::{null, null_mut, NonNull},
 };
 
 use nix::errno::Errno;
 
 use spdk_rs::libspdk::{
-    spdk_env_get_core_count,
     spdk_nvmf_listen_opts,
     spdk_nvmf_listen_opts_init,
     spdk_nvmf_poll_group_destroy,
+    spdk_nvmf_subsystem,
     spdk_nvmf_subsystem_create,
     spdk_nvmf_subsystem_set_mn,
     spdk_nvmf_target_opts,
     spdk_nvmf_tgt,
     spdk_nvmf_tgt_create,
     spdk_nvmf_tgt_destroy,
+    spdk_nvmf_tgt_find_subsystem,
     spdk_nvmf_tgt_listen_ext,
     spdk_nvmf_tgt_stop_listen,
     spdk_subsystem_fini_next,
@@ -27,15 +30,19 @@ use spdk_rs::libspdk::{
 };
 
 use crate::{
+    bdev::nexus::NEXUS_MODULE_NAME,
     constants::NVME_CONTROLLER_MODEL_ID,
-    core::{Cores, Mthread, Reactors},
+    core::{Cores, MayastorEnvironment, Mthread, Reactors, UntypedBdev},
     ffihelper::{AsStr, FfiResult},
     subsys::{
+        config::opts::parse_core_list,
         nvmf::{
+            discovery::init_discovery,
             poll_groups::PollGroup,
+            share_state::{load_state, save_state},
             subsystem::NvmfSubsystem,
             transport,
-            transport::{get_ipv4_address, TransportId},
+            transport::{NvmfListener, NvmfTransport, TransportId},
             Error,
             NVMF_PGS,
         },
@@ -45,18 +52,93 @@ use crate::{
 
 type Result<T, E = Error> = std::result::Result<T, E>;
 
+/// File the addresses listened on for the subsystems which requested them
+/// are kept in, within the ptpl directory.
+const EXTRA_LISTENERS_FILE: &str = "nvmf-listeners.json";
+
 thread_local! {
 pub (crate) static NVMF_TGT: RefCell<Target> = RefCell::new(Target::new());
 }
 
+/// The kind of traffic an NVMF target serves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetKind {
+    /// Host facing traffic to the nexuses.
+    Nexus,
+    /// Inter-node traffic to the replicas.
+    Replica,
+}
+
+impl TargetKind {
+    /// The port the subsystems of this kind listen on. Without a separate
+    /// replica target, all the subsystems listen on the replica port.
+    pub(crate) fn port(self) -> u16 {
+        let cfg = Config::get();
+        match self {
+            Self::Nexus if cfg.nvmf_tgt_conf.replica_target.is_some() => {
+                cfg.nexus_opts.nvmf_nexus_port
+            }
+            _ => cfg.nexus_opts.nvmf_replica_port,
+        }
+    }
+
+    /// The kind of traffic a share of the given bdev serves: the nexuses are
+    /// host facing, any other bdev is a replica.
+    pub(crate) fn of_bdev(name: &str) -> Self {
+        match UntypedBdev::lookup_by_name(name) {
+            Some(b) if b.driver() == NEXUS_MODULE_NAME => Self::Nexus,
+            _ => Self::Replica,
+        }
+    }
+}
+
+/// An address listened on for the subsystems which requested it, in
+/// addition to the listeners of the targets.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ExtraListener {
+    /// kind of traffic of the target listening on the address
+    kind: TargetKind,
+    transport: NvmfTransport,
+    address: String,
+    port: u16,
+    /// NQNs of the subsystems listening on the address
+    nqns: BTreeSet<String>,
+}
+
+impl ExtraListener {
+    fn trid(&self) -> TransportId {
+        TransportId::new_at(self.transport, &self.address, self.port)
+    }
+}
+
+/// Path of the file of the extra listeners, if a ptpl directory is
+/// configured.
+fn extra_listeners_path() -> Option<PathBuf> {
+    MayastorEnvironment::global_or_default()
+        .ptpl_dir()
+        .map(|dir| Path::new(&dir).join(EXTRA_LISTENERS_FILE))
+}
+
 #[derive(Debug)]
 pub struct Target {
     /// the raw pointer to  our target
     pub(crate) tgt: NonNull<spdk_nvmf_tgt>,
-    /// the number of poll groups created for this target
+    /// the separate target for the replica traffic, if configured
+    pub(crate) replica_tgt: Option<NonNull<spdk_nvmf_tgt>>,
+    /// the number of poll groups created for the targets
     poll_group_count: u16,
+    /// the number of poll groups to be created for the targets
+    poll_group_total: u16,
     /// The current state of the target
     next_state: TargetState,
+    /// the listeners of the targets, by target and address
+    listening: HashSet<(*mut spdk_nvmf_tgt, String)>,
+    /// the addresses listened on for the subsystems which requested them,
+    /// by target and address, until the last of these subsystems is
+    /// destroyed; they are persisted so that they are listened on again as
+    /// soon as the target starts after a restart
+    extra_listeners: HashMap<(*mut spdk_nvmf_tgt, String), ExtraListener>,
 }
 
 impl Default for Target {
@@ -100,8 +182,12 @@ impl Target {
         assert_eq!(Cores::current(), Cores::first());
         Self {
             tgt: NonNull::dangling(),
+            replica_tgt: None,
             poll_group_count: 0,
+            poll_group_total: 0,
             next_state: TargetState::Init,
+            listening: HashSet::new(),
+            extra_listeners: HashMap::new(),
         }
     }
 
@@ -111,19 +197,255 @@ impl Target {
         let tgt_ptr: Box<spdk_nvmf_target_opts> =
             cfg.nvmf_tgt_conf.clone().into();
 
-        let tgt =
-            unsafe { spdk_nvmf_tgt_create(&*tgt_ptr as *const _ as *mut _) };
-        if tgt.is_null() {
-            return Err(Error::CreateTarget {
-                msg: "tgt pointer is None".to_string(),
-            });
+        self.tgt = Self::create(tgt_ptr)?;
+        if let Some(opts) = cfg.nvmf_tgt_conf.replica_tgt_opts() {
+            self.replica_tgt = Some(Self::create(opts)?);
+            info!("created a separate nvmf target for the replicas");
         }
-        self.tgt = NonNull::new(tgt).unwrap();
 
         self.next_state();
         Ok(())
     }
 
+    /// create an spdk target with the given options
+    fn create(
+        opts: Box<spdk_nvmf_target_opts>,
+    ) -> Result<NonNull<spdk_nvmf_tgt>> {
+        let tgt = unsafe { spdk_nvmf_tgt_create(&*opts as *const _ as *mut _) };
+        NonNull::new(tgt).ok_or_else(|| Error::CreateTarget {
+            msg: "tgt pointer is None".to_string(),
+        })
+    }
+
+    /// the raw pointers of all the targets, the main one first
+    pub(crate) fn targets(&self) -> Vec<*mut spdk_nvmf_tgt> {
+        std::iter::once(self.tgt)
+            .chain(self.replica_tgt)
+            .map(NonNull::as_ptr)
+            .collect()
+    }
+
+    /// the kinds of traffic of all the targets, in the order of `targets`:
+    /// the main target serves both unless there is a separate replica target
+    fn kinds(&self) -> Vec<TargetKind> {
+        if self.replica_tgt.is_some() {
+            vec![TargetKind::Nexus, TargetKind::Replica]
+        } else {
+            vec![TargetKind::Nexus]
+        }
+    }
+
+    /// the raw pointer of the target serving the given kind of traffic
+    pub(crate) fn tgt_of(&self, kind: TargetKind) -> *mut spdk_nvmf_tgt {
+        match (kind, self.replica_tgt) {
+            (TargetKind::Replica, Some(tgt)) => tgt.as_ptr(),
+            _ => self.tgt.as_ptr(),
+        }
+    }
+
+    /// whether the target is ready to serve new subsystems
+    pub(crate) fn is_running(&self) -> bool {
+        self.next_state == TargetState::Running
+    }
+
+    /// the kind of traffic the given subsystem is serving, the NQN being
+    /// needed to look it up in the replica target
+    pub(crate) fn kind_of(
+        &self,
+        ss: *mut spdk_nvmf_subsystem,
+        nqn: &CString,
+    ) -> TargetKind {
+        match self.replica_tgt {
+            // the discovery subsystems of both targets share their NQN
+            Some(tgt)
+                if unsafe {
+                    spdk_nvmf_tgt_find_subsystem(tgt.as_ptr(), nqn.as_ptr())
+                } == ss =>
+            {
+                TargetKind::Replica
+            }
+            _ => TargetKind::Nexus,
+        }
+    }
+
+    /// the targets with their kind and the cores their poll groups run on
+    fn poll_group_cores(&self) -> Result<Vec<(TargetKind, Vec<u32>)>> {
+        let cfg = Config::get();
+        let cores = |list: &Option<String>| -> Result<Vec<u32>> {
+            match list {
+                Some(list) => {
+                    let cores = parse_core_list(list).map_err(|msg| {
+                        Error::PgError {
+                            msg,
+                        }
+                    })?;
+                    let cores = cores
+                        .into_iter()
+                        .filter(|c| Reactors::iter().any(|r| r.core() == *c))
+                        .collect::<Vec<_>>();
+                    if cores.is_empty() {
+                        return Err(Error::PgError {
+                            msg: format!(
+                                "no reactor runs on the cores '{list}'"
+                            ),
+                        });
+                    }
+                    Ok(cores)
+                }
+                None => Ok(Reactors::iter().map(|r| r.core()).collect()),
+            }
+        };
+
+        let mut targets =
+            vec![(TargetKind::Nexus, cores(&cfg.nvmf_tgt_conf.cores)?)];
+        if let Some(replica) = &cfg.nvmf_tgt_conf.replica_target {
+            targets.push((TargetKind::Replica, cores(&replica.cores)?));
+        }
+        Ok(targets)
+    }
+
+    /// the listeners of the targets on the transports enabled on each of
+    /// them: the main target listens on both ports unless there is a separate
+    /// replica target
+    fn listeners(&self) -> Vec<(*mut spdk_nvmf_tgt, TransportId)> {
+        let cfg = Config::get();
+        [
+            (TargetKind::Nexus, cfg.nexus_opts.nvmf_nexus_port),
+            (TargetKind::Replica, cfg.nexus_opts.nvmf_replica_port),
+        ]
+        .into_iter()
+        .flat_map(|(kind, port)| {
+            let tgt = self.tgt_of(kind);
+            NvmfTransport::enabled(kind)
+                .into_iter()
+                .map(move |transport| {
+                    (tgt, TransportId::new_with(transport, port))
+                })
+        })
+        .collect()
+    }
+
+    /// listen on the given transport ID for the given subsystem with the
+    /// target serving the given kind of traffic, unless the target already
+    /// listens on it
+    pub(crate) fn listen_on(
+        &mut self,
+        kind: TargetKind,
+        trid: &TransportId,
+        nqn: &str,
+    ) -> Result<()> {
+        let key = (self.tgt_of(kind), trid.to_string());
+        if self.listening.contains(&key) {
+            return Ok(());
+        }
+        if let Some(extra) = self.extra_listeners.get_mut(&key) {
+            if extra.nqns.insert(nqn.to_string()) {
+                self.save_listeners();
+            }
+            return Ok(());
+        }
+
+        Self::listen_ext(key.0, trid)?;
+        info!("nvmf {kind:?} target listening on {trid}");
+        let extra = ExtraListener {
+            kind,
+            transport: trid.transport(),
+            address: trid.traddr.as_str().to_string(),
+            port: trid.trsvcid.as_str().parse().unwrap_or_default(),
+            nqns: BTreeSet::from([nqn.to_string()]),
+        };
+        self.extra_listeners.insert(key, extra);
+        self.save_listeners();
+        Ok(())
+    }
+
+    /// stop listening on the addresses the given subsystem, which is being
+    /// destroyed, was the last one to listen on
+    pub(crate) fn release_listeners(&mut self, nqn: &str) {
+        let mut changed = false;
+        self.extra_listeners.retain(|(tgt, _), extra| {
+            if !extra.nqns.remove(nqn) {
+                return true;
+            }
+            changed = true;
+            if !extra.nqns.is_empty() {
+                return true;
+            }
+            let trid = extra.trid();
+            let rc = unsafe { spdk_nvmf_tgt_stop_listen(*tgt, trid.as_ptr()) };
+            if rc != 0 {
+                warn!("failed to stop listening on {trid}: {rc}");
+            } else {
+                info!(
+                    "nvmf {:?} target stopped listening on {trid}",
+                    extra.kind
+                );
+            }
+            false
+        });
+        if changed {
+            self.save_listeners();
+        }
+    }
+
+    /// listen on the given transport ID with the given target
+    fn listen_ext(tgt: *mut spdk_nvmf_tgt, trid: &TransportId) -> Result<()> {
+        let mut opts = Self::listen_opts();
+        let rc =
+            unsafe { spdk_nvmf_tgt_listen_ext(tgt, trid.as_ptr(), &mut opts) };
+        if rc != 0 {
+            return Err(Error::Transport {
+                source: Errno::from_i32(rc.abs()),
+                msg: format!("failed to listen on {trid}"),
+            });
+        }
+        Ok(())
+    }
+
+    /// persist the extra listeners, a failure being only logged as the
+    /// target itself is already updated
+    fn save_listeners(&self) {
+        let Some(path) = extra_listeners_path() else {
+            return;
+        };
+        let listeners = self.extra_listeners.values().collect::<Vec<_>>();
+        if let Err(error) = save_state(&path, &listeners) {
+            error!(%error, "Failed to save listeners '{}'", path.display());
+        }
+    }
+
+    /// listen again on the persisted extra listeners, dropping those which
+    /// cannot be listened on anymore, e.g. as their address was removed
+    fn restore_listeners(&mut self) {
+        let Some(path) = extra_listeners_path() else {
+            return;
+        };
+        let listeners: Vec<ExtraListener> =
+            load_state(&path).unwrap_or_default();
+        let count = listeners.len();
+        for extra in listeners {
+            let trid = extra.trid();
+            let key = (self.tgt_of(extra.kind), trid.to_string());
+            if self.listening.contains(&key)
+                || self.extra_listeners.contains_key(&key)
+            {
+                continue;
+            }
+            let result =
+                NvmfTransport::validate(extra.kind, &[extra.transport])
+                    .and_then(|_| Self::listen_ext(key.0, &trid));
+            if let Err(error) = result {
+                warn!(%error, "Not listening on {trid} again");
+                continue;
+            }
+            info!("nvmf {:?} target listening on {trid} again", extra.kind);
+            self.extra_listeners.insert(key, extra);
+        }
+        if self.extra_listeners.len() != count {
+            self.save_listeners();
+        }
+    }
+
     /// internally drive the target towards the next state
     pub(crate) fn next_state(&mut self) {
         match self.next_state {
@@ -133,7 +455,11 @@ impl Target {
             }
             TargetState::PollGroupInit => {
                 self.next_state = TargetState::AddTransport;
-                self.init_poll_groups();
+                if let Err(error) = self.init_poll_groups() {
+                    error!("failed to create the poll groups: {error}");
+                    self.next_state = TargetState::Invalid;
+                    self.next_state();
+                }
             }
             TargetState::AddTransport => {
                 self.next_state = TargetState::AddListener;
@@ -172,10 +498,18 @@ impl Target {
         };
     }
 
-    /// add the transport to the target
+    /// add the transports enabled on each target to it
     fn add_transport(&self) {
-        Reactors::master().send_future(async {
-            let result = transport::add_tcp_transport().await;
+        let kinds = self.kinds();
+        Reactors::master().send_future(async move {
+            let mut result = Ok(());
+            for kind in kinds {
+                for t in NvmfTransport::enabled(kind) {
+                    if result.is_ok() {
+                        result = transport::add_transport(kind, t).await;
+                    }
+                }
+            }
             NVMF_TGT.with(|t| {
                 if result.is_err() {
                     t.borrow_mut().next_state = TargetState::Invalid;
@@ -185,31 +519,48 @@ impl Target {
         })
     }
 
-    /// init the poll groups per core
-    fn init_poll_groups(&self) {
-        Reactors::iter().for_each(|r| {
-            if let Some(t) = Mthread::new(
-                format!("mayastor_nvmf_tcp_pg_core_{}", r.core()),
-                r.core(),
-            ) {
-                r.send_future(Self::create_poll_group(self.tgt.as_ptr(), t));
-            }
-        });
+    /// init the poll groups per core of every target
+    fn init_poll_groups(&mut self) -> Result<()> {
+        let targets = self.poll_group_cores()?;
+        self.poll_group_total =
+            targets.iter().map(|(_, cores)| cores.len() as u16).sum();
+
+        for (kind, cores) in targets {
+            let tgt = self.tgt_of(kind);
+            let name = match kind {
+                TargetKind::Nexus => "mayastor_nvmf_tcp_pg",
+                TargetKind::Replica => "mayastor_nvmf_tcp_replica_pg",
+            };
+            info!("nvmf {kind:?} target poll groups on cores {cores:?}");
+            Reactors::iter()
+                .filter(|r| cores.contains(&r.core()))
+                .for_each(|r| {
+                    if let Some(t) = Mthread::new(
+                        format!("{name}_core_{}", r.core()),
+                        r.core(),
+                    ) {
+                        r.send_future(Self::create_poll_group(tgt, kind, t));
+                    }
+                });
+        }
+        Ok(())
     }
 
     /// init the poll groups implementation
-    async fn create_poll_group(tgt: *mut spdk_nvmf_tgt, mt: Mthread) {
+    async fn create_poll_group(
+        tgt: *mut spdk_nvmf_tgt,
+        kind: TargetKind,
+        mt: Mthread,
+    ) {
         mt.with(|| {
-            let pg = PollGroup::new(tgt, mt);
+            let pg = PollGroup::new(tgt, kind, mt);
 
             Reactors::master().send_future(async move {
                 NVMF_TGT.with(|tgt| {
                     let mut tgt = tgt.borrow_mut();
                     NVMF_PGS.with(|p| p.borrow_mut().push(pg));
                     tgt.poll_group_count += 1;
-                    if tgt.poll_group_count
-                        == unsafe { spdk_env_get_core_count() as u16 }
-                    {
+                    if tgt.poll_group_count == tgt.poll_group_total {
                         Reactors::master().send_future(async {
                             NVMF_TGT.with(|tgt| {
                                 tgt.borrow_mut().next_state();
@@ -221,11 +572,8 @@ impl Target {
         });
     }
 
-    /// Listen for incoming connections by default we only listen on the replica
-    /// port
-    fn listen(&mut self) -> Result<()> {
-        let cfg = Config::get();
-        let trid_nexus = TransportId::new(cfg.nexus_opts.nvmf_nexus_port);
+    /// default listen options
+    fn listen_opts() -> spdk_nvmf_listen_opts {
         let mut opts = spdk_nvmf_listen_opts {
             opts_size: 0,
             transport_specific: null(),
@@ -239,51 +587,57 @@ impl Target {
                 std::mem::size_of::<spdk_nvmf_listen_opts>() as u64,
             );
         }
-        let rc = unsafe {
-            spdk_nvmf_tgt_listen_ext(
-                self.tgt.as_ptr(),
-                trid_nexus.as_ptr(),
-                &mut opts,
-            )
-        };
-
-        if rc != 0 {
-            return Err(Error::CreateTarget {
-                msg: "failed to back target".into(),
-            });
-        }
+        opts
+    }
 
-        let trid_replica = TransportId::new(cfg.nexus_opts.nvmf_replica_port);
-        let rc = unsafe {
-            spdk_nvmf_tgt_listen_ext(
-                self.tgt.as_ptr(),
-                trid_replica.as_ptr(),
-                &mut opts,
-            )
-        };
+    /// Listen for incoming connections on the nexus and replica ports
+    fn listen(&mut self) -> Result<()> {
+        let mut opts = Self::listen_opts();
 
-        if rc != 0 {
-            return Err(Error::CreateTarget {
-                msg: "failed to front target".into(),
-            });
+        let listeners = self.listeners();
+        for (tgt, trid) in &listeners {
+            let rc = unsafe {
+                spdk_nvmf_tgt_listen_ext(*tgt, trid.as_ptr(), &mut opts)
+            };
+            if rc != 0 {
+                return Err(Error::CreateTarget {
+                    msg: format!("failed to listen on {trid}"),
+                });
+            }
         }
+
+        self.listening = listeners
+            .iter()
+            .map(|(tgt, trid)| (*tgt, trid.to_string()))
+            .collect();
+        let listeners = listeners
+            .iter()
+            .map(|(_, trid)| trid.to_string())
+            .collect::<Vec<_>>();
         info!(
-            "nvmf target listening on {}:({},{})",
-            get_ipv4_address().unwrap(),
-            trid_nexus.trsvcid.as_str(),
-            trid_replica.trsvcid.as_str(),
+            "nvmf target listening on {}{}",
+            listeners.join(", "),
+            if self.replica_tgt.is_some() {
+                " with a separate replica target"
+            } else {
+                ""
+            }
         );
+        self.restore_listeners();
         self.next_state();
         Ok(())
     }
 
     /// Create the discovery for the target -- note that the discovery system is
     /// not started.
-    fn create_discovery_subsystem(&self) -> NvmfSubsystem {
+    fn create_discovery_subsystem(
+        &self,
+        tgt: *mut spdk_nvmf_tgt,
+    ) -> NvmfSubsystem {
         debug!("enabling discovery for target");
         let discovery = unsafe {
             NvmfSubsystem::from(spdk_nvmf_subsystem_create(
-                self.tgt.as_ptr(),
+                tgt,
                 SPDK_NVMF_DISCOVERY_NQN.as_ptr() as *const std::os::raw::c_char,
                 SPDK_NVMF_SUBTYPE_DISCOVERY,
                 0,
@@ -306,11 +660,11 @@ impl Target {
         discovery
     }
 
-    /// stop all subsystems on this target we are borrowed here
+    /// stop all subsystems on the targets we are borrowed here
     fn stop_subsystems(&self) {
-        let tgt = self.tgt.as_ptr();
+        let targets = self.targets();
         Reactors::master().send_future(async move {
-            NvmfSubsystem::stop_all(tgt).await;
+            NvmfSubsystem::stop_all(&targets).await;
             debug!("All subsystems stopped");
             NvmfSubsystem::destroy_all();
         });
@@ -357,13 +711,25 @@ impl Target {
 
     /// Final state for the target during init.
     pub fn running(&mut self) {
-        let discovery = self.create_discovery_subsystem();
+        let discovery = self
+            .targets()
+            .into_iter()
+            .zip(self.kinds())
+            .map(|(tgt, kind)| (self.create_discovery_subsystem(tgt), kind))
+            .collect::<Vec<_>>();
 
         Reactors::master().send_future(async move {
-            let nqn = discovery.get_nqn();
-            if let Err(error) = discovery.start().await {
-                error!("Error starting subsystem '{nqn}': {error}");
+            for (discovery, kind) in discovery {
+                let nqn = discovery.get_nqn();
+                let listeners = NvmfTransport::enabled(kind)
+                    .into_iter()
+                    .map(NvmfListener::from)
+                    .collect::<Vec<_>>();
+                if let Err(error) = discovery.start_with(&listeners).await {
+                    error!("Error starting subsystem '{nqn}': {error}");
+                }
             }
+            init_discovery();
 
             info!(
                 "nvmf target accepting new connections and is ready to roll..{}",
@@ -382,6 +748,18 @@ impl Target {
             }
         }
 
+        // the main target is destroyed once the replica target is gone
+        extern "C" fn replica_destroy_cb(arg: *mut c_void, _status: i32) {
+            debug!("NVMe-oF replica target destroyed");
+            unsafe {
+                spdk_nvmf_tgt_destroy(
+                    arg as *mut spdk_nvmf_tgt,
+                    Some(destroy_cb),
+                    null_mut(),
+                )
+            }
+        }
+
         // TODO: properly fix use-after-free on spdk_nvmf_tgt_stop_listen call.
         if option_env!("ASAN_ENABLE").unwrap_or_default() == "1" {
             warn!(
@@ -390,32 +768,31 @@ impl Target {
                   use-after-free error"
             );
         } else {
-            let cfg = Config::get();
-            let trid_nexus = TransportId::new(cfg.nexus_opts.nvmf_nexus_port);
-            let trid_replica =
-                TransportId::new(cfg.nexus_opts.nvmf_replica_port);
+            for ((tgt, _), extra) in &self.extra_listeners {
+                unsafe {
+                    spdk_nvmf_tgt_stop_listen(*tgt, extra.trid().as_ptr())
+                };
+            }
+            for (tgt, trid) in self.listeners().iter().rev() {
+                unsafe { spdk_nvmf_tgt_stop_listen(*tgt, trid.as_ptr()) };
+            }
+        }
 
-            unsafe {
-                spdk_nvmf_tgt_stop_listen(
-                    self.tgt.as_ptr(),
-                    trid_replica.as_ptr(),
+        match self.replica_tgt {
+            Some(replica_tgt) => unsafe {
+                spdk_nvmf_tgt_destroy(
+                    replica_tgt.as_ptr(),
+                    Some(replica_destroy_cb),
+                    self.tgt.as_ptr() as *mut c_void,
                 )
-            };
-
-            unsafe {
-                spdk_nvmf_tgt_stop_listen(
+            },
+            None => unsafe {
+                spdk_nvmf_tgt_destroy(
                     self.tgt.as_ptr(),
-                    trid_nexus.as_ptr(),
+                    Some(destroy_cb),
+                    null_mut(),
                 )
-            };
-        }
-
-        unsafe {
-            spdk_nvmf_tgt_destroy(
-                self.tgt.as_ptr(),
-                Some(destroy_cb),
-                std::ptr::null_mut(),
-            )
+            },
         }
     }
 
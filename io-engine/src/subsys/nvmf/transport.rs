@@ -1,19 +1,23 @@
 use std::{
     ffi::CString,
     fmt::{Debug, Display, Formatter},
+    net::Ipv4Addr,
     ops::{Deref, DerefMut},
 };
 
 use futures::channel::oneshot;
 use nix::errno::Errno;
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
 
 use spdk_rs::{
     ffihelper::{copy_cstr_with_null, copy_str_with_null},
     libspdk::{
         spdk_nvme_transport_id,
+        spdk_nvme_transport_type,
         spdk_nvmf_tgt_add_transport,
         spdk_nvmf_transport_create,
+        SPDK_NVME_TRANSPORT_RDMA,
         SPDK_NVME_TRANSPORT_TCP,
         SPDK_NVMF_ADRFAM_IPV4,
         SPDK_NVMF_TRSVCID_MAX_LEN,
@@ -24,7 +28,7 @@ use crate::{
     core::MayastorEnvironment,
     ffihelper::{cb_arg, done_errno_cb, AsStr, ErrnoResult, FfiResult},
     subsys::{
-        nvmf::{Error, NVMF_TGT},
+        nvmf::{target::TargetKind, Error, NVMF_TGT},
         Config,
     },
 };
@@ -32,12 +36,164 @@ use crate::{
 static TCP_TRANSPORT: Lazy<CString> =
     Lazy::new(|| CString::new("TCP").unwrap());
 
-pub async fn add_tcp_transport() -> Result<(), Error> {
-    let cfg = Config::get();
-    let mut opts = cfg.nvmf_tgt_conf.opts.into();
-    let transport = unsafe {
-        spdk_nvmf_transport_create(TCP_TRANSPORT.as_ptr(), &mut opts)
+static RDMA_TRANSPORT: Lazy<CString> =
+    Lazy::new(|| CString::new("RDMA").unwrap());
+
+/// Transports the subsystems can listen on.
+#[derive(
+    Debug, Default, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum NvmfTransport {
+    #[default]
+    Tcp,
+    Rdma,
+}
+
+impl NvmfTransport {
+    /// Name of the transport as known by SPDK.
+    fn name(self) -> &'static CString {
+        match self {
+            Self::Tcp => &TCP_TRANSPORT,
+            Self::Rdma => &RDMA_TRANSPORT,
+        }
+    }
+
+    fn trtype(self) -> spdk_nvme_transport_type {
+        match self {
+            Self::Tcp => SPDK_NVME_TRANSPORT_TCP,
+            Self::Rdma => SPDK_NVME_TRANSPORT_RDMA,
+        }
+    }
+
+    /// Scheme of the URIs of the subsystems listening on the transport.
+    fn scheme(self) -> &'static str {
+        match self {
+            Self::Tcp => "nvmf",
+            Self::Rdma => "nvmf+rdma",
+        }
+    }
+
+    /// The transports the target serving the given kind of traffic serves:
+    /// TCP, and RDMA when enabled on that target.
+    pub fn enabled(kind: TargetKind) -> Vec<Self> {
+        let cfg = &Config::get().nvmf_tgt_conf;
+        let rdma = match (kind, &cfg.replica_target) {
+            (TargetKind::Replica, Some(replica)) => replica.rdma,
+            _ => cfg.rdma,
+        };
+        if rdma.unwrap_or_default() {
+            vec![Self::Tcp, Self::Rdma]
+        } else {
+            vec![Self::Tcp]
+        }
+    }
+
+    /// Checks that the given transports are enabled on the target serving
+    /// the given kind of traffic.
+    pub fn validate(
+        kind: TargetKind,
+        transports: &[Self],
+    ) -> Result<(), Error> {
+        let enabled = Self::enabled(kind);
+        match transports.iter().find(|t| !enabled.contains(t)) {
+            Some(transport) => Err(Error::Transport {
+                source: Errno::EPROTONOSUPPORT,
+                msg: format!(
+                    "{transport} transport is not enabled on the {kind:?} \
+                    target"
+                ),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// An address a subsystem listens on.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NvmfListener {
+    /// Transport of the listener.
+    pub transport: NvmfTransport,
+    /// IPv4 address, the address of the target when not given.
+    pub address: Option<String>,
+    /// Port, the port of the target the subsystem belongs to when not given.
+    pub port: Option<u16>,
+}
+
+impl From<NvmfTransport> for NvmfListener {
+    fn from(transport: NvmfTransport) -> Self {
+        Self {
+            transport,
+            ..Default::default()
+        }
+    }
+}
+
+impl NvmfListener {
+    /// Checks that the transports of the given listeners are enabled on the
+    /// target serving the given kind of traffic and that their addresses are
+    /// valid.
+    pub fn validate(kind: TargetKind, listeners: &[Self]) -> Result<(), Error> {
+        for listener in listeners {
+            NvmfTransport::validate(kind, &[listener.transport])?;
+            if let Some(address) = &listener.address {
+                if address.parse::<Ipv4Addr>().is_err() {
+                    return Err(Error::Transport {
+                        source: Errno::EINVAL,
+                        msg: format!("invalid listener address '{address}'"),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The transport ID of the listener on the target serving the given kind
+    /// of traffic, the given port being used when the listener has none.
+    pub(crate) fn trid(
+        &self,
+        kind: TargetKind,
+        default_port: u16,
+    ) -> Result<TransportId, Error> {
+        Self::validate(kind, std::slice::from_ref(self))?;
+        let address = match &self.address {
+            Some(address) => address.clone(),
+            None => get_ipv4_address()?,
+        };
+        Ok(TransportId::new_at(
+            self.transport,
+            &address,
+            self.port.unwrap_or(default_port),
+        ))
+    }
+}
+
+impl Display for NvmfTransport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name().to_string_lossy())
+    }
+}
+
+/// Adds the given transport to the target serving the given kind of traffic.
+pub async fn add_transport(
+    kind: TargetKind,
+    transport: NvmfTransport,
+) -> Result<(), Error> {
+    let cfg = &Config::get().nvmf_tgt_conf;
+    let mut opts = match (kind, &cfg.replica_target, transport) {
+        (TargetKind::Replica, Some(replica), NvmfTransport::Tcp) => {
+            replica.opts.into()
+        }
+        (TargetKind::Replica, Some(replica), NvmfTransport::Rdma) => {
+            replica.rdma_opts.into()
+        }
+        (_, _, NvmfTransport::Tcp) => cfg.opts.into(),
+        (_, _, NvmfTransport::Rdma) => cfg.rdma_opts.into(),
     };
+    let name = transport.name();
+    let transport =
+        unsafe { spdk_nvmf_transport_create(name.as_ptr(), &mut opts) };
 
     transport.to_result(|_| Error::Transport {
         source: Errno::UnknownErrno,
@@ -48,7 +204,7 @@ pub async fn add_tcp_transport() -> Result<(), Error> {
     unsafe {
         NVMF_TGT.with(|t| {
             spdk_nvmf_tgt_add_transport(
-                t.borrow().tgt.as_ptr(),
+                t.borrow().tgt_of(kind),
                 transport,
                 Some(done_errno_cb),
                 cb_arg(s),
@@ -58,7 +214,10 @@ pub async fn add_tcp_transport() -> Result<(), Error> {
 
     let _result = r.await.unwrap();
 
-    debug!("Added TCP nvmf transport");
+    debug!(
+        "Added {} nvmf transport to the {kind:?} target",
+        name.to_string_lossy()
+    );
     Ok(())
 }
 
@@ -79,10 +238,18 @@ impl DerefMut for TransportId {
 
 impl TransportId {
     pub fn new(port: u16) -> Self {
-        let address = get_ipv4_address().unwrap();
+        Self::new_with(NvmfTransport::Tcp, port)
+    }
 
+    /// Transport ID of the given transport on the given port.
+    pub fn new_with(transport: NvmfTransport, port: u16) -> Self {
+        Self::new_at(transport, &get_ipv4_address().unwrap(), port)
+    }
+
+    /// Transport ID of the given transport on the given address and port.
+    pub fn new_at(transport: NvmfTransport, address: &str, port: u16) -> Self {
         let mut trid = spdk_nvme_transport_id {
-            trtype: SPDK_NVME_TRANSPORT_TCP,
+            trtype: transport.trtype(),
             adrfam: SPDK_NVMF_ADRFAM_IPV4,
             ..Default::default()
         };
@@ -90,13 +257,22 @@ impl TransportId {
         let port = format!("{port}");
         assert!(port.len() < SPDK_NVMF_TRSVCID_MAX_LEN as usize);
 
-        copy_cstr_with_null(&TCP_TRANSPORT, &mut trid.trstring);
-        copy_str_with_null(&address, &mut trid.traddr);
+        copy_cstr_with_null(transport.name(), &mut trid.trstring);
+        copy_str_with_null(address, &mut trid.traddr);
         copy_str_with_null(&port, &mut trid.trsvcid);
 
         Self(trid)
     }
 
+    /// The transport of the transport ID.
+    pub fn transport(&self) -> NvmfTransport {
+        if self.0.trtype == SPDK_NVME_TRANSPORT_RDMA {
+            NvmfTransport::Rdma
+        } else {
+            NvmfTransport::Tcp
+        }
+    }
+
     pub fn as_ptr(&self) -> *mut spdk_nvme_transport_id {
         &self.0 as *const _ as *mut spdk_nvme_transport_id
     }
@@ -106,7 +282,8 @@ impl Display for TransportId {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "nvmf://{}:{}",
+            "{}://{}:{}",
+            self.transport().scheme(),
             self.0.traddr.as_str(),
             self.0.trsvcid.as_str()
         )
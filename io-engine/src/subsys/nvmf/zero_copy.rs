@@ -0,0 +1,191 @@
+//! Zero-copy statistics of the NVMe-oF shares.
+//!
+//! With the `zcopy` option of the TCP transport, the target asks the bdev of
+//! a subsystem for the data buffers of the commands, so that the data is
+//! received into and sent from them without being copied; this requires the
+//! bdev to support zero-copy I/O. The sockets accepted by the targets may
+//! additionally send with `MSG_ZEROCOPY`.
+//!
+//! SPDK does not count the zero-copy operations, but it counts the data
+//! buffers the transports take from the iobuf pools to copy the data of the
+//! commands, and the I/O commands completed by the poll groups of the
+//! targets. Both counters are reported as they are: a command may take
+//! several buffers when it is larger than the `io_unit_size` of the
+//! transport, and commands without data or with in-capsule data take none,
+//! so the copied buffers do not tell which commands took the zero-copy path.
+
+use std::ffi::c_void;
+
+use futures::{channel::oneshot, FutureExt};
+use serde::Serialize;
+use spdk_rs::libspdk::{
+    spdk_bdev_io_type_supported,
+    spdk_iobuf_get_stats,
+    spdk_iobuf_module_stats,
+    spdk_iobuf_pool_stats,
+    spdk_nvmf_poll_group_get_stat,
+    spdk_nvmf_poll_group_stat,
+    SPDK_BDEV_IO_TYPE_ZCOPY,
+};
+
+use super::{NvmfSubsystem, SubType, TargetKind, NVMF_PGS, NVMF_TGT};
+use crate::{
+    core::Reactor,
+    ffihelper::{cb_arg, AsStr},
+    jsonrpc::{jsonrpc_register, JsonRpcError},
+    subsys::{
+        config::opts::{GetOpts, PosixSocketOpts},
+        Config,
+    },
+};
+
+/// Prefix of the iobuf modules of the NVMe-oF transports.
+const TRANSPORT_MODULE_PREFIX: &str = "nvmf_";
+
+/// Zero-copy support of a subsystem.
+#[derive(Debug, Clone, Serialize)]
+pub struct SubsystemZeroCopy {
+    pub nqn: String,
+    pub bdev: String,
+    /// Whether the I/O of the subsystem may take the zero-copy path, its
+    /// target and its bdev both supporting it.
+    pub zero_copy: bool,
+}
+
+/// Zero-copy statistics of the targets, counted since startup.
+#[derive(Debug, Clone, Serialize)]
+pub struct ZeroCopyStats {
+    /// Zero-copy operations are enabled on the transport of the nexus
+    /// target.
+    pub nexus_zcopy: bool,
+    /// Zero-copy operations are enabled on the transport of the replica
+    /// target.
+    pub replica_zcopy: bool,
+    /// The sockets accepted by the targets send with `MSG_ZEROCOPY`.
+    pub send_zerocopy: bool,
+    /// Number of I/O commands completed by the targets.
+    pub io_commands: u64,
+    /// Number of data buffers the transports copied the data of the
+    /// commands through.
+    pub copied_buffers: u64,
+    pub subsystems: Vec<SubsystemZeroCopy>,
+}
+
+/// Whether zero-copy operations are enabled on the transport of the target
+/// serving the given kind of traffic.
+fn transport_zcopy(kind: TargetKind) -> bool {
+    let cfg = Config::get();
+    match (kind, &cfg.nvmf_tgt_conf.replica_target) {
+        (TargetKind::Replica, Some(replica)) => replica.opts.zcopy(),
+        _ => cfg.nvmf_tgt_conf.opts.zcopy(),
+    }
+}
+
+/// Buffers taken from a pool, the waits for one included as each is
+/// eventually served.
+fn pool_buffers(stats: &spdk_iobuf_pool_stats) -> u64 {
+    stats.cache + stats.main + stats.retry
+}
+
+/// Counts the data buffers the transports of the targets took from the
+/// iobuf pools.
+async fn copied_buffers() -> u64 {
+    extern "C" fn stats_cb(
+        modules: *mut spdk_iobuf_module_stats,
+        num_modules: u32,
+        arg: *mut c_void,
+    ) {
+        let s = unsafe { Box::from_raw(arg as *mut oneshot::Sender<u64>) };
+        let modules = if modules.is_null() {
+            &[]
+        } else {
+            unsafe { std::slice::from_raw_parts(modules, num_modules as usize) }
+        };
+        let buffers = modules
+            .iter()
+            .filter(|m| m.module.as_str().starts_with(TRANSPORT_MODULE_PREFIX))
+            .map(|m| pool_buffers(&m.small_pool) + pool_buffers(&m.large_pool))
+            .sum();
+        s.send(buffers).ok();
+    }
+
+    let (s, r) = oneshot::channel::<u64>();
+    let rc = unsafe { spdk_iobuf_get_stats(Some(stats_cb), cb_arg(s)) };
+    if rc != 0 {
+        error!("Failed to get the iobuf statistics: {rc}");
+        return 0;
+    }
+    r.await.unwrap_or_default()
+}
+
+/// Counts the I/O commands completed by the poll groups of the targets, on
+/// the thread of every poll group.
+async fn completed_io_commands() -> u64 {
+    let pgs = NVMF_PGS.with(|pgs| pgs.borrow().clone());
+    let mut total = 0;
+    for pg in pgs {
+        let tgt = NVMF_TGT.with(|t| t.borrow().tgt_of(pg.kind));
+        let count = Reactor::spawn_at(&pg.thread, async move {
+            let mut stat = spdk_nvmf_poll_group_stat::default();
+            let rc = unsafe { spdk_nvmf_poll_group_get_stat(tgt, &mut stat) };
+            (rc == 0).then_some(stat.completed_nvme_io)
+        });
+        match count {
+            Ok(rx) => total += rx.await.ok().flatten().unwrap_or_default(),
+            Err(error) => {
+                error!("Failed to count the commands on {pg:?}: {error}")
+            }
+        }
+    }
+    total
+}
+
+/// Collects the zero-copy statistics of the targets and the zero-copy
+/// support of the subsystems.
+pub async fn zero_copy_stats() -> ZeroCopyStats {
+    let subsystems = NvmfSubsystem::first()
+        .map(|first| {
+            first
+                .into_iter()
+                .filter(|s| s.subtype() == SubType::Nvme)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let mut stats = ZeroCopyStats {
+        nexus_zcopy: transport_zcopy(TargetKind::Nexus),
+        replica_zcopy: transport_zcopy(TargetKind::Replica),
+        send_zerocopy: PosixSocketOpts::default().get().zerocopy_send_server(),
+        io_commands: completed_io_commands().await,
+        copied_buffers: copied_buffers().await,
+        subsystems: vec![],
+    };
+
+    for subsystem in subsystems {
+        let Some(bdev) = subsystem.bdev() else {
+            continue;
+        };
+        let supported = unsafe {
+            spdk_bdev_io_type_supported(
+                bdev.unsafe_inner_ptr() as *mut _,
+                SPDK_BDEV_IO_TYPE_ZCOPY,
+            )
+        };
+        stats.subsystems.push(SubsystemZeroCopy {
+            nqn: subsystem.get_nqn(),
+            bdev: bdev.name().to_string(),
+            zero_copy: supported && transport_zcopy(subsystem.target_kind()),
+        });
+    }
+    stats
+}
+
+/// Registers the JSON-RPC methods of the zero-copy statistics.
+pub(super) fn register_rpc_methods() {
+    // zero-copy settings of the targets and subsystems, with the I/O
+    // commands of the targets and the data buffers they copied
+    jsonrpc_register::<(), _, _, JsonRpcError>(
+        "mayastor_zero_copy_stats",
+        |_| async move { Ok(zero_copy_stats().await) }.boxed_local(),
+    );
+}
@@ -0,0 +1,35 @@
+use io_engine::core::{accel::AccelStats, MayastorCliArgs};
+
+pub mod common;
+use common::MayastorTest;
+
+#[tokio::test]
+async fn accel_stats() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        let stats = AccelStats::get().await;
+        // no hardware module is enabled by default, nothing is offloaded
+        assert!(!stats.dsa_enable);
+        assert!(!stats.iaa_enable);
+        assert_eq!(stats.offloaded_ops, 0);
+        assert_eq!(stats.fallback_ops, 0);
+        assert_eq!(stats.hit_rate, 0.0);
+        assert!(stats.opcodes.iter().all(|op| op.offload_module.is_none()));
+        assert!(stats.opcodes.iter().any(|op| op.opcode == "crc32c"));
+        assert!(stats
+            .opcodes
+            .iter()
+            .filter(|op| !op.module.is_empty())
+            .all(|op| op.module == "software"));
+
+        let executed: u64 = stats.modules.iter().map(|m| m.executed).sum();
+        assert_eq!(executed, stats.software_ops);
+
+        // the execution counters of the modules only grow between samples
+        let next = AccelStats::get().await;
+        assert!(next.software_ops >= stats.software_ops);
+        let executed: u64 = next.opcodes.iter().map(|op| op.executed).sum();
+        assert!(executed >= next.software_ops);
+    })
+    .await;
+}
@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+use futures::channel::oneshot;
+use io_engine::{
+    core::{
+        admin_ops::{self, AdminOpState},
+        CoreError,
+        MayastorCliArgs,
+    },
+    sleep::mayastor_sleep,
+};
+
+pub mod common;
+use common::MayastorTest;
+
+#[tokio::test]
+async fn admin_ops() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        let limits = admin_ops::pending();
+        assert!(limits.ops.is_empty());
+
+        // fill the running operations with ones which block
+        let mut blockers = Vec::new();
+        let mut running = Vec::new();
+        for _ in 0 .. limits.max_running {
+            let (tx, rx) = oneshot::channel::<()>();
+            blockers.push(tx);
+            running.push(
+                admin_ops::submit(None, async move { rx.await.is_ok() })
+                    .unwrap(),
+            );
+        }
+        mayastor_sleep(Duration::from_millis(10)).await.unwrap();
+        let pending = admin_ops::pending();
+        assert_eq!(pending.running, limits.max_running);
+        assert_eq!(pending.queued, 0);
+        assert!(pending
+            .ops
+            .iter()
+            .all(|op| op.name.contains("admin_ops.rs")));
+
+        // an operation waiting longer than its timeout is dropped
+        let expired =
+            admin_ops::submit(Some(Duration::from_millis(50)), async { true })
+                .unwrap();
+        mayastor_sleep(Duration::from_millis(10)).await.unwrap();
+        assert_eq!(admin_ops::pending().queued, 1);
+        assert!(expired.await.is_err());
+        assert_eq!(admin_ops::pending().expired, limits.expired + 1);
+
+        // the queue is bounded
+        let mut queued = Vec::new();
+        for i in 0 .. limits.max_queued {
+            queued.push(admin_ops::submit(None, async move { i }).unwrap());
+        }
+        assert!(matches!(
+            admin_ops::submit(None, async {}),
+            Err(CoreError::AdminQueueFull { .. })
+        ));
+        let pending = admin_ops::pending();
+        assert_eq!(pending.queued, limits.max_queued);
+        assert_eq!(pending.rejected, limits.rejected + 1);
+        assert!(pending
+            .ops
+            .iter()
+            .any(|op| op.state == AdminOpState::Queued));
+
+        // the queued operations run once the running ones complete
+        for tx in blockers {
+            tx.send(()).unwrap();
+        }
+        for rx in running {
+            assert!(rx.await.unwrap());
+        }
+        for (i, rx) in queued.into_iter().enumerate() {
+            assert_eq!(rx.await.unwrap(), i);
+        }
+        assert!(admin_ops::pending().ops.is_empty());
+    })
+    .await;
+}
@@ -0,0 +1,136 @@
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup, nexus_lookup_mut},
+    bdev_api::bdev_create,
+    core::{
+        BdevFence,
+        BdevOperation,
+        CoreError,
+        MayastorCliArgs,
+        Protocol,
+        Share,
+        UntypedBdev,
+    },
+};
+use once_cell::sync::OnceCell;
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+static MS: OnceCell<MayastorTest> = OnceCell::new();
+
+fn mayastor() -> &'static MayastorTest<'static> {
+    MS.get_or_init(|| MayastorTest::new(MayastorCliArgs::default()))
+}
+
+const NEXUS_NAME: &str = "fence_nexus";
+
+#[tokio::test]
+async fn bdev_fence_share_unshare() {
+    mayastor()
+        .spawn(async {
+            bdev_create("malloc:///fence0?size_mb=4").await.unwrap();
+            let mut bdev1 = UntypedBdev::lookup_by_name("fence0").unwrap();
+            let mut bdev2 = UntypedBdev::lookup_by_name("fence0").unwrap();
+
+            // the unshare overlaps with the share, which is in progress
+            let (shared, unshared) = futures::join!(
+                Pin::new(&mut bdev1).share_nvmf(None),
+                Pin::new(&mut bdev2).unshare(),
+            );
+            shared.unwrap();
+            assert!(matches!(
+                unshared,
+                Err(CoreError::OperationInProgress {
+                    operation: BdevOperation::Share,
+                    ..
+                })
+            ));
+            assert_eq!(BdevFence::in_progress("fence0"), None);
+            assert_eq!(bdev1.shared(), Some(Protocol::Nvmf));
+
+            Pin::new(&mut bdev1).unshare().await.unwrap();
+            assert_eq!(bdev1.shared(), Some(Protocol::Off));
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn bdev_fence_destroy_shared_nexus() {
+    mayastor()
+        .spawn(async {
+            nexus_create(
+                NEXUS_NAME,
+                4 * 1024 * 1024,
+                None,
+                &["malloc:///fence1?size_mb=8".to_string()],
+            )
+            .await
+            .unwrap();
+            nexus_lookup_mut(NEXUS_NAME)
+                .unwrap()
+                .share(Protocol::Nvmf, None)
+                .await
+                .unwrap();
+
+            // the destroy unshares the nexus itself
+            nexus_lookup_mut(NEXUS_NAME)
+                .unwrap()
+                .destroy()
+                .await
+                .unwrap();
+            assert!(nexus_lookup(NEXUS_NAME).is_none());
+            assert_eq!(BdevFence::in_progress(NEXUS_NAME), None);
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn bdev_fence_destroy_owner() {
+    mayastor()
+        .spawn(async {
+            bdev_create("malloc:///fence2?size_mb=4").await.unwrap();
+            let mut bdev = UntypedBdev::lookup_by_name("fence2").unwrap();
+            Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+
+            // an unshare which is not part of the destroy fails
+            let fence =
+                BdevFence::acquire("fence2", BdevOperation::Destroy).unwrap();
+            assert!(matches!(
+                Pin::new(&mut bdev).unshare().await,
+                Err(CoreError::OperationInProgress {
+                    operation: BdevOperation::Destroy,
+                    ..
+                })
+            ));
+            assert_eq!(bdev.shared(), Some(Protocol::Nvmf));
+
+            // only the fence of the destroy on the very bdev covers it
+            let other =
+                BdevFence::acquire("fence3", BdevOperation::Destroy).unwrap();
+            assert!(BdevFence::acquire_within(
+                "fence2",
+                BdevOperation::Unshare,
+                Some(&other)
+            )
+            .is_err());
+            let nested = BdevFence::acquire_within(
+                "fence2",
+                BdevOperation::Unshare,
+                Some(&fence),
+            )
+            .unwrap();
+            assert!(nested.is_none());
+            assert_eq!(
+                BdevFence::in_progress("fence2"),
+                Some(BdevOperation::Destroy)
+            );
+
+            drop(other);
+            drop(fence);
+            assert_eq!(BdevFence::in_progress("fence2"), None);
+            Pin::new(&mut bdev).unshare().await.unwrap();
+            assert_eq!(bdev.shared(), Some(Protocol::Off));
+        })
+        .await;
+}
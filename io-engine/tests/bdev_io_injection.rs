@@ -0,0 +1,205 @@
+#![cfg(feature = "fault-injection")]
+
+use std::time::{Duration, Instant};
+
+use crossbeam::atomic::AtomicCell;
+use io_engine::{
+    bdev::{device_create, device_destroy, device_open},
+    core::{
+        fault_injection::{
+            add_fault_injection,
+            remove_fault_injection,
+            FaultDomain,
+            FaultIoOperation,
+            FaultMethod,
+            Injection,
+            InjectionBuilder,
+        },
+        BlockDevice,
+        BlockDeviceHandle,
+        IoCompletionStatus,
+        MayastorCliArgs,
+    },
+    sleep::mayastor_sleep,
+};
+use libc::c_void;
+use once_cell::sync::OnceCell;
+use spdk_rs::{DmaBuf, NvmeStatus};
+
+pub mod common;
+use common::MayastorTest;
+
+static MS: OnceCell<MayastorTest> = OnceCell::new();
+
+fn mayastor() -> &'static MayastorTest<'static> {
+    MS.get_or_init(|| MayastorTest::new(MayastorCliArgs::default()))
+}
+
+/// Status of the last flush, None while it is in flight.
+static FLUSH_STATUS: AtomicCell<Option<bool>> = AtomicCell::new(None);
+
+fn flush_completion(
+    _device: &dyn BlockDevice,
+    status: IoCompletionStatus,
+    _ctx: *mut c_void,
+) {
+    FLUSH_STATUS.store(Some(status == IoCompletionStatus::Success));
+}
+
+/// Flushes the device and returns whether the flush succeeded.
+async fn flush(handle: &dyn BlockDeviceHandle) -> bool {
+    FLUSH_STATUS.store(None);
+    handle
+        .flush_io(flush_completion, std::ptr::null_mut())
+        .unwrap();
+    loop {
+        if let Some(success) = FLUSH_STATUS.load() {
+            return success;
+        }
+        mayastor_sleep(Duration::from_millis(10)).await.unwrap();
+    }
+}
+
+fn injection(
+    device: &str,
+    op: FaultIoOperation,
+    method: FaultMethod,
+) -> Injection {
+    InjectionBuilder::default()
+        .with_domain(FaultDomain::BdevIo)
+        .with_device_name(device.to_string())
+        .with_io_operation(op)
+        .with_method(method)
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn bdev_io_injection_delay() {
+    mayastor()
+        .spawn(async {
+            let uri = "malloc:///fi_delay?size_mb=16";
+            let name = device_create(uri).await.unwrap();
+            let handle =
+                device_open(&name, true).unwrap().into_handle().unwrap();
+            let buf =
+                DmaBuf::new(4096, handle.get_device().alignment()).unwrap();
+
+            let inj = injection(
+                &name,
+                FaultIoOperation::Write,
+                FaultMethod::Delay(Duration::from_millis(500)),
+            );
+            add_fault_injection(inj.clone()).unwrap();
+
+            // writes are delayed, reads are not
+            let start = Instant::now();
+            handle.write_at(0, &buf).await.unwrap();
+            assert!(start.elapsed() >= Duration::from_millis(500));
+            let mut read = DmaBuf::new(4096, buf.alignment()).unwrap();
+            let start = Instant::now();
+            handle.read_at(0, &mut read).await.unwrap();
+            assert!(start.elapsed() < Duration::from_millis(500));
+
+            remove_fault_injection(&inj.uri()).unwrap();
+            let start = Instant::now();
+            handle.write_at(0, &buf).await.unwrap();
+            assert!(start.elapsed() < Duration::from_millis(500));
+
+            drop(handle);
+            device_destroy(uri).await.unwrap();
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn bdev_io_injection_drop_write() {
+    mayastor()
+        .spawn(async {
+            let uri = "malloc:///fi_drop?size_mb=16";
+            let name = device_create(uri).await.unwrap();
+            let handle =
+                device_open(&name, true).unwrap().into_handle().unwrap();
+            let mut buf =
+                DmaBuf::new(4096, handle.get_device().alignment()).unwrap();
+            buf.fill(0xaa);
+            handle.write_at(0, &buf).await.unwrap();
+
+            let inj =
+                injection(&name, FaultIoOperation::Write, FaultMethod::Drop);
+            add_fault_injection(inj.clone()).unwrap();
+
+            // the dropped write succeeds without reaching the device
+            buf.fill(0x55);
+            handle.write_at(0, &buf).await.unwrap();
+            remove_fault_injection(&inj.uri()).unwrap();
+
+            let mut read = DmaBuf::new(4096, buf.alignment()).unwrap();
+            handle.read_at(0, &mut read).await.unwrap();
+            assert!(read.as_slice().iter().all(|b| *b == 0xaa));
+
+            drop(handle);
+            device_destroy(uri).await.unwrap();
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn bdev_io_injection_flush() {
+    mayastor()
+        .spawn(async {
+            let uri = "malloc:///fi_flush?size_mb=16";
+            let name = device_create(uri).await.unwrap();
+            let handle =
+                device_open(&name, true).unwrap().into_handle().unwrap();
+            assert!(flush(&*handle).await);
+
+            // only the flushes are failed
+            let inj = injection(
+                &name,
+                FaultIoOperation::Flush,
+                FaultMethod::Status(IoCompletionStatus::NvmeError(
+                    NvmeStatus::DATA_TRANSFER_ERROR,
+                )),
+            );
+            add_fault_injection(inj.clone()).unwrap();
+            assert!(!flush(&*handle).await);
+            let buf =
+                DmaBuf::new(4096, handle.get_device().alignment()).unwrap();
+            handle.write_at(0, &buf).await.unwrap();
+            remove_fault_injection(&inj.uri()).unwrap();
+
+            // dropped flushes succeed
+            let inj =
+                injection(&name, FaultIoOperation::Flush, FaultMethod::Drop);
+            add_fault_injection(inj.clone()).unwrap();
+            assert!(flush(&*handle).await);
+            remove_fault_injection(&inj.uri()).unwrap();
+
+            drop(handle);
+            device_destroy(uri).await.unwrap();
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn bdev_io_injection_domain() {
+    mayastor()
+        .spawn(async {
+            // delays and drops are carried out by the bdev I/O injection only
+            for method in [
+                FaultMethod::Delay(Duration::from_millis(1)),
+                FaultMethod::Drop,
+            ] {
+                let inj = InjectionBuilder::default()
+                    .with_domain(FaultDomain::NexusChild)
+                    .with_device_name("fi_domain".to_string())
+                    .with_io_operation(FaultIoOperation::Write)
+                    .with_method(method)
+                    .build()
+                    .unwrap();
+                assert!(add_fault_injection(inj).is_err());
+            }
+        })
+        .await;
+}
@@ -0,0 +1,87 @@
+use async_trait::async_trait;
+use io_engine::{
+    bdev::{
+        register_uri_scheme,
+        uri_schemes,
+        BdevCreateDestroy,
+        CreateDestroy,
+        GetName,
+    },
+    bdev_api::{bdev_create, bdev_destroy, BdevError},
+    core::{MayastorCliArgs, UntypedBdev},
+};
+use url::Url;
+
+pub mod common;
+use common::MayastorTest;
+
+/// An out-of-tree device type, which is backed by a malloc bdev.
+#[derive(Debug)]
+struct RamDisk {
+    name: String,
+}
+
+impl RamDisk {
+    fn malloc_uri(&self) -> String {
+        format!("malloc:///{}?size_mb=4", self.name)
+    }
+}
+
+#[async_trait(?Send)]
+impl CreateDestroy for RamDisk {
+    type Error = BdevError;
+
+    async fn create(&self) -> Result<String, Self::Error> {
+        bdev_create(&self.malloc_uri()).await
+    }
+
+    async fn destroy(self: Box<Self>) -> Result<(), Self::Error> {
+        bdev_destroy(&self.malloc_uri()).await
+    }
+}
+
+impl GetName for RamDisk {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+fn parse_ramdisk(
+    url: &Url,
+) -> Result<Box<dyn BdevCreateDestroy<Error = BdevError>>, BdevError> {
+    Ok(Box::new(RamDisk {
+        name: url.path().trim_start_matches('/').to_string(),
+    }))
+}
+
+#[tokio::test]
+async fn bdev_uri_registry() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        // an unknown scheme is not supported until registered
+        assert!(matches!(
+            bdev_create("ramdisk:///ram0").await,
+            Err(BdevError::UriSchemeUnsupported { .. })
+        ));
+        register_uri_scheme("ramdisk", "malloc", parse_ramdisk).unwrap();
+        assert!(uri_schemes().contains(&"ramdisk".to_string()));
+
+        // and may be registered once only, as may the built-in ones
+        assert!(matches!(
+            register_uri_scheme("ramdisk", "malloc", parse_ramdisk),
+            Err(BdevError::UriSchemeExists { .. })
+        ));
+        assert!(matches!(
+            register_uri_scheme("malloc", "malloc", parse_ramdisk),
+            Err(BdevError::UriSchemeExists { .. })
+        ));
+
+        let name = bdev_create("ramdisk:///ram0").await.unwrap();
+        assert_eq!(name, "ram0");
+        assert!(UntypedBdev::lookup_by_name("ram0").is_some());
+
+        bdev_destroy("ramdisk:///ram0").await.unwrap();
+        assert!(UntypedBdev::lookup_by_name("ram0").is_none());
+    })
+    .await;
+}
@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use io_engine::core::{
+    clock::{boot_id, check_clock_skew, clock_status, ClockSample},
+    MayastorCliArgs,
+};
+
+pub mod common;
+use common::MayastorTest;
+
+#[tokio::test]
+async fn clock_skew_check() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        let sample = ClockSample::now();
+        assert_eq!(sample.boot_id, boot_id());
+        assert!(!sample.boot_id.is_empty());
+        assert!(sample.monotonic_ns > 0);
+
+        // the clocks advance at the same pace
+        let (_, skew) = check_clock_skew(&sample, Duration::from_millis(500));
+        assert!(skew.is_none());
+        assert!(clock_status().last_skew.is_none());
+
+        // a wall clock which went a second back
+        let earlier = ClockSample {
+            wall_clock_ns: sample.wall_clock_ns + 1_000_000_000,
+            ..sample
+        };
+        let (_, skew) = check_clock_skew(&earlier, Duration::from_millis(500));
+        let skew = skew.unwrap();
+        assert!(skew.skew_ms <= -1000);
+        assert_eq!(clock_status().last_skew.unwrap().skew_ms, skew.skew_ms);
+
+        // a zero threshold is no threshold at all, rather than one every
+        // check goes past
+        let (_, skew) = check_clock_skew(&sample, Duration::ZERO);
+        assert!(skew.is_none());
+        let (_, skew) = check_clock_skew(&earlier, Duration::ZERO);
+        assert!(skew.is_none());
+
+        // the monotonic clock counts the time since the boot, suspended time
+        // included, as the uptime of the node does
+        let uptime = std::fs::read_to_string("/proc/uptime").unwrap();
+        let uptime: f64 =
+            uptime.split_whitespace().next().unwrap().parse().unwrap();
+        let monotonic = ClockSample::now().monotonic_ns as f64 / 1e9;
+        assert!((monotonic - uptime).abs() < 1.0, "{monotonic} {uptime}");
+    })
+    .await;
+}
@@ -0,0 +1,86 @@
+pub mod common;
+
+use std::time::Duration;
+
+use common::{
+    compose::{
+        recovery::{crash_and_recover, Crash, PersistedState},
+        rpc::v1::GrpcConnect,
+        Binary,
+        Builder,
+        ComposeTest,
+    },
+    pool::PoolBuilder,
+    replica::ReplicaBuilder,
+};
+
+const DISK_NAME: &str = "/tmp/crash_recovery_disk";
+const DISK_SIZE: u64 = 128 * 1024 * 1024;
+const REPL_SIZE: u64 = 16;
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Creates a single io-engine container with its pool disk on the host, so
+/// that the pool survives the container being brought down.
+async fn create_compose_test() -> ComposeTest {
+    common::composer_init();
+
+    common::delete_file(&[DISK_NAME.to_string()]);
+    common::truncate_file_bytes(DISK_NAME, DISK_SIZE);
+
+    Builder::new()
+        .name("cargo-test")
+        .network("10.1.0.0/16")
+        .unwrap()
+        .add_container_bin(
+            "ms_0",
+            Binary::from_dbg("io-engine")
+                .with_args(vec!["-l", "1"])
+                .with_bind("/tmp", "/host/tmp"),
+        )
+        .with_clean(true)
+        .build()
+        .await
+        .unwrap()
+}
+
+/// Crashes the io-engine, cleanly and then abruptly, and checks that the
+/// pool and the replicas created before the crash are restored as they were.
+#[tokio::test]
+async fn crash_recovery() {
+    let test = create_compose_test().await;
+    let ms_0 = GrpcConnect::new(&test)
+        .grpc_handle_shared("ms_0")
+        .await
+        .unwrap();
+
+    let mut pool = PoolBuilder::new(ms_0.clone())
+        .with_name("pool0")
+        .with_new_uuid()
+        .with_bdev(&format!("aio:///host{DISK_NAME}?blk_size=512"));
+    pool.create().await.unwrap();
+
+    for (i, thin) in [false, true].into_iter().enumerate() {
+        let mut repl = ReplicaBuilder::new(ms_0.clone())
+            .with_pool(&pool)
+            .with_name(&format!("r{i}"))
+            .with_new_uuid()
+            .with_size_mb(REPL_SIZE)
+            .with_thin(thin);
+        repl.create().await.unwrap();
+        if thin {
+            repl.share().await.unwrap();
+        }
+    }
+
+    let state = PersistedState::capture(ms_0).await.unwrap();
+    assert_eq!(state.pools.len(), 1);
+    assert_eq!(state.replicas.len(), 2);
+    assert_eq!(state.replicas.iter().filter(|r| r.shared).count(), 1);
+
+    for crash in [Crash::Restart, Crash::Kill] {
+        let ms_0 = crash_and_recover(&test, "ms_0", crash, &state, TIMEOUT)
+            .await
+            .unwrap();
+        state.assert_matches(ms_0).await;
+    }
+}
@@ -0,0 +1,28 @@
+use io_engine::core::{iobuf::IoBufStats, MayastorCliArgs};
+
+pub mod common;
+use common::MayastorTest;
+
+#[tokio::test]
+async fn iobuf_stats_sample() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        let stats = IoBufStats::get().await;
+        assert!(stats.small_pool_count > 0);
+        assert!(stats.large_pool_count > 0);
+        assert!(stats.small_bufsize < stats.large_bufsize);
+        assert!(stats.modules.iter().any(|m| m.module == "bdev"));
+
+        // the first sample sets the baseline, an idle node queues no I/O
+        assert!(!IoBufStats::sample().await.exhausted);
+        let stats = IoBufStats::sample().await;
+        assert!(!stats.exhausted);
+        let retry: u64 = stats
+            .modules
+            .iter()
+            .map(|m| m.small_pool.retry + m.large_pool.retry)
+            .sum();
+        assert_eq!(retry, stats.small_pool.retry + stats.large_pool.retry);
+    })
+    .await;
+}
@@ -228,3 +228,42 @@ async fn test_lock_timed_subsystem() {
 async fn test_lock_timed_resource() {
     test_lock_timed_level(LockLevel::Resource).await
 }
+
+#[tokio::test]
+async fn test_lock_resources() {
+    let subsystem = get_lock_manager().get_subsystem(TEST_SUBSYSTEM);
+    let timeout = Some(Duration::from_secs(5));
+
+    // More resources than mutexes: the resources sharing a mutex must not
+    // lock it twice.
+    let ids = (0 .. 32).map(|i| format!("nexus{i}")).collect::<Vec<_>>();
+    let guards = subsystem
+        .lock_resources(&ids, timeout, false)
+        .await
+        .expect("Failed to acquire the locks");
+    assert!(guards.len() <= 8);
+
+    // Any overlapping set of resources must wait.
+    let overlap = subsystem
+        .lock_resources(["other", ids[7].as_str()], None, true)
+        .await;
+    assert!(overlap.is_none(), "Double Lock acquired");
+    drop(guards);
+
+    // Tasks locking the same resources in different orders must not
+    // deadlock.
+    let tasks = [["a", "b", "c"], ["c", "b", "a"]].map(|ids| {
+        tokio::spawn(async move {
+            let subsystem = get_lock_manager().get_subsystem(TEST_SUBSYSTEM);
+            for _ in 0 .. 100 {
+                let guards =
+                    subsystem.lock_resources(ids, timeout, false).await;
+                assert!(guards.is_some(), "Failed to acquire the locks");
+                tokio::task::yield_now().await;
+            }
+        })
+    });
+    for task in tasks {
+        task.await.expect("Test task panicked");
+    }
+}
@@ -64,6 +64,7 @@ async fn lvs_pool_test() {
             disks: vec![format!("aio://{DISKNAME1}")],
             uuid: None,
             cluster_size: None,
+            reserved_pct: None,
             backend: PoolBackend::Lvs,
         })
         .await
@@ -188,6 +189,7 @@ async fn lvs_pool_test() {
             disks: vec!["malloc:///malloc0?size_mb=64".to_string()],
             uuid: None,
             cluster_size: None,
+            reserved_pct: None,
             backend: PoolBackend::Lvs,
         })
         .await
@@ -225,6 +227,7 @@ async fn lvs_pool_test() {
             disks: vec![format!("aio://{DISKNAME1}")],
             uuid: None,
             cluster_size: None,
+            reserved_pct: None,
             backend: PoolBackend::Lvs,
         })
         .await
@@ -369,6 +372,7 @@ async fn lvs_pool_test() {
             disks: vec![format!("aio://{DISKNAME1}")],
             uuid: None,
             cluster_size: None,
+            reserved_pct: None,
             backend: PoolBackend::Lvs,
         })
         .await
@@ -390,6 +394,7 @@ async fn lvs_pool_test() {
             disks: vec![format!("aio://{pool_dev_aio}")],
             uuid: None,
             cluster_size: None,
+            reserved_pct: None,
             backend: PoolBackend::Lvs,
         })
         .await
@@ -417,6 +422,7 @@ async fn lvs_pool_test() {
             disks: vec![format!("uring://{pool_dev_uring}")],
             uuid: None,
             cluster_size: None,
+            reserved_pct: None,
             backend: PoolBackend::Lvs,
         })
         .await
@@ -454,6 +460,7 @@ async fn lvs_pool_test() {
             disks: vec![format!("aio://{DISKNAME1}")],
             uuid: None,
             cluster_size: None,
+            reserved_pct: None,
             backend: PoolBackend::Lvs,
         })
         .await
@@ -471,6 +478,7 @@ async fn lvs_pool_test() {
             disks: vec![format!("aio://{DISKNAME2}")],
             uuid: None,
             cluster_size: None,
+            reserved_pct: None,
             backend: PoolBackend::Lvs,
         })
         .await
@@ -0,0 +1,82 @@
+use common::MayastorTest;
+use io_engine::{
+    core::{LogicalVolume, MayastorCliArgs, Share},
+    lvs::{BsError, Lvs, LvsError, LvsLvol},
+    pool_backend::{PoolArgs, PoolBackend},
+    subsys::NvmfSubsystem,
+};
+use std::pin::Pin;
+
+pub mod common;
+
+static DISKNAME: &str = "/tmp/replica-resize-disk.img";
+const MB: u64 = 1024 * 1024;
+
+#[tokio::test]
+async fn lvs_replica_resize() {
+    common::delete_file(&[DISKNAME.into()]);
+    common::truncate_file(DISKNAME, 64 * 1024);
+
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(PoolArgs {
+            name: "resize_pool".into(),
+            disks: vec![format!("aio://{DISKNAME}")],
+            uuid: None,
+            cluster_size: None,
+            reserved_pct: Some(0),
+            backend: PoolBackend::Lvs,
+        })
+        .await
+        .unwrap();
+
+        let mut thick = pool
+            .create_lvol("thick", 8 * MB, None, false, None)
+            .await
+            .unwrap();
+        let mut thin = pool
+            .create_lvol("thin", 8 * MB, None, true, None)
+            .await
+            .unwrap();
+        // takes capacity, so that growing past the free capacity stays
+        // within the capacity of the pool
+        pool.create_lvol("filler", 16 * MB, None, false, None)
+            .await
+            .unwrap();
+
+        // a shared replica grows online, and so does its namespace
+        Pin::new(&mut thick).share_nvmf(None).await.unwrap();
+        thick.resize_replica(16 * MB).await.unwrap();
+        assert_eq!(thick.size(), 16 * MB);
+        let subsystem = NvmfSubsystem::nqn_lookup(&thick.name()).unwrap();
+        assert_eq!(subsystem.bdev().unwrap().size_in_bytes(), 16 * MB);
+
+        // a thick replica may not grow past the free capacity of its pool,
+        // which a thin one may
+        let past_free = thick.size() + pool.available() + MB;
+        assert!(matches!(
+            thick.resize_replica(past_free).await,
+            Err(LvsError::RepResize {
+                source: BsError::NoSpace {},
+                ..
+            })
+        ));
+        assert_eq!(thick.size(), 16 * MB);
+        thin.resize_replica(pool.available() + MB).await.unwrap();
+
+        // no replica may grow past the capacity of its pool
+        assert!(matches!(
+            thin.resize_replica(pool.capacity() + MB).await,
+            Err(LvsError::RepResize {
+                source: BsError::CapacityOverflow {},
+                ..
+            })
+        ));
+
+        Pin::new(&mut thick).unshare().await.unwrap();
+        pool.destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISKNAME.into()]);
+}
@@ -0,0 +1,69 @@
+use common::MayastorTest;
+use io_engine::{
+    core::{MayastorCliArgs, Share, UntypedBdev},
+    lvs::{Lvol, Lvs, LvsLvol, PropValue},
+    pool_backend::{PoolArgs, PoolBackend},
+};
+use std::pin::Pin;
+
+pub mod common;
+
+static DISKNAME: &str = "/tmp/restore-priority-disk.img";
+const POOL_NAME: &str = "restore_prio_pool";
+const HIGH_UUID: &str = "3a43a4a5-5f2b-4a3d-9fd1-0a6b07d6d8a1";
+const LOW_UUID: &str = "3a43a4a5-5f2b-4a3d-9fd1-0a6b07d6d8a2";
+
+#[tokio::test]
+async fn lvs_restore_priority() {
+    common::delete_file(&[DISKNAME.into()]);
+    common::truncate_file(DISKNAME, 64 * 1024);
+    let disk = format!("aio://{DISKNAME}");
+
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async move {
+        let pool = Lvs::create_or_import(PoolArgs {
+            name: POOL_NAME.into(),
+            disks: vec![disk.clone()],
+            uuid: None,
+            cluster_size: None,
+            reserved_pct: None,
+            backend: PoolBackend::Lvs,
+        })
+        .await
+        .unwrap();
+
+        for (name, uuid, priority) in
+            [("high", HIGH_UUID, 9), ("low", LOW_UUID, 0)]
+        {
+            let mut lvol = pool
+                .create_lvol(name, 4 * 1024 * 1024, Some(uuid), true, None)
+                .await
+                .unwrap();
+            // replicas without a priority are restored last
+            assert_eq!(lvol.restore_priority().await, 0);
+            Pin::new(&mut lvol)
+                .set(PropValue::RestorePriority(priority))
+                .await
+                .unwrap();
+            assert_eq!(lvol.restore_priority().await, priority);
+            Pin::new(&mut lvol).share_nvmf(None).await.unwrap();
+        }
+
+        // the priorities are kept on disk, and the replicas shared again
+        // when the pool is imported
+        pool.export().await.unwrap();
+        let pool = Lvs::import(POOL_NAME, &disk).await.unwrap();
+        for (uuid, priority) in [(HIGH_UUID, 9), (LOW_UUID, 0)] {
+            let lvol = UntypedBdev::lookup_by_uuid_str(uuid)
+                .and_then(Lvol::ok_from)
+                .unwrap();
+            assert_eq!(lvol.restore_priority().await, priority);
+            assert!(lvol.shared().is_some());
+        }
+
+        pool.destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISKNAME.into()]);
+}
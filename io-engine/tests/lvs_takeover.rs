@@ -0,0 +1,76 @@
+use common::MayastorTest;
+use io_engine::{
+    core::{MayastorCliArgs, Share},
+    lvs::{Lvs, LvsError},
+    pool_backend::{PoolArgs, PoolBackend},
+};
+use std::pin::Pin;
+
+pub mod common;
+
+static DISKNAME: &str = "/tmp/takeover-disk.img";
+const POOL_NAME: &str = "takeover_pool";
+const REPLICA_UUID: &str = "5d2e3c1a-7b4f-4e0a-8c6d-2f1b9a0e4d71";
+
+fn pool_args(uuid: Option<String>) -> PoolArgs {
+    PoolArgs {
+        name: POOL_NAME.into(),
+        disks: vec![format!("aio://{DISKNAME}")],
+        uuid,
+        cluster_size: None,
+        reserved_pct: None,
+        backend: PoolBackend::Lvs,
+    }
+}
+
+#[tokio::test]
+async fn lvs_takeover() {
+    common::delete_file(&[DISKNAME.into()]);
+    common::truncate_file(DISKNAME, 64 * 1024);
+
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        let pool = Lvs::create_or_import(pool_args(None)).await.unwrap();
+        let uuid = pool.uuid();
+        let mut lvol = pool
+            .create_lvol(
+                "replica",
+                4 * 1024 * 1024,
+                Some(REPLICA_UUID),
+                true,
+                None,
+            )
+            .await
+            .unwrap();
+        Pin::new(&mut lvol).share_nvmf(None).await.unwrap();
+        let uri = lvol.share_uri().unwrap();
+
+        // the pool is left behind by its node
+        pool.export().await.unwrap();
+
+        // the node it is taken over from must be fenced, and the pool known
+        assert!(matches!(
+            Lvs::takeover(pool_args(Some(uuid.clone())), false).await,
+            Err(LvsError::Takeover { .. })
+        ));
+        assert!(matches!(
+            Lvs::takeover(pool_args(None), true).await,
+            Err(LvsError::Takeover { .. })
+        ));
+        assert!(Lvs::lookup(POOL_NAME).is_none());
+
+        // the replicas are shared again under the same NQNs
+        let takeover = Lvs::takeover(pool_args(Some(uuid.clone())), true)
+            .await
+            .unwrap();
+        assert_eq!(takeover.uuid, uuid);
+        assert_eq!(takeover.replicas.len(), 1);
+        assert_eq!(takeover.replicas[0].uuid, REPLICA_UUID);
+        assert_eq!(takeover.replicas[0].uri.as_deref(), Some(uri.as_str()));
+
+        Lvs::lookup(POOL_NAME).unwrap().destroy().await.unwrap();
+    })
+    .await;
+
+    common::delete_file(&[DISKNAME.into()]);
+}
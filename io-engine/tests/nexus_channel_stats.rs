@@ -0,0 +1,74 @@
+use std::{sync::atomic::Ordering, time::Duration};
+
+use io_engine::{
+    bdev::{
+        device_open,
+        nexus::{
+            nexus_create,
+            nexus_lookup,
+            nexus_lookup_mut,
+            NexusChannelIops,
+            ENABLE_IO_ALL_THRD_NX_CHAN,
+        },
+    },
+    core::{Cores, MayastorCliArgs},
+};
+
+pub mod common;
+use common::MayastorTest;
+
+const NEXUS_NAME: &str = "channel_stats_nexus";
+const BUF_SIZE: u64 = 4096;
+
+#[tokio::test]
+async fn nexus_channel_stats() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        // the channel of the test thread must be an I/O channel
+        ENABLE_IO_ALL_THRD_NX_CHAN.store(true, Ordering::SeqCst);
+
+        nexus_create(
+            NEXUS_NAME,
+            8 * 1024 * 1024,
+            None,
+            &[
+                "malloc:///chstats0?size_mb=16".to_string(),
+                "malloc:///chstats1?size_mb=16".to_string(),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let handle = device_open(NEXUS_NAME, true)
+            .unwrap()
+            .into_handle()
+            .unwrap();
+        let mut buf = handle.dma_malloc(BUF_SIZE).unwrap();
+        handle.write_at(0, &buf).await.unwrap();
+        handle.read_at(0, &mut buf).await.unwrap();
+
+        let stats = nexus_lookup(NEXUS_NAME).unwrap().channel_stats().await;
+        let core = stats.iter().find(|s| s.core == Cores::current()).unwrap();
+        assert_eq!(core.num_write_ops, 1);
+        assert_eq!(core.bytes_written, BUF_SIZE);
+        assert_eq!(core.num_read_ops, 1);
+        assert_eq!(core.bytes_read, BUF_SIZE);
+
+        // all the operations were submitted on the test core
+        let iops =
+            NexusChannelIops::from_samples(&[], &stats, Duration::from_secs(1));
+        let core = iops.iter().find(|s| s.core == Cores::current()).unwrap();
+        assert_eq!(core.write_iops, 1);
+        assert_eq!(core.read_iops, 1);
+        assert_eq!(core.share_pct, 100);
+
+        drop(handle);
+        nexus_lookup_mut(NEXUS_NAME)
+            .unwrap()
+            .destroy()
+            .await
+            .unwrap();
+        ENABLE_IO_ALL_THRD_NX_CHAN.store(false, Ordering::SeqCst);
+    })
+    .await;
+}
@@ -0,0 +1,72 @@
+use std::sync::atomic::Ordering;
+
+use io_engine::{
+    bdev::{
+        device_open,
+        nexus::{
+            nexus_create,
+            nexus_lookup,
+            nexus_lookup_mut,
+            ENABLE_IO_ALL_THRD_NX_CHAN,
+        },
+    },
+    core::MayastorCliArgs,
+};
+use spdk_rs::DmaBuf;
+
+pub mod common;
+use common::MayastorTest;
+
+const NEXUS_NAME: &str = "child_io_stats_nexus";
+
+#[tokio::test]
+async fn nexus_child_io_stats() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        // the channel of the test thread must be an I/O channel
+        ENABLE_IO_ALL_THRD_NX_CHAN.store(true, Ordering::SeqCst);
+
+        let children = [
+            "malloc:///iostats0?size_mb=16".to_string(),
+            "malloc:///iostats1?size_mb=16".to_string(),
+        ];
+        nexus_create(NEXUS_NAME, 8 * 1024 * 1024, None, &children)
+            .await
+            .unwrap();
+
+        let stats = nexus_lookup(NEXUS_NAME).unwrap().child_io_stats().await;
+        assert_eq!(stats.len(), 2);
+        assert!(stats.iter().all(|s| s.num_ops == 0 && s.in_flight == 0));
+
+        let handle = device_open(NEXUS_NAME, true)
+            .unwrap()
+            .into_handle()
+            .unwrap();
+        let mut buf =
+            DmaBuf::new(4096, handle.get_device().alignment()).unwrap();
+
+        // a write goes to all the children, a read to one of them
+        handle.write_at(0, &buf).await.unwrap();
+        handle.read_at(0, &mut buf).await.unwrap();
+
+        let stats = nexus_lookup(NEXUS_NAME).unwrap().child_io_stats().await;
+        for (child, s) in children.iter().zip(&stats) {
+            assert!(s.uri.starts_with(child.as_str()));
+            assert!(s.num_ops >= 1);
+            assert_eq!(s.in_flight, 0);
+            assert_eq!(s.num_errors, 0);
+            assert!(s.p50_latency_us <= s.p99_latency_us);
+            assert!(s.p99_latency_us <= s.max_latency_us);
+        }
+        assert!(stats.iter().map(|s| s.num_ops).sum::<u64>() >= 3);
+
+        drop(handle);
+        nexus_lookup_mut(NEXUS_NAME)
+            .unwrap()
+            .destroy()
+            .await
+            .unwrap();
+        ENABLE_IO_ALL_THRD_NX_CHAN.store(false, Ordering::SeqCst);
+    })
+    .await;
+}
@@ -0,0 +1,118 @@
+#![cfg(feature = "fault-injection")]
+
+use std::time::{Duration, Instant};
+
+use io_engine::{
+    bdev::{
+        device_open,
+        nexus::{
+            nexus_create,
+            nexus_lookup,
+            nexus_lookup_mut,
+            ChildState,
+            FaultReason,
+            NexusStatus,
+        },
+    },
+    core::{
+        fault_injection::{
+            add_fault_injection,
+            FaultDomain,
+            FaultIoOperation,
+            FaultIoStage,
+            FaultMethod,
+            InjectionBuilder,
+        },
+        MayastorCliArgs,
+    },
+    sleep::mayastor_sleep,
+};
+use spdk_rs::DmaBuf;
+
+pub mod common;
+use common::MayastorTest;
+
+const NEXUS_NAME: &str = "child_io_timeout_nexus";
+
+#[tokio::test]
+async fn nexus_child_io_timeout() {
+    let ms = MayastorTest::new(MayastorCliArgs {
+        enable_io_all_thrd_nexus_channels: true,
+        ..Default::default()
+    });
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            8 * 1024 * 1024,
+            None,
+            &[
+                "malloc:///iotimeout0?size_mb=16".to_string(),
+                "malloc:///iotimeout1?size_mb=16".to_string(),
+            ],
+        )
+        .await
+        .unwrap();
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        assert_eq!(nexus.child_io_timeout(), None);
+
+        assert!(nexus.set_child_io_timeout(Some(Duration::ZERO)).is_err());
+        nexus
+            .set_child_io_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+        assert_eq!(nexus.child_io_timeout(), Some(Duration::from_millis(100)));
+
+        // the reads of the first child hang past the timeout
+        let inj_device = nexus.child_at(0).get_device_name().unwrap();
+        add_fault_injection(
+            InjectionBuilder::default()
+                .with_domain(FaultDomain::BdevIo)
+                .with_device_name(inj_device)
+                .with_io_operation(FaultIoOperation::Read)
+                .with_io_stage(FaultIoStage::Submission)
+                .with_method(FaultMethod::Delay(Duration::from_secs(2)))
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+
+        let handle = device_open(NEXUS_NAME, false)
+            .unwrap()
+            .into_handle()
+            .unwrap();
+        let mut buf =
+            DmaBuf::new(4096, handle.get_device().alignment()).unwrap();
+
+        // the reads go to each child in turn, and still succeed: the read
+        // of the hung child is retried on the other one once timed out,
+        // rather than waiting for the hung child
+        let start = Instant::now();
+        for _ in 0 .. 2 {
+            handle.read_at(0, &mut buf).await.unwrap();
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed < Duration::from_secs(1), "reads took {elapsed:?}");
+
+        // the hung child is taken out of the I/O path
+        assert_eq!(
+            nexus.child_at(0).state(),
+            ChildState::Faulted(FaultReason::IoTimeout)
+        );
+        assert!(nexus.child_at(1).is_healthy());
+        assert_eq!(nexus.status(), NexusStatus::Degraded);
+
+        // the late completion of the hung child I/O is dropped, before the
+        // device is destroyed along with the nexus, and the hung read does
+        // not write into the buffer once the nexus read completed
+        buf.fill(0xa5);
+        mayastor_sleep(Duration::from_secs(2)).await.unwrap();
+        assert!(buf.as_slice().iter().all(|b| *b == 0xa5));
+
+        drop(handle);
+        nexus_lookup_mut(NEXUS_NAME)
+            .unwrap()
+            .destroy()
+            .await
+            .unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,87 @@
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup, nexus_lookup_mut},
+    core::MayastorCliArgs,
+};
+
+pub mod common;
+use common::MayastorTest;
+
+#[tokio::test]
+async fn nexus_detail_capabilities() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        let children = vec![
+            "malloc:///detail0?size_mb=64".to_string(),
+            "malloc:///detail1?size_mb=64".to_string(),
+        ];
+        nexus_create("detail_nexus", 32 * 1024 * 1024, None, &children)
+            .await
+            .unwrap();
+
+        let detail = nexus_lookup("detail_nexus").unwrap().detail();
+        assert_eq!(detail.children.len(), 2);
+        for child in &detail.children {
+            assert_eq!(child.transport.as_deref(), Some("malloc"));
+            let caps = child.capabilities.as_ref().unwrap();
+            assert!(caps.write_zeroes);
+            assert!(caps.unmap);
+            assert_eq!(caps.pi_type, 0);
+        }
+        assert!(detail.capabilities.write_zeroes);
+        assert!(detail.capabilities.unmap);
+        assert_eq!(detail.capabilities.pi_type, 0);
+        assert_eq!(detail.capabilities.max_transfer_size, None);
+        assert!(detail.downgrades.is_empty());
+
+        nexus_lookup_mut("detail_nexus")
+            .unwrap()
+            .destroy()
+            .await
+            .unwrap();
+    })
+    .await;
+
+    // the effective capabilities are those supported by all the children
+    ms.spawn(async {
+        let children = vec![
+            "malloc:///detail2?size_mb=64".to_string(),
+            "null:///detail3?size_mb=64".to_string(),
+        ];
+        nexus_create("detail_mixed", 32 * 1024 * 1024, None, &children)
+            .await
+            .unwrap();
+
+        let detail = nexus_lookup("detail_mixed").unwrap().detail();
+        let transports = detail
+            .children
+            .iter()
+            .map(|c| c.transport.clone().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(transports, ["malloc", "null"]);
+        let caps = detail
+            .children
+            .iter()
+            .map(|c| c.capabilities.clone().unwrap())
+            .collect::<Vec<_>>();
+
+        // the null child does not support unmap, which downgrades the nexus
+        assert!(caps[0].unmap);
+        assert!(!caps[1].unmap);
+        assert!(!detail.capabilities.unmap);
+        assert_eq!(
+            detail.downgrades,
+            vec![format!("unmap of {} not advertised", children[0])]
+        );
+        assert_eq!(
+            detail.capabilities.write_zeroes,
+            caps.iter().all(|c| c.write_zeroes)
+        );
+
+        nexus_lookup_mut("detail_mixed")
+            .unwrap()
+            .destroy()
+            .await
+            .unwrap();
+    })
+    .await;
+}
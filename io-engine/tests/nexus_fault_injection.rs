@@ -408,6 +408,37 @@ async fn injection_uri_creation() {
     assert_eq!(src.retries, res.retries);
 }
 
+#[tokio::test]
+async fn injection_uri_delay_drop() {
+    let delay = InjectionBuilder::default()
+        .with_domain(FaultDomain::BdevIo)
+        .with_device_name("dev0".to_string())
+        .with_method(FaultMethod::Delay(Duration::from_millis(250)))
+        .with_io_operation(FaultIoOperation::Write)
+        .build()
+        .unwrap();
+
+    let res = Injection::from_uri(&delay.as_uri()).unwrap();
+    assert_eq!(res.method, FaultMethod::Delay(Duration::from_millis(250)));
+    assert_eq!(res.io_operation, FaultIoOperation::Write);
+
+    let drop = InjectionBuilder::default()
+        .with_domain(FaultDomain::BdevIo)
+        .with_device_name("dev0".to_string())
+        .with_method(FaultMethod::Drop)
+        .with_io_operation(FaultIoOperation::Flush)
+        .build()
+        .unwrap();
+
+    let res = Injection::from_uri(&drop.as_uri()).unwrap();
+    assert_eq!(res.method, FaultMethod::Drop);
+    assert_eq!(res.io_operation, FaultIoOperation::Flush);
+    assert_eq!(
+        res.uri(),
+        "inject://dev0?domain=bdev_io&op=flush&stage=submit&method=drop"
+    );
+}
+
 #[tokio::test]
 async fn replica_bdev_io_injection() {
     common::composer_init();
@@ -0,0 +1,111 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use io_engine::{
+    bdev::{
+        device_open,
+        nexus::{
+            nexus_create,
+            nexus_lookup,
+            nexus_lookup_mut,
+            ChildSyncState,
+            ENABLE_IO_ALL_THRD_NX_CHAN,
+        },
+    },
+    core::{
+        BlockDevice,
+        BlockDeviceHandle,
+        IoCompletionStatus,
+        MayastorCliArgs,
+    },
+    sleep::mayastor_sleep,
+};
+use libc::c_void;
+
+pub mod common;
+use common::MayastorTest;
+
+const NEXUS_NAME: &str = "flush_stats_nexus";
+const CHILD2: &str = "malloc:///flush2?size_mb=16";
+
+static FLUSHED: AtomicBool = AtomicBool::new(false);
+
+fn flush_completion(
+    _device: &dyn BlockDevice,
+    status: IoCompletionStatus,
+    _ctx: *mut c_void,
+) {
+    assert_eq!(status, IoCompletionStatus::Success);
+    FLUSHED.store(true, Ordering::SeqCst);
+}
+
+/// Flushes the nexus and waits for the flush to complete.
+async fn flush(handle: &dyn BlockDeviceHandle) {
+    FLUSHED.store(false, Ordering::SeqCst);
+    handle
+        .flush_io(flush_completion, std::ptr::null_mut())
+        .unwrap();
+    mayastor_sleep(Duration::from_millis(500)).await.unwrap();
+    assert!(FLUSHED.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn nexus_flush_stats() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        // the channel of the test thread must be an I/O channel
+        ENABLE_IO_ALL_THRD_NX_CHAN.store(true, Ordering::SeqCst);
+
+        nexus_create(
+            NEXUS_NAME,
+            8 * 1024 * 1024,
+            None,
+            &[
+                "malloc:///flush0?size_mb=16".to_string(),
+                "malloc:///flush1?size_mb=16".to_string(),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        assert!(!nexus.flush_verify());
+        nexus.set_flush_verify(true);
+
+        let handle = device_open(NEXUS_NAME, true)
+            .unwrap()
+            .into_handle()
+            .unwrap();
+        flush(&*handle).await;
+
+        // the flush reached both children
+        let stats = nexus_lookup(NEXUS_NAME).unwrap().flush_stats().await;
+        assert!(stats.verify);
+        assert_eq!(stats.num_flush_ops, 1);
+        assert_eq!(stats.num_flush_violations, 0);
+
+        // a child which turns healthy without being given to the channel as
+        // a writer is missed by the next flush
+        let mut nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        nexus.as_mut().add_child(CHILD2, true).await.unwrap();
+        let child = nexus.child(CHILD2).unwrap();
+        assert!(child.is_opened_unsync());
+        child.set_sync_state(ChildSyncState::Synced);
+        flush(&*handle).await;
+
+        let stats = nexus_lookup(NEXUS_NAME).unwrap().flush_stats().await;
+        assert_eq!(stats.num_flush_ops, 2);
+        assert_eq!(stats.num_flush_violations, 1);
+
+        drop(handle);
+        nexus_lookup_mut(NEXUS_NAME)
+            .unwrap()
+            .destroy()
+            .await
+            .unwrap();
+        ENABLE_IO_ALL_THRD_NX_CHAN.store(false, Ordering::SeqCst);
+    })
+    .await;
+}
@@ -0,0 +1,149 @@
+pub mod common;
+
+use common::{
+    compose::{
+        network::{
+            clear_netem,
+            container_ips,
+            heal,
+            partition,
+            set_netem,
+            BuilderNetworkExt,
+            Netem,
+            NetworkedTest,
+        },
+        rpc::v1::{nexus::ChildState, GrpcConnect},
+        Binary,
+        Builder,
+    },
+    file_io::DataSize,
+    nexus::{test_write_to_nexus, NexusBuilder},
+    pool::PoolBuilder,
+    replica::ReplicaBuilder,
+};
+
+use std::time::{Duration, Instant};
+
+const POOL_SIZE: u64 = 60;
+const REPL_SIZE: u64 = 50;
+
+/// Creates a compose test where the nexus container and the first replica
+/// container are also attached to an additional network.
+async fn create_compose_test() -> NetworkedTest {
+    common::composer_init();
+
+    Builder::new()
+        .name("cargo-test")
+        .network("10.1.0.0/16")
+        .unwrap()
+        .add_container_bin(
+            "ms_0",
+            Binary::from_dbg("io-engine").with_args(vec!["-l", "1"]),
+        )
+        .add_container_bin(
+            "ms_1",
+            Binary::from_dbg("io-engine").with_args(vec!["-l", "2"]),
+        )
+        .add_container_bin(
+            "ms_nex",
+            Binary::from_dbg("io-engine").with_args(vec!["-l", "3"]),
+        )
+        .with_clean(true)
+        .add_network("cargo-test-storage", "10.2.0.0/16", &["ms_nex"])
+        .with_ip("ms_0", "10.2.0.10")
+        .build()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn nexus_partition() {
+    let test = create_compose_test().await;
+    assert!(test.network("cargo-test-storage").is_some());
+    assert!(container_ips("ms_0")
+        .unwrap()
+        .contains(&"10.2.0.10".to_string()));
+    assert_eq!(container_ips("ms_nex").unwrap().len(), 2);
+
+    let conn = GrpcConnect::new(&test);
+    let ms_0 = conn.grpc_handle_shared("ms_0").await.unwrap();
+    let ms_1 = conn.grpc_handle_shared("ms_1").await.unwrap();
+    let ms_nex = conn.grpc_handle_shared("ms_nex").await.unwrap();
+
+    let mut pool_0 = PoolBuilder::new(ms_0.clone())
+        .with_name("pool0")
+        .with_new_uuid()
+        .with_malloc("mem0", POOL_SIZE);
+    let mut repl_0 = ReplicaBuilder::new(ms_0.clone())
+        .with_pool(&pool_0)
+        .with_name("r0")
+        .with_new_uuid()
+        .with_size_mb(REPL_SIZE)
+        .with_thin(false);
+    pool_0.create().await.unwrap();
+    repl_0.create().await.unwrap();
+    repl_0.share().await.unwrap();
+
+    let mut pool_1 = PoolBuilder::new(ms_1.clone())
+        .with_name("pool1")
+        .with_new_uuid()
+        .with_malloc("mem0", POOL_SIZE);
+    let mut repl_1 = ReplicaBuilder::new(ms_1.clone())
+        .with_pool(&pool_1)
+        .with_name("r1")
+        .with_new_uuid()
+        .with_size_mb(REPL_SIZE)
+        .with_thin(false);
+    pool_1.create().await.unwrap();
+    repl_1.create().await.unwrap();
+    repl_1.share().await.unwrap();
+
+    let mut nex_0 = NexusBuilder::new(ms_nex)
+        .with_name("nexus0")
+        .with_new_uuid()
+        .with_size_mb(REPL_SIZE)
+        .with_replica(&repl_0)
+        .with_replica(&repl_1);
+    nex_0.create().await.unwrap();
+    nex_0.publish().await.unwrap();
+
+    // the replies of the first replica are delayed
+    set_netem("ms_0", "eth0", &Netem::delay(Duration::from_millis(200)))
+        .unwrap();
+    let start = Instant::now();
+    test_write_to_nexus(
+        &nex_0,
+        DataSize::from_bytes(0),
+        1,
+        DataSize::from_kb(4),
+    )
+    .await
+    .unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(200));
+    clear_netem("ms_0", "eth0").unwrap();
+
+    // the nexus loses the second replica, but keeps serving I/O from the
+    // first one
+    partition("ms_nex", "ms_1").unwrap();
+    test_write_to_nexus(
+        &nex_0,
+        DataSize::from_bytes(0),
+        1,
+        DataSize::from_kb(4),
+    )
+    .await
+    .unwrap();
+    nex_0
+        .wait_replica_state(
+            &repl_1,
+            ChildState::Faulted,
+            None,
+            Duration::from_secs(30),
+        )
+        .await
+        .unwrap();
+    let child = nex_0.get_nexus_replica_child(&repl_0).await.unwrap();
+    assert_eq!(child.state, ChildState::Online as i32);
+
+    heal("ms_nex", "ms_1").unwrap();
+}
@@ -0,0 +1,133 @@
+use io_engine::{
+    bdev::{
+        device_open,
+        nexus::{
+            nexus_create,
+            nexus_create_v2,
+            nexus_lookup,
+            nexus_lookup_mut,
+            NexusNvmeParams,
+            NexusReadPolicy,
+        },
+    },
+    core::MayastorCliArgs,
+};
+use once_cell::sync::OnceCell;
+use spdk_rs::DmaBuf;
+
+pub mod common;
+use common::MayastorTest;
+
+static MS: OnceCell<MayastorTest> = OnceCell::new();
+
+fn mayastor() -> &'static MayastorTest<'static> {
+    // the channel of the test thread must be an I/O channel
+    MS.get_or_init(|| {
+        MayastorTest::new(MayastorCliArgs {
+            enable_io_all_thrd_nexus_channels: true,
+            ..Default::default()
+        })
+    })
+}
+
+async fn child_ops(name: &str) -> Vec<u64> {
+    nexus_lookup(name)
+        .unwrap()
+        .child_io_stats()
+        .await
+        .iter()
+        .map(|s| s.num_ops)
+        .collect()
+}
+
+#[tokio::test]
+async fn nexus_read_policy() {
+    const NEXUS_NAME: &str = "read_policy_nexus";
+
+    mayastor()
+        .spawn(async {
+            nexus_create(
+                NEXUS_NAME,
+                8 * 1024 * 1024,
+                None,
+                &[
+                    "malloc:///readpolicy0?size_mb=16".to_string(),
+                    "malloc:///readpolicy1?size_mb=16".to_string(),
+                ],
+            )
+            .await
+            .unwrap();
+            let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+            assert_eq!(nexus.read_policy(), NexusReadPolicy::RoundRobin);
+
+            let handle = device_open(NEXUS_NAME, true)
+                .unwrap()
+                .into_handle()
+                .unwrap();
+            let mut buf =
+                DmaBuf::new(4096, handle.get_device().alignment()).unwrap();
+            handle.write_at(0, &buf).await.unwrap();
+
+            // the reads go to each child in turn
+            for _ in 0 .. 4 {
+                handle.read_at(0, &mut buf).await.unwrap();
+            }
+            assert_eq!(child_ops(NEXUS_NAME).await, vec![3, 3]);
+
+            // both children are local, so they are still read in turn
+            nexus.set_read_policy(NexusReadPolicy::LocalityPreferred);
+            for _ in 0 .. 4 {
+                handle.read_at(0, &mut buf).await.unwrap();
+            }
+            assert_eq!(child_ops(NEXUS_NAME).await, vec![5, 5]);
+
+            // the reads go to the fastest child, whichever it is
+            nexus.set_read_policy(NexusReadPolicy::Adaptive);
+            for _ in 0 .. 8 {
+                handle.read_at(0, &mut buf).await.unwrap();
+            }
+            assert_eq!(child_ops(NEXUS_NAME).await.iter().sum::<u64>(), 18);
+
+            drop(handle);
+            nexus_lookup_mut(NEXUS_NAME)
+                .unwrap()
+                .destroy()
+                .await
+                .unwrap();
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn nexus_read_policy_create() {
+    const NEXUS_NAME: &str = "read_policy_create_nexus";
+
+    mayastor()
+        .spawn(async {
+            let mut params = NexusNvmeParams::default();
+            params.set_read_policy(Some(NexusReadPolicy::LocalityPreferred));
+            nexus_create_v2(
+                NEXUS_NAME,
+                8 * 1024 * 1024,
+                "3f8a2c71-5d4e-4b9a-9e1f-6c7d8e9f0a1b",
+                params,
+                &["malloc:///readpolicycreate0?size_mb=16".to_string()],
+                None,
+            )
+            .await
+            .unwrap();
+
+            // the policy given at creation is kept until changed
+            let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+            assert_eq!(nexus.read_policy(), NexusReadPolicy::LocalityPreferred);
+            nexus.set_read_policy(NexusReadPolicy::RoundRobin);
+            assert_eq!(nexus.read_policy(), NexusReadPolicy::RoundRobin);
+
+            nexus_lookup_mut(NEXUS_NAME)
+                .unwrap()
+                .destroy()
+                .await
+                .unwrap();
+        })
+        .await;
+}
@@ -0,0 +1,106 @@
+#![cfg(feature = "fault-injection")]
+
+use std::time::Duration;
+
+use io_engine::{
+    bdev::{
+        device_open,
+        nexus::{
+            nexus_create,
+            nexus_lookup,
+            nexus_lookup_mut,
+            NexusReadPolicy,
+        },
+    },
+    core::{
+        fault_injection::{
+            add_fault_injection,
+            FaultDomain,
+            FaultIoOperation,
+            FaultIoStage,
+            FaultMethod,
+            InjectionBuilder,
+        },
+        MayastorCliArgs,
+    },
+};
+use spdk_rs::DmaBuf;
+
+pub mod common;
+use common::MayastorTest;
+
+const NEXUS_NAME: &str = "read_policy_adaptive_nexus";
+
+async fn child_ops() -> Vec<u64> {
+    nexus_lookup(NEXUS_NAME)
+        .unwrap()
+        .child_io_stats()
+        .await
+        .iter()
+        .map(|s| s.num_ops)
+        .collect()
+}
+
+#[tokio::test]
+async fn nexus_read_policy_adaptive() {
+    let ms = MayastorTest::new(MayastorCliArgs {
+        enable_io_all_thrd_nexus_channels: true,
+        ..Default::default()
+    });
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            8 * 1024 * 1024,
+            None,
+            &[
+                "malloc:///readadaptive0?size_mb=16".to_string(),
+                "malloc:///readadaptive1?size_mb=16".to_string(),
+            ],
+        )
+        .await
+        .unwrap();
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        nexus.set_read_policy(NexusReadPolicy::Adaptive);
+
+        // the reads of the first child are slow
+        let inj_device = nexus.child_at(0).get_device_name().unwrap();
+        add_fault_injection(
+            InjectionBuilder::default()
+                .with_domain(FaultDomain::BdevIo)
+                .with_device_name(inj_device)
+                .with_io_operation(FaultIoOperation::Read)
+                .with_io_stage(FaultIoStage::Submission)
+                .with_method(FaultMethod::Delay(Duration::from_millis(20)))
+                .build()
+                .unwrap(),
+        )
+        .unwrap();
+
+        let handle = device_open(NEXUS_NAME, false)
+            .unwrap()
+            .into_handle()
+            .unwrap();
+        let mut buf =
+            DmaBuf::new(4096, handle.get_device().alignment()).unwrap();
+
+        // each child is read from until its latency is measured, the reads
+        // then go to the faster one, short of the probes of the slower one
+        let before = child_ops().await;
+        for _ in 0 .. 32 {
+            handle.read_at(0, &mut buf).await.unwrap();
+        }
+        let after = child_ops().await;
+        let slow = after[0] - before[0];
+        let fast = after[1] - before[1];
+        assert_eq!(slow + fast, 32);
+        assert!(slow <= 2, "{slow} reads went to the slow child");
+
+        drop(handle);
+        nexus_lookup_mut(NEXUS_NAME)
+            .unwrap()
+            .destroy()
+            .await
+            .unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,112 @@
+use io_engine::{
+    bdev::nexus::{
+        nexus_create,
+        nexus_lookup,
+        nexus_lookup_mut,
+        nexus_repair_takeover,
+        FaultReason,
+    },
+    bdev_api::bdev_create,
+    core::{MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{NvmfListener, NvmfTransport},
+};
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+const NEXUS_NAME: &str = "takeover_nexus";
+const NQN0: &str = "nqn.2019-05.io.openebs:takeover0";
+const NQN1: &str = "nqn.2019-05.io.openebs:takeover1";
+
+/// Shares the replica of the given name under the NQN, on the given port.
+async fn share(name: &str, nqn: &str, port: u16) {
+    let mut bdev = UntypedBdev::lookup_by_name(name).unwrap();
+    let props = NvmfShareProps::new()
+        .with_nqn(Some(nqn.to_string()))
+        .with_listeners(vec![NvmfListener {
+            transport: NvmfTransport::Tcp,
+            address: Some("127.0.0.1".to_string()),
+            port: Some(port),
+        }]);
+    Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+}
+
+async fn unshare(name: &str) {
+    let mut bdev = UntypedBdev::lookup_by_name(name).unwrap();
+    Pin::new(&mut bdev).unshare().await.unwrap();
+}
+
+#[tokio::test]
+async fn nexus_repair_takeover_moves_unhealthy_children() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        for (name, nqn) in [("takeover0", NQN0), ("takeover1", NQN1)] {
+            bdev_create(&format!("malloc:///{name}?size_mb=16"))
+                .await
+                .unwrap();
+            share(name, nqn, 8469).await;
+        }
+
+        let src0 = format!("nvmf://127.0.0.1:8469/{NQN0}");
+        let src1 = format!("nvmf://127.0.0.1:8469/{NQN1}");
+        nexus_create(
+            NEXUS_NAME,
+            8 * 1024 * 1024,
+            None,
+            &[
+                "malloc:///takeover_local?size_mb=16".to_string(),
+                src0.clone(),
+                src1.clone(),
+            ],
+        )
+        .await
+        .unwrap();
+
+        // the node of the first replica fails, and the pool is taken over
+        // by a node sharing it at another address
+        nexus_lookup_mut(NEXUS_NAME)
+            .unwrap()
+            .fault_child(&src0, FaultReason::OfflinePermanent)
+            .await
+            .unwrap();
+        unshare("takeover0").await;
+        share("takeover0", NQN0, 8470).await;
+
+        // only the child which is not healthy is moved, to the URI of its
+        // NQN; the healthy child is left alone
+        let dst0 = format!("nvmf://127.0.0.1:8470/{NQN0}");
+        let dst1 = format!("nvmf://127.0.0.1:8470/{NQN1}");
+        let other = "nvmf://127.0.0.1:8470/nqn.2019-05.io.openebs:other";
+        let retargeted = nexus_repair_takeover(&[
+            dst0.clone(),
+            dst1.clone(),
+            other.to_string(),
+        ])
+        .await;
+        assert_eq!(retargeted.len(), 1);
+        assert_eq!(retargeted[0].nexus, NEXUS_NAME);
+        assert_eq!(retargeted[0].src_uri, src0);
+        assert_eq!(retargeted[0].dst_uri, dst0);
+        assert_eq!(retargeted[0].error, None);
+
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        assert!(nexus.lookup_child(&src0).is_none());
+        assert!(nexus.lookup_child(&dst0).is_some());
+        assert!(nexus.lookup_child(&src1).is_some_and(|c| c.is_healthy()));
+        assert!(nexus.lookup_child(&dst1).is_none());
+
+        // the nexus is repaired already
+        assert!(nexus_repair_takeover(&[dst0]).await.is_empty());
+
+        nexus_lookup_mut(NEXUS_NAME)
+            .unwrap()
+            .destroy()
+            .await
+            .unwrap();
+        for name in ["takeover0", "takeover1"] {
+            unshare(name).await;
+        }
+    })
+    .await;
+}
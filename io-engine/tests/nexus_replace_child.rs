@@ -0,0 +1,56 @@
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup_mut, ReplacementState},
+    core::MayastorCliArgs,
+    sleep::mayastor_sleep,
+};
+use std::time::Duration;
+
+pub mod common;
+use common::MayastorTest;
+
+const NEXUS_NAME: &str = "replace_nexus";
+const SRC_URI: &str = "malloc:///replace_src?size_mb=64";
+const KEEP_URI: &str = "malloc:///replace_keep?size_mb=64";
+const DST_URI: &str = "malloc:///replace_dst?size_mb=64";
+
+#[tokio::test]
+async fn nexus_replace_child() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        let children = vec![SRC_URI.to_string(), KEEP_URI.to_string()];
+        nexus_create(NEXUS_NAME, 32 * 1024 * 1024, None, &children)
+            .await
+            .unwrap();
+        let nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+
+        // the new child must not be a child already
+        assert!(nexus.replace_child(SRC_URI, KEEP_URI).await.is_err());
+
+        let nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        nexus.replace_child(SRC_URI, DST_URI).await.unwrap();
+
+        // the replaced child is removed once the new one is rebuilt
+        let mut state = ReplacementState::Rebuilding;
+        for _ in 0 .. 100 {
+            let nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+            let replacements = nexus.child_replacements().await;
+            assert_eq!(replacements.len(), 1);
+            state = replacements[0].state;
+            if state != ReplacementState::Rebuilding
+                && !nexus.contains_child_uri(SRC_URI)
+            {
+                break;
+            }
+            mayastor_sleep(Duration::from_millis(100)).await.unwrap();
+        }
+        assert_eq!(state, ReplacementState::Completed);
+
+        let nexus = nexus_lookup_mut(NEXUS_NAME).unwrap();
+        assert!(!nexus.contains_child_uri(SRC_URI));
+        assert!(nexus.contains_child_uri(DST_URI));
+        assert!(nexus.contains_child_uri(KEEP_URI));
+
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
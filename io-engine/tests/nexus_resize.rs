@@ -0,0 +1,72 @@
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup, nexus_lookup_mut, Error},
+    core::{MayastorCliArgs, Share},
+    subsys::{NvmfSubsystem, SubsystemHandle},
+};
+
+pub mod common;
+use common::MayastorTest;
+
+const NEXUS_NAME: &str = "resize_nexus";
+const MB: u64 = 1024 * 1024;
+
+#[tokio::test]
+async fn nexus_resize() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        nexus_create(
+            NEXUS_NAME,
+            8 * MB,
+            None,
+            &[
+                "malloc:///resize0?size_mb=32".to_string(),
+                "malloc:///resize1?size_mb=32".to_string(),
+            ],
+        )
+        .await
+        .unwrap();
+        nexus_lookup_mut(NEXUS_NAME)
+            .unwrap()
+            .share_nvmf(None)
+            .await
+            .unwrap();
+
+        // the nexus grows within the size of its children
+        nexus_lookup_mut(NEXUS_NAME)
+            .unwrap()
+            .resize(16 * MB)
+            .await
+            .unwrap();
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        assert_eq!(nexus.req_size(), 16 * MB);
+        assert_eq!(nexus.size_in_bytes(), 16 * MB);
+
+        // the subsystem is resumed once the nexus is resized
+        assert!(matches!(
+            NvmfSubsystem::nqn_lookup(NEXUS_NAME).unwrap().into_handle(),
+            Ok(SubsystemHandle::Active(_))
+        ));
+
+        assert!(matches!(
+            nexus_lookup_mut(NEXUS_NAME).unwrap().resize(12 * MB).await,
+            Err(Error::InvalidResize { .. })
+        ));
+        assert!(matches!(
+            nexus_lookup_mut(NEXUS_NAME).unwrap().resize(64 * MB).await,
+            Err(Error::ChildTooSmall { .. })
+        ));
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        assert_eq!(nexus.req_size(), 16 * MB);
+        assert!(matches!(
+            NvmfSubsystem::nqn_lookup(NEXUS_NAME).unwrap().into_handle(),
+            Ok(SubsystemHandle::Active(_))
+        ));
+
+        nexus_lookup_mut(NEXUS_NAME)
+            .unwrap()
+            .destroy()
+            .await
+            .unwrap();
+    })
+    .await;
+}
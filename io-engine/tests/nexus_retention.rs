@@ -0,0 +1,118 @@
+use std::{
+    sync::atomic::Ordering,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures::future::join;
+use io_engine::{
+    bdev::{
+        device_open,
+        nexus::{
+            nexus_create,
+            nexus_lookup,
+            nexus_lookup_mut,
+            Error,
+            RetentionMode,
+            ENABLE_IO_ALL_THRD_NX_CHAN,
+        },
+    },
+    core::MayastorCliArgs,
+};
+use spdk_rs::DmaBuf;
+
+pub mod common;
+use common::MayastorTest;
+
+const NEXUS_NAME: &str = "retention_nexus";
+
+#[tokio::test]
+async fn nexus_retention() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        // the channel of the test thread must be an I/O channel
+        ENABLE_IO_ALL_THRD_NX_CHAN.store(true, Ordering::SeqCst);
+
+        nexus_create(
+            NEXUS_NAME,
+            8 * 1024 * 1024,
+            None,
+            &[
+                "malloc:///retention0?size_mb=16".to_string(),
+                "malloc:///retention1?size_mb=16".to_string(),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        assert!(nexus.retention().is_none());
+        assert!(matches!(
+            nexus
+                .set_retention(RetentionMode::AppendOnly, now - 1, 0)
+                .await,
+            Err(Error::InvalidRetention { .. })
+        ));
+        nexus
+            .set_retention(RetentionMode::AppendOnly, now + 3600, 0)
+            .await
+            .unwrap();
+
+        let handle = device_open(NEXUS_NAME, true)
+            .unwrap()
+            .into_handle()
+            .unwrap();
+        let buf = DmaBuf::new(4096, handle.get_device().alignment()).unwrap();
+
+        // the data may be appended, but not overwritten
+        handle.write_at(0, &buf).await.unwrap();
+        handle.write_at(4096, &buf).await.unwrap();
+        assert!(handle.write_at(0, &buf).await.is_err());
+        let block_len = handle.get_device().block_len();
+        let retention = nexus_lookup(NEXUS_NAME).unwrap().retention().unwrap();
+        assert_eq!(retention.append_offset, 8192 / block_len);
+
+        // the writes in flight together are appended in any order, and the
+        // end of the appended data only moves as they complete
+        let (last, first) =
+            join(handle.write_at(12288, &buf), handle.write_at(8192, &buf))
+                .await;
+        last.unwrap();
+        first.unwrap();
+        let retention = nexus_lookup(NEXUS_NAME).unwrap().retention().unwrap();
+        assert_eq!(retention.append_offset, 16384 / block_len);
+        assert!(handle.write_at(8192, &buf).await.is_err());
+
+        // the retention may only be made stricter or longer
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        assert!(matches!(
+            nexus
+                .set_retention(RetentionMode::AppendOnly, now + 60, 0)
+                .await,
+            Err(Error::RetentionInForce { .. })
+        ));
+        nexus
+            .set_retention(RetentionMode::ReadOnly, now + 3600, 0)
+            .await
+            .unwrap();
+        assert!(matches!(
+            nexus
+                .set_retention(RetentionMode::AppendOnly, now + 7200, 0)
+                .await,
+            Err(Error::RetentionInForce { .. })
+        ));
+        assert!(handle.write_at(16384, &buf).await.is_err());
+
+        drop(handle);
+        nexus_lookup_mut(NEXUS_NAME)
+            .unwrap()
+            .destroy()
+            .await
+            .unwrap();
+        ENABLE_IO_ALL_THRD_NX_CHAN.store(false, Ordering::SeqCst);
+    })
+    .await;
+}
@@ -0,0 +1,206 @@
+#![cfg(feature = "fault-injection")]
+
+use std::time::{Duration, Instant};
+
+use io_engine::{
+    bdev::{
+        device_open,
+        nexus::{
+            nexus_create,
+            nexus_lookup,
+            nexus_lookup_mut,
+            NexusWritePolicy,
+        },
+    },
+    core::{
+        fault_injection::{
+            add_fault_injection,
+            remove_fault_injection,
+            FaultDomain,
+            FaultIoOperation,
+            FaultIoStage,
+            FaultMethod,
+            Injection,
+            InjectionBuilder,
+        },
+        MayastorCliArgs,
+    },
+    sleep::mayastor_sleep,
+};
+use once_cell::sync::OnceCell;
+use spdk_rs::DmaBuf;
+
+pub mod common;
+use common::MayastorTest;
+
+/// Delay of the writes of the lagging child.
+const DELAY: Duration = Duration::from_millis(200);
+
+static MS: OnceCell<MayastorTest> = OnceCell::new();
+
+fn mayastor() -> &'static MayastorTest<'static> {
+    // the channel of the test thread must be an I/O channel
+    MS.get_or_init(|| {
+        MayastorTest::new(MayastorCliArgs {
+            enable_io_all_thrd_nexus_channels: true,
+            ..Default::default()
+        })
+    })
+}
+
+/// Creates a nexus of three children acknowledging the writes once two of
+/// them completed them, the writes of the last child being delayed.
+async fn create_nexus(name: &str) -> Injection {
+    nexus_create(
+        name,
+        8 * 1024 * 1024,
+        None,
+        &(0 .. 3)
+            .map(|i| format!("malloc:///{name}{i}?size_mb=16"))
+            .collect::<Vec<_>>(),
+    )
+    .await
+    .unwrap();
+    let nexus = nexus_lookup(name).unwrap();
+    nexus.set_write_policy(NexusWritePolicy::Quorum(2)).unwrap();
+
+    let delay = InjectionBuilder::default()
+        .with_domain(FaultDomain::BdevIo)
+        .with_device_name(format!("{name}2"))
+        .with_io_operation(FaultIoOperation::Write)
+        .with_io_stage(FaultIoStage::Submission)
+        .with_method(FaultMethod::Delay(DELAY))
+        .build()
+        .unwrap();
+    add_fault_injection(delay.clone()).unwrap();
+    delay
+}
+
+/// Reads the first block of the device.
+async fn read_block(name: &str) -> DmaBuf {
+    let handle = device_open(name, false).unwrap().into_handle().unwrap();
+    let mut buf = DmaBuf::new(4096, handle.get_device().alignment()).unwrap();
+    handle.read_at(0, &mut buf).await.unwrap();
+    buf
+}
+
+#[tokio::test]
+async fn nexus_write_laggard_slow() {
+    const NEXUS_NAME: &str = "laggard_slow";
+
+    mayastor()
+        .spawn(async {
+            let delay = create_nexus(NEXUS_NAME).await;
+            let handle = device_open(NEXUS_NAME, true)
+                .unwrap()
+                .into_handle()
+                .unwrap();
+            let alignment = handle.get_device().alignment();
+            let mut first = DmaBuf::new(4096, alignment).unwrap();
+            first.fill(0xa5);
+            let mut second = DmaBuf::new(4096, alignment).unwrap();
+            second.fill(0x5a);
+
+            // the write is acknowledged without the slow child, which is not
+            // read from until it completed it
+            let start = Instant::now();
+            handle.write_at(0, &first).await.unwrap();
+            assert!(start.elapsed() < DELAY);
+            for _ in 0 .. 6 {
+                let mut read = DmaBuf::new(4096, alignment).unwrap();
+                handle.read_at(0, &mut read).await.unwrap();
+                assert_eq!(read.as_slice(), first.as_slice());
+            }
+
+            // an overlapping write waits for the slow child to complete the
+            // first one
+            handle.write_at(0, &second).await.unwrap();
+            let slow = read_block(&format!("{NEXUS_NAME}2")).await;
+            assert_eq!(slow.as_slice(), first.as_slice());
+
+            // the pause waits for the slow child to complete the second one
+            nexus_lookup_mut(NEXUS_NAME).unwrap().pause().await.unwrap();
+            let slow = read_block(&format!("{NEXUS_NAME}2")).await;
+            assert_eq!(slow.as_slice(), second.as_slice());
+            nexus_lookup_mut(NEXUS_NAME)
+                .unwrap()
+                .resume()
+                .await
+                .unwrap();
+
+            let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+            assert!(nexus.children_iter().all(|c| c.is_healthy()));
+
+            remove_fault_injection(&delay.uri()).unwrap();
+            drop(handle);
+            nexus_lookup_mut(NEXUS_NAME)
+                .unwrap()
+                .destroy()
+                .await
+                .unwrap();
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn nexus_write_laggard_failed() {
+    const NEXUS_NAME: &str = "laggard_failed";
+
+    mayastor()
+        .spawn(async {
+            let delay = create_nexus(NEXUS_NAME).await;
+            // the slow child fails the writes as well
+            let error = InjectionBuilder::default()
+                .with_domain(FaultDomain::NexusChild)
+                .with_device_name(format!("{NEXUS_NAME}2"))
+                .with_io_operation(FaultIoOperation::Write)
+                .with_io_stage(FaultIoStage::Completion)
+                .with_method(FaultMethod::DATA_TRANSFER_ERROR)
+                .build()
+                .unwrap();
+            add_fault_injection(error.clone()).unwrap();
+
+            let handle = device_open(NEXUS_NAME, true)
+                .unwrap()
+                .into_handle()
+                .unwrap();
+            let alignment = handle.get_device().alignment();
+            let mut buf = DmaBuf::new(4096, alignment).unwrap();
+            buf.fill(0xa5);
+            handle.write_at(0, &buf).await.unwrap();
+
+            // the failing child is faulted, and is never read from in
+            // between
+            let start = Instant::now();
+            while nexus_lookup(NEXUS_NAME).unwrap().child_at(2).is_healthy() {
+                assert!(start.elapsed() < Duration::from_secs(2));
+                let mut read = DmaBuf::new(4096, alignment).unwrap();
+                handle.read_at(0, &mut read).await.unwrap();
+                assert_eq!(read.as_slice(), buf.as_slice());
+                mayastor_sleep(Duration::from_millis(10)).await.unwrap();
+            }
+            for _ in 0 .. 6 {
+                let mut read = DmaBuf::new(4096, alignment).unwrap();
+                handle.read_at(0, &mut read).await.unwrap();
+                assert_eq!(read.as_slice(), buf.as_slice());
+            }
+
+            // the pause does not wait for the failed write
+            nexus_lookup_mut(NEXUS_NAME).unwrap().pause().await.unwrap();
+            nexus_lookup_mut(NEXUS_NAME)
+                .unwrap()
+                .resume()
+                .await
+                .unwrap();
+
+            remove_fault_injection(&error.uri()).unwrap();
+            remove_fault_injection(&delay.uri()).unwrap();
+            drop(handle);
+            nexus_lookup_mut(NEXUS_NAME)
+                .unwrap()
+                .destroy()
+                .await
+                .unwrap();
+        })
+        .await;
+}
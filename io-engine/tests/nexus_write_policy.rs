@@ -0,0 +1,79 @@
+use std::sync::atomic::Ordering;
+
+use io_engine::{
+    bdev::{
+        device_open,
+        nexus::{
+            nexus_create,
+            nexus_lookup,
+            nexus_lookup_mut,
+            NexusWritePolicy,
+            ENABLE_IO_ALL_THRD_NX_CHAN,
+        },
+    },
+    core::MayastorCliArgs,
+};
+use spdk_rs::DmaBuf;
+
+pub mod common;
+use common::MayastorTest;
+
+const NEXUS_NAME: &str = "write_policy_nexus";
+
+#[tokio::test]
+async fn nexus_write_policy() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        // the channel of the test thread must be an I/O channel
+        ENABLE_IO_ALL_THRD_NX_CHAN.store(true, Ordering::SeqCst);
+
+        nexus_create(
+            NEXUS_NAME,
+            8 * 1024 * 1024,
+            None,
+            &[
+                "malloc:///writepolicy0?size_mb=16".to_string(),
+                "malloc:///writepolicy1?size_mb=16".to_string(),
+                "malloc:///writepolicy2?size_mb=16".to_string(),
+            ],
+        )
+        .await
+        .unwrap();
+        let nexus = nexus_lookup(NEXUS_NAME).unwrap();
+        assert_eq!(nexus.write_policy(), NexusWritePolicy::All);
+
+        // a write cannot be acknowledged by none of the children
+        assert!(nexus.set_write_policy(NexusWritePolicy::Quorum(0)).is_err());
+        assert_eq!(nexus.write_policy(), NexusWritePolicy::All);
+        nexus.set_write_policy(NexusWritePolicy::Quorum(2)).unwrap();
+        assert_eq!(nexus.write_policy(), NexusWritePolicy::Quorum(2));
+
+        let handle = device_open(NEXUS_NAME, true)
+            .unwrap()
+            .into_handle()
+            .unwrap();
+        let alignment = handle.get_device().alignment();
+        let mut buf = DmaBuf::new(4096, alignment).unwrap();
+        buf.fill(0xa5);
+        handle.write_at(0, &buf).await.unwrap();
+
+        // the data written is read back from whichever child
+        for _ in 0 .. 3 {
+            let mut read = DmaBuf::new(4096, alignment).unwrap();
+            handle.read_at(0, &mut read).await.unwrap();
+            assert_eq!(read.as_slice(), buf.as_slice());
+        }
+
+        // the laggards complete, no child is faulted
+        assert!(nexus.children_iter().all(|c| c.is_healthy()));
+
+        drop(handle);
+        nexus_lookup_mut(NEXUS_NAME)
+            .unwrap()
+            .destroy()
+            .await
+            .unwrap();
+        ENABLE_IO_ALL_THRD_NX_CHAN.store(false, Ordering::SeqCst);
+    })
+    .await;
+}
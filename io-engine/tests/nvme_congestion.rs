@@ -0,0 +1,122 @@
+use libc::c_void;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use common::compose::{
+    rpc::v0::{
+        mayastor::{BdevShareRequest, BdevUri, Null},
+        GrpcConnect,
+    },
+    Builder,
+    MayastorTest,
+};
+use io_engine::{
+    bdev::{device_create, device_destroy, device_open, nvme_congestion},
+    constants::NVME_NQN_PREFIX,
+    core::{BlockDevice, IoCompletionStatus, MayastorCliArgs},
+    sleep::mayastor_sleep,
+};
+use spdk_rs::{AsIoVecs, DmaBuf};
+
+pub mod common;
+
+const NUM_IOS: u64 = 512;
+const BUF_SIZE: u64 = 4096;
+
+static COMPLETED: AtomicU64 = AtomicU64::new(0);
+
+fn io_completion_callback(
+    _device: &dyn BlockDevice,
+    status: IoCompletionStatus,
+    _ctx: *mut c_void,
+) {
+    assert_eq!(status, IoCompletionStatus::Success, "I/O operation failed");
+    COMPLETED.fetch_add(1, Ordering::SeqCst);
+}
+
+#[tokio::test]
+async fn nvme_congestion_control() {
+    common::composer_init();
+
+    let test = Builder::new()
+        .name("nvme_congestion")
+        .network("10.1.0.0/16")
+        .unwrap()
+        .add_container_dbg("ms1")
+        .with_clean(true)
+        .build()
+        .await
+        .unwrap();
+
+    let grpc = GrpcConnect::new(&test);
+    let mut hdls = grpc.grpc_handles().await.unwrap();
+    hdls[0].bdev.list(Null {}).await.unwrap();
+    hdls[0]
+        .bdev
+        .create(BdevUri {
+            uri: "malloc:///disk0?size_mb=64".into(),
+        })
+        .await
+        .unwrap();
+    hdls[0]
+        .bdev
+        .share(BdevShareRequest {
+            name: "disk0".into(),
+            proto: "nvmf".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let url = format!(
+        "nvmf://{}:8420/{NVME_NQN_PREFIX}:disk0",
+        hdls[0].endpoint.ip()
+    );
+
+    let ms = MayastorTest::new(MayastorCliArgs {
+        nvme_latency_target_us: Some(1_000_000),
+        ..Default::default()
+    });
+
+    ms.spawn(async move {
+        let name = device_create(&url).await.unwrap();
+        let handle = device_open(&name, false).unwrap().into_handle().unwrap();
+
+        // more writes than the limit at once, the excess waits for
+        // completions
+        let block_len = handle.get_device().block_len();
+        let bufs = (0 .. NUM_IOS)
+            .map(|_| {
+                vec![DmaBuf::new(BUF_SIZE, handle.get_device().alignment())
+                    .unwrap()]
+            })
+            .collect::<Vec<_>>();
+        for (i, buf) in bufs.iter().enumerate() {
+            handle
+                .writev_blocks(
+                    buf.as_io_vecs(),
+                    i as u64 * BUF_SIZE / block_len,
+                    BUF_SIZE / block_len,
+                    io_completion_callback,
+                    std::ptr::null_mut(),
+                )
+                .unwrap();
+        }
+
+        while COMPLETED.load(Ordering::SeqCst) < NUM_IOS {
+            mayastor_sleep(Duration::from_millis(10)).await.unwrap();
+        }
+
+        let states = nvme_congestion();
+        let state = states.iter().find(|s| s.controller == name).unwrap();
+        assert!(state.deferred > 0);
+        assert!(state.limit > 0);
+
+        drop(bufs);
+        drop(handle);
+        device_destroy(&url).await.unwrap();
+        assert!(nvme_congestion().iter().all(|s| s.controller != name));
+    })
+    .await;
+}
@@ -7,7 +7,7 @@ use io_engine::{
         Reactor,
         UntypedBdev,
     },
-    subsys::{NvmfSubsystem, SubType},
+    subsys::{NvmfSubsystem, SubType, SubsystemHandle},
 };
 
 pub mod common;
@@ -72,16 +72,18 @@ fn nvmf_target() {
                     if s.subtype() == SubType::Discovery {
                         continue;
                     }
-                    s.stop().await.unwrap();
-                    let sbdev = s.bdev().unwrap();
+                    let SubsystemHandle::Active(s) = s.into_handle().unwrap()
+                    else {
+                        panic!("subsystem is not active");
+                    };
+                    let s = s.stop().await.unwrap();
+                    let sbdev = s.subsystem().bdev().unwrap();
                     assert_eq!(sbdev.name(), bdev.name());
 
                     assert!(bdev.is_claimed());
                     assert!(bdev.is_claimed_by("NVMe-oF Target"));
 
-                    unsafe {
-                        s.shutdown_unsafe();
-                    }
+                    s.destroy().await.unwrap();
                     assert!(!bdev.is_claimed());
                 }
             });
@@ -0,0 +1,63 @@
+use io_engine::{
+    bdev::{device_create, device_destroy},
+    bdev_api::bdev_create,
+    constants::NVME_NQN_PREFIX,
+    core::{MayastorCliArgs, NvmfShareProps, Share, UntypedBdev, UpdateProps},
+    subsys::{NvmfListener, NvmfSubsystem, NvmfTransport},
+};
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+const HOST1: &str = "nqn.2019-05.io.openebs:allowed-hosts1";
+const HOST2: &str = "nqn.2019-05.io.openebs:allowed-hosts2";
+
+#[tokio::test]
+async fn nvmf_allowed_hosts_disconnect() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        bdev_create("malloc:///allowed0?size_mb=4").await.unwrap();
+        let mut bdev = UntypedBdev::lookup_by_name("allowed0").unwrap();
+
+        // any host is allowed
+        let props = NvmfShareProps::new().with_listeners(vec![NvmfListener {
+            transport: NvmfTransport::Tcp,
+            address: Some("127.0.0.1".to_string()),
+            port: Some(8441),
+        }]);
+        Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+
+        let uri = format!(
+            "nvmf://127.0.0.1:8441/{NVME_NQN_PREFIX}:allowed0?hostnqn={HOST1}"
+        );
+        device_create(&uri).await.unwrap();
+        let subsystem = NvmfSubsystem::nqn_lookup("allowed0").unwrap();
+        assert!(subsystem.allowed_hosts().is_empty());
+        assert_eq!(subsystem.connected_hosts(), vec![HOST1.to_string()]);
+
+        // the host is not registered, yet it is disconnected once no longer
+        // allowed
+        let props = UpdateProps::new().with_allowed_hosts(vec![HOST2.into()]);
+        Pin::new(&mut bdev).update_properties(props).await.unwrap();
+        let subsystem = NvmfSubsystem::nqn_lookup("allowed0").unwrap();
+        assert_eq!(subsystem.allowed_hosts(), vec![HOST2.to_string()]);
+    })
+    .await;
+
+    // the controller goes away once its qpairs are disconnected
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+
+    ms.spawn(async {
+        let subsystem = NvmfSubsystem::nqn_lookup("allowed0").unwrap();
+        assert!(subsystem.connected_hosts().is_empty());
+
+        let uri = format!(
+            "nvmf://127.0.0.1:8441/{NVME_NQN_PREFIX}:allowed0?hostnqn={HOST1}"
+        );
+        device_destroy(&uri).await.ok();
+        let mut bdev = UntypedBdev::lookup_by_name("allowed0").unwrap();
+        Pin::new(&mut bdev).unshare().await.unwrap();
+    })
+    .await;
+}
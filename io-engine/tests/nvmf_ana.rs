@@ -0,0 +1,132 @@
+use io_engine::{
+    bdev_api::bdev_create,
+    core::{MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{
+        NvmfAnaState,
+        NvmfError,
+        NvmfListener,
+        NvmfSubsystem,
+        NvmfTransport,
+    },
+};
+use once_cell::sync::OnceCell;
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+static MS: OnceCell<MayastorTest> = OnceCell::new();
+
+fn mayastor() -> &'static MayastorTest<'static> {
+    MS.get_or_init(|| MayastorTest::new(MayastorCliArgs::default()))
+}
+
+fn listener(port: u16) -> NvmfListener {
+    NvmfListener {
+        transport: NvmfTransport::Tcp,
+        address: Some("127.0.0.1".to_string()),
+        port: Some(port),
+    }
+}
+
+#[tokio::test]
+async fn nvmf_listener_ana_states() {
+    mayastor()
+        .spawn(async {
+            bdev_create("malloc:///ana0?size_mb=4").await.unwrap();
+            let mut bdev = UntypedBdev::lookup_by_name("ana0").unwrap();
+
+            let props = NvmfShareProps::new()
+                .with_ana(true)
+                .with_listeners(vec![listener(8443), listener(8444)]);
+            Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+            let subsystem = NvmfSubsystem::nqn_lookup("ana0").unwrap();
+
+            // a single namespace, in a single group
+            assert_eq!(subsystem.ana_groups(), vec![1]);
+            let states = subsystem.ana_states();
+            assert_eq!(states.len(), 2);
+            assert!(states
+                .iter()
+                .all(|s| s.ana_state == NvmfAnaState::Optimized));
+
+            // the state of one listener only changes
+            subsystem.pause().await.unwrap();
+            subsystem
+                .set_listener_ana(
+                    &listener(8444),
+                    1,
+                    NvmfAnaState::NonOptimized,
+                )
+                .await
+                .unwrap();
+            assert!(matches!(
+                subsystem
+                    .set_listener_ana(
+                        &listener(8444),
+                        2,
+                        NvmfAnaState::Optimized
+                    )
+                    .await,
+                Err(NvmfError::AnaGroupNotFound { .. })
+            ));
+            assert!(matches!(
+                subsystem
+                    .set_listener_ana(
+                        &listener(8445),
+                        1,
+                        NvmfAnaState::Optimized
+                    )
+                    .await,
+                Err(NvmfError::Listener { .. })
+            ));
+            subsystem.resume().await.unwrap();
+
+            let states = subsystem.ana_states();
+            let state = |port| {
+                states
+                    .iter()
+                    .find(|s| s.listener.port == Some(port))
+                    .map(|s| s.ana_state)
+                    .unwrap()
+            };
+            assert_eq!(state(8443), NvmfAnaState::Optimized);
+            assert_eq!(state(8444), NvmfAnaState::NonOptimized);
+
+            Pin::new(&mut bdev).unshare().await.unwrap();
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn nvmf_ana_reporting_toggle() {
+    mayastor()
+        .spawn(async {
+            bdev_create("malloc:///ana1?size_mb=4").await.unwrap();
+            let mut bdev = UntypedBdev::lookup_by_name("ana1").unwrap();
+
+            let props = NvmfShareProps::new()
+                .with_ana(false)
+                .with_listeners(vec![listener(8446)]);
+            Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+            let subsystem = NvmfSubsystem::nqn_lookup("ana1").unwrap();
+            assert!(!subsystem.ana_reporting());
+
+            // only an inactive subsystem takes the setting as such
+            subsystem.pause().await.unwrap();
+            assert!(subsystem.set_ana_reporting(true).is_err());
+            subsystem.resume().await.unwrap();
+            assert!(!subsystem.ana_reporting());
+
+            // while the existing one is stopped meanwhile
+            subsystem.update_ana_reporting(true).await.unwrap();
+            assert!(subsystem.ana_reporting());
+            assert_eq!(subsystem.uri_endpoints().unwrap().len(), 1);
+
+            subsystem.update_ana_reporting(false).await.unwrap();
+            assert!(!subsystem.ana_reporting());
+
+            Pin::new(&mut bdev).unshare().await.unwrap();
+        })
+        .await;
+}
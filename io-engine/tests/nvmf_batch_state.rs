@@ -0,0 +1,79 @@
+use io_engine::{
+    bdev_api::bdev_create,
+    core::{MayastorCliArgs, Share, UntypedBdev},
+    subsys::{NvmfError, NvmfSubsystem, SubsystemStateChange},
+};
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+const BDEVS: [&str; 3] = ["batch0", "batch1", "batch2"];
+
+#[tokio::test]
+async fn nvmf_batch_state_change() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        let mut nqns = vec![];
+        for name in BDEVS {
+            bdev_create(&format!("malloc:///{name}?size_mb=4"))
+                .await
+                .unwrap();
+            let mut bdev = UntypedBdev::lookup_by_name(name).unwrap();
+            Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+            nqns.push(NvmfSubsystem::nqn_lookup(name).unwrap().get_nqn());
+        }
+
+        for change in [
+            SubsystemStateChange::Pause,
+            SubsystemStateChange::Resume,
+            SubsystemStateChange::Stop,
+            SubsystemStateChange::Start,
+        ] {
+            let report =
+                NvmfSubsystem::change_state_nqns(Some(&nqns), change, Some(2))
+                    .await
+                    .unwrap();
+            assert_eq!(report.change, change);
+            assert_eq!(report.total, BDEVS.len());
+            assert_eq!(report.succeeded, BDEVS.len());
+            assert!(report.failures.is_empty());
+        }
+
+        // all the subsystems, but the discovery one
+        let report = NvmfSubsystem::change_state_nqns(
+            None,
+            SubsystemStateChange::Pause,
+            None,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.total, BDEVS.len());
+        assert!(report.failures.is_empty());
+        NvmfSubsystem::change_state_nqns(
+            None,
+            SubsystemStateChange::Resume,
+            None,
+        )
+        .await
+        .unwrap();
+
+        // unknown subsystems are refused before any state change
+        nqns.push("nqn.2019-05.io.openebs:unknown".to_string());
+        assert!(matches!(
+            NvmfSubsystem::change_state_nqns(
+                Some(&nqns),
+                SubsystemStateChange::Pause,
+                None
+            )
+            .await,
+            Err(NvmfError::NotFound { .. })
+        ));
+
+        for name in BDEVS {
+            let mut bdev = UntypedBdev::lookup_by_name(name).unwrap();
+            Pin::new(&mut bdev).unshare().await.unwrap();
+        }
+    })
+    .await;
+}
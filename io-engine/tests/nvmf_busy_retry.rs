@@ -0,0 +1,62 @@
+use io_engine::{
+    bdev_api::bdev_create,
+    core::{MayastorCliArgs, Share, UntypedBdev},
+    subsys::{busy_retry_stats, NvmfBusyRetryOpts, NvmfSubsystem},
+};
+use std::{pin::Pin, time::Duration};
+
+pub mod common;
+use common::MayastorTest;
+
+#[test]
+fn nvmf_busy_retry_backoff() {
+    let opts = NvmfBusyRetryOpts {
+        max_attempts: 0,
+        backoff_base_ms: 50,
+        backoff_cap_ms: 300,
+        jitter: false,
+    };
+    assert_eq!(opts.max_attempts(), 1);
+    assert_eq!(opts.backoff(0), Duration::from_millis(50));
+    assert_eq!(opts.backoff(2), Duration::from_millis(200));
+    assert_eq!(opts.backoff(3), Duration::from_millis(300));
+    assert_eq!(opts.backoff(80), Duration::from_millis(300));
+
+    let opts = NvmfBusyRetryOpts {
+        jitter: true,
+        ..opts
+    };
+    for retry in 0 .. 10 {
+        let delay = opts.backoff(retry).as_millis() as u64;
+        let max = (50u64 << retry).min(300);
+        assert!(delay >= max / 2 && delay <= max);
+    }
+}
+
+#[tokio::test]
+async fn nvmf_busy_retry() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        bdev_create("malloc:///busy0?size_mb=4").await.unwrap();
+        let mut bdev = UntypedBdev::lookup_by_name("busy0").unwrap();
+        Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+        let subsystem = NvmfSubsystem::nqn_lookup("busy0").unwrap();
+
+        // the resume finds the subsystem busy with the pause, and is retried
+        // once the pause completes
+        let before = busy_retry_stats();
+        let (pause, resume) =
+            futures::join!(subsystem.pause(), subsystem.resume());
+        pause.unwrap();
+        resume.unwrap();
+
+        let after = busy_retry_stats();
+        assert!(after.busy > before.busy);
+        assert!(after.retries > before.retries);
+        assert_eq!(after.exhausted, before.exhausted);
+        assert!(after.busy_by_op["resume"] > 0);
+
+        Pin::new(&mut bdev).unshare().await.unwrap();
+    })
+    .await;
+}
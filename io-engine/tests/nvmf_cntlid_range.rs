@@ -0,0 +1,73 @@
+use io_engine::{
+    bdev_api::bdev_create,
+    core::{MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{
+        node_cntlid_range,
+        set_node_cntlid_range,
+        NvmfError,
+        NvmfSubsystem,
+    },
+};
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+/// Returns the controller ID range of the subsystem of the given bdev.
+fn cntlid_range(name: &str) -> (u16, u16) {
+    let info = NvmfSubsystem::nqn_lookup(name).unwrap().info();
+    (info.min_cntlid, info.max_cntlid)
+}
+
+#[tokio::test]
+async fn nvmf_node_cntlid_range() {
+    let ms = MayastorTest::new(MayastorCliArgs {
+        nvmf_cntlid_range: Some("100-199".to_string()),
+        ..Default::default()
+    });
+    ms.spawn(async {
+        for name in ["cntlid0", "cntlid1", "cntlid2", "cntlid3"] {
+            bdev_create(&format!("malloc:///{name}?size_mb=4"))
+                .await
+                .unwrap();
+        }
+        assert_eq!(node_cntlid_range(), Some((100, 199)));
+
+        // the range of the node applies to the shares without one
+        let mut bdev = UntypedBdev::lookup_by_name("cntlid0").unwrap();
+        Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+        assert_eq!(cntlid_range("cntlid0"), (100, 199));
+
+        // and to those with the full range
+        let mut bdev = UntypedBdev::lookup_by_name("cntlid1").unwrap();
+        let props = NvmfShareProps::new().with_range(Some((1, 0xffef)));
+        Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+        assert_eq!(cntlid_range("cntlid1"), (100, 199));
+
+        // but not to those with a narrower range
+        let mut bdev = UntypedBdev::lookup_by_name("cntlid2").unwrap();
+        let props = NvmfShareProps::new().with_range(Some((500, 599)));
+        Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+        assert_eq!(cntlid_range("cntlid2"), (500, 599));
+
+        // a new range applies to the shares from then on
+        assert!(matches!(
+            set_node_cntlid_range(Some("0-10")),
+            Err(NvmfError::InvalidCntlidRange { .. })
+        ));
+        set_node_cntlid_range(Some("200-299")).unwrap();
+        let mut bdev = UntypedBdev::lookup_by_name("cntlid3").unwrap();
+        Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+        assert_eq!(cntlid_range("cntlid3"), (200, 299));
+        assert_eq!(cntlid_range("cntlid0"), (100, 199));
+
+        set_node_cntlid_range(None).unwrap();
+        assert_eq!(node_cntlid_range(), None);
+
+        for name in ["cntlid0", "cntlid1", "cntlid2", "cntlid3"] {
+            let mut bdev = UntypedBdev::lookup_by_name(name).unwrap();
+            Pin::new(&mut bdev).unshare().await.unwrap();
+        }
+    })
+    .await;
+}
@@ -0,0 +1,48 @@
+use io_engine::{
+    core::MayastorCliArgs,
+    subsys::{crd_policies, set_crd_policies, NvmfCrdPolicies, NvmfCrdPolicy},
+};
+
+pub mod common;
+use common::MayastorTest;
+
+#[tokio::test]
+async fn nvmf_crd_policy() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        // the nexuses delay the reservation conflicts and lack of space more
+        let policies = crd_policies();
+        assert_eq!(policies, NvmfCrdPolicies::default());
+        assert_eq!(policies.nexus.crd(1, false, false), 1);
+        assert_eq!(policies.nexus.crd(1, true, false), 2);
+        assert_eq!(policies.nexus.crd(1, false, true), 2);
+        assert_eq!(policies.nexus.crd(3, true, false), 2);
+        assert_eq!(policies.nexus.crd(3, false, false), 3);
+        // the replicas delay all the errors given the first delay more
+        assert_eq!(policies.replica.crd(1, false, false), 3);
+        assert_eq!(policies.replica.crd(1, true, false), 3);
+        assert_eq!(policies.replica.crd(2, false, false), 2);
+        // a completion without CRD is kept as is
+        assert_eq!(policies.nexus.crd(0, true, false), 0);
+        assert_eq!(policies.replica.crd(0, false, false), 0);
+
+        // the CRD may be cleared for initiators which mishandle it
+        set_crd_policies(NvmfCrdPolicies {
+            nexus: NvmfCrdPolicy {
+                enable: false,
+                ..NvmfCrdPolicy::nexus()
+            },
+            replica: NvmfCrdPolicy {
+                default: Some(7),
+                ..NvmfCrdPolicy::replica()
+            },
+        });
+        let policies = crd_policies();
+        assert_eq!(policies.nexus.crd(1, true, false), 0);
+        // and is at most 3
+        assert_eq!(policies.replica.crd(1, false, false), 3);
+
+        set_crd_policies(NvmfCrdPolicies::default());
+    })
+    .await;
+}
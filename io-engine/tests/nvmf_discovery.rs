@@ -0,0 +1,111 @@
+use io_engine::{
+    bdev_api::bdev_create,
+    core::{MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{
+        add_discovery_referral,
+        discovery_info,
+        remove_discovery_referral,
+        set_discovery_restrict_hosts,
+        NvmfError,
+        NvmfListener,
+        NvmfTransport,
+    },
+};
+use once_cell::sync::OnceCell;
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+const HOST: &str = "nqn.2019-05.io.openebs:discovery-host";
+
+static MS: OnceCell<MayastorTest> = OnceCell::new();
+
+fn mayastor() -> &'static MayastorTest<'static> {
+    MS.get_or_init(|| MayastorTest::new(MayastorCliArgs::default()))
+}
+
+/// Share properties allowing the host only.
+fn props_host() -> NvmfShareProps {
+    NvmfShareProps::new().with_allowed_hosts(vec![HOST.to_string()])
+}
+
+#[tokio::test]
+async fn nvmf_discovery_hosts() {
+    mayastor()
+        .spawn(async {
+            bdev_create("malloc:///disc0?size_mb=4").await.unwrap();
+            let mut bdev = UntypedBdev::lookup_by_name("disc0").unwrap();
+            Pin::new(&mut bdev)
+                .share_nvmf(Some(props_host()))
+                .await
+                .unwrap();
+
+            // any host may discover by default
+            let info = discovery_info();
+            assert!(!info.restrict_hosts);
+            assert!(info.allow_any_host);
+
+            // only the hosts allowed on a subsystem once restricted
+            let info = set_discovery_restrict_hosts(true);
+            assert!(!info.allow_any_host);
+            assert_eq!(info.hosts, vec![HOST.to_string()]);
+
+            // which follow the hosts of the subsystems
+            Pin::new(&mut bdev).unshare().await.unwrap();
+            let info = discovery_info();
+            assert!(!info.allow_any_host);
+            assert!(info.hosts.is_empty());
+
+            // a subsystem allowing any host allows any host to discover
+            Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+            assert!(discovery_info().allow_any_host);
+            Pin::new(&mut bdev).unshare().await.unwrap();
+
+            // disabling the restriction allows any host again, which the hosts
+            // of the subsystems no longer change
+            Pin::new(&mut bdev)
+                .share_nvmf(Some(props_host()))
+                .await
+                .unwrap();
+            let info = set_discovery_restrict_hosts(false);
+            assert!(info.allow_any_host);
+            assert!(info.hosts.is_empty());
+            Pin::new(&mut bdev).unshare().await.unwrap();
+            assert!(discovery_info().allow_any_host);
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn nvmf_discovery_referrals() {
+    mayastor()
+        .spawn(async {
+            let referral = NvmfListener {
+                transport: NvmfTransport::Tcp,
+                address: Some("10.1.0.2".to_string()),
+                port: None,
+            };
+            let info = add_discovery_referral(referral.clone()).unwrap();
+            assert_eq!(info.referrals.len(), 1);
+            assert_eq!(info.referrals[0].address, referral.address);
+            assert!(info.referrals[0].port.is_some());
+
+            assert!(matches!(
+                add_discovery_referral(referral.clone()),
+                Err(NvmfError::ReferralExists { .. })
+            ));
+            assert!(matches!(
+                add_discovery_referral(NvmfListener::default()),
+                Err(NvmfError::InvalidReferral { .. })
+            ));
+
+            let info = remove_discovery_referral(referral.clone()).unwrap();
+            assert!(info.referrals.is_empty());
+            assert!(matches!(
+                remove_discovery_referral(referral),
+                Err(NvmfError::ReferralNotFound { .. })
+            ));
+        })
+        .await;
+}
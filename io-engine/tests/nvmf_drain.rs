@@ -0,0 +1,112 @@
+use futures::future::{select, Either};
+use io_engine::{
+    bdev::{device_create, device_destroy, device_open},
+    bdev_api::bdev_create,
+    core::{MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    sleep::mayastor_sleep,
+    subsys::{NvmfError, NvmfListener, NvmfSubsystem, NvmfTransport},
+};
+use once_cell::sync::OnceCell;
+use spdk_rs::DmaBuf;
+use std::{pin::Pin, time::Duration};
+
+pub mod common;
+use common::MayastorTest;
+
+static MS: OnceCell<MayastorTest> = OnceCell::new();
+
+fn mayastor() -> &'static MayastorTest<'static> {
+    MS.get_or_init(|| {
+        MayastorTest::new(MayastorCliArgs {
+            reactor_mask: "0x3".into(),
+            ..Default::default()
+        })
+    })
+}
+
+#[tokio::test]
+async fn nvmf_drain() {
+    mayastor()
+        .spawn(async {
+            bdev_create("malloc:///drain0?size_mb=64").await.unwrap();
+            let mut bdev = UntypedBdev::lookup_by_name("drain0").unwrap();
+            let nqn = Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+
+            let subsystem = NvmfSubsystem::lookup_by_nqn(&nqn).unwrap();
+            assert!(subsystem.outstanding_commands().await.is_drained());
+
+            // without any initiator, the drain completes right away
+            let stats = subsystem
+                .drain(Duration::from_secs(5), Duration::from_millis(10))
+                .await
+                .unwrap();
+            assert!(stats.drained);
+            assert_eq!(stats.nqn, nqn);
+            assert!(stats.initial.is_drained());
+
+            subsystem.resume().await.unwrap();
+            Pin::new(&mut bdev).unshare().await.unwrap();
+
+            assert!(matches!(
+                NvmfSubsystem::lookup_by_nqn(&nqn),
+                Err(NvmfError::NotFound { .. })
+            ));
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn nvmf_drain_connected() {
+    mayastor()
+        .spawn(async {
+            bdev_create("malloc:///drain1?size_mb=64").await.unwrap();
+            let mut bdev = UntypedBdev::lookup_by_name("drain1").unwrap();
+            let props =
+                NvmfShareProps::new().with_listeners(vec![NvmfListener {
+                    transport: NvmfTransport::Tcp,
+                    address: Some("127.0.0.1".to_string()),
+                    port: Some(8457),
+                }]);
+            let nqn =
+                Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+
+            // connect an initiator and complete a write through it
+            let uri = format!("nvmf://127.0.0.1:8457/{nqn}");
+            let name = device_create(&uri).await.unwrap();
+            let handle =
+                device_open(&name, true).unwrap().into_handle().unwrap();
+            let buf =
+                DmaBuf::new(4096, handle.get_device().alignment()).unwrap();
+            handle.write_at(0, &buf).await.unwrap();
+
+            // the connection and its pending async event requests do not
+            // hold the drain back
+            let subsystem = NvmfSubsystem::lookup_by_nqn(&nqn).unwrap();
+            let stats = subsystem
+                .drain(Duration::from_secs(5), Duration::from_millis(10))
+                .await
+                .unwrap();
+            assert!(stats.drained);
+            assert!(stats.initial.is_drained());
+
+            // a write submitted while the subsystem is drained is held back
+            // until it is resumed
+            let write = handle.write_at(4096, &buf);
+            futures::pin_mut!(write);
+            let tick = mayastor_sleep(Duration::from_millis(200));
+            let write = match select(write, tick).await {
+                Either::Left(_) => panic!("a write completed while drained"),
+                Either::Right((_, write)) => write,
+            };
+            assert!(subsystem.outstanding_commands().await.queued > 0);
+
+            subsystem.resume().await.unwrap();
+            write.await.unwrap();
+            assert_eq!(subsystem.outstanding_commands().await.queued, 0);
+
+            drop(handle);
+            device_destroy(&uri).await.unwrap();
+            Pin::new(&mut bdev).unshare().await.unwrap();
+        })
+        .await;
+}
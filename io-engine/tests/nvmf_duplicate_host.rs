@@ -0,0 +1,122 @@
+use io_engine::{
+    bdev::{device_create, device_destroy},
+    bdev_api::bdev_create,
+    constants::NVME_NQN_PREFIX,
+    core::{MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{duplicate_hosts, NvmfListener, NvmfTransport},
+};
+use once_cell::sync::OnceCell;
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+static MS: OnceCell<MayastorTest> = OnceCell::new();
+
+fn mayastor() -> &'static MayastorTest<'static> {
+    MS.get_or_init(|| MayastorTest::new(MayastorCliArgs::default()))
+}
+
+/// Shares a malloc bdev of the given name to the host, on port 8455.
+async fn share(name: &str, host: &str) {
+    bdev_create(&format!("malloc:///{name}?size_mb=4"))
+        .await
+        .unwrap();
+    let mut bdev = UntypedBdev::lookup_by_name(name).unwrap();
+    let props = NvmfShareProps::new()
+        .with_allowed_hosts(vec![host.to_string()])
+        .with_listeners(vec![NvmfListener {
+            transport: NvmfTransport::Tcp,
+            address: Some("127.0.0.1".to_string()),
+            port: Some(8455),
+        }]);
+    Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+}
+
+async fn unshare(name: &str) {
+    let mut bdev = UntypedBdev::lookup_by_name(name).unwrap();
+    Pin::new(&mut bdev).unshare().await.unwrap();
+}
+
+#[tokio::test]
+async fn nvmf_duplicate_host_same_address() {
+    const HOST: &str = "nqn.2019-05.io.openebs:duplicate-host";
+
+    mayastor()
+        .spawn(async {
+            let mut uris = Vec::new();
+            for name in ["duphost0", "duphost1"] {
+                share(name, HOST).await;
+
+                // the same host connects to both subsystems from the same
+                // address, which is not a duplicate
+                let uri = format!(
+                    "nvmf://127.0.0.1:8455/{NVME_NQN_PREFIX}:{name}?\
+                    hostnqn={HOST}"
+                );
+                device_create(&uri).await.unwrap();
+                uris.push(uri);
+            }
+
+            let report = duplicate_hosts();
+            assert!(!report.reject);
+            assert!(!report.hosts.iter().any(|h| h.hostnqn == HOST));
+
+            for uri in uris {
+                device_destroy(&uri).await.unwrap();
+            }
+            for name in ["duphost0", "duphost1"] {
+                unshare(name).await;
+            }
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn nvmf_duplicate_host_second_address() {
+    const HOST: &str = "nqn.2019-05.io.openebs:duplicate-host-clone";
+
+    mayastor()
+        .spawn(async {
+            let detected = duplicate_hosts().detected;
+            let mut uris = Vec::new();
+            for (name, hostaddr) in
+                [("duphost2", "127.0.0.1"), ("duphost3", "127.0.0.2")]
+            {
+                share(name, HOST).await;
+                let uri = format!(
+                    "nvmf://127.0.0.1:8455/{NVME_NQN_PREFIX}:{name}?\
+                    hostnqn={HOST}&hostaddr={hostaddr}"
+                );
+                device_create(&uri).await.unwrap();
+                uris.push(uri);
+            }
+
+            // the host connecting from a second address is detected, but
+            // left connected
+            let report = duplicate_hosts();
+            assert_eq!(report.detected, detected + 1);
+            assert_eq!(report.rejected, 0);
+            let host = report
+                .hosts
+                .iter()
+                .find(|h| h.hostnqn == HOST)
+                .expect("the host should be reported");
+            assert_eq!(host.addresses, vec!["127.0.0.1", "127.0.0.2"]);
+            assert_eq!(
+                host.subsystems,
+                vec![
+                    format!("{NVME_NQN_PREFIX}:duphost2"),
+                    format!("{NVME_NQN_PREFIX}:duphost3"),
+                ]
+            );
+
+            for uri in uris {
+                device_destroy(&uri).await.unwrap();
+            }
+            for name in ["duphost2", "duphost3"] {
+                unshare(name).await;
+            }
+        })
+        .await;
+}
@@ -0,0 +1,66 @@
+use io_engine::{
+    bdev::{device_create, device_destroy},
+    bdev_api::bdev_create,
+    constants::NVME_NQN_PREFIX,
+    core::{MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{duplicate_hosts, NvmfListener, NvmfTransport},
+};
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+const HOST: &str = "nqn.2019-05.io.openebs:duplicate-host-reject";
+
+#[tokio::test]
+async fn nvmf_duplicate_host_reject() {
+    let ms = MayastorTest::new(MayastorCliArgs {
+        reject_duplicate_hosts: true,
+        ..Default::default()
+    });
+    ms.spawn(async {
+        let mut uris = Vec::new();
+        for (name, hostaddr) in
+            [("dupreject0", "127.0.0.1"), ("dupreject1", "127.0.0.2")]
+        {
+            bdev_create(&format!("malloc:///{name}?size_mb=4"))
+                .await
+                .unwrap();
+            let mut bdev = UntypedBdev::lookup_by_name(name).unwrap();
+            let props = NvmfShareProps::new()
+                .with_allowed_hosts(vec![HOST.to_string()])
+                .with_listeners(vec![NvmfListener {
+                    transport: NvmfTransport::Tcp,
+                    address: Some("127.0.0.1".to_string()),
+                    port: Some(8468),
+                }]);
+            Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+            uris.push(format!(
+                "nvmf://127.0.0.1:8468/{NVME_NQN_PREFIX}:{name}?\
+                hostnqn={HOST}&hostaddr={hostaddr}"
+            ));
+        }
+
+        device_create(&uris[0]).await.unwrap();
+        // the controller connected from the second address is disconnected
+        // as it connects, which the initiator may or may not notice before
+        // the device is created
+        let created = device_create(&uris[1]).await.is_ok();
+
+        let report = duplicate_hosts();
+        assert!(report.reject);
+        assert_eq!((report.detected, report.rejected), (1, 1));
+        // the rejected controller is not recorded for the host
+        assert!(report.hosts.is_empty());
+
+        if created {
+            device_destroy(&uris[1]).await.ok();
+        }
+        device_destroy(&uris[0]).await.unwrap();
+        for name in ["dupreject0", "dupreject1"] {
+            let mut bdev = UntypedBdev::lookup_by_name(name).unwrap();
+            Pin::new(&mut bdev).unshare().await.unwrap();
+        }
+    })
+    .await;
+}
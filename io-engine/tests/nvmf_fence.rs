@@ -0,0 +1,156 @@
+use io_engine::{
+    bdev::{device_create, device_destroy, device_open},
+    bdev_api::bdev_create,
+    constants::NVME_NQN_PREFIX,
+    core::{MayastorCliArgs, NvmfShareProps, Share, UntypedBdev, UpdateProps},
+    sleep::mayastor_sleep,
+    subsys::{NvmfError, NvmfListener, NvmfSubsystem, NvmfTransport},
+};
+use once_cell::sync::OnceCell;
+use std::{
+    collections::BTreeMap,
+    pin::Pin,
+    time::{Duration, Instant},
+};
+
+pub mod common;
+use common::MayastorTest;
+
+const PTPL_DIR: &str = "/tmp/io-engine-fence";
+const HOST1: &str = "nqn.2019-05.io.openebs:fence1";
+const HOST2: &str = "nqn.2019-05.io.openebs:fence2";
+const RESV_KEY: u64 = 0x5678;
+
+static MS: OnceCell<MayastorTest> = OnceCell::new();
+
+fn mayastor() -> &'static MayastorTest<'static> {
+    MS.get_or_init(|| {
+        std::fs::remove_dir_all(PTPL_DIR).ok();
+        MayastorTest::new(MayastorCliArgs {
+            ptpl_dir: Some(PTPL_DIR.to_string()),
+            ..Default::default()
+        })
+    })
+}
+
+/// Returns the fenced hosts of the given subsystem, as persisted.
+fn persisted_fence(nqn: &str) -> Vec<String> {
+    let path = format!("{PTPL_DIR}/nvmf-fenced-hosts.json");
+    let Ok(data) = std::fs::read(path) else {
+        return Vec::new();
+    };
+    let mut fences: BTreeMap<String, Vec<String>> =
+        serde_json::from_slice(&data).unwrap();
+    fences.remove(nqn).unwrap_or_default()
+}
+
+/// Returns the number of registrants and the type of the reservation of the
+/// namespace of the subsystem.
+async fn reservations(subsystem: &NvmfSubsystem) -> (usize, u32) {
+    let export = subsystem.export().await.unwrap();
+    subsystem.resume().await.unwrap();
+    let ns = &export.reservations[0];
+    (ns.registrants.len(), ns.rtype)
+}
+
+#[tokio::test]
+async fn nvmf_fence() {
+    mayastor()
+        .spawn(async {
+            bdev_create("malloc:///fence0?size_mb=4").await.unwrap();
+            let mut bdev = UntypedBdev::lookup_by_name("fence0").unwrap();
+
+            // a subsystem allowing any host cannot fence one
+            Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+            let subsystem = NvmfSubsystem::nqn_lookup("fence0").unwrap();
+            assert!(matches!(
+                subsystem.fence_host(HOST1).await,
+                Err(NvmfError::InvalidFence { .. })
+            ));
+            Pin::new(&mut bdev).unshare().await.unwrap();
+
+            let hosts = vec![HOST1.to_string(), HOST2.to_string()];
+            let props = NvmfShareProps::new().with_allowed_hosts(hosts.clone());
+            Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+            let subsystem = NvmfSubsystem::nqn_lookup("fence0").unwrap();
+            let nqn = subsystem.get_nqn();
+
+            // a fenced host is disallowed, and its fence persisted
+            subsystem.fence_host(HOST1).await.unwrap();
+            assert_eq!(subsystem.allowed_hosts(), vec![HOST2.to_string()]);
+            assert_eq!(subsystem.fenced_hosts(), vec![HOST1.to_string()]);
+            assert_eq!(subsystem.info().fenced_hosts, vec![HOST1.to_string()]);
+            assert_eq!(persisted_fence(&nqn), vec![HOST1.to_string()]);
+
+            // and stays so until unfenced
+            assert!(matches!(
+                subsystem.allow_host(HOST1),
+                Err(NvmfError::HostFenced { .. })
+            ));
+            let props = UpdateProps::new().with_allowed_hosts(hosts.clone());
+            Pin::new(&mut bdev).update_properties(props).await.unwrap();
+            assert_eq!(subsystem.allowed_hosts(), vec![HOST2.to_string()]);
+            assert!(Pin::new(&mut bdev)
+                .update_properties(UpdateProps::new())
+                .await
+                .is_err());
+
+            assert!(subsystem.unfence_host(HOST1).unwrap());
+            assert!(!subsystem.unfence_host(HOST1).unwrap());
+            assert!(persisted_fence(&nqn).is_empty());
+            let props = UpdateProps::new().with_allowed_hosts(hosts);
+            Pin::new(&mut bdev).update_properties(props).await.unwrap();
+            assert_eq!(subsystem.allowed_hosts().len(), 2);
+
+            // the fence of a subsystem is forgotten along with it
+            subsystem.fence_host(HOST2).await.unwrap();
+            Pin::new(&mut bdev).unshare().await.unwrap();
+            assert!(persisted_fence(&nqn).is_empty());
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn nvmf_fence_connected() {
+    mayastor()
+        .spawn(async {
+            bdev_create("malloc:///fence1?size_mb=4").await.unwrap();
+            let mut bdev = UntypedBdev::lookup_by_name("fence1").unwrap();
+            let props = NvmfShareProps::new()
+                .with_allowed_hosts(vec![HOST1.to_string()])
+                .with_listeners(vec![NvmfListener {
+                    transport: NvmfTransport::Tcp,
+                    address: Some("127.0.0.1".to_string()),
+                    port: Some(8465),
+                }]);
+            Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+            let subsystem = NvmfSubsystem::nqn_lookup("fence1").unwrap();
+
+            // the host connects and takes a reservation
+            let uri = format!(
+                "nvmf://127.0.0.1:8465/{NVME_NQN_PREFIX}:fence1?hostnqn={HOST1}"
+            );
+            let dev = device_create(&uri).await.unwrap();
+            let handle =
+                device_open(&dev, false).unwrap().into_handle().unwrap();
+            handle.nvme_resv_register(0, RESV_KEY, 0, 0).await.unwrap();
+            handle.nvme_resv_acquire(RESV_KEY, 0, 0, 1).await.unwrap();
+            assert_eq!(subsystem.connected_hosts(), vec![HOST1.to_string()]);
+            assert_eq!(reservations(&subsystem).await, (1, 1));
+
+            // once fenced, it is disconnected and its reservation is gone
+            subsystem.fence_host(HOST1).await.unwrap();
+            assert_eq!(reservations(&subsystem).await, (0, 0));
+            let start = Instant::now();
+            while !subsystem.connected_hosts().is_empty() {
+                assert!(start.elapsed() < Duration::from_secs(5));
+                mayastor_sleep(Duration::from_millis(100)).await.unwrap();
+            }
+            assert!(subsystem.allowed_hosts().is_empty());
+
+            drop(handle);
+            device_destroy(&uri).await.ok();
+            Pin::new(&mut bdev).unshare().await.unwrap();
+        })
+        .await;
+}
@@ -0,0 +1,149 @@
+use io_engine::{
+    bdev_api::bdev_create,
+    core::{CoreError, MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{persisted_share, HostDhChap, NvmfError, NvmfSubsystem},
+};
+use once_cell::sync::OnceCell;
+use std::{os::unix::fs::PermissionsExt, pin::Pin};
+
+pub mod common;
+use common::MayastorTest;
+
+const HOST: &str = "nqn.2019-05.io.openebs:host-auth";
+const PTPL_DIR: &str = "/tmp/io-engine-host-auth";
+const RPC_SOCK: &str = "/tmp/io-engine-host-auth.sock";
+const SECRET: &str =
+    "DHHC-1:00:AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh+KfiaR:";
+
+static MS: OnceCell<MayastorTest> = OnceCell::new();
+
+fn mayastor() -> &'static MayastorTest<'static> {
+    MS.get_or_init(|| {
+        std::fs::remove_dir_all(PTPL_DIR).ok();
+        std::fs::create_dir_all(PTPL_DIR).unwrap();
+        MayastorTest::new(MayastorCliArgs {
+            ptpl_dir: Some(PTPL_DIR.to_string()),
+            rpc_address: RPC_SOCK.to_string(),
+            ..Default::default()
+        })
+    })
+}
+
+/// Loads a secret into the keyring under the given key name.
+async fn add_key(name: &str) {
+    let path = format!("{PTPL_DIR}/{name}");
+    std::fs::write(&path, SECRET).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+        .unwrap();
+    jsonrpc::call::<_, bool>(
+        RPC_SOCK,
+        "keyring_file_add_key",
+        Some(serde_json::json!({ "name": name, "path": path })),
+    )
+    .await
+    .unwrap();
+}
+
+fn auth(host: &str, key: &str) -> HostDhChap {
+    HostDhChap {
+        host: host.to_string(),
+        key: key.to_string(),
+        ctrlr_key: None,
+    }
+}
+
+#[tokio::test]
+async fn nvmf_share_host_auth() {
+    mayastor()
+        .spawn(async {
+            bdev_create("malloc:///host_auth0?size_mb=4").await.unwrap();
+            let mut bdev = UntypedBdev::lookup_by_name("host_auth0").unwrap();
+
+            // the host to authenticate must be an allowed host
+            let props = NvmfShareProps::new()
+                .with_host_auth(vec![auth(HOST, "host-auth-key")]);
+            let result = Pin::new(&mut bdev).share_nvmf(Some(props)).await;
+            assert!(matches!(
+                result,
+                Err(CoreError::ShareNvmf {
+                    source: NvmfError::InvalidHostAuth { .. }
+                })
+            ));
+            assert!(NvmfSubsystem::nqn_lookup("host_auth0").is_none());
+
+            // and its key must be in the keyring
+            let props = NvmfShareProps::new()
+                .with_allowed_hosts(vec![HOST.to_string()])
+                .with_host_auth(vec![auth(HOST, "host-auth-key")]);
+            let result = Pin::new(&mut bdev).share_nvmf(Some(props)).await;
+            assert!(matches!(
+                result,
+                Err(CoreError::ShareNvmf {
+                    source: NvmfError::KeyNotFound { .. }
+                })
+            ));
+            assert!(NvmfSubsystem::nqn_lookup("host_auth0").is_none());
+
+            let props = NvmfShareProps::new()
+                .with_allowed_hosts(vec![HOST.to_string()]);
+            Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+            let subsystem = NvmfSubsystem::nqn_lookup("host_auth0").unwrap();
+            assert!(subsystem.host_auth().is_empty());
+            assert!(subsystem
+                .apply_host_auth(&[auth(HOST, "host-auth-key")])
+                .await
+                .is_err());
+            assert!(subsystem.host_auth().is_empty());
+            assert_eq!(subsystem.allowed_hosts(), vec![HOST.to_string()]);
+
+            Pin::new(&mut bdev).unshare().await.unwrap();
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn nvmf_share_host_auth_key() {
+    let ms = mayastor();
+    add_key("host-auth-key1").await;
+    add_key("host-auth-key2").await;
+
+    ms.spawn(async {
+        bdev_create("malloc:///host_auth1?size_mb=4").await.unwrap();
+        let mut bdev = UntypedBdev::lookup_by_name("host_auth1").unwrap();
+
+        // the keys of the hosts are applied and kept with the share state
+        let props = NvmfShareProps::new()
+            .with_allowed_hosts(vec![HOST.to_string()])
+            .with_host_auth(vec![auth(HOST, "host-auth-key1")]);
+        let nqn = Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+        let subsystem = NvmfSubsystem::lookup_by_nqn(&nqn).unwrap();
+        assert_eq!(subsystem.host_auth(), vec![auth(HOST, "host-auth-key1")]);
+        assert_eq!(subsystem.allowed_hosts(), vec![HOST.to_string()]);
+        let share = persisted_share(&nqn).unwrap();
+        assert_eq!(share.host_auth, vec![auth(HOST, "host-auth-key1")]);
+
+        // as are the keys changed afterwards
+        subsystem
+            .apply_host_auth(&[auth(HOST, "host-auth-key2")])
+            .await
+            .unwrap();
+        assert_eq!(subsystem.host_auth(), vec![auth(HOST, "host-auth-key2")]);
+        assert_eq!(subsystem.allowed_hosts(), vec![HOST.to_string()]);
+        let share = persisted_share(&nqn).unwrap();
+        assert_eq!(share.host_auth, vec![auth(HOST, "host-auth-key2")]);
+
+        // the share state left behind by a crash requires the host to
+        // authenticate again
+        Pin::new(&mut bdev).unshare().await.unwrap();
+        assert!(persisted_share(&nqn).is_none());
+        let path = format!("{PTPL_DIR}/nvmf-share/{nqn}.json");
+        std::fs::create_dir_all(format!("{PTPL_DIR}/nvmf-share")).unwrap();
+        std::fs::write(&path, serde_json::to_vec(&share).unwrap()).unwrap();
+        Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+        let subsystem = NvmfSubsystem::lookup_by_nqn(&nqn).unwrap();
+        assert_eq!(subsystem.host_auth(), vec![auth(HOST, "host-auth-key2")]);
+
+        Pin::new(&mut bdev).unshare().await.unwrap();
+    })
+    .await;
+}
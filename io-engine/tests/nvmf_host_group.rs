@@ -0,0 +1,99 @@
+use io_engine::{
+    bdev_api::bdev_create,
+    core::{MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{HostGroup, NvmfError, NvmfSubsystem},
+};
+use std::{collections::BTreeSet, pin::Pin};
+
+pub mod common;
+use common::MayastorTest;
+
+const HOST1: &str = "nqn.2014-08.org.nvmexpress:uuid:\
+                     0d1c7b1e-1a10-4b8a-8c2f-5b6b3f1d0a01";
+const HOST2: &str = "nqn.2014-08.org.nvmexpress:uuid:\
+                     0d1c7b1e-1a10-4b8a-8c2f-5b6b3f1d0a02";
+const HOST3: &str = "nqn.2019-05.io.openebs:node3";
+
+fn hosts(hosts: &[&str]) -> BTreeSet<String> {
+    hosts.iter().map(|h| h.to_string()).collect()
+}
+
+fn allowed_hosts(bdev: &str) -> Vec<String> {
+    let mut hosts = NvmfSubsystem::nqn_lookup(bdev).unwrap().allowed_hosts();
+    hosts.sort();
+    hosts
+}
+
+#[tokio::test]
+async fn nvmf_share_with_host_group() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        assert!(matches!(
+            HostGroup {
+                name: "bad:name".to_string(),
+                hosts: BTreeSet::new(),
+            }
+            .create(),
+            Err(NvmfError::InvalidHostGroup { .. })
+        ));
+        assert!(matches!(
+            HostGroup {
+                name: "cluster".to_string(),
+                hosts: hosts(&["not-an-nqn"]),
+            }
+            .create(),
+            Err(NvmfError::InvalidNqn { .. })
+        ));
+
+        HostGroup {
+            name: "cluster".to_string(),
+            hosts: hosts(&[HOST1, HOST2]),
+        }
+        .create()
+        .unwrap();
+
+        bdev_create("malloc:///hg0?size_mb=64").await.unwrap();
+        let mut bdev = UntypedBdev::lookup_by_name("hg0").unwrap();
+
+        // an unknown group cannot be referred to
+        let props = NvmfShareProps::new()
+            .with_allowed_hosts(vec!["hostgroup:unknown".to_string()]);
+        assert!(Pin::new(&mut bdev).share_nvmf(Some(props)).await.is_err());
+
+        let props = NvmfShareProps::new().with_allowed_hosts(vec![
+            "hostgroup:cluster".to_string(),
+            HOST3.to_string(),
+        ]);
+        Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+        assert_eq!(allowed_hosts("hg0"), vec![HOST1, HOST2, HOST3]);
+
+        // the members of the group follow its updates
+        HostGroup::remove_hosts("cluster", &hosts(&[HOST2]))
+            .await
+            .unwrap();
+        assert_eq!(allowed_hosts("hg0"), vec![HOST1, HOST3]);
+
+        let group = HostGroup::add_hosts("cluster", &hosts(&[HOST2, HOST3]))
+            .await
+            .unwrap();
+        assert_eq!(group.hosts, hosts(&[HOST1, HOST2, HOST3]));
+        assert_eq!(allowed_hosts("hg0"), vec![HOST1, HOST2, HOST3]);
+
+        // a host allowed explicitly stays allowed once removed from the group
+        HostGroup::remove_hosts("cluster", &hosts(&[HOST3]))
+            .await
+            .unwrap();
+        assert_eq!(allowed_hosts("hg0"), vec![HOST1, HOST2, HOST3]);
+
+        assert!(matches!(
+            HostGroup::delete("cluster"),
+            Err(NvmfError::HostGroupInUse { .. })
+        ));
+
+        Pin::new(&mut bdev).unshare().await.unwrap();
+        HostGroup::delete("cluster").unwrap();
+        assert!(HostGroup::lookup("cluster").is_none());
+        assert!(HostGroup::list().is_empty());
+    })
+    .await;
+}
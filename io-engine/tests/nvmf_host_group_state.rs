@@ -0,0 +1,61 @@
+use io_engine::{core::MayastorCliArgs, subsys::HostGroup};
+use std::collections::{BTreeMap, BTreeSet};
+
+pub mod common;
+use common::MayastorTest;
+
+const PTPL_DIR: &str = "/tmp/io-engine-host-group-state";
+const HOST1: &str = "nqn.2019-05.io.openebs:group-state1";
+const HOST2: &str = "nqn.2019-05.io.openebs:group-state2";
+
+type Groups = BTreeMap<String, BTreeSet<String>>;
+
+fn persisted_groups() -> Groups {
+    let path = format!("{PTPL_DIR}/nvmf-host-groups.json");
+    serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn nvmf_host_group_state() {
+    std::fs::remove_dir_all(PTPL_DIR).ok();
+    std::fs::create_dir_all(PTPL_DIR).unwrap();
+
+    // the groups left behind by a previous run are loaded
+    let groups =
+        Groups::from([("before".to_string(), BTreeSet::from([HOST1.into()]))]);
+    std::fs::write(
+        format!("{PTPL_DIR}/nvmf-host-groups.json"),
+        serde_json::to_vec(&groups).unwrap(),
+    )
+    .unwrap();
+
+    let ms = MayastorTest::new(MayastorCliArgs {
+        ptpl_dir: Some(PTPL_DIR.to_string()),
+        ..Default::default()
+    });
+    ms.spawn(async {
+        let group = HostGroup::lookup("before").unwrap();
+        assert_eq!(group.hosts, BTreeSet::from([HOST1.to_string()]));
+
+        // and every change is persisted
+        HostGroup {
+            name: "after".to_string(),
+            hosts: BTreeSet::from([HOST2.to_string()]),
+        }
+        .create()
+        .unwrap();
+        HostGroup::add_hosts("before", &BTreeSet::from([HOST2.to_string()]))
+            .await
+            .unwrap();
+        HostGroup::delete("after").unwrap();
+
+        let groups = persisted_groups();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(
+            groups["before"],
+            BTreeSet::from([HOST1.to_string(), HOST2.to_string()])
+        );
+    })
+    .await;
+    std::fs::remove_dir_all(PTPL_DIR).ok();
+}
@@ -0,0 +1,87 @@
+use futures::FutureExt;
+use io_engine::{
+    bdev::{device_create, device_destroy},
+    bdev_api::bdev_create,
+    constants::NVME_NQN_PREFIX,
+    core::{MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    sleep::mayastor_sleep,
+    subsys::{
+        register_host_event_hook,
+        unregister_host_event_hook,
+        HostEvent,
+        HostEventKind,
+        NvmfListener,
+        NvmfTransport,
+    },
+};
+use once_cell::sync::Lazy;
+use std::{
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+pub mod common;
+use common::MayastorTest;
+
+static EVENTS: Lazy<Mutex<Vec<HostEvent>>> =
+    Lazy::new(|| Mutex::new(Vec::new()));
+
+fn recorded(kind: HostEventKind) -> Vec<HostEvent> {
+    EVENTS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|e| e.kind == kind)
+        .cloned()
+        .collect()
+}
+
+#[tokio::test]
+async fn nvmf_host_hooks() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        register_host_event_hook(
+            "test",
+            Arc::new(|event| {
+                async move { EVENTS.lock().unwrap().push(event) }.boxed_local()
+            }),
+        );
+
+        bdev_create("malloc:///hooks0?size_mb=4").await.unwrap();
+        let mut bdev = UntypedBdev::lookup_by_name("hooks0").unwrap();
+        let props = NvmfShareProps::new().with_listeners(vec![NvmfListener {
+            transport: NvmfTransport::Tcp,
+            address: Some("127.0.0.1".to_string()),
+            port: Some(8453),
+        }]);
+        Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+        let nqn = format!("{NVME_NQN_PREFIX}:hooks0");
+
+        // the hook is called as the host connects
+        let uri = format!("nvmf://127.0.0.1:8453/{nqn}");
+        device_create(&uri).await.unwrap();
+        mayastor_sleep(Duration::from_millis(500)).await.unwrap();
+        let connects = recorded(HostEventKind::Connect);
+        assert!(!connects.is_empty());
+        assert!(connects.iter().all(|e| e.nqn == nqn));
+        assert!(connects.iter().all(|e| e.bdev.as_deref() == Some("hooks0")));
+
+        // and as it disconnects
+        device_destroy(&uri).await.unwrap();
+        mayastor_sleep(Duration::from_millis(500)).await.unwrap();
+        assert_eq!(recorded(HostEventKind::Disconnect).len(), connects.len());
+
+        // but no longer once unregistered
+        assert!(unregister_host_event_hook("test"));
+        assert!(!unregister_host_event_hook("test"));
+        let count = EVENTS.lock().unwrap().len();
+        device_create(&uri).await.unwrap();
+        device_destroy(&uri).await.unwrap();
+        mayastor_sleep(Duration::from_millis(500)).await.unwrap();
+        assert_eq!(EVENTS.lock().unwrap().len(), count);
+
+        Pin::new(&mut bdev).unshare().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,167 @@
+use io_engine::{
+    bdev::{device_create, device_destroy, device_open},
+    bdev_api::bdev_create,
+    core::{CoreError, MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{
+        IdentifyOverrides,
+        NvmfError,
+        NvmfListener,
+        NvmfSubsystem,
+        NvmfTransport,
+    },
+};
+use once_cell::sync::OnceCell;
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+static MS: OnceCell<MayastorTest> = OnceCell::new();
+
+fn mayastor() -> &'static MayastorTest<'static> {
+    MS.get_or_init(|| MayastorTest::new(MayastorCliArgs::default()))
+}
+
+/// Fields of the identify controller data, as read by a host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Identify {
+    mdts: u8,
+    oncs: u16,
+    awun: u16,
+    awupf: u16,
+    acwu: u16,
+}
+
+/// Connects to the share of the given bdev and identifies its controller.
+async fn identify(
+    bdev: &str,
+    overrides: Option<IdentifyOverrides>,
+) -> Identify {
+    let mut bdev = UntypedBdev::lookup_by_name(bdev).unwrap();
+    let props = NvmfShareProps::new()
+        .with_identify(overrides)
+        .with_listeners(vec![NvmfListener {
+            transport: NvmfTransport::Tcp,
+            address: Some("127.0.0.1".to_string()),
+            port: Some(8462),
+        }]);
+    let nqn = Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+
+    let uri = format!("nvmf://127.0.0.1:8462/{nqn}");
+    let name = device_create(&uri).await.unwrap();
+    let handle = device_open(&name, false).unwrap().into_handle().unwrap();
+    let buf = handle.nvme_identify_ctrlr().await.unwrap();
+    let data = buf.as_slice();
+    let read16 =
+        |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]);
+    let identify = Identify {
+        mdts: data[77],
+        oncs: read16(520),
+        awun: read16(526),
+        awupf: read16(528),
+        acwu: read16(532),
+    };
+
+    drop(handle);
+    device_destroy(&uri).await.unwrap();
+    Pin::new(&mut bdev).unshare().await.unwrap();
+    identify
+}
+
+#[tokio::test]
+async fn nvmf_identify_overrides() {
+    mayastor()
+        .spawn(async {
+            bdev_create("malloc:///quirk0?size_mb=4").await.unwrap();
+
+            // lower the transfer size and hide write zeroes (ONCS bit 3)
+            let overrides = IdentifyOverrides {
+                mdts: Some(5),
+                awun: Some(7),
+                awupf: Some(0),
+                oncs_clear: 1 << 3,
+                ..Default::default()
+            };
+            let mut bdev = UntypedBdev::lookup_by_name("quirk0").unwrap();
+            let props =
+                NvmfShareProps::new().with_identify(Some(overrides.clone()));
+            Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+            let subsystem = NvmfSubsystem::nqn_lookup("quirk0").unwrap();
+            assert_eq!(subsystem.identify_overrides(), overrides);
+
+            // empty overrides remove them
+            subsystem
+                .set_identify_overrides(&IdentifyOverrides::default())
+                .unwrap();
+            assert!(subsystem.identify_overrides().is_empty());
+
+            // and they are forgotten when unsharing
+            subsystem.set_identify_overrides(&overrides).unwrap();
+            Pin::new(&mut bdev).unshare().await.unwrap();
+
+            // invalid overrides are refused before creating the subsystem
+            for overrides in [
+                IdentifyOverrides {
+                    awun: Some(1),
+                    awupf: Some(2),
+                    ..Default::default()
+                },
+                IdentifyOverrides {
+                    mdts: Some(32),
+                    ..Default::default()
+                },
+            ] {
+                let props =
+                    NvmfShareProps::new().with_identify(Some(overrides));
+                let result = Pin::new(&mut bdev).share_nvmf(Some(props)).await;
+                assert!(matches!(
+                    result,
+                    Err(CoreError::ShareNvmf {
+                        source: NvmfError::InvalidIdentifyOverride { .. }
+                    })
+                ));
+                assert!(NvmfSubsystem::nqn_lookup("quirk0").is_none());
+            }
+
+            Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+            let subsystem = NvmfSubsystem::nqn_lookup("quirk0").unwrap();
+            assert!(subsystem.identify_overrides().is_empty());
+            Pin::new(&mut bdev).unshare().await.unwrap();
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn nvmf_identify_host() {
+    mayastor()
+        .spawn(async {
+            bdev_create("malloc:///quirk1?size_mb=4").await.unwrap();
+            let device = identify("quirk1", None).await;
+            assert!(device.mdts == 0 || device.mdts > 3);
+
+            // the host sees the lowered transfer size, without write zeroes
+            let overrides = IdentifyOverrides {
+                mdts: Some(3),
+                awun: Some(0),
+                acwu: Some(0),
+                oncs_clear: 1 << 3,
+                ..Default::default()
+            };
+            let host = identify("quirk1", Some(overrides)).await;
+            assert_eq!(host.mdts, 3);
+            assert_eq!(host.oncs, device.oncs & !(1 << 3));
+            assert_eq!((host.awun, host.awupf, host.acwu), (0, 0, 0));
+
+            // while the atomic write units are never raised above those of
+            // the device
+            let overrides = IdentifyOverrides {
+                awun: Some(device.awun + 7),
+                awupf: Some(device.awun + 7),
+                acwu: Some(device.acwu + 1),
+                ..Default::default()
+            };
+            let host = identify("quirk1", Some(overrides)).await;
+            assert_eq!(host, device);
+        })
+        .await;
+}
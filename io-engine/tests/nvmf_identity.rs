@@ -0,0 +1,98 @@
+use io_engine::{
+    bdev_api::bdev_create,
+    constants::NVME_CONTROLLER_MODEL_ID,
+    core::{CoreError, MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{persisted_share, NvmfError, NvmfSubsystem},
+};
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+const PTPL_DIR: &str = "/tmp/io-engine-identity";
+
+#[tokio::test]
+async fn nvmf_share_identity() {
+    std::fs::remove_dir_all(PTPL_DIR).ok();
+    std::fs::create_dir_all(PTPL_DIR).unwrap();
+    let ms = MayastorTest::new(MayastorCliArgs {
+        ptpl_dir: Some(PTPL_DIR.to_string()),
+        ..Default::default()
+    });
+    ms.spawn(async {
+        bdev_create("malloc:///ident0?size_mb=4").await.unwrap();
+        bdev_create("malloc:///ident1?size_mb=4").await.unwrap();
+
+        // explicit serial and model numbers
+        let mut bdev = UntypedBdev::lookup_by_name("ident0").unwrap();
+        let props = NvmfShareProps::new()
+            .with_serial(Some("SN-IDENT-0".to_string()))
+            .with_model(Some("Multipath Model".to_string()));
+        let nqn = Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+        let info = NvmfSubsystem::nqn_lookup("ident0").unwrap().info();
+        assert_eq!(info.serial, "SN-IDENT-0");
+        assert_eq!(info.model, "Multipath Model");
+
+        // they are kept with the share state
+        let share = persisted_share(&nqn).unwrap();
+        assert_eq!(share.serial.as_deref(), Some("SN-IDENT-0"));
+        assert_eq!(share.model.as_deref(), Some("Multipath Model"));
+
+        // and apply again when the share state is left behind by a crash
+        Pin::new(&mut bdev).unshare().await.unwrap();
+        assert!(persisted_share(&nqn).is_none());
+        std::fs::create_dir_all(format!("{PTPL_DIR}/nvmf-share")).unwrap();
+        std::fs::write(
+            format!("{PTPL_DIR}/nvmf-share/{nqn}.json"),
+            serde_json::to_vec(&share).unwrap(),
+        )
+        .unwrap();
+        Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+        let info = NvmfSubsystem::nqn_lookup("ident0").unwrap().info();
+        assert_eq!(info.serial, "SN-IDENT-0");
+        assert_eq!(info.model, "Multipath Model");
+
+        // or the derived ones
+        let mut bdev = UntypedBdev::lookup_by_name("ident1").unwrap();
+        Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+        let info = NvmfSubsystem::nqn_lookup("ident1").unwrap().info();
+        assert_ne!(info.serial, "SN-IDENT-0");
+        assert_eq!(info.model, NVME_CONTROLLER_MODEL_ID);
+        Pin::new(&mut bdev).unshare().await.unwrap();
+
+        // invalid numbers are refused before creating the subsystem
+        for props in [
+            NvmfShareProps::new().with_serial(Some("x".repeat(21))),
+            NvmfShareProps::new().with_model(Some("model\n".to_string())),
+            NvmfShareProps::new().with_serial(Some(String::new())),
+        ] {
+            let result = Pin::new(&mut bdev).share_nvmf(Some(props)).await;
+            assert!(matches!(
+                result,
+                Err(CoreError::ShareNvmf {
+                    source: NvmfError::InvalidIdentity { .. }
+                })
+            ));
+            assert!(NvmfSubsystem::nqn_lookup("ident1").is_none());
+        }
+
+        // a subsystem failing to be configured is not left behind
+        let props = NvmfShareProps::new()
+            .with_serial(Some("SN-IDENT-1".to_string()))
+            .with_range(Some((10, 5)));
+        let result = Pin::new(&mut bdev).share_nvmf(Some(props)).await;
+        assert!(matches!(
+            result,
+            Err(CoreError::ShareNvmf {
+                source: NvmfError::Subsystem { .. }
+            })
+        ));
+        assert!(NvmfSubsystem::nqn_lookup("ident1").is_none());
+        Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+        Pin::new(&mut bdev).unshare().await.unwrap();
+
+        let mut bdev = UntypedBdev::lookup_by_name("ident0").unwrap();
+        Pin::new(&mut bdev).unshare().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,110 @@
+pub mod common;
+
+use std::time::Duration;
+
+use common::{
+    compose::{
+        initiator::Initiator,
+        rpc::v1::GrpcConnect,
+        Binary,
+        Builder,
+        ContainerSpec,
+    },
+    file_io::DataSize,
+    fio::{FioBuilder, FioJobBuilder},
+    nexus::NexusBuilder,
+    pool::PoolBuilder,
+    replica::ReplicaBuilder,
+};
+
+/// Environment variable naming the image of the initiator container, which
+/// must provide nvme-cli and fio and keep running, e.g. with `sleep infinity`
+/// as its command.
+const INITIATOR_IMAGE: &str = "NVME_INITIATOR_IMAGE";
+
+/// Connects to a published nexus with the kernel initiator of a separate
+/// container, checks the ANA state of the path and verifies data written
+/// through it.
+#[tokio::test]
+async fn nvmf_initiator() {
+    let Ok(image) = std::env::var(INITIATOR_IMAGE) else {
+        println!("{INITIATOR_IMAGE} is not set, skipping the test");
+        return;
+    };
+    common::composer_init();
+
+    let test = Builder::new()
+        .name("cargo-test")
+        .network("10.1.0.0/16")
+        .unwrap()
+        .add_container_bin(
+            "ms_0",
+            Binary::from_dbg("io-engine").with_args(vec!["-l", "1"]),
+        )
+        .add_container_spec(ContainerSpec::from_image("initiator", &image))
+        .with_clean(true)
+        .build()
+        .await
+        .unwrap();
+
+    let ms_0 = GrpcConnect::new(&test)
+        .grpc_handle_shared("ms_0")
+        .await
+        .unwrap();
+
+    let mut pool = PoolBuilder::new(ms_0.clone())
+        .with_name("pool0")
+        .with_new_uuid()
+        .with_malloc("mem0", 60);
+    let mut repl = ReplicaBuilder::new(ms_0.clone())
+        .with_pool(&pool)
+        .with_name("r0")
+        .with_new_uuid()
+        .with_size_mb(50)
+        .with_thin(false);
+    pool.create().await.unwrap();
+    repl.create().await.unwrap();
+
+    let mut nex = NexusBuilder::new(ms_0)
+        .with_name("nexus0")
+        .with_new_uuid()
+        .with_size_mb(50)
+        .with_replica(&repl);
+    nex.create().await.unwrap();
+    nex.publish().await.unwrap();
+
+    let target = nex.nvmf_location().addr.ip().to_string();
+    let initiator = Initiator::new("initiator");
+    initiator.connect(&target, 8420, &nex.nqn()).unwrap();
+    let device = initiator
+        .wait_device(&nex.nqn(), Duration::from_secs(10))
+        .unwrap();
+
+    if initiator.multipath_enabled().unwrap() {
+        initiator
+            .wait_ana_state(
+                &nex.nqn(),
+                &target,
+                "optimized",
+                Duration::from_secs(10),
+            )
+            .unwrap();
+    }
+
+    let fio = FioBuilder::new()
+        .with_job(
+            FioJobBuilder::new()
+                .with_filename(&device)
+                .with_rw("write")
+                .with_size(DataSize::from_mb(8))
+                .with_verify("crc32c")
+                .with_do_verify(true)
+                .with_verify_fatal(true)
+                .build(),
+        )
+        .build();
+    initiator.fio(&fio).unwrap();
+
+    initiator.disconnect(&nex.nqn()).unwrap();
+    assert!(initiator.device(&nex.nqn()).unwrap().is_none());
+}
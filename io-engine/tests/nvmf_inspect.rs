@@ -0,0 +1,69 @@
+use io_engine::{
+    bdev::{device_create, device_destroy},
+    bdev_api::bdev_create,
+    constants::NVME_NQN_PREFIX,
+    core::{MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{nvmf_subsystems, NvmfListener, NvmfSubsystem, NvmfTransport},
+};
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+const HOST: &str = "nqn.2019-05.io.openebs:inspect-host";
+
+#[tokio::test]
+async fn nvmf_subsystem_inspect() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        bdev_create("malloc:///inspect0?size_mb=4").await.unwrap();
+        let mut bdev = UntypedBdev::lookup_by_name("inspect0").unwrap();
+
+        let props = NvmfShareProps::new()
+            .with_allowed_hosts(vec![HOST.to_string()])
+            .with_listeners(vec![NvmfListener {
+                transport: NvmfTransport::Tcp,
+                address: Some("127.0.0.1".to_string()),
+                port: Some(8447),
+            }]);
+        Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+
+        let uri = format!(
+            "nvmf://127.0.0.1:8447/{NVME_NQN_PREFIX}:inspect0?hostnqn={HOST}"
+        );
+        device_create(&uri).await.unwrap();
+
+        let nqn = NvmfSubsystem::nqn_lookup("inspect0").unwrap().get_nqn();
+        let info = nvmf_subsystems()
+            .into_iter()
+            .find(|s| s.nqn == nqn)
+            .unwrap();
+        assert!(!info.allow_any_host);
+        assert_eq!(info.allowed_hosts, vec![HOST.to_string()]);
+        assert_eq!(info.listeners.len(), 1);
+        assert_eq!(info.listeners[0].port, Some(8447));
+        assert!(!info.serial.is_empty());
+        assert!(!info.model.is_empty());
+
+        assert_eq!(info.namespaces.len(), 1);
+        assert_eq!(info.namespaces[0].nsid, 1);
+        assert_eq!(info.namespaces[0].bdev.as_deref(), Some("inspect0"));
+
+        // the admin and I/O qpairs of the connected host
+        assert_eq!(info.controllers.len(), 1);
+        assert_eq!(info.controllers[0].hostnqn, HOST);
+        assert!(info.controllers[0].num_qpairs >= 2);
+        assert!(info.controllers[0].connected_at.is_some());
+        assert!(info.controllers[0].connection_age_secs.is_some());
+
+        // as seen from the shared bdev
+        let controllers = bdev.nvmf_controllers();
+        assert_eq!(controllers.len(), 1);
+        assert_eq!(controllers[0].cntlid, info.controllers[0].cntlid);
+
+        device_destroy(&uri).await.unwrap();
+        Pin::new(&mut bdev).unshare().await.unwrap();
+        assert!(nvmf_subsystems().iter().all(|s| s.nqn != nqn));
+    })
+    .await;
+}
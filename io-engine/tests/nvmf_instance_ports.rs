@@ -0,0 +1,46 @@
+use io_engine::{
+    bdev_api::bdev_create,
+    core::{lock_state_path, MayastorCliArgs, Share, UntypedBdev},
+    subsys::{Config, NvmfSubsystem},
+};
+use std::{path::Path, pin::Pin};
+
+pub mod common;
+use common::MayastorTest;
+
+#[tokio::test]
+async fn nvmf_instance_ports() {
+    // ports and state paths of their own, so that another instance may run
+    // alongside
+    let ms = MayastorTest::new(MayastorCliArgs {
+        nvmf_nexus_port: Some(4521),
+        nvmf_replica_port: Some(8520),
+        shm_id: Some(7),
+        ptpl_dir: Some("/tmp/instance_ports".to_string()),
+        nvmf_port_state: Some("/tmp/instance_ports.json".to_string()),
+        ..Default::default()
+    });
+    ms.spawn(async {
+        let opts = &Config::get().nexus_opts;
+        assert_eq!(opts.nvmf_nexus_port, 4521);
+        assert_eq!(opts.nvmf_replica_port, 8520);
+
+        // the state paths are locked for as long as the instance runs
+        for lock in
+            ["/tmp/instance_ports/.lock", "/tmp/instance_ports.json.lock"]
+        {
+            assert!(!lock_state_path(Path::new(lock)).unwrap());
+        }
+
+        bdev_create("malloc:///instance0?size_mb=4").await.unwrap();
+        let mut bdev = UntypedBdev::lookup_by_name("instance0").unwrap();
+        Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+        let endpoints = NvmfSubsystem::nqn_lookup("instance0")
+            .unwrap()
+            .uri_endpoints()
+            .unwrap();
+        assert!(endpoints[0].contains(":8520/"), "{endpoints:?}");
+        Pin::new(&mut bdev).unshare().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,247 @@
+use io_engine::{
+    bdev::{
+        device_create,
+        device_destroy,
+        device_open,
+        nexus::{nexus_create, nexus_lookup_mut},
+    },
+    bdev_api::bdev_create,
+    constants::NVME_NQN_PREFIX,
+    core::{MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{
+        nvmf_io_stats,
+        HostIoCounters,
+        HostIoRates,
+        InFlightCommands,
+        NvmfHostIoStats,
+        NvmfListener,
+        NvmfSubsystem,
+        NvmfSubsystemIoStats,
+        NvmfTransport,
+        OutstandingCommands,
+        TargetIoCounters,
+    },
+};
+use once_cell::sync::OnceCell;
+use spdk_rs::DmaBuf;
+use std::{pin::Pin, time::Duration};
+
+pub mod common;
+use common::MayastorTest;
+
+const HOST: &str = "nqn.2019-05.io.openebs:io-stats-host";
+
+static MS: OnceCell<MayastorTest> = OnceCell::new();
+
+fn mayastor() -> &'static MayastorTest<'static> {
+    MS.get_or_init(|| MayastorTest::new(MayastorCliArgs::default()))
+}
+
+fn listener(port: u16) -> NvmfShareProps {
+    NvmfShareProps::new()
+        .with_allowed_hosts(vec![HOST.to_string()])
+        .with_listeners(vec![NvmfListener {
+            transport: NvmfTransport::Tcp,
+            address: Some("127.0.0.1".to_string()),
+            port: Some(port),
+        }])
+}
+
+#[tokio::test]
+async fn nvmf_io_stats_per_host() {
+    mayastor()
+        .spawn(async {
+            bdev_create("malloc:///iostats0?size_mb=4").await.unwrap();
+            let mut bdev = UntypedBdev::lookup_by_name("iostats0").unwrap();
+            Pin::new(&mut bdev)
+                .share_nvmf(Some(listener(8454)))
+                .await
+                .unwrap();
+            let subsystem = NvmfSubsystem::nqn_lookup("iostats0").unwrap();
+
+            // no host is connected yet
+            let stats = subsystem.io_stats().await;
+            assert_eq!(stats.bdev.as_deref(), Some("iostats0"));
+            assert!(stats.hosts.is_empty());
+
+            let uri = format!(
+                "nvmf://127.0.0.1:8454/{NVME_NQN_PREFIX}:iostats0?hostnqn={HOST}"
+            );
+            let name = device_create(&uri).await.unwrap();
+            let handle =
+                device_open(&name, false).unwrap().into_handle().unwrap();
+            let buf =
+                DmaBuf::new(4096, handle.get_device().alignment()).unwrap();
+            let before = subsystem.io_stats().await.io;
+            handle.write_at(0, &buf).await.unwrap();
+
+            // the write went through the subsystem, and nothing is left in
+            // flight for the host
+            let stats = subsystem.io_stats().await;
+            assert_eq!(stats.io.num_write_ops, before.num_write_ops + 1);
+            assert_eq!(stats.io.bytes_written, before.bytes_written + 4096);
+            assert!(stats.outstanding.is_drained());
+            assert_eq!(stats.hosts.len(), 1);
+            assert_eq!(stats.hosts[0].hostnqn, HOST);
+            assert_eq!(stats.hosts[0].in_flight, InFlightCommands::default());
+            // the hosts of a bdev other than a nexus are not counted
+            assert_eq!(stats.hosts[0].io, None);
+
+            // the I/O submitted to the bdev without the target is left out
+            let local = device_open("iostats0", true)
+                .unwrap()
+                .into_handle()
+                .unwrap();
+            local.write_at(4096, &buf).await.unwrap();
+            assert_eq!(subsystem.io_stats().await.io, stats.io);
+            drop(local);
+
+            let all = nvmf_io_stats().await;
+            assert!(all.iter().any(|s| s.nqn == stats.nqn));
+
+            drop(handle);
+            device_destroy(&uri).await.unwrap();
+            Pin::new(&mut bdev).unshare().await.unwrap();
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn nvmf_io_stats_nexus_hosts() {
+    mayastor()
+        .spawn(async {
+            nexus_create(
+                "iostats_nexus",
+                32 * 1024 * 1024,
+                None,
+                &["malloc:///iostats1?size_mb=64".into()],
+            )
+            .await
+            .unwrap();
+            let mut nexus = nexus_lookup_mut("iostats_nexus").unwrap();
+            nexus
+                .as_mut()
+                .share_nvmf(Some(listener(8466)))
+                .await
+                .unwrap();
+            let subsystem = NvmfSubsystem::nqn_lookup("iostats_nexus").unwrap();
+            let nqn = subsystem.get_nqn();
+
+            let uri = format!("nvmf://127.0.0.1:8466/{nqn}?hostnqn={HOST}");
+            let name = device_create(&uri).await.unwrap();
+            let handle =
+                device_open(&name, true).unwrap().into_handle().unwrap();
+            let buf =
+                DmaBuf::new(4096, handle.get_device().alignment()).unwrap();
+            let mut rbuf =
+                DmaBuf::new(8192, handle.get_device().alignment()).unwrap();
+
+            let host_io = |stats: &NvmfSubsystemIoStats| {
+                assert_eq!(stats.hosts.len(), 1);
+                stats.hosts[0].io.unwrap()
+            };
+            let before = host_io(&subsystem.io_stats().await);
+
+            for i in 0 .. 3 {
+                handle.write_at(i * 4096, &buf).await.unwrap();
+            }
+            for _ in 0 .. 2 {
+                handle.read_at(0, &mut rbuf).await.unwrap();
+            }
+
+            // the reads and writes of the host are counted exactly
+            let after = host_io(&subsystem.io_stats().await);
+            assert_eq!(after.num_write_ops - before.num_write_ops, 3);
+            assert_eq!(after.bytes_written - before.bytes_written, 3 * 4096);
+            assert_eq!(after.num_read_ops - before.num_read_ops, 2);
+            assert_eq!(after.bytes_read - before.bytes_read, 2 * 8192);
+
+            // the I/O submitted to the nexus without the target is not
+            // counted for the host
+            let local = device_open("iostats_nexus", true)
+                .unwrap()
+                .into_handle()
+                .unwrap();
+            local.write_at(0, &buf).await.unwrap();
+            assert_eq!(host_io(&subsystem.io_stats().await), after);
+            drop(local);
+
+            drop(handle);
+            device_destroy(&uri).await.unwrap();
+            nexus_lookup_mut("iostats_nexus")
+                .unwrap()
+                .destroy()
+                .await
+                .unwrap();
+        })
+        .await;
+}
+
+#[test]
+fn nvmf_io_stats_host_rates() {
+    let sample = |cntlid: u16, io: HostIoCounters| NvmfSubsystemIoStats {
+        nqn: "nqn".to_string(),
+        bdev: None,
+        io: TargetIoCounters::default(),
+        outstanding: OutstandingCommands::default(),
+        hosts: vec![NvmfHostIoStats {
+            cntlid,
+            hostnqn: HOST.to_string(),
+            io: Some(io),
+            in_flight: InFlightCommands::default(),
+        }],
+    };
+    let before = sample(
+        1,
+        HostIoCounters {
+            num_read_ops: 10,
+            num_write_ops: 20,
+            bytes_read: 4096,
+            bytes_written: 8192,
+        },
+    );
+    let after = sample(
+        1,
+        HostIoCounters {
+            num_read_ops: 30,
+            num_write_ops: 20,
+            bytes_read: 4096 * 21,
+            bytes_written: 8192,
+        },
+    );
+
+    let rates =
+        HostIoRates::from_samples(&[before], &[after], Duration::from_secs(2));
+    assert_eq!(
+        rates,
+        vec![HostIoRates {
+            nqn: "nqn".to_string(),
+            cntlid: 1,
+            hostnqn: HOST.to_string(),
+            read_iops: 10,
+            write_iops: 0,
+            read_bps: 40960,
+            write_bps: 0,
+        }]
+    );
+
+    // a controller connected in between counts from zero, and an idle one
+    // is left out
+    let idle = sample(2, HostIoCounters::default());
+    let busy = sample(
+        3,
+        HostIoCounters {
+            num_write_ops: 5,
+            bytes_written: 5 * 4096,
+            ..Default::default()
+        },
+    );
+    let rates = HostIoRates::from_samples(
+        &[idle.clone()],
+        &[idle, busy],
+        Duration::from_secs(1),
+    );
+    assert_eq!(rates.len(), 1);
+    assert_eq!((rates[0].cntlid, rates[0].write_iops), (3, 5));
+    assert_eq!(rates[0].write_bps, 5 * 4096);
+}
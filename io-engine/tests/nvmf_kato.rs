@@ -0,0 +1,127 @@
+use io_engine::{
+    bdev::{device_create, device_destroy},
+    bdev_api::bdev_create,
+    constants::NVME_NQN_PREFIX,
+    core::{CoreError, MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    sleep::mayastor_sleep,
+    subsys::{NvmfError, NvmfListener, NvmfSubsystem, NvmfTransport},
+};
+use once_cell::sync::OnceCell;
+use std::{
+    pin::Pin,
+    time::{Duration, Instant},
+};
+
+pub mod common;
+use common::MayastorTest;
+
+static MS: OnceCell<MayastorTest> = OnceCell::new();
+
+fn mayastor() -> &'static MayastorTest<'static> {
+    MS.get_or_init(|| {
+        // the hosts ask for no keep alive timeout and send no keep alive
+        // commands, leaving it to the target
+        std::env::set_var("NVME_KATO", "0ms");
+        MayastorTest::new(MayastorCliArgs {
+            nvmf_replica_kato_ms: Some(20_000),
+            ..Default::default()
+        })
+    })
+}
+
+#[tokio::test]
+async fn nvmf_kato_policy() {
+    mayastor()
+        .spawn(async {
+            bdev_create("malloc:///kato0?size_mb=4").await.unwrap();
+            bdev_create("malloc:///kato1?size_mb=4").await.unwrap();
+
+            // the timeout of the replicas applies to the shares without one
+            let mut bdev = UntypedBdev::lookup_by_name("kato0").unwrap();
+            Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+            let kato = NvmfSubsystem::nqn_lookup("kato0").unwrap().kato();
+            assert_eq!(kato.kato_ms, Some(20_000));
+            assert!(!kato.kato_override);
+            Pin::new(&mut bdev).unshare().await.unwrap();
+
+            // a share may override it, and its controllers are given it
+            let mut bdev = UntypedBdev::lookup_by_name("kato1").unwrap();
+            let props = NvmfShareProps::new()
+                .with_kato(Some(7_000))
+                .with_listeners(vec![NvmfListener {
+                    transport: NvmfTransport::Tcp,
+                    address: Some("127.0.0.1".to_string()),
+                    port: Some(8448),
+                }]);
+            Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+            let subsystem = NvmfSubsystem::nqn_lookup("kato1").unwrap();
+            assert_eq!(subsystem.kato().kato_ms, Some(7_000));
+            assert!(subsystem.kato().kato_override);
+
+            let uri = format!("nvmf://127.0.0.1:8448/{NVME_NQN_PREFIX}:kato1");
+            device_create(&uri).await.unwrap();
+            let controllers = subsystem.controllers();
+            assert_eq!(controllers.len(), 1);
+            assert_eq!(controllers[0].kato_ms, 7_000);
+            device_destroy(&uri).await.unwrap();
+
+            // back to the default of the replicas
+            subsystem.set_kato(None).unwrap();
+            assert_eq!(subsystem.kato().kato_ms, Some(20_000));
+            assert!(matches!(
+                subsystem.set_kato(Some(10)),
+                Err(NvmfError::InvalidKato { .. })
+            ));
+            Pin::new(&mut bdev).unshare().await.unwrap();
+
+            // invalid timeouts are refused before creating the subsystem
+            let props = NvmfShareProps::new().with_kato(Some(0));
+            let result = Pin::new(&mut bdev).share_nvmf(Some(props)).await;
+            assert!(matches!(
+                result,
+                Err(CoreError::ShareNvmf {
+                    source: NvmfError::InvalidKato { .. }
+                })
+            ));
+            assert!(NvmfSubsystem::nqn_lookup("kato1").is_none());
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn nvmf_kato_timeout() {
+    mayastor()
+        .spawn(async {
+            bdev_create("malloc:///kato2?size_mb=4").await.unwrap();
+            let mut bdev = UntypedBdev::lookup_by_name("kato2").unwrap();
+            let props = NvmfShareProps::new()
+                .with_kato(Some(2_000))
+                .with_listeners(vec![NvmfListener {
+                    transport: NvmfTransport::Tcp,
+                    address: Some("127.0.0.1".to_string()),
+                    port: Some(8463),
+                }]);
+            Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+            let subsystem = NvmfSubsystem::nqn_lookup("kato2").unwrap();
+
+            // the host asked for no timeout, yet its controller is given one
+            let uri = format!("nvmf://127.0.0.1:8463/{NVME_NQN_PREFIX}:kato2");
+            let start = Instant::now();
+            device_create(&uri).await.unwrap();
+            let controllers = subsystem.controllers();
+            assert_eq!(controllers.len(), 1);
+            assert_eq!(controllers[0].kato_ms, 2_000);
+            let cntlid = controllers[0].cntlid;
+
+            // and is disconnected once it expires, without keep alives
+            while subsystem.controllers().iter().any(|c| c.cntlid == cntlid) {
+                assert!(start.elapsed() < Duration::from_secs(10));
+                mayastor_sleep(Duration::from_millis(100)).await.unwrap();
+            }
+            assert!(start.elapsed() >= Duration::from_millis(1_500));
+
+            device_destroy(&uri).await.ok();
+            Pin::new(&mut bdev).unshare().await.unwrap();
+        })
+        .await;
+}
@@ -0,0 +1,104 @@
+use io_engine::{
+    bdev_api::bdev_create,
+    core::{CoreError, MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{NvmfError, NvmfListener, NvmfSubsystem, NvmfTransport},
+};
+use std::{net::TcpStream, pin::Pin};
+
+pub mod common;
+use common::MayastorTest;
+
+const PTPL_DIR: &str = "/tmp/io-engine-listeners";
+const LISTENERS_FILE: &str = "/tmp/io-engine-listeners/nvmf-listeners.json";
+
+/// Whether the target accepts connections on the given local port.
+fn listening(port: u16) -> bool {
+    TcpStream::connect(("127.0.0.1", port)).is_ok()
+}
+
+fn listener(address: &str, port: u16) -> NvmfListener {
+    NvmfListener {
+        transport: NvmfTransport::Tcp,
+        address: Some(address.to_string()),
+        port: Some(port),
+    }
+}
+
+#[tokio::test]
+async fn nvmf_share_listeners() {
+    // an extra listener persisted before a restart for the first share
+    std::fs::remove_dir_all(PTPL_DIR).ok();
+    std::fs::create_dir_all(PTPL_DIR).unwrap();
+    std::fs::write(
+        LISTENERS_FILE,
+        r#"[{"kind": "replica", "transport": "tcp", "address": "127.0.0.1",
+            "port": 8440, "nqns": ["nqn.2019-05.io.openebs:listeners0"]}]"#,
+    )
+    .unwrap();
+
+    let ms = MayastorTest::new(MayastorCliArgs {
+        ptpl_dir: Some(PTPL_DIR.to_string()),
+        ..Default::default()
+    });
+    ms.spawn(async {
+        // it is listened on again as soon as the target starts
+        assert!(listening(8440));
+
+        for name in ["listeners0", "listeners1", "listeners2"] {
+            bdev_create(&format!("malloc:///{name}?size_mb=4"))
+                .await
+                .unwrap();
+        }
+
+        // an explicit address only
+        let mut bdev = UntypedBdev::lookup_by_name("listeners0").unwrap();
+        let props = NvmfShareProps::new()
+            .with_listeners(vec![listener("127.0.0.1", 8440)]);
+        Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+        let endpoints = NvmfSubsystem::nqn_lookup("listeners0")
+            .unwrap()
+            .uri_endpoints()
+            .unwrap();
+        assert_eq!(endpoints.len(), 1);
+        assert!(endpoints[0].starts_with("nvmf://127.0.0.1:8440/"));
+
+        // an explicit address, which the target already listens on, and the
+        // address of the target
+        let mut bdev = UntypedBdev::lookup_by_name("listeners1").unwrap();
+        let props = NvmfShareProps::new()
+            .with_listeners(vec![listener("127.0.0.1", 8440)])
+            .with_transports(vec![NvmfTransport::Tcp]);
+        Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+        let endpoints = NvmfSubsystem::nqn_lookup("listeners1")
+            .unwrap()
+            .uri_endpoints()
+            .unwrap();
+        assert_eq!(endpoints.len(), 2);
+        assert!(endpoints[0].starts_with("nvmf://127.0.0.1:8440/"));
+
+        // the address must be a valid IPv4 address
+        let mut bdev = UntypedBdev::lookup_by_name("listeners2").unwrap();
+        let props = NvmfShareProps::new()
+            .with_listeners(vec![listener("not-an-address", 8440)]);
+        let result = Pin::new(&mut bdev).share_nvmf(Some(props)).await;
+        assert!(matches!(
+            result,
+            Err(CoreError::ShareNvmf {
+                source: NvmfError::Transport { .. }
+            })
+        ));
+        assert!(NvmfSubsystem::nqn_lookup("listeners2").is_none());
+
+        // the extra listener is kept until the last share using it is gone
+        let persisted = || std::fs::read_to_string(LISTENERS_FILE).unwrap();
+        assert!(persisted().contains("listeners1"));
+        for name in ["listeners0", "listeners1"] {
+            assert!(listening(8440));
+            let mut bdev = UntypedBdev::lookup_by_name(name).unwrap();
+            Pin::new(&mut bdev).unshare().await.unwrap();
+        }
+        assert!(!listening(8440));
+        assert!(!persisted().contains("8440"));
+    })
+    .await;
+}
@@ -0,0 +1,125 @@
+use io_engine::{
+    bdev::{device_create, device_destroy, device_open},
+    bdev_api::bdev_create,
+    core::{MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{
+        import_subsystem,
+        NvmfError,
+        NvmfListener,
+        NvmfSubsystem,
+        NvmfTransport,
+        SubsystemHandle,
+    },
+};
+use once_cell::sync::OnceCell;
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+const PTPL_DIR: &str = "/tmp/io-engine-migrate";
+const NQN: &str = "nqn.2019-05.io.openebs:migrate0";
+const HOST: &str = "nqn.2019-05.io.openebs:migrate-host";
+const RESV_KEY: u64 = 0xfeed;
+
+static MS: OnceCell<MayastorTest> = OnceCell::new();
+
+fn mayastor() -> &'static MayastorTest<'static> {
+    MS.get_or_init(|| {
+        std::fs::remove_dir_all(PTPL_DIR).ok();
+        MayastorTest::new(MayastorCliArgs {
+            ptpl_dir: Some(PTPL_DIR.to_string()),
+            ..Default::default()
+        })
+    })
+}
+
+#[tokio::test]
+async fn nvmf_migrate() {
+    mayastor()
+        .spawn(async {
+            bdev_create("malloc:///migrate0?size_mb=4").await.unwrap();
+            let mut bdev = UntypedBdev::lookup_by_name("migrate0").unwrap();
+
+            let props = NvmfShareProps::new()
+                .with_nqn(Some(NQN.to_string()))
+                .with_serial(Some("MIGRATE0".to_string()))
+                .with_allowed_hosts(vec![HOST.to_string()])
+                .with_listeners(vec![NvmfListener {
+                    transport: NvmfTransport::Tcp,
+                    address: Some("127.0.0.1".to_string()),
+                    port: Some(8467),
+                }]);
+            Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+
+            // the host connects and takes a reservation
+            let uri = format!("nvmf://127.0.0.1:8467/{NQN}?hostnqn={HOST}");
+            let dev = device_create(&uri).await.unwrap();
+            let handle =
+                device_open(&dev, false).unwrap().into_handle().unwrap();
+            handle.nvme_resv_register(0, RESV_KEY, 0, 0).await.unwrap();
+            handle.nvme_resv_acquire(RESV_KEY, 0, 0, 1).await.unwrap();
+            let hostid =
+                uuid::Uuid::from_bytes(handle.host_id().await.unwrap())
+                    .to_string();
+            drop(handle);
+            device_destroy(&uri).await.unwrap();
+
+            // the exported subsystem is left paused
+            let subsystem = NvmfSubsystem::lookup_by_nqn(NQN).unwrap();
+            let export = subsystem.export().await.unwrap();
+            assert!(matches!(
+                NvmfSubsystem::lookup_by_nqn(NQN).unwrap().into_handle(),
+                Ok(SubsystemHandle::Paused(_))
+            ));
+            assert_eq!(export.bdev, "migrate0");
+            assert_eq!(export.serial, "MIGRATE0");
+            assert_eq!(export.allowed_hosts, vec![HOST.to_string()]);
+            assert_eq!(subsystem.export().await.unwrap(), export);
+            let exported = &export.reservations[0];
+            assert_eq!(exported.registrants.len(), 1);
+            assert_eq!(exported.registrants[0].hostid, hostid);
+            assert_eq!(exported.registrants[0].rkey, RESV_KEY);
+            assert_eq!(exported.holder.as_deref(), Some(hostid.as_str()));
+            assert_eq!((exported.rtype, exported.crkey), (1, RESV_KEY));
+
+            // the bdev must exist to import the subsystem
+            Pin::new(&mut bdev).unshare().await.unwrap();
+            let mut missing = export.clone();
+            missing.bdev = "migrate1".to_string();
+            assert!(matches!(
+                import_subsystem(&missing, None).await,
+                Err(NvmfError::ImportBdevNotFound { .. })
+            ));
+
+            // the reservations are loaded along with the namespace, and
+            // persisted from then on
+            let subsystem = import_subsystem(&export, None).await.unwrap();
+            assert_eq!(subsystem.get_nqn(), NQN);
+            assert!(matches!(
+                NvmfSubsystem::lookup_by_nqn(NQN).unwrap().into_handle(),
+                Ok(SubsystemHandle::Active(_))
+            ));
+            let imported = subsystem.export().await.unwrap();
+            subsystem.resume().await.unwrap();
+            assert_eq!(imported.serial, export.serial);
+            assert_eq!(imported.allowed_hosts, export.allowed_hosts);
+            let restored = &imported.reservations[0];
+            assert_eq!(restored.registrants, exported.registrants);
+            assert_eq!(restored.holder, exported.holder);
+            assert_eq!((restored.rtype, restored.crkey), (1, RESV_KEY));
+            assert!(restored.ptpl_activated);
+
+            // the host holds the reservation again once reconnected
+            let dev = device_create(&uri).await.unwrap();
+            let handle =
+                device_open(&dev, false).unwrap().into_handle().unwrap();
+            assert!(handle.nvme_resv_acquire(0x1, 0, 0, 1).await.is_err());
+            handle.nvme_resv_acquire(RESV_KEY, 0, 0, 1).await.unwrap();
+            drop(handle);
+            device_destroy(&uri).await.unwrap();
+
+            Pin::new(&mut bdev).unshare().await.unwrap();
+        })
+        .await;
+}
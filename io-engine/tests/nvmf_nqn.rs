@@ -0,0 +1,85 @@
+use io_engine::{
+    bdev_api::bdev_create,
+    core::{CoreError, MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{validate_nqn, validate_nqn_prefix, NvmfError, NvmfSubsystem},
+};
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+const NQN: &str = "nqn.2023-04.com.example.storage:volume-1";
+
+#[test]
+fn nqn_validation() {
+    assert!(validate_nqn_prefix("nqn.2019-05.io.openebs").is_ok());
+    assert!(validate_nqn_prefix("nqn.2023-04.com.example-corp").is_ok());
+    assert!(validate_nqn_prefix("iqn.2019-05.io.openebs").is_err());
+    assert!(validate_nqn_prefix("nqn.2019-5.io.openebs").is_err());
+    assert!(validate_nqn_prefix("nqn.2019-05.io.-openebs").is_err());
+    assert!(validate_nqn_prefix("nqn.2019-05").is_err());
+
+    assert!(validate_nqn(NQN).is_ok());
+    assert!(validate_nqn(
+        "nqn.2014-08.org.nvmexpress:uuid:\
+         f81d4fae-7dec-11d0-a765-00a0c91e6bf6"
+    )
+    .is_ok());
+    assert!(validate_nqn("nqn.2014-08.org.nvmexpress:uuid:nope").is_err());
+    assert!(validate_nqn("nqn.2023-04.com.example").is_err());
+    assert!(validate_nqn("nqn.2023-04.com.example:").is_err());
+    assert!(
+        validate_nqn(&format!("nqn.2023-04.com.x:{}", "a".repeat(223)))
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn nvmf_share_explicit_nqn() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        bdev_create("malloc:///nqn0?size_mb=64").await.unwrap();
+        bdev_create("malloc:///nqn1?size_mb=64").await.unwrap();
+
+        let mut bdev = UntypedBdev::lookup_by_name("nqn0").unwrap();
+        let props = NvmfShareProps::new().with_nqn(Some(NQN.to_string()));
+        let nqn = Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+        assert_eq!(nqn, NQN);
+
+        // the subsystem is still found by the name of its bdev
+        let subsystem = NvmfSubsystem::nqn_lookup("nqn0").unwrap();
+        assert_eq!(subsystem.get_nqn(), NQN);
+        assert!(bdev.share_uri().unwrap().ends_with(NQN));
+
+        // the NQN of another subsystem cannot be reused
+        let mut other = UntypedBdev::lookup_by_name("nqn1").unwrap();
+        let props = NvmfShareProps::new().with_nqn(Some(NQN.to_string()));
+        let error = Pin::new(&mut other)
+            .share_nvmf(Some(props))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            CoreError::ShareNvmf {
+                source: NvmfError::NqnInUse { .. }
+            }
+        ));
+
+        let props =
+            NvmfShareProps::new().with_nqn(Some("volume-1".to_string()));
+        let error = Pin::new(&mut other)
+            .share_nvmf(Some(props))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            CoreError::ShareNvmf {
+                source: NvmfError::InvalidNqn { .. }
+            }
+        ));
+
+        Pin::new(&mut bdev).unshare().await.unwrap();
+        assert!(NvmfSubsystem::nqn_lookup("nqn0").is_none());
+    })
+    .await;
+}
@@ -0,0 +1,41 @@
+use io_engine::{
+    bdev::{device_create, device_destroy},
+    bdev_api::bdev_create,
+    constants::NVME_NQN_PREFIX,
+    core::{MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{nqn_index_stats, NvmfListener, NvmfTransport},
+};
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+#[tokio::test]
+async fn nvmf_nqn_index() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        bdev_create("malloc:///index0?size_mb=4").await.unwrap();
+        let mut bdev = UntypedBdev::lookup_by_name("index0").unwrap();
+        let entries = nqn_index_stats().entries;
+
+        // the bdev of a subsystem is indexed while it is shared
+        let props = NvmfShareProps::new().with_listeners(vec![NvmfListener {
+            transport: NvmfTransport::Tcp,
+            address: Some("127.0.0.1".to_string()),
+            port: Some(8452),
+        }]);
+        Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+        assert_eq!(nqn_index_stats().entries, entries + 1);
+
+        // and the target of a host connect is found in the index
+        let hits = nqn_index_stats().hits;
+        let uri = format!("nvmf://127.0.0.1:8452/{NVME_NQN_PREFIX}:index0");
+        device_create(&uri).await.unwrap();
+        assert!(nqn_index_stats().hits > hits);
+        device_destroy(&uri).await.unwrap();
+
+        Pin::new(&mut bdev).unshare().await.unwrap();
+        assert_eq!(nqn_index_stats().entries, entries);
+    })
+    .await;
+}
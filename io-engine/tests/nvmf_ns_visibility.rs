@@ -0,0 +1,74 @@
+use io_engine::{
+    bdev_api::bdev_create,
+    core::{MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{NvmfError, NvmfSubsystem},
+};
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+const HOST1: &str = "nqn.2019-05.io.openebs:ns-visibility-host1";
+const HOST2: &str = "nqn.2019-05.io.openebs:ns-visibility-host2";
+
+#[tokio::test]
+async fn nvmf_private_namespaces() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        bdev_create("malloc:///nsvis0?size_mb=4").await.unwrap();
+        bdev_create("malloc:///nsvis1?size_mb=4").await.unwrap();
+        let mut bdev = UntypedBdev::lookup_by_name("nsvis0").unwrap();
+        let props = NvmfShareProps::new()
+            .with_allowed_hosts(vec![HOST1.to_string(), HOST2.to_string()]);
+        Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+        let subsystem = NvmfSubsystem::nqn_lookup("nsvis0").unwrap();
+
+        // the namespace of the share is visible to all the hosts
+        assert!(subsystem.namespace_visible_hosts(1).is_none());
+        assert!(matches!(
+            subsystem.set_namespace_visible(1, HOST1, false).await,
+            Err(NvmfError::InvalidNamespace { .. })
+        ));
+
+        let private = UntypedBdev::lookup_by_name("nsvis1").unwrap();
+        let nsid = subsystem
+            .attach_private_namespace(&private, &[HOST1.to_string()])
+            .await
+            .unwrap();
+        assert_eq!(nsid, 2);
+        let namespaces = subsystem.namespaces();
+        assert_eq!(namespaces.len(), 2);
+        assert_eq!(namespaces[1].bdev.as_deref(), Some("nsvis1"));
+        assert_eq!(namespaces[1].visible_hosts, Some(vec![HOST1.to_string()]));
+
+        subsystem
+            .set_namespace_visible(nsid, HOST2, true)
+            .await
+            .unwrap();
+        subsystem
+            .set_namespace_visible(nsid, HOST1, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            subsystem.namespace_visible_hosts(nsid),
+            Some(vec![HOST2.to_string()])
+        );
+
+        subsystem.detach_private_namespace(nsid).await.unwrap();
+        assert_eq!(subsystem.namespaces().len(), 1);
+        assert!(matches!(
+            subsystem.detach_private_namespace(nsid).await,
+            Err(NvmfError::NamespaceNotFound { .. })
+        ));
+
+        // the private namespaces are removed along with the subsystem
+        let nsid = subsystem
+            .attach_private_namespace(&private, &[HOST1.to_string()])
+            .await
+            .unwrap();
+        assert_eq!(nsid, 2);
+        Pin::new(&mut bdev).unshare().await.unwrap();
+        assert!(NvmfSubsystem::nqn_lookup("nsvis0").is_none());
+    })
+    .await;
+}
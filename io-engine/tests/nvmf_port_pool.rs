@@ -0,0 +1,84 @@
+use io_engine::{
+    bdev_api::bdev_create,
+    core::{CoreError, MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{nvmf_ports, NvmfError, NvmfListener, NvmfSubsystem},
+};
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+const PORT_STATE: &str = "/tmp/nvmf_port_pool.json";
+
+#[tokio::test]
+async fn nvmf_share_port_pool() {
+    common::delete_file(&[PORT_STATE.to_string()]);
+
+    let ms = MayastorTest::new(MayastorCliArgs {
+        nvmf_port_range: Some("8450-8451".to_string()),
+        nvmf_port_state: Some(PORT_STATE.to_string()),
+        ..Default::default()
+    });
+    ms.spawn(async {
+        for name in ["ports0", "ports1", "ports2", "ports3"] {
+            bdev_create(&format!("malloc:///{name}?size_mb=4"))
+                .await
+                .unwrap();
+        }
+
+        // every subsystem gets a port of its own
+        for (name, port) in [("ports0", 8450), ("ports1", 8451)] {
+            let mut bdev = UntypedBdev::lookup_by_name(name).unwrap();
+            Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+            let endpoints = NvmfSubsystem::nqn_lookup(name)
+                .unwrap()
+                .uri_endpoints()
+                .unwrap();
+            assert_eq!(endpoints.len(), 1);
+            assert!(endpoints[0].contains(&format!(":{port}/")));
+        }
+        assert_eq!(nvmf_ports().len(), 2);
+
+        // until the range is exhausted
+        let mut bdev = UntypedBdev::lookup_by_name("ports2").unwrap();
+        let result = Pin::new(&mut bdev).share_nvmf(None).await;
+        assert!(matches!(
+            result,
+            Err(CoreError::ShareNvmf {
+                source: NvmfError::NoFreePort { .. }
+            })
+        ));
+
+        // an explicit port is taken as is
+        let mut bdev = UntypedBdev::lookup_by_name("ports3").unwrap();
+        let props = NvmfShareProps::new().with_listeners(vec![NvmfListener {
+            port: Some(8452),
+            ..Default::default()
+        }]);
+        Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+        assert_eq!(nvmf_ports().len(), 2);
+
+        // the port of an unshared subsystem goes back to the pool
+        let mut bdev = UntypedBdev::lookup_by_name("ports0").unwrap();
+        Pin::new(&mut bdev).unshare().await.unwrap();
+        let ports = nvmf_ports();
+        assert_eq!(ports.len(), 1);
+        assert_eq!(ports[0].port, 8451);
+        let state = std::fs::read_to_string(PORT_STATE).unwrap();
+        assert!(state.contains(&ports[0].nqn));
+        assert!(state.contains("8451"));
+
+        let mut bdev = UntypedBdev::lookup_by_name("ports2").unwrap();
+        Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+        assert_eq!(nvmf_ports().len(), 2);
+
+        for name in ["ports1", "ports2", "ports3"] {
+            let mut bdev = UntypedBdev::lookup_by_name(name).unwrap();
+            Pin::new(&mut bdev).unshare().await.unwrap();
+        }
+        assert!(nvmf_ports().is_empty());
+    })
+    .await;
+
+    common::delete_file(&[PORT_STATE.to_string()]);
+}
@@ -0,0 +1,141 @@
+use io_engine::{
+    bdev::{device_create, device_destroy, device_open},
+    bdev_api::bdev_create,
+    constants::NVME_NQN_PREFIX,
+    core::{
+        BlockDeviceDescriptor,
+        BlockDeviceHandle,
+        MayastorCliArgs,
+        NvmfShareProps,
+        Share,
+        UntypedBdev,
+    },
+    sleep::mayastor_sleep,
+    subsys::{NvmfListener, NvmfSubsystem, NvmfTransport},
+};
+use once_cell::sync::OnceCell;
+use std::{
+    pin::Pin,
+    time::{Duration, Instant},
+};
+
+pub mod common;
+use common::MayastorTest;
+
+const HOST: &str = "nqn.2019-05.io.openebs:resv-host";
+const RESV_KEY: u64 = 0x1234;
+
+static MS: OnceCell<MayastorTest> = OnceCell::new();
+
+fn mayastor() -> &'static MayastorTest<'static> {
+    MS.get_or_init(|| {
+        // the hosts ask for no keep alive timeout and send no keep alive
+        // commands, leaving it to the target
+        std::env::set_var("NVME_KATO", "0ms");
+        MayastorTest::new(MayastorCliArgs {
+            nvmf_kato_resv_grace_ms: Some(500),
+            ..Default::default()
+        })
+    })
+}
+
+/// Shares a bdev on the given port, with the given keep alive timeout.
+async fn share(name: &str, port: u16, kato_ms: Option<u32>) -> NvmfSubsystem {
+    bdev_create(&format!("malloc:///{name}?size_mb=4"))
+        .await
+        .unwrap();
+    let mut bdev = UntypedBdev::lookup_by_name(name).unwrap();
+    let props = NvmfShareProps::new()
+        .with_kato(kato_ms)
+        .with_listeners(vec![NvmfListener {
+            transport: NvmfTransport::Tcp,
+            address: Some("127.0.0.1".to_string()),
+            port: Some(port),
+        }]);
+    Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+    NvmfSubsystem::nqn_lookup(name).unwrap()
+}
+
+/// Connects as the host, registers and takes a reservation of the given
+/// type, and returns the URI and the ID of the host.
+async fn reserve(name: &str, port: u16, rtype: u8) -> (String, [u8; 16]) {
+    let uri = format!(
+        "nvmf://127.0.0.1:{port}/{NVME_NQN_PREFIX}:{name}?hostnqn={HOST}"
+    );
+    let dev = device_create(&uri).await.unwrap();
+    let handle = device_open(&dev, false).unwrap().into_handle().unwrap();
+    handle.nvme_resv_register(0, RESV_KEY, 0, 0).await.unwrap();
+    handle
+        .nvme_resv_acquire(RESV_KEY, 0, 0, rtype)
+        .await
+        .unwrap();
+    let hostid = handle.host_id().await.unwrap();
+    (uri, hostid)
+}
+
+/// Returns the number of registrants and the type of the reservation of the
+/// namespace of the subsystem.
+async fn reservations(subsystem: &NvmfSubsystem) -> (usize, u32) {
+    let export = subsystem.export().await.unwrap();
+    subsystem.resume().await.unwrap();
+    let ns = &export.reservations[0];
+    (ns.registrants.len(), ns.rtype)
+}
+
+#[tokio::test]
+async fn nvmf_release_host_reservations() {
+    mayastor()
+        .spawn(async {
+            let subsystem = share("resv0", 8449, None).await;
+            let (uri, hostid) = reserve("resv0", 8449, 1).await;
+            assert_eq!(reservations(&subsystem).await, (1, 1));
+
+            // the reservations of a connected host are kept
+            assert_eq!(
+                subsystem.release_host_reservations(hostid).await.unwrap(),
+                0
+            );
+
+            // not those of a host gone without unregistering, which are
+            // preempted through SPDK
+            device_destroy(&uri).await.unwrap();
+            assert_eq!(
+                subsystem.release_host_reservations(hostid).await.unwrap(),
+                1
+            );
+            assert_eq!(reservations(&subsystem).await, (0, 0));
+            assert_eq!(
+                subsystem.release_host_reservations(hostid).await.unwrap(),
+                0
+            );
+
+            let mut bdev = UntypedBdev::lookup_by_name("resv0").unwrap();
+            Pin::new(&mut bdev).unshare().await.unwrap();
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn nvmf_release_kato_reservations() {
+    mayastor()
+        .spawn(async {
+            // the host sends no keep alive, so its controller times out
+            let subsystem = share("resv1", 8464, Some(1_000)).await;
+            let start = Instant::now();
+            let (uri, _) = reserve("resv1", 8464, 5).await;
+            assert_eq!(reservations(&subsystem).await, (1, 5));
+
+            // and its reservation is released once the grace period is over
+            while reservations(&subsystem).await != (0, 0) {
+                assert!(start.elapsed() < Duration::from_secs(10));
+                mayastor_sleep(Duration::from_millis(100)).await.unwrap();
+            }
+            assert!(start.elapsed() >= Duration::from_millis(1_500));
+            assert!(subsystem.controllers().is_empty());
+
+            device_destroy(&uri).await.ok();
+            let mut bdev = UntypedBdev::lookup_by_name("resv1").unwrap();
+            Pin::new(&mut bdev).unshare().await.unwrap();
+        })
+        .await;
+}
@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup, nexus_lookup_mut},
+    core::{MayastorCliArgs, Protocol, Share},
+    subsys::{share_audit_loop, DriftKind, ShareAudit},
+};
+use once_cell::sync::OnceCell;
+
+pub mod common;
+use common::MayastorTest;
+
+static MS: OnceCell<MayastorTest> = OnceCell::new();
+
+fn mayastor() -> &'static MayastorTest<'static> {
+    MS.get_or_init(|| MayastorTest::new(MayastorCliArgs::default()))
+}
+
+/// Returns the drifts found on the given nexus.
+async fn nexus_drifts(name: &str) -> Vec<DriftKind> {
+    let uuid = nexus_lookup(name).unwrap().uuid().to_string();
+    ShareAudit::default()
+        .run()
+        .await
+        .into_iter()
+        .filter(|d| d.nexus.as_deref() == Some(uuid.as_str()))
+        .map(|d| {
+            assert!(!d.corrected);
+            d.kind
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn share_audit_nexus() {
+    mayastor()
+        .spawn(async {
+            nexus_create(
+                "audit_nexus",
+                32 * 1024 * 1024,
+                None,
+                &["malloc:///audit_malloc?size_mb=64".into()],
+            )
+            .await
+            .unwrap();
+            assert!(nexus_drifts("audit_nexus").await.is_empty());
+
+            let nexus = nexus_lookup_mut("audit_nexus").unwrap();
+            nexus.share_ext(Protocol::Nvmf, None, vec![]).await.unwrap();
+            assert!(nexus_drifts("audit_nexus").await.is_empty());
+
+            // the subsystem is removed behind the back of the nexus
+            let nexus = nexus_lookup_mut("audit_nexus").unwrap();
+            nexus.unshare().await.unwrap();
+            assert_eq!(
+                nexus_drifts("audit_nexus").await,
+                vec![DriftKind::NotPublished]
+            );
+
+            // the nexus is shared without being published
+            let mut nexus = nexus_lookup_mut("audit_nexus").unwrap();
+            nexus.as_mut().unshare_nexus().await.unwrap();
+            nexus.share_nvmf(None).await.unwrap();
+            assert_eq!(
+                nexus_drifts("audit_nexus").await,
+                vec![DriftKind::UnexpectedlyPublished]
+            );
+
+            nexus_lookup_mut("audit_nexus")
+                .unwrap()
+                .destroy()
+                .await
+                .unwrap();
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn share_audit_zero_interval() {
+    // a zero interval disables the audit instead of panicking
+    tokio::time::timeout(
+        Duration::from_secs(1),
+        share_audit_loop(Duration::ZERO, false),
+    )
+    .await
+    .unwrap();
+}
@@ -0,0 +1,80 @@
+use io_engine::{
+    core::{LogicalVolume, MayastorCliArgs, NvmfShareProps, Protocol, Share},
+    lvs::Lvs,
+    pool_backend::{PoolArgs, PoolBackend},
+    sleep::mayastor_sleep,
+    subsys::{expire_share_leases, NvmfError, NvmfSubsystem, ShareLease},
+};
+use std::{pin::Pin, time::Duration};
+
+pub mod common;
+use common::MayastorTest;
+
+#[tokio::test]
+async fn nvmf_share_lease_expiry() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        let lvs = Lvs::create_or_import(PoolArgs {
+            name: "lease_pool".to_string(),
+            disks: vec!["malloc:///lease_disk?size_mb=128".to_string()],
+            uuid: None,
+            cluster_size: None,
+            reserved_pct: None,
+            backend: PoolBackend::Lvs,
+        })
+        .await
+        .unwrap();
+        let mut leased = lvs
+            .create_lvol("leased", 8 * 1024 * 1024, None, false, None)
+            .await
+            .unwrap();
+        let mut permanent = lvs
+            .create_lvol("permanent", 8 * 1024 * 1024, None, false, None)
+            .await
+            .unwrap();
+
+        let props =
+            NvmfShareProps::new().with_lease(Some(Duration::from_millis(200)));
+        Pin::new(&mut leased).share_nvmf(Some(props)).await.unwrap();
+        Pin::new(&mut permanent).share_nvmf(None).await.unwrap();
+
+        let nqn = NvmfSubsystem::nqn_lookup(&leased.name()).unwrap().get_nqn();
+        let permanent_nqn = NvmfSubsystem::nqn_lookup(&permanent.name())
+            .unwrap()
+            .get_nqn();
+        assert_eq!(ShareLease::list().len(), 1);
+        assert_eq!(ShareLease::lookup(&nqn).unwrap().ttl_ms, 200);
+        assert!(matches!(
+            ShareLease::renew(&permanent_nqn),
+            Err(NvmfError::LeaseNotFound { .. })
+        ));
+
+        // a renewed lease does not expire
+        for _ in 0 .. 3 {
+            mayastor_sleep(Duration::from_millis(100)).await.unwrap();
+            ShareLease::renew(&nqn).unwrap();
+            assert!(expire_share_leases().await.is_empty());
+        }
+
+        mayastor_sleep(Duration::from_millis(300)).await.unwrap();
+        let expired = expire_share_leases().await;
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].nqn, nqn);
+        assert!(expired[0].error.is_none());
+        assert!(NvmfSubsystem::lookup_by_nqn(&nqn).is_err());
+        assert!(matches!(leased.shared(), Some(Protocol::Off)));
+        assert!(ShareLease::list().is_empty());
+
+        // shares without lease are left alone
+        assert!(NvmfSubsystem::lookup_by_nqn(&permanent_nqn).is_ok());
+
+        // a lease can be set on an existing share, and removed again
+        let subsystem = NvmfSubsystem::lookup_by_nqn(&permanent_nqn).unwrap();
+        subsystem.set_lease(Some(Duration::ZERO)).unwrap();
+        subsystem.set_lease(None);
+        assert!(expire_share_leases().await.is_empty());
+
+        lvs.destroy().await.unwrap();
+    })
+    .await;
+}
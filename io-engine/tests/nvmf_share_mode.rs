@@ -0,0 +1,64 @@
+use io_engine::{
+    bdev_api::bdev_create,
+    core::{
+        CoreError,
+        MayastorCliArgs,
+        NvmfShareProps,
+        Share,
+        UntypedBdev,
+        UpdateProps,
+    },
+    subsys::{NvmfError, NvmfShareMode, NvmfSubsystem},
+};
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+const HOST1: &str = "nqn.2019-05.io.openebs:share-mode1";
+const HOST2: &str = "nqn.2019-05.io.openebs:share-mode2";
+
+#[tokio::test]
+async fn nvmf_share_mode() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        bdev_create("null:///mode0?size_mb=4").await.unwrap();
+
+        // shares are exclusive by default
+        let mut bdev = UntypedBdev::lookup_by_name("mode0").unwrap();
+        Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+        let subsystem = NvmfSubsystem::nqn_lookup("mode0").unwrap();
+        assert_eq!(subsystem.share_mode(), NvmfShareMode::Exclusive);
+        Pin::new(&mut bdev).unshare().await.unwrap();
+
+        // a shared share needs the hosts sharing it
+        let props =
+            NvmfShareProps::new().with_share_mode(NvmfShareMode::Shared);
+        assert!(matches!(
+            Pin::new(&mut bdev).share_nvmf(Some(props)).await,
+            Err(CoreError::ShareNvmf {
+                source: NvmfError::InvalidShareMode { .. }
+            })
+        ));
+        assert!(NvmfSubsystem::nqn_lookup("mode0").is_none());
+
+        // the null bdev has no volatile write cache
+        let props = NvmfShareProps::new()
+            .with_share_mode(NvmfShareMode::Shared)
+            .with_allowed_hosts(vec![HOST1.to_string(), HOST2.to_string()]);
+        Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+        let subsystem = NvmfSubsystem::nqn_lookup("mode0").unwrap();
+        assert_eq!(subsystem.share_mode(), NvmfShareMode::Shared);
+        assert_eq!(subsystem.info().share_mode, NvmfShareMode::Shared);
+
+        // and may not be opened to any host
+        assert!(Pin::new(&mut bdev)
+            .update_properties(UpdateProps::new())
+            .await
+            .is_err());
+        let props = UpdateProps::new().with_allowed_hosts(vec![HOST1.into()]);
+        Pin::new(&mut bdev).update_properties(props).await.unwrap();
+        Pin::new(&mut bdev).unshare().await.unwrap();
+    })
+    .await;
+}
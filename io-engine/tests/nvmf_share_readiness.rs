@@ -0,0 +1,85 @@
+use io_engine::{
+    bdev_api::bdev_create,
+    core::{CoreError, MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{
+        share_readiness,
+        NvmfError,
+        NvmfListener,
+        NvmfSubsystem,
+        NvmfTransport,
+        ShareReadiness,
+        HOST_GROUP_PREFIX,
+    },
+};
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+/// Whether the check of the given name passed.
+fn passed(readiness: &ShareReadiness, check: &str) -> bool {
+    readiness
+        .checks
+        .iter()
+        .find(|c| c.check == check)
+        .map(|c| c.ok)
+        .unwrap()
+}
+
+#[tokio::test]
+async fn nvmf_share_readiness() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        bdev_create("malloc:///ready0?size_mb=4").await.unwrap();
+
+        // a bdev not shared yet is ready, and stays unshared
+        let readiness = share_readiness("ready0", None);
+        assert!(readiness.ready, "{readiness:?}");
+        assert!(NvmfSubsystem::nqn_lookup("ready0").is_none());
+        let bdev = UntypedBdev::lookup_by_name("ready0").unwrap();
+        assert!(!bdev.is_claimed());
+
+        // every failing step is reported
+        let props = NvmfShareProps::new()
+            .with_allowed_hosts(vec![format!("{HOST_GROUP_PREFIX}unknown")])
+            .with_listeners(vec![NvmfListener {
+                transport: NvmfTransport::Tcp,
+                address: Some("not-an-address".to_string()),
+                port: None,
+            }])
+            .with_serial(Some("x".repeat(64)))
+            .with_ana(true);
+        let readiness = share_readiness("ready0", Some(props));
+        assert!(!readiness.ready);
+        assert!(passed(&readiness, "target"));
+        assert!(passed(&readiness, "bdev"));
+        assert!(passed(&readiness, "nqn"));
+        assert!(!passed(&readiness, "hosts"));
+        assert!(!passed(&readiness, "listeners"));
+        assert!(!passed(&readiness, "identity"));
+        assert!(!passed(&readiness, "ana"));
+
+        // which the share enforces as well, without claiming the bdev
+        let mut bdev = UntypedBdev::lookup_by_name("ready0").unwrap();
+        let props = NvmfShareProps::new().with_ana(true);
+        assert!(matches!(
+            Pin::new(&mut bdev).share_nvmf(Some(props)).await,
+            Err(CoreError::ShareNvmf {
+                source: NvmfError::InvalidAna { .. }
+            })
+        ));
+        assert!(!bdev.is_claimed());
+
+        // a shared bdev is not ready to be shared again
+        Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+        let readiness = share_readiness("ready0", None);
+        assert!(!readiness.ready);
+        assert!(!passed(&readiness, "bdev"));
+        assert!(!passed(&readiness, "nqn"));
+        Pin::new(&mut bdev).unshare().await.unwrap();
+
+        let readiness = share_readiness("missing", None);
+        assert!(!passed(&readiness, "bdev"));
+    })
+    .await;
+}
@@ -0,0 +1,115 @@
+use io_engine::{
+    bdev_api::bdev_create,
+    core::{MayastorCliArgs, NvmfShareProps, Share, UntypedBdev, UpdateProps},
+    subsys::{
+        persisted_share,
+        NvmfListener,
+        NvmfSubsystem,
+        NvmfTransport,
+        PersistedShare,
+        ShareLease,
+    },
+};
+use std::{pin::Pin, time::Duration};
+
+pub mod common;
+use common::MayastorTest;
+
+const PTPL_DIR: &str = "/tmp/io-engine-share-state";
+const HOST1: &str = "nqn.2019-05.io.openebs:share-state1";
+const HOST2: &str = "nqn.2019-05.io.openebs:share-state2";
+
+/// Leaves the given share state behind as a crash would.
+fn crash(share: &PersistedShare) {
+    let dir = format!("{PTPL_DIR}/nvmf-share");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = format!("{dir}/{}.json", share.nqn);
+    std::fs::write(path, serde_json::to_vec(share).unwrap()).unwrap();
+}
+
+#[tokio::test]
+async fn nvmf_share_state() {
+    std::fs::remove_dir_all(PTPL_DIR).ok();
+    let ms = MayastorTest::new(MayastorCliArgs {
+        ptpl_dir: Some(PTPL_DIR.to_string()),
+        ..Default::default()
+    });
+    ms.spawn(async {
+        bdev_create("malloc:///state0?size_mb=4").await.unwrap();
+        let mut bdev = UntypedBdev::lookup_by_name("state0").unwrap();
+        let listener = NvmfListener {
+            transport: NvmfTransport::Tcp,
+            address: Some("127.0.0.1".to_string()),
+            port: Some(8451),
+        };
+
+        // the hosts, listeners and lease of the share are recorded
+        let props = NvmfShareProps::new()
+            .with_allowed_hosts(vec![HOST1.to_string()])
+            .with_listeners(vec![listener.clone()])
+            .with_lease(Some(Duration::from_secs(60)));
+        Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+        let nqn = NvmfSubsystem::nqn_lookup("state0").unwrap().get_nqn();
+        let share = persisted_share(&nqn).unwrap();
+        assert_eq!(share.allowed_hosts, vec![HOST1.to_string()]);
+        assert_eq!(share.listeners, vec![listener.clone()]);
+        assert_eq!(share.lease_ms, Some(60_000));
+
+        // and so are the hosts updated afterwards
+        let props = UpdateProps::new().with_allowed_hosts(vec![HOST2.into()]);
+        Pin::new(&mut bdev).update_properties(props).await.unwrap();
+        let share = persisted_share(&nqn).unwrap();
+        assert_eq!(share.allowed_hosts, vec![HOST2.to_string()]);
+
+        // which are forgotten once unshared
+        Pin::new(&mut bdev).unshare().await.unwrap();
+        assert!(persisted_share(&nqn).is_none());
+
+        // the state left behind by a crash is replayed on the next share
+        crash(&share);
+        Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+        let subsystem = NvmfSubsystem::nqn_lookup("state0").unwrap();
+        assert_eq!(subsystem.allowed_hosts(), vec![HOST2.to_string()]);
+        let endpoints = subsystem.uri_endpoints().unwrap();
+        assert_eq!(endpoints.len(), 1);
+        assert!(endpoints[0].contains("127.0.0.1:8451"), "{endpoints:?}");
+        assert_eq!(ShareLease::lookup(&nqn).unwrap().ttl_ms, 60_000);
+
+        // the hosts allowed or disallowed one by one are recorded as well
+        subsystem.allow_host(HOST1).unwrap();
+        subsystem.disallow_host(HOST2).unwrap();
+        let replayed = persisted_share(&nqn).unwrap();
+        assert_eq!(
+            replayed.allowed_hosts,
+            vec![HOST2.to_string(), HOST1.to_string()]
+        );
+        assert_eq!(replayed.disallowed_hosts, vec![HOST2.to_string()]);
+        Pin::new(&mut bdev).unshare().await.unwrap();
+
+        // and stay disallowed once replayed
+        crash(&replayed);
+        Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+        let subsystem = NvmfSubsystem::nqn_lookup("state0").unwrap();
+        assert_eq!(subsystem.allowed_hosts(), vec![HOST1.to_string()]);
+        Pin::new(&mut bdev).unshare().await.unwrap();
+
+        // a share giving its own hosts is not overridden by the state left
+        // behind, which only provides the listeners it does not give
+        crash(&share);
+        let props = NvmfShareProps::new()
+            .with_allowed_hosts(vec![HOST1.to_string()])
+            .with_lease(Some(Duration::from_secs(30)));
+        Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+        let subsystem = NvmfSubsystem::nqn_lookup("state0").unwrap();
+        assert_eq!(subsystem.allowed_hosts(), vec![HOST1.to_string()]);
+        let endpoints = subsystem.uri_endpoints().unwrap();
+        assert!(endpoints[0].contains("127.0.0.1:8451"), "{endpoints:?}");
+        assert_eq!(ShareLease::lookup(&nqn).unwrap().ttl_ms, 30_000);
+        let share = persisted_share(&nqn).unwrap();
+        assert_eq!(share.allowed_hosts, vec![HOST1.to_string()]);
+
+        Pin::new(&mut bdev).unshare().await.unwrap();
+    })
+    .await;
+    std::fs::remove_dir_all(PTPL_DIR).ok();
+}
@@ -0,0 +1,64 @@
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup_mut},
+    bdev_api::bdev_create,
+    core::{MayastorCliArgs, Share, UntypedBdev},
+    subsys::{NvmfSubsystem, NvmfTargetKind},
+};
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+/// Checks the target and the port a shared bdev is exported on.
+fn check_export(name: &str, kind: NvmfTargetKind, port: u16) {
+    let subsystem = NvmfSubsystem::nqn_lookup(name).unwrap();
+    assert_eq!(subsystem.target_kind(), kind);
+    let uris = subsystem.uri_endpoints().unwrap();
+    assert_eq!(uris.len(), 1);
+    assert!(uris[0].contains(&format!(":{port}/")), "{uris:?}");
+}
+
+#[tokio::test]
+async fn nvmf_split_target() {
+    let args = MayastorCliArgs {
+        reactor_mask: "0x3".into(),
+        nvmf_tgt_cores: Some("0".into()),
+        nvmf_replica_tgt_cores: Some("1".into()),
+        ..Default::default()
+    };
+
+    let ms = MayastorTest::new(args);
+    ms.spawn(async {
+        // any bdev but a nexus is exported by the replica target
+        bdev_create("malloc:///malloc0?size_mb=64").await.unwrap();
+        let mut bdev = UntypedBdev::lookup_by_name("malloc0").unwrap();
+        Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+        check_export("malloc0", NvmfTargetKind::Replica, 8420);
+
+        nexus_create(
+            "nexus0",
+            32 * 1024 * 1024,
+            None,
+            &["malloc:///malloc1?size_mb=64".into()],
+        )
+        .await
+        .unwrap();
+        let mut nexus = nexus_lookup_mut("nexus0").unwrap();
+        nexus.as_mut().share_nvmf(None).await.unwrap();
+        check_export("nexus0", NvmfTargetKind::Nexus, 4421);
+
+        // the subsystems of both targets are visible
+        let names = NvmfSubsystem::first()
+            .unwrap()
+            .into_iter()
+            .map(|s| s.get_nqn())
+            .collect::<Vec<_>>();
+        assert!(names.iter().any(|n| n.ends_with(":malloc0")));
+        assert!(names.iter().any(|n| n.ends_with(":nexus0")));
+
+        nexus.as_mut().unshare().await.unwrap();
+        nexus.destroy().await.unwrap();
+        Pin::new(&mut bdev).unshare().await.unwrap();
+    })
+    .await;
+}
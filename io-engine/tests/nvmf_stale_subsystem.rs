@@ -0,0 +1,61 @@
+use io_engine::{
+    bdev_api::{bdev_create, bdev_destroy},
+    core::{MayastorCliArgs, Share, UntypedBdev},
+    sleep::mayastor_sleep,
+    subsys::{reconcile_subsystems, stale_subsystem_loop, NvmfSubsystem},
+};
+use std::{pin::Pin, time::Duration};
+
+pub mod common;
+use common::MayastorTest;
+
+#[tokio::test]
+async fn nvmf_stale_subsystem_cleanup() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        bdev_create("malloc:///stale0?size_mb=64").await.unwrap();
+        bdev_create("malloc:///live0?size_mb=64").await.unwrap();
+        for name in ["stale0", "live0"] {
+            let mut bdev = UntypedBdev::lookup_by_name(name).unwrap();
+            Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+        }
+        let nqn = NvmfSubsystem::nqn_lookup("stale0").unwrap().get_nqn();
+
+        // nothing is stale while the bdevs exist
+        assert!(reconcile_subsystems().await.is_empty());
+
+        // removing the bdev behind the back of the subsystem leaves it
+        // without namespace
+        bdev_destroy("malloc:///stale0?size_mb=64").await.unwrap();
+        for _ in 0 .. 100 {
+            let subsystem = NvmfSubsystem::lookup_by_nqn(&nqn).unwrap();
+            if subsystem.is_stale() {
+                break;
+            }
+            mayastor_sleep(Duration::from_millis(10)).await.unwrap();
+        }
+        assert!(NvmfSubsystem::lookup_by_nqn(&nqn).unwrap().is_stale());
+
+        let stale = reconcile_subsystems().await;
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].nqn, nqn);
+        assert!(stale[0].destroyed);
+        assert!(NvmfSubsystem::lookup_by_nqn(&nqn).is_err());
+
+        // the other subsystem is left untouched
+        assert!(NvmfSubsystem::nqn_lookup("live0").is_some());
+        assert!(reconcile_subsystems().await.is_empty());
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn nvmf_stale_subsystem_zero_interval() {
+    // a zero interval disables the cleanup instead of panicking
+    tokio::time::timeout(
+        Duration::from_secs(1),
+        stale_subsystem_loop(Duration::ZERO),
+    )
+    .await
+    .unwrap();
+}
@@ -0,0 +1,65 @@
+use io_engine::{
+    bdev::{device_create, device_destroy},
+    bdev_api::bdev_create,
+    constants::NVME_NQN_PREFIX,
+    core::{MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{NvmfListener, NvmfSubsystem, NvmfTransport, SubsystemHandle},
+};
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+#[tokio::test]
+async fn nvmf_subsystem_handle() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        bdev_create("malloc:///handle0?size_mb=4").await.unwrap();
+        bdev_create("malloc:///handle1?size_mb=4").await.unwrap();
+
+        // the state changes go through the handles
+        let mut bdev = UntypedBdev::lookup_by_name("handle0").unwrap();
+        Pin::new(&mut bdev).share_nvmf(None).await.unwrap();
+        let subsystem = NvmfSubsystem::nqn_lookup("handle0").unwrap();
+        let SubsystemHandle::Active(active) = subsystem.into_handle().unwrap()
+        else {
+            panic!("subsystem is not active");
+        };
+        let paused = active.pause().await.unwrap();
+        let active = paused.resume().await.unwrap();
+        let stopped = active.stop().await.unwrap();
+        let active = stopped.start().await.unwrap();
+
+        // a paused subsystem is destroyed without being resumed
+        let paused = active.pause().await.unwrap();
+        let subsystem = NvmfSubsystem::nqn_lookup("handle0").unwrap();
+        assert!(matches!(
+            subsystem.into_handle().unwrap(),
+            SubsystemHandle::Paused(_)
+        ));
+        paused.destroy().await.unwrap();
+        assert!(NvmfSubsystem::nqn_lookup("handle0").is_none());
+        assert!(!bdev.is_claimed());
+
+        // the shutdown of a subsystem completes once its hosts are
+        // disconnected
+        let mut bdev = UntypedBdev::lookup_by_name("handle1").unwrap();
+        let props = NvmfShareProps::new().with_listeners(vec![NvmfListener {
+            transport: NvmfTransport::Tcp,
+            address: Some("127.0.0.1".to_string()),
+            port: Some(8450),
+        }]);
+        Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+        let uri = format!("nvmf://127.0.0.1:8450/{NVME_NQN_PREFIX}:handle1");
+        device_create(&uri).await.unwrap();
+
+        let subsystem = NvmfSubsystem::nqn_lookup("handle1").unwrap();
+        assert_eq!(subsystem.controllers().len(), 1);
+        subsystem.shutdown().await.unwrap();
+        assert!(NvmfSubsystem::nqn_lookup("handle1").is_none());
+        assert!(!bdev.is_claimed());
+
+        device_destroy(&uri).await.ok();
+    })
+    .await;
+}
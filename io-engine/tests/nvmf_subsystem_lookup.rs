@@ -0,0 +1,57 @@
+use io_engine::{
+    bdev_api::bdev_create,
+    core::{MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::NvmfSubsystem,
+};
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+/// Number of shared bdevs, enough for a lookup walking all the subsystems to
+/// stand out, yet below the default maximum number of subsystems.
+const COUNT: usize = 1024;
+
+const HOST: &str = "nqn.2019-05.io.openebs:lookup-host";
+
+#[tokio::test]
+async fn nvmf_subsystem_lookup_many() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        for i in 0 .. COUNT {
+            let name = format!("lookup{i}");
+            bdev_create(&format!("null:///{name}?size_mb=4"))
+                .await
+                .unwrap();
+            let mut bdev = UntypedBdev::lookup_by_name(&name).unwrap();
+            // every other subsystem has an explicit NQN
+            let nqn = (i % 2 == 0)
+                .then(|| format!("nqn.2023-04.com.example:lookup-{i}"));
+            let props = NvmfShareProps::new()
+                .with_nqn(nqn)
+                .with_allowed_hosts(vec![HOST.to_string()]);
+            Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+        }
+
+        for i in 0 .. COUNT {
+            let name = format!("lookup{i}");
+            let subsystem = NvmfSubsystem::nqn_lookup(&name).unwrap();
+            assert_eq!(subsystem.bdev().unwrap().name(), name);
+            assert_eq!(subsystem.allowed_hosts(), vec![HOST.to_string()]);
+            let nqn = subsystem.get_nqn();
+            assert_eq!(
+                NvmfSubsystem::lookup_by_nqn(&nqn).unwrap().get_nqn(),
+                nqn
+            );
+        }
+        assert!(NvmfSubsystem::nqn_lookup("lookup-none").is_none());
+
+        for i in 0 .. COUNT {
+            let name = format!("lookup{i}");
+            let mut bdev = UntypedBdev::lookup_by_name(&name).unwrap();
+            Pin::new(&mut bdev).unshare().await.unwrap();
+            assert!(NvmfSubsystem::nqn_lookup(&name).is_none());
+        }
+    })
+    .await;
+}
@@ -0,0 +1,56 @@
+use io_engine::{
+    bdev_api::bdev_create,
+    core::{CoreError, MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{NvmfError, NvmfSubsystem, NvmfTargetKind, NvmfTransport},
+};
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+#[tokio::test]
+async fn nvmf_share_transports() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        for kind in [NvmfTargetKind::Nexus, NvmfTargetKind::Replica] {
+            assert_eq!(NvmfTransport::enabled(kind), vec![NvmfTransport::Tcp]);
+        }
+
+        bdev_create("malloc:///transports0?size_mb=4")
+            .await
+            .unwrap();
+        bdev_create("malloc:///transports1?size_mb=4")
+            .await
+            .unwrap();
+
+        // TCP is used when no transport is given
+        let mut bdev = UntypedBdev::lookup_by_name("transports0").unwrap();
+        Pin::new(&mut bdev)
+            .share_nvmf(Some(NvmfShareProps::new()))
+            .await
+            .unwrap();
+        let endpoints = NvmfSubsystem::nqn_lookup("transports0")
+            .unwrap()
+            .uri_endpoints()
+            .unwrap();
+        assert_eq!(endpoints.len(), 1);
+        assert!(endpoints[0].starts_with("nvmf://"));
+
+        // RDMA cannot be listened on unless enabled
+        let mut bdev = UntypedBdev::lookup_by_name("transports1").unwrap();
+        let props = NvmfShareProps::new()
+            .with_transports(vec![NvmfTransport::Tcp, NvmfTransport::Rdma]);
+        let result = Pin::new(&mut bdev).share_nvmf(Some(props)).await;
+        assert!(matches!(
+            result,
+            Err(CoreError::ShareNvmf {
+                source: NvmfError::Transport { .. }
+            })
+        ));
+        assert!(NvmfSubsystem::nqn_lookup("transports1").is_none());
+
+        let mut bdev = UntypedBdev::lookup_by_name("transports0").unwrap();
+        Pin::new(&mut bdev).unshare().await.unwrap();
+    })
+    .await;
+}
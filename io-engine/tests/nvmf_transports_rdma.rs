@@ -0,0 +1,87 @@
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup_mut},
+    bdev_api::bdev_create,
+    core::{CoreError, MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{NvmfError, NvmfSubsystem, NvmfTargetKind, NvmfTransport},
+};
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+/// Whether an RDMA device, e.g. a soft-RoCE one, is present.
+fn rdma_device() -> bool {
+    std::fs::read_dir("/sys/class/infiniband")
+        .map_or(false, |mut devices| devices.next().is_some())
+}
+
+#[tokio::test]
+async fn nvmf_share_rdma() {
+    if !rdma_device() {
+        println!("no RDMA device found, skipping the test");
+        return;
+    }
+
+    // RDMA is only enabled on the host facing target
+    let args = MayastorCliArgs {
+        reactor_mask: "0x3".into(),
+        nvmf_tgt_cores: Some("0".into()),
+        nvmf_replica_tgt_cores: Some("1".into()),
+        rdma: true,
+        ..Default::default()
+    };
+    let ms = MayastorTest::new(args);
+    ms.spawn(async {
+        assert_eq!(
+            NvmfTransport::enabled(NvmfTargetKind::Nexus),
+            vec![NvmfTransport::Tcp, NvmfTransport::Rdma]
+        );
+        assert_eq!(
+            NvmfTransport::enabled(NvmfTargetKind::Replica),
+            vec![NvmfTransport::Tcp]
+        );
+        let both = || {
+            NvmfShareProps::new()
+                .with_transports(vec![NvmfTransport::Tcp, NvmfTransport::Rdma])
+        };
+
+        // the nexus listens on both transports
+        nexus_create(
+            "rdma_nexus",
+            32 * 1024 * 1024,
+            None,
+            &["malloc:///rdma_malloc0?size_mb=64".into()],
+        )
+        .await
+        .unwrap();
+        let mut nexus = nexus_lookup_mut("rdma_nexus").unwrap();
+        nexus.as_mut().share_nvmf(Some(both())).await.unwrap();
+        let mut endpoints = NvmfSubsystem::nqn_lookup("rdma_nexus")
+            .unwrap()
+            .uri_endpoints()
+            .unwrap();
+        endpoints.sort();
+        assert_eq!(endpoints.len(), 2);
+        assert!(endpoints[0].starts_with("nvmf://"), "{endpoints:?}");
+        assert!(endpoints[1].starts_with("nvmf+rdma://"), "{endpoints:?}");
+        assert!(endpoints[1].contains(":4421/"), "{endpoints:?}");
+
+        // a replica cannot be listened on over RDMA
+        bdev_create("malloc:///rdma_malloc1?size_mb=64")
+            .await
+            .unwrap();
+        let mut bdev = UntypedBdev::lookup_by_name("rdma_malloc1").unwrap();
+        let result = Pin::new(&mut bdev).share_nvmf(Some(both())).await;
+        assert!(matches!(
+            result,
+            Err(CoreError::ShareNvmf {
+                source: NvmfError::Transport { .. }
+            })
+        ));
+        assert!(NvmfSubsystem::nqn_lookup("rdma_malloc1").is_none());
+
+        nexus.as_mut().unshare().await.unwrap();
+        nexus.destroy().await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,88 @@
+use io_engine::{
+    bdev::{device_create, device_destroy, device_open},
+    bdev_api::bdev_create,
+    core::{MayastorCliArgs, NvmfShareProps, Share, UntypedBdev},
+    subsys::{zero_copy_stats, NvmfListener, NvmfTransport, ZeroCopyStats},
+};
+use spdk_rs::DmaBuf;
+use std::pin::Pin;
+
+pub mod common;
+use common::MayastorTest;
+
+const PORT: u16 = 8460;
+
+/// Writes and reads back 64KiB, larger than the in-capsule data, through an
+/// initiator connected to the share of the given bdev, returning the
+/// statistics from before and after.
+async fn io_through(name: &str) -> (ZeroCopyStats, ZeroCopyStats) {
+    let nqn = format!("nqn.2019-05.io.openebs:{name}");
+    let uri = format!("nvmf://127.0.0.1:{PORT}/{nqn}");
+    let device = device_create(&uri).await.unwrap();
+    let handle = device_open(&device, true).unwrap().into_handle().unwrap();
+    let mut buf =
+        DmaBuf::new(64 * 1024, handle.get_device().alignment()).unwrap();
+
+    let before = zero_copy_stats().await;
+    for i in 0 .. 8 {
+        handle.write_at(i * 64 * 1024, &buf).await.unwrap();
+        handle.read_at(i * 64 * 1024, &mut buf).await.unwrap();
+    }
+    let after = zero_copy_stats().await;
+
+    drop(handle);
+    device_destroy(&uri).await.unwrap();
+    (before, after)
+}
+
+#[tokio::test]
+async fn nvmf_zero_copy_stats() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        // malloc bdevs support zero-copy I/O, null bdevs do not
+        bdev_create("malloc:///zcopy_malloc?size_mb=4")
+            .await
+            .unwrap();
+        bdev_create("null:///zcopy_null?size_mb=4").await.unwrap();
+        for name in ["zcopy_malloc", "zcopy_null"] {
+            let mut bdev = UntypedBdev::lookup_by_name(name).unwrap();
+            let props =
+                NvmfShareProps::new().with_listeners(vec![NvmfListener {
+                    transport: NvmfTransport::Tcp,
+                    address: Some("127.0.0.1".to_string()),
+                    port: Some(PORT),
+                }]);
+            Pin::new(&mut bdev).share_nvmf(Some(props)).await.unwrap();
+        }
+
+        let stats = zero_copy_stats().await;
+        assert!(stats.nexus_zcopy);
+        assert_eq!(stats.subsystems.len(), 2);
+        let zero_copy = |name: &str| {
+            stats
+                .subsystems
+                .iter()
+                .find(|s| s.bdev == name)
+                .unwrap()
+                .zero_copy
+        };
+        assert!(zero_copy("zcopy_malloc"));
+        assert!(!zero_copy("zcopy_null"));
+
+        // the data of the commands to the null bdev is copied
+        let (before, after) = io_through("zcopy_null").await;
+        assert!(after.io_commands >= before.io_commands + 16);
+        assert!(after.copied_buffers >= before.copied_buffers + 16);
+
+        // while the malloc bdev provides the buffers of its commands
+        let (before, after) = io_through("zcopy_malloc").await;
+        assert!(after.io_commands >= before.io_commands + 16);
+        assert_eq!(after.copied_buffers, before.copied_buffers);
+
+        for name in ["zcopy_malloc", "zcopy_null"] {
+            let mut bdev = UntypedBdev::lookup_by_name(name).unwrap();
+            Pin::new(&mut bdev).unshare().await.unwrap();
+        }
+    })
+    .await;
+}
@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use io_engine::core::op_stats::{self, Operation};
+
+#[tokio::test]
+async fn op_stats_histogram() {
+    op_stats::reset();
+    assert!(op_stats::snapshot().is_empty());
+
+    op_stats::record(Operation::PoolCreate, Duration::from_millis(3), true);
+    op_stats::record(Operation::PoolCreate, Duration::from_millis(40), false);
+    op_stats::record(Operation::PoolCreate, Duration::from_secs(20), true);
+
+    let res = op_stats::measure(Operation::NexusPublish, async {
+        Err::<(), _>("publish failed")
+    })
+    .await;
+    assert!(res.is_err());
+
+    // operations which never ran are not reported
+    let stats = op_stats::snapshot();
+    assert_eq!(stats.len(), 2);
+
+    let pool = &stats[0];
+    assert_eq!(pool.operation, Operation::PoolCreate);
+    assert_eq!(pool.operation.as_ref(), "pool_create");
+    assert_eq!(pool.count, 3);
+    assert_eq!(pool.failures, 1);
+    assert_eq!(pool.max_ms, 20_000.0);
+    assert!((pool.total_ms - 20_043.0).abs() < 0.001);
+
+    // the buckets are cumulative, the last one being unbounded
+    let count = |le_ms: Option<u64>| {
+        pool.buckets
+            .iter()
+            .find(|b| b.le_ms == le_ms)
+            .unwrap()
+            .count
+    };
+    assert_eq!(count(Some(1)), 0);
+    assert_eq!(count(Some(5)), 1);
+    assert_eq!(count(Some(50)), 2);
+    assert_eq!(count(Some(10000)), 2);
+    assert_eq!(count(None), 3);
+
+    let publish = &stats[1];
+    assert_eq!(publish.operation, Operation::NexusPublish);
+    assert_eq!((publish.count, publish.failures), (1, 1));
+
+    op_stats::reset();
+    assert!(op_stats::snapshot().is_empty());
+}
@@ -0,0 +1,73 @@
+use common::MayastorTest;
+use io_engine::{
+    bdev_api::{bdev_create, bdev_destroy},
+    core::{
+        perf::{PerfJob, PerfPattern},
+        MayastorCliArgs,
+    },
+};
+pub mod common;
+
+const MALLOC: &str = "malloc:///perf0?blk_size=512&size_mb=64";
+
+#[tokio::test]
+async fn perf_job() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        bdev_create(MALLOC).await.unwrap();
+    })
+    .await;
+
+    ms.spawn(async {
+        for pattern in [
+            PerfPattern::Write,
+            PerfPattern::RandRead,
+            PerfPattern::RandRw,
+        ] {
+            let stats = PerfJob {
+                bdev: "perf0".to_string(),
+                pattern,
+                queue_depth: 8,
+                runtime_secs: 1,
+                ..Default::default()
+            }
+            .run()
+            .await
+            .unwrap();
+
+            assert_eq!(stats.num_errors, 0);
+            assert!(stats.iops > 0);
+            assert!(stats.lat_min_us <= stats.lat_avg_us);
+            assert!(stats.lat_avg_us <= stats.lat_max_us);
+            assert!(stats.lat_p50_us <= stats.lat_p99_us);
+            match pattern {
+                PerfPattern::Write => assert_eq!(stats.num_read_ops, 0),
+                PerfPattern::RandRead => assert_eq!(stats.num_write_ops, 0),
+                _ => {}
+            }
+        }
+
+        // invalid jobs are rejected before any I/O is issued
+        assert!(PerfJob {
+            bdev: "perf0".to_string(),
+            io_size: 1000,
+            ..Default::default()
+        }
+        .run()
+        .await
+        .is_err());
+        assert!(PerfJob {
+            bdev: "missing".to_string(),
+            ..Default::default()
+        }
+        .run()
+        .await
+        .is_err());
+    })
+    .await;
+
+    ms.spawn(async {
+        bdev_destroy(MALLOC).await.unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,130 @@
+use futures::channel::oneshot;
+use io_engine::{
+    core::{
+        perf::{PerfJob, PerfPattern},
+        MayastorCliArgs,
+        UntypedBdev,
+    },
+    lvs::{Lvs, PoolBackpressure},
+    pool_backend::{PoolArgs, PoolBackend},
+};
+use spdk_rs::{
+    ffihelper::{cb_arg, done_errno_cb, ErrnoResult},
+    libspdk::{
+        spdk_bdev_get_qos_rate_limits,
+        spdk_bdev_set_qos_rate_limits,
+        SPDK_BDEV_QOS_NUM_RATE_LIMIT_TYPES,
+        SPDK_BDEV_QOS_RW_IOPS_RATE_LIMIT,
+    },
+};
+use std::time::Duration;
+
+pub mod common;
+use common::MayastorTest;
+
+const QOS_NUM_LIMITS: usize = SPDK_BDEV_QOS_NUM_RATE_LIMIT_TYPES as usize;
+const QOS_IOPS: usize = SPDK_BDEV_QOS_RW_IOPS_RATE_LIMIT as usize;
+
+/// Sets the IOPS limit of a bdev, as a user would through the bdev QoS.
+async fn set_user_iops_limit(bdev: &mut UntypedBdev, iops: u64) {
+    let mut limits = [u64::MAX; QOS_NUM_LIMITS];
+    limits[QOS_IOPS] = iops;
+    let (s, r) = oneshot::channel::<ErrnoResult<()>>();
+    unsafe {
+        spdk_bdev_set_qos_rate_limits(
+            bdev.unsafe_inner_mut_ptr(),
+            limits.as_mut_ptr(),
+            Some(done_errno_cb),
+            cb_arg(s),
+        );
+    }
+    r.await.unwrap().unwrap();
+}
+
+/// Returns the IOPS limit of a bdev, 0 when it is not limited.
+fn iops_limit(bdev: &mut UntypedBdev) -> u64 {
+    let mut limits = [0; QOS_NUM_LIMITS];
+    unsafe {
+        spdk_bdev_get_qos_rate_limits(
+            bdev.unsafe_inner_mut_ptr(),
+            limits.as_mut_ptr(),
+        );
+    }
+    limits[QOS_IOPS]
+}
+
+/// Saturates the lvol with random writes for a second.
+async fn saturate(bdev: &str) {
+    PerfJob {
+        bdev: bdev.to_string(),
+        pattern: PerfPattern::RandWrite,
+        queue_depth: 64,
+        runtime_secs: 1,
+        ..Default::default()
+    }
+    .run()
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn pool_backpressure_throttles_saturated_pool() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        let lvs = Lvs::create_or_import(PoolArgs {
+            name: "bp_pool".to_string(),
+            disks: vec!["malloc:///bp_disk?size_mb=128".to_string()],
+            uuid: None,
+            cluster_size: None,
+            reserved_pct: None,
+            backend: PoolBackend::Lvs,
+        })
+        .await
+        .unwrap();
+        let lvol = lvs
+            .create_lvol("bp_lvol", 64 * 1024 * 1024, None, false, None)
+            .await
+            .unwrap();
+
+        // the first sample only sets the baseline
+        let states = PoolBackpressure::sample(Duration::ZERO).await;
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[0].iops_limit, None);
+
+        saturate(lvol.as_bdev().name()).await;
+
+        // any latency is past a zero threshold
+        let states = PoolBackpressure::sample(Duration::ZERO).await;
+        assert!(states[0].iops > 0);
+        assert!(states[0].latency_us > 0);
+        let limit = states[0].iops_limit.unwrap();
+        assert!(limit >= 1000 && limit % 1000 == 0);
+        assert!(limit <= (states[0].iops * 80 / 100).max(1000) + 1000);
+
+        // an idle pool is no longer constrained by the cap
+        let states = PoolBackpressure::sample(Duration::from_secs(1)).await;
+        assert_eq!(states[0].iops, 0);
+        assert_eq!(states[0].iops_limit, None);
+        assert_eq!(PoolBackpressure::snapshot().len(), 1);
+
+        // the limit set by the user is never lifted by the cap, and is
+        // restored once the cap is removed
+        let mut base = lvs.base_bdev();
+        set_user_iops_limit(&mut base, 5000).await;
+        saturate(lvol.as_bdev().name()).await;
+        let states = PoolBackpressure::sample(Duration::ZERO).await;
+        assert_eq!(states[0].user_iops_limit, Some(5000));
+        let cap = states[0].iops_limit.unwrap();
+        assert_eq!(iops_limit(&mut base), cap.min(5000));
+
+        let states = PoolBackpressure::sample(Duration::from_secs(1)).await;
+        assert_eq!(states[0].iops_limit, None);
+        assert_eq!(states[0].user_iops_limit, Some(5000));
+        assert_eq!(iops_limit(&mut base), 5000);
+
+        lvs.destroy().await.unwrap();
+        PoolBackpressure::sample(Duration::ZERO).await;
+        assert!(PoolBackpressure::snapshot().is_empty());
+    })
+    .await;
+}
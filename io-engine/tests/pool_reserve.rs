@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use io_engine::{
+    core::{LvolSnapshotOps, MayastorCliArgs},
+    lvs::{BsError, Lvs, LvsError},
+    pool_backend::{PoolArgs, PoolBackend},
+};
+
+pub mod common;
+use common::MayastorTest;
+
+const MB: u64 = 1024 * 1024;
+const PTPL_DIR: &str = "/tmp/io-engine-pool-reserve";
+
+fn pool_args(reserved_pct: Option<u8>) -> PoolArgs {
+    PoolArgs {
+        name: "reserve_pool".to_string(),
+        disks: vec!["malloc:///reserve_disk?size_mb=128".to_string()],
+        uuid: None,
+        cluster_size: None,
+        reserved_pct,
+        backend: PoolBackend::Lvs,
+    }
+}
+
+/// Returns the shares persisted in the ptpl directory.
+fn persisted() -> HashMap<String, u8> {
+    let path = format!("{PTPL_DIR}/pool-reserved.json");
+    std::fs::read_to_string(path)
+        .map(|s| serde_json::from_str(&s).unwrap())
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+async fn pool_reserved_capacity() {
+    std::fs::remove_dir_all(PTPL_DIR).ok();
+    std::fs::create_dir_all(PTPL_DIR).unwrap();
+    let ms = MayastorTest::new(MayastorCliArgs {
+        ptpl_dir: Some(PTPL_DIR.to_string()),
+        ..Default::default()
+    });
+    ms.spawn(async {
+        let lvs = Lvs::create_or_import(pool_args(Some(25))).await.unwrap();
+        assert_eq!(persisted().get("reserve_pool"), Some(&25));
+
+        let info = lvs.capacity_info();
+        assert_eq!(info.reserved_pct, 25);
+        assert!(info.reserved_override);
+        assert_eq!(info.reserved, info.capacity * 25 / 100);
+        assert_eq!(info.usable, info.capacity - info.reserved);
+
+        // a thick replica may not reach into the reservation
+        let size = lvs.usable() - lvs.used() + 4 * MB;
+        let result = lvs
+            .create_lvol("reserve_thick", size, None, false, None)
+            .await;
+        assert!(matches!(
+            result,
+            Err(LvsError::RepCreate {
+                source: BsError::NoSpace {},
+                ..
+            })
+        ));
+
+        // while a thin one takes no capacity yet
+        lvs.create_lvol("reserve_thin", size, None, true, None)
+            .await
+            .unwrap();
+
+        // invalid shares are refused
+        assert!(lvs.set_reserved_pct(Some(101)).is_err());
+        assert_eq!(lvs.reserved_pct(), 25);
+
+        // back to the default of the node, which reserves nothing
+        lvs.set_reserved_pct(None).unwrap();
+        assert_eq!(lvs.reserved_pct(), 0);
+        assert!(!lvs.capacity_info().reserved_override);
+        assert!(persisted().is_empty());
+        let lvol = lvs
+            .create_lvol("reserve_thick", size, None, false, None)
+            .await
+            .unwrap();
+
+        // no snapshot is taken on a pool using its reservation
+        lvs.set_reserved_pct(Some(100)).unwrap();
+        let snap_config = lvol
+            .prepare_snap_config(
+                "reserve_snap",
+                "reserve_entity",
+                "reserve_txn",
+                "2b9cd0a4-7c5e-4b7e-9a53-0d7dbc1a4f01",
+            )
+            .unwrap();
+        assert!(matches!(
+            lvol.create_snapshot(snap_config).await,
+            Err(LvsError::SnapshotCreate {
+                source: BsError::NoSpace {},
+                ..
+            })
+        ));
+
+        // the share is kept across an export and an import which does not
+        // carry any, as done through gRPC
+        lvs.export().await.unwrap();
+        assert_eq!(persisted().get("reserve_pool"), Some(&100));
+        let lvs = Lvs::create_or_import(pool_args(None)).await.unwrap();
+        assert_eq!(lvs.reserved_pct(), 100);
+
+        // the share is forgotten along with the pool
+        lvs.destroy().await.unwrap();
+        assert!(persisted().is_empty());
+    })
+    .await;
+}
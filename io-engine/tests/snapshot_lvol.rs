@@ -68,6 +68,7 @@ async fn create_test_pool(
         disks: vec![disk],
         uuid: None,
         cluster_size,
+        reserved_pct: None,
         backend: PoolBackend::Lvs,
     })
     .await
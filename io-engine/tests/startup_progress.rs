@@ -0,0 +1,51 @@
+use io_engine::{
+    core::MayastorCliArgs,
+    lvs::Lvs,
+    subsys::{StartupPhase, StartupProgress},
+};
+
+pub mod common;
+use common::MayastorTest;
+
+static POOL_CONFIG: &str = "/tmp/startup_progress_pools.yaml";
+
+#[tokio::test]
+async fn startup_progress_pools() {
+    std::fs::write(
+        POOL_CONFIG,
+        "pools:
+  - name: startup_pool0
+    disks: [\"malloc:///startup_disk0?size_mb=64\"]
+    backend: Lvs
+  - name: startup_pool1
+    disks: [\"malloc:///startup_disk1?size_mb=64\"]
+    backend: Lvs
+  - name: startup_pool2
+    disks: []
+    backend: Lvs
+",
+    )
+    .unwrap();
+
+    let ms = MayastorTest::new(MayastorCliArgs {
+        pool_config: Some(POOL_CONFIG.to_string()),
+        startup_concurrency: 2,
+        ..Default::default()
+    });
+
+    ms.spawn(async {
+        let progress = StartupProgress::get();
+        assert_eq!(progress.phase, StartupPhase::Ready);
+        assert_eq!(progress.pools_total, 3);
+        assert_eq!(progress.pools_imported, 2);
+        assert_eq!(progress.pools_failed, 1);
+        assert_eq!(progress.replicas_shared, 0);
+
+        for name in ["startup_pool0", "startup_pool1"] {
+            Lvs::lookup(name).unwrap().destroy().await.unwrap();
+        }
+    })
+    .await;
+
+    std::fs::remove_file(POOL_CONFIG).unwrap();
+}
@@ -0,0 +1,125 @@
+use futures::future::{join, poll_fn};
+use io_engine::{
+    core::{MayastorCliArgs, Share},
+    lvs::{Lvs, LvsLvol, PropValue},
+    pool_backend::{PoolArgs, PoolBackend},
+    subsys::{
+        NvmfSubsystem,
+        PoolConfig,
+        StartupPhase,
+        StartupProgress,
+        SubsystemHandle,
+    },
+};
+use std::{pin::Pin, task::Poll};
+
+pub mod common;
+use common::MayastorTest;
+
+static DISKNAME: &str = "/tmp/startup-priority-disk.img";
+static POOL_CONFIG: &str = "/tmp/startup_priority_pools.yaml";
+const POOL_NAME: &str = "startup_prio_pool";
+const HIGH_UUID: &str = "5c1e8a0e-0d7c-4d36-a1a4-0e54b5e2f3b1";
+const LOW_UUID: &str = "5c1e8a0e-0d7c-4d36-a1a4-0e54b5e2f3b2";
+
+/// Whether the replica of the given UUID is shared, its subsystem started.
+fn started(uuid: &str) -> bool {
+    matches!(
+        NvmfSubsystem::nqn_lookup(uuid).map(|s| s.into_handle()),
+        Some(Ok(SubsystemHandle::Active(_)))
+    )
+}
+
+/// Lets the reactor poll once before returning.
+async fn yield_now() {
+    let mut yielded = false;
+    poll_fn(|cx| {
+        if yielded {
+            return Poll::Ready(());
+        }
+        yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    })
+    .await
+}
+
+#[tokio::test]
+async fn startup_restore_priority() {
+    common::delete_file(&[DISKNAME.into()]);
+    common::truncate_file(DISKNAME, 64 * 1024);
+    let disk = format!("aio://{DISKNAME}");
+    std::fs::write(
+        POOL_CONFIG,
+        format!(
+            "pools:
+  - name: {POOL_NAME}
+    disks: [\"{disk}\"]
+    backend: Lvs
+"
+        ),
+    )
+    .unwrap();
+
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async move {
+        let pool = Lvs::create_or_import(PoolArgs {
+            name: POOL_NAME.into(),
+            disks: vec![disk.clone()],
+            uuid: None,
+            cluster_size: None,
+            reserved_pct: None,
+            backend: PoolBackend::Lvs,
+        })
+        .await
+        .unwrap();
+
+        // the replica of the lower priority is created first
+        for (name, uuid, priority) in
+            [("low", LOW_UUID, 0), ("high", HIGH_UUID, 9)]
+        {
+            let mut lvol = pool
+                .create_lvol(name, 4 * 1024 * 1024, Some(uuid), true, None)
+                .await
+                .unwrap();
+            Pin::new(&mut lvol)
+                .set(PropValue::RestorePriority(priority))
+                .await
+                .unwrap();
+            Pin::new(&mut lvol).share_nvmf(None).await.unwrap();
+        }
+        pool.export().await.unwrap();
+
+        // the pool is imported as at startup, one operation at a time: its
+        // replicas are only shared once it is imported, by decreasing
+        // priority
+        let config = PoolConfig::load(POOL_CONFIG).unwrap();
+        let probe = async {
+            loop {
+                let progress = StartupProgress::get();
+                if progress.phase == StartupPhase::ImportingPools {
+                    assert!(!started(HIGH_UUID) && !started(LOW_UUID));
+                }
+                if progress.replicas_shared > 0 {
+                    return (started(HIGH_UUID), started(LOW_UUID));
+                }
+                yield_now().await;
+            }
+        };
+        let (errors, first) = join(config.restore(1), probe).await;
+        assert_eq!(errors, 0);
+        assert_eq!(first, (true, false));
+
+        let progress = StartupProgress::get();
+        assert_eq!(progress.phase, StartupPhase::SharingReplicas);
+        assert_eq!(progress.pools_imported, 1);
+        assert_eq!(progress.replicas_shared, 2);
+        assert!(started(HIGH_UUID) && started(LOW_UUID));
+
+        Lvs::lookup(POOL_NAME).unwrap().destroy().await.unwrap();
+    })
+    .await;
+
+    std::fs::remove_file(POOL_CONFIG).unwrap();
+    common::delete_file(&[DISKNAME.into()]);
+}
@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use io_engine::{
+    bdev::nexus::{nexus_create, nexus_lookup_mut},
+    core::{
+        telemetry::{
+            sign,
+            telemetry_preview,
+            Error,
+            TelemetryConfig,
+            TelemetryReport,
+        },
+        MayastorCliArgs,
+    },
+};
+
+pub mod common;
+use common::MayastorTest;
+
+const NEXUS_NAME: &str = "telemetry_nexus";
+const KEY_FILE: &str = "/tmp/telemetry.key";
+
+#[test]
+fn telemetry_signature() {
+    // HMAC-SHA256 test case 2 of RFC 4231
+    assert_eq!(
+        sign(b"Jefe", b"what do ya want for nothing?"),
+        "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+}
+
+#[test]
+fn telemetry_config() {
+    let interval = Duration::from_secs(60);
+    std::fs::write(KEY_FILE, b"secret\n").unwrap();
+    TelemetryConfig::new(
+        "https://127.0.0.1:8443/report",
+        KEY_FILE.as_ref(),
+        interval,
+    )
+    .unwrap();
+    TelemetryConfig::new(
+        "https://[::1]:8443/report",
+        KEY_FILE.as_ref(),
+        interval,
+    )
+    .unwrap();
+    // the reports are not sent in plaintext
+    assert!(matches!(
+        TelemetryConfig::new(
+            "http://collector/report",
+            KEY_FILE.as_ref(),
+            interval
+        ),
+        Err(Error::InvalidEndpoint { .. })
+    ));
+
+    std::fs::write(KEY_FILE, b"\n").unwrap();
+    assert!(matches!(
+        TelemetryConfig::new(
+            "https://collector/report",
+            KEY_FILE.as_ref(),
+            interval
+        ),
+        Err(Error::EmptyKey { .. })
+    ));
+    std::fs::remove_file(KEY_FILE).unwrap();
+}
+
+#[tokio::test]
+async fn telemetry_report() {
+    let ms = MayastorTest::new(MayastorCliArgs::default());
+    ms.spawn(async {
+        let before = TelemetryReport::collect();
+        nexus_create(
+            NEXUS_NAME,
+            8 * 1024 * 1024,
+            None,
+            &["malloc:///telemetry0?size_mb=16".to_string()],
+        )
+        .await
+        .unwrap();
+
+        let report = TelemetryReport::collect();
+        assert_eq!(report.volumes, before.volumes + 1);
+        assert_eq!(report.volume_bytes, before.volume_bytes + 8 * 1024 * 1024);
+        assert_eq!(report.instance_id, before.instance_id);
+
+        // nothing identifying the nexus is reported
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(!json.contains(NEXUS_NAME));
+        assert!(!json.contains("telemetry0"));
+
+        // the telemetry is not enabled, so the report is not signed
+        let preview = telemetry_preview();
+        assert!(!preview.enabled);
+        assert!(preview.signature.is_none());
+        assert_eq!(preview.report.volumes, report.volumes);
+
+        nexus_lookup_mut(NEXUS_NAME)
+            .unwrap()
+            .destroy()
+            .await
+            .unwrap();
+    })
+    .await;
+}
@@ -0,0 +1,116 @@
+use io_engine::{
+    core::{
+        volume_stats::{volume_io_stats, Error, VolumeLabels, VolumeObject},
+        MayastorCliArgs,
+    },
+    lvs::Lvs,
+    pool_backend::{PoolArgs, PoolBackend},
+};
+use std::collections::{BTreeMap, HashMap};
+
+pub mod common;
+use common::MayastorTest;
+
+const VOLUME: &str = "ec4e66fd-3b33-4439-b504-d49aba53da26";
+const PERSISTED: &str = "5f0a4c3e-7f7b-4e55-9d0e-2a6c1f3b9e41";
+const PTPL_DIR: &str = "/tmp/io-engine-volume-stats";
+
+type Labels = HashMap<String, BTreeMap<String, String>>;
+
+fn labels_file() -> String {
+    format!("{PTPL_DIR}/volume-labels.json")
+}
+
+fn persisted_labels() -> Labels {
+    serde_json::from_slice(&std::fs::read(labels_file()).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn volume_stats_with_labels() {
+    // the labels left behind by a previous run are loaded
+    std::fs::remove_dir_all(PTPL_DIR).ok();
+    std::fs::create_dir_all(PTPL_DIR).unwrap();
+    let persisted = BTreeMap::from([("app".to_string(), "web".to_string())]);
+    let labels = Labels::from([(PERSISTED.to_string(), persisted.clone())]);
+    std::fs::write(labels_file(), serde_json::to_vec(&labels).unwrap())
+        .unwrap();
+
+    let ms = MayastorTest::new(MayastorCliArgs {
+        ptpl_dir: Some(PTPL_DIR.to_string()),
+        ..Default::default()
+    });
+    ms.spawn(async move {
+        assert_eq!(VolumeLabels::lookup(PERSISTED).unwrap().labels, persisted);
+
+        let lvs = Lvs::create_or_import(PoolArgs {
+            name: "vs_pool".to_string(),
+            disks: vec!["malloc:///vs_disk?size_mb=128".to_string()],
+            uuid: None,
+            cluster_size: None,
+            reserved_pct: None,
+            backend: PoolBackend::Lvs,
+        })
+        .await
+        .unwrap();
+        lvs.create_lvol(
+            "vs_replica",
+            8 * 1024 * 1024,
+            None,
+            false,
+            Some(VOLUME.to_string()),
+        )
+        .await
+        .unwrap();
+        lvs.create_lvol("vs_other", 8 * 1024 * 1024, None, false, None)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            VolumeLabels {
+                volume_uuid: "not-a-uuid".to_string(),
+                labels: BTreeMap::new(),
+            }
+            .set(),
+            Err(Error::InvalidVolumeUuid { .. })
+        ));
+
+        let labels = BTreeMap::from([
+            ("app".to_string(), "db".to_string()),
+            ("tier".to_string(), "gold".to_string()),
+        ]);
+        VolumeLabels {
+            volume_uuid: VOLUME.to_uppercase(),
+            labels: labels.clone(),
+        }
+        .set()
+        .unwrap();
+        assert_eq!(VolumeLabels::lookup(VOLUME).unwrap().labels, labels);
+        assert_eq!(persisted_labels()[VOLUME], labels);
+
+        let stats = volume_io_stats(None).await;
+        assert_eq!(stats.len(), 2);
+        let other = stats.iter().find(|s| s.name == "vs_other").unwrap();
+        assert_eq!(other.volume_uuid, None);
+        assert!(other.labels.is_empty());
+
+        let stats = volume_io_stats(Some(VOLUME)).await;
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].kind, VolumeObject::Replica);
+        assert_eq!(stats[0].name, "vs_replica");
+        assert_eq!(stats[0].volume_uuid.as_deref(), Some(VOLUME));
+        assert_eq!(stats[0].labels, labels);
+
+        VolumeLabels::remove(VOLUME).unwrap();
+        assert!(!persisted_labels().contains_key(VOLUME));
+        assert!(matches!(
+            VolumeLabels::remove(VOLUME),
+            Err(Error::NotFound { .. })
+        ));
+        let stats = volume_io_stats(Some(VOLUME)).await;
+        assert!(stats[0].labels.is_empty());
+
+        lvs.destroy().await.unwrap();
+    })
+    .await;
+    std::fs::remove_dir_all(PTPL_DIR).ok();
+}